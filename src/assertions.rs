@@ -0,0 +1,272 @@
+//! Response assertions declared in collection `expect:` blocks
+//!
+//! A request in a collection can describe the response it expects:
+//!
+//! ```yaml
+//! expect:
+//!   status: 200
+//!   headers:
+//!     content-type: application/json
+//!   json:
+//!     user.name: Alice
+//!     items.0.id: 1
+//! ```
+//!
+//! Header expectations pass when the response header contains the expected
+//! text. JSON expectations look up a dotted path or JSONPath query (see
+//! [`crate::query`]) in the response body and compare it with the expected
+//! value; queries with wildcards are compared as an array of their matches.
+
+use crate::collection::{resolve_vars, resolve_yaml_vars, yaml_to_json};
+use crate::http::HttpResponse;
+use crate::query;
+use anstyle::{AnsiColor, Style};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Expected response for a collection request
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+    /// Exact status code
+    pub status: Option<u16>,
+    /// Header name to text the header value must contain
+    pub headers: Option<HashMap<String, String>>,
+    /// Dotted path or JSONPath query to the value found there
+    pub json: Option<HashMap<String, serde_yaml::Value>>,
+}
+
+/// Outcome of a single assertion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    /// What was asserted, e.g. `status is 200`
+    pub description: String,
+    /// Why the assertion failed, or `None` if it passed
+    pub failure: Option<String>,
+}
+
+impl Check {
+    fn new(description: String, failure: Option<String>) -> Self {
+        Self {
+            description,
+            failure,
+        }
+    }
+
+    /// Returns true if the assertion held
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl Expect {
+    /// Resolves `${var}` references in expected header values and JSON strings
+    pub fn resolve_vars(&self, vars: &HashMap<String, String>) -> Result<Expect, String> {
+        let headers = match &self.headers {
+            Some(hs) => {
+                let mut resolved = HashMap::new();
+                for (k, v) in hs {
+                    resolved.insert(k.clone(), resolve_vars(v, vars)?);
+                }
+                Some(resolved)
+            }
+            None => None,
+        };
+        let json = match &self.json {
+            Some(paths) => {
+                let mut resolved = HashMap::new();
+                for (path, expected) in paths {
+                    resolved.insert(path.clone(), resolve_yaml_vars(expected, vars)?);
+                }
+                Some(resolved)
+            }
+            None => None,
+        };
+        Ok(Expect {
+            status: self.status,
+            headers,
+            json,
+        })
+    }
+
+    /// Evaluates every expectation against a response
+    ///
+    /// Checks are returned in a stable order: status, headers, then JSON
+    /// paths, each sorted by name.
+    pub fn evaluate(&self, resp: &HttpResponse) -> Vec<Check> {
+        let mut checks = Vec::new();
+        if let Some(status) = self.status {
+            checks.push(Check::new(
+                format!("status is {status}"),
+                (resp.status != status).then(|| format!("got {}", resp.status)),
+            ));
+        }
+        if let Some(headers) = &self.headers {
+            let mut names: Vec<_> = headers.keys().collect();
+            names.sort();
+            for name in names {
+                let expected = &headers[name];
+                let failure = match resp.headers.get(name.as_str()) {
+                    None => Some("header is missing".to_string()),
+                    Some(value) => {
+                        let actual = value.to_str().unwrap_or("");
+                        (!actual.contains(expected.as_str())).then(|| format!("got '{actual}'"))
+                    }
+                };
+                checks.push(Check::new(
+                    format!("header {name} contains '{expected}'"),
+                    failure,
+                ));
+            }
+        }
+        if let Some(paths) = &self.json {
+            let body = serde_json::from_str::<Value>(&resp.body).ok();
+            let mut sorted: Vec<_> = paths.keys().collect();
+            sorted.sort();
+            for path in sorted {
+                let expected = yaml_to_json(&paths[path]);
+                let failure = match body.as_ref().map(|b| query::lookup(b, path)) {
+                    None => Some("body is not JSON".to_string()),
+                    Some(Err(e)) => Some(e),
+                    Some(Ok(None)) => Some("path not found".to_string()),
+                    Some(Ok(Some(actual))) => (actual != expected).then(|| format!("got {actual}")),
+                };
+                checks.push(Check::new(format!("json {path} is {expected}"), failure));
+            }
+        }
+        checks
+    }
+}
+
+/// Formats assertion results as one line per check
+pub fn format_checks(checks: &[Check]) -> String {
+    let mut output = String::new();
+    for check in checks {
+        let (mark, color) = if check.passed() {
+            ("✓", AnsiColor::Green)
+        } else {
+            ("✗", AnsiColor::Red)
+        };
+        let style = Style::new().fg_color(Some(anstyle::Color::Ansi(color)));
+        output.push_str(&format!(
+            "{}{mark}{} {}",
+            style.render(),
+            anstyle::Reset.render(),
+            check.description
+        ));
+        if let Some(failure) = &check.failure {
+            output.push_str(&format!(": {failure}"));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::http::HeaderMap;
+
+    fn response() -> HttpResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "content-type",
+            "application/json; charset=utf-8".parse().unwrap(),
+        );
+        HttpResponse::new(
+            201,
+            headers,
+            r#"{"user":{"name":"Alice","id":42},"items":[{"id":1}]}"#,
+        )
+    }
+
+    fn expect(yaml: &str) -> Expect {
+        serde_yaml::from_str(yaml).expect("Test: parse expect")
+    }
+
+    #[test]
+    fn test_evaluate_all_passing() {
+        let checks = expect(
+            r#"
+status: 201
+headers:
+  Content-Type: application/json
+json:
+  user.name: Alice
+  user.id: 42
+  items.0.id: 1
+"#,
+        )
+        .evaluate(&response());
+        assert_eq!(checks.len(), 5);
+        assert!(checks.iter().all(Check::passed), "{checks:?}");
+    }
+
+    #[test]
+    fn test_evaluate_reports_failures() {
+        let checks = expect(
+            r#"
+status: 200
+headers:
+  x-missing: yes
+json:
+  user.name: Bob
+  user.email: a@b.c
+"#,
+        )
+        .evaluate(&response());
+        let failures: Vec<_> = checks.iter().filter_map(|c| c.failure.clone()).collect();
+        assert_eq!(
+            failures,
+            vec![
+                "got 201".to_string(),
+                "header is missing".to_string(),
+                "path not found".to_string(),
+                r#"got "Alice""#.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_non_json_body() {
+        let resp = HttpResponse::new(200, HeaderMap::new(), "plain text");
+        let checks = expect("json:\n  id: 1\n").evaluate(&resp);
+        assert_eq!(checks[0].failure.as_deref(), Some("body is not JSON"));
+    }
+
+    #[test]
+    fn test_unknown_expect_key_rejected() {
+        assert!(serde_yaml::from_str::<Expect>("statuss: 200\n").is_err());
+    }
+
+    #[test]
+    fn test_resolve_vars_in_expectations() {
+        let mut vars = HashMap::new();
+        vars.insert("name".to_string(), "Alice".to_string());
+        let resolved = expect("json:\n  user.name: ${name}\n")
+            .resolve_vars(&vars)
+            .unwrap();
+        assert!(resolved.evaluate(&response())[0].passed());
+    }
+
+    #[test]
+    fn test_resolve_vars_in_nested_expectations() {
+        let mut vars = HashMap::new();
+        vars.insert("id".to_string(), "1".to_string());
+        let resolved = expect("json:\n  $.items[*].id: [\"${id}\"]\n")
+            .resolve_vars(&vars)
+            .unwrap();
+        let expected = resolved.json.as_ref().unwrap()["$.items[*].id"].clone();
+        assert_eq!(
+            expected,
+            serde_yaml::from_str::<serde_yaml::Value>("['1']").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_evaluate_wildcard_query() {
+        let checks = expect("json:\n  $.items[*].id: [1]\n").evaluate(&response());
+        assert!(checks[0].passed(), "{checks:?}");
+    }
+}
@@ -0,0 +1,275 @@
+//! Load testing with `wave bench`
+//!
+//! A benchmark sends the same request a fixed number of times, keeping up to
+//! `--concurrency` requests in flight, and summarizes how it went: latency
+//! percentiles, throughput and the share of failed requests. A request fails
+//! when no response arrives or the status is 4xx or 5xx.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Outcome of one request in a benchmark
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Time from sending the request to receiving the whole response
+    pub elapsed: Duration,
+    /// Response status, or why no response arrived
+    pub outcome: Result<u16, String>,
+}
+
+impl Sample {
+    /// Returns true if a response arrived with a status below 400
+    pub fn succeeded(&self) -> bool {
+        matches!(self.outcome, Ok(status) if status < 400)
+    }
+}
+
+/// Latency distribution in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Latency {
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// Summary of a benchmark, printed as text or with `--format json`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchReport {
+    /// Method and URL of the request
+    pub request: String,
+    /// Requests sent
+    pub requests: usize,
+    /// Most requests in flight at once
+    pub concurrency: usize,
+    /// Requests with a status below 400
+    pub succeeded: usize,
+    /// Requests without a response or with a 4xx/5xx status
+    pub failed: usize,
+    /// Share of failed requests, from 0 to 1
+    pub error_rate: f64,
+    /// Wall-clock time of the whole benchmark
+    pub duration_ms: f64,
+    /// Completed requests per second
+    pub requests_per_sec: f64,
+    /// Latency of every request, failed ones included
+    pub latency_ms: Latency,
+    /// How many responses had each status code
+    pub status_codes: BTreeMap<u16, usize>,
+    /// How many requests failed with each transport error
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl BenchReport {
+    /// Summarizes the samples of a benchmark that took `duration` overall
+    pub fn new(request: &str, concurrency: usize, samples: &[Sample], duration: Duration) -> Self {
+        let mut latencies: Vec<f64> = samples.iter().map(|s| millis(s.elapsed)).collect();
+        latencies.sort_by(f64::total_cmp);
+        let mut status_codes = BTreeMap::new();
+        let mut errors = BTreeMap::new();
+        for sample in samples {
+            match &sample.outcome {
+                Ok(status) => *status_codes.entry(*status).or_insert(0) += 1,
+                Err(e) => *errors.entry(e.clone()).or_insert(0) += 1,
+            }
+        }
+        let succeeded = samples.iter().filter(|s| s.succeeded()).count();
+        let failed = samples.len() - succeeded;
+        let secs = duration.as_secs_f64();
+        BenchReport {
+            request: request.to_string(),
+            requests: samples.len(),
+            concurrency,
+            succeeded,
+            failed,
+            error_rate: ratio(failed, samples.len()),
+            duration_ms: millis(duration),
+            requests_per_sec: if secs > 0.0 {
+                samples.len() as f64 / secs
+            } else {
+                0.0
+            },
+            latency_ms: Latency {
+                min: latencies.first().copied().unwrap_or(0.0),
+                mean: if latencies.is_empty() {
+                    0.0
+                } else {
+                    latencies.iter().sum::<f64>() / latencies.len() as f64
+                },
+                p50: percentile(&latencies, 50.0),
+                p90: percentile(&latencies, 90.0),
+                p95: percentile(&latencies, 95.0),
+                p99: percentile(&latencies, 99.0),
+                max: latencies.last().copied().unwrap_or(0.0),
+            },
+            status_codes,
+            errors,
+        }
+    }
+
+    /// Serializes the report for `--format json`
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Returns the nearest-rank percentile of sorted values
+///
+/// # Examples
+/// ```
+/// use wave::bench::percentile;
+///
+/// let values: Vec<f64> = (1..=100).map(f64::from).collect();
+/// assert_eq!(percentile(&values, 50.0), 50.0);
+/// assert_eq!(percentile(&values, 99.0), 99.0);
+/// assert_eq!(percentile(&[], 50.0), 0.0);
+/// ```
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+fn ratio(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// Formats milliseconds with a unit that keeps them readable
+fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2} s", ms / 1000.0)
+    } else {
+        format!("{ms:.1} ms")
+    }
+}
+
+/// Formats a report for the terminal
+pub fn format_report(report: &BenchReport) -> String {
+    let latency = &report.latency_ms;
+    let mut output = format!(
+        "{}\n\n  Requests:     {} ({} at a time)\n  Duration:     {}\n  Throughput:   {:.1} req/s\n  Succeeded:    {} ({:.1}%)\n  Failed:       {} ({:.1}%)\n",
+        report.request,
+        report.requests,
+        report.concurrency,
+        format_ms(report.duration_ms),
+        report.requests_per_sec,
+        report.succeeded,
+        ratio(report.succeeded, report.requests) * 100.0,
+        report.failed,
+        report.error_rate * 100.0,
+    );
+    output.push_str(&format!(
+        "\n  Latency:      min {}, mean {}, max {}\n                p50 {}, p90 {}, p95 {}, p99 {}\n",
+        format_ms(latency.min),
+        format_ms(latency.mean),
+        format_ms(latency.max),
+        format_ms(latency.p50),
+        format_ms(latency.p90),
+        format_ms(latency.p95),
+        format_ms(latency.p99),
+    ));
+    if !report.status_codes.is_empty() {
+        let codes: Vec<String> = report
+            .status_codes
+            .iter()
+            .map(|(status, count)| format!("{status} x{count}"))
+            .collect();
+        output.push_str(&format!("\n  Status codes: {}\n", codes.join(", ")));
+    }
+    for (error, count) in &report.errors {
+        output.push_str(&format!("  Error x{count}: {error}\n"));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(ms: u64, outcome: Result<u16, &str>) -> Sample {
+        Sample {
+            elapsed: Duration::from_millis(ms),
+            outcome: outcome.map_err(str::to_string),
+        }
+    }
+
+    fn report() -> BenchReport {
+        let samples = vec![
+            sample(40, Ok(200)),
+            sample(10, Ok(200)),
+            sample(20, Ok(201)),
+            sample(30, Ok(503)),
+            sample(100, Err("timed out")),
+        ];
+        BenchReport::new("GET http://x.io", 2, &samples, Duration::from_millis(500))
+    }
+
+    #[test]
+    fn test_report_counts_and_rates() {
+        let report = report();
+        assert_eq!(report.requests, 5);
+        assert_eq!(report.succeeded, 3);
+        assert_eq!(report.failed, 2);
+        assert_eq!(report.error_rate, 0.4);
+        assert_eq!(report.requests_per_sec, 10.0);
+        assert_eq!(
+            report.status_codes,
+            BTreeMap::from([(200, 2), (201, 1), (503, 1)])
+        );
+        assert_eq!(report.errors["timed out"], 1);
+    }
+
+    #[test]
+    fn test_report_latency() {
+        let latency = report().latency_ms;
+        assert_eq!((latency.min, latency.max), (10.0, 100.0));
+        assert_eq!(latency.mean, 40.0);
+        assert_eq!(
+            (latency.p50, latency.p90, latency.p99),
+            (30.0, 100.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn test_empty_report() {
+        let report = BenchReport::new("GET http://x.io", 1, &[], Duration::ZERO);
+        assert_eq!(report.error_rate, 0.0);
+        assert_eq!(report.requests_per_sec, 0.0);
+        assert_eq!(report.latency_ms, Latency::default());
+    }
+
+    #[test]
+    fn test_format_report() {
+        let text = format_report(&report());
+        assert!(text.starts_with("GET http://x.io\n"));
+        assert!(text.contains("  Requests:     5 (2 at a time)\n"));
+        assert!(text.contains("  Failed:       2 (40.0%)\n"));
+        assert!(text.contains("p50 30.0 ms, p90 100.0 ms"));
+        assert!(text.contains("  Status codes: 200 x2, 201 x1, 503 x1\n"));
+        assert!(text.contains("  Error x1: timed out\n"));
+        assert_eq!(format_ms(1234.0), "1.23 s");
+    }
+
+    #[test]
+    fn test_report_json() {
+        let json: serde_json::Value = serde_json::from_str(&report().to_json()).unwrap();
+        assert_eq!(json["failed"], 2);
+        assert_eq!(json["status_codes"]["503"], 1);
+        assert_eq!(json["latency_ms"]["p50"], 30.0);
+    }
+}
@@ -6,7 +6,7 @@
 //!
 //! ## Features
 //!
-//! - Support for all major HTTP methods (GET, POST, PUT, DELETE, PATCH)
+//! - Support for all major HTTP methods (GET, POST, PUT, DELETE, PATCH, HEAD, OPTIONS)
 //! - Interactive request/response display with colored output
 //! - Save/load collections of requests in YAML format
 //! - Header and body parameter support via CLI arguments
@@ -20,6 +20,39 @@
 //! wave post example.com name=john age=30
 //! wave put example.com Authorization:Bearer123 status=active
 //!
+//! # Any other method, such as WebDAV's PROPFIND or a cache PURGE
+//! wave request -X PURGE example.com/assets/app.js
+//!
+//! # Call a gRPC method, or list a server's methods through reflection
+//! wave grpc --proto helloworld.proto localhost:50051 helloworld.Greeter/SayHello name=alice
+//! wave grpc localhost:50051
+//!
+//! # Create a starter collection in .wave/api.yaml
+//! wave init
+//!
+//! # Preview a request as a curl command without sending it
+//! wave post example.com name=john --dry-run --as-curl
+//! wave export curl myCollection myRequest
+//!
+//! # Run every request in a collection and report pass/fail
+//! wave run myCollection
+//!
+//! # Send 500 requests, 20 at a time, and report latency percentiles
+//! wave bench -n 500 --concurrency 20 https://example.com/health
+//!
+//! # Save a response snapshot, then check later responses against it
+//! wave snapshot myCollection myRequest
+//! wave snapshot myCollection myRequest --check --ignore updated_at
+//!
+//! # Serve the mock: responses of a collection on http://127.0.0.1:8080
+//! wave mock myCollection
+//!
+//! # Check configuration, proxies and the connection to a host for a bug report
+//! wave doctor api.example.com
+//!
+//! # Tab-complete commands, flags, collections and requests in bash
+//! source <(wave completions bash)
+//!
 //! # Using saved collections
 //! wave myCollection myRequest
 //! wave myCollection myRequest Authorization:Bearer456  # Override collection headers
@@ -27,8 +60,11 @@
 
 use clap::Parser;
 use wave::{
-    error::WaveError, handle_collection, handle_delete, handle_get, handle_patch, handle_post,
-    handle_put, Cli,
+    config::Config, error::WaveError, handle_bench, handle_collection, handle_collection_edit,
+    handle_completions, handle_delete, handle_describe, handle_doctor, handle_edit,
+    handle_export_curl, handle_get, handle_graphql, handle_grpc, handle_head, handle_init,
+    handle_list, handle_mock, handle_names, handle_options, handle_patch, handle_post, handle_put,
+    handle_request, handle_run, handle_snapshot, handle_validate, handle_vars, Cli,
 };
 
 /// Creates a spinner message for HTTP requests
@@ -69,59 +105,295 @@ fn spinner_msg(method: &str, url: &str, params: &[String]) -> String {
 /// - Missing collection files or requests
 async fn run() -> Result<(), WaveError> {
     let cli = Cli::parse();
-    use wave::Command;
+    wave::logging::init(cli.debug, cli.log_file.as_deref())?;
+    // Reports configuration errors instead of failing on them
+    if let wave::Command::Doctor { target } = &cli.command {
+        return handle_doctor(target.as_deref()).await;
+    }
+    let config = Config::load()?;
+    let collection_dir = config.collection_dir();
+    use wave::{Command, ExportFormat};
     match cli.command {
         Command::Get {
             url,
             params,
+            body,
             verbose,
+            options,
         } => {
             let msg = spinner_msg("GET", &url, &params);
-            handle_get(&url, &params, verbose, &msg).await?;
+            let options = options.with_config(&config);
+            handle_get(&url, &params, &body, verbose, &options, &msg).await?;
         }
         Command::Post {
             url,
             params,
-            form,
+            body,
             verbose,
+            options,
         } => {
             let msg = spinner_msg("POST", &url, &params);
-            handle_post(&url, &params, form, verbose, &msg).await?;
+            handle_post(
+                &url,
+                &params,
+                &body,
+                verbose,
+                &options.with_config(&config),
+                &msg,
+            )
+            .await?;
         }
         Command::Put {
             url,
             params,
-            form,
+            body,
             verbose,
+            options,
         } => {
             let msg = spinner_msg("PUT", &url, &params);
-            handle_put(&url, &params, form, verbose, &msg).await?;
+            handle_put(
+                &url,
+                &params,
+                &body,
+                verbose,
+                &options.with_config(&config),
+                &msg,
+            )
+            .await?;
         }
         Command::Patch {
             url,
             params,
-            form,
+            body,
             verbose,
+            options,
         } => {
             let msg = spinner_msg("PATCH", &url, &params);
-            handle_patch(&url, &params, form, verbose, &msg).await?;
+            handle_patch(
+                &url,
+                &params,
+                &body,
+                verbose,
+                &options.with_config(&config),
+                &msg,
+            )
+            .await?;
         }
         Command::Delete {
             url,
             params,
+            body,
             verbose,
+            options,
         } => {
             let msg = spinner_msg("DELETE", &url, &params);
-            handle_delete(&url, &params, verbose, &msg).await?;
+            let options = options.with_config(&config);
+            handle_delete(&url, &params, &body, verbose, &options, &msg).await?;
+        }
+        Command::Head {
+            url,
+            params,
+            verbose,
+            options,
+        } => {
+            let msg = spinner_msg("HEAD", &url, &params);
+            handle_head(&url, &params, verbose, &options.with_config(&config), &msg).await?;
+        }
+        Command::Options {
+            url,
+            params,
+            verbose,
+            options,
+        } => {
+            let msg = spinner_msg("OPTIONS", &url, &params);
+            handle_options(&url, &params, verbose, &options.with_config(&config), &msg).await?;
+        }
+        Command::Request {
+            method,
+            url,
+            params,
+            body,
+            verbose,
+            options,
+        } => {
+            let msg = spinner_msg(&method, &url, &params);
+            handle_request(
+                &method,
+                &url,
+                &params,
+                &body,
+                verbose,
+                &options.with_config(&config),
+                &msg,
+            )
+            .await?;
+        }
+        Command::Graphql {
+            url,
+            query,
+            params,
+            graphql,
+            verbose,
+            options,
+        } => {
+            let msg = spinner_msg("POST", &url, &params);
+            handle_graphql(
+                &url,
+                &query,
+                &params,
+                &graphql,
+                verbose,
+                &options.with_config(&config),
+                &msg,
+            )
+            .await?;
+        }
+        Command::Grpc {
+            address,
+            method,
+            params,
+            grpc,
+            verbose,
+            options,
+        } => {
+            handle_grpc(
+                &address,
+                method.as_deref(),
+                &params,
+                &grpc,
+                verbose,
+                &options.with_config(&config),
+            )
+            .await?;
         }
         Command::Collection {
+            edit: Some(edit), ..
+        } => handle_collection_edit(&collection_dir, &edit)?,
+        Command::Collection {
+            collection: Some(collection),
+            request: Some(request),
+            verbose,
+            options,
+            vars,
+            params,
+            edit: None,
+        } => {
+            handle_collection(
+                &collection_dir,
+                &collection,
+                &request,
+                verbose,
+                &options.with_config(&config),
+                &vars,
+                &params,
+            )
+            .await?;
+        }
+        Command::Collection { .. } => unreachable!("clap requires a collection and request"),
+        Command::Run {
+            collection,
+            requests,
+            verbose,
+            run,
+            options,
+            vars,
+        } => {
+            handle_run(
+                &collection_dir,
+                &collection,
+                &requests,
+                verbose,
+                &run,
+                &options.with_config(&config),
+                &vars,
+            )
+            .await?;
+        }
+        Command::Snapshot {
             collection,
             request,
+            check,
+            ignore,
             verbose,
-            var,
-            params,
+            options,
+            vars,
+        } => {
+            handle_snapshot(
+                &collection_dir,
+                &collection,
+                &request,
+                check,
+                &ignore,
+                verbose,
+                &options.with_config(&config),
+                &vars,
+            )
+            .await?;
+        }
+        Command::Bench {
+            target,
+            request,
+            bench,
+            options,
+            vars,
+        } => {
+            handle_bench(
+                &collection_dir,
+                &target,
+                request.as_deref(),
+                &bench,
+                &options.with_config(&config),
+                &vars,
+            )
+            .await?;
+        }
+        Command::Mock {
+            collection,
+            port,
+            host,
+            vars,
+        } => {
+            handle_mock(&collection_dir, &collection, &host, port, &vars).await?;
+        }
+        Command::List { collection, tag } => {
+            handle_list(&collection_dir, collection.as_deref(), &tag)?;
+        }
+        Command::Validate { collection, vars } => {
+            handle_validate(&collection_dir, collection.as_deref(), &vars)?;
+        }
+        Command::Vars { collection, vars } => {
+            handle_vars(&collection_dir, &collection, &vars)?;
+        }
+        Command::Describe {
+            collection,
+            request,
+            vars,
+        } => {
+            handle_describe(&collection_dir, &collection, request.as_deref(), &vars)?;
+        }
+        Command::Edit {
+            collection,
+            request,
+            vars,
         } => {
-            handle_collection(&collection, &request, verbose, &var, &params).await?;
+            handle_edit(&collection_dir, &collection, request.as_deref(), &vars)?;
+        }
+        Command::Schema => println!("{}", wave::collection::COLLECTION_SCHEMA.trim_end()),
+        Command::Export { format } => match format {
+            ExportFormat::Curl {
+                collection,
+                request,
+                vars,
+                params,
+            } => {
+                handle_export_curl(&collection_dir, &collection, &request, &vars, &params)?;
+            }
+        },
+        Command::Completions { shell } => handle_completions(shell),
+        Command::Names { collection } => handle_names(&collection_dir, collection.as_deref()),
+        Command::Doctor { .. } => unreachable!("handled before loading the configuration"),
+        Command::Init { name, force } => {
+            handle_init(&collection_dir, &name, force)?;
         }
     }
     Ok(())
@@ -130,7 +402,8 @@ async fn run() -> Result<(), WaveError> {
 /// Application entry point
 ///
 /// Initializes the tokio async runtime and executes the wave CLI application.
-/// Handles error reporting and sets appropriate exit codes.
+/// Reports errors on stderr and exits with the code for their class, see
+/// [`wave::error::exit_code`].
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
@@ -138,6 +411,6 @@ async fn main() {
         if let Some(suggestion) = e.suggestion() {
             eprintln!("Suggestion: {suggestion}");
         }
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
     }
 }
@@ -3,13 +3,62 @@
 //! This module provides functionality for loading and managing collections of HTTP requests
 //! from YAML files, including variable resolution and request parsing.
 
-use crate::http::parse_method;
+use crate::assertions::Expect;
+use crate::condition::Condition;
+use crate::dynamic;
+use crate::error::{CollectionError, WaveError};
+use crate::http::{parse_method, Auth, HttpRequest, MultipartPart, OAuth2, TlsOptions};
+use crate::mock::Mock;
+use crate::snapshot::SnapshotConfig;
 use http::Method;
 use serde::de::{self, Deserializer, MapAccess, Visitor};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Starter collection written by `wave init`
+///
+/// Demonstrates variables, headers, and a JSON body so new users have a
+/// working example to copy from. Requests target httpbin.org so they can be
+/// run immediately.
+pub const STARTER_COLLECTION: &str = r#"# Collection created by `wave init`.
+# Run a request with: wave -c <collection> <request>
+
+variables:
+  base_url: https://httpbin.org
+  user_id: "42"
+
+# Select an environment with --env, e.g. wave -c <collection> get-example --env local
+environments:
+  local:
+    base_url: http://localhost:8080
+
+requests:
+  - name: get-example
+    method: GET
+    url: ${base_url}/get?user_id=${user_id}
+    headers:
+      Accept: application/json
+    # Run only tagged requests with: wave run <collection> --tag smoke
+    tags: [smoke]
+
+  - name: create-example
+    method: POST
+    url: ${base_url}/post
+    headers:
+      Accept: application/json
+    body:
+      json:
+        name: Alice
+        user_id: 42
+        active: true
+"#;
 
 /// Converts a serde_yaml::Value to serde_json::Value for YAML-to-JSON conversion
 ///
@@ -72,12 +121,690 @@ pub fn yaml_to_json(val: &serde_yaml::Value) -> serde_json::Value {
 ///
 /// Collections are loaded from YAML files and contain reusable HTTP requests
 /// along with variables that can be referenced within those requests.
+/// Requests that `extends:` a template already have it applied.
 #[derive(Debug, Deserialize)]
+#[serde(try_from = "CollectionFile")]
 pub struct Collection {
+    /// What the collection is for, shown by `wave describe`
+    pub description: Option<String>,
     /// Variables defined in the collection file that can be referenced in requests
     pub variables: Option<HashMap<String, String>>,
+    /// Named variable sets (e.g. dev, staging, prod) layered over `variables`
+    pub environments: Option<HashMap<String, HashMap<String, String>>>,
+    /// TLS settings for every request in the collection (CLI flags take precedence)
+    pub tls: Option<TlsOptions>,
+    /// Timeouts for every request in the collection (CLI flags take precedence)
+    pub timeouts: Option<Timeouts>,
+    /// Headers sent with every request in the collection, unless the request
+    /// sets a header of the same name
+    pub headers: Option<HashMap<String, String>>,
+    /// Prefix for request URLs that are relative paths, e.g. `/users/${id}`
+    pub base_url: Option<String>,
+    /// Shared request settings that requests pick with `extends:`
+    pub templates: HashMap<String, Template>,
     /// List of HTTP requests in this collection
     pub requests: Vec<Request>,
+    /// Variables from `.env` files, filled in when the collection is loaded
+    /// by name (see [`crate::dotenv`])
+    pub dotenv: HashMap<String, String>,
+}
+
+/// Timeouts in seconds from a collection's `timeouts:` block
+///
+/// ```yaml
+/// timeouts:
+///   connect: 5  # server unreachable
+///   read: 30    # server stopped sending
+///   total: 120
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Timeouts {
+    /// Limit for the whole request, as with `--timeout`
+    pub total: Option<u64>,
+    /// Limit for opening the connection, as with `--connect-timeout`
+    pub connect: Option<u64>,
+    /// Limit for each wait on the server while it responds, as with `--read-timeout`
+    pub read: Option<u64>,
+}
+
+/// JSON Schema of the collection format, for editors and CI
+///
+/// Printed by `wave schema`. Point YAML editors at a saved copy with a
+/// `# yaml-language-server: $schema=./collection.schema.json` comment.
+pub const COLLECTION_SCHEMA: &str = include_str!("../schema/collection.schema.json");
+
+/// Top-level keys of a collection file
+pub(crate) const COLLECTION_KEYS: &[&str] = &[
+    "description",
+    "includes",
+    "variables",
+    "environments",
+    "tls",
+    "timeouts",
+    "headers",
+    "base_url",
+    "templates",
+    "requests",
+    "strict",
+];
+
+/// Keys of a request entry
+pub(crate) const REQUEST_KEYS: &[&str] = &[
+    "name",
+    "description",
+    "extends",
+    "method",
+    "url",
+    "query",
+    "headers",
+    "body",
+    "auth",
+    "expect",
+    "expect_status",
+    "capture",
+    "pre",
+    "post",
+    "tags",
+    "depends_on",
+    "only_if",
+    "skip_if",
+    "delay",
+    "save_response",
+    "snapshot",
+    "mock",
+];
+
+/// Variable holding the name of the request being resolved, e.g. for
+/// `save_response: ./out/${request_name}.json`; a variable of the same name
+/// defined by the collection wins
+pub const REQUEST_NAME_VAR: &str = "request_name";
+
+/// Variables before expansion: values that may refer to other variables,
+/// and values taken literally (see [`Collection::variable_layers`])
+pub type VariableLayers = (HashMap<String, String>, HashMap<String, String>);
+
+/// Finds the lines of top-level keys and of the keys of `requests:` entries
+///
+/// Works on block-style YAML as wave writes it; keys in flow style
+/// (`{name: a, ...}`) are not found and are reported without a line.
+pub(crate) struct KeyLines {
+    lines: Vec<String>,
+    /// Index of the first line of each `requests:` entry
+    items: Vec<usize>,
+    /// Index of the line after the `requests:` list
+    end: usize,
+    /// Column at which the keys of a request entry start
+    key_col: usize,
+}
+
+impl KeyLines {
+    pub(crate) fn new(content: &str) -> KeyLines {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let mut items = Vec::new();
+        let mut end = lines.len();
+        let mut dash_col = None;
+        let header = lines.iter().position(|l| l.starts_with("requests:"));
+        for (i, line) in lines.iter().enumerate().skip(header.map_or(end, |h| h + 1)) {
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let col = line.len() - trimmed.len();
+            if col == 0 && !trimmed.starts_with('-') {
+                end = i;
+                break;
+            }
+            if trimmed.starts_with("- ") && col == *dash_col.get_or_insert(col) {
+                items.push(i);
+            }
+        }
+        KeyLines {
+            lines,
+            items,
+            end,
+            key_col: dash_col.map_or(0, |col| col + 2),
+        }
+    }
+
+    /// Returns the line number of a top-level key
+    pub(crate) fn top_level(&self, key: &str) -> Option<usize> {
+        let prefix = format!("{key}:");
+        self.lines
+            .iter()
+            .position(|l| l.starts_with(&prefix))
+            .map(|i| i + 1)
+    }
+
+    /// Returns the line number of a key of the `index`-th request entry
+    pub(crate) fn request_key(&self, index: usize, key: &str) -> Option<usize> {
+        let start = *self.items.get(index)?;
+        let end = self.items.get(index + 1).copied().unwrap_or(self.end);
+        let prefix = format!("{key}:");
+        (start..end)
+            .find(|&i| {
+                let line = &self.lines[i];
+                line.get(self.key_col..)
+                    .is_some_and(|rest| rest.starts_with(&prefix))
+                    && matches!(line[..self.key_col].trim_start_matches(' '), "" | "- ")
+            })
+            .map(|i| i + 1)
+    }
+}
+
+/// Returns the keys wave does not know in a collection file, each with its
+/// line number when it can be found and a message naming it
+pub(crate) fn unknown_keys(
+    value: &serde_yaml::Value,
+    lines: &KeyLines,
+) -> Vec<(Option<usize>, String)> {
+    let mut unknown = Vec::new();
+    let keys = |value: &serde_yaml::Value| {
+        value
+            .as_mapping()
+            .into_iter()
+            .flat_map(|map| map.keys().filter_map(serde_yaml::Value::as_str))
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    for key in keys(value) {
+        if !COLLECTION_KEYS.contains(&key.as_str()) {
+            unknown.push((lines.top_level(&key), format!("unknown key '{key}'")));
+        }
+    }
+    let requests = value
+        .get("requests")
+        .and_then(serde_yaml::Value::as_sequence);
+    for (i, request) in requests.into_iter().flatten().enumerate() {
+        let name = request
+            .get("name")
+            .and_then(serde_yaml::Value::as_str)
+            .unwrap_or("");
+        for key in keys(request) {
+            if !REQUEST_KEYS.contains(&key.as_str()) {
+                unknown.push((
+                    lines.request_key(i, &key),
+                    format!("unknown key '{key}' in request '{name}'"),
+                ));
+            }
+        }
+    }
+    unknown
+}
+
+/// A collection file as written, before templates are applied
+#[derive(Deserialize)]
+struct CollectionFile {
+    /// Other files merged into this one, relative to this file; only
+    /// followed by [`load_collection`]
+    #[serde(default)]
+    includes: Vec<String>,
+    /// Reject keys wave does not know, in this file and the files it includes
+    #[serde(default)]
+    strict: bool,
+    description: Option<String>,
+    variables: Option<HashMap<String, String>>,
+    environments: Option<HashMap<String, HashMap<String, String>>>,
+    tls: Option<TlsOptions>,
+    timeouts: Option<Timeouts>,
+    headers: Option<HashMap<String, String>>,
+    base_url: Option<String>,
+    #[serde(default)]
+    templates: HashMap<String, Template>,
+    #[serde(default)]
+    requests: Vec<RequestEntry>,
+    /// File each request was defined in, for collision errors
+    #[serde(skip)]
+    request_files: HashMap<String, PathBuf>,
+    /// File each template was defined in, for collision errors
+    #[serde(skip)]
+    template_files: HashMap<String, PathBuf>,
+}
+
+impl CollectionFile {
+    /// Reads a collection file and, recursively, the files it includes
+    ///
+    /// `stack` holds the files being loaded, to report include cycles, and
+    /// `strict` is set when an including file has `strict: true`. Files in
+    /// `edits`, by canonical path, are read from there instead of the disk.
+    fn load(
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        strict: bool,
+        edits: &HashMap<PathBuf, String>,
+    ) -> Result<CollectionFile, CollectionError> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let content = match edits.get(&canonical) {
+            Some(content) => content.clone(),
+            None => fs::read_to_string(path)
+                .map_err(|_| CollectionError::FileNotFound(path.display().to_string()))?,
+        };
+        let mut file: CollectionFile = serde_yaml::from_str(&content)
+            .map_err(|e| CollectionError::InvalidYaml(format!("{}: {e}", path.display())))?;
+        let strict = strict || file.strict;
+        if strict {
+            let value: serde_yaml::Value = serde_yaml::from_str(&content)
+                .map_err(|e| CollectionError::InvalidYaml(format!("{}: {e}", path.display())))?;
+            let unknown: Vec<String> = unknown_keys(&value, &KeyLines::new(&content))
+                .into_iter()
+                .map(|(line, message)| match line {
+                    Some(line) => format!("{}:{line}: {message}", path.display()),
+                    None => format!("{}: {message}", path.display()),
+                })
+                .collect();
+            if !unknown.is_empty() {
+                return Err(CollectionError::InvalidYaml(unknown.join("\n")));
+            }
+        }
+        if stack.contains(&canonical) {
+            let chain: Vec<String> = stack
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(CollectionError::InvalidYaml(format!(
+                "include cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+        let base = path.parent().unwrap_or(Path::new(""));
+        for entry in &mut file.requests {
+            file.request_files
+                .entry(entry.name.clone())
+                .or_insert_with(|| path.to_path_buf());
+            // Body files and templates are relative to the collection file,
+            // unless the path starts with a variable
+            if let Some(
+                Body::File {
+                    path: file_path, ..
+                }
+                | Body::Template {
+                    path: file_path, ..
+                },
+            ) = &mut entry.body
+            {
+                if file_path.is_relative() && !file_path.to_string_lossy().starts_with("${") {
+                    *file_path = base.join(&*file_path);
+                }
+            }
+        }
+        for name in file.templates.keys() {
+            file.template_files.insert(name.clone(), path.to_path_buf());
+        }
+        stack.push(canonical);
+        for include in std::mem::take(&mut file.includes) {
+            let included = CollectionFile::load(&base.join(include), stack, strict, edits)?;
+            file.merge(included)?;
+        }
+        stack.pop();
+        Ok(file)
+    }
+
+    /// Adds the requests and templates of `other` after this file's own
+    ///
+    /// Variables, environments, headers, `tls`, `timeouts` and `base_url` already set in
+    /// this file win over those of `other`. The `description` of an included
+    /// file is not used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CollectionError::NameCollision` if both files define a
+    /// request or a template with the same name.
+    fn merge(&mut self, other: CollectionFile) -> Result<(), CollectionError> {
+        let collision =
+            |kind, name: &str, first: &PathBuf, second: &PathBuf| CollectionError::NameCollision {
+                kind,
+                name: name.to_string(),
+                first: first.display().to_string(),
+                second: second.display().to_string(),
+            };
+        for (name, second) in &other.request_files {
+            if let Some(first) = self.request_files.get(name) {
+                return Err(collision("request", name, first, second));
+            }
+        }
+        for (name, second) in &other.template_files {
+            if let Some(first) = self.template_files.get(name) {
+                return Err(collision("template", name, first, second));
+            }
+        }
+        self.requests.extend(other.requests);
+        self.request_files.extend(other.request_files);
+        self.templates.extend(other.templates);
+        self.template_files.extend(other.template_files);
+        if let Some(variables) = other.variables {
+            let own = self.variables.get_or_insert_with(HashMap::new);
+            for (name, value) in variables {
+                own.entry(name).or_insert(value);
+            }
+        }
+        if let Some(environments) = other.environments {
+            let own = self.environments.get_or_insert_with(HashMap::new);
+            for (env, variables) in environments {
+                let own_env = own.entry(env).or_default();
+                for (name, value) in variables {
+                    own_env.entry(name).or_insert(value);
+                }
+            }
+        }
+        if let Some(headers) = other.headers {
+            self.headers = Some(merge_headers(&headers, self.headers.take()));
+        }
+        self.tls = self.tls.take().or(other.tls);
+        self.timeouts = self.timeouts.take().or(other.timeouts);
+        self.base_url = self.base_url.take().or(other.base_url);
+        Ok(())
+    }
+}
+
+impl TryFrom<CollectionFile> for Collection {
+    type Error = String;
+
+    fn try_from(file: CollectionFile) -> Result<Self, Self::Error> {
+        let requests = file
+            .requests
+            .into_iter()
+            .map(|entry| {
+                let template = match &entry.extends {
+                    Some(name) => Some(file.templates.get(name).ok_or_else(|| {
+                        let mut available: Vec<&str> =
+                            file.templates.keys().map(String::as_str).collect();
+                        available.sort();
+                        format!(
+                            "request '{}' extends unknown template '{name}' (templates: {})",
+                            entry.name,
+                            if available.is_empty() {
+                                "none".to_string()
+                            } else {
+                                available.join(", ")
+                            }
+                        )
+                    })?),
+                    None => None,
+                };
+                entry.into_request(template)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        check_depends_on(&requests)?;
+        Ok(Collection {
+            description: file.description,
+            variables: file.variables,
+            environments: file.environments,
+            tls: file.tls,
+            timeouts: file.timeouts,
+            headers: file.headers,
+            base_url: file.base_url,
+            templates: file.templates,
+            requests,
+            dotenv: HashMap::new(),
+        })
+    }
+}
+
+/// Checks that every `depends_on:` names another request of the collection
+/// and that no request depends on itself through others
+fn check_depends_on(requests: &[Request]) -> Result<(), String> {
+    let index: HashMap<&str, usize> = requests
+        .iter()
+        .enumerate()
+        .map(|(i, r)| (r.name.as_str(), i))
+        .collect();
+    for req in requests {
+        for dep in &req.depends_on {
+            if !index.contains_key(dep.as_str()) {
+                return Err(format!(
+                    "request '{}' depends_on unknown request '{dep}'",
+                    req.name
+                ));
+            }
+        }
+    }
+    // Depth-first, with the requests on the current path kept to name the cycle
+    fn visit<'a>(
+        i: usize,
+        requests: &'a [Request],
+        index: &HashMap<&str, usize>,
+        done: &mut Vec<bool>,
+        path: &mut Vec<&'a str>,
+    ) -> Result<(), String> {
+        let name = requests[i].name.as_str();
+        if let Some(start) = path.iter().position(|&n| n == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name);
+            return Err(format!("depends_on cycle: {}", cycle.join(" -> ")));
+        }
+        if done[i] {
+            return Ok(());
+        }
+        path.push(name);
+        for dep in &requests[i].depends_on {
+            visit(index[dep.as_str()], requests, index, done, path)?;
+        }
+        path.pop();
+        done[i] = true;
+        Ok(())
+    }
+    let mut done = vec![false; requests.len()];
+    for i in 0..requests.len() {
+        visit(i, requests, &index, &mut done, &mut Vec::new())?;
+    }
+    Ok(())
+}
+
+/// Request settings shared through `extends:`
+///
+/// ```yaml
+/// templates:
+///   authed:
+///     method: GET
+///     headers:
+///       Accept: application/json
+///     auth:
+///       bearer: ${token}
+///
+/// requests:
+///   - name: list-users
+///     extends: authed
+///     url: ${base_url}/users
+/// ```
+///
+/// A request's own `method` and `auth` replace the template's, and its
+/// `headers` are layered over the template's by name, case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    #[serde(default, deserialize_with = "deserialize_method")]
+    pub method: Option<Method>,
+    pub headers: Option<HashMap<String, String>>,
+    pub auth: Option<Auth>,
+}
+
+fn deserialize_method<'de, D>(deserializer: D) -> Result<Option<Method>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|method| {
+            parse_method(&method)
+                .map_err(|e| de::Error::custom(format!("Invalid HTTP method: {e}")))
+        })
+        .transpose()
+}
+
+/// Layers `headers` over `defaults`, matching names case-insensitively
+fn merge_headers(
+    defaults: &HashMap<String, String>,
+    headers: Option<HashMap<String, String>>,
+) -> HashMap<String, String> {
+    let mut merged = headers.unwrap_or_default();
+    for (name, value) in defaults {
+        if !merged.keys().any(|k| k.eq_ignore_ascii_case(name)) {
+            merged.insert(name.clone(), value.clone());
+        }
+    }
+    merged
+}
+
+impl Collection {
+    /// Builds the variable set used to resolve requests
+    ///
+    /// Starts from the collection `variables`, overlays the `.env` values and,
+    /// when `env` is given, the variables of that environment, each layer
+    /// taking precedence over the previous one. `.env` values are also stored
+    /// as `env:NAME` so `${env:NAME}` can fall back to them. Values that refer
+    /// to other variables are expanded (see [`expand_variables`]); ones that
+    /// cannot be, because of a missing variable or a cycle, are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CollectionError::EnvironmentNotFound` if `env` is not defined
+    /// in the collection's `environments` section.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::collection::Collection;
+    ///
+    /// let coll: Collection = serde_yaml::from_str(r#"
+    /// variables:
+    ///   base_url: http://localhost:8080
+    ///   users_url: ${base_url}/users
+    /// environments:
+    ///   prod:
+    ///     base_url: https://api.example.com
+    /// requests: []
+    /// "#)?;
+    ///
+    /// let vars = coll.variables_for_env(Some("prod"))?;
+    /// assert_eq!(vars["users_url"], "https://api.example.com/users");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn variables_for_env(
+        &self,
+        env: Option<&str>,
+    ) -> Result<HashMap<String, String>, CollectionError> {
+        let (templates, literals) = self.variable_layers(env)?;
+        Ok(expand_variables(&templates, &literals).0)
+    }
+
+    /// Returns the variables of the collection and `env` before expansion:
+    /// the `variables:` and environment values, which may refer to other
+    /// variables, and the `.env` values, which are taken literally
+    ///
+    /// The two never share a name. Layer `--var` and captured values on top
+    /// by removing them from the first map and adding them to the second,
+    /// then pass both to [`expand_variables`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `CollectionError::EnvironmentNotFound` if `env` is not defined
+    /// in the collection's `environments` section.
+    pub fn variable_layers(&self, env: Option<&str>) -> Result<VariableLayers, CollectionError> {
+        let mut templates = self.variables.clone().unwrap_or_default();
+        let mut literals = HashMap::new();
+        for (name, value) in &self.dotenv {
+            templates.remove(name);
+            literals.insert(name.clone(), value.clone());
+            literals.insert(format!("env:{name}"), value.clone());
+        }
+        if let Some(env_name) = env {
+            let env_vars = self
+                .environments
+                .as_ref()
+                .and_then(|envs| envs.get(env_name))
+                .ok_or_else(|| {
+                    let mut available: Vec<String> = self
+                        .environments
+                        .as_ref()
+                        .map(|envs| envs.keys().cloned().collect())
+                        .unwrap_or_default();
+                    available.sort();
+                    CollectionError::EnvironmentNotFound {
+                        environment: env_name.to_string(),
+                        available,
+                    }
+                })?;
+            for (name, value) in env_vars {
+                literals.remove(name);
+                templates.insert(name.clone(), value.clone());
+            }
+        }
+        Ok((templates, literals))
+    }
+
+    /// Returns the named request with the collection's `headers:` merged in
+    /// and its URL joined to `base_url:` if it is relative
+    ///
+    /// Header names are compared case-insensitively, and the request's own
+    /// headers win. See [`join_url`] for which URLs are relative.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::collection::Collection;
+    ///
+    /// let coll: Collection = serde_yaml::from_str(r#"
+    /// headers:
+    ///   Accept: application/json
+    ///   X-Team: payments
+    /// requests:
+    ///   - name: get-user
+    ///     method: GET
+    ///     url: https://api.example.com/users/1
+    ///     headers:
+    ///       accept: text/plain
+    /// "#)?;
+    ///
+    /// let headers = coll.request("get-user").unwrap().headers.unwrap();
+    /// assert_eq!(headers["accept"], "text/plain");
+    /// assert_eq!(headers["X-Team"], "payments");
+    /// assert!(!headers.contains_key("Accept"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn request(&self, name: &str) -> Option<Request> {
+        let mut req = self.requests.iter().find(|r| r.name == name)?.clone();
+        if let Some(defaults) = &self.headers {
+            req.headers = Some(merge_headers(defaults, req.headers.take()));
+        }
+        if let Some(base_url) = &self.base_url {
+            req.url = join_url(base_url, &req.url);
+        }
+        Some(req)
+    }
+}
+
+/// Joins a request URL to a collection's `base_url`
+///
+/// URLs with a scheme (`https://...`) or starting with a variable
+/// (`${base_url}/...`) are absolute and returned unchanged. Anything else is
+/// a path appended to `base_url` with exactly one `/` between them; an empty
+/// URL is `base_url` itself and a query string (`?page=2`) is appended directly.
+///
+/// # Examples
+///
+/// ```
+/// use wave::collection::join_url;
+///
+/// assert_eq!(join_url("https://api.example.com/v1/", "/users/${id}"), "https://api.example.com/v1/users/${id}");
+/// assert_eq!(join_url("https://api.example.com", "users"), "https://api.example.com/users");
+/// assert_eq!(join_url("https://api.example.com/search", "?q=wave"), "https://api.example.com/search?q=wave");
+/// assert_eq!(join_url("https://api.example.com", "https://other.example.com/x"), "https://other.example.com/x");
+/// assert_eq!(join_url("https://api.example.com", "${auth_url}/token"), "${auth_url}/token");
+/// ```
+pub fn join_url(base_url: &str, url: &str) -> String {
+    let url = url.trim();
+    if url.contains("://") || url.starts_with("${") {
+        return url.to_string();
+    }
+    let base = base_url.trim().trim_end_matches('/');
+    let path = url.trim_start_matches('/');
+    if path.is_empty() && url.is_empty() {
+        base.to_string()
+    } else if path.starts_with(['?', '#']) {
+        format!("{base}{path}")
+    } else {
+        format!("{base}/{path}")
+    }
 }
 
 /// An HTTP request definition from a collection file
@@ -85,58 +812,577 @@ pub struct Collection {
 /// Represents a single HTTP request with all its components including method,
 /// URL, headers, and optional body. Variables in any field can be resolved
 /// using collection variables or environment variables.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Request {
     /// Human-readable name for the request
     pub name: String,
+    /// What the request does, shown by `wave describe`
+    pub description: Option<String>,
     /// HTTP method (GET, POST, PUT, etc.)
     pub method: Method,
     /// Target URL (may contain variables)
     pub url: String,
+    /// Query parameters in file order, appended to `url` when variables are
+    /// resolved; a list value repeats the parameter
+    pub query: Vec<(String, String)>,
     /// Optional HTTP headers
     pub headers: Option<HashMap<String, String>>,
     /// Optional request body (JSON or form data)
     pub body: Option<Body>, // Body is now validated for mutual exclusivity
+    /// Optional authentication, applied as an `Authorization` header
+    pub auth: Option<Auth>,
+    /// Optional assertions checked against the response
+    pub expect: Option<Expect>,
+    /// Variables to extract from the JSON response during `wave run`,
+    /// mapping variable name to a dotted or JSONPath-style path
+    pub capture: Option<HashMap<String, String>>,
+    /// Shell commands run before the request is sent (see [`crate::hooks`])
+    pub pre: Vec<String>,
+    /// Shell commands run after the response arrives (see [`crate::hooks`])
+    pub post: Vec<String>,
+    /// Labels used to pick requests with `--tag`, e.g. `[smoke, auth]`
+    pub tags: Vec<String>,
+    /// Requests that `wave run` sends before this one, by name; this one is
+    /// skipped if any of them does not pass
+    pub depends_on: Vec<String>,
+    /// Condition that must hold for `wave run` to send the request
+    pub only_if: Option<Condition>,
+    /// Condition under which `wave run` skips the request
+    pub skip_if: Option<Condition>,
+    /// Time `wave run` waits before sending the request, e.g. `500ms`
+    pub delay: Option<Duration>,
+    /// File the response body is written to, relative to the working
+    /// directory, e.g. `./out/${request_name}.json`
+    pub save_response: Option<String>,
+    /// Settings for `wave snapshot`, such as fields left out of `--check`
+    pub snapshot: Option<SnapshotConfig>,
+    /// Canned response served by `wave mock`
+    pub mock: Option<Mock>,
 }
 
-impl<'de> Deserialize<'de> for Request {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        #[derive(Deserialize)]
-        struct RequestHelper {
-            name: String,
-            method: String,
-            url: String,
-            headers: Option<HashMap<String, String>>,
-            body: Option<Body>,
+impl Request {
+    /// Returns true if the request has any of `tags`, or if `tags` is empty
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty() || tags.iter().any(|tag| self.tags.contains(tag))
+    }
+
+    /// Returns the names of the `${...}` variables the request refers to
+    ///
+    /// Covers every field that [`resolve_request_vars`] resolves; `${env:...}`
+    /// references are not included.
+    pub fn variable_names(&self) -> HashSet<String> {
+        self.templates()
+            .into_iter()
+            .flat_map(|(_, text)| variable_refs(&text))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Returns the sorted names of variables without a `:-` default that are
+    /// not in `vars` and are neither dynamic variables nor `${request_name}`
+    pub fn missing_variables(&self, vars: &HashMap<String, String>) -> Vec<String> {
+        self.undefined_variables(&HashMap::new(), vars)
+    }
+
+    /// Like [`Request::missing_variables`] for the variables of
+    /// [`Collection::variable_layers`] before expansion: names in neither map,
+    /// including those that values in `templates` refer to
+    pub fn undefined_variables(
+        &self,
+        templates: &HashMap<String, String>,
+        literals: &HashMap<String, String>,
+    ) -> Vec<String> {
+        let mut pending: Vec<(String, bool)> = self
+            .templates()
+            .into_iter()
+            .flat_map(|(_, text)| variable_refs(&text))
+            .collect();
+        let mut expanded = HashSet::new();
+        let mut missing = Vec::new();
+        while let Some((name, has_default)) = pending.pop() {
+            if let Some(value) = templates.get(&name) {
+                if expanded.insert(name) {
+                    pending.extend(variable_refs(value));
+                }
+            } else if !literals.contains_key(&name)
+                && !has_default
+                && !dynamic::is_dynamic(&name)
+                && name != REQUEST_NAME_VAR
+            {
+                missing.push(name);
+            }
+        }
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    /// Returns every `${...}` reference of the request, `${env:...}` included,
+    /// with the field it is in (`url`, `query`, `headers`, `body`, `auth`,
+    /// `expect`, `only_if`, `skip_if` or `save_response`) and whether it has a
+    /// `:-` default
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::collection::Request;
+    ///
+    /// let yaml = "name: me\nmethod: GET\nurl: ${base}/me\nheaders:\n  Authorization: ${env:TOKEN:-dev}\n";
+    /// let req: Request = serde_yaml::from_str(yaml).unwrap();
+    /// assert_eq!(
+    ///     req.variable_uses(),
+    ///     [("url", "base".to_string(), false), ("headers", "env:TOKEN".to_string(), true)]
+    /// );
+    /// ```
+    pub fn variable_uses(&self) -> Vec<(&'static str, String, bool)> {
+        let mut uses = Vec::new();
+        for (field, text) in self.templates() {
+            for (name, has_default) in variable_references(&text) {
+                if !uses.contains(&(field, name.clone(), has_default)) {
+                    uses.push((field, name, has_default));
+                }
+            }
+        }
+        uses
+    }
+
+    /// Returns every string field that may contain `${...}` references, with
+    /// the name of the field it belongs to
+    fn templates(&self) -> Vec<(&'static str, Cow<'_, str>)> {
+        let mut texts: Vec<(&'static str, &str)> = vec![("url", &self.url)];
+        texts.extend(
+            self.query
+                .iter()
+                .flat_map(|(k, v)| [("query", k.as_str()), ("query", v.as_str())]),
+        );
+        texts.extend(
+            self.headers
+                .iter()
+                .flat_map(|h| h.values().map(|v| ("headers", v.as_str()))),
+        );
+        let mut body: Vec<&str> = Vec::new();
+        match &self.body {
+            Some(Body::Json(value)) => body.extend(json_strings(value)),
+            Some(Body::Form(map)) => body.extend(map.values().map(String::as_str)),
+            Some(Body::Multipart(map)) => body.extend(map.values().map(|f| match f {
+                MultipartField::Text(value) => value.as_str(),
+                MultipartField::File { file } => file.as_str(),
+            })),
+            Some(Body::Text(data) | Body::Raw { data, .. }) => body.push(data),
+            Some(Body::File { path, .. } | Body::Template { path, .. }) => {
+                body.extend(path.to_str())
+            }
+            None => {}
+        }
+        texts.extend(body.into_iter().map(|text| ("body", text)));
+        let mut auth: Vec<&str> = Vec::new();
+        match &self.auth {
+            Some(Auth::Basic { username, password }) => {
+                auth.push(username);
+                auth.extend(password.as_deref());
+            }
+            Some(Auth::Bearer(token)) => auth.push(token),
+            Some(Auth::OAuth2(oauth2)) => {
+                auth.extend([
+                    oauth2.token_url.as_str(),
+                    &oauth2.client_id,
+                    &oauth2.client_secret,
+                ]);
+                auth.extend(oauth2.scopes.iter().map(String::as_str));
+            }
+            None => {}
+        }
+        texts.extend(auth.into_iter().map(|text| ("auth", text)));
+        for (field, condition) in [("only_if", &self.only_if), ("skip_if", &self.skip_if)] {
+            texts.extend(
+                condition
+                    .iter()
+                    .flat_map(Condition::values)
+                    .map(|value| (field, value)),
+            );
+        }
+        texts.extend(
+            self.save_response
+                .as_deref()
+                .map(|path| ("save_response", path)),
+        );
+        if let Some(expect) = &self.expect {
+            texts.extend(
+                expect
+                    .headers
+                    .iter()
+                    .flat_map(|h| h.values().map(|v| ("expect", v.as_str()))),
+            );
+            texts.extend(
+                expect
+                    .json
+                    .iter()
+                    .flat_map(|j| j.values().flat_map(json_strings))
+                    .map(|text| ("expect", text)),
+            );
         }
+        let mut texts: Vec<(&'static str, Cow<'_, str>)> = texts
+            .into_iter()
+            .map(|(field, text)| (field, Cow::Borrowed(text)))
+            .collect();
+        // The variables of a template are in its file, read once its path
+        // no longer depends on variables
+        if let Some(Body::Template { path, .. }) = &self.body {
+            let template = path
+                .to_str()
+                .filter(|path| !path.contains("${"))
+                .and_then(|path| fs::read_to_string(path).ok());
+            texts.extend(template.map(|text| ("body", Cow::Owned(text))));
+        }
+        texts
+    }
+}
 
-        let helper = RequestHelper::deserialize(deserializer)?;
-        let method = parse_method(&helper.method)
-            .map_err(|e| de::Error::custom(format!("Invalid HTTP method: {e}")))?;
+/// A request as written in a collection file, before its template is applied
+#[derive(Deserialize)]
+struct RequestEntry {
+    name: String,
+    description: Option<String>,
+    extends: Option<String>,
+    method: Option<String>,
+    url: String,
+    #[serde(default, deserialize_with = "deserialize_query")]
+    query: Vec<(String, String)>,
+    headers: Option<HashMap<String, String>>,
+    body: Option<Body>,
+    auth: Option<Auth>,
+    expect: Option<Expect>,
+    /// Shorthand for `expect: { status: ... }`
+    expect_status: Option<u16>,
+    capture: Option<HashMap<String, String>>,
+    #[serde(default, deserialize_with = "deserialize_commands")]
+    pre: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_commands")]
+    post: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    only_if: Option<Condition>,
+    skip_if: Option<Condition>,
+    #[serde(default, deserialize_with = "deserialize_delay")]
+    delay: Option<Duration>,
+    save_response: Option<String>,
+    snapshot: Option<SnapshotConfig>,
+    mock: Option<Mock>,
+}
 
+impl RequestEntry {
+    /// Builds the request, filling in what it leaves out from `template`
+    fn into_request(self, template: Option<&Template>) -> Result<Request, String> {
+        let method = match (&self.method, template.and_then(|t| t.method.as_ref())) {
+            (Some(method), _) => {
+                parse_method(method).map_err(|e| format!("Invalid HTTP method: {e}"))?
+            }
+            (None, Some(method)) => method.clone(),
+            (None, None) => {
+                return Err(match &self.extends {
+                    Some(name) => format!(
+                        "request '{}' has no method and template '{name}' does not set one",
+                        self.name
+                    ),
+                    None => format!("request '{}': missing field `method`", self.name),
+                })
+            }
+        };
+        let headers = match template.and_then(|t| t.headers.as_ref()) {
+            Some(defaults) => Some(merge_headers(defaults, self.headers)),
+            None => self.headers,
+        };
+        let expect = match (self.expect, self.expect_status) {
+            (_, Some(status)) if !(100..=999).contains(&status) => {
+                return Err(format!(
+                    "request '{}': expect_status {status} is not an HTTP status code",
+                    self.name
+                ))
+            }
+            (
+                Some(Expect {
+                    status: Some(set), ..
+                }),
+                Some(status),
+            ) if set != status => {
+                return Err(format!(
+                    "request '{}' sets both expect_status: {status} and expect.status: {set}",
+                    self.name
+                ))
+            }
+            (expect, Some(status)) => Some(Expect {
+                status: Some(status),
+                ..expect.unwrap_or_default()
+            }),
+            (expect, None) => expect,
+        };
         Ok(Request {
-            name: helper.name,
+            name: self.name,
+            description: self.description,
             method,
-            url: helper.url,
-            headers: helper.headers,
-            body: helper.body,
+            url: self.url,
+            query: self.query,
+            headers,
+            body: self.body,
+            auth: self.auth.or_else(|| template.and_then(|t| t.auth.clone())),
+            expect,
+            capture: self.capture,
+            pre: self.pre,
+            post: self.post,
+            tags: self.tags,
+            depends_on: self.depends_on,
+            only_if: self.only_if,
+            skip_if: self.skip_if,
+            delay: self.delay,
+            save_response: self.save_response,
+            snapshot: self.snapshot,
+            mock: self.mock,
         })
     }
 }
 
+/// Reads a `delay:` such as `500ms` or `2s` (see [`crate::runner::parse_delay`])
+fn deserialize_delay<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::String(s) => s,
+        serde_yaml::Value::Number(n) => n.to_string(),
+        _ => return Err(de::Error::custom("delay must be a duration such as 500ms")),
+    };
+    crate::runner::parse_delay(&text)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+/// Reads a `pre:` or `post:` hook: one command, or a list run in order
+fn deserialize_commands<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Commands {
+        One(String),
+        Many(Vec<String>),
+    }
+    match Commands::deserialize(deserializer) {
+        Ok(Commands::One(command)) => Ok(vec![command]),
+        Ok(Commands::Many(commands)) => Ok(commands),
+        Err(_) => Err(de::Error::custom(
+            "hooks must be a command or a list of commands",
+        )),
+    }
+}
+
+/// Reads a `query:` map, keeping the parameters in file order
+///
+/// Values may be strings, numbers or booleans, or a list of them to repeat a
+/// parameter (`tag: [a, b]` is `tag=a&tag=b`).
+fn deserialize_query<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    fn scalar<E: de::Error>(key: &str, value: &serde_yaml::Value) -> Result<String, E> {
+        match value {
+            serde_yaml::Value::String(s) => Ok(s.clone()),
+            serde_yaml::Value::Number(n) => Ok(n.to_string()),
+            serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+            _ => Err(E::custom(format!(
+                "query parameter '{key}' must be a string, number, boolean or a list of them"
+            ))),
+        }
+    }
+    let mut query = Vec::new();
+    for (key, value) in serde_yaml::Mapping::deserialize(deserializer)? {
+        let key = scalar::<D::Error>("", &key)
+            .map_err(|_| de::Error::custom("query parameter names must be strings"))?;
+        match &value {
+            serde_yaml::Value::Sequence(items) => {
+                for item in items {
+                    query.push((key.clone(), scalar(&key, item)?));
+                }
+            }
+            value => query.push((key.clone(), scalar(&key, value)?)),
+        }
+    }
+    Ok(query)
+}
+
+/// Appends percent-encoded query parameters to a URL
+///
+/// Parameters go after any query string already in the URL and before its
+/// `#fragment`.
+///
+/// # Examples
+///
+/// ```
+/// use wave::collection::with_query;
+///
+/// let query = vec![("q".to_string(), "rust & wave".to_string())];
+/// assert_eq!(with_query("https://api.example.com/search", &query), "https://api.example.com/search?q=rust%20%26%20wave");
+/// assert_eq!(with_query("https://api.example.com/search?page=2#top", &query), "https://api.example.com/search?page=2&q=rust%20%26%20wave#top");
+/// assert_eq!(with_query("https://api.example.com/search", &[]), "https://api.example.com/search");
+/// ```
+pub fn with_query(url: &str, query: &[(String, String)]) -> String {
+    if query.is_empty() {
+        return url.to_string();
+    }
+    let (base, fragment) = match url.find('#') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    let encoded: Vec<String> = query
+        .iter()
+        .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+        .collect();
+    let separator = match base.find('?') {
+        None => "?",
+        Some(i) if i + 1 == base.len() || base.ends_with('&') => "",
+        Some(_) => "&",
+    };
+    format!("{base}{separator}{}{fragment}", encoded.join("&"))
+}
+
+impl<'de> Deserialize<'de> for Request {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        RequestEntry::deserialize(deserializer)?
+            .into_request(None)
+            .map_err(de::Error::custom)
+    }
+}
+
 /// HTTP request body types supported in collections
 ///
-/// Request bodies can be either JSON objects or form data. The YAML parser
-/// enforces mutual exclusivity - only one body type can be specified per request.
-#[derive(Debug)]
+/// Request bodies can be JSON values, form data, multipart forms, text or the
+/// contents of a file. The YAML parser enforces mutual exclusivity - only one
+/// body type can be specified per request.
+///
+/// ```yaml
+/// body:
+///   text: Hello ${name}
+///
+/// body:
+///   raw: <user><name>${name}</name></user>
+///   content_type: application/xml
+///
+/// body:
+///   file: ./payloads/avatar.png
+///   content_type: image/png
+///
+/// body:
+///   template: ./payloads/order.json.tmpl
+/// ```
+#[derive(Debug, Clone)]
 pub enum Body {
-    /// JSON object body (key-value pairs with YAML values)
-    Json(HashMap<String, serde_yaml::Value>),
+    /// JSON body written as YAML: usually a mapping, but any array, string,
+    /// number or boolean is sent as it is
+    Json(serde_yaml::Value),
     /// Form data body (key-value string pairs)
     Form(HashMap<String, String>),
+    /// Multipart form body (text fields and `file:` uploads)
+    Multipart(HashMap<String, MultipartField>),
+    /// Plain text, sent as `text/plain`
+    Text(String),
+    /// Text sent as written, with its own content type (`text/plain` when
+    /// omitted)
+    Raw {
+        data: String,
+        content_type: Option<String>,
+    },
+    /// Contents of a file, sent byte for byte as `application/octet-stream`
+    /// unless a content type is given
+    ///
+    /// Relative paths are relative to the collection file when the collection
+    /// is loaded from disk.
+    File {
+        path: PathBuf,
+        content_type: Option<String>,
+    },
+    /// Text file whose `${...}` variables are resolved, for bodies too large
+    /// to write inline; sent as a `raw` body
+    ///
+    /// Without a content type, one is picked from the file extension once a
+    /// trailing `.tmpl` is removed, see [`template_content_type`]. Relative
+    /// paths are relative to the collection file, like `file:` paths.
+    Template {
+        path: PathBuf,
+        content_type: Option<String>,
+    },
+}
+
+/// Returns the content type implied by a body template's file name
+///
+/// A trailing `.tmpl` or `.tpl` is ignored, so `order.json.tmpl` is JSON.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use wave::collection::template_content_type;
+///
+/// assert_eq!(template_content_type(Path::new("order.json.tmpl")), Some("application/json"));
+/// assert_eq!(template_content_type(Path::new("feed.xml")), Some("application/xml"));
+/// assert_eq!(template_content_type(Path::new("notes.tmpl")), None);
+/// ```
+pub fn template_content_type(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    let name = name
+        .strip_suffix(".tmpl")
+        .or_else(|| name.strip_suffix(".tpl"))
+        .unwrap_or(&name);
+    match name.rsplit_once('.')?.1 {
+        "json" => Some("application/json"),
+        "xml" => Some("application/xml"),
+        "yaml" | "yml" => Some("application/yaml"),
+        "html" | "htm" => Some("text/html"),
+        "csv" => Some("text/csv"),
+        "graphql" | "gql" => Some("application/graphql"),
+        _ => None,
+    }
+}
+
+/// A field in a `multipart:` request body
+///
+/// Plain strings are sent as text fields; a map with a `file:` key uploads
+/// the file at that path:
+///
+/// ```yaml
+/// body:
+///   multipart:
+///     title: Holiday
+///     photo:
+///       file: ./photo.png
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum MultipartField {
+    /// Text field value
+    Text(String),
+    /// File upload, path relative to the working directory
+    File { file: String },
+}
+
+impl MultipartField {
+    /// Converts the field into a request part with the given name
+    pub fn to_part(&self, name: &str) -> MultipartPart {
+        match self {
+            MultipartField::Text(value) => MultipartPart::Text {
+                name: name.to_string(),
+                value: value.clone(),
+            },
+            MultipartField::File { file } => MultipartPart::File {
+                name: name.to_string(),
+                path: PathBuf::from(file),
+            },
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for Body {
@@ -148,40 +1394,84 @@ impl<'de> Deserialize<'de> for Body {
         impl<'de> Visitor<'de> for BodyVisitor {
             type Value = Body;
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("a map with either 'json' or 'form' key")
+                formatter.write_str(
+                    "a map with one of 'json', 'form', 'multipart', 'text', 'raw', 'file' or 'template' keys",
+                )
             }
             fn visit_map<A>(self, mut map: A) -> Result<Body, A::Error>
             where
                 A: MapAccess<'de>,
             {
-                let mut json: Option<HashMap<String, serde_yaml::Value>> = None;
-                let mut form: Option<HashMap<String, String>> = None;
+                let mut body: Option<Body> = None;
+                let mut content_type: Option<String> = None;
                 while let Some(key) = map.next_key::<String>()? {
-                    match key.as_str() {
-                        "json" => {
-                            if json.is_some() {
-                                return Err(de::Error::duplicate_field("json"));
+                    let parsed = match key.as_str() {
+                        "json" => Body::Json(map.next_value()?),
+                        "form" => Body::Form(map.next_value()?),
+                        "multipart" => Body::Multipart(map.next_value()?),
+                        "text" => Body::Text(map.next_value()?),
+                        "raw" => Body::Raw {
+                            data: map.next_value()?,
+                            content_type: None,
+                        },
+                        "file" => Body::File {
+                            path: map.next_value()?,
+                            content_type: None,
+                        },
+                        "template" => Body::Template {
+                            path: map.next_value()?,
+                            content_type: None,
+                        },
+                        "content_type" => {
+                            if content_type.is_some() {
+                                return Err(de::Error::duplicate_field("content_type"));
                             }
-                            json = Some(map.next_value()?);
-                        }
-                        "form" => {
-                            if form.is_some() {
-                                return Err(de::Error::duplicate_field("form"));
-                            }
-                            form = Some(map.next_value()?);
+                            content_type = Some(map.next_value()?);
+                            continue;
                         }
                         other => {
-                            return Err(de::Error::unknown_field(other, &["json", "form"]));
+                            return Err(de::Error::unknown_field(
+                                other,
+                                &[
+                                    "json",
+                                    "form",
+                                    "multipart",
+                                    "text",
+                                    "raw",
+                                    "file",
+                                    "template",
+                                    "content_type",
+                                ],
+                            ));
                         }
+                    };
+                    if let Some(existing) = &body {
+                        return Err(match (existing, &parsed) {
+                            (Body::Json(_), Body::Json(_)) => de::Error::duplicate_field("json"),
+                            (Body::Form(_), Body::Form(_)) => de::Error::duplicate_field("form"),
+                            (Body::Multipart(_), Body::Multipart(_)) => {
+                                de::Error::duplicate_field("multipart")
+                            }
+                            _ => de::Error::custom("Only one of 'json', 'form', 'multipart', 'text', 'raw', 'file' or 'template' can be used in the body of a request. Please specify just one of them."),
+                        });
                     }
+                    body = Some(parsed);
                 }
-                match (json, form) {
-                    (Some(_), Some(_)) => {
-                        Err(de::Error::custom("Only one of 'json' or 'form' can be used in the body of a request. Please specify either 'json' or 'form', not both."))
+                let body = body.ok_or_else(|| {
+                    de::Error::custom("Body must contain a 'json', 'form', 'multipart', 'text', 'raw', 'file' or 'template' key.")
+                })?;
+                match (body, content_type) {
+                    (body, None) => Ok(body),
+                    (Body::Raw { data, .. }, content_type) => Ok(Body::Raw { data, content_type }),
+                    (Body::File { path, .. }, content_type) => {
+                        Ok(Body::File { path, content_type })
+                    }
+                    (Body::Template { path, .. }, content_type) => {
+                        Ok(Body::Template { path, content_type })
                     }
-                    (Some(j), None) => Ok(Body::Json(j)),
-                    (None, Some(f)) => Ok(Body::Form(f)),
-                    (None, None) => Err(de::Error::custom("Body must contain either 'json' or 'form' key.")),
+                    _ => Err(de::Error::custom(
+                        "'content_type' can only be used with a 'raw', 'file' or 'template' body",
+                    )),
                 }
             }
         }
@@ -212,42 +1502,894 @@ impl<'de> Deserialize<'de> for Body {
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 /// Load collection and parse yaml collection
+///
+/// Files listed under `includes:`, relative to the including file, are merged
+/// in after the file's own requests. Request and template names must be
+/// unique across the files; the including file's variables, environments
+/// and headers win over the included ones.
 pub fn load_collection(path: &str) -> Result<Collection, Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(path)?;
-    let coll: Collection = serde_yaml::from_str(&content)?;
-    Ok(coll)
+    let path = Path::new(path);
+    Ok(load_files(&[path.to_path_buf()], &HashMap::new(), path)?)
 }
 
-/// Resolves variables in a string using file-defined and environment variables
-///
-/// Processes variable references in the format `${variable_name}` or `${env:ENV_VAR}`.
-/// File variables are resolved from the provided HashMap, while environment variables
-/// are resolved from the system environment using the `env:` prefix.
-///
-/// # Arguments
+/// Loads every `.yaml` and `.yml` file in a directory as one collection
 ///
-/// * `input` - String that may contain variable references
-/// * `file_vars` - Collection variables from the YAML file
+/// Files are merged in name order, each with its own `includes:`. Request and
+/// template names must be unique across all of the files; variables,
+/// environments and headers set in an earlier file win over later ones.
 ///
-/// # Returns
+/// # Errors
 ///
-/// Returns the string with all variables resolved, or an error if any variable is missing.
+/// Returns `CollectionError::NameCollision` if two files define a request or
+/// template with the same name, and `CollectionError::FileNotFound` if the
+/// directory holds no collection files.
 ///
 /// # Examples
 ///
-/// ```
-/// use std::collections::HashMap;
-/// use wave::collection::resolve_vars;
+/// ```no_run
+/// use std::path::Path;
+/// use wave::collection::load_collection_dir;
 ///
-/// let mut vars = HashMap::new();
+/// // .wave/api/users.yaml, .wave/api/billing.yaml, ...
+/// let collection = load_collection_dir(Path::new(".wave/api"))?;
+/// println!("Loaded {} requests", collection.requests.len());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn load_collection_dir(dir: &Path) -> Result<Collection, Box<dyn std::error::Error>> {
+    let files = yaml_files(dir)?;
+    if files.is_empty() {
+        return Err(CollectionError::FileNotFound(dir.join("*.yaml").display().to_string()).into());
+    }
+    Ok(load_files(&files, &HashMap::new(), dir)?)
+}
+
+/// Loads the collection made of `paths`, merged in order, reading the files
+/// in `edits` (by canonical path) from there instead of the disk and naming
+/// `shown` in errors
+fn load_files(
+    paths: &[PathBuf],
+    edits: &HashMap<PathBuf, String>,
+    shown: &Path,
+) -> Result<Collection, CollectionError> {
+    let mut merged: Option<CollectionFile> = None;
+    for path in paths {
+        let file = CollectionFile::load(path, &mut Vec::new(), false, edits)?;
+        match &mut merged {
+            Some(merged) => merged.merge(file)?,
+            None => merged = Some(file),
+        }
+    }
+    let merged =
+        merged.ok_or_else(|| CollectionError::FileNotFound(shown.display().to_string()))?;
+    into_collection(merged, shown)
+}
+
+/// Parses the text of one collection file without following its includes
+/// or applying templates, for `wave validate`
+pub(crate) fn check_syntax(content: &str) -> Result<(), serde_yaml::Error> {
+    serde_yaml::from_str::<CollectionFile>(content).map(|_| ())
+}
+
+/// Applies templates to a loaded file, naming `path` in errors
+fn into_collection(file: CollectionFile, path: &Path) -> Result<Collection, CollectionError> {
+    Collection::try_from(file)
+        .map_err(|e| CollectionError::InvalidYaml(format!("{}: {e}", path.display())))
+}
+
+/// Returns the `.yaml` and `.yml` files in a directory, sorted by name
+fn yaml_files(dir: &Path) -> Result<Vec<PathBuf>, WaveError> {
+    let entries = fs::read_dir(dir).map_err(|_| {
+        WaveError::Collection(CollectionError::DirectoryNotFound(
+            dir.display().to_string(),
+        ))
+    })?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Scaffolds a new collection file with starter content
+///
+/// Creates `dir` if it does not exist and writes [`STARTER_COLLECTION`] to
+/// `<dir>/<name>.yaml`. Existing collections (`.yaml` or `.yml`) are never
+/// replaced unless `force` is set.
+///
+/// # Arguments
+///
+/// * `dir` - Collection directory, usually `.wave`
+/// * `name` - Collection name (file name without extension)
+/// * `force` - Overwrite an existing collection file
+///
+/// # Returns
+///
+/// Returns the path of the written collection file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use wave::collection::init_collection;
+///
+/// let path = init_collection(Path::new(".wave"), "api", false)?;
+/// println!("Created {}", path.display());
+/// # Ok::<(), wave::error::WaveError>(())
+/// ```
+pub fn init_collection(dir: &Path, name: &str, force: bool) -> Result<PathBuf, WaveError> {
+    let yaml_path = dir.join(format!("{name}.yaml"));
+    let yml_path = dir.join(format!("{name}.yml"));
+    if !force {
+        if let Some(existing) = [&yaml_path, &yml_path].into_iter().find(|p| p.exists()) {
+            return Err(WaveError::Collection(CollectionError::AlreadyExists(
+                existing.display().to_string(),
+            )));
+        }
+    }
+    fs::create_dir_all(dir)?;
+    write_atomically(&yaml_path, STARTER_COLLECTION)?;
+    Ok(yaml_path)
+}
+
+/// Returns the names of the collections in a directory, sorted
+///
+/// Every `.yaml` and `.yml` file counts as a collection, and so does every
+/// subdirectory holding such files (see [`load_collection_dir`]); a name
+/// present more than once is listed once.
+///
+/// # Errors
+///
+/// Returns `CollectionError::DirectoryNotFound` if `dir` cannot be read.
+pub fn collection_names(dir: &Path) -> Result<Vec<String>, WaveError> {
+    let entries = fs::read_dir(dir).map_err(|_| {
+        WaveError::Collection(CollectionError::DirectoryNotFound(
+            dir.display().to_string(),
+        ))
+    })?;
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| {
+            if path.is_dir() {
+                path.file_name()
+                    .filter(|_| yaml_files(&path).is_ok_and(|files| !files.is_empty()))
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+            {
+                path.file_stem()
+            } else {
+                None
+            }
+            .map(|s| s.to_string_lossy().into_owned())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Names that `wave collection` reads as subcommands rather than collections
+///
+/// Collections with these names are not created. An existing one is still
+/// run with `--` before its name, e.g. `wave -c -- mv get-example`.
+pub const RESERVED_COLLECTION_NAMES: [&str; 3] = ["rm", "mv", "help"];
+
+/// Where `--save COLLECTION:REQUEST` writes an ad-hoc request
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveTarget {
+    /// Collection name (file name without extension)
+    pub collection: String,
+    /// Name of the new request entry
+    pub request: String,
+}
+
+impl FromStr for SaveTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (collection, request) = s
+            .split_once(':')
+            .map(|(c, r)| (c.trim(), r.trim()))
+            .filter(|(c, r)| !c.is_empty() && !r.is_empty())
+            .ok_or_else(|| format!("expected COLLECTION:REQUEST, got '{s}'"))?;
+        if collection.contains(['/', '\\']) {
+            return Err(format!(
+                "invalid collection name '{collection}', use a plain file name without path separators"
+            ));
+        }
+        if RESERVED_COLLECTION_NAMES.contains(&collection) {
+            return Err(format!(
+                "collection name '{collection}' is reserved for a 'wave collection' subcommand"
+            ));
+        }
+        Ok(SaveTarget {
+            collection: collection.to_string(),
+            request: request.to_string(),
+        })
+    }
+}
+
+/// Appends a request to a collection file, creating the file if needed
+///
+/// The entry is added to the end of the `requests:` list as text, so comments
+/// and formatting in the rest of the file are kept. An existing `.yml` file is
+/// used in preference to creating `<name>.yaml`. The file is replaced
+/// atomically, keeping the previous version as `<file>.bak`.
+///
+/// # Errors
+///
+/// Returns `CollectionError::RequestExists` if the collection already has a
+/// request with the same name, `CollectionError::CannotSave` if the request
+/// body has no collection equivalent, and `CollectionError::InvalidYaml` if
+/// the existing file is not a valid collection.
+///
+/// # Examples
+///
+/// ```no_run
+/// use http::{HeaderMap, Method};
+/// use std::path::Path;
+/// use wave::collection::save_request;
+/// use wave::http::HttpRequest;
+///
+/// let req = HttpRequest::new("https://api.example.com/users", Method::GET, None, HeaderMap::new());
+/// let target = "api:list-users".parse().unwrap();
+/// let path = save_request(Path::new(".wave"), &target, &req)?;
+/// println!("Saved to {}", path.display());
+/// # Ok::<(), wave::error::WaveError>(())
+/// ```
+pub fn save_request(
+    dir: &Path,
+    target: &SaveTarget,
+    req: &HttpRequest,
+) -> Result<PathBuf, WaveError> {
+    let name = &target.collection;
+    let path = [
+        dir.join(format!("{name}.yaml")),
+        dir.join(format!("{name}.yml")),
+    ]
+    .into_iter()
+    .find(|p| p.exists())
+    .unwrap_or_else(|| dir.join(format!("{name}.yaml")));
+    let existing = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    if !existing.trim().is_empty() {
+        let coll = load_collection(&path.to_string_lossy()).map_err(|e| match e.downcast() {
+            Ok(e) => *e,
+            Err(e) => CollectionError::InvalidYaml(e.to_string()),
+        })?;
+        if coll.requests.iter().any(|r| r.name == target.request) {
+            return Err(WaveError::Collection(CollectionError::RequestExists {
+                collection: name.clone(),
+                request: target.request.clone(),
+            }));
+        }
+    }
+
+    let entry = request_entry(&target.request, req)?;
+    let updated = insert_request_entry(&existing, &entry);
+    fs::create_dir_all(dir)?;
+    write_collection_file(&path, &updated)?;
+    Ok(path)
+}
+
+/// Renders a request as a YAML list item (`- name: ...`)
+fn request_entry(name: &str, req: &HttpRequest) -> Result<String, WaveError> {
+    let cannot_save =
+        |msg: &str| WaveError::Collection(CollectionError::CannotSave(msg.to_string()));
+    let content_type = req
+        .headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    let (body, implied_type) = if let Some(parts) = &req.multipart {
+        let mut fields = serde_yaml::Mapping::new();
+        for part in parts {
+            let (name, field) = match part {
+                MultipartPart::Text { name, value } => (name, value.as_str().into()),
+                MultipartPart::File { name, path } => {
+                    let mut file = serde_yaml::Mapping::new();
+                    file.insert("file".into(), path.display().to_string().into());
+                    (name, serde_yaml::Value::Mapping(file))
+                }
+            };
+            fields.insert(name.as_str().into(), field);
+        }
+        (
+            Some(("multipart", serde_yaml::Value::Mapping(fields))),
+            None,
+        )
+    } else if let Some(text) = &req.body {
+        if content_type.starts_with("application/x-www-form-urlencoded") {
+            let mut fields = serde_yaml::Mapping::new();
+            for pair in text.split('&').filter(|p| !p.is_empty()) {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                let decode = |s: &str| {
+                    urlencoding::decode(&s.replace('+', " "))
+                        .map(|s| s.into_owned())
+                        .map_err(|_| cannot_save("form body is not valid UTF-8"))
+                };
+                fields.insert(decode(key)?.into(), decode(value)?.into());
+            }
+            (
+                Some(("form", serde_yaml::Value::Mapping(fields))),
+                Some("application/x-www-form-urlencoded"),
+            )
+        } else {
+            match serde_json::from_str::<serde_json::Value>(text) {
+                Ok(json) => (
+                    Some(("json", serde_yaml::to_value(json)?)),
+                    Some("application/json"),
+                ),
+                Err(_) => {
+                    return Err(cannot_save(
+                        "only JSON, form and multipart bodies can be stored in a collection",
+                    ))
+                }
+            }
+        }
+    } else {
+        (None, None)
+    };
+
+    let mut entry = serde_yaml::Mapping::new();
+    entry.insert("name".into(), name.into());
+    entry.insert("method".into(), req.method.as_str().into());
+    entry.insert("url".into(), req.url.as_str().into());
+
+    let mut headers = serde_yaml::Mapping::new();
+    for (header, value) in &req.headers {
+        let implied = header == http::header::CONTENT_TYPE
+            && (req.multipart.is_some() || implied_type == Some(content_type));
+        if implied {
+            continue;
+        }
+        let value = value
+            .to_str()
+            .map_err(|_| cannot_save(&format!("header '{header}' is not valid text")))?;
+        // Collections hold one value per header, so repeated values are joined
+        let value = match headers.get(header.as_str()).and_then(|v| v.as_str()) {
+            Some(earlier) => format!("{earlier}, {value}"),
+            None => value.to_string(),
+        };
+        headers.insert(header.as_str().into(), value.into());
+    }
+    if !headers.is_empty() {
+        entry.insert("headers".into(), headers.into());
+    }
+    if let Some((kind, fields)) = body {
+        let mut body = serde_yaml::Mapping::new();
+        body.insert(kind.into(), fields);
+        entry.insert("body".into(), body.into());
+    }
+
+    let yaml = serde_yaml::to_string(&vec![serde_yaml::Value::Mapping(entry)])?;
+    Ok(yaml)
+}
+
+/// Inserts a rendered list item at the end of the top-level `requests:` list
+///
+/// The item is indented like the existing entries (two spaces by default) and
+/// separated from them by a blank line.
+fn insert_request_entry(content: &str, entry: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let is_content = |line: &str| !line.trim().is_empty() && !line.trim_start().starts_with('#');
+    let header = lines
+        .iter()
+        .position(|l| l.starts_with("requests:"))
+        .unwrap_or_else(|| {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push("requests:".to_string());
+            lines.len() - 1
+        });
+    if lines[header]["requests:".len()..].trim() == "[]" {
+        lines[header] = "requests:".to_string();
+    }
+
+    let block_end = lines[header + 1..]
+        .iter()
+        .position(|l| is_content(l) && !l.starts_with([' ', '\t', '-']))
+        .map_or(lines.len(), |i| header + 1 + i);
+    let last_item_line = lines[header + 1..block_end]
+        .iter()
+        .rposition(|l| is_content(l))
+        .map(|i| header + 1 + i);
+    let indent = lines[header + 1..block_end]
+        .iter()
+        .find(|l| l.trim_start().starts_with('-'))
+        .map_or(2, |l| l.len() - l.trim_start().len());
+
+    let mut new_lines: Vec<String> = Vec::new();
+    if last_item_line.is_some() {
+        new_lines.push(String::new());
+    }
+    new_lines.extend(entry.lines().map(|l| format!("{}{l}", " ".repeat(indent))));
+    let at = last_item_line.map_or(header + 1, |i| i + 1);
+    lines.splice(at..at, new_lines);
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Removes a request from a collection and returns the file it was in
+///
+/// The request's lines are cut from the file as text, so comments and
+/// formatting elsewhere are kept; comments between the request and the next
+/// one are left for the next one. Collections split over several files or
+/// pulled in with `includes:` are edited in the file defining the request.
+///
+/// # Errors
+///
+/// Returns `CollectionError::RequestNotFound` if the collection has no such
+/// request, `CollectionError::RequestInUse` if other requests list it under
+/// `depends_on:`, and `CollectionError::InvalidYaml` if the edit would leave
+/// the collection invalid.
+pub fn remove_request(dir: &Path, collection: &str, request: &str) -> Result<PathBuf, WaveError> {
+    let (path, index) = crate::validate::request_index(dir, collection, request)?;
+    let dependents = crate::validate::dependents(dir, collection, request)?;
+    if !dependents.is_empty() {
+        return Err(WaveError::Collection(CollectionError::RequestInUse {
+            collection: collection.to_string(),
+            request: request.to_string(),
+            dependents: dependents.into_iter().map(|(_, _, name)| name).collect(),
+        }));
+    }
+    let content = fs::read_to_string(&path)?;
+    let updated = remove_request_entry(&content, index);
+    write_collection_files(dir, collection, vec![(path.clone(), updated)])?;
+    Ok(path)
+}
+
+/// Renames a request of a collection and returns the file it is in
+///
+/// Only the value of the request's `name:` key changes, along with the
+/// `depends_on:` entries naming it in any file of the collection; comments
+/// after them are kept.
+///
+/// # Errors
+///
+/// Returns `CollectionError::RequestNotFound` if the collection has no
+/// request `old`, `CollectionError::RequestExists` if it already has one
+/// named `new`, and `CollectionError::InvalidYaml` if the edit would leave
+/// the collection invalid.
+pub fn rename_request(
+    dir: &Path,
+    collection: &str,
+    old: &str,
+    new: &str,
+) -> Result<PathBuf, WaveError> {
+    let (path, index) = crate::validate::request_index(dir, collection, old)?;
+    if old != new && crate::validate::request_index(dir, collection, new).is_ok() {
+        return Err(WaveError::Collection(CollectionError::RequestExists {
+            collection: collection.to_string(),
+            request: new.to_string(),
+        }));
+    }
+    let content = fs::read_to_string(&path)?;
+    let updated = rename_request_entry(&content, index, new)?.ok_or_else(|| {
+        CollectionError::InvalidYaml(format!(
+            "{}: cannot find the name: line of request '{old}'",
+            path.display()
+        ))
+    })?;
+    let mut edits = vec![(path.clone(), updated)];
+    for (file, index, name) in crate::validate::dependents(dir, collection, old)? {
+        let at = match edits.iter().position(|(edited, _)| *edited == file) {
+            Some(at) => at,
+            None => {
+                edits.push((file.clone(), fs::read_to_string(&file)?));
+                edits.len() - 1
+            }
+        };
+        edits[at].1 = rename_dependency(&edits[at].1, index, old, new)?.ok_or_else(|| {
+            CollectionError::InvalidYaml(format!(
+                "{}: cannot find the depends_on: entry of request '{name}' naming '{old}'",
+                file.display()
+            ))
+        })?;
+    }
+    write_collection_files(dir, collection, edits)?;
+    Ok(path)
+}
+
+/// Writes an edited collection file after checking that it still parses
+///
+/// A file that would not parse is never written, and the write itself is
+/// atomic (see [`write_atomically`]).
+fn write_collection_file(path: &Path, content: &str) -> Result<(), WaveError> {
+    serde_yaml::from_str::<CollectionFile>(content)
+        .map_err(|e| CollectionError::InvalidYaml(format!("{}: {e}", path.display())))?;
+    write_atomically(path, content)
+}
+
+/// Writes edited files of a collection after checking that the collection
+/// still loads with them
+///
+/// Nothing is written unless every file parses and the whole collection,
+/// includes, templates and `depends_on:` included, loads as it would for a
+/// request. Each write is atomic (see [`write_atomically`]).
+fn write_collection_files(
+    dir: &Path,
+    collection: &str,
+    edits: Vec<(PathBuf, String)>,
+) -> Result<(), WaveError> {
+    for (path, content) in &edits {
+        serde_yaml::from_str::<CollectionFile>(content)
+            .map_err(|e| CollectionError::InvalidYaml(format!("{}: {e}", path.display())))?;
+    }
+    let pending: HashMap<PathBuf, String> = edits
+        .iter()
+        .map(|(path, content)| {
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+            (canonical, content.clone())
+        })
+        .collect();
+    let roots = crate::validate::root_files(dir, collection)?;
+    let shown = edits.first().map_or(dir, |(path, _)| path.as_path());
+    load_files(&roots, &pending, shown)?;
+    for (path, content) in &edits {
+        write_atomically(path, content)?;
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` with `content` without ever leaving it half
+/// written
+///
+/// The content goes to a temporary file next to `path`, is flushed to disk,
+/// then renamed over `path`, which is atomic within a file system. The
+/// previous version, if any, is kept as `<file>.bak`, which collection
+/// loading ignores.
+fn write_atomically(path: &Path, content: &str) -> Result<(), WaveError> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = dir.join(format!(".{file_name}.{}.tmp", std::process::id()));
+    let write = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&tmp, metadata.permissions())?;
+            fs::copy(path, dir.join(format!("{file_name}.bak")))?;
+        }
+        fs::rename(&tmp, path)?;
+        // Makes the rename durable too; some platforms cannot open directories
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        WaveError::Io(format!("failed to write '{}': {e}", path.display()))
+    })
+}
+
+/// Cuts the `index`-th entry out of the top-level `requests:` list
+///
+/// Comments right above the entry go with it. Blank lines and comments after
+/// its last line belong to the next entry and are kept, except for a blank
+/// line that would be doubled. An emptied list becomes `requests: []`.
+fn remove_request_entry(content: &str, index: usize) -> String {
+    let keys = KeyLines::new(content);
+    let Some(&first) = keys.items.get(index) else {
+        return content.to_string();
+    };
+    let mut start = first;
+    while start > 0 && keys.lines[start - 1].trim_start().starts_with('#') {
+        start -= 1;
+    }
+    let next = keys.items.get(index + 1).copied().unwrap_or(keys.end);
+    // Blank lines, and comments no deeper than the `-`, lead up to the next entry
+    let dash_col = keys.key_col.saturating_sub(2);
+    let leads_to_next = |line: &str| {
+        let trimmed = line.trim_start();
+        trimmed.is_empty() || (trimmed.starts_with('#') && line.len() - trimmed.len() <= dash_col)
+    };
+    let mut stop = next;
+    while stop > first + 1 && leads_to_next(&keys.lines[stop - 1]) {
+        stop -= 1;
+    }
+    let mut lines = keys.lines;
+    let gap_before = start == 0
+        || lines[start - 1].trim().is_empty()
+        || lines[start - 1].starts_with("requests:");
+    while stop < next && gap_before && lines[stop].trim().is_empty() {
+        stop += 1;
+    }
+    lines.drain(start..stop);
+    if keys.items.len() == 1 {
+        if let Some(header) = lines.iter_mut().find(|l| l.starts_with("requests:")) {
+            *header = "requests: []".to_string();
+        }
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Sets the value of the `name:` key of the `index`-th request entry,
+/// keeping a trailing comment, or returns `None` if the key is not on a
+/// line of its own
+fn rename_request_entry(
+    content: &str,
+    index: usize,
+    name: &str,
+) -> Result<Option<String>, WaveError> {
+    let keys = KeyLines::new(content);
+    let Some(line_no) = keys.request_key(index, "name") else {
+        return Ok(None);
+    };
+    let mut lines = keys.lines;
+    let line = &lines[line_no - 1];
+    let key_end = line.find("name:").map_or(line.len(), |i| i + "name:".len());
+    let (key, value) = line.split_at(key_end);
+    let (_, comment) = split_comment(value);
+    let quoted = serde_yaml::to_string(name)?;
+    lines[line_no - 1] = format!("{key} {}{comment}", quoted.trim_end());
+    let mut text = lines.join("\n");
+    text.push('\n');
+    Ok(Some(text))
+}
+
+/// Replaces `old` with `new` in the `depends_on:` list of the `index`-th
+/// request entry, in flow (`[a, b]`) or block (`- a`) style, keeping
+/// trailing comments, or returns `None` if no entry was found to change
+fn rename_dependency(
+    content: &str,
+    index: usize,
+    old: &str,
+    new: &str,
+) -> Result<Option<String>, WaveError> {
+    let keys = KeyLines::new(content);
+    let Some(line_no) = keys.request_key(index, "depends_on") else {
+        return Ok(None);
+    };
+    let quote = |name: &str| -> Result<String, WaveError> {
+        Ok(serde_yaml::to_string(name)?.trim_end().to_string())
+    };
+    let mut lines = keys.lines;
+    let mut changed = false;
+    let line = &lines[line_no - 1];
+    let key_end = line
+        .find("depends_on:")
+        .map_or(line.len(), |i| i + "depends_on:".len());
+    let (key, value) = line.split_at(key_end);
+    let (list, comment) = split_comment(value);
+    if !list.trim().is_empty() {
+        let Ok(names) = serde_yaml::from_str::<Vec<String>>(list) else {
+            return Ok(None);
+        };
+        if names.iter().any(|name| name == old) {
+            let names = names
+                .iter()
+                .map(|name| quote(if name == old { new } else { name }))
+                .collect::<Result<Vec<_>, _>>()?;
+            lines[line_no - 1] = format!("{key} [{}]{comment}", names.join(", "));
+            changed = true;
+        }
+    } else {
+        // Block items may sit at the key's own column or deeper
+        for line in lines.iter_mut().skip(line_no) {
+            let trimmed = line.trim_start();
+            let col = line.len() - trimmed.len();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let Some(item) = trimmed.strip_prefix("- ").filter(|_| col >= keys.key_col) else {
+                break;
+            };
+            let (item, comment) = split_comment(item);
+            if serde_yaml::from_str::<String>(item).is_ok_and(|name| name == old) {
+                *line = format!("{}- {}{comment}", " ".repeat(col), quote(new)?);
+                changed = true;
+            }
+        }
+    }
+    if !changed {
+        return Ok(None);
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    Ok(Some(text))
+}
+
+/// Splits a YAML value on a line from its trailing comment, which keeps the
+/// whitespace before its `#`
+///
+/// A `#` starts a comment only after whitespace and outside of the value.
+fn split_comment(value: &str) -> (&str, &str) {
+    let start = value
+        .match_indices(" #")
+        .map(|(i, _)| i)
+        .find(|&i| serde_yaml::from_str::<serde_yaml::Value>(&value[..i]).is_ok())
+        .map_or(value.len(), |i| value[..i].trim_end().len());
+    value.split_at(start)
+}
+
+/// Returns the strings of a JSON body that are resolved: every string at any
+/// depth, mapping keys included
+fn json_strings(value: &serde_yaml::Value) -> Vec<&str> {
+    let mut texts = Vec::new();
+    collect_strings(value, &mut texts);
+    texts
+}
+
+/// Collects the strings in a YAML value at any depth, mapping keys included
+fn collect_strings<'a>(value: &'a serde_yaml::Value, texts: &mut Vec<&'a str>) {
+    match value {
+        serde_yaml::Value::String(s) => texts.push(s),
+        serde_yaml::Value::Sequence(items) => {
+            items.iter().for_each(|item| collect_strings(item, texts))
+        }
+        serde_yaml::Value::Mapping(map) => {
+            for (k, v) in map {
+                collect_strings(k, texts);
+                collect_strings(v, texts);
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => collect_strings(&tagged.value, texts),
+        serde_yaml::Value::Null | serde_yaml::Value::Bool(_) | serde_yaml::Value::Number(_) => {}
+    }
+}
+
+/// Resolves variables in every string of a YAML value, at any depth
+///
+/// Mapping keys are resolved too, so a body can use `${field}: value`.
+/// Numbers, booleans and nulls are kept as they are.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use wave::collection::resolve_yaml_vars;
+///
+/// let vars = HashMap::from([("id".to_string(), "7".to_string())]);
+/// let body: serde_yaml::Value = serde_yaml::from_str("user:\n  ids: [\"${id}\", 8]").unwrap();
+/// let resolved = resolve_yaml_vars(&body, &vars)?;
+/// assert_eq!(resolved["user"]["ids"][0], serde_yaml::Value::from("7"));
+/// # Ok::<(), String>(())
+/// ```
+pub fn resolve_yaml_vars(
+    value: &serde_yaml::Value,
+    file_vars: &HashMap<String, String>,
+) -> Result<serde_yaml::Value, String> {
+    Ok(match value {
+        serde_yaml::Value::String(s) => serde_yaml::Value::String(resolve_vars(s, file_vars)?),
+        serde_yaml::Value::Sequence(items) => serde_yaml::Value::Sequence(
+            items
+                .iter()
+                .map(|item| resolve_yaml_vars(item, file_vars))
+                .collect::<Result<_, _>>()?,
+        ),
+        serde_yaml::Value::Mapping(map) => serde_yaml::Value::Mapping(
+            map.iter()
+                .map(|(k, v)| {
+                    Ok((
+                        resolve_yaml_vars(k, file_vars)?,
+                        resolve_yaml_vars(v, file_vars)?,
+                    ))
+                })
+                .collect::<Result<_, String>>()?,
+        ),
+        serde_yaml::Value::Tagged(tagged) => {
+            serde_yaml::Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
+                tag: tagged.tag.clone(),
+                value: resolve_yaml_vars(&tagged.value, file_vars)?,
+            }))
+        }
+        other => other.clone(),
+    })
+}
+
+/// Returns the `${...}` variables in a string with whether each has a `:-`
+/// default, skipping `${env:...}`
+fn variable_refs(input: &str) -> Vec<(String, bool)> {
+    variable_references(input)
+        .into_iter()
+        .filter(|(name, _)| !name.starts_with("env:"))
+        .collect()
+}
+
+/// Returns the `${...}` variables in a string with whether each has a `:-`
+/// default
+///
+/// # Examples
+///
+/// ```
+/// use wave::collection::variable_references;
+///
+/// assert_eq!(
+///     variable_references("${scheme:-https}://${host}"),
+///     [("scheme".to_string(), true), ("host".to_string(), false)]
+/// );
+/// ```
+pub fn variable_references(input: &str) -> Vec<(String, bool)> {
+    input
+        .split("${")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+        .map(|name| match name.split_once(":-") {
+            // `${random_int:-5:5}` is a dynamic variable, not a default
+            Some((short, _)) if !dynamic::is_dynamic(name) => (short.to_string(), true),
+            _ => (name.to_string(), false),
+        })
+        .collect()
+}
+
+/// Resolves variables in a string using file-defined and environment variables
+///
+/// Processes variable references in the format `${variable_name}` or `${env:ENV_VAR}`.
+/// File variables are resolved from the provided HashMap, while environment variables
+/// are resolved from the system environment using the `env:` prefix, falling back
+/// to an `env:ENV_VAR` entry in the HashMap (how `.env` values are passed in).
+/// Either form can end in `:-default`, used when the variable is not set.
+/// Names not found in the HashMap can also be built-in dynamic variables such
+/// as `${uuid}` (see [`crate::dynamic`]).
+///
+/// Values from the HashMap are inserted as they are, even if they contain
+/// `${...}`: values that refer to other variables are expanded beforehand by
+/// [`expand_variables`], so a value that came from a response or `--var`
+/// can never pull in other variables or the environment.
+///
+/// # Arguments
+///
+/// * `input` - String that may contain variable references
+/// * `file_vars` - Collection variables from the YAML file
+///
+/// # Returns
+///
+/// Returns the string with all variables resolved, or an error if any variable is missing.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use wave::collection::resolve_vars;
+///
+/// let mut vars = HashMap::new();
 /// vars.insert("api_host".to_string(), "api.example.com".to_string());
 ///
 /// let result = resolve_vars("https://${api_host}/users", &vars)?;
 /// assert_eq!(result, "https://api.example.com/users");
+///
+/// let result = resolve_vars("${scheme:-https}://${api_host}", &vars)?;
+/// assert_eq!(result, "https://api.example.com");
+///
+/// vars.insert("token".to_string(), "${env:HOME}".to_string());
+/// assert_eq!(resolve_vars("Bearer ${token}", &vars)?, "Bearer ${env:HOME}");
 /// # Ok::<(), String>(())
 /// ```
-/// Resolves variables in a string using file-defined and environment variables.
 pub fn resolve_vars(input: &str, file_vars: &HashMap<String, String>) -> Result<String, String> {
+    resolve_refs(input, &mut |name| Ok(file_vars.get(name).cloned()), None)
+}
+
+/// Resolves the `${...}` references in `input`, looking names up with `lookup`
+///
+/// `used_by` is the variable whose value `input` is, named in errors.
+fn resolve_refs(
+    input: &str,
+    lookup: &mut dyn FnMut(&str) -> Result<Option<String>, String>,
+    used_by: Option<&str>,
+) -> Result<String, String> {
     let mut result = String::new();
     let mut chars = input.chars().peekable();
     while let Some(c) = chars.next() {
@@ -262,15 +2404,44 @@ pub fn resolve_vars(input: &str, file_vars: &HashMap<String, String>) -> Result<
                 var_name.push(next_c);
                 chars.next();
             }
-            if let Some(env_var) = var_name.strip_prefix("env:") {
-                match std::env::var(env_var) {
-                    Ok(val) => result.push_str(&val),
-                    Err(_) => return Err(format!("Missing environment variable: {env_var}")),
+            // `${random_int:-5:5}` is a dynamic variable, not a default
+            let (name, default) = match var_name.split_once(":-") {
+                Some((name, default)) if !dynamic::is_dynamic(&var_name) => (name, Some(default)),
+                _ => (var_name.as_str(), None),
+            };
+            if let Some(env_var) = name.strip_prefix("env:") {
+                if let Ok(val) = std::env::var(env_var) {
+                    tracing::debug!(variable = %name, "resolved from the environment");
+                    result.push_str(&val);
+                } else if let Some(val) = lookup(name)? {
+                    tracing::debug!(variable = %name, "resolved from the environment");
+                    result.push_str(&val);
+                } else if let Some(val) = default {
+                    tracing::debug!(variable = %name, "resolved to its default");
+                    result.push_str(val);
+                } else {
+                    return Err(format!("Missing environment variable: {env_var}"));
                 }
+            } else if let Some(val) = lookup(name)? {
+                tracing::debug!(variable = %name, "resolved from collection variables");
+                result.push_str(&val);
+            } else if let Some(generated) = dynamic::generate(name) {
+                tracing::debug!(variable = %name, "generated dynamic value");
+                result.push_str(&generated?);
             } else {
-                match file_vars.get(&var_name) {
-                    Some(val) => result.push_str(val),
-                    None => return Err(format!("Missing variable: {var_name}")),
+                match default {
+                    Some(val) => {
+                        tracing::debug!(variable = %name, "resolved to its default");
+                        result.push_str(val)
+                    }
+                    None => {
+                        return Err(match used_by {
+                            Some(user) => {
+                                format!("Missing variable: {name} (used by variable '{user}')")
+                            }
+                            None => format!("Missing variable: {name}"),
+                        })
+                    }
                 }
             }
         } else {
@@ -280,6 +2451,96 @@ pub fn resolve_vars(input: &str, file_vars: &HashMap<String, String>) -> Result<
     Ok(result)
 }
 
+/// Expands the values of `templates`, which may refer to each other
+///
+/// A reference to another template is expanded in turn, to any depth, and
+/// one to a name in `literals` gets that value as it is, so text from a
+/// `.env` file, `--var` or a response is never expanded. Returns `literals`
+/// with the templates that could be expanded added, and the problem of each
+/// one that could not: a missing variable, or a cycle such as
+/// `Variable cycle: a -> b -> a`, named from its first variable in sort order.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use wave::collection::expand_variables;
+///
+/// let templates = HashMap::from([
+///     ("api_url".to_string(), "https://${host}/${version}".to_string()),
+///     ("host".to_string(), "api.example.com".to_string()),
+///     ("loop".to_string(), "${loop}".to_string()),
+/// ]);
+/// let literals = HashMap::from([("version".to_string(), "${v2}".to_string())]);
+/// let (vars, problems) = expand_variables(&templates, &literals);
+/// assert_eq!(vars["api_url"], "https://api.example.com/${v2}");
+/// assert_eq!(problems["loop"], "Variable cycle: loop -> loop");
+/// ```
+pub fn expand_variables(
+    templates: &HashMap<String, String>,
+    literals: &HashMap<String, String>,
+) -> (HashMap<String, String>, BTreeMap<String, String>) {
+    let mut done: HashMap<String, Result<String, String>> = HashMap::new();
+    let mut names: Vec<&String> = templates.keys().collect();
+    names.sort();
+    for name in names {
+        let _ = expand_template(name, templates, literals, &mut Vec::new(), &mut done);
+    }
+    let mut vars = literals.clone();
+    let mut problems = BTreeMap::new();
+    for (name, result) in done {
+        match result {
+            Ok(value) => {
+                vars.insert(name, value);
+            }
+            Err(problem) => {
+                problems.insert(name, problem);
+            }
+        }
+    }
+    (vars, problems)
+}
+
+/// Expands template `name`, remembering the outcome of every template in `done`
+///
+/// `chain` holds the templates being expanded, outermost first.
+fn expand_template(
+    name: &str,
+    templates: &HashMap<String, String>,
+    literals: &HashMap<String, String>,
+    chain: &mut Vec<String>,
+    done: &mut HashMap<String, Result<String, String>>,
+) -> Result<String, String> {
+    if let Some(result) = done.get(name) {
+        return result.clone();
+    }
+    if let Some(start) = chain.iter().position(|n| n == name) {
+        let mut cycle = chain[start..].to_vec();
+        // Start the cycle at its first name in sort order, so it reads the
+        // same whichever of its variables is expanded first
+        let first = (0..cycle.len()).min_by_key(|i| &cycle[*i]).unwrap_or(0);
+        cycle.rotate_left(first);
+        cycle.push(cycle[0].clone());
+        return Err(format!("Variable cycle: {}", cycle.join(" -> ")));
+    }
+    chain.push(name.to_string());
+    let result = resolve_refs(
+        &templates[name],
+        &mut |other| match templates.contains_key(other) {
+            true => expand_template(other, templates, literals, chain, done).map(Some),
+            false => Ok(literals.get(other).cloned()),
+        },
+        Some(name),
+    );
+    chain.pop();
+    // A cycle is recorded for its outermost variable only, the one it was
+    // found from
+    if chain.is_empty() || !matches!(&result, Err(e) if e.starts_with("Variable cycle:")) {
+        done.insert(name.to_string(), result.clone());
+    }
+    result
+}
+
 /// Recursively resolves variables in all request fields
 ///
 /// Creates a new Request with all variable references resolved in the URL, headers,
@@ -294,12 +2555,30 @@ pub fn resolve_vars(input: &str, file_vars: &HashMap<String, String>) -> Result<
 /// # Returns
 ///
 /// Returns a new Request with resolved variables, or an error if any variable is missing.
+/// The resolved `query` parameters are appended to the URL and left empty.
+/// `${request_name}` is the request's name unless `file_vars` sets it.
 /// Recursively resolves variables in all request fields
 pub fn resolve_request_vars(
     req: &Request,
     file_vars: &HashMap<String, String>,
 ) -> Result<Request, String> {
-    let url = resolve_vars(&req.url, file_vars)?;
+    let with_name;
+    let file_vars = if file_vars.contains_key(REQUEST_NAME_VAR) {
+        file_vars
+    } else {
+        with_name = {
+            let mut vars = file_vars.clone();
+            vars.insert(REQUEST_NAME_VAR.to_string(), req.name.clone());
+            vars
+        };
+        &with_name
+    };
+    let query = req
+        .query
+        .iter()
+        .map(|(k, v)| Ok((resolve_vars(k, file_vars)?, resolve_vars(v, file_vars)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let url = with_query(&resolve_vars(&req.url, file_vars)?, &query);
     let headers = match &req.headers {
         Some(hs) => {
             let mut resolved = HashMap::new();
@@ -311,34 +2590,103 @@ pub fn resolve_request_vars(
         None => None,
     };
     let body = match &req.body {
-        Some(Body::Json(map)) => {
+        Some(Body::Json(value)) => Some(Body::Json(resolve_yaml_vars(value, file_vars)?)),
+        Some(Body::Form(map)) => {
             let mut resolved = HashMap::new();
             for (k, v) in map {
-                let resolved_value = match v {
-                    serde_yaml::Value::String(s) => {
-                        serde_yaml::Value::String(resolve_vars(s, file_vars)?)
-                    }
-                    other => other.clone(),
-                };
-                resolved.insert(k.clone(), resolved_value);
+                resolved.insert(k.clone(), resolve_vars(v, file_vars)?);
             }
-            Some(Body::Json(resolved))
+            Some(Body::Form(resolved))
         }
-        Some(Body::Form(map)) => {
+        Some(Body::Multipart(map)) => {
             let mut resolved = HashMap::new();
             for (k, v) in map {
-                resolved.insert(k.clone(), resolve_vars(v, file_vars)?);
+                let resolved_field = match v {
+                    MultipartField::Text(value) => {
+                        MultipartField::Text(resolve_vars(value, file_vars)?)
+                    }
+                    MultipartField::File { file } => MultipartField::File {
+                        file: resolve_vars(file, file_vars)?,
+                    },
+                };
+                resolved.insert(k.clone(), resolved_field);
             }
-            Some(Body::Form(resolved))
+            Some(Body::Multipart(resolved))
         }
+        Some(Body::Text(data)) => Some(Body::Text(resolve_vars(data, file_vars)?)),
+        Some(Body::Raw { data, content_type }) => Some(Body::Raw {
+            data: resolve_vars(data, file_vars)?,
+            content_type: content_type.clone(),
+        }),
+        Some(Body::File { path, content_type }) => Some(Body::File {
+            path: PathBuf::from(resolve_vars(&path.to_string_lossy(), file_vars)?),
+            content_type: content_type.clone(),
+        }),
+        Some(Body::Template { path, content_type }) => {
+            let path = PathBuf::from(resolve_vars(&path.to_string_lossy(), file_vars)?);
+            let template = fs::read_to_string(&path)
+                .map_err(|e| format!("cannot read body template '{}': {e}", path.display()))?;
+            Some(Body::Raw {
+                data: resolve_vars(&template, file_vars)
+                    .map_err(|e| format!("{}: {e}", path.display()))?,
+                content_type: content_type
+                    .clone()
+                    .or_else(|| template_content_type(&path).map(str::to_string)),
+            })
+        }
+        None => None,
+    };
+    let auth = match &req.auth {
+        Some(Auth::Basic { username, password }) => Some(Auth::Basic {
+            username: resolve_vars(username, file_vars)?,
+            password: password
+                .as_deref()
+                .map(|p| resolve_vars(p, file_vars))
+                .transpose()?,
+        }),
+        Some(Auth::Bearer(token)) => Some(Auth::Bearer(resolve_vars(token, file_vars)?)),
+        Some(Auth::OAuth2(oauth2)) => Some(Auth::OAuth2(OAuth2 {
+            token_url: resolve_vars(&oauth2.token_url, file_vars)?,
+            client_id: resolve_vars(&oauth2.client_id, file_vars)?,
+            client_secret: resolve_vars(&oauth2.client_secret, file_vars)?,
+            scopes: oauth2
+                .scopes
+                .iter()
+                .map(|scope| resolve_vars(scope, file_vars))
+                .collect::<Result<_, _>>()?,
+        })),
         None => None,
     };
+    let expect = req
+        .expect
+        .as_ref()
+        .map(|e| e.resolve_vars(file_vars))
+        .transpose()?;
     Ok(Request {
         name: req.name.clone(),
+        description: req.description.clone(),
         method: req.method.clone(),
         url,
+        query: Vec::new(),
         headers,
         body,
+        auth,
+        expect,
+        capture: req.capture.clone(),
+        pre: req.pre.clone(),
+        post: req.post.clone(),
+        tags: req.tags.clone(),
+        depends_on: req.depends_on.clone(),
+        only_if: req.only_if.clone(),
+        skip_if: req.skip_if.clone(),
+        delay: req.delay,
+        save_response: req
+            .save_response
+            .as_deref()
+            .map(|path| resolve_vars(path, file_vars))
+            .transpose()?,
+        snapshot: req.snapshot.clone(),
+        mock: req.mock.clone(),
     })
 }
 
@@ -478,4 +2826,1312 @@ requests:
         );
         assert_eq!(json_result, serde_json::Value::Object(expected_map));
     }
+
+    #[test]
+    fn test_multipart_body_parses_and_resolves() {
+        let yaml = r#"
+variables:
+  dir: ./fixtures
+requests:
+  - name: upload
+    method: POST
+    url: https://example.com/upload
+    body:
+      multipart:
+        title: Holiday in ${dir}
+        photo:
+          file: ${dir}/photo.png
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        let vars = coll.variables.clone().expect("Test: Variables exist");
+        let resolved = resolve_request_vars(&coll.requests[0], &vars).expect("Test: Resolve");
+        let Some(Body::Multipart(fields)) = &resolved.body else {
+            panic!("Expected multipart body");
+        };
+        assert_eq!(
+            fields["title"],
+            MultipartField::Text("Holiday in ./fixtures".to_string())
+        );
+        assert_eq!(
+            fields["photo"].to_part("photo"),
+            MultipartPart::File {
+                name: "photo".to_string(),
+                path: PathBuf::from("./fixtures/photo.png"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_body_rejects_multiple_types() {
+        let yaml = r#"
+name: bad
+method: POST
+url: https://example.com
+body:
+  json:
+    a: 1
+  multipart:
+    b: two
+"#;
+        let err = serde_yaml::from_str::<Request>(yaml).unwrap_err();
+        assert!(err.to_string().contains("Only one of"));
+    }
+
+    #[test]
+    fn test_text_raw_and_file_bodies_parse_and_resolve() {
+        let yaml = r#"
+variables:
+  name: joe
+requests:
+  - name: note
+    method: POST
+    url: https://example.com/notes
+    body:
+      text: Hello ${name}
+  - name: xml
+    method: POST
+    url: https://example.com/users
+    body:
+      raw: <user>${name}</user>
+      content_type: application/xml
+  - name: avatar
+    method: PUT
+    url: https://example.com/avatar
+    body:
+      file: ./${name}.png
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        let vars = coll.variables.clone().expect("Test: Variables exist");
+        let resolve = |i: usize| {
+            resolve_request_vars(&coll.requests[i], &vars)
+                .expect("Test: Resolve")
+                .body
+        };
+        assert!(matches!(resolve(0), Some(Body::Text(text)) if text == "Hello joe"));
+        assert!(matches!(
+            resolve(1),
+            Some(Body::Raw { data, content_type: Some(content_type) })
+                if data == "<user>joe</user>" && content_type == "application/xml"
+        ));
+        assert!(matches!(
+            resolve(2),
+            Some(Body::File { path, content_type: None }) if path == Path::new("./joe.png")
+        ));
+
+        let err = serde_yaml::from_str::<Request>(
+            "name: a\nurl: https://example.com\nbody:\n  text: hi\n  content_type: text/csv\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("'content_type' can only be used"));
+        let err = serde_yaml::from_str::<Request>(
+            "name: a\nurl: https://example.com\nbody:\n  text: hi\n  file: a.bin\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Only one of"));
+    }
+
+    #[test]
+    fn test_body_files_are_relative_to_the_collection_file() {
+        let dir = collection_files(
+            "body_files",
+            &[(
+                "api.yaml",
+                "includes: [uploads/more.yaml]\nrequests:\n  - name: a\n    method: POST\n    url: https://example.com\n    body:\n      file: a.bin\n",
+            ),
+            (
+                "uploads/more.yaml",
+                "requests:\n  - name: b\n    method: POST\n    url: https://example.com\n    body:\n      file: b.bin\n  - name: c\n    method: POST\n    url: https://example.com\n    body:\n      file: ${dir}/c.bin\n",
+            )],
+        );
+        let coll = load_collection(&dir.join("api.yaml").to_string_lossy()).expect("Test: load");
+        let file = |name: &str| match &coll.request(name).expect("Test: request").body {
+            Some(Body::File { path, .. }) => path.clone(),
+            other => panic!("Expected file body, got {other:?}"),
+        };
+        assert_eq!(file("a"), dir.join("a.bin"));
+        assert_eq!(file("b"), dir.join("uploads").join("b.bin"));
+        assert_eq!(file("c"), PathBuf::from("${dir}/c.bin"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_body_templates_are_read_and_resolved() {
+        let dir = collection_files(
+            "body_templates",
+            &[
+                (
+                    "api.yaml",
+                    "variables:\n  sku: A-1\nrequests:\n  - name: order\n    method: POST\n    url: https://example.com\n    body:\n      template: payloads/order.json.tmpl\n  - name: note\n    method: POST\n    url: https://example.com\n    body:\n      template: payloads/note.tmpl\n      content_type: text/markdown\n",
+                ),
+                (
+                    "payloads/order.json.tmpl",
+                    "{\"sku\": \"${sku}\", \"qty\": ${qty:-1}, \"by\": \"${user}\"}\n",
+                ),
+                ("payloads/note.tmpl", "# ${sku}\n"),
+            ],
+        );
+        let coll = load_collection(&dir.join("api.yaml").to_string_lossy()).expect("Test: load");
+        let order = coll.request("order").expect("Test: request");
+        let vars = coll.variables.clone().unwrap_or_default();
+        assert_eq!(order.missing_variables(&vars), ["user"]);
+
+        let mut vars = vars;
+        vars.insert("user".to_string(), "ann".to_string());
+        let resolved = resolve_request_vars(&order, &vars).expect("Test: resolve");
+        assert!(matches!(
+            resolved.body,
+            Some(Body::Raw { data, content_type: Some(content_type) })
+                if data == "{\"sku\": \"A-1\", \"qty\": 1, \"by\": \"ann\"}\n"
+                    && content_type == "application/json"
+        ));
+        let note = resolve_request_vars(&coll.request("note").unwrap(), &vars).unwrap();
+        assert!(matches!(
+            note.body,
+            Some(Body::Raw { data, content_type: Some(content_type) })
+                if data == "# A-1\n" && content_type == "text/markdown"
+        ));
+
+        fs::remove_file(dir.join("payloads/note.tmpl")).unwrap();
+        let err = resolve_request_vars(&coll.request("note").unwrap(), &vars).unwrap_err();
+        assert!(err.starts_with("cannot read body template"), "{err}");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_nested_json_bodies_are_resolved() {
+        let yaml = r#"
+variables:
+  id: "7"
+  field: nickname
+  team: core
+requests:
+  - name: update
+    method: PUT
+    url: https://example.com/users/${id}
+    query:
+      ${field}: ${team}
+    body:
+      json:
+        user:
+          id: ${id}
+          ${field}: ann
+          roles: [admin, "${team}-lead"]
+          groups:
+            - name: ${team}
+              size: 3
+        active: true
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        let req = &coll.requests[0];
+        let names = req.variable_names();
+        for name in ["id", "field", "team"] {
+            assert!(names.contains(name), "{name}");
+        }
+        let vars = coll.variables.clone().expect("Test: Variables exist");
+        let resolved = resolve_request_vars(req, &vars).expect("Test: Resolve");
+        assert_eq!(resolved.url, "https://example.com/users/7?nickname=core");
+        let Some(Body::Json(body)) = &resolved.body else {
+            panic!("expected a JSON body");
+        };
+        assert_eq!(
+            yaml_to_json(body),
+            serde_json::json!({
+                "user": {
+                    "id": "7",
+                    "nickname": "ann",
+                    "roles": ["admin", "core-lead"],
+                    "groups": [{"name": "core", "size": 3}]
+                },
+                "active": true
+            })
+        );
+
+        let mut partial = vars.clone();
+        partial.remove("team");
+        assert_eq!(req.missing_variables(&partial), ["team"]);
+        assert_eq!(
+            resolve_request_vars(req, &partial).unwrap_err(),
+            "Missing variable: team"
+        );
+    }
+
+    #[test]
+    fn test_query_parameters_are_resolved_and_encoded() {
+        let yaml = r#"
+variables:
+  term: rust & wave
+requests:
+  - name: search
+    method: GET
+    url: https://example.com/search?lang=en#results
+    query:
+      q: ${term}
+      page: 2
+      exact: true
+      tag: [a b, c]
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        let req = &coll.requests[0];
+        assert_eq!(req.query[1], ("page".to_string(), "2".to_string()));
+        assert!(req.variable_names().contains("term"));
+        let vars = coll.variables.clone().expect("Test: Variables exist");
+        let resolved = resolve_request_vars(req, &vars).expect("Test: Resolve");
+        assert_eq!(
+            resolved.url,
+            "https://example.com/search?lang=en&q=rust%20%26%20wave&page=2&exact=true&tag=a%20b&tag=c#results"
+        );
+        assert!(resolved.query.is_empty());
+
+        let err = serde_yaml::from_str::<Request>(
+            "name: a\nmethod: GET\nurl: https://example.com\nquery:\n  q: {nested: 1}\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("query parameter 'q' must be"));
+    }
+
+    #[test]
+    fn test_requests_extend_templates() {
+        let yaml = r#"
+templates:
+  authed:
+    method: POST
+    headers:
+      Accept: application/json
+      X-Team: payments
+    auth:
+      bearer: ${token}
+requests:
+  - name: create
+    extends: authed
+    url: https://example.com/items
+    headers:
+      accept: text/csv
+  - name: list
+    extends: authed
+    method: GET
+    url: https://example.com/items
+    auth:
+      basic:
+        username: alice
+  - name: plain
+    method: DELETE
+    url: https://example.com/items/1
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        let create = coll.request("create").expect("Test: Request exists");
+        assert_eq!(create.method, Method::POST);
+        assert_eq!(create.auth, Some(Auth::Bearer("${token}".to_string())));
+        assert_eq!(create.missing_variables(&HashMap::new()), vec!["token"]);
+        let headers = create.headers.expect("Test: Headers");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["accept"], "text/csv");
+        assert_eq!(headers["X-Team"], "payments");
+
+        let list = coll.request("list").expect("Test: Request exists");
+        assert_eq!(list.method, Method::GET);
+        assert!(matches!(list.auth, Some(Auth::Basic { .. })));
+
+        let plain = coll.request("plain").expect("Test: Request exists");
+        assert_eq!(plain.headers, None);
+        assert_eq!(plain.auth, None);
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let unknown = r#"
+templates:
+  a: {}
+  b: {}
+requests:
+  - name: x
+    extends: c
+    method: GET
+    url: https://example.com
+"#;
+        let err = serde_yaml::from_str::<Collection>(unknown).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("request 'x' extends unknown template 'c' (templates: a, b)"));
+
+        let no_method = r#"
+templates:
+  a:
+    headers: {X-A: "1"}
+requests:
+  - name: x
+    extends: a
+    url: https://example.com
+"#;
+        let err = serde_yaml::from_str::<Collection>(no_method).unwrap_err();
+        assert!(err.to_string().contains("template 'a' does not set one"));
+
+        let typo = "templates:\n  a:\n    header: {}\nrequests: []\n";
+        assert!(serde_yaml::from_str::<Collection>(typo).is_err());
+    }
+
+    #[test]
+    fn test_timeouts_block() {
+        let yaml = "timeouts:\n  connect: 5\n  read: 30\nrequests: []\n";
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        assert_eq!(
+            coll.timeouts,
+            Some(Timeouts {
+                total: None,
+                connect: Some(5),
+                read: Some(30),
+            })
+        );
+
+        let typo = "timeouts:\n  conect: 5\nrequests: []\n";
+        assert!(serde_yaml::from_str::<Collection>(typo).is_err());
+    }
+
+    #[test]
+    fn test_base_url_joins_relative_urls() {
+        let yaml = r#"
+base_url: ${host}/v1/
+variables:
+  host: http://localhost:8080
+environments:
+  prod:
+    host: https://api.example.com
+requests:
+  - name: user
+    method: GET
+    url: /users/${id}
+  - name: root
+    method: GET
+    url: /
+  - name: absolute
+    method: GET
+    url: https://status.example.com/health
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        let mut vars = coll.variables_for_env(Some("prod")).expect("Test: Env");
+        vars.insert("id".to_string(), "7".to_string());
+        let url = |name| {
+            let req = coll.request(name).expect("Test: Request exists");
+            resolve_request_vars(&req, &vars)
+                .expect("Test: Resolve")
+                .url
+        };
+        assert_eq!(url("user"), "https://api.example.com/v1/users/7");
+        assert_eq!(url("root"), "https://api.example.com/v1/");
+        assert_eq!(url("absolute"), "https://status.example.com/health");
+        assert_eq!(
+            coll.request("user")
+                .expect("Test: Request exists")
+                .missing_variables(&HashMap::new()),
+            vec!["host", "id"]
+        );
+        assert_eq!(join_url("https://x.io/", ""), "https://x.io");
+    }
+
+    #[test]
+    fn test_request_auth_block_resolves_vars() {
+        let yaml = r#"
+variables:
+  token: abc123
+  password: hunter2
+requests:
+  - name: bearer
+    method: GET
+    url: https://example.com
+    auth:
+      bearer: ${token}
+  - name: basic
+    method: GET
+    url: https://example.com
+    auth:
+      basic:
+        username: alice
+        password: ${password}
+  - name: oauth2
+    method: GET
+    url: https://example.com
+    auth:
+      oauth2:
+        token_url: https://auth.example.com/token
+        client_id: app
+        client_secret: ${password}
+        scopes: [read, "${token}"]
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        let vars = coll.variables.clone().expect("Test: Variables exist");
+
+        let bearer = resolve_request_vars(&coll.requests[0], &vars).expect("Test: Resolve");
+        assert_eq!(bearer.auth, Some(Auth::Bearer("abc123".to_string())));
+
+        let basic = resolve_request_vars(&coll.requests[1], &vars).expect("Test: Resolve");
+        assert_eq!(
+            basic.auth,
+            Some(Auth::Basic {
+                username: "alice".to_string(),
+                password: Some("hunter2".to_string()),
+            })
+        );
+
+        let oauth2 = resolve_request_vars(&coll.requests[2], &vars).expect("Test: Resolve");
+        let Some(Auth::OAuth2(oauth2)) = oauth2.auth else {
+            panic!("Test: oauth2 auth expected");
+        };
+        assert_eq!(oauth2.client_secret, "hunter2");
+        assert_eq!(oauth2.scopes, vec!["read", "abc123"]);
+        assert_eq!(
+            coll.requests[2].missing_variables(&HashMap::new()),
+            vec!["password", "token"]
+        );
+    }
+
+    #[test]
+    fn test_request_expect_block_resolves_vars() {
+        let yaml = r#"
+variables:
+  name: Alice
+requests:
+  - name: get
+    method: GET
+    url: https://example.com/user
+    expect:
+      status: 200
+      json:
+        user.name: ${name}
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).unwrap();
+        let vars = coll.variables_for_env(None).unwrap();
+        let resolved = resolve_request_vars(&coll.requests[0], &vars).unwrap();
+        let expect = resolved.expect.expect("Test: expect block");
+        assert_eq!(expect.status, Some(200));
+        assert_eq!(
+            expect.json.unwrap()["user.name"],
+            serde_yaml::Value::String("Alice".to_string())
+        );
+    }
+
+    #[test]
+    fn test_depends_on_names_known_requests_without_cycles() {
+        let parse =
+            |yaml: &str| serde_yaml::from_str::<Collection>(yaml).map_err(|e| e.to_string());
+        let request = |name: &str, deps: &str| {
+            format!("  - name: {name}\n    method: GET\n    url: /\n    depends_on: {deps}\n")
+        };
+        let coll = parse(&format!(
+            "requests:\n{}{}",
+            request("a", "[]"),
+            request("b", "[a]")
+        ))
+        .unwrap();
+        assert_eq!(coll.requests[1].depends_on, vec!["a"]);
+        assert!(parse(&format!("requests:\n{}", request("a", "[nope]")))
+            .unwrap_err()
+            .contains("request 'a' depends_on unknown request 'nope'"));
+        let cycle = format!(
+            "requests:\n{}{}{}",
+            request("a", "[c]"),
+            request("b", "[a]"),
+            request("c", "[b]")
+        );
+        assert!(parse(&cycle)
+            .unwrap_err()
+            .contains("depends_on cycle: a -> c -> b -> a"));
+        assert!(parse(&format!("requests:\n{}", request("a", "[a]")))
+            .unwrap_err()
+            .contains("depends_on cycle: a -> a"));
+    }
+
+    #[test]
+    fn test_request_delay() {
+        let parse = |delay: &str| {
+            serde_yaml::from_str::<Collection>(&format!(
+                "requests:\n  - name: a\n    method: GET\n    url: /\n    delay: {delay}\n"
+            ))
+            .map(|coll| coll.requests[0].delay)
+            .map_err(|e| e.to_string())
+        };
+        assert_eq!(parse("500ms"), Ok(Some(Duration::from_millis(500))));
+        assert_eq!(parse("0"), Ok(Some(Duration::ZERO)));
+        assert!(parse("500").unwrap_err().contains("invalid delay '500'"));
+        assert!(parse("[1s]").is_err());
+    }
+
+    #[test]
+    fn test_save_response_resolves_request_name() {
+        let coll: Collection = serde_yaml::from_str(
+            "requests:\n  - name: get-user\n    method: GET\n    url: https://example.com\n    save_response: ${dir}/${request_name}.json\n",
+        )
+        .unwrap();
+        let req = &coll.requests[0];
+        assert_eq!(req.missing_variables(&HashMap::new()), ["dir"]);
+        let vars = HashMap::from([("dir".to_string(), "out".to_string())]);
+        let resolved = resolve_request_vars(req, &vars).unwrap();
+        assert_eq!(resolved.save_response.as_deref(), Some("out/get-user.json"));
+
+        let mut vars = vars;
+        vars.insert(REQUEST_NAME_VAR.to_string(), "mine".to_string());
+        let resolved = resolve_request_vars(req, &vars).unwrap();
+        assert_eq!(resolved.save_response.as_deref(), Some("out/mine.json"));
+    }
+
+    #[test]
+    fn test_expect_status_shorthand() {
+        let yaml = r#"
+requests:
+  - name: missing
+    method: GET
+    url: https://example.com/users/0
+    expect_status: 404
+  - name: gone
+    method: DELETE
+    url: https://example.com/users/1
+    expect_status: 410
+    expect:
+      headers:
+        X-Reason: deleted
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(coll.requests[0].expect.as_ref().unwrap().status, Some(404));
+        let gone = coll.requests[1].expect.as_ref().unwrap();
+        assert_eq!(gone.status, Some(410));
+        assert_eq!(gone.headers.as_ref().unwrap()["X-Reason"], "deleted");
+
+        let conflicting = "requests:\n  - name: a\n    method: GET\n    url: /\n    expect_status: 404\n    expect:\n      status: 200\n";
+        let err = serde_yaml::from_str::<Collection>(conflicting).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("sets both expect_status: 404 and expect.status: 200"));
+        let invalid =
+            "requests:\n  - name: a\n    method: GET\n    url: /\n    expect_status: 42\n";
+        assert!(serde_yaml::from_str::<Collection>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_resolve_vars_defaults() {
+        let vars = HashMap::from([("host".to_string(), "api.example.com".to_string())]);
+        assert_eq!(
+            resolve_vars("${host:-localhost}/${port:-8080}/${empty:-}", &vars),
+            Ok("api.example.com/8080/".to_string())
+        );
+        assert_eq!(
+            resolve_vars("${env:WAVE_DEFAULT_UNSET_TEST:-fallback}", &vars),
+            Ok("fallback".to_string())
+        );
+        assert_eq!(
+            resolve_vars("${port}", &vars),
+            Err("Missing variable: port".to_string())
+        );
+
+        let req: Request = serde_yaml::from_str(
+            "name: r\nmethod: GET\nurl: https://${host:-x}/${id}/${page:-1}\nheaders:\n  Authorization: Bearer ${token}\n",
+        )
+        .expect("Test: parse request");
+        assert_eq!(
+            req.variable_names(),
+            HashSet::from(["host", "id", "page", "token"].map(String::from))
+        );
+        assert_eq!(req.missing_variables(&vars), vec!["id", "token"]);
+    }
+
+    #[test]
+    fn test_expand_variables_follows_nested_variables() {
+        let to_map = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let templates = to_map(&[
+            ("scheme", "https"),
+            ("host", "api.example.com"),
+            ("api_url", "${scheme}://${host}/v1"),
+            ("users_url", "${api_url}/users"),
+            ("auth", "Bearer ${token}"),
+            ("a", "${b}"),
+            ("b", "x${c}"),
+            ("c", "${a}"),
+            ("self", "${self}!"),
+            ("broken", "${nope}/v1"),
+        ]);
+        // A captured value is sent as it is, never expanded again
+        let literals = to_map(&[("token", "${env:HOME}")]);
+        let (vars, problems) = expand_variables(&templates, &literals);
+        assert_eq!(
+            resolve_vars("${users_url}?page=${page:-1}", &vars),
+            Ok("https://api.example.com/v1/users?page=1".to_string())
+        );
+        assert_eq!(
+            resolve_vars("${auth}", &vars),
+            Ok("Bearer ${env:HOME}".to_string())
+        );
+        for name in ["a", "b", "c"] {
+            assert_eq!(problems[name], "Variable cycle: a -> b -> c -> a");
+        }
+        assert_eq!(problems["self"], "Variable cycle: self -> self");
+        assert_eq!(
+            problems["broken"],
+            "Missing variable: nope (used by variable 'broken')"
+        );
+        assert_eq!(problems.len(), 5);
+
+        let req: Request =
+            serde_yaml::from_str("name: r\nmethod: GET\nurl: ${users_url}/${broken}/${id}\n")
+                .expect("Test: parse request");
+        assert_eq!(
+            req.undefined_variables(&templates, &literals),
+            ["id", "nope"]
+        );
+        assert_eq!(req.missing_variables(&vars), ["broken", "id"]);
+        let req: Request =
+            serde_yaml::from_str("name: r\nmethod: GET\nurl: ${a}\n").expect("Test: parse request");
+        assert!(req.undefined_variables(&templates, &literals).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_vars_dynamic_values() {
+        let resolved = resolve_vars("/items/${uuid}?n=${random_int:-5:-1}", &HashMap::new())
+            .expect("Test: resolve dynamic");
+        let (uuid, n) = resolved
+            .trim_start_matches("/items/")
+            .split_once("?n=")
+            .expect("Test: split");
+        assert_eq!(uuid.len(), 36);
+        assert!((-5..=-1).contains(&n.parse::<i64>().expect("Test: int")));
+
+        let defined = HashMap::from([("uuid".to_string(), "fixed".to_string())]);
+        assert_eq!(resolve_vars("${uuid}", &defined), Ok("fixed".to_string()));
+        assert!(resolve_vars("${random_int:3:1}", &HashMap::new()).is_err());
+
+        let req: Request = serde_yaml::from_str(
+            "name: r\nmethod: GET\nurl: https://x.io/${uuid}/${timestamp}/${id}\n",
+        )
+        .expect("Test: parse request");
+        assert_eq!(req.missing_variables(&HashMap::new()), vec!["id"]);
+    }
+
+    #[test]
+    fn test_dotenv_sits_between_variables_and_environments() {
+        let yaml = r#"
+variables:
+  base_url: http://localhost:8080
+  token: from-collection
+environments:
+  prod:
+    base_url: https://api.example.com
+requests: []
+"#;
+        let mut coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+        coll.dotenv = HashMap::from([
+            ("token".to_string(), "from-dotenv".to_string()),
+            ("base_url".to_string(), "http://dotenv".to_string()),
+            ("WAVE_DOTENV_ONLY_TEST".to_string(), "secret".to_string()),
+        ]);
+
+        let base = coll.variables_for_env(None).expect("Test: Base vars");
+        assert_eq!(base["token"], "from-dotenv");
+        assert_eq!(base["base_url"], "http://dotenv");
+        let prod = coll
+            .variables_for_env(Some("prod"))
+            .expect("Test: Prod vars");
+        assert_eq!(prod["base_url"], "https://api.example.com");
+
+        assert_eq!(
+            resolve_vars("${env:WAVE_DOTENV_ONLY_TEST}", &base),
+            Ok("secret".to_string())
+        );
+        env::set_var("WAVE_DOTENV_SHADOW_TEST", "from-process");
+        let shadowed = HashMap::from([(
+            "env:WAVE_DOTENV_SHADOW_TEST".to_string(),
+            "from-dotenv".to_string(),
+        )]);
+        assert_eq!(
+            resolve_vars("${env:WAVE_DOTENV_SHADOW_TEST}", &shadowed),
+            Ok("from-process".to_string())
+        );
+        env::remove_var("WAVE_DOTENV_SHADOW_TEST");
+
+        // `.env` values are taken literally, even where a variable refers to them
+        coll.dotenv = crate::dotenv::parse("PATTERN='kept ${literally}'\n").expect("Test: .env");
+        coll.variables = Some(HashMap::from([(
+            "search".to_string(),
+            "q=${PATTERN}".to_string(),
+        )]));
+        let vars = coll.variables_for_env(None).expect("Test: Literal vars");
+        assert_eq!(
+            resolve_vars("${PATTERN}", &vars),
+            Ok("kept ${literally}".to_string())
+        );
+        assert_eq!(vars["search"], "q=kept ${literally}");
+    }
+
+    #[test]
+    fn test_variables_for_env_overlays_environment() {
+        let yaml = r#"
+variables:
+  base_url: http://localhost:8080
+  user_id: "1"
+environments:
+  staging:
+    base_url: https://staging.example.com
+  prod:
+    base_url: https://api.example.com
+    token: prod-token
+requests: []
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: Parse collection");
+
+        let base = coll.variables_for_env(None).expect("Test: Base vars");
+        assert_eq!(base["base_url"], "http://localhost:8080");
+
+        let prod = coll
+            .variables_for_env(Some("prod"))
+            .expect("Test: Prod vars");
+        assert_eq!(prod["base_url"], "https://api.example.com");
+        assert_eq!(prod["user_id"], "1");
+        assert_eq!(prod["token"], "prod-token");
+
+        match coll.variables_for_env(Some("qa")).unwrap_err() {
+            CollectionError::EnvironmentNotFound {
+                environment,
+                available,
+            } => {
+                assert_eq!(environment, "qa");
+                assert_eq!(available, vec!["prod".to_string(), "staging".to_string()]);
+            }
+            other => panic!("Expected EnvironmentNotFound, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_init_collection_writes_loadable_starter() {
+        let dir = std::env::temp_dir().join(format!("wave_init_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let wave_dir = dir.join(".wave");
+
+        let path = init_collection(&wave_dir, "starter", false).expect("Test: init");
+        assert_eq!(path, wave_dir.join("starter.yaml"));
+
+        let coll = load_collection(path.to_str().expect("Test: Valid path"))
+            .expect("Test: Starter collection loads");
+        let file_vars = coll.variables.clone().expect("Test: Variables exist");
+        for req in &coll.requests {
+            resolve_request_vars(req, &file_vars).expect("Test: Starter vars resolve");
+        }
+        assert!(coll.requests.iter().any(|r| r.name == "get-example"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_init_collection_refuses_overwrite_without_force() {
+        let dir = std::env::temp_dir().join(format!("wave_init_force_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Test: create dir");
+        fs::write(dir.join("api.yml"), "requests: []").expect("Test: write existing");
+
+        let err = init_collection(&dir, "api", false).unwrap_err();
+        assert!(matches!(
+            err,
+            WaveError::Collection(CollectionError::AlreadyExists(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(dir.join("api.yml")).expect("Test: read"),
+            "requests: []"
+        );
+
+        init_collection(&dir, "api", true).expect("Test: forced init");
+        assert_eq!(
+            fs::read_to_string(dir.join("api.yaml")).expect("Test: read"),
+            STARTER_COLLECTION
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_save_target() {
+        assert_eq!(
+            "mycoll:create-user".parse::<SaveTarget>(),
+            Ok(SaveTarget {
+                collection: "mycoll".to_string(),
+                request: "create-user".to_string(),
+            })
+        );
+        for spec in ["mycoll", "mycoll:", ":req", "a/b:req", "mv:req", "help:req"] {
+            assert!(
+                spec.parse::<SaveTarget>().is_err(),
+                "{spec} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_save_request_creates_and_appends() {
+        let dir = std::env::temp_dir().join(format!("wave_save_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-trace", http::HeaderValue::from_static("1"));
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("application/json"),
+        );
+        let create = HttpRequest::new(
+            "https://api.example.com/users",
+            Method::POST,
+            Some(r#"{"name":"joe"}"#.to_string()),
+            headers,
+        );
+        let list = HttpRequest::new(
+            "https://api.example.com/users",
+            Method::GET,
+            None,
+            http::HeaderMap::new(),
+        );
+
+        let target: SaveTarget = "mycoll:create-user".parse().expect("Test: target");
+        let path = save_request(&dir, &target, &create).expect("Test: save new file");
+        assert_eq!(path, dir.join("mycoll.yaml"));
+        let target: SaveTarget = "mycoll:list-users".parse().expect("Test: target");
+        save_request(&dir, &target, &list).expect("Test: append");
+
+        let coll = load_collection(path.to_str().expect("Test: Valid path"))
+            .expect("Test: Saved collection loads");
+        let names: Vec<&str> = coll.requests.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["create-user", "list-users"]);
+        let saved = &coll.requests[0];
+        assert_eq!(saved.method, Method::POST);
+        assert_eq!(
+            saved.headers,
+            Some(HashMap::from([("x-trace".to_string(), "1".to_string())]))
+        );
+        assert!(matches!(&saved.body, Some(Body::Json(map)) if map["name"] == "joe"));
+
+        let err = save_request(&dir, &target, &list).unwrap_err();
+        assert!(matches!(
+            err,
+            WaveError::Collection(CollectionError::RequestExists { .. })
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_save_request_rejects_raw_bodies() {
+        let req = HttpRequest::new(
+            "https://x.io",
+            Method::POST,
+            Some("plain text".to_string()),
+            http::HeaderMap::new(),
+        );
+        assert!(matches!(
+            request_entry("raw", &req),
+            Err(WaveError::Collection(CollectionError::CannotSave(_)))
+        ));
+    }
+
+    #[test]
+    fn test_save_request_keeps_json_arrays() {
+        let req = HttpRequest::new(
+            "https://x.io",
+            Method::POST,
+            Some(r#"[1, "two"]"#.to_string()),
+            http::HeaderMap::new(),
+        );
+        let entry = request_entry("batch", &req).expect("Test: render entry");
+        let saved: Vec<Request> = serde_yaml::from_str(&entry).expect("Test: parse entry");
+        let Some(Body::Json(json)) = &saved[0].body else {
+            panic!("expected a JSON body");
+        };
+        assert_eq!(yaml_to_json(json), serde_json::json!([1, "two"]));
+    }
+
+    #[test]
+    fn test_save_request_joins_repeated_headers() {
+        let req = HttpRequest::builder("https://x.io", Method::GET)
+            .header("Accept", "application/json")
+            .append_header("Accept", "text/plain")
+            .build();
+        let entry = request_entry("both", &req).expect("Test: render entry");
+        let saved: Vec<Request> = serde_yaml::from_str(&entry).expect("Test: parse entry");
+        assert_eq!(
+            saved[0].headers.as_ref().unwrap()["accept"],
+            "application/json, text/plain"
+        );
+    }
+
+    #[test]
+    fn test_insert_request_entry_keeps_layout() {
+        let entry = "- name: new\n  method: GET\n  url: https://x.io\n";
+        let content = "# comment\nrequests:\n- name: a\n  method: GET\n  url: https://a.io\n\nvariables:\n  x: \"1\"\n";
+        assert_eq!(
+            insert_request_entry(content, entry),
+            "# comment\nrequests:\n- name: a\n  method: GET\n  url: https://a.io\n\n- name: new\n  method: GET\n  url: https://x.io\n\nvariables:\n  x: \"1\"\n"
+        );
+        assert_eq!(
+            insert_request_entry("requests: []\n", entry),
+            "requests:\n  - name: new\n    method: GET\n    url: https://x.io\n"
+        );
+        assert_eq!(
+            insert_request_entry("variables:\n  x: \"1\"\n", entry),
+            "variables:\n  x: \"1\"\n\nrequests:\n  - name: new\n    method: GET\n    url: https://x.io\n"
+        );
+    }
+
+    #[test]
+    fn test_collection_writes_are_atomic_with_backup() {
+        let dir = std::env::temp_dir().join(format!("wave_atomic_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Test: create dir");
+        let path = dir.join("api.yaml");
+        let first = "requests:\n  - name: a\n    method: GET\n    url: /a\n";
+        write_collection_file(&path, first).expect("Test: first write");
+        assert!(!dir.join("api.yaml.bak").exists());
+
+        write_collection_file(&path, "requests: []\n").expect("Test: second write");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "requests: []\n");
+        assert_eq!(fs::read_to_string(dir.join("api.yaml.bak")).unwrap(), first);
+
+        assert!(matches!(
+            write_collection_file(&path, "requests: {oops"),
+            Err(WaveError::Collection(CollectionError::InvalidYaml(_)))
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "requests: []\n");
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["api.yaml", "api.yaml.bak"]);
+        assert_eq!(collection_names(&dir).unwrap(), ["api"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_remove_request_entry_keeps_layout() {
+        let content = "requests:\n  - name: a\n    url: /a\n\n  # about b\n  - name: b\n    url: /b\n    # end of b\n\n  - name: c\n    url: /c\n\nvariables:\n  x: \"1\"\n";
+        assert_eq!(
+            remove_request_entry(content, 1),
+            "requests:\n  - name: a\n    url: /a\n\n  - name: c\n    url: /c\n\nvariables:\n  x: \"1\"\n"
+        );
+        assert_eq!(
+            remove_request_entry(content, 0),
+            "requests:\n  # about b\n  - name: b\n    url: /b\n    # end of b\n\n  - name: c\n    url: /c\n\nvariables:\n  x: \"1\"\n"
+        );
+        assert_eq!(
+            remove_request_entry("requests:\n- name: a\n  url: /a\n# the end\n", 0),
+            "requests: []\n# the end\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_request_entry_keeps_comments() {
+        let content =
+            "requests:\n  - method: GET\n    name: a # first\n  - name: 'b #2'   # second\n";
+        assert_eq!(
+            rename_request_entry(content, 0, "new").unwrap().unwrap(),
+            "requests:\n  - method: GET\n    name: new # first\n  - name: 'b #2'   # second\n"
+        );
+        assert_eq!(
+            rename_request_entry(content, 1, "yes").unwrap().unwrap(),
+            "requests:\n  - method: GET\n    name: a # first\n  - name: yes   # second\n"
+        );
+        assert_eq!(rename_request_entry(content, 2, "x").unwrap(), None);
+    }
+
+    #[test]
+    fn test_remove_and_rename_request_in_files() {
+        let dir = std::env::temp_dir().join(format!("wave_rm_mv_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("api")).expect("Test: create dir");
+        fs::write(
+            dir.join("api/a.yaml"),
+            "requests:\n  - name: one\n    method: GET\n    url: /1\n",
+        )
+        .expect("Test: write");
+        fs::write(
+            dir.join("api/b.yaml"),
+            "requests:\n  - name: two\n    method: GET\n    url: /2\n",
+        )
+        .expect("Test: write");
+
+        let path = rename_request(&dir, "api", "two", "three").expect("Test: rename");
+        assert_eq!(path, dir.join("api/b.yaml"));
+        assert!(matches!(
+            rename_request(&dir, "api", "three", "one"),
+            Err(WaveError::Collection(CollectionError::RequestExists { .. }))
+        ));
+        remove_request(&dir, "api", "one").expect("Test: remove");
+        assert_eq!(
+            fs::read_to_string(dir.join("api/a.yaml")).unwrap(),
+            "requests: []\n"
+        );
+        let coll = load_collection_dir(&dir.join("api")).expect("Test: load");
+        let names: Vec<&str> = coll.requests.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["three"]);
+        assert!(matches!(
+            remove_request(&dir, "api", "one"),
+            Err(WaveError::Collection(
+                CollectionError::RequestNotFound { .. }
+            ))
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rename_and_remove_follow_depends_on() {
+        let dir = std::env::temp_dir().join(format!("wave_depends_mv_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Test: create dir");
+        let api = "includes: [more.yaml]\nrequests:\n  - name: login\n    method: POST\n    url: /login\n  - name: me\n    method: GET\n    url: /me\n    depends_on: [login, setup] # first\n";
+        let more = "requests:\n  - name: setup\n    method: GET\n    url: /setup\n  - name: orders\n    method: GET\n    url: /orders\n    depends_on:\n      - login  # needs a token\n      - setup\n";
+        fs::write(dir.join("api.yaml"), api).expect("Test: write");
+        fs::write(dir.join("more.yaml"), more).expect("Test: write");
+
+        rename_request(&dir, "api", "login", "signin").expect("Test: rename");
+        assert_eq!(
+            fs::read_to_string(dir.join("api.yaml")).unwrap(),
+            api.replace("name: login", "name: signin")
+                .replace("[login, setup]", "[signin, setup]")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("more.yaml")).unwrap(),
+            more.replace("- login", "- signin")
+        );
+        let coll = load_collection(&dir.join("api.yaml").to_string_lossy()).expect("Test: load");
+        assert_eq!(coll.requests[1].depends_on, ["signin", "setup"]);
+        assert_eq!(coll.requests[3].depends_on, ["signin", "setup"]);
+
+        match remove_request(&dir, "api", "signin") {
+            Err(WaveError::Collection(CollectionError::RequestInUse { dependents, .. })) => {
+                assert_eq!(dependents, ["me", "orders"])
+            }
+            other => panic!("Expected RequestInUse, got {other:?}"),
+        }
+        remove_request(&dir, "api", "orders").expect("Test: remove");
+
+        // Edits are checked with the full loader before anything is written
+        let broken = more.replace("- login", "- nope");
+        assert!(matches!(
+            write_collection_files(&dir, "api", vec![(dir.join("more.yaml"), broken)]),
+            Err(WaveError::Collection(CollectionError::InvalidYaml(e)))
+                if e.contains("depends_on unknown request 'nope'")
+        ));
+        assert!(!fs::read_to_string(dir.join("more.yaml"))
+            .unwrap()
+            .contains("nope"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_collection_names_lists_yaml_files() {
+        let dir = std::env::temp_dir().join(format!("wave_list_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Test: create dir");
+        for file in ["b.yaml", "a.yml", "a.yaml", "notes.txt"] {
+            fs::write(dir.join(file), "requests: []").expect("Test: write");
+        }
+        assert_eq!(
+            collection_names(&dir).expect("Test: list"),
+            vec!["a".to_string(), "b".to_string()]
+        );
+        let _ = fs::remove_dir_all(&dir);
+        assert!(matches!(
+            collection_names(&dir),
+            Err(WaveError::Collection(CollectionError::DirectoryNotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn test_collection_names_lists_collection_directories() {
+        let dir = std::env::temp_dir().join(format!("wave_list_dirs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("api")).expect("Test: create dir");
+        fs::create_dir_all(dir.join("tokens")).expect("Test: create dir");
+        fs::write(dir.join("api/users.yaml"), "requests: []").expect("Test: write");
+        fs::write(dir.join("tokens/default.json"), "{}").expect("Test: write");
+        fs::write(dir.join("other.yaml"), "requests: []").expect("Test: write");
+        assert_eq!(
+            collection_names(&dir).expect("Test: list"),
+            vec!["api".to_string(), "other".to_string()]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn collection_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wave_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, content) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Test: create dir");
+            fs::write(path, content).expect("Test: write");
+        }
+        dir
+    }
+
+    #[test]
+    fn test_includes_are_merged() {
+        let dir = collection_files(
+            "includes",
+            &[
+                (
+                    "api.yaml",
+                    "includes: [parts/users.yaml]\nvariables:\n  host: main\nrequests:\n  - name: health\n    method: GET\n    url: /health\n",
+                ),
+                (
+                    "parts/users.yaml",
+                    "variables:\n  host: part\n  id: \"1\"\nbase_url: https://api.io\ntemplates:\n  get:\n    method: GET\nrequests:\n  - name: get-user\n    extends: get\n    url: /users/${id}\n",
+                ),
+            ],
+        );
+        let coll = load_collection(dir.join("api.yaml").to_str().unwrap()).expect("Test: load");
+        let names: Vec<&str> = coll.requests.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["health", "get-user"]);
+        let vars = coll.variables.as_ref().unwrap();
+        assert_eq!(vars["host"], "main");
+        assert_eq!(vars["id"], "1");
+        assert_eq!(coll.request("health").unwrap().url, "https://api.io/health");
+        assert_eq!(coll.request("get-user").unwrap().method, Method::GET);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let dir = collection_files(
+            "include_errors",
+            &[
+                (
+                    "dup.yaml",
+                    "includes: [other.yaml]\nrequests:\n  - name: a\n    method: GET\n    url: /a\n",
+                ),
+                (
+                    "other.yaml",
+                    "requests:\n  - name: a\n    method: POST\n    url: /a\n",
+                ),
+                ("cycle.yaml", "includes: [cycle2.yaml]\n"),
+                ("cycle2.yaml", "includes: [cycle.yaml]\n"),
+                ("missing.yaml", "includes: [nope.yaml]\n"),
+            ],
+        );
+        let load = |file: &str| {
+            *load_collection(dir.join(file).to_str().unwrap())
+                .unwrap_err()
+                .downcast::<CollectionError>()
+                .expect("Test: collection error")
+        };
+        match load("dup.yaml") {
+            CollectionError::NameCollision {
+                kind,
+                name,
+                first,
+                second,
+            } => {
+                assert_eq!((kind, name.as_str()), ("request", "a"));
+                assert!(first.ends_with("dup.yaml"));
+                assert!(second.ends_with("other.yaml"));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+        assert!(load("cycle.yaml").to_string().contains("include cycle"));
+        assert!(matches!(
+            load("missing.yaml"),
+            CollectionError::FileNotFound(path) if path.ends_with("nope.yaml")
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_strict_collections_reject_unknown_keys() {
+        let dir = collection_files(
+            "strict",
+            &[
+                (
+                    "loose.yaml",
+                    "requests:\n  - name: a\n    method: GET\n    url: /a\n    hedaers: {}\n",
+                ),
+                (
+                    "strict.yaml",
+                    "strict: true\nincludes: [part.yaml]\nvaribles: {}\nrequests:\n  - name: a\n    method: GET\n    url: /a\n    hedaers: {}\n",
+                ),
+                ("part.yaml", "requests:\n  - name: b\n    url: /b\n    mehtod: GET\n"),
+                (
+                    "includes_loose.yaml",
+                    "strict: true\nincludes: [part.yaml]\nrequests: []\n",
+                ),
+            ],
+        );
+        let load = |file: &str| load_collection(dir.join(file).to_str().unwrap());
+        assert!(load("loose.yaml").is_ok());
+        let strict = dir.join("strict.yaml").display().to_string();
+        assert_eq!(
+            load("strict.yaml").unwrap_err().to_string(),
+            format!(
+                "Invalid YAML in collection file: {strict}:3: unknown key 'varibles'\n{strict}:8: unknown key 'hedaers' in request 'a'"
+            )
+        );
+        let err = load("includes_loose.yaml").unwrap_err().to_string();
+        assert!(
+            err.ends_with("part.yaml:4: unknown key 'mehtod' in request 'b'"),
+            "{err}"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_schema_matches_collection_keys() {
+        let schema: serde_json::Value =
+            serde_json::from_str(COLLECTION_SCHEMA).expect("Test: schema is JSON");
+        let keys = |value: &serde_json::Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let sorted = |keys: &[&str]| {
+            let mut keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&schema["properties"]), sorted(COLLECTION_KEYS));
+        assert_eq!(
+            keys(&schema["definitions"]["request"]["properties"]),
+            sorted(REQUEST_KEYS)
+        );
+        assert_eq!(
+            keys(&schema["definitions"]["template"]["properties"]),
+            sorted(&["method", "headers", "auth"])
+        );
+    }
+
+    #[test]
+    fn test_load_collection_dir() {
+        let dir = collection_files(
+            "coll_dir",
+            &[
+                (
+                    "b.yaml",
+                    "requests:\n  - name: list-invoices\n    method: GET\n    url: /invoices\n",
+                ),
+                (
+                    "a.yml",
+                    "base_url: https://api.io\nrequests:\n  - name: list-users\n    method: GET\n    url: /users\n",
+                ),
+            ],
+        );
+        let coll = load_collection_dir(&dir).expect("Test: load");
+        let names: Vec<&str> = coll.requests.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["list-users", "list-invoices"]);
+        assert_eq!(
+            coll.request("list-invoices").unwrap().url,
+            "https://api.io/invoices"
+        );
+
+        fs::write(
+            dir.join("c.yaml"),
+            "requests:\n  - name: list-users\n    method: GET\n    url: /v2/users\n",
+        )
+        .expect("Test: write");
+        let err = load_collection_dir(&dir).unwrap_err().to_string();
+        assert!(
+            err.contains("request 'list-users' is defined in both"),
+            "{err}"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,533 @@
+//! Shell completion scripts for `wave completions`
+//!
+//! The scripts are generated from the clap command definition, so new
+//! subcommands and flags are picked up automatically. Collection and request
+//! names are completed at runtime: the scripts call the hidden
+//! `wave __names [COLLECTION]` command, which lists the collections in
+//! `.wave/`, or the requests of one collection.
+//!
+//! Each script is a small set of lookup tables (flags, flags taking a value,
+//! their possible values, nested subcommands, which positionals are
+//! collection or request names) plus a driver that walks the words typed so
+//! far to find the current subcommand.
+
+use clap::{Command, ValueEnum};
+
+/// Shells `wave completions` can generate a script for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Lookup table from a key to the words it completes to
+type Table = Vec<(String, Vec<String>)>;
+
+/// Completion data for every subcommand, keyed by subcommand path
+/// (`""` for the top level, `export curl` for nested subcommands)
+#[derive(Debug, Default)]
+struct Tables {
+    /// `PARENT:WORD` to the subcommand path the word selects
+    canonical: Table,
+    /// Every flag of a subcommand
+    flags: Table,
+    /// Flags that take a value
+    value_flags: Table,
+    /// `PATH:FLAG` to the values the flag accepts
+    flag_values: Table,
+    /// Names of nested subcommands
+    subcommands: Table,
+    /// Values accepted by the first positional argument
+    positional_values: Table,
+    /// `collection`, `request` or `requests`: which positionals are names
+    kinds: Table,
+}
+
+impl Tables {
+    /// Returns the tables with the function-name suffix each is looked up by
+    fn named(&self) -> [(&str, &Table); 7] {
+        [
+            ("canonical", &self.canonical),
+            ("flags", &self.flags),
+            ("value_flags", &self.value_flags),
+            ("flag_values", &self.flag_values),
+            ("subcommands", &self.subcommands),
+            ("positional_values", &self.positional_values),
+            ("kind", &self.kinds),
+        ]
+    }
+}
+
+/// Generates the completion script for `shell`
+///
+/// # Examples
+/// ```
+/// use clap::CommandFactory;
+/// use wave::completions::{generate, Shell};
+///
+/// let script = generate(Shell::Bash, &mut wave::Cli::command());
+/// assert!(script.contains("complete -F _wave wave"));
+/// ```
+pub fn generate(shell: Shell, cmd: &mut Command) -> String {
+    cmd.build();
+    let mut tables = Tables::default();
+    collect(cmd, "", &mut tables);
+    match shell {
+        Shell::Bash => format!("{}{POSIX_COMMON}{BASH}", posix_functions(&tables)),
+        Shell::Zsh => format!(
+            "#compdef wave\n\n{}{POSIX_COMMON}{ZSH}",
+            posix_functions(&tables)
+        ),
+        Shell::Fish => format!("{}{FISH}", fish_functions(&tables)),
+        Shell::Powershell => POWERSHELL.replace("    # TABLES\n", &powershell_tables(&tables)),
+    }
+}
+
+fn collect(cmd: &Command, path: &str, tables: &mut Tables) {
+    let mut flags = Vec::new();
+    let mut value_flags = Vec::new();
+    let mut positionals = Vec::new();
+    for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+        if arg.is_positional() {
+            positionals.push(arg);
+            continue;
+        }
+        let mut names: Vec<String> = arg
+            .get_long()
+            .map(|l| format!("--{l}"))
+            .into_iter()
+            .collect();
+        names.extend(arg.get_short().map(|s| format!("-{s}")));
+        if arg.get_action().takes_values() {
+            let values: Vec<String> = arg
+                .get_possible_values()
+                .iter()
+                .filter(|v| !v.is_hide_set())
+                .map(|v| v.get_name().to_string())
+                .collect();
+            for name in &names {
+                if !values.is_empty() {
+                    tables
+                        .flag_values
+                        .push((format!("{path}:{name}"), values.clone()));
+                }
+            }
+            value_flags.extend(names.iter().cloned());
+        }
+        flags.extend(names);
+    }
+
+    let mut subcommands = Vec::new();
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        let sub_path = if path.is_empty() {
+            sub.get_name().to_string()
+        } else {
+            format!("{path} {}", sub.get_name())
+        };
+        let mut names = vec![sub.get_name().to_string()];
+        names.extend(sub.get_visible_aliases().map(str::to_string));
+        let short_flags = sub
+            .get_short_flag()
+            .into_iter()
+            .chain(sub.get_visible_short_flag_aliases())
+            .map(|c| format!("-{c}"));
+        for flag in short_flags {
+            if !flags.contains(&flag) {
+                flags.push(flag.clone());
+            }
+            names.push(flag);
+        }
+        names.dedup();
+        for name in &names {
+            tables
+                .canonical
+                .push((format!("{path}:{name}"), vec![sub_path.clone()]));
+        }
+        subcommands.extend(names.into_iter().filter(|n| !n.starts_with('-')));
+        collect(sub, &sub_path, tables);
+    }
+
+    if let Some(first) = positionals.first() {
+        let values: Vec<String> = first
+            .get_possible_values()
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        if !values.is_empty() {
+            tables.positional_values.push((path.to_string(), values));
+        }
+        let ids: Vec<&str> = positionals.iter().map(|a| a.get_id().as_str()).collect();
+        let kind = match ids.as_slice() {
+            ["collection" | "target", "requests", ..] => Some("requests"),
+            ["collection" | "target", "request", ..] => Some("request"),
+            ["collection" | "target", ..] => Some("collection"),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            tables
+                .kinds
+                .push((path.to_string(), vec![kind.to_string()]));
+        }
+    }
+    for (table, words) in [
+        (&mut tables.flags, flags),
+        (&mut tables.value_flags, value_flags),
+        (&mut tables.subcommands, subcommands),
+    ] {
+        if !words.is_empty() {
+            table.push((path.to_string(), words));
+        }
+    }
+}
+
+/// Renders the lookup tables as bash/zsh functions taking the key as `$1`
+fn posix_functions(tables: &Tables) -> String {
+    let mut output = String::new();
+    for (name, table) in tables.named() {
+        output.push_str(&format!("_wave_{name}() {{\n    case \"$1\" in\n"));
+        for (key, words) in table {
+            output.push_str(&format!("        '{key}') echo '{}' ;;\n", words.join(" ")));
+        }
+        output.push_str("    esac\n}\n\n");
+    }
+    output
+}
+
+/// Renders the lookup tables as fish functions printing one word per line
+fn fish_functions(tables: &Tables) -> String {
+    let mut output = String::new();
+    for (name, table) in tables.named() {
+        output.push_str(&format!(
+            "function __wave_{name}\n    switch \"$argv[1]\"\n"
+        ));
+        for (key, words) in table {
+            let quoted: Vec<String> = words.iter().map(|w| format!("'{w}'")).collect();
+            output.push_str(&format!(
+                "        case '{key}'\n            printf '%s\\n' {}\n",
+                quoted.join(" ")
+            ));
+        }
+        output.push_str("    end\nend\n\n");
+    }
+    output
+}
+
+/// Renders the lookup tables as PowerShell hashtables
+fn powershell_tables(tables: &Tables) -> String {
+    let mut output = String::new();
+    for (name, table) in tables.named() {
+        output.push_str(&format!("    ${name} = @{{\n"));
+        for (key, words) in table {
+            let quoted: Vec<String> = words.iter().map(|w| format!("'{w}'")).collect();
+            output.push_str(&format!("        '{key}' = @({})\n", quoted.join(", ")));
+        }
+        output.push_str("    }\n");
+    }
+    output
+}
+
+/// Helpers shared by the bash and zsh drivers
+const POSIX_COMMON: &str = r#"_wave_has() {
+    [[ " $1 " == *" $2 "* ]]
+}
+
+# Collection names, or request names once the collection is known
+_wave_names() {
+    local cmd="$1" kind
+    kind=$(_wave_kind "$cmd")
+    shift
+    if [[ $# -eq 0 ]]; then
+        _wave_positional_values "$cmd"
+        [[ -n $kind ]] && wave __names 2>/dev/null
+    elif [[ $kind == requests || ($kind == request && $# -eq 1) ]]; then
+        wave __names "$1" 2>/dev/null
+    fi
+}
+"#;
+
+const BASH: &str = r#"
+_wave() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local cmd="" next word i
+    local -a args=()
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${COMP_WORDS[i]}"
+        next=$(_wave_canonical "$cmd:$word")
+        if [[ -n $next && ${#args[@]} -eq 0 ]]; then
+            cmd="$next"
+        elif [[ $word == -* ]]; then
+            _wave_has "$(_wave_value_flags "$cmd")" "$word" && ((i++))
+        else
+            args+=("$word")
+        fi
+    done
+
+    if [[ $cur == -* ]]; then
+        COMPREPLY=($(compgen -W "$(_wave_flags "$cmd")" -- "$cur"))
+    elif _wave_has "$(_wave_value_flags "$cmd")" "$prev"; then
+        local values
+        values=$(_wave_flag_values "$cmd:$prev")
+        if [[ -n $values ]]; then
+            COMPREPLY=($(compgen -W "$values" -- "$cur"))
+        else
+            COMPREPLY=($(compgen -f -- "$cur"))
+        fi
+    elif [[ ${#args[@]} -eq 0 ]]; then
+        COMPREPLY=($(compgen -W "$(_wave_subcommands "$cmd") $(_wave_names "$cmd")" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -W "$(_wave_names "$cmd" "${args[@]}")" -- "$cur"))
+    fi
+}
+
+complete -F _wave wave
+"#;
+
+const ZSH: &str = r#"
+_wave() {
+    local cur="${words[CURRENT]}" prev="${words[CURRENT-1]}"
+    local cmd="" next word i
+    local -a args candidates
+    for ((i = 2; i < CURRENT; i++)); do
+        word="${words[i]}"
+        next=$(_wave_canonical "$cmd:$word")
+        if [[ -n $next && ${#args[@]} -eq 0 ]]; then
+            cmd="$next"
+        elif [[ $word == -* ]]; then
+            _wave_has "$(_wave_value_flags "$cmd")" "$word" && ((i++))
+        else
+            args+=("$word")
+        fi
+    done
+
+    if [[ $cur == -* ]]; then
+        candidates=(${=$(_wave_flags "$cmd")})
+    elif _wave_has "$(_wave_value_flags "$cmd")" "$prev"; then
+        candidates=(${=$(_wave_flag_values "$cmd:$prev")})
+        if (( ${#candidates[@]} == 0 )); then
+            _files
+            return
+        fi
+    elif [[ ${#args[@]} -eq 0 ]]; then
+        candidates=(${=$(_wave_subcommands "$cmd")} ${=$(_wave_names "$cmd")})
+    else
+        candidates=(${=$(_wave_names "$cmd" "${args[@]}")})
+    fi
+    compadd -- "${candidates[@]}"
+}
+
+if [ "$funcstack[1]" = "_wave" ]; then
+    _wave "$@"
+else
+    compdef _wave wave
+fi
+"#;
+
+const FISH: &str = r#"function __wave_complete
+    set -l tokens (commandline -opc)
+    set -l cur (commandline -ct)
+    set -l cmd ''
+    set -l args
+    set -l skip 0
+    for word in $tokens[2..-1]
+        if test $skip = 1
+            set skip 0
+            continue
+        end
+        set -l next (__wave_canonical "$cmd:$word")
+        if test -n "$next"; and test (count $args) -eq 0
+            set cmd $next
+        else if string match -q -- '-*' $word
+            if contains -- $word (__wave_value_flags $cmd)
+                set skip 1
+            end
+        else
+            set -a args $word
+        end
+    end
+
+    set -l prev $tokens[-1]
+    if string match -q -- '-*' $cur
+        __wave_flags $cmd
+    else if contains -- $prev (__wave_value_flags $cmd)
+        set -l values (__wave_flag_values "$cmd:$prev")
+        if test (count $values) -gt 0
+            printf '%s\n' $values
+        else
+            __fish_complete_path $cur
+        end
+    else
+        set -l kind (__wave_kind $cmd)
+        if test (count $args) -eq 0
+            __wave_subcommands $cmd
+            __wave_positional_values $cmd
+            test -n "$kind"; and wave __names 2>/dev/null
+        else if test "$kind" = requests; or begin; test "$kind" = request; and test (count $args) -eq 1; end
+            wave __names $args[1] 2>/dev/null
+        end
+    end
+end
+
+complete -c wave -f -a '(__wave_complete)'
+"#;
+
+const POWERSHELL: &str = r#"Register-ArgumentCompleter -Native -CommandName wave -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    # TABLES
+
+    $words = @($commandAst.CommandElements |
+        Where-Object { $_.Extent.EndOffset -lt $cursorPosition } |
+        ForEach-Object { $_.ToString() })
+    $cmd = ''
+    $positionals = @()
+    for ($i = 1; $i -lt $words.Count; $i++) {
+        $word = $words[$i]
+        $next = $canonical["${cmd}:$word"]
+        if ($next -and $positionals.Count -eq 0) {
+            $cmd = $next[0]
+        } elseif ($word.StartsWith('-')) {
+            if ($value_flags[$cmd] -ccontains $word) { $i++ }
+        } else {
+            $positionals += $word
+        }
+    }
+
+    $prev = $words[-1]
+    $nameKind = @($kind[$cmd])[0]
+    $candidates = if ($wordToComplete.StartsWith('-')) {
+        $flags[$cmd]
+    } elseif ($value_flags[$cmd] -ccontains $prev) {
+        $flag_values["${cmd}:$prev"]
+    } elseif ($positionals.Count -eq 0) {
+        @($subcommands[$cmd]) + @($positional_values[$cmd]) +
+            @(if ($nameKind) { wave __names 2>$null })
+    } elseif ($nameKind -eq 'requests' -or ($nameKind -eq 'request' -and $positionals.Count -eq 1)) {
+        wave __names $positionals[0] 2>$null
+    }
+
+    $candidates | Where-Object { $_ -and $_ -clike "$wordToComplete*" } | ForEach-Object {
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use clap::CommandFactory;
+
+    fn tables() -> Tables {
+        let mut cmd = Cli::command();
+        cmd.build();
+        let mut tables = Tables::default();
+        collect(&cmd, "", &mut tables);
+        tables
+    }
+
+    fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Vec<String>> {
+        table.iter().find(|(k, _)| k == key).map(|(_, words)| words)
+    }
+
+    #[test]
+    fn test_subcommand_names_and_aliases() {
+        let tables = tables();
+        let top = lookup(&tables.subcommands, "").unwrap();
+        assert!(top.contains(&"collection".to_string()) && top.contains(&"c".to_string()));
+        assert!(!top.iter().any(|name| name == "__names"));
+        for word in ["collection", "c", "-c"] {
+            assert_eq!(
+                lookup(&tables.canonical, &format!(":{word}")),
+                Some(&vec!["collection".to_string()])
+            );
+        }
+        assert_eq!(
+            lookup(&tables.canonical, "export:curl"),
+            Some(&vec!["export curl".to_string()])
+        );
+        assert!(lookup(&tables.flags, "")
+            .unwrap()
+            .contains(&"-c".to_string()));
+    }
+
+    #[test]
+    fn test_name_positionals() {
+        let tables = tables();
+        for (path, kind) in [
+            ("collection", "request"),
+            ("export curl", "request"),
+            ("snapshot", "request"),
+            ("bench", "request"),
+            ("run", "requests"),
+            ("list", "collection"),
+        ] {
+            assert_eq!(
+                lookup(&tables.kinds, path),
+                Some(&vec![kind.to_string()]),
+                "{path}"
+            );
+        }
+        assert_eq!(lookup(&tables.kinds, "get"), None);
+        assert_eq!(
+            lookup(&tables.positional_values, "completions"),
+            Some(
+                &vec!["bash", "zsh", "fish", "powershell"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            )
+        );
+    }
+
+    #[test]
+    fn test_flags_with_values() {
+        let tables = tables();
+        let value_flags = lookup(&tables.value_flags, "get").unwrap();
+        assert!(value_flags.contains(&"--timeout".to_string()));
+        assert!(value_flags.contains(&"-o".to_string()));
+        assert!(!value_flags.contains(&"--verbose".to_string()));
+        assert_eq!(
+            lookup(&tables.flag_values, "get:--format"),
+            Some(&vec!["pretty".to_string(), "json".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_generate_every_shell() {
+        for (shell, marker) in [
+            (Shell::Bash, "complete -F _wave wave"),
+            (Shell::Zsh, "compdef _wave wave"),
+            (Shell::Fish, "complete -c wave -f -a '(__wave_complete)'"),
+            (
+                Shell::Powershell,
+                "Register-ArgumentCompleter -Native -CommandName wave",
+            ),
+        ] {
+            let script = generate(shell, &mut Cli::command());
+            assert!(script.contains(marker), "{shell:?}");
+            assert!(script.contains("wave __names"), "{shell:?}");
+        }
+        let powershell = generate(Shell::Powershell, &mut Cli::command());
+        assert!(!powershell.contains("# TABLES"));
+        assert!(powershell.contains("        ':-c' = @('collection')\n"));
+    }
+
+    #[test]
+    fn test_bash_completes_collection_names_next_to_subcommands() {
+        // `wave` is stubbed with a function listing two collections
+        let script = format!(
+            "{}\nwave() {{ printf 'api\\nmvp\\n'; }}\nCOMP_WORDS=(wave c m)\nCOMP_CWORD=2\n_wave\necho \"${{COMPREPLY[*]}}\"",
+            generate(Shell::Bash, &mut Cli::command())
+        );
+        let Ok(output) = std::process::Command::new("bash")
+            .args(["-c", &script])
+            .output()
+        else {
+            return;
+        };
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "mv mvp");
+    }
+}
@@ -0,0 +1,286 @@
+//! Conditions for `only_if:` and `skip_if:` on collection requests
+//!
+//! A condition compares two values with `==` or `!=`, or tests a single
+//! value, and joins such tests with `&&` and `||`, where `&&` binds tighter.
+//! Values are bare words or quoted strings and may use `${...}` variables.
+//! Variables are resolved after the condition is parsed, so a value that
+//! contains `==` or a quote cannot change what the condition means. A single
+//! value holds unless it is empty, `false` or `0`.
+//!
+//! ```yaml
+//! only_if: ${env} == "staging"
+//! skip_if: ${token:-} == "" || ${read_only:-false}
+//! ```
+
+use crate::collection::resolve_vars;
+use serde::de::{self, Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed `only_if:` or `skip_if:` condition
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use wave::condition::Condition;
+///
+/// let condition: Condition = r#"${env} == "staging" && ${region:-eu} != us"#.parse()?;
+/// let vars = HashMap::from([("env".to_string(), "staging".to_string())]);
+/// assert!(condition.resolve_vars(&vars)?.holds());
+/// let vars = HashMap::from([("env".to_string(), "prod".to_string())]);
+/// assert!(!condition.resolve_vars(&vars)?.holds());
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    /// The condition as written, for messages
+    source: String,
+    /// Tests joined by `||`, each a list of tests joined by `&&`
+    any: Vec<Vec<Test>>,
+}
+
+/// One test of a condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Test {
+    /// `left == right`, or `left != right` when `equal` is false
+    Compare {
+        left: String,
+        equal: bool,
+        right: String,
+    },
+    /// A single value, which holds unless it is empty, `false` or `0`
+    Value(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Value(String),
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl Condition {
+    /// Resolves `${var}` references in the values of the condition
+    pub fn resolve_vars(&self, vars: &HashMap<String, String>) -> Result<Condition, String> {
+        let resolve = |value: &String| resolve_vars(value, vars);
+        let any = self
+            .any
+            .iter()
+            .map(|all| {
+                all.iter()
+                    .map(|test| match test {
+                        Test::Compare { left, equal, right } => Ok(Test::Compare {
+                            left: resolve(left)?,
+                            equal: *equal,
+                            right: resolve(right)?,
+                        }),
+                        Test::Value(value) => Ok(Test::Value(resolve(value)?)),
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Condition {
+            source: self.source.clone(),
+            any,
+        })
+    }
+
+    /// Returns true if the condition holds for its values as they are
+    pub fn holds(&self) -> bool {
+        self.any.iter().any(|all| {
+            all.iter().all(|test| match test {
+                Test::Compare { left, equal, right } => (left == right) == *equal,
+                Test::Value(value) => {
+                    !(value.is_empty() || value == "0" || value.eq_ignore_ascii_case("false"))
+                }
+            })
+        })
+    }
+
+    /// Returns the values of the condition, which may hold `${...}` references
+    pub fn values(&self) -> Vec<&str> {
+        self.any
+            .iter()
+            .flatten()
+            .flat_map(|test| match test {
+                Test::Compare { left, right, .. } => vec![left.as_str(), right.as_str()],
+                Test::Value(value) => vec![value.as_str()],
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let invalid = |msg: &str| format!("invalid condition '{source}': {msg}");
+        let tokens = tokenize(source).map_err(|msg| invalid(&msg))?;
+        if tokens.is_empty() {
+            return Err(invalid("it is empty"));
+        }
+        let mut any = Vec::new();
+        for group in tokens.split(|t| *t == Token::Or) {
+            let mut all = Vec::new();
+            for part in group.split(|t| *t == Token::And) {
+                all.push(match part {
+                    [Token::Value(value)] => Test::Value(value.clone()),
+                    [Token::Value(left), op @ (Token::Equal | Token::NotEqual), Token::Value(right)] => {
+                        Test::Compare {
+                            left: left.clone(),
+                            equal: *op == Token::Equal,
+                            right: right.clone(),
+                        }
+                    }
+                    [] => return Err(invalid("'&&' and '||' need a test on both sides")),
+                    _ => {
+                        return Err(invalid(
+                            "expected a value, or two values compared with '==' or '!=', between '&&' and '||'",
+                        ))
+                    }
+                });
+            }
+            any.push(all);
+        }
+        Ok(Condition {
+            source: source.trim().to_string(),
+            any,
+        })
+    }
+}
+
+/// Splits a condition into values and operators
+///
+/// Quoted values keep their spaces; bare values end at whitespace, a quote
+/// or an operator, except inside `${...}`.
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    const OPERATORS: [(&str, Token); 4] = [
+        ("==", Token::Equal),
+        ("!=", Token::NotEqual),
+        ("&&", Token::And),
+        ("||", Token::Or),
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        if let Some((op, token)) = OPERATORS.iter().find(|(op, _)| rest.starts_with(op)) {
+            tokens.push(token.clone());
+            rest = &rest[op.len()..];
+        } else if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let end = rest[1..]
+                .find(quote)
+                .ok_or_else(|| format!("missing closing {quote}"))?;
+            tokens.push(Token::Value(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let mut end = 0;
+            while end < rest.len() {
+                let tail = &rest[end..];
+                if tail.starts_with("${") {
+                    end += tail.find('}').map_or(tail.len(), |close| close + 1);
+                    continue;
+                }
+                let c = tail.chars().next().unwrap_or_default();
+                if c.is_whitespace()
+                    || c == '"'
+                    || c == '\''
+                    || OPERATORS.iter().any(|(op, _)| tail.starts_with(op))
+                {
+                    break;
+                }
+                end += c.len_utf8();
+            }
+            tokens.push(Token::Value(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = match serde_yaml::Value::deserialize(deserializer)? {
+            serde_yaml::Value::String(s) => s,
+            serde_yaml::Value::Bool(b) => b.to_string(),
+            serde_yaml::Value::Number(n) => n.to_string(),
+            _ => return Err(de::Error::custom("a condition must be a string")),
+        };
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holds(condition: &str, vars: &[(&str, &str)]) -> Result<bool, String> {
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Ok(condition.parse::<Condition>()?.resolve_vars(&vars)?.holds())
+    }
+
+    #[test]
+    fn test_comparisons_and_values() {
+        let env = [("env", "staging"), ("empty", ""), ("off", "False")];
+        assert_eq!(holds(r#"${env} == "staging""#, &env), Ok(true));
+        assert_eq!(holds("${env}==staging", &env), Ok(true));
+        assert_eq!(holds("${env} != 'staging'", &env), Ok(false));
+        assert_eq!(holds("${env}", &env), Ok(true));
+        for falsy in ["${empty}", "${off}", "0", "${missing:-}"] {
+            assert_eq!(holds(falsy, &env), Ok(false), "{falsy}");
+        }
+        assert_eq!(holds(r#"${empty} == """#, &env), Ok(true));
+        assert_eq!(holds("${missing:-a b} == 'a b'", &env), Ok(true));
+        // Values are compared after parsing, so they cannot inject operators
+        let tricky = [("v", "x == x"), ("q", "\"")];
+        assert_eq!(holds("${v}", &tricky), Ok(true));
+        assert_eq!(holds("${v} == x", &tricky), Ok(false));
+        assert_eq!(holds(r#"${q} == '"'"#, &tricky), Ok(true));
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        let vars = [("a", "1"), ("b", "0")];
+        assert_eq!(holds("${a} && ${b} || ${a}", &vars), Ok(true));
+        assert_eq!(holds("${b} || ${a} && ${b}", &vars), Ok(false));
+        assert_eq!(holds("${a} == 1 && ${b} == 0", &vars), Ok(true));
+    }
+
+    #[test]
+    fn test_invalid_conditions() {
+        for (condition, message) in [
+            ("", "it is empty"),
+            ("a ==", "expected a value"),
+            ("a b", "expected a value"),
+            ("a && || b", "need a test on both sides"),
+            ("'open", "missing closing '"),
+        ] {
+            let err = condition.parse::<Condition>().unwrap_err();
+            assert!(err.contains(message), "{condition}: {err}");
+        }
+        assert!(holds("${nope} == x", &[]).is_err());
+    }
+
+    #[test]
+    fn test_values_and_display() {
+        let condition: Condition = " ${env} == \"staging\" || ${force:-} ".parse().unwrap();
+        assert_eq!(condition.values(), ["${env}", "staging", "${force:-}"]);
+        assert_eq!(condition.to_string(), "${env} == \"staging\" || ${force:-}");
+    }
+}
@@ -0,0 +1,526 @@
+//! User and project configuration
+//!
+//! Defaults are read from a user-level file (`$XDG_CONFIG_HOME/wave/config.toml`,
+//! falling back to `~/.config/wave/config.toml`) and then from the project's
+//! `.wave/config.toml`, whose settings win. Command-line flags override both.
+//!
+//! The project is found the way git finds a repository: the nearest `.wave`
+//! directory in the working directory or one of its parents. Its collections
+//! are used from anywhere inside the project, and a relative `collection_dir`
+//! in its `config.toml` is relative to the directory holding `.wave`. Outside
+//! of any project, collections come from `global_collection_dir` if the user
+//! configuration sets one.
+//!
+//! ```toml
+//! timeout = 30
+//! proxy = "http://proxy.local:3128"
+//! color = "auto"
+//! fail = true
+//! strict = true
+//! collection_dir = "api/.wave"
+//! global_collection_dir = "~/wave-collections"
+//! max_body = 262144
+//! pager = true
+//! request_id = "traceparent"
+//! default_scheme = "http"
+//!
+//! [headers]
+//! User-Agent = "wave"
+//! ```
+//!
+//! The files use the subset of TOML that these settings need: `key = value`
+//! pairs with string, integer and boolean values, `[table]` headers and `#`
+//! comments.
+
+use crate::error::{ConfigError, WaveError};
+use crate::http::RequestIdFormat;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Project configuration file, relative to the project root
+pub const PROJECT_CONFIG: &str = ".wave/config.toml";
+
+/// Directory that holds collections unless configured otherwise
+pub const DEFAULT_COLLECTION_DIR: &str = ".wave";
+
+/// When to use colored output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// Scheme added to URLs given without one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultScheme {
+    /// `https://`, except for localhost and loopback addresses, which get `http://`
+    #[default]
+    Https,
+    /// `http://`
+    Http,
+}
+
+/// Settings loaded from configuration files
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Default request timeout in seconds
+    pub timeout: Option<u64>,
+    /// Default proxy URL
+    pub proxy: Option<String>,
+    /// Colored output preference
+    pub color: Option<ColorChoice>,
+    /// Exit non-zero on 4xx/5xx responses, as with `--fail`
+    pub fail: Option<bool>,
+    /// Fail on arguments a request would not use, as with `--strict`
+    pub strict: Option<bool>,
+    /// Directory containing collection files
+    pub collection_dir: Option<PathBuf>,
+    /// Directory of collections used outside of any project; a leading `~/`
+    /// is the home directory
+    pub global_collection_dir: Option<PathBuf>,
+    /// Bytes of a response body to print before cutting it, as with `--max-body`
+    pub max_body: Option<usize>,
+    /// Show responses through `$PAGER` on a terminal, as with `--pager`
+    pub pager: Option<bool>,
+    /// Tag requests with a correlation ID, as with `--request-id`
+    pub request_id: Option<RequestIdFormat>,
+    /// Header the correlation ID is sent in, as with `--request-id-header`
+    pub request_id_header: Option<String>,
+    /// Scheme for URLs without one, as with `--default-scheme`
+    pub default_scheme: Option<DefaultScheme>,
+    /// Headers added to every request that does not set them itself
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Nearest `.wave` directory found by [`Config::load`], not read from a file
+    #[serde(skip)]
+    pub project_dir: Option<PathBuf>,
+}
+
+impl ColorChoice {
+    /// Decides whether to color output written to stdout
+    ///
+    /// `Auto` follows the <https://no-color.org> convention: a non-empty
+    /// `NO_COLOR` environment variable turns color off.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                use std::io::IsTerminal;
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                !no_color && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Loads the user configuration overlaid with the configuration of the
+    /// project the working directory is in
+    ///
+    /// Missing files are skipped.
+    ///
+    /// # Errors
+    /// Returns `ConfigError::InvalidConfig` if a file cannot be read or parsed
+    pub fn load() -> Result<Config, WaveError> {
+        let user = match user_config_path() {
+            Some(path) => Config::from_file(&path)?,
+            None => None,
+        };
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let project_dir = find_project_dir(&cwd);
+        let project = match project_dir.as_deref().and_then(Path::parent) {
+            Some(root) => Config::from_file(&root.join(PROJECT_CONFIG))?
+                .map(|config| config.relative_to(root)),
+            None => None,
+        };
+        let mut config = user.unwrap_or_default().merge(project.unwrap_or_default());
+        // Keeps paths short in messages when the project is below the working directory
+        config.project_dir = project_dir.map(|dir| match dir.strip_prefix(&cwd) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => dir,
+        });
+        tracing::debug!(project_dir = ?config.project_dir, "found project");
+        Ok(config)
+    }
+
+    /// Makes a relative `collection_dir` relative to `root` instead of the
+    /// working directory
+    fn relative_to(mut self, root: &Path) -> Config {
+        if let Some(dir) = self.collection_dir.as_mut().filter(|dir| dir.is_relative()) {
+            *dir = root.join(&*dir);
+        }
+        self
+    }
+
+    /// Reads a single configuration file, returning `None` if it does not exist
+    ///
+    /// # Errors
+    /// Returns `ConfigError::InvalidConfig` if the file cannot be read or parsed
+    pub fn from_file(path: &Path) -> Result<Option<Config>, WaveError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!(path = %path.display(), "no config file");
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(WaveError::Config(ConfigError::InvalidConfig(format!(
+                    "failed to read '{}': {e}",
+                    path.display()
+                ))))
+            }
+        };
+        tracing::debug!(path = %path.display(), "reading config file");
+        Config::from_toml(&content).map(Some).map_err(|e| {
+            WaveError::Config(ConfigError::InvalidConfig(format!(
+                "{}: {e}",
+                path.display()
+            )))
+        })
+    }
+
+    /// Parses configuration from TOML text
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::config::Config;
+    ///
+    /// let config = Config::parse("timeout = 10\n[headers]\nX-Team = \"api\"\n").unwrap();
+    /// assert_eq!(config.timeout, Some(10));
+    /// assert_eq!(config.headers["X-Team"], "api");
+    /// ```
+    pub fn parse(content: &str) -> Result<Config, WaveError> {
+        Config::from_toml(content).map_err(|e| WaveError::Config(ConfigError::InvalidConfig(e)))
+    }
+
+    fn from_toml(content: &str) -> Result<Config, String> {
+        serde_json::from_value(parse_toml(content)?).map_err(|e| e.to_string())
+    }
+
+    /// Overlays `other` on `self`: set values in `other` win, headers are combined
+    pub fn merge(mut self, other: Config) -> Config {
+        self.headers.extend(other.headers);
+        Config {
+            timeout: other.timeout.or(self.timeout),
+            proxy: other.proxy.or(self.proxy),
+            color: other.color.or(self.color),
+            fail: other.fail.or(self.fail),
+            strict: other.strict.or(self.strict),
+            collection_dir: other.collection_dir.or(self.collection_dir),
+            global_collection_dir: other.global_collection_dir.or(self.global_collection_dir),
+            max_body: other.max_body.or(self.max_body),
+            pager: other.pager.or(self.pager),
+            request_id: other.request_id.or(self.request_id),
+            request_id_header: other.request_id_header.or(self.request_id_header),
+            default_scheme: other.default_scheme.or(self.default_scheme),
+            headers: self.headers,
+            project_dir: other.project_dir.or(self.project_dir),
+        }
+    }
+
+    /// Directory to load collections from
+    ///
+    /// A configured `collection_dir` wins, then the project's `.wave`
+    /// directory, then `global_collection_dir`. With none of them, `.wave` in
+    /// the working directory, where `wave init` creates it.
+    pub fn collection_dir(&self) -> PathBuf {
+        self.collection_dir
+            .clone()
+            .or_else(|| self.project_dir.clone())
+            .or_else(|| self.global_collection_dir.as_deref().map(expand_home))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_COLLECTION_DIR))
+    }
+}
+
+/// Finds the nearest `.wave` directory in `start` or one of its parents
+///
+/// # Examples
+/// ```
+/// use wave::config::find_project_dir;
+///
+/// let root = std::env::temp_dir().join("wave_doc_find_project");
+/// std::fs::create_dir_all(root.join(".wave")).unwrap();
+/// std::fs::create_dir_all(root.join("src/deep")).unwrap();
+/// assert_eq!(find_project_dir(&root.join("src/deep")), Some(root.join(".wave")));
+/// ```
+pub fn find_project_dir(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(DEFAULT_COLLECTION_DIR))
+        .find(|dir| dir.is_dir())
+}
+
+/// Replaces a leading `~` with the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Parses the TOML subset used by configuration files into a JSON object
+fn parse_toml(content: &str) -> Result<Value, String> {
+    let mut root = Map::new();
+    let mut table: Option<String> = None;
+    for (index, raw) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = parse_key(name.trim()).map_err(|e| format!("line {line_no}: {e}"))?;
+            if root.contains_key(&name) {
+                return Err(format!("line {line_no}: duplicate table [{name}]"));
+            }
+            root.insert(name.clone(), Value::Object(Map::new()));
+            table = Some(name);
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {line_no}: expected 'key = value'"))?;
+        let key = parse_key(key.trim()).map_err(|e| format!("line {line_no}: {e}"))?;
+        let value = parse_value(value.trim()).map_err(|e| format!("line {line_no}: {e}"))?;
+        let target = match &table {
+            Some(name) => root
+                .get_mut(name)
+                .and_then(Value::as_object_mut)
+                .expect("table was inserted when its header was read"),
+            None => &mut root,
+        };
+        if target.insert(key.clone(), value).is_some() {
+            return Err(format!("line {line_no}: duplicate key '{key}'"));
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+/// Removes a trailing `#` comment that is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_key(key: &str) -> Result<String, String> {
+    if key.starts_with(['"', '\'']) {
+        return match parse_value(key)? {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("invalid key {key}")),
+        };
+    }
+    let bare = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if key.is_empty() || !key.chars().all(bare) {
+        return Err(format!("invalid key '{key}'"));
+    }
+    Ok(key.to_string())
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    if let Some(literal) = value.strip_prefix('\'') {
+        return literal
+            .strip_suffix('\'')
+            .filter(|s| !s.contains('\''))
+            .map(|s| Value::String(s.to_string()))
+            .ok_or_else(|| format!("unterminated string {value}"));
+    }
+    if let Some(basic) = value.strip_prefix('"') {
+        let mut output = String::new();
+        let mut chars = basic.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().is_empty() => return Ok(Value::String(output)),
+                '"' => return Err(format!("unexpected text after string {value}")),
+                '\\' => output.push(match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    other => return Err(format!("unsupported escape \\{}", other.unwrap_or(' '))),
+                }),
+                c => output.push(c),
+            }
+        }
+        return Err(format!("unterminated string {value}"));
+    }
+    match value {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => value
+            .replace('_', "")
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| format!("unsupported value '{value}'")),
+    }
+}
+
+/// Location of the user-level configuration file, if a home directory is known
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("wave").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_config() {
+        let config = Config::parse(
+            r#"
+timeout = 30
+proxy = "http://proxy.local:3128"
+color = "never"
+fail = true
+strict = true
+collection_dir = "api"
+max_body = 4096
+pager = true
+request_id = "traceparent"
+request_id_header = "X-Trace"
+default_scheme = "http"
+
+[headers]
+User-Agent = "wave-test"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.timeout, Some(30));
+        assert_eq!(config.proxy.as_deref(), Some("http://proxy.local:3128"));
+        assert_eq!(config.color, Some(ColorChoice::Never));
+        assert_eq!(config.fail, Some(true));
+        assert_eq!(config.strict, Some(true));
+        assert_eq!(config.collection_dir(), PathBuf::from("api"));
+        assert_eq!(config.max_body, Some(4096));
+        assert_eq!(config.pager, Some(true));
+        assert_eq!(config.request_id, Some(RequestIdFormat::Traceparent));
+        assert_eq!(config.request_id_header.as_deref(), Some("X-Trace"));
+        assert_eq!(config.default_scheme, Some(DefaultScheme::Http));
+        assert_eq!(config.headers["User-Agent"], "wave-test");
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_keys() {
+        let err = Config::parse("timout = 30\n").unwrap_err();
+        assert!(matches!(
+            err,
+            WaveError::Config(ConfigError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_project_over_user() {
+        let user = Config::parse(
+            "timeout = 30\nproxy = \"http://user\"\n[headers]\nA = \"1\"\nB = \"1\"\n",
+        )
+        .unwrap();
+        let project = Config::parse("timeout = 5\n[headers]\nB = \"2\"\n").unwrap();
+        let merged = user.merge(project);
+        assert_eq!(merged.timeout, Some(5));
+        assert_eq!(merged.proxy.as_deref(), Some("http://user"));
+        assert_eq!(merged.headers["A"], "1");
+        assert_eq!(merged.headers["B"], "2");
+        assert_eq!(merged.collection_dir(), PathBuf::from(".wave"));
+    }
+
+    #[test]
+    fn test_collection_dir_precedence() {
+        let mut config = Config::parse("global_collection_dir = \"/srv/wave\"\n").unwrap();
+        assert_eq!(config.collection_dir(), PathBuf::from("/srv/wave"));
+        config.project_dir = Some(PathBuf::from("/work/app/.wave"));
+        assert_eq!(config.collection_dir(), PathBuf::from("/work/app/.wave"));
+
+        let project = Config::parse("collection_dir = \"api/.wave\"\n")
+            .unwrap()
+            .relative_to(Path::new("/work/app"));
+        let config = config.merge(project);
+        assert_eq!(
+            config.collection_dir(),
+            PathBuf::from("/work/app/api/.wave")
+        );
+        assert_eq!(config.project_dir, Some(PathBuf::from("/work/app/.wave")));
+
+        let absolute = Config::parse("collection_dir = \"/abs\"\n")
+            .unwrap()
+            .relative_to(Path::new("/work/app"));
+        assert_eq!(absolute.collection_dir(), PathBuf::from("/abs"));
+    }
+
+    #[test]
+    fn test_find_project_dir_walks_up() {
+        let root = std::env::temp_dir().join(format!("wave_find_project_{}", std::process::id()));
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        assert_ne!(
+            find_project_dir(&root.join("a/b")),
+            Some(root.join(".wave"))
+        );
+        fs::create_dir_all(root.join(".wave")).unwrap();
+        assert_eq!(
+            find_project_dir(&root.join("a/b")),
+            Some(root.join(".wave"))
+        );
+        fs::create_dir_all(root.join("a/.wave")).unwrap();
+        assert_eq!(
+            find_project_dir(&root.join("a/b")),
+            Some(root.join("a/.wave"))
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_color_choice_always_and_never() {
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+
+    #[test]
+    fn test_parse_toml_syntax() {
+        let config = Config::parse(
+            "# defaults\ntimeout = 1_0 # seconds\nproxy = 'http://p#1'\n\n[headers]\n\"X Quoted\" = \"a \\\"b\\\" # c\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.timeout, Some(10));
+        assert_eq!(config.proxy.as_deref(), Some("http://p#1"));
+        assert_eq!(config.headers["X Quoted"], "a \"b\" # c");
+
+        for bad in ["timeout = ", "timeout 30", "proxy = \"open", "a = 1\na = 2"] {
+            assert!(Config::parse(bad).is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_from_file_missing_is_none() {
+        let path = Path::new("/nonexistent/wave/config.toml");
+        assert_eq!(Config::from_file(path).unwrap(), None);
+    }
+}
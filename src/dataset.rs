@@ -0,0 +1,247 @@
+//! Datasets for data-driven runs (`wave run --data FILE`)
+//!
+//! A dataset is a list of rows, each mapping column names to values that
+//! become variables of one pass over the run's requests. Two formats are
+//! read, picked by the file extension:
+//!
+//! ```text
+//! # users.csv: a header row, then one row per pass
+//! name,email
+//! Ann,ann@example.com
+//! "Lee, Bo","bo@example.com"
+//! ```
+//!
+//! ```text
+//! // users.json: an array of objects
+//! [{"name": "Ann", "age": 31}, {"name": "Bo", "tags": ["admin"]}]
+//! ```
+//!
+//! CSV fields may be quoted, with `""` for a quote inside a quoted field and
+//! line breaks kept. In JSON rows, strings are used as they are and other
+//! values as their JSON text, as with `capture:`.
+
+use crate::error::{CliError, WaveError};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// One row of a dataset: column name to value
+pub type Row = HashMap<String, String>;
+
+/// Reads a `.csv` or `.json` dataset
+///
+/// # Errors
+/// Returns `CliError::InvalidDataFile` if the file cannot be read, has
+/// another extension, is malformed or holds no rows.
+pub fn load(path: &Path) -> Result<Vec<Row>, WaveError> {
+    let invalid = |msg: String| {
+        WaveError::Cli(CliError::InvalidDataFile(format!(
+            "'{}': {msg}",
+            path.display()
+        )))
+    };
+    let content = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    let rows = match extension.as_deref() {
+        Some("csv") => parse_csv(&content),
+        Some("json") => parse_json(&content),
+        _ => Err("expected a .csv or .json file".to_string()),
+    }
+    .map_err(invalid)?;
+    if rows.is_empty() {
+        return Err(invalid("no rows".to_string()));
+    }
+    Ok(rows)
+}
+
+/// Parses CSV with a header row into rows
+///
+/// # Errors
+/// Returns a description with the line number for rows whose number of
+/// fields differs from the header, and for unterminated quotes.
+///
+/// # Examples
+/// ```
+/// use wave::dataset::parse_csv;
+///
+/// let rows = parse_csv("name,note\nAnn,\"says \"\"hi\"\", twice\"\n").unwrap();
+/// assert_eq!(rows[0]["name"], "Ann");
+/// assert_eq!(rows[0]["note"], "says \"hi\", twice");
+/// ```
+pub fn parse_csv(content: &str) -> Result<Vec<Row>, String> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut records = csv_records(content)?.into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header.into_iter().map(|h| h.trim().to_string()).collect();
+    if let Some(empty) = header.iter().position(String::is_empty) {
+        return Err(format!("line 1: column {} has no name", empty + 1));
+    }
+    records
+        .map(|(line, fields)| {
+            if fields.len() != header.len() {
+                return Err(format!(
+                    "line {line}: expected {} fields, found {}",
+                    header.len(),
+                    fields.len()
+                ));
+            }
+            Ok(header.iter().cloned().zip(fields).collect())
+        })
+        .collect()
+}
+
+/// Splits CSV text into records, each with the line it starts on; blank
+/// lines are skipped
+fn csv_records(content: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => fields.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                fields.push(std::mem::take(&mut field));
+                let record = std::mem::take(&mut fields);
+                if !(record.len() == 1 && record[0].is_empty()) {
+                    records.push((start, record));
+                }
+                line += 1;
+                start = line;
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("line {start}: unterminated quoted field"));
+    }
+    fields.push(field);
+    if !(fields.len() == 1 && fields[0].is_empty()) {
+        records.push((start, fields));
+    }
+    Ok(records)
+}
+
+/// Parses a JSON array of objects into rows
+///
+/// # Errors
+/// Returns a description for invalid JSON and for items that are not
+/// objects.
+///
+/// # Examples
+/// ```
+/// use wave::dataset::parse_json;
+///
+/// let rows = parse_json(r#"[{"name": "Ann", "age": 31, "admin": null}]"#).unwrap();
+/// assert_eq!(rows[0]["name"], "Ann");
+/// assert_eq!(rows[0]["age"], "31");
+/// assert_eq!(rows[0]["admin"], "null");
+/// ```
+pub fn parse_json(content: &str) -> Result<Vec<Row>, String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let Value::Array(items) = value else {
+        return Err("expected an array of objects".to_string());
+    };
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| match item {
+            Value::Object(fields) => Ok(fields
+                .into_iter()
+                .map(|(key, value)| {
+                    let text = match value {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (key, text)
+                })
+                .collect()),
+            _ => Err(format!("item {} is not an object", i + 1)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_quotes_and_line_breaks() {
+        let rows = parse_csv(
+            "\u{feff}name, email\r\nAnn,ann@example.com\r\n\r\n\"Lee, Bo\",\"two\nlines\"\n",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["email"], "ann@example.com");
+        assert_eq!(rows[1]["name"], "Lee, Bo");
+        assert_eq!(rows[1]["email"], "two\nlines");
+        assert!(parse_csv("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_csv_errors() {
+        assert_eq!(
+            parse_csv("a,b\n1,2\n\"x\ny\"\n").unwrap_err(),
+            "line 3: expected 2 fields, found 1"
+        );
+        assert_eq!(
+            parse_csv("a,b\n1,\"open\n").unwrap_err(),
+            "line 2: unterminated quoted field"
+        );
+        assert_eq!(
+            parse_csv("a,,c\n").unwrap_err(),
+            "line 1: column 2 has no name"
+        );
+    }
+
+    #[test]
+    fn test_parse_json_rows() {
+        let rows = parse_json(r#"[{"id": 1, "tags": ["a"]}, {"id": "two"}]"#).unwrap();
+        assert_eq!(rows[0]["id"], "1");
+        assert_eq!(rows[0]["tags"], r#"["a"]"#);
+        assert_eq!(rows[1]["id"], "two");
+        assert_eq!(parse_json("[1]").unwrap_err(), "item 1 is not an object");
+        assert!(parse_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_load_checks_extension_and_rows() {
+        let dir = std::env::temp_dir().join(format!("wave_dataset_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("users.csv");
+        fs::write(&csv, "name\nAnn\n").unwrap();
+        assert_eq!(load(&csv).unwrap()[0]["name"], "Ann");
+        let empty = dir.join("empty.json");
+        fs::write(&empty, "[]").unwrap();
+        let txt = dir.join("users.txt");
+        fs::write(&txt, "name\nAnn\n").unwrap();
+        for path in [empty, txt, dir.join("missing.csv")] {
+            assert!(matches!(
+                load(&path),
+                Err(WaveError::Cli(CliError::InvalidDataFile(_)))
+            ));
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
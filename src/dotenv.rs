@@ -0,0 +1,157 @@
+//! Variables from `.env` files
+//!
+//! Secrets such as API tokens can live in a `.env` file instead of being
+//! exported into the shell. Two files are read, when present: `.env` in the
+//! working directory, then `.env` in the collection directory (usually
+//! `.wave/.env`), whose values win.
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! API_TOKEN=secret123
+//! export BASE_URL=https://staging.example.com
+//! GREETING="hello\nworld"
+//! PATTERN='kept ${literally}'
+//! ```
+//!
+//! Values are available as collection variables (`${API_TOKEN}`) and as a
+//! fallback for environment references (`${env:API_TOKEN}`) when the process
+//! environment does not set them.
+
+use crate::error::{ConfigError, WaveError};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Name of the file read from the working and collection directories
+pub const DOTENV_FILE: &str = ".env";
+
+/// Parses the contents of a `.env` file
+///
+/// # Errors
+/// Returns a description with the line number for lines that are not
+/// `KEY=VALUE` pairs, comments or blank.
+///
+/// # Examples
+/// ```
+/// use wave::dotenv::parse;
+///
+/// let vars = parse("# tokens\nexport API_TOKEN=abc # dev only\nNAME=\"Ann Lee\"\n").unwrap();
+/// assert_eq!(vars["API_TOKEN"], "abc");
+/// assert_eq!(vars["NAME"], "Ann Lee");
+/// ```
+pub fn parse(content: &str) -> Result<HashMap<String, String>, String> {
+    let mut vars = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected KEY=VALUE", index + 1))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: invalid variable name '{key}'", index + 1));
+        }
+        vars.insert(key.to_string(), parse_value(value.trim()));
+    }
+    Ok(vars)
+}
+
+/// Unquotes a value; double quotes allow `\n`, `\t`, `\"` and `\\` escapes
+fn parse_value(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].to_string();
+    }
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut result = String::new();
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        }
+        return result;
+    }
+    // Unquoted values end at an inline ` #` comment
+    match value.find(" #") {
+        Some(end) => value[..end].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Reads a `.env` file, returning no variables if it does not exist
+///
+/// # Errors
+/// Returns `ConfigError::InvalidConfig` naming the file for malformed lines.
+pub fn load(path: &Path) -> Result<HashMap<String, String>, WaveError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => {
+            return Err(WaveError::Io(format!(
+                "Failed to read '{}': {e}",
+                path.display()
+            )))
+        }
+    };
+    parse(&content).map_err(|e| {
+        WaveError::Config(ConfigError::InvalidConfig(format!(
+            "{}: {e}",
+            path.display()
+        )))
+    })
+}
+
+/// Reads `./.env` and then `<collection_dir>/.env`, the latter winning
+pub fn load_project(collection_dir: &Path) -> Result<HashMap<String, String>, WaveError> {
+    let mut vars = load(Path::new(DOTENV_FILE))?;
+    let project = collection_dir.join(DOTENV_FILE);
+    if project != Path::new(DOTENV_FILE) {
+        vars.extend(load(&project)?);
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quotes_comments_and_export() {
+        let vars = parse(
+            "# comment\n\nexport TOKEN=abc123\nURL = https://x.io/#frag\nNOTE=a # trailing\nDQ=\"line\\nnext \\\"q\\\"\"\nSQ='${not_a_var} \\n'\nEMPTY=\n",
+        )
+        .unwrap();
+        assert_eq!(vars["TOKEN"], "abc123");
+        assert_eq!(vars["URL"], "https://x.io/#frag");
+        assert_eq!(vars["NOTE"], "a");
+        assert_eq!(vars["DQ"], "line\nnext \"q\"");
+        assert_eq!(vars["SQ"], "${not_a_var} \\n");
+        assert_eq!(vars["EMPTY"], "");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_lines() {
+        assert_eq!(
+            parse("A=1\njust words\n"),
+            Err("line 2: expected KEY=VALUE".to_string())
+        );
+        assert!(parse("MY KEY=1").is_err());
+        assert!(parse("=1").is_err());
+    }
+
+    #[test]
+    fn test_load_missing_file_is_empty() {
+        let vars = load(Path::new("/definitely/not/here/.env")).unwrap();
+        assert!(vars.is_empty());
+    }
+}
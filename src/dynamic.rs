@@ -0,0 +1,202 @@
+//! Built-in dynamic variables for collection templates
+//!
+//! These names resolve to a freshly generated value each time they appear,
+//! unless the collection, an environment or `--var` defines a variable with
+//! the same name:
+//!
+//! | Variable                  | Value                                        |
+//! |---------------------------|----------------------------------------------|
+//! | `${uuid}`                 | random UUID v4                               |
+//! | `${timestamp}`            | Unix time in seconds                         |
+//! | `${timestamp_iso}`        | UTC time as RFC 3339, `2024-05-01T12:00:00Z` |
+//! | `${random_int:MIN:MAX}`   | integer between MIN and MAX, inclusive       |
+//! | `${random_string:LEN}`    | LEN random letters and digits                |
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Returns true if `name` refers to a dynamic variable, valid or not
+pub fn is_dynamic(name: &str) -> bool {
+    matches!(name, "uuid" | "timestamp" | "timestamp_iso")
+        || name.starts_with("random_int:")
+        || name.starts_with("random_string:")
+}
+
+/// Generates the value of a dynamic variable
+///
+/// Returns `None` if `name` is not a dynamic variable, and an error for
+/// malformed arguments such as `${random_int:9:1}`.
+///
+/// # Examples
+/// ```
+/// use wave::dynamic::generate;
+///
+/// let n: i64 = generate("random_int:1:6").unwrap().unwrap().parse().unwrap();
+/// assert!((1..=6).contains(&n));
+/// assert_eq!(generate("random_string:8").unwrap().unwrap().len(), 8);
+/// assert!(generate("user_id").is_none());
+/// ```
+pub fn generate(name: &str) -> Option<Result<String, String>> {
+    let (kind, args) = name.split_once(':').unwrap_or((name, ""));
+    let value = match kind {
+        "uuid" if args.is_empty() => Ok(uuid_v4()),
+        "timestamp" if args.is_empty() => Ok(unix_now().to_string()),
+        "timestamp_iso" if args.is_empty() => Ok(format_rfc3339(unix_now())),
+        "random_int" => random_int(args),
+        "random_string" => random_string(args),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("Failed to generate random data: {e}"))?;
+    Ok(bytes)
+}
+
+pub(crate) fn uuid_v4() -> String {
+    // Fall back to the clock if the OS has no randomness to offer
+    let mut bytes = random_bytes::<16>().unwrap_or_else(|_| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        nanos.to_le_bytes()
+    });
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// `MIN:MAX`, both inclusive
+fn random_int(args: &str) -> Result<String, String> {
+    let usage = || format!("random_int expects MIN:MAX, got '{args}'");
+    let (min, max) = args.split_once(':').ok_or_else(usage)?;
+    let min: i64 = min.trim().parse().map_err(|_| usage())?;
+    let max: i64 = max.trim().parse().map_err(|_| usage())?;
+    if min > max {
+        return Err(format!("random_int range is empty: {min} > {max}"));
+    }
+    let span = (i128::from(max) - i128::from(min) + 1) as u128;
+    // Reject draws from the incomplete last block so every value is equally likely
+    let limit = (1u128 << 64) - (1u128 << 64) % span;
+    loop {
+        let draw = u128::from(u64::from_le_bytes(random_bytes::<8>()?));
+        if draw < limit {
+            return Ok((i128::from(min) + (draw % span) as i128).to_string());
+        }
+    }
+}
+
+/// `LEN` letters and digits
+fn random_string(args: &str) -> Result<String, String> {
+    let len: usize = args
+        .trim()
+        .parse()
+        .map_err(|_| format!("random_string expects a length, got '{args}'"))?;
+    let mut value = String::with_capacity(len);
+    while value.len() < len {
+        for byte in random_bytes::<32>()? {
+            // 248 is the largest multiple of 62 that fits in a byte
+            if byte < 248 && value.len() < len {
+                value.push(ALPHANUMERIC[usize::from(byte) % ALPHANUMERIC.len()] as char);
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Formats Unix seconds as an RFC 3339 UTC timestamp
+fn format_rfc3339(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+
+    // Civil date from days since the epoch (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_v4_format() {
+        let uuid = generate("uuid").unwrap().unwrap();
+        let parts: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(
+            parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+            vec![8, 4, 4, 4, 12]
+        );
+        assert!(parts[2].starts_with('4'));
+        assert!(matches!(
+            parts[3].chars().next(),
+            Some('8' | '9' | 'a' | 'b')
+        ));
+        assert_ne!(uuid, generate("uuid").unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_rfc3339(1_445_412_480), "2015-10-21T07:28:00Z");
+    }
+
+    #[test]
+    fn test_random_values_and_errors() {
+        for _ in 0..100 {
+            let n: i64 = generate("random_int:-3:3")
+                .unwrap()
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((-3..=3).contains(&n));
+        }
+        assert_eq!(generate("random_int:7:7").unwrap(), Ok("7".to_string()));
+        assert!(generate("random_int:9:1").unwrap().is_err());
+        assert!(generate("random_int:x").unwrap().is_err());
+
+        let s = generate("random_string:40").unwrap().unwrap();
+        assert_eq!(s.len(), 40);
+        assert!(s.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert!(generate("random_string:abc").unwrap().is_err());
+
+        let ts: u64 = generate("timestamp").unwrap().unwrap().parse().unwrap();
+        assert!(ts > 1_600_000_000);
+        assert!(generate("uuid:extra").is_none());
+        assert!(!is_dynamic("user_id"));
+        assert!(is_dynamic("random_int:1:2"));
+    }
+}
@@ -11,6 +11,7 @@
 use std::fmt;
 use std::io;
 
+use crate::grpc::GrpcError;
 use crate::http::HttpError;
 
 /// Central error type for the wave application
@@ -50,6 +51,26 @@ pub enum WaveError {
     Config(ConfigError),
     /// Runtime and system errors
     Runtime(String),
+    /// gRPC definition, transport and status errors
+    Grpc(GrpcError),
+}
+
+/// Process exit codes, so scripts and CI can branch on the kind of failure
+///
+/// Clap exits with [`USAGE`](exit_code::USAGE) for malformed arguments as well.
+pub mod exit_code {
+    /// Any error without a more specific code
+    pub const ERROR: i32 = 1;
+    /// Invalid command line: unknown flags, or a malformed URL, header or body param
+    pub const USAGE: i32 = 2;
+    /// No response: DNS, connection, TLS, timeout or redirect failures
+    pub const NETWORK: i32 = 3;
+    /// A 4xx response with `--fail`
+    pub const CLIENT_ERROR: i32 = 4;
+    /// A 5xx response with `--fail`
+    pub const SERVER_ERROR: i32 = 5;
+    /// An `expect:` block, `wave run` or `wave snapshot --check` failed
+    pub const ASSERTION: i32 = 6;
 }
 
 /// Collection and YAML related errors
@@ -68,6 +89,66 @@ pub enum CollectionError {
     VariableResolution(String),
     /// Collection directory (.wave/) not found
     DirectoryNotFound(String),
+    /// Collection file already exists and would be overwritten
+    AlreadyExists(String),
+    /// A request with this name is already in the collection
+    RequestExists { collection: String, request: String },
+    /// Other requests list the request under `depends_on:`
+    RequestInUse {
+        collection: String,
+        request: String,
+        dependents: Vec<String>,
+    },
+    /// No request in the collection has any of the requested tags
+    NoTaggedRequests {
+        collection: String,
+        tags: Vec<String>,
+    },
+    /// The request cannot be written as a collection entry
+    CannotSave(String),
+    /// Requested environment is not defined in the collection
+    EnvironmentNotFound {
+        environment: String,
+        available: Vec<String>,
+    },
+    /// A request's `expect:` assertions did not hold
+    ExpectationFailed {
+        request: String,
+        failed: usize,
+        total: usize,
+    },
+    /// One or more requests failed during `wave run`
+    RunFailed {
+        collection: String,
+        failed: usize,
+        total: usize,
+    },
+    /// `wave snapshot --check` found no saved snapshot for the request
+    SnapshotNotFound { collection: String, request: String },
+    /// The response no longer matches the saved snapshot
+    SnapshotMismatch { request: String, differences: usize },
+    /// `wave mock` found no request with a `mock:` block
+    NoMocks { collection: String },
+    /// The OAuth2 token endpoint did not return an access token
+    TokenRequest { token_url: String, message: String },
+    /// `wave validate` found problems in collection files
+    ValidationFailed { problems: usize },
+    /// Two files of a collection define a request or template of the same name
+    NameCollision {
+        kind: &'static str,
+        name: String,
+        first: String,
+        second: String,
+    },
+    /// A `pre:` or `post:` command of a request could not run or exited with an error
+    HookFailed {
+        request: String,
+        stage: &'static str,
+        command: String,
+        message: String,
+    },
+    /// The editor started by `wave edit` could not run or exited with an error
+    EditorFailed { editor: String, message: String },
 }
 
 /// CLI argument parsing and validation errors
@@ -84,10 +165,26 @@ pub enum CliError {
     InvalidHeaderFormat(String),
     /// Body parameter not in 'key=value' format
     InvalidBodyFormat(String),
+    /// Nested body field path is malformed or clashes with another field
+    InvalidBodyField(String),
     /// HTTP method is not supported
     UnsupportedMethod(String),
     /// Variable override (--var) is malformed
     InvalidVarOverride(String),
+    /// Collection name is empty or contains path separators
+    InvalidCollectionName(String),
+    /// Collection name is taken by a `wave collection` subcommand
+    ReservedCollectionName(String),
+    /// Arguments were given that cannot be used together
+    ConflictingArguments(String),
+    /// Session name is empty or contains path separators
+    InvalidSessionName(String),
+    /// The `--data` file of a run cannot be read or parsed
+    InvalidDataFile(String),
+    /// Arguments were given that the request would not use, with `--strict`
+    IgnoredArguments(String),
+    /// A JSON document given as an argument, e.g. with `--json`, is malformed
+    InvalidJsonArgument(String),
 }
 
 /// Parsing related errors
@@ -127,6 +224,7 @@ impl fmt::Display for WaveError {
             WaveError::Parse(err) => write!(f, "{err}"),
             WaveError::Config(err) => write!(f, "{err}"),
             WaveError::Runtime(msg) => write!(f, "Runtime error: {msg}"),
+            WaveError::Grpc(err) => write!(f, "{err}"),
         }
     }
 }
@@ -152,6 +250,145 @@ impl fmt::Display for CollectionError {
             CollectionError::DirectoryNotFound(path) => {
                 write!(f, "Collection directory not found: '{path}'. Try running 'wave init' to create a collection.")
             }
+            CollectionError::AlreadyExists(path) => {
+                write!(f, "Collection file already exists: '{path}'")
+            }
+            CollectionError::RequestExists {
+                collection,
+                request,
+            } => {
+                write!(
+                    f,
+                    "Request '{request}' already exists in collection '{collection}'"
+                )
+            }
+            CollectionError::RequestInUse {
+                collection,
+                request,
+                dependents,
+            } => {
+                write!(
+                    f,
+                    "Request '{request}' in collection '{collection}' is a depends_on of {}",
+                    dependents.join(", ")
+                )
+            }
+            CollectionError::NoTaggedRequests { collection, tags } => {
+                write!(
+                    f,
+                    "No requests in '{collection}' are tagged {}",
+                    tags.join(" or ")
+                )
+            }
+            CollectionError::CannotSave(msg) => {
+                write!(f, "Cannot save request to a collection: {msg}")
+            }
+            CollectionError::EnvironmentNotFound {
+                environment,
+                available,
+            } => {
+                if available.is_empty() {
+                    write!(f, "Environment '{environment}' not found. The collection does not define any environments.")
+                } else {
+                    write!(
+                        f,
+                        "Environment '{environment}' not found. Available environments: {}",
+                        available.join(", ")
+                    )
+                }
+            }
+            CollectionError::ExpectationFailed {
+                request,
+                failed,
+                total,
+            } => {
+                if *total == 0 {
+                    write!(f, "No response received for request '{request}', so its expectations could not be checked")
+                } else {
+                    write!(
+                        f,
+                        "{failed} of {total} expectations failed for request '{request}'"
+                    )
+                }
+            }
+            CollectionError::RunFailed {
+                collection,
+                failed,
+                total,
+            } => {
+                write!(
+                    f,
+                    "{failed} of {total} requests failed in collection '{collection}'"
+                )
+            }
+            CollectionError::SnapshotNotFound {
+                collection,
+                request,
+            } => {
+                write!(
+                    f,
+                    "No snapshot saved for request '{request}' in collection '{collection}'"
+                )
+            }
+            CollectionError::SnapshotMismatch {
+                request,
+                differences,
+            } => {
+                let noun = if *differences == 1 {
+                    "difference"
+                } else {
+                    "differences"
+                };
+                write!(
+                    f,
+                    "Response for request '{request}' does not match its snapshot ({differences} {noun})"
+                )
+            }
+            CollectionError::NoMocks { collection } => {
+                write!(
+                    f,
+                    "No request in collection '{collection}' has a mock response"
+                )
+            }
+            CollectionError::TokenRequest { token_url, message } => {
+                write!(
+                    f,
+                    "Failed to get an OAuth2 token from {token_url}: {message}"
+                )
+            }
+            CollectionError::ValidationFailed { problems } => {
+                let noun = if *problems == 1 {
+                    "problem"
+                } else {
+                    "problems"
+                };
+                write!(f, "Found {problems} {noun} in collection files")
+            }
+            CollectionError::NameCollision {
+                kind,
+                name,
+                first,
+                second,
+            } => {
+                write!(
+                    f,
+                    "The {kind} '{name}' is defined in both '{first}' and '{second}'"
+                )
+            }
+            CollectionError::HookFailed {
+                request,
+                stage,
+                command,
+                message,
+            } => {
+                write!(
+                    f,
+                    "{stage}: hook of request '{request}' failed: '{command}' {message}"
+                )
+            }
+            CollectionError::EditorFailed { editor, message } => {
+                write!(f, "Editor '{editor}' {message}")
+            }
         }
     }
 }
@@ -180,12 +417,45 @@ impl fmt::Display for CliError {
                     "Invalid body format '{body}'. Body data must be in 'key=value' format"
                 )
             }
+            CliError::InvalidBodyField(msg) => {
+                write!(f, "Invalid body field {msg}")
+            }
             CliError::UnsupportedMethod(method) => {
-                write!(f, "Unsupported HTTP method: '{method}'. Supported methods: GET, POST, PUT, DELETE, PATCH, HEAD, OPTIONS")
+                write!(f, "Invalid HTTP method: '{method}'. A method is a single token such as GET, PURGE or PROPFIND")
             }
             CliError::InvalidVarOverride(msg) => {
                 write!(f, "Invalid variable override: {msg}")
             }
+            CliError::InvalidCollectionName(name) => {
+                write!(
+                    f,
+                    "Invalid collection name '{name}'. Use a plain file name without path separators"
+                )
+            }
+            CliError::ReservedCollectionName(name) => {
+                write!(
+                    f,
+                    "Invalid collection name '{name}'. It is reserved for the 'wave collection {name}' subcommand"
+                )
+            }
+            CliError::ConflictingArguments(msg) => {
+                write!(f, "Conflicting arguments: {msg}")
+            }
+            CliError::InvalidSessionName(name) => {
+                write!(
+                    f,
+                    "Invalid session name '{name}'. Use a plain name without path separators"
+                )
+            }
+            CliError::InvalidDataFile(msg) => {
+                write!(f, "Invalid --data file {msg}")
+            }
+            CliError::IgnoredArguments(msg) => {
+                write!(f, "Unused arguments: {msg}")
+            }
+            CliError::InvalidJsonArgument(msg) => {
+                write!(f, "Invalid {msg}")
+            }
         }
     }
 }
@@ -235,6 +505,12 @@ impl From<HttpError> for WaveError {
     }
 }
 
+impl From<GrpcError> for WaveError {
+    fn from(err: GrpcError) -> Self {
+        WaveError::Grpc(err)
+    }
+}
+
 impl From<CollectionError> for WaveError {
     fn from(err: CollectionError) -> Self {
         WaveError::Collection(err)
@@ -278,6 +554,34 @@ impl From<serde_json::Error> for WaveError {
 }
 
 impl WaveError {
+    /// The process exit code for the error, see [`exit_code`]
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::error::{exit_code, invalid_url, WaveError};
+    /// use wave::http::HttpError;
+    ///
+    /// assert_eq!(invalid_url("example").exit_code(), exit_code::USAGE);
+    /// assert_eq!(WaveError::Http(HttpError::Status(503)).exit_code(), exit_code::SERVER_ERROR);
+    /// ```
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            WaveError::Cli(_) | WaveError::Parse(ParseError::Url(_) | ParseError::Header(_)) => {
+                exit_code::USAGE
+            }
+            WaveError::Http(HttpError::Status(400..=499)) => exit_code::CLIENT_ERROR,
+            WaveError::Http(HttpError::Status(500..=599)) => exit_code::SERVER_ERROR,
+            WaveError::Http(err) if err.failure().is_some() => exit_code::NETWORK,
+            WaveError::Grpc(GrpcError::Transport(_)) => exit_code::NETWORK,
+            WaveError::Collection(
+                CollectionError::ExpectationFailed { .. }
+                | CollectionError::RunFailed { .. }
+                | CollectionError::SnapshotMismatch { .. },
+            ) => exit_code::ASSERTION,
+            _ => exit_code::ERROR,
+        }
+    }
+
     /// Provides actionable suggestions for resolving errors
     ///
     /// Returns helpful guidance for common error conditions that users
@@ -303,20 +607,81 @@ impl WaveError {
                 Some("Make sure the file exists in the .wave directory")
             }
             WaveError::Collection(CollectionError::DirectoryNotFound(_)) => {
-                Some("No .wave directory found for collections")
+                Some("No .wave directory found for collections in this directory or its parents; run 'wave init' or set global_collection_dir in ~/.config/wave/config.toml")
             }
             WaveError::Collection(CollectionError::RequestNotFound { .. }) => {
                 Some("Check the collection YAML file to see all available requests")
             }
-            WaveError::Cli(CliError::InvalidUrl(_)) => {
-                Some("Example: wave get https://api.example.com/users")
+            WaveError::Collection(CollectionError::EnvironmentNotFound { .. }) => {
+                Some("Define environments in the collection's 'environments:' section")
+            }
+            WaveError::Collection(CollectionError::AlreadyExists(_)) => {
+                Some("Pass --force to overwrite it, or choose another name with --name")
+            }
+            WaveError::Collection(CollectionError::NoTaggedRequests { .. }) => {
+                Some("Add tags to requests with 'tags: [smoke]', or see them with 'wave list'")
             }
+            WaveError::Collection(CollectionError::RequestExists { .. }) => {
+                Some("Choose another name, e.g. create-user-2, or remove the existing request with 'wave collection rm'")
+            }
+            WaveError::Collection(CollectionError::RequestInUse { .. }) => {
+                Some("Remove it from their depends_on first, or rename it with 'wave collection mv'")
+            }
+            WaveError::Collection(CollectionError::SnapshotNotFound { .. }) => {
+                Some("Save one first by running the same command without --check")
+            }
+            WaveError::Collection(CollectionError::SnapshotMismatch { .. }) => Some(
+                "Ignore fields that change on every call with --ignore, or re-run without --check to accept the new response",
+            ),
+            WaveError::Collection(CollectionError::TokenRequest { .. }) => Some(
+                "Check the token_url, client_id, client_secret and scopes of the oauth2: block",
+            ),
+            WaveError::Collection(CollectionError::NoMocks { .. }) => Some(
+                "Add a mock: block with status, headers and body to the requests to serve",
+            ),
+            WaveError::Collection(CollectionError::ValidationFailed { .. }) => Some(
+                "Fix the problems listed above; pass --env and --var to check variables as a run would",
+            ),
+            WaveError::Collection(CollectionError::HookFailed { .. }) => Some(
+                "Run the command in a shell to debug it; hooks get the body on stdin and WAVE_* environment variables",
+            ),
+            WaveError::Collection(CollectionError::EditorFailed { .. }) => Some(
+                "Set $VISUAL or $EDITOR to the command that starts your editor and waits for it to close, e.g. 'code --wait'",
+            ),
+            WaveError::Collection(CollectionError::NameCollision { .. }) => Some(
+                "Rename one of them; names must be unique across a collection's includes and files",
+            ),
+            WaveError::Http(err) => err.suggestion(),
+            WaveError::Grpc(GrpcError::Proto(_) | GrpcError::UnknownMethod(_)) => Some(
+                "Check the --proto files and -I import paths, or list the server's methods with 'wave grpc <address>'",
+            ),
+            WaveError::Grpc(GrpcError::Reflection(_)) => {
+                Some("Pass the service's .proto files with --proto")
+            }
+            WaveError::Grpc(GrpcError::Transport(_)) => Some(
+                "Check the address; use https:// for servers that require TLS",
+            ),
+            WaveError::Cli(CliError::InvalidUrl(_)) | WaveError::Parse(ParseError::Url(_)) => {
+                Some("Example: wave get https://api.example.com/users or wave get localhost:8080/users")
+            }
+            WaveError::Parse(ParseError::Header(_)) => Some(
+                "Header names use letters, digits and '-', and values cannot contain line breaks. Example: X-Api-Key:abc123",
+            ),
             WaveError::Cli(CliError::InvalidHeaderFormat(_)) => {
                 Some("Example: Authorization:Bearer123 Content-Type:application/json")
             }
             WaveError::Cli(CliError::InvalidBodyFormat(_)) => {
                 Some("Example: name=john age=30 active=true")
             }
+            WaveError::Cli(CliError::InvalidBodyField(_)) => Some(
+                "Nested fields look like user.name=joe, user[name]=joe or tags[]=a; use a backslash for a literal dot or bracket, e.g. version\\.major=1",
+            ),
+            WaveError::Cli(CliError::InvalidDataFile(_)) => Some(
+                "Use a .csv file with a header row, or a .json file holding an array of objects",
+            ),
+            WaveError::Cli(CliError::IgnoredArguments(_)) => Some(
+                "Remove the arguments, or drop --strict (or strict = true in config.toml) to send the request with a warning",
+            ),
             WaveError::Cli(CliError::InvalidVarOverride(_)) => {
                 Some("Example: --var user_id=42 --var base_url=https://staging.example.com")
             }
@@ -369,6 +734,7 @@ pub fn runtime_error(msg: &str) -> WaveError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::RequestFailure;
 
     #[test]
     fn test_error_display() {
@@ -391,9 +757,32 @@ mod tests {
         assert!(err.suggestion().unwrap().contains(".wave directory"));
     }
 
+    #[test]
+    fn test_http_error_suggestions_follow_the_cause() {
+        let failure = || RequestFailure::new(::http::Method::GET, "https://example.com", "cause");
+        let suggestion = |err| WaveError::Http(err).suggestion().map(str::to_string);
+        assert!(suggestion(HttpError::Dns(failure()))
+            .unwrap()
+            .contains("host name"));
+        assert!(suggestion(HttpError::Connect(failure()))
+            .unwrap()
+            .contains("listening"));
+        assert!(suggestion(HttpError::Tls(failure()))
+            .unwrap()
+            .contains("--insecure"));
+        assert!(suggestion(HttpError::Redirect(failure()))
+            .unwrap()
+            .contains("loop"));
+        assert_eq!(suggestion(HttpError::Network(failure())), None);
+    }
+
     #[test]
     fn test_error_conversions() {
-        let http_err = HttpError::Network("connection failed".to_string());
+        let http_err = HttpError::Network(RequestFailure::new(
+            ::http::Method::GET,
+            "https://example.com",
+            "connection failed",
+        ));
         let wave_err: WaveError = http_err.into();
         matches!(wave_err, WaveError::Http(_));
     }
@@ -462,6 +851,18 @@ mod tests {
                 WaveError::Cli(CliError::InvalidBodyFormat("bad=body".to_string())),
                 true,
             ),
+            (
+                WaveError::Collection(CollectionError::AlreadyExists("api.yaml".to_string())),
+                true,
+            ),
+            (
+                WaveError::Http(HttpError::Timeout(RequestFailure::new(
+                    ::http::Method::GET,
+                    "https://example.com",
+                    "deadline elapsed",
+                ))),
+                true,
+            ),
             (WaveError::Runtime("runtime error".to_string()), false),
         ];
 
@@ -480,6 +881,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_environment_not_found_error_message() {
+        let err = CollectionError::EnvironmentNotFound {
+            environment: "qa".to_string(),
+            available: vec!["dev".to_string(), "prod".to_string()],
+        };
+        assert!(err.to_string().contains("Environment 'qa' not found"));
+        assert!(err.to_string().contains("dev, prod"));
+    }
+
     #[test]
     fn test_request_not_found_error_message() {
         let err = CollectionError::RequestNotFound {
@@ -491,4 +902,63 @@ mod tests {
         assert!(msg.contains("Check the collection YAML file"));
         assert!(!msg.contains("wave list")); // Ensure old message is gone
     }
+
+    #[test]
+    fn test_exit_codes_follow_the_error_class() {
+        let failure = || RequestFailure::new(::http::Method::GET, "https://example.com", "cause");
+        let cases = [
+            (
+                WaveError::Cli(CliError::MissingArguments("url".into())),
+                exit_code::USAGE,
+            ),
+            (
+                WaveError::Parse(ParseError::Header("bad".into())),
+                exit_code::USAGE,
+            ),
+            (
+                WaveError::Parse(ParseError::Json("bad".into())),
+                exit_code::ERROR,
+            ),
+            (
+                crate::load_json_document("{not json").unwrap_err(),
+                exit_code::USAGE,
+            ),
+            (
+                WaveError::Http(HttpError::Dns(failure())),
+                exit_code::NETWORK,
+            ),
+            (
+                WaveError::Http(HttpError::ConnectTimeout(failure())),
+                exit_code::NETWORK,
+            ),
+            (
+                WaveError::Grpc(GrpcError::Transport("reset".into())),
+                exit_code::NETWORK,
+            ),
+            (
+                WaveError::Http(HttpError::Status(404)),
+                exit_code::CLIENT_ERROR,
+            ),
+            (
+                WaveError::Http(HttpError::Status(502)),
+                exit_code::SERVER_ERROR,
+            ),
+            (
+                WaveError::Http(HttpError::Parse("bad".into())),
+                exit_code::ERROR,
+            ),
+            (
+                WaveError::Collection(CollectionError::RunFailed {
+                    collection: "api".into(),
+                    failed: 1,
+                    total: 2,
+                }),
+                exit_code::ASSERTION,
+            ),
+            (WaveError::Io("disk full".into()), exit_code::ERROR),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.exit_code(), code, "{err:?}");
+        }
+    }
 }
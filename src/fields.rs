@@ -0,0 +1,416 @@
+//! Nested JSON fields from `key=value` body params
+//!
+//! The key of a body param is a path into the JSON body, so nested objects
+//! and arrays can be built from the command line:
+//!
+//! | Key            | Sets                                          |
+//! |----------------|-----------------------------------------------|
+//! | `name`         | a top-level member                            |
+//! | `user.name`    | a member of a nested object                   |
+//! | `user[name]`   | the same, in bracket form                     |
+//! | `tags[]`       | a new element at the end of an array          |
+//! | `items[0]`     | an array element by index                     |
+//! | `[0].name`     | a member of an element of a top-level array   |
+//!
+//! Intermediate objects and arrays are created as needed, and setting a
+//! member that already exists replaces it, so a param can override one field
+//! deep inside a collection body. A backslash keeps `.`, `[` and `]` as part
+//! of a name, e.g. `version\.major=1`.
+//!
+//! The start of a value can say what type it is, instead of leaving it to
+//! type inference:
+//!
+//! | Param            | Value                                       |
+//! |------------------|---------------------------------------------|
+//! | `key==text`      | the string `text`, even if it looks numeric |
+//! | `key:=json`      | a literal JSON value, e.g. `ids:=[1,2]`     |
+//! | `key:=@path`     | the JSON document in a file                 |
+//! | `key=@path`      | the contents of a file, as a string         |
+//!
+//! Params are split into a key and the text after it, without the first
+//! `=`, so these markers reach [`marked_value`] as a leading `=`, `:=` or
+//! `@`. An extra `=` sends such text literally: `handle==@joe`.
+
+use crate::error::{ParseError, WaveError};
+use serde_json::{Map, Value};
+
+/// Resolves the type marker at the start of a param value
+///
+/// Returns `None` when the value has no marker, so the caller can decide
+/// how to type plain text.
+///
+/// # Errors
+/// Returns an error when a file cannot be read or `:=` is not valid JSON
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wave::fields::marked_value;
+///
+/// assert_eq!(marked_value("=01234").unwrap(), Some(json!("01234")));
+/// assert_eq!(marked_value(":=[1, 2]").unwrap(), Some(json!([1, 2])));
+/// assert_eq!(marked_value("01234").unwrap(), None);
+/// ```
+pub fn marked_value(value: &str) -> Result<Option<Value>, WaveError> {
+    if let Some(raw) = value.strip_prefix(":=") {
+        let (text, source) = match raw.strip_prefix('@') {
+            Some(path) => (read_file(path)?, format!("'{path}'")),
+            None => (raw.to_string(), format!("':={raw}'")),
+        };
+        return serde_json::from_str(&text).map(Some).map_err(|e| {
+            WaveError::Parse(ParseError::Json(format!("Invalid JSON in {source}: {e}")))
+        });
+    }
+    Ok(marked_text(value)?.map(Value::String))
+}
+
+/// Resolves the type marker of a value sent as text, e.g. in a form body
+///
+/// `==` and `=@` work as for JSON, and `:=` values are sent as written.
+///
+/// # Errors
+/// Returns an error when a file cannot be read
+pub fn marked_text(value: &str) -> Result<Option<String>, WaveError> {
+    if let Some(text) = value.strip_prefix('=') {
+        Ok(Some(text.to_string()))
+    } else if let Some(path) = value.strip_prefix('@') {
+        Ok(Some(read_file(path)?))
+    } else if let Some(raw) = value.strip_prefix(":=") {
+        Ok(Some(match raw.strip_prefix('@') {
+            Some(path) => read_file(path)?,
+            None => raw.to_string(),
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+fn read_file(path: &str) -> Result<String, WaveError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| WaveError::Io(format!("Failed to read body value from '{path}': {e}")))
+}
+
+/// One step of a field path
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    /// Object member; also an array index when the name is a number
+    Key(String),
+    /// Array element by index
+    Index(usize),
+    /// New element at the end of an array
+    Append,
+}
+
+/// Splits a param key into path segments
+fn parse_path(key: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut name = String::new();
+    let mut chars = key.chars();
+    // Whether `name` holds a segment still to be pushed; false after `]`
+    let mut pending = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if !pending {
+                    return Err(format!("'{key}': expected '.' or '[' after ']'"));
+                }
+                name.push(chars.next().unwrap_or('\\'));
+            }
+            '.' => {
+                push_key(&mut segments, &mut name, pending, key)?;
+                pending = true;
+            }
+            '[' => {
+                // A path may start with an index into a top-level array
+                if pending && !(segments.is_empty() && name.is_empty()) {
+                    push_key(&mut segments, &mut name, true, key)?;
+                }
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => inner.push(chars.next().unwrap_or('\\')),
+                        Some(c) => inner.push(c),
+                        None => return Err(format!("'{key}': missing ']'")),
+                    }
+                }
+                segments.push(if inner.is_empty() {
+                    Segment::Append
+                } else if let Ok(index) = inner.parse() {
+                    Segment::Index(index)
+                } else {
+                    Segment::Key(inner)
+                });
+                pending = false;
+            }
+            ']' => return Err(format!("'{key}': unexpected ']'")),
+            c => {
+                if !pending {
+                    return Err(format!("'{key}': expected '.' or '[' after ']'"));
+                }
+                name.push(c);
+            }
+        }
+    }
+    if pending {
+        push_key(&mut segments, &mut name, true, key)?;
+    }
+    Ok(segments)
+}
+
+fn push_key(
+    segments: &mut Vec<Segment>,
+    name: &mut String,
+    pending: bool,
+    key: &str,
+) -> Result<(), String> {
+    if !pending {
+        return Ok(());
+    }
+    if name.is_empty() {
+        return Err(format!("'{key}': empty field name"));
+    }
+    segments.push(Segment::Key(std::mem::take(name)));
+    Ok(())
+}
+
+/// Sets the field at `key` in `target`, creating objects and arrays on the way
+///
+/// # Errors
+/// Returns a message when the key is malformed or a value on the path is
+/// not an object or array, e.g. `user=joe user.name=joe`.
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wave::fields::insert;
+///
+/// let mut body = json!({});
+/// insert(&mut body, "user[name]", json!("joe")).unwrap();
+/// insert(&mut body, "user.tags[]", json!("a")).unwrap();
+/// insert(&mut body, "user.tags[]", json!("b")).unwrap();
+/// assert_eq!(body, json!({"user": {"name": "joe", "tags": ["a", "b"]}}));
+/// ```
+pub fn insert(target: &mut Value, key: &str, value: Value) -> Result<(), String> {
+    let segments = parse_path(key)?;
+    let mut current = target;
+    for (i, segment) in segments.iter().enumerate() {
+        let next = segments.get(i + 1);
+        let slot = step(current, segment, key)?;
+        match next {
+            None => {
+                *slot = value;
+                return Ok(());
+            }
+            Some(next) => {
+                if slot.is_null() {
+                    *slot = match next {
+                        Segment::Key(_) => Value::Object(Map::new()),
+                        Segment::Index(_) | Segment::Append => Value::Array(Vec::new()),
+                    };
+                }
+                current = slot;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the child of `value` that `segment` names, adding it when missing
+fn step<'a>(value: &'a mut Value, segment: &Segment, key: &str) -> Result<&'a mut Value, String> {
+    let kind = kind(value);
+    match (value, segment) {
+        (Value::Object(map), Segment::Key(name)) => {
+            Ok(map.entry(name.clone()).or_insert(Value::Null))
+        }
+        (Value::Array(items), Segment::Key(name)) => match name.parse() {
+            Ok(index) => Ok(element(items, index)),
+            Err(_) => Err(format!(
+                "'{key}': cannot set '{name}' on {kind}, expected an object"
+            )),
+        },
+        (Value::Array(items), Segment::Index(index)) => Ok(element(items, *index)),
+        (Value::Array(items), Segment::Append) => {
+            let index = items.len();
+            Ok(element(items, index))
+        }
+        (_, Segment::Key(name)) => Err(format!(
+            "'{key}': cannot set '{name}' on {kind}, expected an object"
+        )),
+        (_, Segment::Index(_) | Segment::Append) => Err(format!(
+            "'{key}': cannot add an element to {kind}, expected an array"
+        )),
+    }
+}
+
+/// Returns the element at `index`, padding the array with nulls up to it
+fn element(items: &mut Vec<Value>, index: usize) -> &mut Value {
+    if index >= items.len() {
+        items.resize(index + 1, Value::Null);
+    }
+    &mut items[index]
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn build(params: &[(&str, Value)]) -> Result<Value, String> {
+        let mut body = json!({});
+        for (key, value) in params {
+            insert(&mut body, key, value.clone())?;
+        }
+        Ok(body)
+    }
+
+    #[test]
+    fn test_marked_values() {
+        assert_eq!(marked_value("=01234").unwrap(), Some(json!("01234")));
+        assert_eq!(marked_value("==x").unwrap(), Some(json!("=x")));
+        assert_eq!(marked_value("=@joe").unwrap(), Some(json!("@joe")));
+        assert_eq!(
+            marked_value(r#":={"a": [true, null]}"#).unwrap(),
+            Some(json!({"a": [true, null]}))
+        );
+        assert_eq!(marked_value(":=\"01234\"").unwrap(), Some(json!("01234")));
+        assert_eq!(marked_value("plain").unwrap(), None);
+        assert!(matches!(
+            marked_value(":={oops"),
+            Err(WaveError::Parse(ParseError::Json(_)))
+        ));
+        assert!(matches!(
+            marked_value("@/definitely/not/here.txt"),
+            Err(WaveError::Io(_))
+        ));
+
+        let path = std::env::temp_dir().join(format!("wave_field_{}.txt", std::process::id()));
+        std::fs::write(&path, "line\n").expect("Test: write value");
+        let json_path =
+            std::env::temp_dir().join(format!("wave_field_{}.json", std::process::id()));
+        std::fs::write(&json_path, "[1, 2]").expect("Test: write value");
+        assert_eq!(
+            marked_value(&format!("@{}", path.display())).unwrap(),
+            Some(json!("line\n"))
+        );
+        assert_eq!(
+            marked_value(&format!(":=@{}", json_path.display())).unwrap(),
+            Some(json!([1, 2]))
+        );
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&json_path);
+        assert_eq!(marked_text(":=[1, 2]").unwrap().as_deref(), Some("[1, 2]"));
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("name"), Ok(vec![Segment::Key("name".into())]));
+        for key in ["user.name", "user[name]"] {
+            assert_eq!(
+                parse_path(key),
+                Ok(vec![
+                    Segment::Key("user".into()),
+                    Segment::Key("name".into())
+                ])
+            );
+        }
+        assert_eq!(
+            parse_path("a[0][].b"),
+            Ok(vec![
+                Segment::Key("a".into()),
+                Segment::Index(0),
+                Segment::Append,
+                Segment::Key("b".into())
+            ])
+        );
+        assert_eq!(
+            parse_path(r"version\.major"),
+            Ok(vec![Segment::Key("version.major".into())])
+        );
+        assert_eq!(
+            parse_path("[0].id"),
+            Ok(vec![Segment::Index(0), Segment::Key("id".into())])
+        );
+        for key in ["", ".a", "a.", "a..b", "a[", "a]", "a[b]c", "[0]x", ".[0]"] {
+            assert!(parse_path(key).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn test_insert_builds_nested_values() {
+        let body = build(&[
+            ("user[name]", json!("joe")),
+            ("user.address.city", json!("Oslo")),
+            ("tags[]", json!("a")),
+            ("tags[]", json!("b")),
+            ("matrix[1][0]", json!(5)),
+            ("items[].id", json!(1)),
+        ])
+        .unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "user": {"name": "joe", "address": {"city": "Oslo"}},
+                "tags": ["a", "b"],
+                "matrix": [null, [5]],
+                "items": [{"id": 1}]
+            })
+        );
+    }
+
+    #[test]
+    fn test_insert_overrides_existing_fields() {
+        let mut body = json!({"user": {"name": "ann", "id": 7}, "items": [{"id": 1}, {"id": 2}]});
+        insert(&mut body, "user.name", json!("joe")).unwrap();
+        insert(&mut body, "items.1.id", json!(3)).unwrap();
+        insert(&mut body, "items[]", json!({"id": 4})).unwrap();
+        insert(&mut body, "items[0].tags[]", json!("new")).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "user": {"name": "joe", "id": 7},
+                "items": [{"id": 1, "tags": ["new"]}, {"id": 3}, {"id": 4}]
+            })
+        );
+    }
+
+    #[test]
+    fn test_insert_into_top_level_array() {
+        let mut body = json!([{"id": 1}]);
+        insert(&mut body, "[0].id", json!(2)).unwrap();
+        insert(&mut body, "[]", json!({"id": 3})).unwrap();
+        assert_eq!(body, json!([{"id": 2}, {"id": 3}]));
+    }
+
+    #[test]
+    fn test_insert_rejects_conflicting_types() {
+        let err = build(&[("user", json!("joe")), ("user.name", json!("joe"))]).unwrap_err();
+        assert_eq!(
+            err,
+            "'user.name': cannot set 'name' on a string, expected an object"
+        );
+        let err = build(&[("[0]", json!(1))]).unwrap_err();
+        assert_eq!(
+            err,
+            "'[0]': cannot add an element to an object, expected an array"
+        );
+        let err = build(&[("tags", json!({})), ("tags[]", json!("a"))]).unwrap_err();
+        assert_eq!(
+            err,
+            "'tags[]': cannot add an element to an object, expected an array"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! Unary and server-streaming gRPC calls over HTTP/2
+
+use super::error::GrpcError;
+use crate::http::TlsOptions;
+use ::http::{HeaderMap, HeaderValue, Request};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+/// Where to connect: `host:port` or an `http://`/`https://` URL
+///
+/// Plain `host:port` and `http://` use HTTP/2 without TLS (h2c), as local
+/// gRPC servers usually do; `https://` uses TLS. The port defaults to 443
+/// with TLS and 80 without.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Target {
+    /// Parses a server address
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::grpc::Target;
+    ///
+    /// let target = Target::parse("localhost:50051").unwrap();
+    /// assert_eq!((target.tls, target.host.as_str(), target.port), (false, "localhost", 50051));
+    /// let target = Target::parse("https://api.example.com").unwrap();
+    /// assert_eq!((target.tls, target.port), (true, 443));
+    /// assert!(Target::parse("localhost:port").is_err());
+    /// ```
+    pub fn parse(address: &str) -> Result<Target, GrpcError> {
+        let invalid = || GrpcError::Transport(format!("invalid server address '{address}'"));
+        let (tls, rest) = match address.split_once("://") {
+            Some(("https", rest)) => (true, rest),
+            Some(("http", rest)) => (false, rest),
+            Some(_) => return Err(invalid()),
+            None => (false, address),
+        };
+        let rest = rest.trim_end_matches('/');
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) if !rest.ends_with(']') => {
+                (host, port.parse::<u16>().map_err(|_| invalid())?)
+            }
+            _ => (rest, if tls { 443 } else { 80 }),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() || host.contains('/') {
+            return Err(invalid());
+        }
+        Ok(Target {
+            tls,
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    /// Returns the URL of a method path such as `/helloworld.Greeter/SayHello`
+    pub fn url(&self, path: &str) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{scheme}://{}{path}", self.authority())
+    }
+
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// The messages and metadata the server answered with
+#[derive(Debug, Default)]
+pub struct Reply {
+    pub headers: HeaderMap,
+    pub messages: Vec<Vec<u8>>,
+    pub trailers: HeaderMap,
+}
+
+/// An HTTP/2 connection to a gRPC server
+pub struct Channel {
+    send: h2::client::SendRequest<Bytes>,
+    target: Target,
+}
+
+impl Channel {
+    /// Connects to the server, negotiating HTTP/2 through ALPN when using TLS
+    pub async fn connect(target: &Target, tls: &TlsOptions) -> Result<Channel, GrpcError> {
+        let transport = |e: &dyn std::fmt::Display| {
+            GrpcError::Transport(format!("failed to connect to {}: {e}", target.authority()))
+        };
+        let tcp = TcpStream::connect((target.host.as_str(), target.port))
+            .await
+            .map_err(|e| transport(&e))?;
+        let send = if target.tls {
+            let connector = tls
+                .native_connector(&["h2"])
+                .map_err(|e| GrpcError::Transport(e.to_string()))?;
+            let stream = tokio_native_tls::TlsConnector::from(connector)
+                .connect(&target.host, tcp)
+                .await
+                .map_err(|e| transport(&e))?;
+            handshake(stream).await
+        } else {
+            handshake(tcp).await
+        }
+        .map_err(|e| transport(&e))?;
+        Ok(Channel {
+            send,
+            target: target.clone(),
+        })
+    }
+
+    /// Sends one request message to `path` (`/package.Service/Method`) and
+    /// reads every response message until the server ends the call
+    ///
+    /// # Errors
+    /// Returns `GrpcError::Status` when the call ends with a non-OK
+    /// `grpc-status`, and `GrpcError::Transport` for HTTP/2 failures.
+    pub async fn call(
+        &mut self,
+        path: &str,
+        metadata: &HeaderMap,
+        message: &[u8],
+    ) -> Result<Reply, GrpcError> {
+        let transport = |e: h2::Error| GrpcError::Transport(e.to_string());
+        let mut request = Request::post(self.target.url(path))
+            .body(())
+            .map_err(|e| GrpcError::Transport(e.to_string()))?;
+        let headers = request.headers_mut();
+        headers.extend(metadata.clone());
+        headers.insert("content-type", HeaderValue::from_static("application/grpc"));
+        headers.insert("te", HeaderValue::from_static("trailers"));
+        headers.insert(
+            "user-agent",
+            HeaderValue::from_static(concat!("wave/", env!("CARGO_PKG_VERSION"))),
+        );
+
+        let mut send = self.send.clone().ready().await.map_err(transport)?;
+        let (response, mut stream) = send.send_request(request, false).map_err(transport)?;
+        stream
+            .send_data(Bytes::from(frame(message)), true)
+            .map_err(transport)?;
+        let response = response.await.map_err(transport)?;
+        let (parts, mut body) = response.into_parts();
+        if parts.status != ::http::StatusCode::OK {
+            return Err(GrpcError::Transport(format!(
+                "the server answered with HTTP status {}",
+                parts.status
+            )));
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(transport)?;
+            let _ = body.flow_control().release_capacity(chunk.len());
+            data.extend_from_slice(&chunk);
+        }
+        let trailers = body
+            .trailers()
+            .await
+            .map_err(transport)?
+            .unwrap_or_default();
+        check_status(&parts.headers, &trailers)?;
+        Ok(Reply {
+            headers: parts.headers,
+            messages: unframe(&data)?,
+            trailers,
+        })
+    }
+}
+
+async fn handshake<T>(io: T) -> Result<h2::client::SendRequest<Bytes>, h2::Error>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (send, connection) = h2::client::handshake(io).await?;
+    tokio::spawn(async move {
+        let _ = connection.await;
+    });
+    Ok(send)
+}
+
+/// Prefixes a message with the gRPC length header (uncompressed)
+pub(crate) fn frame(message: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(message.len() + 5);
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    framed
+}
+
+/// Splits a response body into its length-prefixed messages
+pub(crate) fn unframe(mut data: &[u8]) -> Result<Vec<Vec<u8>>, GrpcError> {
+    let mut messages = Vec::new();
+    while !data.is_empty() {
+        let header = data
+            .get(..5)
+            .ok_or_else(|| GrpcError::Transport("truncated gRPC message".to_string()))?;
+        if header[0] != 0 {
+            return Err(GrpcError::Transport(
+                "the server sent a compressed message, which is not supported".to_string(),
+            ));
+        }
+        let len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as usize;
+        let message = data
+            .get(5..5 + len)
+            .ok_or_else(|| GrpcError::Transport("truncated gRPC message".to_string()))?;
+        messages.push(message.to_vec());
+        data = &data[5 + len..];
+    }
+    Ok(messages)
+}
+
+/// Reads `grpc-status` from the trailers, or from the headers of a
+/// trailers-only response
+fn check_status(headers: &HeaderMap, trailers: &HeaderMap) -> Result<(), GrpcError> {
+    let source = if trailers.contains_key("grpc-status") {
+        trailers
+    } else {
+        headers
+    };
+    let Some(status) = source.get("grpc-status") else {
+        return Err(GrpcError::Transport(
+            "the call ended without a grpc-status".to_string(),
+        ));
+    };
+    let code: u32 = status
+        .to_str()
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .ok_or_else(|| GrpcError::Transport(format!("invalid grpc-status {status:?}")))?;
+    if code == 0 {
+        return Ok(());
+    }
+    let message = source
+        .get("grpc-message")
+        .and_then(|m| m.to_str().ok())
+        .map(|m| urlencoding::decode(m).map_or_else(|_| m.to_string(), |m| m.into_owned()))
+        .unwrap_or_default();
+    Err(GrpcError::Status { code, message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_parse() {
+        assert_eq!(
+            Target::parse("http://127.0.0.1:9000/").unwrap(),
+            Target {
+                tls: false,
+                host: "127.0.0.1".to_string(),
+                port: 9000
+            }
+        );
+        let ipv6 = Target::parse("https://[::1]:8443").unwrap();
+        assert_eq!(ipv6.host, "::1");
+        assert_eq!(ipv6.url("/a.B/C"), "https://[::1]:8443/a.B/C");
+        assert_eq!(Target::parse("https://[::1]").unwrap().port, 443);
+        assert!(Target::parse("ftp://example.com").is_err());
+        assert!(Target::parse("").is_err());
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let mut data = frame(b"abc");
+        assert_eq!(data, [0, 0, 0, 0, 3, b'a', b'b', b'c']);
+        data.extend(frame(b""));
+        assert_eq!(unframe(&data).unwrap(), [b"abc".to_vec(), Vec::new()]);
+        assert!(unframe(&data[..4]).is_err());
+        assert!(unframe(&[1, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_check_status() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        assert!(check_status(&HeaderMap::new(), &trailers).is_ok());
+        let mut headers = HeaderMap::new();
+        headers.insert("grpc-status", HeaderValue::from_static("5"));
+        headers.insert("grpc-message", HeaderValue::from_static("no%20such%20user"));
+        let err = check_status(&headers, &HeaderMap::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "gRPC call failed with NOT_FOUND: no such user"
+        );
+        assert!(check_status(&HeaderMap::new(), &HeaderMap::new()).is_err());
+    }
+
+    /// Serves `/test.Echo/Echo`, which answers with the request message
+    /// twice, and fails every other method with NOT_FOUND
+    async fn echo_server() -> Target {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut connection = h2::server::handshake(tcp).await.unwrap();
+            while let Some(Ok((request, mut respond))) = connection.accept().await {
+                let (parts, mut body) = request.into_parts();
+                let mut data = Vec::new();
+                while let Some(chunk) = body.data().await {
+                    data.extend_from_slice(&chunk.unwrap());
+                }
+                let mut response = ::http::Response::new(());
+                response
+                    .headers_mut()
+                    .insert("content-type", HeaderValue::from_static("application/grpc"));
+                if parts.uri.path() != "/test.Echo/Echo" {
+                    response
+                        .headers_mut()
+                        .insert("grpc-status", HeaderValue::from_static("5"));
+                    respond.send_response(response, true).unwrap();
+                    continue;
+                }
+                let mut stream = respond.send_response(response, false).unwrap();
+                data.extend_from_slice(&data.clone());
+                stream.send_data(Bytes::from(data), false).unwrap();
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                stream.send_trailers(trailers).unwrap();
+            }
+        });
+        Target {
+            tls: false,
+            host: "127.0.0.1".to_string(),
+            port,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_reads_streamed_messages_and_status() {
+        let target = echo_server().await;
+        let mut channel = Channel::connect(&target, &TlsOptions::default())
+            .await
+            .unwrap();
+        let reply = channel
+            .call("/test.Echo/Echo", &HeaderMap::new(), b"hi")
+            .await
+            .unwrap();
+        assert_eq!(reply.messages, [b"hi".to_vec(), b"hi".to_vec()]);
+        assert_eq!(reply.trailers["grpc-status"], "0");
+        let err = channel
+            .call("/test.Echo/Missing", &HeaderMap::new(), b"")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GrpcError::Status { code: 5, .. }));
+    }
+}
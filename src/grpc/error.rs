@@ -0,0 +1,81 @@
+use std::fmt;
+
+/// Errors from `wave grpc`
+///
+/// Covers reading service definitions, converting between JSON and protobuf
+/// messages, the HTTP/2 transport and non-OK statuses returned by the server.
+#[derive(Debug, Clone)]
+pub enum GrpcError {
+    /// A `.proto` file could not be read or parsed
+    Proto(String),
+    /// The service or method is not in the loaded definitions
+    UnknownMethod(String),
+    /// The JSON payload does not match the request message
+    Encode(String),
+    /// A response message does not match the response message type
+    Decode(String),
+    /// Connecting to the server or exchanging HTTP/2 frames failed
+    Transport(String),
+    /// The server does not support reflection or reported a reflection error
+    Reflection(String),
+    /// The call finished with a non-OK gRPC status
+    Status { code: u32, message: String },
+}
+
+impl GrpcError {
+    /// Returns the canonical name of a gRPC status code
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::grpc::GrpcError;
+    ///
+    /// assert_eq!(GrpcError::code_name(5), "NOT_FOUND");
+    /// assert_eq!(GrpcError::code_name(99), "UNKNOWN");
+    /// ```
+    pub fn code_name(code: u32) -> &'static str {
+        match code {
+            0 => "OK",
+            1 => "CANCELLED",
+            3 => "INVALID_ARGUMENT",
+            4 => "DEADLINE_EXCEEDED",
+            5 => "NOT_FOUND",
+            6 => "ALREADY_EXISTS",
+            7 => "PERMISSION_DENIED",
+            8 => "RESOURCE_EXHAUSTED",
+            9 => "FAILED_PRECONDITION",
+            10 => "ABORTED",
+            11 => "OUT_OF_RANGE",
+            12 => "UNIMPLEMENTED",
+            13 => "INTERNAL",
+            14 => "UNAVAILABLE",
+            15 => "DATA_LOSS",
+            16 => "UNAUTHENTICATED",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for GrpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrpcError::Proto(msg) => write!(f, "Invalid proto definition: {msg}"),
+            GrpcError::UnknownMethod(msg) => write!(f, "Unknown gRPC method: {msg}"),
+            GrpcError::Encode(msg) => write!(f, "Invalid request message: {msg}"),
+            GrpcError::Decode(msg) => write!(f, "Invalid response message: {msg}"),
+            GrpcError::Transport(msg) => write!(f, "gRPC transport error: {msg}"),
+            GrpcError::Reflection(msg) => write!(f, "Server reflection failed: {msg}"),
+            GrpcError::Status { code, message } if message.is_empty() => {
+                write!(f, "gRPC call failed with {}", GrpcError::code_name(*code))
+            }
+            GrpcError::Status { code, message } => {
+                write!(
+                    f,
+                    "gRPC call failed with {}: {message}",
+                    GrpcError::code_name(*code)
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GrpcError {}
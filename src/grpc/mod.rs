@@ -0,0 +1,18 @@
+//! gRPC calls for `wave grpc`
+//!
+//! Service definitions come from `.proto` files or from server reflection.
+//! Request messages are written as JSON, encoded with the protobuf wire
+//! format and sent over HTTP/2; responses are decoded back to JSON.
+
+pub mod client;
+pub mod codec;
+pub mod error;
+pub mod proto;
+pub mod reflection;
+mod wire;
+
+pub use client::{Channel, Reply, Target};
+pub use codec::{decode, encode};
+pub use error::GrpcError;
+pub use proto::{split_method, Descriptors, Method, Service};
+pub use reflection::fetch_descriptors;
@@ -0,0 +1,1261 @@
+//! Message and service definitions, read from `.proto` files or from the
+//! `FileDescriptorProto`s returned by server reflection
+
+use super::error::GrpcError;
+use super::wire;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Protobuf scalar value types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scalar {
+    Double,
+    Float,
+    Int64,
+    Uint64,
+    Int32,
+    Fixed64,
+    Fixed32,
+    Bool,
+    String,
+    Bytes,
+    Uint32,
+    Sfixed32,
+    Sfixed64,
+    Sint32,
+    Sint64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "double" => Scalar::Double,
+            "float" => Scalar::Float,
+            "int64" => Scalar::Int64,
+            "uint64" => Scalar::Uint64,
+            "int32" => Scalar::Int32,
+            "fixed64" => Scalar::Fixed64,
+            "fixed32" => Scalar::Fixed32,
+            "bool" => Scalar::Bool,
+            "string" => Scalar::String,
+            "bytes" => Scalar::Bytes,
+            "uint32" => Scalar::Uint32,
+            "sfixed32" => Scalar::Sfixed32,
+            "sfixed64" => Scalar::Sfixed64,
+            "sint32" => Scalar::Sint32,
+            "sint64" => Scalar::Sint64,
+            _ => return None,
+        })
+    }
+
+    /// Maps `FieldDescriptorProto.type` to a scalar; messages, enums and
+    /// groups are not scalars
+    fn from_descriptor_type(ty: u64) -> Option<Scalar> {
+        Some(match ty {
+            1 => Scalar::Double,
+            2 => Scalar::Float,
+            3 => Scalar::Int64,
+            4 => Scalar::Uint64,
+            5 => Scalar::Int32,
+            6 => Scalar::Fixed64,
+            7 => Scalar::Fixed32,
+            8 => Scalar::Bool,
+            9 => Scalar::String,
+            12 => Scalar::Bytes,
+            13 => Scalar::Uint32,
+            15 => Scalar::Sfixed32,
+            16 => Scalar::Sfixed64,
+            17 => Scalar::Sint32,
+            18 => Scalar::Sint64,
+            _ => return None,
+        })
+    }
+}
+
+/// The type of a message field; messages and enums are named by their fully
+/// qualified name without a leading dot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+    Scalar(Scalar),
+    Message(String),
+    Enum(String),
+    /// A type name not yet looked up, from a `.proto` file being parsed
+    Unresolved(String),
+}
+
+/// A message field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// Name used in JSON: `json_name`, or the lowerCamelCase field name
+    pub json_name: String,
+    pub number: u32,
+    pub ty: FieldType,
+    pub repeated: bool,
+}
+
+/// A message type
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Message {
+    /// Fully qualified name, e.g. `helloworld.HelloRequest`
+    pub name: String,
+    pub fields: Vec<Field>,
+    /// Set on the synthetic `key`/`value` messages behind `map<K, V>` fields
+    pub map_entry: bool,
+}
+
+/// An enum type and its values in declaration order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Enum {
+    pub name: String,
+    pub values: Vec<(String, i32)>,
+}
+
+/// An RPC of a service
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: String,
+    pub input: String,
+    pub output: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+}
+
+/// A service and its RPCs
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Service {
+    /// Fully qualified name, e.g. `helloworld.Greeter`
+    pub name: String,
+    pub methods: Vec<Method>,
+}
+
+/// Messages, enums and services from one or more proto files
+#[derive(Debug, Clone, Default)]
+pub struct Descriptors {
+    messages: HashMap<String, Message>,
+    enums: HashMap<String, Enum>,
+    services: Vec<Service>,
+    files: HashSet<String>,
+}
+
+impl Descriptors {
+    /// Parses `.proto` files and everything they import
+    ///
+    /// Imports are looked up in `import_paths`, then next to each file given
+    /// and in the current directory. `google/protobuf/empty.proto`,
+    /// `timestamp.proto`, `duration.proto` and `wrappers.proto` are built in
+    /// when they are not found on disk.
+    ///
+    /// # Errors
+    /// Returns `GrpcError::Proto` naming the file and line of a syntax error,
+    /// a missing import or an unknown type.
+    pub fn from_proto_files(
+        files: &[PathBuf],
+        import_paths: &[PathBuf],
+    ) -> Result<Descriptors, GrpcError> {
+        let mut search: Vec<PathBuf> = import_paths.to_vec();
+        for file in files {
+            search.push(file.parent().unwrap_or(Path::new("")).to_path_buf());
+        }
+        search.push(PathBuf::new());
+        let mut descriptors = Descriptors::default();
+        for file in files {
+            let source = fs::read_to_string(file).map_err(|e| {
+                GrpcError::Proto(format!("failed to read '{}': {e}", file.display()))
+            })?;
+            let name = file.display().to_string();
+            descriptors.add_proto_source(&name, &source, &search)?;
+        }
+        descriptors.resolve()?;
+        Ok(descriptors)
+    }
+
+    /// Parses one `.proto` source, loading its imports from `search`
+    fn add_proto_source(
+        &mut self,
+        name: &str,
+        source: &str,
+        search: &[PathBuf],
+    ) -> Result<(), GrpcError> {
+        if !self.files.insert(name.to_string()) {
+            return Ok(());
+        }
+        let file = Parser::new(name, source)?.parse_file()?;
+        for import in &file.imports {
+            if self.files.contains(import) {
+                continue;
+            }
+            let found = search
+                .iter()
+                .map(|dir| dir.join(import))
+                .find(|path| path.is_file());
+            let source = match (found, builtin_proto(import)) {
+                (Some(path), _) => fs::read_to_string(&path).map_err(|e| {
+                    GrpcError::Proto(format!("failed to read '{}': {e}", path.display()))
+                })?,
+                (None, Some(source)) => source.to_string(),
+                (None, None) => {
+                    return Err(GrpcError::Proto(format!(
+                        "{name}: import '{import}' not found (add its directory with -I)"
+                    )))
+                }
+            };
+            self.add_proto_source(import, &source, search)?;
+        }
+        for message in file.messages {
+            self.messages.insert(message.name.clone(), message);
+        }
+        for e in file.enums {
+            self.enums.insert(e.name.clone(), e);
+        }
+        self.services.extend(file.services);
+        Ok(())
+    }
+
+    /// Adds a serialized `FileDescriptorProto`, as sent by server reflection
+    ///
+    /// Returns the file's name and the names of the files it depends on.
+    /// Types are looked up later with [`Descriptors::resolve`]; a file already
+    /// added is skipped.
+    pub(crate) fn add_file_descriptor(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<(String, Vec<String>), GrpcError> {
+        let invalid = |e: String| GrpcError::Reflection(format!("invalid file descriptor: {e}"));
+        let fields = wire::read_fields(bytes).map_err(invalid)?;
+        let name = wire::string_field(&fields, 1).unwrap_or_default();
+        let dependencies: Vec<String> = wire::len_fields(&fields, 3)
+            .into_iter()
+            .map(|dep| String::from_utf8_lossy(dep).into_owned())
+            .collect();
+        if !self.files.insert(name.clone()) {
+            return Ok((name, dependencies));
+        }
+        let package = wire::string_field(&fields, 2).unwrap_or_default();
+        for message in wire::len_fields(&fields, 4) {
+            self.add_descriptor_message(&package, message)
+                .map_err(invalid)?;
+        }
+        for e in wire::len_fields(&fields, 5) {
+            self.add_descriptor_enum(&package, e).map_err(invalid)?;
+        }
+        for service in wire::len_fields(&fields, 6) {
+            let fields = wire::read_fields(service).map_err(invalid)?;
+            let mut svc = Service {
+                name: qualify(
+                    &package,
+                    &wire::string_field(&fields, 1).unwrap_or_default(),
+                ),
+                methods: Vec::new(),
+            };
+            for method in wire::len_fields(&fields, 2) {
+                let fields = wire::read_fields(method).map_err(invalid)?;
+                // Fully qualified with a leading dot, as `resolve` expects
+                let type_name = |n| wire::string_field(&fields, n).unwrap_or_default();
+                svc.methods.push(Method {
+                    name: wire::string_field(&fields, 1).unwrap_or_default(),
+                    input: type_name(2),
+                    output: type_name(3),
+                    client_streaming: wire::varint_field(&fields, 5) == Some(1),
+                    server_streaming: wire::varint_field(&fields, 6) == Some(1),
+                });
+            }
+            self.services.push(svc);
+        }
+        Ok((name, dependencies))
+    }
+
+    fn add_descriptor_message(&mut self, scope: &str, bytes: &[u8]) -> Result<(), String> {
+        let fields = wire::read_fields(bytes)?;
+        let name = qualify(scope, &wire::string_field(&fields, 1).unwrap_or_default());
+        let map_entry = match wire::len_fields(&fields, 7).last() {
+            Some(options) => wire::varint_field(&wire::read_fields(options)?, 7) == Some(1),
+            None => false,
+        };
+        let mut message = Message {
+            name: name.clone(),
+            fields: Vec::new(),
+            map_entry,
+        };
+        for field in wire::len_fields(&fields, 2) {
+            let f = wire::read_fields(field)?;
+            let field_name = wire::string_field(&f, 1).unwrap_or_default();
+            let type_name = wire::string_field(&f, 6)
+                .unwrap_or_default()
+                .trim_start_matches('.')
+                .to_string();
+            let ty = match wire::varint_field(&f, 5).unwrap_or(0) {
+                11 => FieldType::Message(type_name),
+                14 => FieldType::Enum(type_name),
+                10 => return Err(format!("group field '{field_name}' is not supported")),
+                ty => FieldType::Scalar(
+                    Scalar::from_descriptor_type(ty)
+                        .ok_or_else(|| format!("unknown type {ty} of field '{field_name}'"))?,
+                ),
+            };
+            message.fields.push(Field {
+                json_name: wire::string_field(&f, 10).unwrap_or_else(|| json_name(&field_name)),
+                name: field_name,
+                number: wire::varint_field(&f, 3).unwrap_or(0) as u32,
+                ty,
+                repeated: wire::varint_field(&f, 4) == Some(3),
+            });
+        }
+        for nested in wire::len_fields(&fields, 3) {
+            self.add_descriptor_message(&name, nested)?;
+        }
+        for e in wire::len_fields(&fields, 4) {
+            self.add_descriptor_enum(&name, e)?;
+        }
+        self.messages.insert(name, message);
+        Ok(())
+    }
+
+    fn add_descriptor_enum(&mut self, scope: &str, bytes: &[u8]) -> Result<(), String> {
+        let fields = wire::read_fields(bytes)?;
+        let name = qualify(scope, &wire::string_field(&fields, 1).unwrap_or_default());
+        let mut values = Vec::new();
+        for value in wire::len_fields(&fields, 2) {
+            let v = wire::read_fields(value)?;
+            values.push((
+                wire::string_field(&v, 1).unwrap_or_default(),
+                wire::varint_field(&v, 2).unwrap_or(0) as i32,
+            ));
+        }
+        self.enums.insert(name.clone(), Enum { name, values });
+        Ok(())
+    }
+
+    /// Adds a bundled well-known type file, returning false if `name` is not one
+    pub(crate) fn add_builtin(&mut self, name: &str) -> Result<bool, GrpcError> {
+        match builtin_proto(name) {
+            Some(source) => {
+                self.add_proto_source(name, source, &[])?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Returns true once a file of this name has been added
+    pub(crate) fn has_file(&self, name: &str) -> bool {
+        self.files.contains(name)
+    }
+
+    /// Looks up the type names left unresolved by the `.proto` parser
+    ///
+    /// Names are searched from the innermost enclosing scope outwards, as
+    /// protoc does, and message and service types must exist.
+    pub(crate) fn resolve(&mut self) -> Result<(), GrpcError> {
+        let lookup = |messages: &HashMap<String, Message>,
+                      enums: &HashMap<String, Enum>,
+                      scope: &str,
+                      name: &str|
+         -> Option<FieldType> {
+            let candidates: Vec<String> = match name.strip_prefix('.') {
+                Some(absolute) => vec![absolute.to_string()],
+                None => {
+                    let mut scope = scope.to_string();
+                    let mut candidates = Vec::new();
+                    loop {
+                        candidates.push(qualify(&scope, name));
+                        match scope.rfind('.') {
+                            Some(i) => scope.truncate(i),
+                            None if scope.is_empty() => break,
+                            None => scope.clear(),
+                        }
+                    }
+                    candidates
+                }
+            };
+            candidates.into_iter().find_map(|full| {
+                if messages.contains_key(&full) {
+                    Some(FieldType::Message(full))
+                } else if enums.contains_key(&full) {
+                    Some(FieldType::Enum(full))
+                } else {
+                    None
+                }
+            })
+        };
+        let mut resolved = Vec::new();
+        for message in self.messages.values() {
+            for (i, field) in message.fields.iter().enumerate() {
+                if let FieldType::Unresolved(name) = &field.ty {
+                    let ty = lookup(&self.messages, &self.enums, &message.name, name).ok_or_else(
+                        || {
+                            GrpcError::Proto(format!(
+                                "unknown type '{name}' of field '{}.{}'",
+                                message.name, field.name
+                            ))
+                        },
+                    )?;
+                    resolved.push((message.name.clone(), i, ty));
+                }
+            }
+        }
+        for (message, i, ty) in resolved {
+            if let Some(message) = self.messages.get_mut(&message) {
+                message.fields[i].ty = ty;
+            }
+        }
+        let mut services = std::mem::take(&mut self.services);
+        for service in &mut services {
+            for method in &mut service.methods {
+                for name in [&mut method.input, &mut method.output] {
+                    match lookup(&self.messages, &self.enums, &service.name, name) {
+                        Some(FieldType::Message(full)) => *name = full,
+                        _ => {
+                            return Err(GrpcError::Proto(format!(
+                                "unknown message type '{name}' in '{}/{}'",
+                                service.name, method.name
+                            )))
+                        }
+                    }
+                }
+            }
+        }
+        self.services = services;
+        Ok(())
+    }
+
+    /// Returns a message type by its fully qualified name
+    pub fn message(&self, name: &str) -> Result<&Message, GrpcError> {
+        self.messages
+            .get(name)
+            .ok_or_else(|| GrpcError::Proto(format!("unknown message type '{name}'")))
+    }
+
+    /// Returns an enum type by its fully qualified name
+    pub fn enum_type(&self, name: &str) -> Option<&Enum> {
+        self.enums.get(name)
+    }
+
+    /// Returns the services, sorted by name
+    pub fn services(&self) -> Vec<&Service> {
+        let mut services: Vec<&Service> = self.services.iter().collect();
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+        services
+    }
+
+    /// Finds a method given as `package.Service/Method` or `package.Service.Method`
+    ///
+    /// # Errors
+    /// Returns `GrpcError::UnknownMethod` listing the services or methods that
+    /// do exist.
+    pub fn method(&self, path: &str) -> Result<(&Service, &Method), GrpcError> {
+        let (service_name, method_name) = split_method(path)?;
+        let service = self
+            .services
+            .iter()
+            .find(|s| s.name == service_name)
+            .ok_or_else(|| {
+                let known: Vec<&str> = self.services().iter().map(|s| s.name.as_str()).collect();
+                GrpcError::UnknownMethod(format!(
+                    "service '{service_name}' not found (services: {})",
+                    known.join(", ")
+                ))
+            })?;
+        let method = service
+            .methods
+            .iter()
+            .find(|m| m.name == method_name)
+            .ok_or_else(|| {
+                let known: Vec<&str> = service.methods.iter().map(|m| m.name.as_str()).collect();
+                GrpcError::UnknownMethod(format!(
+                    "method '{method_name}' not found in '{service_name}' (methods: {})",
+                    known.join(", ")
+                ))
+            })?;
+        Ok((service, method))
+    }
+}
+
+/// Splits `package.Service/Method` (or `package.Service.Method`) into the
+/// service and method names
+///
+/// # Examples
+/// ```
+/// use wave::grpc::split_method;
+///
+/// assert_eq!(split_method("helloworld.Greeter/SayHello").unwrap(), ("helloworld.Greeter", "SayHello"));
+/// assert_eq!(split_method("helloworld.Greeter.SayHello").unwrap(), ("helloworld.Greeter", "SayHello"));
+/// assert!(split_method("SayHello").is_err());
+/// ```
+pub fn split_method(path: &str) -> Result<(&str, &str), GrpcError> {
+    let path = path.trim_start_matches('/');
+    path.rsplit_once('/')
+        .or_else(|| path.rsplit_once('.'))
+        .filter(|(service, method)| !service.is_empty() && !method.is_empty())
+        .ok_or_else(|| {
+            GrpcError::UnknownMethod(format!(
+                "'{path}' is not of the form package.Service/Method"
+            ))
+        })
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{scope}.{name}")
+    }
+}
+
+/// The lowerCamelCase JSON name protoc gives a field, e.g. `user_id` -> `userId`
+fn json_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Well-known types bundled for imports not found on disk
+fn builtin_proto(import: &str) -> Option<&'static str> {
+    Some(match import {
+        "google/protobuf/empty.proto" => "package google.protobuf; message Empty {}",
+        "google/protobuf/timestamp.proto" => {
+            "package google.protobuf; message Timestamp { int64 seconds = 1; int32 nanos = 2; }"
+        }
+        "google/protobuf/duration.proto" => {
+            "package google.protobuf; message Duration { int64 seconds = 1; int32 nanos = 2; }"
+        }
+        "google/protobuf/wrappers.proto" => {
+            "package google.protobuf;
+            message DoubleValue { double value = 1; }
+            message FloatValue { float value = 1; }
+            message Int64Value { int64 value = 1; }
+            message UInt64Value { uint64 value = 1; }
+            message Int32Value { int32 value = 1; }
+            message UInt32Value { uint32 value = 1; }
+            message BoolValue { bool value = 1; }
+            message StringValue { string value = 1; }
+            message BytesValue { bytes value = 1; }"
+        }
+        _ => return None,
+    })
+}
+
+/// Definitions from one `.proto` file, with type names not yet resolved
+#[derive(Default)]
+struct ProtoFile {
+    imports: Vec<String>,
+    messages: Vec<Message>,
+    enums: Vec<Enum>,
+    services: Vec<Service>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(String),
+    Str(String),
+    Symbol(char),
+}
+
+/// A recursive-descent parser for the proto2 and proto3 syntax
+///
+/// Only what is needed to encode and decode messages is kept: options,
+/// extensions and reserved ranges are skipped.
+struct Parser<'a> {
+    file: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    package: String,
+}
+
+impl<'a> Parser<'a> {
+    fn new(file: &'a str, source: &str) -> Result<Parser<'a>, GrpcError> {
+        Ok(Parser {
+            file,
+            tokens: tokenize(file, source)?,
+            pos: 0,
+            package: String::new(),
+        })
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> GrpcError {
+        let line = self
+            .tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line);
+        GrpcError::Proto(format!("{}:{line}: {message}", self.file))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token, GrpcError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), GrpcError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{symbol}'")))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, GrpcError> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a name"))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, GrpcError> {
+        match self.next()? {
+            Token::Str(s) => Ok(s),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a string"))
+            }
+        }
+    }
+
+    fn int(&mut self) -> Result<i64, GrpcError> {
+        let negative = self.eat('-');
+        let text = match self.next()? {
+            Token::Int(text) => text,
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected a number"));
+            }
+        };
+        let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None if text.len() > 1 && text.starts_with('0') => i64::from_str_radix(&text, 8),
+            None => text.parse(),
+        }
+        .map_err(|_| self.error(format!("invalid number '{text}'")))?;
+        Ok(if negative { -value } else { value })
+    }
+
+    /// Skips to the end of a statement, including any `{...}` block in it
+    fn skip_statement(&mut self) -> Result<(), GrpcError> {
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                Token::Symbol(';') if depth == 0 => return Ok(()),
+                Token::Symbol('{') => depth += 1,
+                Token::Symbol('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_file(mut self) -> Result<ProtoFile, GrpcError> {
+        let mut file = ProtoFile::default();
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Symbol(';') => self.pos += 1,
+                Token::Ident(word) => match word.as_str() {
+                    "syntax" | "edition" => {
+                        self.pos += 1;
+                        self.expect('=')?;
+                        let syntax = self.string()?;
+                        if !matches!(syntax.as_str(), "proto2" | "proto3") {
+                            return Err(self.error(format!("unsupported syntax '{syntax}'")));
+                        }
+                        self.expect(';')?;
+                    }
+                    "package" => {
+                        self.pos += 1;
+                        self.package = self.ident()?;
+                        self.expect(';')?;
+                    }
+                    "import" => {
+                        self.pos += 1;
+                        if matches!(self.peek(), Some(Token::Ident(w)) if w == "public" || w == "weak")
+                        {
+                            self.pos += 1;
+                        }
+                        file.imports.push(self.string()?);
+                        self.expect(';')?;
+                    }
+                    "message" => {
+                        self.pos += 1;
+                        let scope = self.package.clone();
+                        self.parse_message(&scope, &mut file)?;
+                    }
+                    "enum" => {
+                        self.pos += 1;
+                        let scope = self.package.clone();
+                        file.enums.push(self.parse_enum(&scope)?);
+                    }
+                    "service" => {
+                        self.pos += 1;
+                        file.services.push(self.parse_service()?);
+                    }
+                    "option" | "extend" => self.skip_statement()?,
+                    other => return Err(self.error(format!("unexpected '{other}'"))),
+                },
+                _ => return Err(self.error("expected a definition")),
+            }
+        }
+        Ok(file)
+    }
+
+    fn parse_message(&mut self, scope: &str, file: &mut ProtoFile) -> Result<(), GrpcError> {
+        let name = qualify(scope, &self.ident()?);
+        let mut message = Message {
+            name: name.clone(),
+            ..Default::default()
+        };
+        self.expect('{')?;
+        self.parse_message_body(&mut message, file)?;
+        file.messages.push(message);
+        Ok(())
+    }
+
+    /// Parses fields and nested definitions up to the closing `}`
+    fn parse_message_body(
+        &mut self,
+        message: &mut Message,
+        file: &mut ProtoFile,
+    ) -> Result<(), GrpcError> {
+        loop {
+            let word = match self.next()? {
+                Token::Symbol('}') => return Ok(()),
+                Token::Symbol(';') => continue,
+                Token::Ident(word) => word,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected a field or definition"));
+                }
+            };
+            match word.as_str() {
+                "message" => self.parse_message(&message.name.clone(), file)?,
+                "enum" => file.enums.push(self.parse_enum(&message.name)?),
+                "oneof" => {
+                    self.ident()?;
+                    self.expect('{')?;
+                    self.parse_message_body(message, file)?;
+                }
+                "option" | "reserved" | "extensions" | "extend" => {
+                    self.pos -= 1;
+                    self.skip_statement()?;
+                }
+                "map" => {
+                    self.expect('<')?;
+                    let key = self.ident()?;
+                    self.expect(',')?;
+                    let value = self.ident()?;
+                    self.expect('>')?;
+                    let field = self.parse_field_rest(FieldType::Unresolved(String::new()))?;
+                    let entry_name = format!("{}.{}Entry", message.name, upper_camel(&field.name));
+                    let key_ty = Scalar::from_name(&key)
+                        .ok_or_else(|| self.error(format!("invalid map key type '{key}'")))?;
+                    file.messages.push(Message {
+                        name: entry_name.clone(),
+                        fields: vec![
+                            Field {
+                                name: "key".to_string(),
+                                json_name: "key".to_string(),
+                                number: 1,
+                                ty: FieldType::Scalar(key_ty),
+                                repeated: false,
+                            },
+                            Field {
+                                name: "value".to_string(),
+                                json_name: "value".to_string(),
+                                number: 2,
+                                ty: field_type(&value),
+                                repeated: false,
+                            },
+                        ],
+                        map_entry: true,
+                    });
+                    message.fields.push(Field {
+                        ty: FieldType::Message(entry_name),
+                        repeated: true,
+                        ..field
+                    });
+                }
+                "group" => return Err(self.error("groups are not supported")),
+                _ => {
+                    let (repeated, type_name) = match word.as_str() {
+                        "repeated" => (true, self.ident()?),
+                        "optional" | "required" => (false, self.ident()?),
+                        _ => (false, word),
+                    };
+                    if type_name == "group" {
+                        return Err(self.error("groups are not supported"));
+                    }
+                    let field = self.parse_field_rest(field_type(&type_name))?;
+                    message.fields.push(Field { repeated, ..field });
+                }
+            }
+        }
+    }
+
+    /// Parses `name = number [options];` after a field's type
+    fn parse_field_rest(&mut self, ty: FieldType) -> Result<Field, GrpcError> {
+        let name = self.ident()?;
+        self.expect('=')?;
+        let number = self.int()?;
+        let number = u32::try_from(number)
+            .ok()
+            .filter(|n| (1..=536_870_911).contains(n))
+            .ok_or_else(|| self.error(format!("invalid field number {number}")))?;
+        let mut json = None;
+        if self.eat('[') {
+            loop {
+                match self.next()? {
+                    Token::Symbol(']') => break,
+                    Token::Ident(option) if option == "json_name" => {
+                        self.expect('=')?;
+                        json = Some(self.string()?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.expect(';')?;
+        Ok(Field {
+            json_name: json.unwrap_or_else(|| json_name(&name)),
+            name,
+            number,
+            ty,
+            repeated: false,
+        })
+    }
+
+    fn parse_enum(&mut self, scope: &str) -> Result<Enum, GrpcError> {
+        let name = qualify(scope, &self.ident()?);
+        let mut values = Vec::new();
+        self.expect('{')?;
+        loop {
+            match self.next()? {
+                Token::Symbol('}') => break,
+                Token::Symbol(';') => {}
+                Token::Ident(word) if word == "option" || word == "reserved" => {
+                    self.pos -= 1;
+                    self.skip_statement()?;
+                }
+                Token::Ident(value) => {
+                    self.expect('=')?;
+                    let number = self.int()?;
+                    let number = i32::try_from(number)
+                        .map_err(|_| self.error(format!("enum value {number} out of range")))?;
+                    if self.eat('[') {
+                        while self.next()? != Token::Symbol(']') {}
+                    }
+                    self.expect(';')?;
+                    values.push((value, number));
+                }
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected an enum value"));
+                }
+            }
+        }
+        Ok(Enum { name, values })
+    }
+
+    fn parse_service(&mut self) -> Result<Service, GrpcError> {
+        let name = self.ident()?;
+        let name = qualify(&self.package, &name);
+        let mut methods = Vec::new();
+        self.expect('{')?;
+        loop {
+            match self.next()? {
+                Token::Symbol('}') => break,
+                Token::Symbol(';') => {}
+                Token::Ident(word) if word == "option" => {
+                    self.pos -= 1;
+                    self.skip_statement()?;
+                }
+                Token::Ident(word) if word == "rpc" => {
+                    let method = self.ident()?;
+                    let (client_streaming, input) = self.parse_rpc_type()?;
+                    if self.ident()? != "returns" {
+                        self.pos -= 1;
+                        return Err(self.error("expected 'returns'"));
+                    }
+                    let (server_streaming, output) = self.parse_rpc_type()?;
+                    if self.peek() == Some(&Token::Symbol('{')) {
+                        self.skip_statement()?;
+                    } else {
+                        self.expect(';')?;
+                    }
+                    methods.push(Method {
+                        name: method,
+                        input,
+                        output,
+                        client_streaming,
+                        server_streaming,
+                    });
+                }
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected 'rpc'"));
+                }
+            }
+        }
+        Ok(Service { name, methods })
+    }
+
+    /// Parses `(stream? Type)`
+    fn parse_rpc_type(&mut self) -> Result<(bool, String), GrpcError> {
+        self.expect('(')?;
+        let mut name = self.ident()?;
+        let streaming = name == "stream" && self.peek() != Some(&Token::Symbol(')'));
+        if streaming {
+            name = self.ident()?;
+        }
+        self.expect(')')?;
+        Ok((streaming, name))
+    }
+}
+
+fn field_type(name: &str) -> FieldType {
+    match Scalar::from_name(name) {
+        Some(scalar) => FieldType::Scalar(scalar),
+        None => FieldType::Unresolved(name.to_string()),
+    }
+}
+
+/// The UpperCamelCase protoc uses for map entry names, e.g. `user_ids` -> `UserIds`
+fn upper_camel(name: &str) -> String {
+    let camel = json_name(name);
+    let mut chars = camel.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => camel,
+    }
+}
+
+fn tokenize(file: &str, source: &str) -> Result<Vec<(Token, usize)>, GrpcError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '/' => {
+                chars.next();
+                match chars.next() {
+                    Some('/') => {
+                        while chars.peek().is_some_and(|&c| c != '\n') {
+                            chars.next();
+                        }
+                    }
+                    Some('*') => {
+                        let mut prev = ' ';
+                        loop {
+                            match chars.next() {
+                                Some('/') if prev == '*' => break,
+                                Some(c) => {
+                                    if c == '\n' {
+                                        line += 1;
+                                    }
+                                    prev = c;
+                                }
+                                None => {
+                                    return Err(GrpcError::Proto(format!(
+                                        "{file}:{line}: unterminated comment"
+                                    )))
+                                }
+                            }
+                        }
+                    }
+                    _ => return Err(GrpcError::Proto(format!("{file}:{line}: unexpected '/'"))),
+                }
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some(other) => text.push(other),
+                            None => break,
+                        },
+                        Some('\n') | None => {
+                            return Err(GrpcError::Proto(format!(
+                                "{file}:{line}: unterminated string"
+                            )))
+                        }
+                        Some(other) => text.push(other),
+                    }
+                }
+                tokens.push((Token::Str(text), line));
+            }
+            c if c.is_ascii_digit() => {
+                let mut text = String::new();
+                while chars
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '.')
+                {
+                    text.push(chars.next().unwrap());
+                }
+                tokens.push((Token::Int(text), line));
+            }
+            c if c.is_alphabetic() || c == '_' || c == '.' => {
+                let mut text = String::new();
+                while chars
+                    .peek()
+                    .is_some_and(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+                {
+                    text.push(chars.next().unwrap());
+                }
+                tokens.push((Token::Ident(text), line));
+            }
+            _ => {
+                chars.next();
+                tokens.push((Token::Symbol(c), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOP: &str = r#"
+syntax = "proto3";
+package shop.v1;
+
+import "google/protobuf/timestamp.proto";
+
+// A product in the catalogue
+message Product {
+  string id = 1;
+  string display_name = 2;
+  int64 price_cents = 3 [json_name = "price"];
+  repeated string tags = 4;
+  Kind kind = 5;
+  map<string, int32> stock_by_store = 6;
+  google.protobuf.Timestamp created_at = 7;
+  oneof discount {
+    int32 percent_off = 8;
+    Amount amount_off = 9;
+  }
+  message Amount { int64 cents = 1; }
+  enum Kind { KIND_UNSPECIFIED = 0; PHYSICAL = 1; DIGITAL = 2; }
+  reserved 10 to 12;
+}
+
+message GetProductRequest { string id = 1; }
+
+service Catalogue {
+  option deprecated = false;
+  rpc GetProduct (GetProductRequest) returns (Product);
+  rpc WatchProducts (GetProductRequest) returns (stream Product) {}
+}
+"#;
+
+    fn shop() -> Descriptors {
+        let mut descriptors = Descriptors::default();
+        descriptors
+            .add_proto_source("shop.proto", SHOP, &[])
+            .expect("Test: parse shop.proto");
+        descriptors.resolve().expect("Test: resolve types");
+        descriptors
+    }
+
+    #[test]
+    fn test_parses_messages_enums_and_services() {
+        let descriptors = shop();
+        let product = descriptors.message("shop.v1.Product").unwrap();
+        let field = |name: &str| product.fields.iter().find(|f| f.name == name).unwrap();
+        assert_eq!(field("display_name").json_name, "displayName");
+        assert_eq!(field("price_cents").json_name, "price");
+        assert!(field("tags").repeated);
+        assert_eq!(
+            field("kind").ty,
+            FieldType::Enum("shop.v1.Product.Kind".to_string())
+        );
+        assert_eq!(
+            field("amount_off").ty,
+            FieldType::Message("shop.v1.Product.Amount".to_string())
+        );
+        assert_eq!(
+            field("created_at").ty,
+            FieldType::Message("google.protobuf.Timestamp".to_string())
+        );
+        let stock = field("stock_by_store");
+        assert!(stock.repeated);
+        let entry = match &stock.ty {
+            FieldType::Message(name) => descriptors.message(name).unwrap(),
+            other => panic!("Expected a map entry, got {other:?}"),
+        };
+        assert_eq!(entry.name, "shop.v1.Product.StockByStoreEntry");
+        assert!(entry.map_entry);
+        assert_eq!(
+            descriptors
+                .enum_type("shop.v1.Product.Kind")
+                .unwrap()
+                .values[2],
+            ("DIGITAL".to_string(), 2)
+        );
+
+        let (service, method) = descriptors.method("shop.v1.Catalogue/GetProduct").unwrap();
+        assert_eq!(service.name, "shop.v1.Catalogue");
+        assert_eq!(method.input, "shop.v1.GetProductRequest");
+        assert_eq!(method.output, "shop.v1.Product");
+        assert!(!method.server_streaming);
+        let (_, watch) = descriptors
+            .method("shop.v1.Catalogue.WatchProducts")
+            .unwrap();
+        assert!(watch.server_streaming && !watch.client_streaming);
+    }
+
+    #[test]
+    fn test_unknown_methods_and_types_are_reported() {
+        let descriptors = shop();
+        let err = descriptors.method("shop.v1.Catalogue/Nope").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("methods: GetProduct, WatchProducts"));
+        let err = descriptors.method("shop.Missing/Get").unwrap_err();
+        assert!(err.to_string().contains("services: shop.v1.Catalogue"));
+
+        let mut broken = Descriptors::default();
+        broken
+            .add_proto_source("bad.proto", "message A { Missing b = 1; }", &[])
+            .unwrap();
+        let err = broken.resolve().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unknown type 'Missing' of field 'A.b'"));
+
+        let err = Descriptors::default()
+            .add_proto_source("bad.proto", "message A {\n  string b = ;\n}", &[])
+            .unwrap_err();
+        assert!(err.to_string().contains("bad.proto:2: expected a number"));
+        let err = Descriptors::default()
+            .add_proto_source("bad.proto", "import \"other.proto\";", &[])
+            .unwrap_err();
+        assert!(err.to_string().contains("import 'other.proto' not found"));
+    }
+
+    #[test]
+    fn test_proto_files_load_imports_from_disk() {
+        let dir = std::env::temp_dir().join(format!("wave_grpc_protos_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("common")).unwrap();
+        fs::write(
+            dir.join("common/money.proto"),
+            "syntax = \"proto3\"; package common; message Money { int64 cents = 1; }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("api.proto"),
+            "syntax = \"proto3\"; package api; import \"common/money.proto\";\n\
+             message Price { common.Money amount = 1; }\n\
+             service Prices { rpc Get (Price) returns (Price); }",
+        )
+        .unwrap();
+        let descriptors = Descriptors::from_proto_files(&[dir.join("api.proto")], &[]).unwrap();
+        assert_eq!(
+            descriptors.message("api.Price").unwrap().fields[0].ty,
+            FieldType::Message("common.Money".to_string())
+        );
+        assert_eq!(descriptors.services()[0].name, "api.Prices");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_file_descriptors_from_reflection() {
+        // FileDescriptorProto for:
+        //   package demo; message Ping { string text = 1; repeated Ping replies = 2; }
+        //   service Echo { rpc Send (Ping) returns (stream Ping); }
+        let mut text = Vec::new();
+        wire::put_len(&mut text, 1, b"text");
+        wire::put_key(&mut text, 3, wire::VARINT);
+        wire::put_varint(&mut text, 1);
+        wire::put_key(&mut text, 5, wire::VARINT);
+        wire::put_varint(&mut text, 9);
+        let mut replies = Vec::new();
+        wire::put_len(&mut replies, 1, b"replies");
+        wire::put_key(&mut replies, 3, wire::VARINT);
+        wire::put_varint(&mut replies, 2);
+        wire::put_key(&mut replies, 4, wire::VARINT);
+        wire::put_varint(&mut replies, 3);
+        wire::put_key(&mut replies, 5, wire::VARINT);
+        wire::put_varint(&mut replies, 11);
+        wire::put_len(&mut replies, 6, b".demo.Ping");
+        let mut ping = Vec::new();
+        wire::put_len(&mut ping, 1, b"Ping");
+        wire::put_len(&mut ping, 2, &text);
+        wire::put_len(&mut ping, 2, &replies);
+        let mut send = Vec::new();
+        wire::put_len(&mut send, 1, b"Send");
+        wire::put_len(&mut send, 2, b".demo.Ping");
+        wire::put_len(&mut send, 3, b".demo.Ping");
+        wire::put_key(&mut send, 6, wire::VARINT);
+        wire::put_varint(&mut send, 1);
+        let mut echo = Vec::new();
+        wire::put_len(&mut echo, 1, b"Echo");
+        wire::put_len(&mut echo, 2, &send);
+        let mut file = Vec::new();
+        wire::put_len(&mut file, 1, b"demo.proto");
+        wire::put_len(&mut file, 2, b"demo");
+        wire::put_len(&mut file, 3, b"google/protobuf/empty.proto");
+        wire::put_len(&mut file, 4, &ping);
+        wire::put_len(&mut file, 6, &echo);
+
+        let mut descriptors = Descriptors::default();
+        let (name, deps) = descriptors.add_file_descriptor(&file).unwrap();
+        assert_eq!(name, "demo.proto");
+        assert_eq!(deps, ["google/protobuf/empty.proto"]);
+        assert!(descriptors.has_file("demo.proto"));
+        descriptors.resolve().unwrap();
+        let ping = descriptors.message("demo.Ping").unwrap();
+        assert_eq!(ping.fields[0].ty, FieldType::Scalar(Scalar::String));
+        assert!(ping.fields[1].repeated);
+        assert_eq!(ping.fields[1].json_name, "replies");
+        let (_, send) = descriptors.method("demo.Echo/Send").unwrap();
+        assert!(send.server_streaming);
+        assert_eq!(send.output, "demo.Ping");
+    }
+}
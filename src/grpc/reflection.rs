@@ -0,0 +1,154 @@
+//! Loads service definitions from a server through gRPC server reflection
+//!
+//! Both `grpc.reflection.v1` and the older `grpc.reflection.v1alpha` services
+//! are tried. Only the requests needed here are used: listing services and
+//! fetching files by symbol or by name.
+
+use super::client::Channel;
+use super::error::GrpcError;
+use super::proto::Descriptors;
+use super::wire;
+use ::http::HeaderMap;
+
+const REFLECTION_PATHS: [&str; 2] = [
+    "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo",
+    "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo",
+];
+
+/// `ServerReflectionRequest` fields
+const FILE_BY_FILENAME: u32 = 3;
+const FILE_CONTAINING_SYMBOL: u32 = 4;
+const LIST_SERVICES: u32 = 7;
+
+/// `ServerReflectionResponse` fields
+const FILE_DESCRIPTOR_RESPONSE: u32 = 4;
+const LIST_SERVICES_RESPONSE: u32 = 6;
+const ERROR_RESPONSE: u32 = 7;
+
+/// gRPC status code returned by servers without the reflection service
+const UNIMPLEMENTED: u32 = 12;
+
+struct Reflection<'a> {
+    channel: &'a mut Channel,
+    metadata: &'a HeaderMap,
+    path: Option<&'static str>,
+}
+
+impl Reflection<'_> {
+    /// Sends one reflection request and returns the response's fields
+    async fn ask(&mut self, field: u32, value: &str) -> Result<Vec<u8>, GrpcError> {
+        let mut request = Vec::new();
+        wire::put_len(&mut request, field, value.as_bytes());
+        let paths: Vec<&'static str> = match self.path {
+            Some(path) => vec![path],
+            None => REFLECTION_PATHS.to_vec(),
+        };
+        let mut last_error = None;
+        for path in paths {
+            match self.channel.call(path, self.metadata, &request).await {
+                Ok(reply) => {
+                    self.path = Some(path);
+                    return reply.messages.into_iter().next().ok_or_else(|| {
+                        GrpcError::Reflection("the server sent no response".to_string())
+                    });
+                }
+                Err(GrpcError::Status { code, .. }) if code == UNIMPLEMENTED => {
+                    last_error = Some(GrpcError::Reflection(
+                        "the server does not support reflection; pass the service's .proto files with --proto"
+                            .to_string(),
+                    ));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.expect("at least one reflection path is tried"))
+    }
+
+    /// Returns the serialized `FileDescriptorProto`s of a response
+    async fn files(&mut self, field: u32, value: &str) -> Result<Vec<Vec<u8>>, GrpcError> {
+        let response = self.ask(field, value).await?;
+        let invalid = |e: String| GrpcError::Reflection(format!("invalid response: {e}"));
+        let fields = wire::read_fields(&response).map_err(invalid)?;
+        if let Some(error) = wire::len_fields(&fields, ERROR_RESPONSE).last() {
+            let error = wire::read_fields(error).map_err(invalid)?;
+            return Err(GrpcError::Reflection(format!(
+                "{value}: {}",
+                wire::string_field(&error, 2).unwrap_or_default()
+            )));
+        }
+        let Some(files) = wire::len_fields(&fields, FILE_DESCRIPTOR_RESPONSE).pop() else {
+            return Err(GrpcError::Reflection(format!(
+                "no file descriptors returned for {value}"
+            )));
+        };
+        let files = wire::read_fields(files).map_err(invalid)?;
+        Ok(wire::len_fields(&files, 1)
+            .into_iter()
+            .map(<[u8]>::to_vec)
+            .collect())
+    }
+
+    async fn list_services(&mut self) -> Result<Vec<String>, GrpcError> {
+        let response = self.ask(LIST_SERVICES, "").await?;
+        let invalid = |e: String| GrpcError::Reflection(format!("invalid response: {e}"));
+        let fields = wire::read_fields(&response).map_err(invalid)?;
+        let mut names = Vec::new();
+        for list in wire::len_fields(&fields, LIST_SERVICES_RESPONSE) {
+            let list = wire::read_fields(list).map_err(invalid)?;
+            for service in wire::len_fields(&list, 1) {
+                let service = wire::read_fields(service).map_err(invalid)?;
+                names.extend(wire::string_field(&service, 1));
+            }
+        }
+        Ok(names)
+    }
+}
+
+/// Fetches the definitions of `service`, or of every service the server
+/// lists when `service` is `None`, with all the files they depend on
+pub async fn fetch_descriptors(
+    channel: &mut Channel,
+    metadata: &HeaderMap,
+    service: Option<&str>,
+) -> Result<Descriptors, GrpcError> {
+    let mut reflection = Reflection {
+        channel,
+        metadata,
+        path: None,
+    };
+    let services = match service {
+        Some(service) => vec![service.to_string()],
+        None => reflection
+            .list_services()
+            .await?
+            .into_iter()
+            .filter(|name| !name.starts_with("grpc.reflection."))
+            .collect(),
+    };
+    let mut descriptors = Descriptors::default();
+    let mut pending: Vec<String> = Vec::new();
+    for service in services {
+        for file in reflection.files(FILE_CONTAINING_SYMBOL, &service).await? {
+            let (_, dependencies) = descriptors.add_file_descriptor(&file)?;
+            pending.extend(dependencies);
+        }
+    }
+    while let Some(name) = pending.pop() {
+        if descriptors.has_file(&name) {
+            continue;
+        }
+        match reflection.files(FILE_BY_FILENAME, &name).await {
+            Ok(files) => {
+                for file in files {
+                    let (_, dependencies) = descriptors.add_file_descriptor(&file)?;
+                    pending.extend(dependencies);
+                }
+            }
+            // Well-known types are often not registered with reflection
+            Err(GrpcError::Reflection(_)) if descriptors.add_builtin(&name)? => {}
+            Err(e) => return Err(e),
+        }
+    }
+    descriptors.resolve()?;
+    Ok(descriptors)
+}
@@ -0,0 +1,167 @@
+//! Protocol buffer wire format: varints, field keys and length-delimited values
+
+pub(crate) const VARINT: u8 = 0;
+pub(crate) const FIXED64: u8 = 1;
+pub(crate) const LEN: u8 = 2;
+pub(crate) const FIXED32: u8 = 5;
+
+/// A field value as it appears on the wire, before its type is known
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum WireValue<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Len(&'a [u8]),
+    Fixed32(u32),
+}
+
+pub(crate) fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+pub(crate) fn put_key(buf: &mut Vec<u8>, number: u32, wire_type: u8) {
+    put_varint(buf, (u64::from(number) << 3) | u64::from(wire_type));
+}
+
+pub(crate) fn put_len(buf: &mut Vec<u8>, number: u32, bytes: &[u8]) {
+    put_key(buf, number, LEN);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+pub(crate) fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub(crate) fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Reads a varint at the start of `buf`, returning it and the bytes it used
+pub(crate) fn read_varint(buf: &[u8]) -> Result<(u64, usize), String> {
+    let mut value = 0u64;
+    for (i, byte) in buf.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err("truncated or overlong varint".to_string())
+}
+
+/// Splits a message into its fields, in wire order
+///
+/// Groups (wire types 3 and 4), deprecated since proto2, are not supported.
+pub(crate) fn read_fields(mut buf: &[u8]) -> Result<Vec<(u32, WireValue<'_>)>, String> {
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let (key, used) = read_varint(buf)?;
+        buf = &buf[used..];
+        let number = u32::try_from(key >> 3).map_err(|_| "field number out of range")?;
+        let value = match (key & 7) as u8 {
+            VARINT => {
+                let (value, used) = read_varint(buf)?;
+                buf = &buf[used..];
+                WireValue::Varint(value)
+            }
+            FIXED64 => {
+                let bytes = buf.get(..8).ok_or("truncated 64-bit field")?;
+                buf = &buf[8..];
+                WireValue::Fixed64(u64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            LEN => {
+                let (len, used) = read_varint(buf)?;
+                let end = usize::try_from(len)
+                    .ok()
+                    .and_then(|len| used.checked_add(len))
+                    .filter(|&end| end <= buf.len())
+                    .ok_or("truncated length-delimited field")?;
+                let bytes = &buf[used..end];
+                buf = &buf[end..];
+                WireValue::Len(bytes)
+            }
+            FIXED32 => {
+                let bytes = buf.get(..4).ok_or("truncated 32-bit field")?;
+                buf = &buf[4..];
+                WireValue::Fixed32(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            other => return Err(format!("unsupported wire type {other} in field {number}")),
+        };
+        fields.push((number, value));
+    }
+    Ok(fields)
+}
+
+/// Returns the last string value of field `number`, as protobuf merges do
+pub(crate) fn string_field(fields: &[(u32, WireValue<'_>)], number: u32) -> Option<String> {
+    fields.iter().rev().find_map(|(n, value)| match value {
+        WireValue::Len(bytes) if *n == number => Some(String::from_utf8_lossy(bytes).into_owned()),
+        _ => None,
+    })
+}
+
+/// Returns every length-delimited value of field `number`
+pub(crate) fn len_fields<'a>(fields: &[(u32, WireValue<'a>)], number: u32) -> Vec<&'a [u8]> {
+    fields
+        .iter()
+        .filter_map(|(n, value)| match value {
+            WireValue::Len(bytes) if *n == number => Some(*bytes),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the last varint value of field `number`
+pub(crate) fn varint_field(fields: &[(u32, WireValue<'_>)], number: u32) -> Option<u64> {
+    fields.iter().rev().find_map(|(n, value)| match value {
+        WireValue::Varint(v) if *n == number => Some(*v),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varints_and_zigzag_round_trip() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut buf = Vec::new();
+            put_varint(&mut buf, value);
+            assert_eq!(read_varint(&buf), Ok((value, buf.len())));
+        }
+        let mut buf = Vec::new();
+        put_varint(&mut buf, 300);
+        assert_eq!(buf, [0xac, 0x02]);
+        for value in [0, -1, 1, i64::MIN, i64::MAX] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert!(read_varint(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn test_read_fields() {
+        let mut buf = Vec::new();
+        put_key(&mut buf, 1, VARINT);
+        put_varint(&mut buf, 150);
+        put_len(&mut buf, 2, b"testing");
+        put_key(&mut buf, 3, FIXED32);
+        buf.extend_from_slice(&7u32.to_le_bytes());
+        let fields = read_fields(&buf).unwrap();
+        assert_eq!(
+            fields,
+            [
+                (1, WireValue::Varint(150)),
+                (2, WireValue::Len(b"testing")),
+                (3, WireValue::Fixed32(7)),
+            ]
+        );
+        assert_eq!(string_field(&fields, 2).as_deref(), Some("testing"));
+        assert_eq!(varint_field(&fields, 1), Some(150));
+        assert!(read_fields(&buf[..buf.len() - 1]).is_err());
+    }
+}
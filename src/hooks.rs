@@ -0,0 +1,246 @@
+//! `pre:` and `post:` shell commands of collection requests
+//!
+//! A `pre:` command runs once the request has been built, before it is sent,
+//! so it can compute values such as an HMAC signature of the body. A `post:`
+//! command runs once the response has arrived, to inspect it or to pass
+//! values on to later requests of a `wave run`.
+//!
+//! Commands run with `sh -c` (`cmd /C` on Windows) in the current directory.
+//! Each one gets the request body (`pre:`) or the response body (`post:`) on
+//! stdin, and these environment variables:
+//!
+//! - `WAVE_REQUEST`, `WAVE_METHOD` and `WAVE_URL`
+//! - `WAVE_STATUS`, for `post:` commands
+//! - `WAVE_VAR_<name>` for every collection variable, with characters other
+//!   than letters, digits and `_` in the name replaced by `_`
+//!
+//! Every `name=value` line a command prints sets a variable; other lines are
+//! passed through to stderr. A command that exits with a non-zero status
+//! stops the request (`pre:`) or fails it (`post:`).
+
+use crate::error::{CollectionError, WaveError};
+use crate::http::{HttpRequest, HttpResponse};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Before the request is sent
+    Pre,
+    /// After the response has arrived
+    Post,
+}
+
+impl Stage {
+    /// The collection key the commands are listed under
+    pub fn key(self) -> &'static str {
+        match self {
+            Stage::Pre => "pre",
+            Stage::Post => "post",
+        }
+    }
+}
+
+/// What a hook is told about the request being made
+pub struct HookContext<'a> {
+    /// Name of the collection request
+    pub name: &'a str,
+    pub request: &'a HttpRequest,
+    /// The response, for `post:` commands
+    pub response: Option<&'a HttpResponse>,
+    /// Collection variables, exported as `WAVE_VAR_<name>`
+    pub vars: &'a HashMap<String, String>,
+}
+
+/// Runs `commands` in order and returns the variables they set
+///
+/// Variables set by one command are visible to the next through
+/// `WAVE_VAR_<name>`.
+///
+/// # Errors
+/// Returns `CollectionError::HookFailed` if a command cannot be started or
+/// exits with a non-zero status.
+pub fn run_hooks(
+    commands: &[String],
+    stage: Stage,
+    context: &HookContext<'_>,
+) -> Result<HashMap<String, String>, WaveError> {
+    let mut vars = context.vars.clone();
+    let mut set = HashMap::new();
+    for command in commands {
+        let output = run_command(command, stage, context, &vars).map_err(|message| {
+            WaveError::Collection(CollectionError::HookFailed {
+                request: context.name.to_string(),
+                stage: stage.key(),
+                command: command.clone(),
+                message,
+            })
+        })?;
+        for line in output.lines() {
+            match parse_assignment(line) {
+                Some((name, value)) => {
+                    vars.insert(name.to_string(), value.to_string());
+                    set.insert(name.to_string(), value.to_string());
+                }
+                None => eprintln!("{line}"),
+            }
+        }
+    }
+    Ok(set)
+}
+
+/// Runs one command and returns its stdout
+fn run_command(
+    command: &str,
+    stage: Stage,
+    context: &HookContext<'_>,
+    vars: &HashMap<String, String>,
+) -> Result<String, String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut cmd = Command::new(shell);
+    cmd.arg(flag)
+        .arg(command)
+        .env("WAVE_REQUEST", context.name)
+        .env("WAVE_METHOD", context.request.method.as_str())
+        .env("WAVE_URL", &context.request.url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    for (name, value) in vars {
+        cmd.env(format!("WAVE_VAR_{}", env_name(name)), value);
+    }
+    let stdin = match (stage, context.response) {
+        (Stage::Post, Some(resp)) => {
+            cmd.env("WAVE_STATUS", resp.status.to_string());
+            resp.body.as_bytes().to_vec()
+        }
+        _ => request_body(context.request),
+    };
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("could not start {shell}: {e}"))?;
+    if let Some(mut pipe) = child.stdin.take() {
+        // Written from another thread so a command that prints a lot before
+        // reading its input cannot deadlock; commands may ignore stdin
+        std::thread::spawn(move || {
+            let _ = pipe.write_all(&stdin);
+        });
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        // Whatever the command printed usually explains why it failed
+        eprint!("{}", String::from_utf8_lossy(&output.stdout));
+        return Err(match output.status.code() {
+            Some(code) => format!("exited with status {code}"),
+            None => "was killed by a signal".to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns the bytes the request will send, reading a `file:` body from disk
+fn request_body(req: &HttpRequest) -> Vec<u8> {
+    match (&req.body, &req.body_file) {
+        (Some(body), _) => body.as_bytes().to_vec(),
+        (None, Some(path)) => std::fs::read(path).unwrap_or_default(),
+        (None, None) => Vec::new(),
+    }
+}
+
+/// Turns a variable name into a portable environment variable name
+fn env_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Parses a `name=value` line printed by a hook
+///
+/// Names follow the rules of collection variables: letters, digits, `_`,
+/// `-` and `.`, not starting with a digit.
+///
+/// # Examples
+/// ```
+/// use wave::hooks::parse_assignment;
+///
+/// assert_eq!(parse_assignment("signature=ab12=="), Some(("signature", "ab12==")));
+/// assert_eq!(parse_assignment("Signed the body"), None);
+/// assert_eq!(parse_assignment("1x=2"), None);
+/// ```
+pub fn parse_assignment(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = line.trim_end_matches('\r').split_once('=')?;
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    let starts_well = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    (valid && starts_well).then_some((name, value))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use ::http::{HeaderMap, Method};
+
+    fn context<'a>(
+        request: &'a HttpRequest,
+        response: Option<&'a HttpResponse>,
+        vars: &'a HashMap<String, String>,
+    ) -> HookContext<'a> {
+        HookContext {
+            name: "create-user",
+            request,
+            response,
+            vars,
+        }
+    }
+
+    #[test]
+    fn test_pre_hooks_see_the_request_and_set_variables() {
+        let req = HttpRequest::builder("https://api.example.com/users", Method::POST)
+            .body(crate::http::RequestBody::text("hello".to_string()))
+            .build();
+        let vars = HashMap::from([("api-key".to_string(), "k1".to_string())]);
+        let commands = [
+            r#"echo "length=$(wc -c)"; echo "seen=$WAVE_METHOD $WAVE_URL $WAVE_VAR_api_key""#
+                .to_string(),
+            r#"echo "again=$WAVE_VAR_length"; echo not an assignment"#.to_string(),
+        ];
+        let set = run_hooks(&commands, Stage::Pre, &context(&req, None, &vars)).unwrap();
+        assert_eq!(set["length"].trim(), "5");
+        assert_eq!(set["seen"], "POST https://api.example.com/users k1");
+        assert_eq!(set["again"], set["length"]);
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn test_post_hooks_see_the_response_and_fail_on_error() {
+        let req = HttpRequest::new("https://example.com", Method::GET, None, HeaderMap::new());
+        let resp = HttpResponse::new(201, HeaderMap::new(), r#"{"id":7}"#);
+        let vars = HashMap::new();
+        let ctx = context(&req, Some(&resp), &vars);
+        let set = run_hooks(
+            &[r#"echo "status=$WAVE_STATUS"; echo "body=$(cat)""#.to_string()],
+            Stage::Post,
+            &ctx,
+        )
+        .unwrap();
+        assert_eq!(set["status"], "201");
+        assert_eq!(set["body"], r#"{"id":7}"#);
+
+        let err = run_hooks(&["exit 3".to_string()], Stage::Post, &ctx).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "post: hook of request 'create-user' failed: 'exit 3' exited with status 3"
+        );
+    }
+}
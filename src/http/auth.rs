@@ -0,0 +1,284 @@
+use crate::http::utils::is_loopback;
+use ::http::{header::AUTHORIZATION, HeaderMap, HeaderValue};
+use base64::Engine;
+use serde::Deserialize;
+use url::Url;
+
+/// Authentication schemes that can be applied to a request
+///
+/// Each scheme produces an `Authorization` header so credentials don't have to
+/// be assembled by hand. In collection files it is written as an `auth:` block:
+///
+/// ```yaml
+/// auth:
+///   bearer: ${token}
+/// ```
+///
+/// or
+///
+/// ```yaml
+/// auth:
+///   basic:
+///     username: alice
+///     password: ${password}
+/// ```
+///
+/// or, to fetch a bearer token with the OAuth2 client-credentials grant,
+///
+/// ```yaml
+/// auth:
+///   oauth2:
+///     token_url: https://auth.example.com/oauth/token
+///     client_id: my-client
+///     client_secret: ${client_secret}
+///     scopes: [read, write]
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use wave::http::Auth;
+///
+/// let auth = Auth::parse_basic("aladdin:opensesame");
+/// assert_eq!(auth.header_value().as_deref(), Some("Basic YWxhZGRpbjpvcGVuc2VzYW1l"));
+///
+/// let auth = Auth::Bearer("token123".to_string());
+/// assert_eq!(auth.header_value().as_deref(), Some("Bearer token123"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "AuthBlock")]
+pub enum Auth {
+    /// HTTP Basic authentication with an optional password
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    /// Bearer token authentication
+    Bearer(String),
+    /// Bearer token fetched from an OAuth2 token endpoint (see [`crate::oauth2`])
+    OAuth2(OAuth2),
+}
+
+/// OAuth2 client-credentials settings from an `auth: oauth2:` block
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OAuth2 {
+    /// Token endpoint of the authorization server
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Scopes to request, sent space-separated
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+/// The `auth:` block as written in YAML, with exactly one scheme set
+///
+/// serde_yaml expects enums as YAML tags (`!bearer token`), so the block is
+/// read as a struct and converted instead.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuthBlock {
+    basic: Option<BasicCredentials>,
+    bearer: Option<String>,
+    oauth2: Option<OAuth2>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BasicCredentials {
+    username: String,
+    password: Option<String>,
+}
+
+impl TryFrom<AuthBlock> for Auth {
+    type Error = String;
+
+    fn try_from(block: AuthBlock) -> Result<Self, Self::Error> {
+        match (block.basic, block.bearer, block.oauth2) {
+            (Some(BasicCredentials { username, password }), None, None) => {
+                Ok(Auth::Basic { username, password })
+            }
+            (None, Some(token), None) => Ok(Auth::Bearer(token)),
+            (None, None, Some(oauth2)) => Ok(Auth::OAuth2(oauth2)),
+            _ => Err("auth needs exactly one of 'basic', 'bearer' or 'oauth2'".to_string()),
+        }
+    }
+}
+
+impl Auth {
+    /// Parse Basic credentials in `user:password` form
+    ///
+    /// Everything after the first `:` is the password, so passwords may
+    /// contain colons. Without a `:` the whole string is the username.
+    pub fn parse_basic(credentials: &str) -> Self {
+        match credentials.split_once(':') {
+            Some((username, password)) => Auth::Basic {
+                username: username.to_string(),
+                password: Some(password.to_string()),
+            },
+            None => Auth::Basic {
+                username: credentials.to_string(),
+                password: None,
+            },
+        }
+    }
+
+    /// Returns the value for the `Authorization` header
+    ///
+    /// `OAuth2` has no value until its token is fetched and it is replaced
+    /// with `Bearer`, so it returns `None`.
+    pub fn header_value(&self) -> Option<String> {
+        match self {
+            Auth::Basic { username, password } => {
+                let credentials = format!("{username}:{}", password.as_deref().unwrap_or(""));
+                Some(format!(
+                    "Basic {}",
+                    base64::engine::general_purpose::STANDARD.encode(credentials)
+                ))
+            }
+            Auth::Bearer(token) => Some(format!("Bearer {token}")),
+            Auth::OAuth2(_) => None,
+        }
+    }
+
+    /// Sets the `Authorization` header, replacing any existing value
+    ///
+    /// Values that are not valid header values (e.g. containing newlines)
+    /// are ignored, matching how other invalid headers are handled.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Some(Ok(value)) = self.header_value().as_deref().map(HeaderValue::from_str) {
+            headers.insert(AUTHORIZATION, value);
+        }
+    }
+}
+
+/// Names the credentials a request would send unencrypted, if any
+///
+/// Returns `"Basic"` or `"Bearer"` when the request goes to a plain `http://`
+/// URL on another machine with that kind of `Authorization` header. Requests
+/// to localhost never leave the machine, so they are not reported.
+///
+/// # Examples
+///
+/// ```
+/// use http::{header::AUTHORIZATION, HeaderMap};
+/// use wave::http::cleartext_credentials;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert(AUTHORIZATION, "Bearer abc".parse().unwrap());
+/// assert_eq!(cleartext_credentials("http://api.example.com", &headers), Some("Bearer"));
+/// assert_eq!(cleartext_credentials("https://api.example.com", &headers), None);
+/// assert_eq!(cleartext_credentials("http://localhost:8080", &headers), None);
+/// ```
+pub fn cleartext_credentials(url: &str, headers: &HeaderMap) -> Option<&'static str> {
+    let url = Url::parse(url).ok()?;
+    if url.scheme() != "http" || is_loopback(&url) {
+        return None;
+    }
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, _) = value.split_once(' ')?;
+    ["Basic", "Bearer"]
+        .into_iter()
+        .find(|known| scheme.eq_ignore_ascii_case(known))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_basic() {
+        assert_eq!(
+            Auth::parse_basic("alice:s3cr:et"),
+            Auth::Basic {
+                username: "alice".to_string(),
+                password: Some("s3cr:et".to_string()),
+            }
+        );
+        assert_eq!(
+            Auth::parse_basic("alice"),
+            Auth::Basic {
+                username: "alice".to_string(),
+                password: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_header_values() {
+        assert_eq!(
+            Auth::parse_basic("aladdin:opensesame").header_value(),
+            Some("Basic YWxhZGRpbjpvcGVuc2VzYW1l".to_string())
+        );
+        assert_eq!(
+            Auth::parse_basic("alice").header_value(),
+            Some("Basic YWxpY2U6".to_string())
+        );
+        assert_eq!(
+            Auth::Bearer("abc.def".to_string()).header_value(),
+            Some("Bearer abc.def".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_replaces_authorization_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer old".parse().unwrap());
+        Auth::Bearer("new".to_string()).apply(&mut headers);
+        assert_eq!(headers.get(AUTHORIZATION).unwrap(), "Bearer new");
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn test_deserialize_from_yaml() {
+        let bearer: Auth = serde_yaml::from_str("bearer: token123").unwrap();
+        assert_eq!(bearer, Auth::Bearer("token123".to_string()));
+
+        let basic: Auth =
+            serde_yaml::from_str("basic:\n  username: alice\n  password: secret").unwrap();
+        assert_eq!(
+            basic,
+            Auth::Basic {
+                username: "alice".to_string(),
+                password: Some("secret".to_string()),
+            }
+        );
+
+        let oauth2: Auth = serde_yaml::from_str(
+            "oauth2:\n  token_url: https://auth.io/token\n  client_id: app\n  client_secret: s3cret",
+        )
+        .unwrap();
+        assert_eq!(
+            oauth2,
+            Auth::OAuth2(OAuth2 {
+                token_url: "https://auth.io/token".to_string(),
+                client_id: "app".to_string(),
+                client_secret: "s3cret".to_string(),
+                scopes: Vec::new(),
+            })
+        );
+        assert_eq!(oauth2.header_value(), None);
+
+        assert!(serde_yaml::from_str::<Auth>("{}").is_err());
+        assert!(serde_yaml::from_str::<Auth>("bearer: a\nbasic:\n  username: b").is_err());
+    }
+
+    #[test]
+    fn test_cleartext_credentials() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(cleartext_credentials("http://example.com", &headers), None);
+        Auth::parse_basic("alice:secret").apply(&mut headers);
+        assert_eq!(
+            cleartext_credentials("http://example.com/login", &headers),
+            Some("Basic")
+        );
+        assert_eq!(cleartext_credentials("HTTPS://example.com", &headers), None);
+        assert_eq!(
+            cleartext_credentials("http://127.0.0.1:3000", &headers),
+            None
+        );
+        headers.insert(AUTHORIZATION, "Digest username=alice".parse().unwrap());
+        assert_eq!(cleartext_credentials("http://example.com", &headers), None);
+    }
+}
@@ -1,6 +1,22 @@
-use crate::http::{error::HttpError, request::HttpRequest, response::HttpResponse};
+use crate::http::{
+    charset::Charset,
+    decode,
+    error::{HttpError, RequestFailure},
+    request::{HttpRequest, MultipartPart},
+    resolve::Resolve,
+    response::{HttpResponse, Timing},
+    tls::TlsOptions,
+};
 use ::http::{HeaderMap, Method};
 use async_trait::async_trait;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Callback invoked while a body is downloaded, with the bytes written so far
+/// and the total from `Content-Length` when the server sent one
+pub type ProgressFn<'a> = &'a (dyn Fn(u64, Option<u64>) + Send + Sync);
 
 /// Trait for HTTP backends that handle the actual network communication
 ///
@@ -35,19 +51,327 @@ pub trait HttpBackend {
     /// This is the core method that implementations must provide to handle
     /// the actual HTTP communication.
     async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError>;
+
+    /// Send an HTTP request and write the response body to `path`
+    ///
+    /// Returns the response (with an empty body) and the number of bytes
+    /// written. The default implementation buffers the whole body via
+    /// [`send`](HttpBackend::send); backends that can stream should override
+    /// it so large downloads never sit in memory.
+    ///
+    /// A request with a `Range: bytes=N-` header resumes a download: a 206
+    /// response starting at byte N is appended to `path`, and a 4xx or 5xx
+    /// response leaves the file as it was and is returned with its body.
+    async fn download(
+        &self,
+        req: &HttpRequest,
+        path: &Path,
+        progress: ProgressFn<'_>,
+    ) -> Result<(HttpResponse, u64), HttpError> {
+        let resp = self.send(req).await?;
+        let mut file = match download_target(req, resp.status, &resp.headers)? {
+            Some(target) => target.open(path)?,
+            None => return Ok((resp, 0)),
+        };
+        file.write_all(&resp.bytes)
+            .map_err(|e| write_error(path, e))?;
+        let written = resp.bytes.len() as u64;
+        progress(written, Some(written));
+        Ok((
+            HttpResponse::new(resp.status, resp.headers, Vec::new()),
+            written,
+        ))
+    }
+}
+
+/// Lets the backend be picked at run time, e.g. with or without a cassette
+#[async_trait]
+impl<B: HttpBackend + Send + Sync + ?Sized> HttpBackend for Box<B> {
+    async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        self.as_ref().send(req).await
+    }
+
+    async fn download(
+        &self,
+        req: &HttpRequest,
+        path: &Path,
+        progress: ProgressFn<'_>,
+    ) -> Result<(HttpResponse, u64), HttpError> {
+        self.as_ref().download(req, path, progress).await
+    }
+}
+
+/// Lets a client share its backend, e.g. to keep a handle on a
+/// [`MockBackend`](crate::http::MockBackend) and look at the requests it got
+#[async_trait]
+impl<B: HttpBackend + Send + Sync + ?Sized> HttpBackend for Arc<B> {
+    async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        self.as_ref().send(req).await
+    }
+
+    async fn download(
+        &self,
+        req: &HttpRequest,
+        path: &Path,
+        progress: ProgressFn<'_>,
+    ) -> Result<(HttpResponse, u64), HttpError> {
+        self.as_ref().download(req, path, progress).await
+    }
+}
+
+/// How a download writes the response body to its file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DownloadTarget {
+    /// Replace the file with the whole body
+    Create,
+    /// Add the rest of a resumed download to the end of the file
+    Append,
+}
+
+impl DownloadTarget {
+    fn open(self, path: &Path) -> Result<std::fs::File, HttpError> {
+        let mut options = std::fs::OpenOptions::new();
+        match self {
+            DownloadTarget::Create => options.write(true).create(true).truncate(true),
+            DownloadTarget::Append => options.append(true),
+        };
+        options.open(path).map_err(|e| write_error(path, e))
+    }
+}
+
+/// Returns N for a request that resumes a download with `Range: bytes=N-`
+fn resume_offset(req: &HttpRequest) -> Option<u64> {
+    let range = req.headers.get(::http::header::RANGE)?.to_str().ok()?;
+    range
+        .trim()
+        .strip_prefix("bytes=")?
+        .strip_suffix('-')?
+        .parse()
+        .ok()
+}
+
+/// Decides how the response to a download request is written
+///
+/// Without a resume `Range`, the file is always replaced. When resuming, a
+/// 206 is appended if its `Content-Range` starts where the file ends, a 200
+/// (the server ignored the range) replaces the file, and an error status
+/// (`None`) leaves it alone so the bytes already downloaded are kept.
+fn download_target(
+    req: &HttpRequest,
+    status: u16,
+    headers: &HeaderMap,
+) -> Result<Option<DownloadTarget>, HttpError> {
+    let Some(offset) = resume_offset(req) else {
+        return Ok(Some(DownloadTarget::Create));
+    };
+    if status >= 400 {
+        return Ok(None);
+    }
+    if status != 206 {
+        return Ok(Some(DownloadTarget::Create));
+    }
+    let content_range = headers
+        .get(::http::header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let start = content_range
+        .strip_prefix("bytes ")
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.trim().parse::<u64>().ok());
+    if start != Some(offset) {
+        return Err(HttpError::Parse(format!(
+            "asked to resume at byte {offset}, but the server sent Content-Range '{content_range}'"
+        )));
+    }
+    Ok(Some(DownloadTarget::Append))
+}
+
+fn write_error(path: &Path, e: std::io::Error) -> HttpError {
+    HttpError::Other(format!("Failed to write '{}': {e}", path.display()))
+}
+
+fn body_error(req: &HttpRequest, e: reqwest::Error) -> HttpError {
+    if e.is_timeout() {
+        request_error(req, &e)
+    } else {
+        HttpError::Parse(e.to_string())
+    }
+}
+
+/// Sorts a reqwest error by its cause, keeping the request it belongs to
+fn request_error(req: &HttpRequest, e: &reqwest::Error) -> HttpError {
+    let causes = error_causes(e);
+    let failure = RequestFailure::new(req.method.clone(), &req.url, causes.join(": "));
+    let mentions = |words: &[&str]| {
+        causes.iter().any(|cause| {
+            let cause = cause.to_lowercase();
+            words.iter().any(|word| cause.contains(word))
+        })
+    };
+    if e.is_timeout() && e.is_connect() {
+        HttpError::ConnectTimeout(failure)
+    } else if e.is_timeout() {
+        HttpError::Timeout(failure)
+    } else if e.is_redirect() {
+        HttpError::Redirect(failure)
+    } else if is_tls_error(e) || mentions(&["certificate", "ssl", "tls"]) {
+        HttpError::Tls(failure)
+    } else if e.is_connect() && mentions(&["dns error", "failed to lookup address"]) {
+        HttpError::Dns(failure)
+    } else if e.is_connect() {
+        HttpError::Connect(failure)
+    } else {
+        HttpError::Network(failure)
+    }
+}
+
+/// The messages of an error's sources, skipping reqwest's "error sending
+/// request for url" wrapper, which repeats the URL
+fn error_causes(e: &reqwest::Error) -> Vec<String> {
+    let mut causes: Vec<String> = Vec::new();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        let message = cause.to_string();
+        // TLS libraries repeat their error in the message of the wrapper
+        if !causes
+            .last()
+            .is_some_and(|last| last.contains(message.trim_end_matches(':')))
+        {
+            causes.push(message);
+        }
+        source = cause.source();
+    }
+    if causes.is_empty() {
+        causes.push(e.to_string());
+    }
+    causes
+}
+
+fn is_tls_error(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        if cause.downcast_ref::<native_tls::Error>().is_some() {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+fn response_headers(resp: &reqwest::Response) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (k, v) in resp.headers() {
+        headers.append(k.clone(), v.clone());
+    }
+    headers
+}
+
+/// Encodes multipart/form-data parts, reading file parts from disk
+///
+/// Returns the `Content-Type` header value (with the generated boundary) and
+/// the encoded body. File parts are sent as `application/octet-stream`.
+fn multipart_body(parts: &[MultipartPart]) -> Result<(String, Vec<u8>), HttpError> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let boundary = format!("----wave-boundary-{nanos:x}");
+    let mut body = Vec::new();
+    for part in parts {
+        body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+        match part {
+            MultipartPart::Text { name, value } => {
+                body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                        quote_field(name)
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(value.as_bytes());
+            }
+            MultipartPart::File { name, path } => {
+                let data = std::fs::read(path).map_err(|e| {
+                    HttpError::Other(format!("Failed to read '{}': {e}", path.display()))
+                })?;
+                let file_name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                         Content-Type: application/octet-stream\r\n\r\n",
+                        quote_field(name),
+                        quote_field(&file_name)
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(&data);
+            }
+        }
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    Ok((format!("multipart/form-data; boundary={boundary}"), body))
+}
+
+/// Escapes a field or file name for a quoted Content-Disposition parameter
+fn quote_field(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
 }
 
 /// Default backend using reqwest for real HTTP requests
 ///
 /// This is the production backend that performs actual network communication
-/// using the reqwest library. It handles all standard HTTP methods and
-/// automatically manages connection pooling, timeouts, and other network concerns.
-pub struct ReqwestBackend;
+/// using the reqwest library. It handles all standard HTTP methods and holds a
+/// single configured `reqwest::Client`, so connections and TLS sessions are
+/// pooled and reused across every request sent through the same backend.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use wave::http::{Client, ReqwestBackend};
+///
+/// let backend = ReqwestBackend::builder()
+///     .timeout(Duration::from_secs(30))
+///     .build()
+///     .unwrap();
+/// let client = Client::new(backend);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestBackend {
+    client: reqwest::Client,
+    no_decompress: bool,
+    response_charset: Option<Charset>,
+}
 
-#[async_trait]
-impl HttpBackend for ReqwestBackend {
-    async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
-        let client = reqwest::Client::new();
+impl ReqwestBackend {
+    /// Creates a backend with reqwest's default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts building a backend with custom client configuration
+    pub fn builder() -> ReqwestBackendBuilder {
+        ReqwestBackendBuilder::default()
+    }
+
+    /// Wraps an already configured `reqwest::Client`
+    pub fn from_client(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            no_decompress: false,
+            response_charset: None,
+        }
+    }
+
+    /// Builds and sends the request, returning the response before its body is read
+    async fn execute(&self, req: &HttpRequest) -> Result<reqwest::Response, HttpError> {
+        let client = &self.client;
         let mut request_builder = match &req.method {
             &Method::GET => client.get(&req.url),
             &Method::POST => client.post(&req.url),
@@ -64,27 +388,335 @@ impl HttpBackend for ReqwestBackend {
         if let Some(ref body) = req.body {
             request_builder = request_builder.body(body.clone());
         }
+        if let Some(ref path) = req.body_file {
+            let data = std::fs::read(path).map_err(|e| {
+                HttpError::Other(format!("Failed to read '{}': {e}", path.display()))
+            })?;
+            request_builder = request_builder.body(data);
+        }
+        if let Some(ref parts) = req.multipart {
+            let (content_type, body) = multipart_body(parts)?;
+            request_builder = request_builder
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(body);
+        }
+        if let Some(timeout) = req.timeout {
+            request_builder = request_builder.timeout(timeout);
+        }
         // Set headers
         for (key, value) in &req.headers {
             request_builder = request_builder.header(key.as_str(), value.to_str().unwrap_or(""));
         }
-        let resp = request_builder
-            .send()
-            .await
-            .map_err(|e| HttpError::Network(e.to_string()))?;
+        tracing::debug!(
+            method = %req.method,
+            url = %req.url,
+            headers = req.headers.len(),
+            "sending request"
+        );
+        request_builder.send().await.map_err(|e| {
+            let err = request_error(req, &e);
+            tracing::debug!(error = %err, "request failed");
+            err
+        })
+    }
+}
+
+/// Builder for a [`ReqwestBackend`] with a shared, configured client
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestBackendBuilder {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    proxy: Option<String>,
+    proxy_auth: Option<(String, String)>,
+    no_proxy: bool,
+    tls: TlsOptions,
+    resolve: Vec<Resolve>,
+    no_decompress: bool,
+    response_charset: Option<Charset>,
+}
+
+impl ReqwestBackendBuilder {
+    /// Sets the default total timeout for every request sent by the backend
+    ///
+    /// A timeout set on an individual [`HttpRequest`] takes precedence.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for establishing a connection
+    ///
+    /// Requests that hit it fail with `HttpError::ConnectTimeout` rather than
+    /// `HttpError::Timeout`, so an unreachable server can be told apart from
+    /// a slow one.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long to wait for each read from the server, so a response
+    /// that stalls fails without limiting how long a steady download takes
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends every request through the proxy at `url`
+    ///
+    /// Without an explicit proxy, reqwest honors the `HTTP_PROXY`,
+    /// `HTTPS_PROXY` and `NO_PROXY` environment variables. Hosts listed in
+    /// `NO_PROXY` bypass an explicit proxy as well.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Sets basic authentication credentials for the proxy
+    pub fn proxy_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.proxy_auth = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Ignores proxies configured through environment variables
+    pub fn no_proxy(mut self) -> Self {
+        self.no_proxy = true;
+        self
+    }
+
+    /// Connects to fixed addresses for a host instead of looking it up
+    ///
+    /// A later override for the same host replaces an earlier one.
+    pub fn resolve(mut self, resolve: Resolve) -> Self {
+        self.resolve.push(resolve);
+        self
+    }
+
+    /// Keeps compressed bodies as they were received
+    ///
+    /// By default, bodies sent with a `Content-Encoding` of `gzip` or
+    /// `deflate` are decoded, see [`HttpResponse::decompress`].
+    pub fn no_decompress(mut self) -> Self {
+        self.no_decompress = true;
+        self
+    }
+
+    /// Decodes the text of every response with `charset`, for servers whose
+    /// `Content-Type` names the wrong one or none, see [`HttpResponse::with_charset`]
+    pub fn response_charset(mut self, charset: Charset) -> Self {
+        self.response_charset = Some(charset);
+        self
+    }
+
+    /// Sets certificate verification, extra root certificates and the client identity
+    pub fn tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Builds the backend
+    ///
+    /// # Errors
+    /// Returns `HttpError::Other` if the proxy URL is invalid, a certificate
+    /// cannot be read or parsed, or reqwest cannot build the client
+    pub fn build(self) -> Result<ReqwestBackend, HttpError> {
+        let mut builder = reqwest::Client::builder();
+        if self.no_proxy {
+            builder = builder.no_proxy();
+        }
+        if let Some(url) = &self.proxy {
+            let mut proxy = reqwest::Proxy::all(url.as_str())
+                .map_err(|e| HttpError::Other(format!("Invalid proxy URL '{url}': {e}")))?
+                .no_proxy(reqwest::NoProxy::from_env());
+            if let Some((username, password)) = &self.proxy_auth {
+                proxy = proxy.basic_auth(username, password);
+            }
+            builder = builder.proxy(proxy);
+        }
+        for resolve in &self.resolve {
+            builder = builder.resolve_to_addrs(&resolve.host, &resolve.socket_addrs());
+        }
+        builder = self.tls.apply(builder)?;
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| HttpError::Other(format!("Failed to build HTTP client: {e}")))?;
+        Ok(ReqwestBackend {
+            client,
+            no_decompress: self.no_decompress,
+            response_charset: self.response_charset,
+        })
+    }
+}
+
+#[async_trait]
+impl HttpBackend for ReqwestBackend {
+    async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        let started = Instant::now();
+        let resp = self.execute(req).await?;
+        let ttfb = started.elapsed();
         let status = resp.status().as_u16();
-        let mut headers = HeaderMap::new();
-        for (k, v) in resp.headers() {
-            headers.insert(k.clone(), v.clone());
-        }
-        let body = resp
-            .text()
-            .await
-            .map_err(|e| HttpError::Parse(e.to_string()))?;
-        Ok(HttpResponse {
+        let headers = response_headers(&resp);
+        let bytes = resp.bytes().await.map_err(|e| body_error(req, e))?;
+        let timing = Timing {
+            ttfb,
+            total: started.elapsed(),
+        };
+        tracing::debug!(
             status,
-            headers,
-            body,
+            bytes = bytes.len(),
+            ttfb_ms = ttfb.as_millis() as u64,
+            total_ms = timing.total.as_millis() as u64,
+            "received response"
+        );
+        let mut resp = HttpResponse::new(status, headers, bytes.to_vec()).with_timing(timing);
+        if !self.no_decompress {
+            resp = resp.decompress()?;
+        }
+        Ok(match self.response_charset {
+            Some(charset) => resp.with_charset(charset),
+            None => resp,
         })
     }
+
+    async fn download(
+        &self,
+        req: &HttpRequest,
+        path: &Path,
+        progress: ProgressFn<'_>,
+    ) -> Result<(HttpResponse, u64), HttpError> {
+        let started = Instant::now();
+        let mut resp = self.execute(req).await?;
+        let ttfb = started.elapsed();
+        let status = resp.status().as_u16();
+        let headers = response_headers(&resp);
+        let total = resp.content_length();
+        let Some(target) = download_target(req, status, &headers)? else {
+            let bytes = resp.bytes().await.map_err(|e| body_error(req, e))?;
+            return Ok((HttpResponse::new(status, headers, bytes.to_vec()), 0));
+        };
+        // Compressed bodies are decoded on their way to the file, without a
+        // size limit as nothing is kept in memory; the rest of a resumed one
+        // cannot be decoded on its own
+        // Compressed bodies are decoded on their way to the file, without a
+        // size limit as nothing is kept in memory; the rest of a resumed one
+        // cannot be decoded on its own
+        let coding = (!self.no_decompress && target == DownloadTarget::Create)
+            .then(|| headers.get(::http::header::CONTENT_ENCODING))
+            .flatten()
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        tracing::debug!(status, path = %path.display(), "downloading response body");
+        let file = target.open(path)?;
+        let size = |file: &std::fs::File| file.metadata().map(|meta| meta.len());
+        let before = size(&file).map_err(|e| write_error(path, e))?;
+        // Invalid or oversized data is a decoding error, anything else a write error
+        let error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::InvalidData => HttpError::Parse(e.to_string()),
+            _ => write_error(path, e),
+        };
+        let mut decoder = decode::Decoder::new(coding, None, file);
+        let mut received = 0u64;
+        while let Some(chunk) = resp.chunk().await.map_err(|e| body_error(req, e))? {
+            decoder.write_all(&chunk).map_err(error)?;
+            received += chunk.len() as u64;
+            progress(received, total);
+        }
+        let mut file = decoder.finish().map_err(error)?;
+        file.flush().map_err(|e| write_error(path, e))?;
+        let written = size(&file).map_err(|e| write_error(path, e))? - before;
+        let timing = Timing {
+            ttfb,
+            total: started.elapsed(),
+        };
+        Ok((
+            HttpResponse::new(status, headers, Vec::new()).with_timing(timing),
+            written,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connection_refused_is_a_connect_error() {
+        // A port that was just free is very unlikely to be listening again
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+        let req = HttpRequest::builder(&url, Method::POST).build();
+        let err = ReqwestBackend::new().send(&req).await.unwrap_err();
+        let HttpError::Connect(failure) = err else {
+            panic!("Expected HttpError::Connect, got {err:?}");
+        };
+        assert_eq!((failure.method, failure.url), (Method::POST, url));
+        assert!(!failure.message.contains("error sending request"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_host_is_a_dns_error() {
+        let req = HttpRequest::builder("http://wave-test.invalid/", Method::GET).build();
+        let err = ReqwestBackend::new().send(&req).await.unwrap_err();
+        assert!(matches!(err, HttpError::Dns(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_resumed_download_appends_or_keeps_the_file() {
+        use crate::http::MockBackend;
+        let partial = |status, content_range: Option<&str>, body: &str| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = content_range {
+                headers.insert(::http::header::CONTENT_RANGE, range.parse().unwrap());
+            }
+            HttpResponse::new(status, headers, body.to_string())
+        };
+        let backend = MockBackend::new()
+            .respond(partial(206, Some("bytes 6-10/11"), "world"))
+            .respond(partial(503, None, "try later"))
+            .respond(partial(206, Some("bytes 0-10/11"), "hello world"));
+        let path = std::env::temp_dir().join(format!("wave_resume_{}.txt", std::process::id()));
+        std::fs::write(&path, "hello ").unwrap();
+        let req = HttpRequest::builder("http://example.com/file", Method::GET)
+            .header("Range", "bytes=6-")
+            .build();
+
+        let (_, written) = backend.download(&req, &path, &|_, _| {}).await.unwrap();
+        assert_eq!(written, 5);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world");
+        let (resp, written) = backend.download(&req, &path, &|_, _| {}).await.unwrap();
+        assert_eq!(
+            (resp.status, resp.body.as_str(), written),
+            (503, "try later", 0)
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world");
+        let err = backend.download(&req, &path, &|_, _| {}).await.unwrap_err();
+        assert!(err.to_string().contains("resume at byte 6"), "{err}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stalled_response_is_a_timeout() {
+        // Accepts the connection but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let backend = ReqwestBackend::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .read_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let req = HttpRequest::builder(&url, Method::GET).build();
+        let err = backend.send(&req).await.unwrap_err();
+        assert!(matches!(err, HttpError::Timeout(_)), "{err:?}");
+        drop(listener);
+    }
 }
@@ -0,0 +1,422 @@
+//! Recording responses to a cassette file and replaying them (`--record`,
+//! `--replay`)
+//!
+//! A cassette lists request/response pairs in the order they happened:
+//!
+//! ```yaml
+//! interactions:
+//! - request:
+//!     method: POST
+//!     url: https://api.example.com/users
+//!     body: '{"name":"Ann"}'
+//!   response:
+//!     status: 201
+//!     headers:
+//!       content-type: application/json
+//!       set-cookie:
+//!       - a=1
+//!       - b=2
+//!     body: '{"id":7}'
+//! ```
+//!
+//! Cassettes are YAML, or JSON when the file name ends in `.json`. Request
+//! headers are not recorded, so credentials stay out of the file; response
+//! bodies that are not UTF-8 are stored as `body_base64`.
+
+use crate::http::{
+    backend::HttpBackend,
+    error::{HttpError, RequestFailure},
+    request::HttpRequest,
+    response::HttpResponse,
+};
+use ::http::header::{HeaderMap, HeaderName, HeaderValue};
+use ::http::Method;
+use async_trait::async_trait;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The contents of a cassette file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+/// A request and the response it got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// The parts of a request a replayed request is matched on, and its body
+/// for reference
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+/// A response as stored in a cassette
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, HeaderValues>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+/// The values of a response header: one, or a list for repeated headers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HeaderValues {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Cassette {
+    /// Reads a cassette file
+    ///
+    /// # Errors
+    /// Returns `HttpError::Other` if the file cannot be read or parsed.
+    pub fn load(path: &Path) -> Result<Self, HttpError> {
+        let failed = |e: String| {
+            HttpError::Other(format!("Failed to read cassette '{}': {e}", path.display()))
+        };
+        let content = fs::read_to_string(path).map_err(|e| failed(e.to_string()))?;
+        if is_json(path) {
+            serde_json::from_str(&content).map_err(|e| failed(e.to_string()))
+        } else {
+            serde_yaml::from_str(&content).map_err(|e| failed(e.to_string()))
+        }
+    }
+
+    /// Writes the cassette, creating its directory if needed
+    ///
+    /// # Errors
+    /// Returns `HttpError::Other` if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), HttpError> {
+        let failed = |e: String| {
+            HttpError::Other(format!(
+                "Failed to write cassette '{}': {e}",
+                path.display()
+            ))
+        };
+        let content = if is_json(path) {
+            serde_json::to_string_pretty(self)
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string())
+        } else {
+            serde_yaml::to_string(self).map_err(|e| e.to_string())
+        }
+        .map_err(failed)?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| failed(e.to_string()))?;
+        }
+        fs::write(path, content).map_err(|e| failed(e.to_string()))
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+impl Interaction {
+    /// Records a request and its response
+    ///
+    /// A body the backend decompressed is recorded decoded, without the
+    /// `Content-Encoding` and `Content-Length` that described it compressed.
+    pub fn new(req: &HttpRequest, resp: &HttpResponse) -> Self {
+        let mut headers: BTreeMap<String, HeaderValues> = BTreeMap::new();
+        let decoded = resp.content_encoding.is_some();
+        for name in resp.headers.keys() {
+            if decoded && matches!(name.as_str(), "content-encoding" | "content-length") {
+                continue;
+            }
+            let mut values: Vec<String> = resp
+                .headers
+                .get_all(name)
+                .iter()
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                .collect();
+            let values = match values.len() {
+                1 => HeaderValues::One(values.remove(0)),
+                _ => HeaderValues::Many(values),
+            };
+            headers.insert(name.as_str().to_string(), values);
+        }
+        let (body, body_base64) = match std::str::from_utf8(&resp.bytes) {
+            Ok("") => (None, None),
+            Ok(text) => (Some(text.to_string()), None),
+            Err(_) => (
+                None,
+                Some(base64::engine::general_purpose::STANDARD.encode(&resp.bytes)),
+            ),
+        };
+        Interaction {
+            request: RecordedRequest {
+                method: req.method.to_string(),
+                url: req.url.clone(),
+                body: req.body.clone(),
+            },
+            response: RecordedResponse {
+                status: resp.status,
+                headers,
+                body,
+                body_base64,
+            },
+        }
+    }
+
+    /// Returns true if this interaction answers `req`: same method and URL
+    pub fn matches(&self, req: &HttpRequest) -> bool {
+        self.request.url == req.url
+            && Method::from_bytes(self.request.method.as_bytes()).ok() == Some(req.method.clone())
+    }
+
+    /// Builds the recorded response
+    ///
+    /// # Errors
+    /// Returns `HttpError::Parse` for header names or values, or a base64
+    /// body, that are not valid.
+    pub fn response(&self) -> Result<HttpResponse, HttpError> {
+        let recorded = &self.response;
+        let invalid = |e: String| HttpError::Parse(format!("Invalid cassette response: {e}"));
+        let mut headers = HeaderMap::new();
+        for (name, values) in &recorded.headers {
+            let name =
+                HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+            let values = match values {
+                HeaderValues::One(value) => std::slice::from_ref(value),
+                HeaderValues::Many(values) => values.as_slice(),
+            };
+            for value in values {
+                let value = HeaderValue::from_str(value).map_err(|e| invalid(e.to_string()))?;
+                headers.append(name.clone(), value);
+            }
+        }
+        let bytes = match (&recorded.body_base64, &recorded.body) {
+            (Some(encoded), _) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| invalid(e.to_string()))?,
+            (None, Some(body)) => body.clone().into_bytes(),
+            (None, None) => Vec::new(),
+        };
+        Ok(HttpResponse::new(recorded.status, headers, bytes))
+    }
+}
+
+/// Whether a [`CassetteBackend`] sends requests or answers them from the
+/// cassette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests through the wrapped backend and write each response to
+    /// the cassette, replacing what it held before
+    Record,
+    /// Answer requests from the cassette without sending anything
+    Replay,
+}
+
+/// Backend wrapper that records responses to a cassette, or replays them
+///
+/// When replaying, each request gets the first interaction with the same
+/// method and URL that has not been used yet, so a request sent twice gets
+/// both recorded responses in turn; once they are used up, the last one is
+/// repeated. Requests with no recorded interaction fail with
+/// `HttpError::Offline`, as nothing is sent.
+///
+/// When recording, the cassette is written after every response, so it is
+/// complete up to the last one even if the command is interrupted.
+///
+/// # Examples
+///
+/// ```
+/// use http::{HeaderMap, Method};
+/// use wave::http::{CassetteBackend, Client, HttpResponse, MockBackend};
+///
+/// # tokio_test::block_on(async {
+/// let path = std::env::temp_dir().join(format!("wave_doc_cassette_{}.yaml", std::process::id()));
+/// let server = MockBackend::new().respond(HttpResponse::new(200, HeaderMap::new(), "live"));
+/// let recorder = Client::new(CassetteBackend::record(server, &path));
+/// recorder.get("https://api.example.com/health").send().await.unwrap();
+///
+/// let replayer = Client::new(CassetteBackend::<MockBackend>::replay(&path).unwrap());
+/// let replayed = replayer.get("https://api.example.com/health").send().await.unwrap();
+/// assert_eq!(replayed.body, "live");
+/// assert!(replayer.get("https://api.example.com/other").send().await.is_err());
+/// # std::fs::remove_file(&path).unwrap();
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct CassetteBackend<B> {
+    /// The backend requests are sent through; `None` when replaying
+    inner: Option<B>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+    /// Which interactions have been replayed
+    used: Mutex<Vec<bool>>,
+}
+
+impl<B> CassetteBackend<B> {
+    /// Wraps `inner`, recording every response it gets to the cassette at
+    /// `path`
+    pub fn record(inner: B, path: impl Into<PathBuf>) -> Self {
+        CassetteBackend {
+            inner: Some(inner),
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+            used: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answers requests from the cassette at `path`
+    ///
+    /// # Errors
+    /// Returns `HttpError::Other` if the cassette cannot be read or parsed.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self, HttpError> {
+        let path = path.into();
+        let cassette = Cassette::load(&path)?;
+        let used = vec![false; cassette.interactions.len()];
+        Ok(CassetteBackend {
+            inner: None,
+            path,
+            cassette: Mutex::new(cassette),
+            used: Mutex::new(used),
+        })
+    }
+
+    /// Whether requests are recorded or replayed
+    pub fn mode(&self) -> CassetteMode {
+        match self.inner {
+            Some(_) => CassetteMode::Record,
+            None => CassetteMode::Replay,
+        }
+    }
+
+    /// Finds the recorded response for a request
+    fn replayed(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        let cassette = self.cassette.lock().unwrap();
+        let mut used = self.used.lock().unwrap();
+        let matching: Vec<usize> = (0..cassette.interactions.len())
+            .filter(|&i| cassette.interactions[i].matches(req))
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&i| !used[i])
+            .or_else(|| matching.last().copied())
+            .ok_or_else(|| {
+                HttpError::Offline(RequestFailure::new(
+                    req.method.clone(),
+                    &req.url,
+                    format!("no response recorded in cassette '{}'", self.path.display()),
+                ))
+            })?;
+        used[index] = true;
+        cassette.interactions[index].response()
+    }
+}
+
+#[async_trait]
+impl<B: HttpBackend + Send + Sync> HttpBackend for CassetteBackend<B> {
+    async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        let Some(inner) = &self.inner else {
+            return self.replayed(req);
+        };
+        let resp = inner.send(req).await?;
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(Interaction::new(req, &resp));
+        cassette.save(&self.path)?;
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::MockBackend;
+    use tokio_test::block_on;
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::new(url, Method::GET, None, HeaderMap::new())
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wave_cassette_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_record_then_replay_in_order() {
+        let path = temp_path("order.json");
+        let mut headers = HeaderMap::new();
+        headers.append("set-cookie", "a=1".parse().unwrap());
+        headers.append("set-cookie", "b=2".parse().unwrap());
+        let server = MockBackend::new()
+            .respond(HttpResponse::new(202, HeaderMap::new(), "pending"))
+            .respond(HttpResponse::new(200, headers.clone(), vec![0xff, 0x00]));
+        let recorder = CassetteBackend::record(server, &path);
+        assert_eq!(recorder.mode(), CassetteMode::Record);
+        block_on(recorder.send(&get("http://a/job"))).unwrap();
+        block_on(recorder.send(&get("http://a/job"))).unwrap();
+
+        let replayer = CassetteBackend::<MockBackend>::replay(&path).unwrap();
+        assert_eq!(replayer.mode(), CassetteMode::Replay);
+        let first = block_on(replayer.send(&get("http://a/job"))).unwrap();
+        assert_eq!((first.status, first.body.as_str()), (202, "pending"));
+        let second = block_on(replayer.send(&get("http://a/job"))).unwrap();
+        assert_eq!(second.bytes, vec![0xff, 0x00]);
+        assert_eq!(second.headers, headers);
+        // Used up, the last one repeats
+        let third = block_on(replayer.send(&get("http://a/job"))).unwrap();
+        assert_eq!(third.status, 200);
+
+        let post = HttpRequest::new("http://a/job", Method::POST, None, HeaderMap::new());
+        let missing = block_on(replayer.send(&post)).unwrap_err().to_string();
+        assert!(missing.contains("POST http://a/job: no response recorded in cassette"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_cassette_yaml_format() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        let req = HttpRequest::new(
+            "http://a/users",
+            Method::POST,
+            Some(r#"{"name":"Ann"}"#.to_string()),
+            HeaderMap::new(),
+        );
+        let resp = HttpResponse::new(201, headers, r#"{"id":7}"#);
+        let cassette = Cassette {
+            interactions: vec![Interaction::new(&req, &resp)],
+        };
+        let path = temp_path("format.yaml");
+        cassette.save(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            r#"interactions:
+- request:
+    method: POST
+    url: http://a/users
+    body: '{"name":"Ann"}'
+  response:
+    status: 201
+    headers:
+      content-type: application/json
+    body: '{"id":7}'
+"#
+        );
+        assert_eq!(Cassette::load(&path).unwrap(), cassette);
+        let _ = fs::remove_file(&path);
+        assert!(Cassette::load(&path).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Decoding of response bodies into text
+//!
+//! The text of a body is decoded with the `charset` parameter of its
+//! `Content-Type`, e.g. `text/plain; charset=iso-8859-1`, or as UTF-8 when
+//! none is given or the charset is unknown. A byte order mark wins over the
+//! header, and malformed sequences are replaced with U+FFFD. Labels follow
+//! the WHATWG Encoding Standard, so `latin1` and `utf8` are understood too.
+
+use encoding_rs::Encoding;
+use std::fmt;
+use std::str::FromStr;
+
+/// A character encoding that response bodies can be decoded with
+///
+/// # Examples
+///
+/// ```
+/// use wave::http::Charset;
+///
+/// let latin1: Charset = "latin1".parse()?;
+/// assert_eq!(latin1.decode(b"caf\xe9"), "café");
+/// assert_eq!(latin1.to_string(), "windows-1252");
+/// assert!("klingon".parse::<Charset>().is_err());
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Charset(&'static Encoding);
+
+impl Charset {
+    /// UTF-8, used when a body does not name its charset
+    pub const UTF_8: Charset = Charset(encoding_rs::UTF_8);
+
+    /// Returns the charset named by a `Content-Type` value, if it names a known one
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::Charset;
+    ///
+    /// let charset = Charset::from_content_type("text/plain; Charset=\"ISO-8859-1\"");
+    /// assert_eq!(charset.map(|c| c.to_string()).as_deref(), Some("windows-1252"));
+    /// assert_eq!(Charset::from_content_type("application/json"), None);
+    /// ```
+    pub fn from_content_type(content_type: &str) -> Option<Charset> {
+        content_type.split(';').skip(1).find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            if !name.trim().eq_ignore_ascii_case("charset") {
+                return None;
+            }
+            let label = value.trim().trim_matches('"');
+            let charset = label.parse().ok();
+            if charset.is_none() {
+                tracing::debug!(charset = label, "unknown charset, decoding as UTF-8");
+            }
+            charset
+        })
+    }
+
+    /// Decodes a body, replacing malformed sequences
+    pub fn decode(self, bytes: &[u8]) -> String {
+        self.0.decode(bytes).0.into_owned()
+    }
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        Charset::UTF_8
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.name().to_ascii_lowercase())
+    }
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        Encoding::for_label(label.trim().as_bytes())
+            .map(Charset)
+            .ok_or_else(|| format!("unknown charset '{label}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decoding_by_charset() {
+        let shift_jis: Charset = "Shift_JIS".parse().unwrap();
+        assert_eq!(shift_jis.decode(b"\x82\xa0"), "あ");
+        let utf16 = Charset::from_content_type("application/json;charset=utf-16le").unwrap();
+        assert_eq!(utf16.decode(b"{\0}\0"), "{}");
+        // A byte order mark wins over the charset
+        assert_eq!(shift_jis.decode(b"\xef\xbb\xbfok"), "ok");
+        assert_eq!(Charset::UTF_8.decode(b"\xffok"), "\u{fffd}ok");
+        assert_eq!(Charset::from_content_type("text/plain; charset=nope"), None);
+    }
+}
@@ -1,6 +1,14 @@
 use crate::http::{
-    backend::HttpBackend, error::HttpError, request::HttpRequest, response::HttpResponse,
+    auth::Auth,
+    backend::{HttpBackend, ProgressFn},
+    error::HttpError,
+    request::{HttpRequest, RequestBody, RequestBuilder},
+    response::HttpResponse,
 };
+use crate::KeyValuePairs;
+use ::http::{HeaderMap, Method};
+use std::path::Path;
+use std::time::Duration;
 
 /// HTTP client generic over backend
 ///
@@ -15,7 +23,7 @@ use crate::http::{
 /// use http::{HeaderMap, Method};
 ///
 /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-/// let client = Client::new(ReqwestBackend);
+/// let client = Client::new(ReqwestBackend::new());
 /// let request = HttpRequest::new(
 ///     "https://httpbin.org/get",
 ///     Method::GET,
@@ -56,7 +64,7 @@ impl<B: HttpBackend + Send + Sync> Client<B> {
     /// use http::{HeaderMap, Method};
     ///
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-    /// let client = Client::new(ReqwestBackend);
+    /// let client = Client::new(ReqwestBackend::new());
     /// let request = HttpRequest::builder("https://httpbin.org/get", Method::GET)
     ///     .header("User-Agent", "wave/1.0")
     ///     .build();
@@ -78,62 +86,215 @@ impl<B: HttpBackend + Send + Sync> Client<B> {
     pub async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
         self.backend.send(req).await
     }
+
+    /// Sends an HTTP request and streams the response body into a file
+    ///
+    /// `progress` is called after every chunk with the bytes written so far
+    /// and the expected total, if known. Returns the response (without a
+    /// body) and the number of bytes written.
+    pub async fn download(
+        &self,
+        req: &HttpRequest,
+        path: &Path,
+        progress: ProgressFn<'_>,
+    ) -> Result<(HttpResponse, u64), HttpError> {
+        self.backend.download(req, path, progress).await
+    }
+
+    /// Starts a request with any method, sent with [`ClientRequest::send`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::{Client, ReqwestBackend};
+    /// use http::Method;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new(ReqwestBackend::new());
+    /// let response = client
+    ///     .request(Method::from_bytes(b"PURGE")?, "https://cdn.example.com/app.js")
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request(&self, method: Method, url: impl Into<String>) -> ClientRequest<'_, B> {
+        ClientRequest {
+            client: self,
+            builder: RequestBuilder::new(url, method),
+            error: None,
+        }
+    }
+
+    /// Starts a GET request
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::{Client, ReqwestBackend};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new(ReqwestBackend::new());
+    /// let response = client
+    ///     .get("https://httpbin.org/get")
+    ///     .header("Accept", "application/json")
+    ///     .send()
+    ///     .await?;
+    /// println!("Status: {}", response.status);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get(&self, url: impl Into<String>) -> ClientRequest<'_, B> {
+        self.request(Method::GET, url)
+    }
+
+    /// Starts a POST request
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::{Client, ReqwestBackend};
+    /// use serde_json::json;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new(ReqwestBackend::new());
+    /// let response = client
+    ///     .post("https://httpbin.org/post")
+    ///     .json(&json!({"name": "alice"}))
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn post(&self, url: impl Into<String>) -> ClientRequest<'_, B> {
+        self.request(Method::POST, url)
+    }
+
+    /// Starts a PUT request
+    pub fn put(&self, url: impl Into<String>) -> ClientRequest<'_, B> {
+        self.request(Method::PUT, url)
+    }
+
+    /// Starts a PATCH request
+    pub fn patch(&self, url: impl Into<String>) -> ClientRequest<'_, B> {
+        self.request(Method::PATCH, url)
+    }
+
+    /// Starts a DELETE request
+    pub fn delete(&self, url: impl Into<String>) -> ClientRequest<'_, B> {
+        self.request(Method::DELETE, url)
+    }
+
+    /// Starts a HEAD request
+    pub fn head(&self, url: impl Into<String>) -> ClientRequest<'_, B> {
+        self.request(Method::HEAD, url)
+    }
+
+    /// Starts an OPTIONS request
+    pub fn options(&self, url: impl Into<String>) -> ClientRequest<'_, B> {
+        self.request(Method::OPTIONS, url)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ::http::{HeaderMap, Method};
-    use async_trait::async_trait;
-    use std::sync::{Arc, Mutex};
-    use tokio_test::block_on;
+/// A request being prepared for a [`Client`], created by [`Client::get`],
+/// [`Client::post`] and the other method helpers
+///
+/// Wraps a [`RequestBuilder`] and sends the request through the client that
+/// created it. Errors from preparing the request, such as a body that cannot
+/// be serialized to JSON, are returned by [`ClientRequest::send`].
+pub struct ClientRequest<'a, B: HttpBackend + Send + Sync> {
+    client: &'a Client<B>,
+    builder: RequestBuilder,
+    error: Option<HttpError>,
+}
 
-    struct MockBackend {
-        pub last_request: Mutex<Option<HttpRequest>>,
-        pub response: HttpResponse,
-        pub error: Option<HttpError>,
-    }
-
-    #[async_trait]
-    impl HttpBackend for MockBackend {
-        async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
-            let mut last = self.last_request.lock().unwrap();
-            *last = Some(req.clone());
-            if let Some(ref err) = self.error {
-                Err(err.clone())
-            } else {
-                Ok(self.response.clone())
-            }
+impl<B: HttpBackend + Send + Sync> ClientRequest<'_, B> {
+    /// Adds a header, replacing an earlier one with the same name
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.builder = self.builder.header(key, value);
+        self
+    }
+
+    /// Adds another value for a header, keeping values added earlier
+    pub fn append_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.builder = self.builder.append_header(key, value);
+        self
+    }
+
+    /// Adds multiple headers
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.builder = self.builder.headers(headers);
+        self
+    }
+
+    /// Sets the `Authorization` header
+    pub fn auth(mut self, auth: Auth) -> Self {
+        self.builder = self.builder.auth(auth);
+        self
+    }
+
+    /// Sets the request body
+    pub fn body(mut self, body: RequestBody) -> Self {
+        self.builder = self.builder.body(body);
+        self
+    }
+
+    /// Sends `data` as a JSON body
+    pub fn json<T: serde::Serialize>(mut self, data: &T) -> Self {
+        match RequestBody::json(data) {
+            Ok(body) => self.builder = self.builder.body(body),
+            Err(e) => self.error = Some(e),
         }
+        self
+    }
+
+    /// Sends `data` as a URL-encoded form body
+    pub fn form(self, data: KeyValuePairs) -> Self {
+        self.body(RequestBody::form(data))
     }
 
-    // Implementing HttpBackend for Arc<MockBackend> allows us to pass an Arc-wrapped backend to the Client,
-    // which takes ownership of its backend. This enables tests to retain a reference to the same backend instance
-    // even after passing it to the client, so we can inspect and assert on properties like `last_request` after
-    // the client has made a request. Without this, tests could only check the response, not verify that the client
-    // constructed and sent the correct request (URL, method, headers, body).
-    #[async_trait]
-    impl HttpBackend for Arc<MockBackend> {
-        async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
-            self.as_ref().send(req).await
+    /// Sends `text` as a plain text body
+    pub fn text(self, text: impl Into<String>) -> Self {
+        self.body(RequestBody::text(text.into()))
+    }
+
+    /// Sets a timeout for the whole request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.builder = self.builder.timeout(timeout);
+        self
+    }
+
+    /// Builds the request without sending it
+    pub fn build(self) -> Result<HttpRequest, HttpError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.builder.build()),
         }
     }
 
+    /// Sends the request through the client and returns the response
+    pub async fn send(self) -> Result<HttpResponse, HttpError> {
+        let client = self.client;
+        let req = self.build()?;
+        client.send(&req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{MockBackend, RequestFailure};
+    use ::http::{HeaderMap, Method};
+    use std::sync::{Arc, Mutex};
+    use tokio_test::block_on;
+
     #[test]
     fn test_client_get_calls_backend_and_returns_response() {
         let mut expected_headers = HeaderMap::new();
         expected_headers.insert("x-resp", "ok".parse().unwrap());
-        let expected_response = HttpResponse {
-            status: 200,
-            headers: expected_headers.clone(),
-            body: "test body".to_string(),
-        };
-
-        let backend = Arc::new(MockBackend {
-            last_request: Mutex::new(None),
-            response: expected_response.clone(),
-            error: None,
-        });
+        let expected_response = HttpResponse::new(200, expected_headers.clone(), "test body");
+
+        let backend = Arc::new(MockBackend::new().respond(expected_response.clone()));
 
         let client = Client::new(backend.clone());
         let req = HttpRequest::new("http://example.com", Method::GET, None, HeaderMap::new());
@@ -146,24 +307,18 @@ mod tests {
         assert_eq!(response.body, "test body");
 
         // Verify the backend received the correct request
-        let last_req = backend.last_request.lock().unwrap();
-        assert!(last_req.is_some());
-        let sent_req = last_req.as_ref().unwrap();
+        let sent_req = backend.last_request().unwrap();
         assert_eq!(sent_req.url, "http://example.com");
         assert_eq!(sent_req.method, Method::GET);
     }
 
     #[test]
     fn test_client_post_calls_backend_and_returns_response() {
-        let backend = Arc::new(MockBackend {
-            last_request: Mutex::new(None),
-            response: HttpResponse {
-                status: 201,
-                headers: HeaderMap::new(),
-                body: "created".to_string(),
-            },
-            error: None,
-        });
+        let backend = Arc::new(MockBackend::new().respond(HttpResponse::new(
+            201,
+            HeaderMap::new(),
+            "created",
+        )));
 
         let client = Client::new(backend.clone());
         let mut headers = HeaderMap::new();
@@ -181,8 +336,7 @@ mod tests {
         assert_eq!(response.status, 201);
 
         // Verify the backend received the POST request with body
-        let last_req = backend.last_request.lock().unwrap();
-        let sent_req = last_req.as_ref().unwrap();
+        let sent_req = backend.last_request().unwrap();
         assert_eq!(sent_req.method, Method::POST);
         assert_eq!(sent_req.body, Some(r#"{"data":"value"}"#.to_string()));
         assert_eq!(
@@ -191,17 +345,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_method_helpers_send_through_the_client() {
+        let backend = Arc::new(MockBackend::new().respond(HttpResponse::new(
+            201,
+            HeaderMap::new(),
+            "created",
+        )));
+        let client = Client::new(backend.clone());
+
+        let resp = block_on(
+            client
+                .post("http://example.com/users")
+                .header("X-Trace", "1")
+                .json(&serde_json::json!({"name": "alice"}))
+                .send(),
+        )
+        .unwrap();
+        assert_eq!(resp.status, 201);
+        let sent = backend.last_request().unwrap();
+        assert_eq!(sent.method, Method::POST);
+        assert_eq!(sent.url, "http://example.com/users");
+        assert_eq!(sent.headers["x-trace"], "1");
+        assert_eq!(sent.headers["content-type"], "application/json");
+        assert_eq!(sent.body.as_deref(), Some(r#"{"name":"alice"}"#));
+
+        for (req, method) in [
+            (client.get("http://example.com"), Method::GET),
+            (client.put("http://example.com"), Method::PUT),
+            (client.patch("http://example.com"), Method::PATCH),
+            (client.delete("http://example.com"), Method::DELETE),
+            (client.head("http://example.com"), Method::HEAD),
+            (client.options("http://example.com"), Method::OPTIONS),
+        ] {
+            assert_eq!(req.build().unwrap().method, method);
+        }
+    }
+
+    #[test]
+    fn test_json_errors_are_returned_by_send() {
+        struct Unserializable;
+        impl serde::Serialize for Unserializable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("nope"))
+            }
+        }
+        let backend =
+            Arc::new(MockBackend::new().respond(HttpResponse::new(200, HeaderMap::new(), "")));
+        let client = Client::new(backend.clone());
+        let err = block_on(
+            client
+                .post("http://example.com")
+                .json(&Unserializable)
+                .send(),
+        );
+        assert!(matches!(err, Err(HttpError::Parse(_))));
+        assert!(backend.last_request().is_none());
+    }
+
     #[test]
     fn test_client_handles_backend_error() {
-        let backend = Arc::new(MockBackend {
-            last_request: Mutex::new(None),
-            response: HttpResponse {
-                status: 500,
-                headers: HeaderMap::new(),
-                body: "".to_string(),
-            },
-            error: Some(HttpError::Network("Connection failed".to_string())),
-        });
+        let backend = MockBackend::new().fail(HttpError::Connect(RequestFailure::new(
+            Method::GET,
+            "http://example.com",
+            "Connection failed",
+        )));
 
         let client = Client::new(backend);
         let req = HttpRequest::new("http://example.com", Method::GET, None, HeaderMap::new());
@@ -209,8 +417,37 @@ mod tests {
         let result = block_on(client.send(&req));
         assert!(result.is_err());
         match result.unwrap_err() {
-            HttpError::Network(msg) => assert_eq!(msg, "Connection failed"),
-            _ => panic!("Expected HttpError::Network"),
+            HttpError::Connect(failure) => assert_eq!(failure.message, "Connection failed"),
+            _ => panic!("Expected HttpError::Connect"),
         }
     }
+
+    #[test]
+    fn test_client_download_writes_body_and_reports_progress() {
+        let raw = vec![0x89, b'P', b'N', b'G', 0x00, 0xff];
+        let backend =
+            MockBackend::new().respond(HttpResponse::new(200, HeaderMap::new(), raw.clone()));
+        let client = Client::new(backend);
+        let req = HttpRequest::new(
+            "http://example.com/file",
+            Method::GET,
+            None,
+            HeaderMap::new(),
+        );
+        let path = std::env::temp_dir().join(format!("wave_download_{}.bin", std::process::id()));
+        let reported = Mutex::new(Vec::new());
+        let progress = |done: u64, total: Option<u64>| reported.lock().unwrap().push((done, total));
+
+        let (resp, written) = block_on(client.download(&req, &path, &progress)).unwrap();
+        assert_eq!(resp.status, 200);
+        assert!(resp.bytes.is_empty());
+        assert_eq!(written, raw.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), raw);
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![(raw.len() as u64, Some(raw.len() as u64))]
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,140 @@
+use crate::http::request::{HttpRequest, MultipartPart};
+use ::http::Method;
+
+/// Quotes a value for safe use as a single POSIX shell word
+///
+/// Wraps the value in single quotes and escapes embedded single quotes,
+/// so the result can be pasted into any POSIX shell unchanged.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Converts a request into an equivalent `curl` command
+///
+/// The command includes the method, URL, every header, the body and the
+/// timeout, so it can be shared with people who don't use wave. Arguments
+/// are shell-quoted and placed on separate lines for readability.
+///
+/// # Examples
+///
+/// ```
+/// use wave::http::{to_curl, HttpRequest};
+/// use http::Method;
+///
+/// let req = HttpRequest::builder("https://api.example.com/users", Method::DELETE)
+///     .header("Authorization", "Bearer token123")
+///     .build();
+///
+/// assert_eq!(
+///     to_curl(&req),
+///     "curl -X DELETE 'https://api.example.com/users' \\\n  -H 'authorization: Bearer token123'"
+/// );
+/// ```
+pub fn to_curl(req: &HttpRequest) -> String {
+    let mut first = String::from("curl");
+    match req.method {
+        Method::GET => {}
+        Method::HEAD => first.push_str(" --head"),
+        ref method => {
+            first.push_str(" -X ");
+            first.push_str(method.as_str());
+        }
+    }
+    first.push(' ');
+    first.push_str(&shell_quote(&req.url));
+
+    let mut parts = vec![first];
+    for (name, value) in &req.headers {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        parts.push(format!("-H {}", shell_quote(&format!("{name}: {value}"))));
+    }
+    if let Some(body) = &req.body {
+        parts.push(format!("--data-raw {}", shell_quote(body)));
+    }
+    if let Some(path) = &req.body_file {
+        parts.push(format!(
+            "--data-binary {}",
+            shell_quote(&format!("@{}", path.display()))
+        ));
+    }
+    for part in req.multipart.iter().flatten() {
+        let field = match part {
+            MultipartPart::Text { name, value } => format!("{name}={value}"),
+            MultipartPart::File { name, path } => format!("{name}=@{}", path.display()),
+        };
+        parts.push(format!("-F {}", shell_quote(&field)));
+    }
+    if let Some(timeout) = req.timeout {
+        parts.push(format!("--max-time {}", timeout.as_secs_f64()));
+    }
+    parts.join(" \\\n  ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::RequestBody;
+    use std::time::Duration;
+
+    #[test]
+    fn test_to_curl_get() {
+        let req = HttpRequest::builder("https://example.com/get", Method::GET).build();
+        assert_eq!(to_curl(&req), "curl 'https://example.com/get'");
+    }
+
+    #[test]
+    fn test_to_curl_post_with_body_and_timeout() {
+        let req = HttpRequest::builder("https://example.com/post", Method::POST)
+            .body(RequestBody::json(&serde_json::json!({"name": "O'Brien"})).unwrap())
+            .timeout(Duration::from_secs(5))
+            .build();
+        assert_eq!(
+            to_curl(&req),
+            "curl -X POST 'https://example.com/post' \\\n  \
+             -H 'content-type: application/json' \\\n  \
+             --data-raw '{\"name\":\"O'\\''Brien\"}' \\\n  \
+             --max-time 5"
+        );
+    }
+
+    #[test]
+    fn test_to_curl_file_body() {
+        let req = HttpRequest::builder("https://example.com/avatar", Method::PUT)
+            .body(RequestBody::file("./avatar.png"))
+            .build();
+        assert_eq!(
+            to_curl(&req),
+            "curl -X PUT 'https://example.com/avatar' \\\n  \
+             -H 'content-type: application/octet-stream' \\\n  \
+             --data-binary '@./avatar.png'"
+        );
+    }
+
+    #[test]
+    fn test_to_curl_multipart() {
+        let req = HttpRequest::builder("https://example.com/upload", Method::POST)
+            .body(RequestBody::multipart(vec![
+                MultipartPart::Text {
+                    name: "title".to_string(),
+                    value: "Holiday".to_string(),
+                },
+                MultipartPart::File {
+                    name: "photo".to_string(),
+                    path: "./photo.png".into(),
+                },
+            ]))
+            .build();
+        assert_eq!(
+            to_curl(&req),
+            "curl -X POST 'https://example.com/upload' \\\n  \
+             -F 'title=Holiday' \\\n  \
+             -F 'photo=@./photo.png'"
+        );
+    }
+
+    #[test]
+    fn test_to_curl_head() {
+        let req = HttpRequest::builder("https://example.com", Method::HEAD).build();
+        assert_eq!(to_curl(&req), "curl --head 'https://example.com'");
+    }
+}
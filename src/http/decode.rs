@@ -0,0 +1,917 @@
+//! Decoding of compressed response bodies
+//!
+//! Servers compress a body when the request's `Accept-Encoding` allows it
+//! and say so in `Content-Encoding`. The `gzip` (or `x-gzip`) and `deflate`
+//! codings are decoded here, with a small implementation of DEFLATE (RFC
+//! 1951) and its gzip (RFC 1952) and zlib (RFC 1950) wrappers. Other codings,
+//! such as `br` and `zstd`, are not supported and such bodies are left as
+//! they were received; `--compressed` does not ask for them.
+//!
+//! A [`Decoder`] takes the body piece by piece as it arrives, so a download
+//! is decoded on its way to disk. Bodies decoded in memory with [`decode`]
+//! are limited to [`MAX_DECODED_SIZE`], so a small body that expands
+//! enormously (a "compression bomb") cannot use up memory.
+
+/// Largest body [`decode`] decodes in memory, 256 MiB
+pub const MAX_DECODED_SIZE: u64 = 256 * 1024 * 1024;
+
+use std::io::{self, Write};
+
+/// Error of a step that ran out of input; the step is tried again when
+/// more arrives
+const END: &str = "unexpected end of data";
+
+/// Decoded bytes a DEFLATE distance may refer back to
+const WINDOW: usize = 32 * 1024;
+
+/// Decodes a body sent with the given `Content-Encoding` header value
+///
+/// Returns `Ok(None)` when there is nothing to decode, or when one of the
+/// codings is not supported, in which case the body should be kept as it is.
+///
+/// # Errors
+/// Returns a description of the problem when the body is not valid data in
+/// the coding it claims, or decodes to more than [`MAX_DECODED_SIZE`].
+///
+/// # Examples
+/// ```
+/// use wave::http::decode::decode;
+///
+/// // "hi" compressed with gzip
+/// let gzip = [
+///     0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 2, 3, 0xcb, 0xc8, 4, 0, 0xac, 0x2a, 0x93, 0xd8, 2, 0, 0, 0,
+/// ];
+/// assert_eq!(decode("gzip", &gzip), Ok(Some(b"hi".to_vec())));
+/// assert_eq!(decode("identity", b"hi"), Ok(None));
+/// assert_eq!(decode("br", b"\x0b\x00\x80hi\x03"), Ok(None));
+/// assert!(decode("gzip", b"hi").is_err());
+/// ```
+pub fn decode(content_encoding: &str, bytes: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    if !can_decode(content_encoding) {
+        return Ok(None);
+    }
+    let mut decoder = Decoder::new(content_encoding, Some(MAX_DECODED_SIZE), Vec::new());
+    decoder
+        .write_all(bytes)
+        .and_then(|()| decoder.finish())
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Returns true if [`decode`] would decode a body with this `Content-Encoding`
+///
+/// # Examples
+/// ```
+/// use wave::http::decode::can_decode;
+///
+/// assert!(can_decode("GZIP"));
+/// assert!(can_decode("deflate, identity"));
+/// assert!(!can_decode("gzip, br"));
+/// assert!(!can_decode("identity"));
+/// ```
+pub fn can_decode(content_encoding: &str) -> bool {
+    let codings = codings(content_encoding);
+    !codings.is_empty()
+        && codings
+            .iter()
+            .all(|coding| matches!(coding.as_str(), "gzip" | "x-gzip" | "deflate"))
+}
+
+/// Returns the codings of a `Content-Encoding` value, lowercased and without
+/// `identity`
+fn codings(content_encoding: &str) -> Vec<String> {
+    content_encoding
+        .split(',')
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty() && coding != "identity")
+        .collect()
+}
+
+/// Decodes a compressed body piece by piece, as it arrives, writing the
+/// decoded bytes to `out`
+///
+/// Bodies with no `Content-Encoding`, or one [`can_decode`] rejects, are
+/// passed through as they are. Decoded bytes are written as they are found,
+/// so memory use stays small however much a body expands. Invalid data and a
+/// body decoding to more than the limit fail with
+/// [`ErrorKind::InvalidData`](io::ErrorKind::InvalidData).
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use wave::http::decode::Decoder;
+///
+/// // "hi" compressed with deflate, in two pieces
+/// let mut decoder = Decoder::new("deflate", None, Vec::new());
+/// decoder.write_all(&[0x78, 0x9c, 0xcb, 0xc8])?;
+/// decoder.write_all(&[4, 0, 1, 0x3b, 0, 0xd2])?;
+/// assert_eq!(decoder.finish()?, b"hi");
+///
+/// let mut limited = Decoder::new("deflate", Some(1), Vec::new());
+/// assert!(limited.write_all(&[0x78, 0x9c, 0xcb, 0xc8, 4, 0, 1, 0x3b, 0, 0xd2]).is_err());
+///
+/// let mut unsupported = Decoder::new("br", None, Vec::new());
+/// unsupported.write_all(b"as is")?;
+/// assert_eq!(unsupported.finish()?, b"as is");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Decoder<W: Write> {
+    /// One stage per coding, in the order they are undone
+    stages: Vec<Stage>,
+    out: W,
+}
+
+impl<W: Write> Decoder<W> {
+    /// Starts decoding a body with the given `Content-Encoding`
+    ///
+    /// With a `limit`, decoding fails once any coding decodes to more bytes.
+    pub fn new(content_encoding: &str, limit: Option<u64>, out: W) -> Decoder<W> {
+        // Codings are listed in the order they were applied
+        let stages = match can_decode(content_encoding) {
+            true => codings(content_encoding)
+                .into_iter()
+                .rev()
+                .map(|coding| Stage::new(coding, limit))
+                .collect(),
+            false => Vec::new(),
+        };
+        Decoder { stages, out }
+    }
+
+    /// Ends the body, writes the last decoded bytes and returns `out`
+    ///
+    /// # Errors
+    /// Fails if the body is invalid or was cut short, or writing fails.
+    pub fn finish(mut self) -> io::Result<W> {
+        feed(&mut self.stages, &[], true, &mut self.out)?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> Write for Decoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        feed(&mut self.stages, buf, false, &mut self.out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Passes `data` through the first of `stages`, its output through the
+/// next one, and so on into `out`; with `last`, every stage is ended
+fn feed(stages: &mut [Stage], data: &[u8], last: bool, out: &mut dyn Write) -> io::Result<()> {
+    let Some((stage, rest)) = stages.split_first_mut() else {
+        return out.write_all(data);
+    };
+    stage
+        .push(data, last, &mut |decoded| feed(rest, decoded, false, out))
+        .and_then(|()| match last {
+            true => feed(rest, &[], true, out),
+            false => Ok(()),
+        })
+}
+
+/// Decoding of one coding
+struct Stage {
+    coding: String,
+    state: State,
+    bits: Bits,
+    /// The last [`WINDOW`] decoded bytes already returned, then the ones not
+    /// returned yet
+    window: Vec<u8>,
+    /// Start of the bytes in `window` not returned yet
+    returned: usize,
+    /// CRC-32 (gzip) or Adler-32 (zlib) of the current member's output
+    check: u32,
+    /// Length of the current member's output, modulo 2^32 as gzip records it
+    member_len: u32,
+    decoded: u64,
+    limit: Option<u64>,
+    inflate: Inflate,
+}
+
+/// Where a [`Stage`] is in its data
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    GzipHeader,
+    /// After a gzip member: another one may follow
+    GzipMemberEnd,
+    GzipTrailer,
+    /// Before `deflate` data, which may or may not have a zlib header
+    DeflateStart,
+    ZlibTrailer,
+    /// In DEFLATE data; `zlib` tells what follows it
+    Inflate {
+        zlib: bool,
+    },
+    Done,
+}
+
+impl Stage {
+    fn new(coding: String, limit: Option<u64>) -> Stage {
+        let state = match coding.as_str() {
+            "deflate" => State::DeflateStart,
+            _ => State::GzipHeader,
+        };
+        Stage {
+            coding,
+            state,
+            bits: Bits::default(),
+            window: Vec::new(),
+            returned: 0,
+            check: 0,
+            member_len: 0,
+            decoded: 0,
+            limit,
+            inflate: Inflate::default(),
+        }
+    }
+
+    /// Adds input and decodes as much of it as possible, passing the decoded
+    /// bytes to `emit`; with `last` set, the input is complete
+    fn push(
+        &mut self,
+        data: &[u8],
+        last: bool,
+        emit: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        self.bits.data.extend_from_slice(data);
+        loop {
+            let saved = self.bits.checkpoint();
+            let before = self.window.len();
+            match self.step(last) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) if e == END && !last => {
+                    self.bits.restore(saved);
+                    break;
+                }
+                Err(e) => return Err(invalid(format!("invalid {} data: {e}", self.coding))),
+            }
+            let produced = &self.window[before..];
+            self.check = match self.state {
+                State::Inflate { zlib: true } | State::ZlibTrailer => adler32(self.check, produced),
+                _ => crc32(self.check, produced),
+            };
+            self.member_len = self.member_len.wrapping_add(produced.len() as u32);
+            self.decoded += produced.len() as u64;
+            if let Some(limit) = self.limit.filter(|limit| self.decoded > *limit) {
+                return Err(invalid(format!(
+                    "{} body decodes to more than {limit} bytes",
+                    self.coding
+                )));
+            }
+            if self.window.len() - self.returned >= WINDOW {
+                self.emit(emit)?;
+            }
+        }
+        self.bits.compact();
+        self.emit(emit)
+    }
+
+    /// Passes the decoded bytes not passed on yet to `emit`, keeping the last
+    /// [`WINDOW`] bytes for later distances to refer to
+    fn emit(&mut self, emit: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        if self.returned < self.window.len() {
+            emit(&self.window[self.returned..])?;
+        }
+        let keep = self.window.len().saturating_sub(WINDOW);
+        self.window.drain(..keep);
+        self.returned = self.window.len();
+        Ok(())
+    }
+
+    /// Takes one step through the data, returning false when there is
+    /// nothing more to do yet
+    ///
+    /// A step that runs out of input fails with [`END`] before changing
+    /// anything but the read position, so it can be tried again.
+    fn step(&mut self, last: bool) -> Result<bool, String> {
+        match self.state {
+            State::GzipHeader => {
+                let len = gzip_header_len(self.bits.rest())?;
+                self.bits.pos += len;
+                self.start_member(false);
+            }
+            State::GzipMemberEnd => {
+                if self.bits.rest().is_empty() {
+                    if last {
+                        self.state = State::Done;
+                    }
+                    return Ok(false);
+                }
+                self.state = State::GzipHeader;
+            }
+            State::GzipTrailer => {
+                let trailer = self.bits.bytes(8)?;
+                if u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]])
+                    != self.check
+                {
+                    return Err("CRC mismatch".to_string());
+                }
+                if u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]])
+                    != self.member_len
+                {
+                    return Err("length mismatch".to_string());
+                }
+                self.state = State::GzipMemberEnd;
+            }
+            State::DeflateStart => {
+                let data = self.bits.rest();
+                if data.len() < 2 && !last {
+                    return Err(END.to_string());
+                }
+                let zlib = data.len() >= 2
+                    && data[0] & 0x0f == 8
+                    && (u16::from(data[0]) << 8 | u16::from(data[1])) % 31 == 0;
+                if zlib {
+                    if data[1] & 0x20 != 0 {
+                        return Err("preset dictionaries are not supported".to_string());
+                    }
+                    self.bits.pos += 2;
+                }
+                self.start_member(zlib);
+            }
+            State::ZlibTrailer => {
+                let checksum = self.bits.bytes(4)?;
+                if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]])
+                    != self.check
+                {
+                    return Err("checksum mismatch".to_string());
+                }
+                self.state = State::Done;
+            }
+            State::Inflate { zlib } => {
+                if self.inflate.step(&mut self.bits, &mut self.window)? {
+                    self.bits.align();
+                    self.state = match (self.coding.as_str(), zlib) {
+                        ("deflate", true) => State::ZlibTrailer,
+                        ("deflate", false) => State::Done,
+                        _ => State::GzipTrailer,
+                    };
+                }
+            }
+            // Data after the end is ignored
+            State::Done => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn start_member(&mut self, zlib: bool) {
+        self.check = if zlib { 1 } else { 0 };
+        self.member_len = 0;
+        self.inflate = Inflate::default();
+        self.state = State::Inflate { zlib };
+    }
+}
+
+/// Returns the length of the gzip member header at the start of `data`
+fn gzip_header_len(data: &[u8]) -> Result<usize, String> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.len() >= 2 && (data[0] != 0x1f || data[1] != 0x8b) {
+        return Err("not gzip data".to_string());
+    }
+    if data.len() < 10 {
+        return Err(END.to_string());
+    }
+    if data[2] != 8 {
+        return Err(format!("unknown compression method {}", data[2]));
+    }
+    let flags = data[3];
+    let mut len = 10;
+    if flags & FEXTRA != 0 {
+        let extra = data.get(len..len + 2).ok_or(END)?;
+        len += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(len..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or(END)?;
+            len += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        len += 2;
+    }
+    if len > data.len() {
+        return Err(END.to_string());
+    }
+    Ok(len)
+}
+
+/// Where DEFLATE decoding is within its blocks
+#[derive(Default)]
+enum Inflate {
+    /// At a block header
+    #[default]
+    Header,
+    /// In a stored block, with its bytes still to copy
+    Stored {
+        left: usize,
+        last: bool,
+    },
+    /// In a compressed block
+    Codes {
+        lengths: Huffman,
+        distances: Huffman,
+        last: bool,
+    },
+    Done,
+}
+
+impl Inflate {
+    /// Decodes one block header, one code or a piece of a stored block into
+    /// `window`, returning true at the end of the last block
+    fn step(&mut self, bits: &mut Bits, window: &mut Vec<u8>) -> Result<bool, String> {
+        match self {
+            Inflate::Header => {
+                let last = bits.take(1)? == 1;
+                *self = match bits.take(2)? {
+                    0 => {
+                        bits.align();
+                        let header = bits.bytes(4)?;
+                        let len = u16::from_le_bytes([header[0], header[1]]);
+                        if len != !u16::from_le_bytes([header[2], header[3]]) {
+                            return Err("invalid stored block length".to_string());
+                        }
+                        Inflate::Stored {
+                            left: len as usize,
+                            last,
+                        }
+                    }
+                    1 => {
+                        let (lengths, distances) = fixed_codes()?;
+                        Inflate::Codes {
+                            lengths,
+                            distances,
+                            last,
+                        }
+                    }
+                    2 => {
+                        let (lengths, distances) = dynamic_codes(bits)?;
+                        Inflate::Codes {
+                            lengths,
+                            distances,
+                            last,
+                        }
+                    }
+                    _ => return Err("invalid block type".to_string()),
+                };
+            }
+            Inflate::Stored { left, last } => {
+                let n = bits.rest().len().min(*left);
+                if n == 0 && *left > 0 {
+                    return Err(END.to_string());
+                }
+                window.extend_from_slice(&bits.rest()[..n]);
+                bits.pos += n;
+                *left -= n;
+                if *left == 0 {
+                    *self = Inflate::end_of_block(*last);
+                }
+            }
+            Inflate::Codes {
+                lengths,
+                distances,
+                last,
+            } => {
+                let symbol = lengths.decode(bits)? as usize;
+                match symbol {
+                    0..=255 => window.push(symbol as u8),
+                    256 => *self = Inflate::end_of_block(*last),
+                    _ => {
+                        let index = symbol - 257;
+                        if index >= LENGTH_BASE.len() {
+                            return Err("invalid length code".to_string());
+                        }
+                        let len = LENGTH_BASE[index] as usize
+                            + bits.take(u32::from(LENGTH_EXTRA[index]))? as usize;
+                        let index = distances.decode(bits)? as usize;
+                        if index >= DISTANCE_BASE.len() {
+                            return Err("invalid distance code".to_string());
+                        }
+                        let distance = DISTANCE_BASE[index] as usize
+                            + bits.take(u32::from(DISTANCE_EXTRA[index]))? as usize;
+                        if distance > window.len() {
+                            return Err("distance too far back".to_string());
+                        }
+                        let start = window.len() - distance;
+                        for i in 0..len {
+                            window.push(window[start + i]);
+                        }
+                    }
+                }
+            }
+            Inflate::Done => {}
+        }
+        Ok(matches!(self, Inflate::Done))
+    }
+
+    fn end_of_block(last: bool) -> Inflate {
+        if last {
+            Inflate::Done
+        } else {
+            Inflate::Header
+        }
+    }
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.take(5)? as usize + 257;
+    let distances = bits.take(5)? as usize + 1;
+    let code_lengths = bits.take(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err("too many codes".to_string());
+    }
+    let mut lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; literals + distances];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 if i > 0 => (lengths[i - 1], 3 + bits.take(2)?),
+            16 => return Err("repeated length without a previous one".to_string()),
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        let end = i + repeat as usize;
+        if end > lengths.len() {
+            return Err("too many code lengths".to_string());
+        }
+        lengths[i..end].fill(value);
+        i = end;
+    }
+    if lengths[256] == 0 {
+        return Err("missing end-of-block code".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..])?,
+    ))
+}
+
+/// Continues a CRC-32 over more data, starting from 0
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Continues an Adler-32 over more data, starting from 1
+fn adler32(adler: u32, data: &[u8]) -> u32 {
+    let (mut a, mut b) = (adler & 0xffff, adler >> 16);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Reads DEFLATE data bit by bit, least significant bit first, from the
+/// input received so far
+#[derive(Default)]
+struct Bits {
+    data: Vec<u8>,
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits {
+    fn take(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or(END)?;
+            self.pos += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u32 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Takes `n` whole bytes; the bits must be aligned
+    fn bytes(&mut self, n: usize) -> Result<&[u8], String> {
+        let bytes = self.data.get(self.pos..self.pos + n).ok_or(END)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// Returns the whole bytes not read yet
+    fn rest(&self) -> &[u8] {
+        &self.data[self.pos..]
+    }
+
+    /// Drops the bits left in the current byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn checkpoint(&self) -> (usize, u32, u32) {
+        (self.pos, self.buffer, self.count)
+    }
+
+    fn restore(&mut self, (pos, buffer, count): (usize, u32, u32)) {
+        self.pos = pos;
+        self.buffer = buffer;
+        self.count = count;
+    }
+
+    /// Forgets the input already read
+    fn compact(&mut self) {
+        self.data.drain(..self.pos);
+        self.pos = 0;
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the
+/// symbols ordered by code
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err("invalid Huffman code".to_string());
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        counts[0] = 0;
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which the code length code lengths of a dynamic block are sent
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    fn bytes(encoded: &str) -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_decode_gzip_with_dynamic_codes() {
+        let text = [
+            b"The quick brown fox jumps over the lazy dog. ".repeat(20),
+            (0..=255).collect(),
+        ]
+        .concat();
+        let data = bytes("H4sIAAAAAAACAwvJSFUoLM1MzlZIKsovz1NIy69QyCrNLShWyC9LLVIoAUrnJFZVKqTkp+sphIwqHlU8qpjaihkYmZhZWNnYOTi5uHl4+fgFBIWERUTFxCUkpaRlZOXkFRSVlFVU1dQ1NLW0dXT19A0MjYxNTM3MLSytrG1s7ewdHJ2cXVzd3D08vbx9fP38AwKDgkNCw8IjIqOiY2Lj4hMSk5JTUtPSMzKzsnNy8/ILCouKS0rLyisqq6prauvqGxqbmlta29o7Oru6e3r7+idMnDR5ytRp02fMnDV7ztx58xcsXLR4ydJly1esXLV6zdp16zds3LR5y9Zt23fs3LV7z959+w8cPHT4yNFjx0+cPHX6zNlz5y9cvHT5ytVr12/cvHX7zt179x88fPT4ydNnz1+8fPX6zdt37z98/PT5y9dv33/8/PX7z99//wHQ79iqhAQAAA==");
+        assert_eq!(decode("gzip", &data), Ok(Some(text.clone())));
+        assert_eq!(decode("X-Gzip", &data), Ok(Some(text)));
+
+        let mut corrupt = data.clone();
+        corrupt[200] ^= 0xff;
+        assert!(decode("gzip", &corrupt).is_err());
+        assert!(decode("gzip", &data[..data.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn test_decode_gzip_members_with_file_names() {
+        let data =
+            bytes("H4sICAAAAAAC/2EudHh0AMvPS1UAAElhrCMEAAAAH4sIAAAAAAACAyspzwcAZorKEQMAAAA=");
+        assert_eq!(decode("gzip", &data), Ok(Some(b"one two".to_vec())));
+    }
+
+    #[test]
+    fn test_decode_deflate_zlib_raw_and_stored() {
+        assert_eq!(
+            decode("deflate", &bytes("eJzLSM3JyVfIQJAAOi4GfQ==")),
+            Ok(Some(b"hello hello hello".to_vec()))
+        );
+        assert_eq!(
+            decode("deflate", &bytes("K0osV0hJTctJLEkFAA==")),
+            Ok(Some(b"raw deflate".to_vec()))
+        );
+        assert_eq!(
+            decode("deflate", &bytes("eAEBBgD5/3N0b3JlZAk8ApI=")),
+            Ok(Some(b"stored".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_decoder_takes_the_body_in_pieces() {
+        let gzip =
+            bytes("H4sICAAAAAAC/2EudHh0AMvPS1UAAElhrCMEAAAAH4sIAAAAAAACAyspzwcAZorKEQMAAAA=");
+        let zlib = bytes("eJzLSM3JyVfIQJAAOi4GfQ==");
+        let stored = bytes("eAEBBgD5/3N0b3JlZAk8ApI=");
+        for (coding, data, expected) in [
+            ("gzip", &gzip, &b"one two"[..]),
+            ("deflate", &zlib, b"hello hello hello"),
+            ("deflate", &stored, b"stored"),
+        ] {
+            let mut decoder = Decoder::new(coding, None, Vec::new());
+            for byte in data.iter() {
+                decoder.write_all(&[*byte]).unwrap();
+            }
+            assert_eq!(decoder.finish().unwrap(), expected, "{coding}");
+        }
+
+        // Cut short
+        let mut decoder = Decoder::new("gzip", None, Vec::new());
+        decoder.write_all(&gzip[..20]).unwrap();
+        assert!(decoder.finish().is_err());
+        // Invalid data fails as soon as it arrives
+        let err = Decoder::new("gzip", None, Vec::new())
+            .write_all(b"hi")
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decoded_size_is_limited() {
+        let data = bytes("eJzLSM3JyVfIQJAAOi4GfQ==");
+        let err = Decoder::new("deflate", Some(16), Vec::new())
+            .write_all(&data)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "deflate body decodes to more than 16 bytes"
+        );
+        assert_eq!(
+            decode("deflate", &data),
+            Ok(Some(b"hello hello hello".to_vec()))
+        );
+
+        // A megabyte of zeros in a few KiB of zlib data: written out in
+        // pieces when there is no limit, refused past one
+        let mut bomb = vec![0x78, 0x01];
+        bomb.extend(zeros_deflate(1 << 20));
+        bomb.extend(adler32(1, &vec![0; 1 << 20]).to_be_bytes());
+        let mut decoder = Decoder::new("deflate", None, Counter::default());
+        decoder.write_all(&bomb).unwrap();
+        let counter = decoder.finish().unwrap();
+        assert_eq!(counter.total, 1 << 20);
+        assert!(counter.largest <= 2 * WINDOW, "{}", counter.largest);
+        assert!(Decoder::new("deflate", Some(1 << 19), io::sink())
+            .write_all(&bomb)
+            .is_err());
+    }
+
+    /// Counts the bytes written to it, and the most in one write
+    #[derive(Default)]
+    struct Counter {
+        total: usize,
+        largest: usize,
+    }
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.total += buf.len();
+            self.largest = self.largest.max(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Raw DEFLATE data for `n` zero bytes: a literal, then copies of 258
+    /// bytes at distance 1 with the fixed codes, and shorter copies or
+    /// literals for the rest
+    fn zeros_deflate(n: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let (mut buffer, mut count) = (0u64, 0);
+        let mut put = |value: u32, bits: u32, reversed: bool| {
+            let value = if reversed {
+                value.reverse_bits() >> (32 - bits)
+            } else {
+                value
+            };
+            buffer |= u64::from(value) << count;
+            count += bits;
+            while count >= 8 {
+                out.push(buffer as u8);
+                buffer >>= 8;
+                count -= 8;
+            }
+        };
+        // Final block with fixed codes
+        put(1, 1, false);
+        put(1, 2, false);
+        // Literal 0 is 0b00110000 in 8 bits
+        let literal = |put: &mut dyn FnMut(u32, u32, bool)| put(0x30, 8, true);
+        literal(&mut put);
+        let mut left = n - 1;
+        while left >= 258 {
+            // Length 258 is code 285: 0b11000101 in 8 bits, then distance code 0
+            put(0xc5, 8, true);
+            put(0, 5, true);
+            left -= 258;
+        }
+        for _ in 0..left {
+            literal(&mut put);
+        }
+        // End of block, code 256: 0000000 in 7 bits
+        put(0, 7, true);
+        put(0, 7, false);
+        out
+    }
+
+    #[test]
+    fn test_decode_applies_codings_in_reverse() {
+        let data = bytes("eJzLSM3JyVfIQJAAOi4GfQ==");
+        assert_eq!(
+            decode("identity, deflate", &data),
+            Ok(Some(b"hello hello hello".to_vec()))
+        );
+        assert_eq!(decode("deflate, br", &data), Ok(None));
+        assert_eq!(decode("", &data), Ok(None));
+    }
+}
@@ -1,29 +1,148 @@
+use ::http::Method;
 use std::fmt;
 
 /// Custom error types for HTTP operations
 ///
 /// Represents various failure modes that can occur during HTTP requests,
-/// from network connectivity issues to parsing problems.
+/// from network connectivity issues to parsing problems. Failures to send a
+/// request keep the request's method and URL and are split by cause, so
+/// [`crate::error::WaveError::suggestion`] can point at the likely fix.
 #[derive(Debug, Clone)]
 pub enum HttpError {
-    /// Network-related errors (connection failed, timeout, etc.)
-    Network(String),
+    /// The host name could not be resolved
+    Dns(RequestFailure),
+    /// No connection could be opened (refused, reset, unreachable)
+    Connect(RequestFailure),
+    /// The TLS handshake failed, e.g. the certificate was not trusted
+    Tls(RequestFailure),
+    /// No connection was established within the connect timeout
+    ConnectTimeout(RequestFailure),
+    /// The server did not respond within the total or read timeout
+    Timeout(RequestFailure),
+    /// The server redirected too many times or in a loop
+    Redirect(RequestFailure),
+    /// Other failures while sending the request or reading the response
+    Network(RequestFailure),
+    /// The request was not sent because of `--offline`, or had no response
+    /// in the `--replay` cassette
+    Offline(RequestFailure),
     /// HTTP parsing errors (malformed response, invalid headers, etc.)
     Parse(String),
     /// Unsupported HTTP method
     UnsupportedMethod(String),
+    /// The server answered with a 4xx or 5xx status (reported with `--fail`)
+    Status(u16),
     /// Other errors
     Other(String),
 }
 
+/// The request that failed and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestFailure {
+    /// Method of the failed request
+    pub method: Method,
+    /// URL of the failed request
+    pub url: String,
+    /// The underlying error, from the outermost cause to the root cause
+    pub message: String,
+}
+
+impl RequestFailure {
+    /// Describes a failed request
+    ///
+    /// # Examples
+    /// ```
+    /// use http::Method;
+    /// use wave::http::{HttpError, RequestFailure};
+    ///
+    /// let err = HttpError::Connect(RequestFailure::new(
+    ///     Method::GET,
+    ///     "http://localhost:9",
+    ///     "Connection refused (os error 111)",
+    /// ));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Connection failed: GET http://localhost:9: Connection refused (os error 111)"
+    /// );
+    /// ```
+    pub fn new(method: Method, url: impl Into<String>, message: impl Into<String>) -> Self {
+        RequestFailure {
+            method,
+            url: url.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RequestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.method, self.url, self.message)
+    }
+}
+
+impl HttpError {
+    /// The failed request, for errors raised while sending it
+    pub fn failure(&self) -> Option<&RequestFailure> {
+        match self {
+            HttpError::Dns(failure)
+            | HttpError::Connect(failure)
+            | HttpError::Tls(failure)
+            | HttpError::ConnectTimeout(failure)
+            | HttpError::Timeout(failure)
+            | HttpError::Redirect(failure)
+            | HttpError::Network(failure)
+            | HttpError::Offline(failure) => Some(failure),
+            _ => None,
+        }
+    }
+
+    /// Provides a hint for fixing the failure, based on its cause
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            HttpError::Dns(_) => Some(
+                "Is the host name spelled correctly and reachable from here? Some hosts need a VPN or --proxy",
+            ),
+            HttpError::Connect(_) => Some(
+                "Is the host reachable and the server listening on that port? Check http:// vs https:// and any proxy",
+            ),
+            HttpError::Tls(_) => Some(
+                "Trust the server's CA with --cacert <pem>, or try --insecure to skip certificate checks",
+            ),
+            HttpError::ConnectTimeout(_) => Some(
+                "The server could not be reached; check the host, port and any firewall or VPN, or raise --connect-timeout",
+            ),
+            HttpError::Timeout(_) => Some(
+                "The server was reached but was slow to respond; raise --timeout or --read-timeout",
+            ),
+            HttpError::Redirect(_) => {
+                Some("The server redirects in a loop; request the final URL directly")
+            }
+            HttpError::Offline(_) => Some(
+                "Record the response while online with --record FILE, then answer from it with --replay FILE",
+            ),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for HttpError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            HttpError::Network(msg) => write!(f, "Network error: {msg}"),
+            HttpError::Dns(failure) => write!(f, "Could not resolve host: {failure}"),
+            HttpError::Connect(failure) => write!(f, "Connection failed: {failure}"),
+            HttpError::Tls(failure) => write!(f, "TLS error: {failure}"),
+            HttpError::ConnectTimeout(failure) => write!(f, "Connection timed out: {failure}"),
+            HttpError::Timeout(failure) => write!(f, "Request timed out: {failure}"),
+            HttpError::Redirect(failure) => write!(f, "Too many redirects: {failure}"),
+            HttpError::Network(failure) => write!(f, "Network error: {failure}"),
+            HttpError::Offline(failure) => write!(f, "Not sent while offline: {failure}"),
             HttpError::Parse(msg) => write!(f, "Parse error: {msg}"),
             HttpError::UnsupportedMethod(method) => {
                 write!(f, "Unsupported HTTP method: {method}")
             }
+            HttpError::Status(status) => {
+                write!(f, "Server responded with error status {status}")
+            }
             HttpError::Other(msg) => write!(f, "Error: {msg}"),
         }
     }
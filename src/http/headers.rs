@@ -0,0 +1,193 @@
+//! Merging request headers from several sources
+//!
+//! A request's headers are layered, from lowest to highest precedence:
+//!
+//! 1. defaults: the config file's `[headers]` and session headers, which only
+//!    fill in headers nothing else sets ([`fill_defaults`])
+//! 2. the collection: the collection's and template's `headers:` under the
+//!    request's own
+//! 3. the command line: `key:value` params
+//!
+//! Names are compared case-insensitively, so `content-type` from a collection
+//! and `Content-Type` from the command line are one header. A higher layer
+//! replaces every value of a header from the layers below, unless the value
+//! is added with `key+:value`, which keeps them. Headers keep the position in
+//! which they were first set.
+
+use crate::http::error::HttpError;
+use crate::http::request::parse_header;
+use ::http::{HeaderMap, HeaderName, HeaderValue};
+
+/// The headers from one source, in the order they were given
+///
+/// # Examples
+///
+/// ```
+/// use wave::http::HeaderLayer;
+///
+/// let collection = HeaderLayer::parse([("content-type", "text/csv"), ("Accept", "a")])?;
+/// let cli = HeaderLayer::parse([("Content-Type", "application/json"), ("Accept+", "b")])?;
+/// let headers = HeaderLayer::merge([&collection, &cli]);
+///
+/// assert_eq!(headers.get_all("content-type").iter().count(), 1);
+/// assert_eq!(headers["content-type"], "application/json");
+/// let accept: Vec<_> = headers.get_all("accept").iter().collect();
+/// assert_eq!(accept, ["a", "b"]);
+/// # Ok::<(), wave::http::HttpError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HeaderLayer {
+    entries: Vec<(HeaderName, HeaderValue, bool)>,
+}
+
+impl HeaderLayer {
+    /// Parses `key`/`value` pairs; a key ending in `+` adds a value instead of
+    /// replacing the header
+    ///
+    /// # Errors
+    /// Returns `HttpError::Parse` naming the first header that is not valid HTTP
+    pub fn parse<K: AsRef<str>, V: AsRef<str>>(
+        pairs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, HttpError> {
+        let mut layer = HeaderLayer::default();
+        for (key, value) in pairs {
+            let key = key.as_ref();
+            let (key, append) = match key.strip_suffix('+') {
+                Some(key) => (key, true),
+                None => (key, false),
+            };
+            let (name, value) = parse_header(key, value.as_ref())?;
+            layer.entries.push((name, value, append));
+        }
+        Ok(layer)
+    }
+
+    /// Applies the layer over `headers`, replacing the values of each header
+    /// it sets unless the entry appends
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value, append) in &self.entries {
+            if *append {
+                tracing::debug!(header = %name, "header value added");
+                headers.append(name.clone(), value.clone());
+            } else {
+                if headers.contains_key(name) {
+                    tracing::debug!(header = %name, "header replaced by a higher layer");
+                }
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Applies `layers` in order, lowest precedence first
+    pub fn merge<'a>(layers: impl IntoIterator<Item = &'a HeaderLayer>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for layer in layers {
+            layer.apply(&mut headers);
+        }
+        headers
+    }
+}
+
+/// Adds each header of `defaults` that `headers` does not set, with all of
+/// its values
+///
+/// # Examples
+///
+/// ```
+/// use http::{HeaderMap, HeaderValue};
+/// use wave::http::headers::fill_defaults;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert("accept", HeaderValue::from_static("text/csv"));
+/// let mut defaults = HeaderMap::new();
+/// defaults.insert("Accept", HeaderValue::from_static("*/*"));
+/// defaults.insert("User-Agent", HeaderValue::from_static("wave"));
+///
+/// fill_defaults(&mut headers, &defaults);
+/// assert_eq!(headers["accept"], "text/csv");
+/// assert_eq!(headers["user-agent"], "wave");
+/// ```
+pub fn fill_defaults(headers: &mut HeaderMap, defaults: &HeaderMap) {
+    for name in defaults.keys() {
+        if headers.contains_key(name) {
+            continue;
+        }
+        tracing::debug!(header = %name, "default header added");
+        for value in defaults.get_all(name) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+/// Collects `name`/`value` pairs into a header map, skipping invalid ones
+///
+/// For headers from settings that were accepted before they could be
+/// checked, such as the config file's `[headers]`.
+pub fn lenient_header_map<K: AsRef<str>, V: AsRef<str>>(
+    pairs: impl IntoIterator<Item = (K, V)>,
+) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        match parse_header(name.as_ref(), value.as_ref()) {
+            Ok((name, value)) => {
+                headers.insert(name, value);
+            }
+            Err(e) => tracing::debug!(error = %e, "header skipped"),
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_higher_layers_win_case_insensitively() {
+        let collection = HeaderLayer::parse([
+            ("Authorization", "Bearer123"),
+            ("content-type", "text/csv"),
+            ("X-Trace", "collection"),
+        ])
+        .unwrap();
+        let cli = HeaderLayer::parse([
+            ("authorization", "BearerCLI"),
+            ("Content-Type", "application/json"),
+            ("X-Custom", "header"),
+        ])
+        .unwrap();
+        let headers = HeaderLayer::merge([&collection, &cli]);
+
+        let names: Vec<&str> = headers.keys().map(HeaderName::as_str).collect();
+        assert_eq!(
+            names,
+            ["authorization", "content-type", "x-trace", "x-custom"]
+        );
+        assert_eq!(headers["authorization"], "BearerCLI");
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers["x-trace"], "collection");
+        assert_eq!(headers.len(), 4);
+    }
+
+    #[test]
+    fn test_append_keeps_lower_layer_values() {
+        let collection = HeaderLayer::parse([("Accept", "a"), ("Accept+", "b")]).unwrap();
+        let cli = HeaderLayer::parse([("Accept+", "c")]).unwrap();
+        let headers = HeaderLayer::merge([&collection, &cli]);
+        let accept: Vec<_> = headers.get_all("accept").iter().collect();
+        assert_eq!(accept, ["a", "b", "c"]);
+
+        let cli = HeaderLayer::parse([("accept", "d")]).unwrap();
+        let headers = HeaderLayer::merge([&collection, &cli]);
+        let accept: Vec<_> = headers.get_all("accept").iter().collect();
+        assert_eq!(accept, ["d"]);
+    }
+
+    #[test]
+    fn test_invalid_headers_are_errors_or_skipped() {
+        assert!(HeaderLayer::parse([("Bad Name", "1")]).is_err());
+        let headers = lenient_header_map([("Bad Name", "1"), ("X-Ok", "2")]);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["x-ok"], "2");
+    }
+}
@@ -0,0 +1,175 @@
+//! In-memory backend for testing code that sends requests through [`Client`]
+//!
+//! Available with the `test-util` feature:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! wave = { version = "0.2", features = ["test-util"] }
+//! ```
+//!
+//! [`Client`]: crate::http::Client
+
+use crate::http::{
+    backend::HttpBackend, error::HttpError, request::HttpRequest, response::HttpResponse,
+};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Backend that answers with queued responses and records what it was sent
+///
+/// Responses and errors are returned in the order they were queued. The last
+/// one is repeated once the queue runs out, so a backend with a single
+/// response answers every request with it; with nothing queued, sends fail
+/// with `HttpError::Other`. Every request is recorded, including those
+/// answered with an error.
+///
+/// The client owns its backend, which stays reachable as `client.backend`;
+/// wrap the mock in an `Arc` to keep a handle of your own.
+///
+/// # Examples
+///
+/// ```
+/// use http::{HeaderMap, Method};
+/// use std::time::Duration;
+/// use wave::http::{Client, HttpResponse, MockBackend};
+///
+/// # tokio_test::block_on(async {
+/// let backend = MockBackend::new()
+///     .respond(HttpResponse::new(201, HeaderMap::new(), r#"{"id":7}"#))
+///     .respond(HttpResponse::new(200, HeaderMap::new(), "ok"))
+///     .latency(Duration::from_millis(5));
+/// let client = Client::new(backend);
+///
+/// let created = client.post("https://api.example.com/users").send().await.unwrap();
+/// assert_eq!(created.status, 201);
+/// let fetched = client.get("https://api.example.com/users/7").send().await.unwrap();
+/// assert_eq!(fetched.body, "ok");
+///
+/// let sent = client.backend.requests();
+/// assert_eq!(sent.len(), 2);
+/// assert_eq!(sent[0].method, Method::POST);
+/// assert_eq!(sent[1].url, "https://api.example.com/users/7");
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    responses: Mutex<VecDeque<Result<HttpResponse, HttpError>>>,
+    requests: Mutex<Vec<HttpRequest>>,
+    latency: Duration,
+}
+
+impl MockBackend {
+    /// Creates a backend with no responses queued
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a response
+    pub fn respond(self, response: HttpResponse) -> Self {
+        self.push(Ok(response));
+        self
+    }
+
+    /// Queues an error, as if the request could not be sent
+    pub fn fail(self, error: HttpError) -> Self {
+        self.push(Err(error));
+        self
+    }
+
+    /// Waits this long before answering each request
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Queues a response or error on a backend already in use, e.g. through
+    /// an `Arc`
+    pub fn push(&self, response: Result<HttpResponse, HttpError>) {
+        self.responses.lock().unwrap().push_back(response);
+    }
+
+    /// Returns the requests sent so far, oldest first
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the most recent request, if any was sent
+    pub fn last_request(&self) -> Option<HttpRequest> {
+        self.requests.lock().unwrap().last().cloned()
+    }
+
+    /// Takes the next queued answer, keeping the last one for later requests
+    fn next_response(&self) -> Result<HttpResponse, HttpError> {
+        let mut responses = self.responses.lock().unwrap();
+        match responses.len() {
+            0 => Err(HttpError::Other(
+                "MockBackend has no response queued".to_string(),
+            )),
+            1 => responses[0].clone(),
+            _ => responses.pop_front().unwrap(),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpBackend for MockBackend {
+    async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        self.requests.lock().unwrap().push(req.clone());
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        self.next_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::http::{HeaderMap, Method};
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tokio_test::block_on;
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::new(url, Method::GET, None, HeaderMap::new())
+    }
+
+    #[test]
+    fn test_queued_answers_come_in_order_and_the_last_repeats() {
+        let backend = Arc::new(
+            MockBackend::new()
+                .respond(HttpResponse::new(503, HeaderMap::new(), ""))
+                .fail(HttpError::Other("reset".to_string())),
+        );
+        backend.push(Ok(HttpResponse::new(200, HeaderMap::new(), "ok")));
+        let shared = backend.clone();
+
+        assert_eq!(block_on(shared.send(&get("http://a"))).unwrap().status, 503);
+        assert!(block_on(shared.send(&get("http://b"))).is_err());
+        for url in ["http://c", "http://d"] {
+            assert_eq!(block_on(shared.send(&get(url))).unwrap().body, "ok");
+        }
+        let urls: Vec<String> = backend.requests().into_iter().map(|r| r.url).collect();
+        assert_eq!(urls, ["http://a", "http://b", "http://c", "http://d"]);
+        assert_eq!(backend.last_request().unwrap().url, "http://d");
+    }
+
+    #[test]
+    fn test_empty_queue_fails_and_latency_delays() {
+        let empty = MockBackend::new();
+        assert!(matches!(
+            block_on(empty.send(&get("http://a"))),
+            Err(HttpError::Other(_))
+        ));
+        assert_eq!(empty.requests().len(), 1);
+
+        let slow = MockBackend::new()
+            .respond(HttpResponse::new(200, HeaderMap::new(), ""))
+            .latency(Duration::from_millis(20));
+        let started = Instant::now();
+        block_on(slow.send(&get("http://a"))).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+}
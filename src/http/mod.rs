@@ -1,13 +1,36 @@
+pub mod auth;
 pub mod backend;
+pub mod cassette;
+pub mod charset;
 pub mod client;
+pub mod curl;
+pub mod decode;
 pub mod error;
+pub mod headers;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+pub mod offline;
 pub mod request;
+pub mod request_id;
+pub mod resolve;
 pub mod response;
+pub mod tls;
 pub mod utils;
 
-pub use backend::{HttpBackend, ReqwestBackend};
-pub use client::Client;
-pub use error::HttpError;
-pub use request::{HttpRequest, RequestBody, RequestBuilder};
-pub use response::HttpResponse;
-pub use utils::parse_method;
+pub use auth::{cleartext_credentials, Auth, OAuth2};
+pub use backend::{HttpBackend, ProgressFn, ReqwestBackend, ReqwestBackendBuilder};
+pub use cassette::{Cassette, CassetteBackend, CassetteMode};
+pub use charset::Charset;
+pub use client::{Client, ClientRequest};
+pub use curl::to_curl;
+pub use error::{HttpError, RequestFailure};
+pub use headers::HeaderLayer;
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::MockBackend;
+pub use offline::OfflineBackend;
+pub use request::{HttpRequest, MultipartPart, RequestBody, RequestBuilder};
+pub use request_id::RequestIdFormat;
+pub use resolve::Resolve;
+pub use response::{ContentEncoding, HttpResponse, Timing};
+pub use tls::TlsOptions;
+pub use utils::{is_loopback, parse_method};
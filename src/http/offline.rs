@@ -0,0 +1,104 @@
+//! Keeping requests off the network (`--offline`)
+
+use crate::http::{
+    backend::{HttpBackend, ProgressFn},
+    error::{HttpError, RequestFailure},
+    request::HttpRequest,
+    response::HttpResponse,
+    utils::is_loopback,
+};
+use async_trait::async_trait;
+use std::path::Path;
+use url::Url;
+
+/// Backend wrapper that only sends requests to loopback hosts, such as a
+/// local server or `wave mock`, and fails every other request with
+/// `HttpError::Offline`
+///
+/// # Examples
+///
+/// ```
+/// use http::HeaderMap;
+/// use wave::http::{Client, HttpError, HttpResponse, MockBackend, OfflineBackend};
+///
+/// # tokio_test::block_on(async {
+/// let local = MockBackend::new().respond(HttpResponse::new(200, HeaderMap::new(), "ok"));
+/// let client = Client::new(OfflineBackend::new(local));
+/// assert!(client.get("http://localhost:8080/health").send().await.is_ok());
+/// let remote = client.get("https://api.example.com/health").send().await;
+/// assert!(matches!(remote, Err(HttpError::Offline(_))));
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct OfflineBackend<B> {
+    inner: B,
+}
+
+impl<B> OfflineBackend<B> {
+    /// Wraps the backend that sends requests to loopback hosts
+    pub fn new(inner: B) -> Self {
+        OfflineBackend { inner }
+    }
+
+    /// Fails requests to hosts other than loopback ones
+    fn check(req: &HttpRequest) -> Result<(), HttpError> {
+        match Url::parse(&req.url) {
+            Ok(url) if is_loopback(&url) => Ok(()),
+            _ => Err(HttpError::Offline(RequestFailure::new(
+                req.method.clone(),
+                &req.url,
+                "--offline only allows requests to localhost",
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl<B: HttpBackend + Send + Sync> HttpBackend for OfflineBackend<B> {
+    async fn send(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        Self::check(req)?;
+        self.inner.send(req).await
+    }
+
+    async fn download(
+        &self,
+        req: &HttpRequest,
+        path: &Path,
+        progress: ProgressFn<'_>,
+    ) -> Result<(HttpResponse, u64), HttpError> {
+        Self::check(req)?;
+        self.inner.download(req, path, progress).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::MockBackend;
+    use ::http::{HeaderMap, Method};
+    use tokio_test::block_on;
+
+    #[test]
+    fn test_only_loopback_requests_are_sent() {
+        let backend = OfflineBackend::new(MockBackend::new().respond(HttpResponse::new(
+            200,
+            HeaderMap::new(),
+            "",
+        )));
+        let send = |url: &str| {
+            block_on(backend.send(&HttpRequest::new(url, Method::GET, None, HeaderMap::new())))
+        };
+        for url in [
+            "http://localhost:3000/",
+            "http://127.0.0.1/",
+            "http://[::1]:80/",
+        ] {
+            assert!(send(url).is_ok(), "{url}");
+        }
+        for url in ["https://example.com/", "http://10.0.0.5/", "not a url"] {
+            let err = send(url).unwrap_err();
+            assert!(matches!(err, HttpError::Offline(_)), "{url}");
+        }
+        assert_eq!(backend.inner.requests().len(), 3);
+    }
+}
@@ -1,6 +1,21 @@
+use crate::http::auth::Auth;
 use crate::http::error::HttpError;
 use crate::KeyValuePairs;
 use ::http::{HeaderMap, Method};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A single part of a multipart/form-data body
+///
+/// File parts only record the path; the backend reads the file when the
+/// request is sent so large uploads are not held in memory while building.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MultipartPart {
+    /// Plain text field
+    Text { name: String, value: String },
+    /// File upload field, sent with the file's name
+    File { name: String, path: PathBuf },
+}
 
 /// Represents different types of request bodies with automatic serialization
 ///
@@ -36,6 +51,12 @@ pub enum RequestBody {
     Text(String),
     /// Binary data body - automatically sets Content-Type to application/octet-stream
     Bytes(Vec<u8>),
+    /// Multipart form body with text and file parts - encoded by the backend,
+    /// which sets Content-Type to multipart/form-data with a generated boundary
+    Multipart(Vec<MultipartPart>),
+    /// Contents of a file, read by the backend when the request is sent -
+    /// automatically sets Content-Type to application/octet-stream
+    File(PathBuf),
 }
 
 impl RequestBody {
@@ -97,11 +118,38 @@ impl RequestBody {
         RequestBody::Bytes(data)
     }
 
+    /// Create a multipart/form-data body
+    ///
+    /// For file uploads and forms mixing text fields with files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::{MultipartPart, RequestBody};
+    ///
+    /// let body = RequestBody::multipart(vec![
+    ///     MultipartPart::Text { name: "title".to_string(), value: "Holiday".to_string() },
+    ///     MultipartPart::File { name: "photo".to_string(), path: "./photo.png".into() },
+    /// ]);
+    /// ```
+    pub fn multipart(parts: Vec<MultipartPart>) -> Self {
+        RequestBody::Multipart(parts)
+    }
+
+    /// Create a body sent from a file
+    ///
+    /// The file is read when the request is sent, so binary payloads are
+    /// sent byte for byte.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        RequestBody::File(path.into())
+    }
+
     /// Serialize the body to a string and set appropriate Content-Type header
     ///
     /// Converts the body to its wire format and automatically sets the correct
     /// Content-Type header based on the body type. This method is used internally
-    /// when building HTTP requests.
+    /// when building HTTP requests. Multipart and file bodies are read by the
+    /// backend, so they serialize to an empty string here.
     pub fn serialize(&self, headers: &mut HeaderMap) -> String {
         match self {
             RequestBody::Json(value) => {
@@ -123,6 +171,11 @@ impl RequestBody {
                 Self::ensure_content_type(headers, "application/octet-stream");
                 String::from_utf8_lossy(bytes).to_string()
             }
+            RequestBody::Multipart(_) => String::new(),
+            RequestBody::File(_) => {
+                Self::ensure_content_type(headers, "application/octet-stream");
+                String::new()
+            }
         }
     }
 
@@ -163,6 +216,7 @@ pub struct RequestBuilder {
     method: Method,
     headers: HeaderMap,
     body: Option<RequestBody>,
+    timeout: Option<Duration>,
 }
 
 impl RequestBuilder {
@@ -176,25 +230,70 @@ impl RequestBuilder {
             method,
             headers: HeaderMap::new(),
             body: None,
+            timeout: None,
         }
     }
 
     /// Add a header to the request
     ///
     /// Sets a single header key-value pair. If the header already exists,
-    /// it will be replaced with the new value.
+    /// it will be replaced with the new value. A name or value that is not
+    /// valid in HTTP is skipped; use [`RequestBuilder::try_header`] to get an
+    /// error instead.
     pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
-        let key_str = key.into();
-        let value_str = value.into();
-        if let (Ok(header_name), Ok(header_value)) = (
-            key_str.parse::<::http::HeaderName>(),
-            value_str.parse::<::http::HeaderValue>(),
-        ) {
-            self.headers.insert(header_name, header_value);
+        if let Ok((name, value)) = parse_header(&key.into(), &value.into()) {
+            self.headers.insert(name, value);
         }
         self
     }
 
+    /// Add another value for a header, keeping values added earlier
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::RequestBuilder;
+    /// use http::Method;
+    ///
+    /// let request = RequestBuilder::new("https://api.example.com", Method::GET)
+    ///     .header("Accept", "application/json")
+    ///     .append_header("Accept", "text/plain")
+    ///     .build();
+    /// let accept: Vec<_> = request.headers.get_all("accept").iter().collect();
+    /// assert_eq!(accept, ["application/json", "text/plain"]);
+    /// ```
+    pub fn append_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        if let Ok((name, value)) = parse_header(&key.into(), &value.into()) {
+            self.headers.append(name, value);
+        }
+        self
+    }
+
+    /// Add a header to the request, failing if the name or value is invalid
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::RequestBuilder;
+    /// use http::Method;
+    ///
+    /// let builder = RequestBuilder::new("https://api.example.com", Method::GET);
+    /// let err = builder.try_header("X Api Key", "abc").unwrap_err();
+    /// assert!(err.to_string().contains("invalid header name 'X Api Key'"));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `HttpError::Parse` naming the header that could not be parsed
+    pub fn try_header(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Self, HttpError> {
+        let (name, value) = parse_header(&key.into(), &value.into())?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
     /// Add multiple headers to the request
     pub fn headers(mut self, headers: HeaderMap) -> Self {
         self.headers.extend(headers);
@@ -202,18 +301,47 @@ impl RequestBuilder {
     }
 
     /// Add multiple headers from Vec (convenience method for backward compatibility)
+    ///
+    /// Invalid headers are skipped, as with [`RequestBuilder::header`].
     pub fn headers_from_vec(mut self, headers: KeyValuePairs) -> Self {
         for (key, value) in headers {
-            if let (Ok(header_name), Ok(header_value)) = (
-                key.parse::<::http::HeaderName>(),
-                value.parse::<::http::HeaderValue>(),
-            ) {
-                self.headers.insert(header_name, header_value);
-            }
+            self = self.header(key, value);
         }
         self
     }
 
+    /// Add multiple headers from Vec, failing on the first invalid one
+    ///
+    /// # Errors
+    /// Returns `HttpError::Parse` naming the header that could not be parsed
+    pub fn try_headers_from_vec(mut self, headers: KeyValuePairs) -> Result<Self, HttpError> {
+        for (key, value) in headers {
+            self = self.try_header(key, value)?;
+        }
+        Ok(self)
+    }
+
+    /// Authenticate the request
+    ///
+    /// Sets the `Authorization` header for the given scheme, replacing any
+    /// `Authorization` header added earlier.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::{Auth, RequestBuilder};
+    /// use http::Method;
+    ///
+    /// let request = RequestBuilder::new("https://api.example.com/me", Method::GET)
+    ///     .auth(Auth::Bearer("token123".to_string()))
+    ///     .build();
+    /// assert_eq!(request.headers["authorization"], "Bearer token123");
+    /// ```
+    pub fn auth(mut self, auth: Auth) -> Self {
+        auth.apply(&mut self.headers);
+        self
+    }
+
     /// Set the request body
     ///
     /// Sets the request body using a `RequestBody` instance. Use `RequestBody` static methods
@@ -245,19 +373,39 @@ impl RequestBuilder {
         self
     }
 
+    /// Set a timeout for the whole request
+    ///
+    /// The backend aborts the request with `HttpError::Timeout` if the
+    /// response has not been fully received within this duration.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Build the final HttpRequest
     ///
     /// Consumes the builder and produces an `HttpRequest` ready to be sent.
     /// This method handles final serialization of the body and header setup.
     pub fn build(self) -> HttpRequest {
         let mut headers = self.headers;
-        let body = self.body.map(|b| b.serialize(&mut headers));
+        let (body, multipart, body_file) = match self.body {
+            Some(RequestBody::Multipart(parts)) => (None, Some(parts), None),
+            Some(RequestBody::File(path)) => {
+                RequestBody::ensure_content_type(&mut headers, "application/octet-stream");
+                (None, None, Some(path))
+            }
+            Some(b) => (Some(b.serialize(&mut headers)), None, None),
+            None => (None, None, None),
+        };
 
         HttpRequest {
             url: self.url,
             method: self.method,
             body,
             headers,
+            timeout: self.timeout,
+            multipart,
+            body_file,
         }
     }
 }
@@ -301,6 +449,12 @@ pub struct HttpRequest {
     pub body: Option<String>,
     /// HTTP headers to send
     pub headers: HeaderMap,
+    /// Optional limit on how long the whole request may take
+    pub timeout: Option<Duration>,
+    /// Multipart form parts, sent instead of `body` when present
+    pub multipart: Option<Vec<MultipartPart>>,
+    /// File whose contents are sent instead of `body` when present
+    pub body_file: Option<PathBuf>,
 }
 
 impl HttpRequest {
@@ -331,6 +485,9 @@ impl HttpRequest {
             method,
             body,
             headers,
+            timeout: None,
+            multipart: None,
+            body_file: None,
         }
     }
 
@@ -356,6 +513,37 @@ impl HttpRequest {
     }
 }
 
+/// Parses a header name and value, naming the offending input on failure
+///
+/// # Examples
+///
+/// ```
+/// use wave::http::request::parse_header;
+///
+/// let (name, value) = parse_header("X-Api-Key", "abc123")?;
+/// assert_eq!((name.as_str(), value.to_str().unwrap()), ("x-api-key", "abc123"));
+///
+/// let err = parse_header("X-Note", "line\nbreak").unwrap_err();
+/// assert!(err.to_string().contains("invalid value for header 'X-Note'"));
+/// # Ok::<(), wave::http::HttpError>(())
+/// ```
+///
+/// # Errors
+/// Returns `HttpError::Parse` if the name is not a valid header name or the
+/// value contains characters not allowed in headers, such as line breaks
+pub fn parse_header(
+    key: &str,
+    value: &str,
+) -> Result<(::http::HeaderName, ::http::HeaderValue), HttpError> {
+    let name = key
+        .parse::<::http::HeaderName>()
+        .map_err(|_| HttpError::Parse(format!("invalid header name '{key}'")))?;
+    let value = value
+        .parse::<::http::HeaderValue>()
+        .map_err(|_| HttpError::Parse(format!("invalid value for header '{key}': {value:?}")))?;
+    Ok((name, value))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -436,5 +624,69 @@ mod tests {
         assert_eq!(req.headers.get("authorization").unwrap(), "Bearer token");
         assert_eq!(req.headers.get("content-type").unwrap(), "application/json");
         assert!(req.body.is_some());
+        assert_eq!(req.timeout, None);
+    }
+
+    #[test]
+    fn test_try_header_reports_invalid_headers() {
+        let builder = RequestBuilder::new("https://example.com", Method::GET);
+        let req = builder
+            .try_header("X-Ok", "1")
+            .unwrap()
+            .header("Bad Name", "2")
+            .header("X-Bad-Value", "a\r\nb")
+            .build();
+        assert_eq!(req.headers.len(), 1);
+
+        let err = RequestBuilder::new("https://example.com", Method::GET)
+            .try_headers_from_vec(vec![
+                ("X-Ok".to_string(), "1".to_string()),
+                ("X-Bad-Value".to_string(), "a\nb".to_string()),
+            ])
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            HttpError::Parse(msg) if msg == r#"invalid value for header 'X-Bad-Value': "a\nb""#
+        ));
+    }
+
+    #[test]
+    fn test_request_builder_auth() {
+        let req = HttpRequest::builder("https://example.com", Method::GET)
+            .header("Authorization", "Bearer stale")
+            .auth(Auth::parse_basic("user:pass"))
+            .build();
+        assert_eq!(
+            req.headers.get("authorization").unwrap(),
+            "Basic dXNlcjpwYXNz"
+        );
+    }
+
+    #[test]
+    fn test_request_builder_multipart() {
+        let parts = vec![
+            MultipartPart::Text {
+                name: "title".to_string(),
+                value: "Holiday".to_string(),
+            },
+            MultipartPart::File {
+                name: "photo".to_string(),
+                path: PathBuf::from("./photo.png"),
+            },
+        ];
+        let req = HttpRequest::builder("https://example.com/upload", Method::POST)
+            .body(RequestBody::multipart(parts.clone()))
+            .build();
+        assert_eq!(req.body, None);
+        assert_eq!(req.multipart, Some(parts));
+        assert!(!req.headers.contains_key("content-type"));
+    }
+
+    #[test]
+    fn test_request_builder_timeout() {
+        let req = HttpRequest::builder("https://example.com", Method::GET)
+            .timeout(Duration::from_secs(5))
+            .build();
+        assert_eq!(req.timeout, Some(Duration::from_secs(5)));
     }
 }
@@ -0,0 +1,59 @@
+//! Correlation IDs for `--request-id`
+//!
+//! Each request gets a fresh ID in a header, which wave prints so the request
+//! can be found in server logs and traces.
+
+use ::http::HeaderName;
+use serde::Deserialize;
+
+/// How a correlation ID is written, and the header it goes in by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestIdFormat {
+    /// A random UUID in `X-Request-Id`
+    #[default]
+    Uuid,
+    /// A W3C Trace Context `traceparent` with a random trace and span ID
+    Traceparent,
+}
+
+impl RequestIdFormat {
+    /// The header the ID is sent in unless another one is configured
+    pub fn default_header(self) -> HeaderName {
+        match self {
+            RequestIdFormat::Uuid => HeaderName::from_static("x-request-id"),
+            RequestIdFormat::Traceparent => HeaderName::from_static("traceparent"),
+        }
+    }
+
+    /// Generates a new ID
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::http::RequestIdFormat;
+    ///
+    /// let id = RequestIdFormat::Uuid.generate();
+    /// assert_eq!(id.len(), 36);
+    /// assert_ne!(id, RequestIdFormat::Uuid.generate());
+    ///
+    /// let parts: Vec<String> = RequestIdFormat::Traceparent
+    ///     .generate()
+    ///     .split('-')
+    ///     .map(str::to_string)
+    ///     .collect();
+    /// assert_eq!(parts.iter().map(String::len).collect::<Vec<_>>(), [2, 32, 16, 2]);
+    /// assert_eq!((parts[0].as_str(), parts[3].as_str()), ("00", "01"));
+    /// ```
+    pub fn generate(self) -> String {
+        let uuid = crate::dynamic::uuid_v4();
+        match self {
+            RequestIdFormat::Uuid => uuid,
+            RequestIdFormat::Traceparent => {
+                // Version 00, sampled; a UUID supplies 32 random hex digits
+                let trace_id = uuid.replace('-', "");
+                let span_id = crate::dynamic::uuid_v4().replace('-', "");
+                format!("00-{trace_id}-{}-01", &span_id[..16])
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Hostname resolution overrides for `--resolve`
+
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+/// Sends requests for a host to fixed addresses instead of looking it up in DNS
+///
+/// Written like curl's `--resolve`, as `HOST:PORT:ADDR[,ADDR...]`. IPv6
+/// addresses may be wrapped in brackets. The `Host` header and TLS server
+/// name still use the hostname, so this reaches a specific server behind a
+/// load balancer, or a new server before DNS points at it.
+///
+/// # Examples
+/// ```
+/// use wave::http::Resolve;
+///
+/// let resolve: Resolve = "api.example.com:443:10.0.0.5".parse().unwrap();
+/// assert_eq!(resolve.host, "api.example.com");
+/// assert_eq!(resolve.port, 443);
+/// assert_eq!(resolve.addrs, vec!["10.0.0.5".parse::<std::net::IpAddr>().unwrap()]);
+///
+/// assert!("api.example.com:10.0.0.5".parse::<Resolve>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolve {
+    /// Hostname as written in request URLs, lowercased
+    pub host: String,
+    /// Port of the override, as in curl
+    ///
+    /// reqwest overrides a hostname on every port, so the port a request
+    /// connects to is always the one in its URL.
+    pub port: u16,
+    /// Addresses to connect to, tried in order
+    pub addrs: Vec<IpAddr>,
+}
+
+impl Resolve {
+    /// Returns the addresses as socket addresses for reqwest
+    ///
+    /// The port is 0 so reqwest uses the URL's port, or the scheme's default.
+    pub(crate) fn socket_addrs(&self) -> Vec<SocketAddr> {
+        self.addrs
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, 0))
+            .collect()
+    }
+}
+
+impl FromStr for Resolve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("expected HOST:PORT:ADDR, e.g. example.com:443:127.0.0.1, got '{s}'");
+        let mut parts = s.splitn(3, ':');
+        let (Some(host), Some(port), Some(addrs)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let host = host.trim();
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let port = port
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| format!("invalid port '{port}' in '{s}'"))?;
+        let addrs = addrs
+            .split(',')
+            .map(|addr| {
+                let addr = addr.trim();
+                let bare = addr
+                    .strip_prefix('[')
+                    .and_then(|a| a.strip_suffix(']'))
+                    .unwrap_or(addr);
+                bare.parse::<IpAddr>()
+                    .map_err(|_| format!("invalid IP address '{addr}' in '{s}'"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Resolve {
+            host: host.to_ascii_lowercase(),
+            port,
+            addrs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_several_and_ipv6_addresses() {
+        let resolve: Resolve = "API.example.com:8443:[::1], 10.0.0.1".parse().unwrap();
+        assert_eq!(resolve.host, "api.example.com");
+        assert_eq!(resolve.port, 8443);
+        assert_eq!(
+            resolve.addrs,
+            vec![
+                "::1".parse::<IpAddr>().unwrap(),
+                "10.0.0.1".parse::<IpAddr>().unwrap()
+            ]
+        );
+        let bare: Resolve = "h:80:::1".parse().unwrap();
+        assert_eq!(bare.addrs, vec!["::1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("example.com:443".parse::<Resolve>().is_err());
+        assert!(":443:127.0.0.1".parse::<Resolve>().is_err());
+        assert!("example.com:https:127.0.0.1"
+            .parse::<Resolve>()
+            .unwrap_err()
+            .contains("invalid port"));
+        assert!("example.com:443:localhost"
+            .parse::<Resolve>()
+            .unwrap_err()
+            .contains("invalid IP address 'localhost'"));
+    }
+
+    #[test]
+    fn test_socket_addrs_leave_the_port_to_the_url() {
+        let resolve: Resolve = "example.com:443:127.0.0.1".parse().unwrap();
+        assert_eq!(
+            resolve.socket_addrs(),
+            vec!["127.0.0.1:0".parse::<SocketAddr>().unwrap()]
+        );
+    }
+}
@@ -1,5 +1,29 @@
+use crate::http::charset::Charset;
+use crate::http::decode;
 use crate::http::error::HttpError;
 use ::http::HeaderMap;
+use std::time::Duration;
+
+/// Timings measured by the backend while receiving a response
+///
+/// reqwest does not report the individual connection phases, so DNS lookup,
+/// connecting and the TLS handshake are all part of `ttfb`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// Time to first byte: until the status line and headers were received
+    pub ttfb: Duration,
+    /// Until the whole body was received
+    pub total: Duration,
+}
+
+/// A body that arrived compressed and was decoded, see [`HttpResponse::decompress`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentEncoding {
+    /// The `Content-Encoding` the server sent, e.g. `gzip`
+    pub coding: String,
+    /// Size of the body as it was received
+    pub encoded_size: usize,
+}
 
 /// Represents an HTTP response with status, headers, and body
 ///
@@ -15,11 +39,7 @@ use ::http::HeaderMap;
 /// let mut headers = HeaderMap::new();
 /// headers.insert("content-type", "application/json".parse().unwrap());
 ///
-/// let response = HttpResponse {
-///     status: 200,
-///     headers,
-///     body: r#"{"message": "success"}"#.to_string(),
-/// };
+/// let response = HttpResponse::new(200, headers, r#"{"message": "success"}"#);
 ///
 /// assert!(response.is_success());
 /// assert!(response.is_json());
@@ -30,11 +50,128 @@ pub struct HttpResponse {
     pub status: u16,
     /// Response headers
     pub headers: HeaderMap,
-    /// Response body as string
+    /// Response body as text, `bytes` decoded with the charset of the
+    /// `Content-Type` (UTF-8 when it names none), see [`Charset`]
     pub body: String,
+    /// Raw response body exactly as received
+    pub bytes: Vec<u8>,
+    /// How long the response took, if the backend measured it
+    pub timing: Option<Timing>,
+    /// How the body was compressed, if it was decoded
+    pub content_encoding: Option<ContentEncoding>,
 }
 
 impl HttpResponse {
+    /// Constructs a new HttpResponse from the raw body bytes
+    ///
+    /// The text `body` is decoded from `bytes` with the charset named by the
+    /// `Content-Type` header, replacing malformed sequences, while `bytes`
+    /// keeps the original data for binary downloads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wave::http::HttpResponse;
+    /// use http::HeaderMap;
+    ///
+    /// let response = HttpResponse::new(200, HeaderMap::new(), vec![0xff, b'o', b'k']);
+    /// assert_eq!(response.bytes, vec![0xff, b'o', b'k']);
+    /// assert_eq!(response.body, "\u{fffd}ok");
+    /// ```
+    pub fn new(status: u16, headers: HeaderMap, bytes: impl Into<Vec<u8>>) -> Self {
+        let bytes = bytes.into();
+        let charset = headers
+            .get(::http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Charset::from_content_type)
+            .unwrap_or_default();
+        Self {
+            status,
+            headers,
+            body: charset.decode(&bytes),
+            bytes,
+            timing: None,
+            content_encoding: None,
+        }
+    }
+
+    /// Attaches the timings measured while receiving the response
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = Some(timing);
+        self
+    }
+
+    /// Decodes the text `body` with `charset`, whatever the `Content-Type` says
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::http::{Charset, HttpResponse};
+    /// use http::HeaderMap;
+    ///
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("content-type", "text/plain; charset=iso-8859-1".parse().unwrap());
+    /// let response = HttpResponse::new(200, headers, "café");
+    /// assert_eq!(response.body, "cafÃ©");
+    /// let response = response.with_charset(Charset::UTF_8);
+    /// assert_eq!(response.body, "café");
+    /// ```
+    pub fn with_charset(mut self, charset: Charset) -> Self {
+        self.body = charset.decode(&self.bytes);
+        self
+    }
+
+    /// Decodes a body compressed with a `Content-Encoding` wave supports
+    ///
+    /// The headers are kept as they were received, so `Content-Encoding`
+    /// still shows how the body was sent. Bodies with no or an unsupported
+    /// encoding (such as `br`) are left as they are.
+    ///
+    /// # Errors
+    /// Returns `HttpError::Parse` if the body is not valid data in the
+    /// encoding the server claimed.
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::http::HttpResponse;
+    /// use http::HeaderMap;
+    ///
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("content-encoding", "deflate".parse().unwrap());
+    /// let raw = vec![0x78, 0x9c, 0xcb, 0xc8, 4, 0, 1, 0x3b, 0, 0xd2];
+    /// let response = HttpResponse::new(200, headers, raw).decompress().unwrap();
+    /// assert_eq!(response.body, "hi");
+    /// assert_eq!(response.content_encoding.unwrap().encoded_size, 10);
+    /// ```
+    pub fn decompress(self) -> Result<Self, HttpError> {
+        let Some(coding) = self
+            .headers
+            .get(::http::header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Ok(self);
+        };
+        let coding = coding.to_string();
+        match decode::decode(&coding, &self.bytes).map_err(HttpError::Parse)? {
+            Some(decoded) => {
+                tracing::debug!(
+                    coding = %coding,
+                    encoded = self.bytes.len(),
+                    decoded = decoded.len(),
+                    "decoded response body"
+                );
+                Ok(HttpResponse {
+                    timing: self.timing,
+                    content_encoding: Some(ContentEncoding {
+                        coding,
+                        encoded_size: self.bytes.len(),
+                    }),
+                    ..HttpResponse::new(self.status, self.headers, decoded)
+                })
+            }
+            None => Ok(self),
+        }
+    }
+
     /// Returns true if the response status indicates success (2xx)
     pub fn is_success(&self) -> bool {
         self.status >= 200 && self.status < 300
@@ -80,11 +217,11 @@ impl HttpResponse {
     ///     email: String,
     /// }
     ///
-    /// let response = HttpResponse {
-    ///     status: 200,
-    ///     headers: HeaderMap::new(),
-    ///     body: r#"{"name": "Alice", "email": "alice@example.com"}"#.to_string(),
-    /// };
+    /// let response = HttpResponse::new(
+    ///     200,
+    ///     HeaderMap::new(),
+    ///     r#"{"name": "Alice", "email": "alice@example.com"}"#,
+    /// );
     ///
     /// let user: User = response.json()?;
     /// assert_eq!(user.name, "Alice");
@@ -115,35 +252,19 @@ mod tests {
     #[test]
     fn test_response_status_methods() {
         // Test is_success (2xx range)
-        let resp_200 = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: "OK".to_string(),
-        };
-        let resp_201 = HttpResponse {
-            status: 201,
-            headers: HeaderMap::new(),
-            body: "Created".to_string(),
-        };
+        let resp_200 = HttpResponse::new(200, HeaderMap::new(), "OK");
+        let resp_201 = HttpResponse::new(201, HeaderMap::new(), "Created");
         assert!(resp_200.is_success());
         assert!(resp_201.is_success());
 
         // Test is_client_error (4xx range)
-        let resp_404 = HttpResponse {
-            status: 404,
-            headers: HeaderMap::new(),
-            body: "Not Found".to_string(),
-        };
+        let resp_404 = HttpResponse::new(404, HeaderMap::new(), "Not Found");
         assert!(resp_404.is_client_error());
         assert!(resp_404.is_error());
         assert!(!resp_404.is_success());
 
         // Test is_server_error (5xx range)
-        let resp_500 = HttpResponse {
-            status: 500,
-            headers: HeaderMap::new(),
-            body: "Internal Server Error".to_string(),
-        };
+        let resp_500 = HttpResponse::new(500, HeaderMap::new(), "Internal Server Error");
         assert!(resp_500.is_server_error());
         assert!(resp_500.is_error());
         assert!(!resp_500.is_success());
@@ -163,23 +284,11 @@ mod tests {
             ::http::HeaderValue::from_static("text/html"),
         );
 
-        let resp_json = HttpResponse {
-            status: 200,
-            headers: headers_json,
-            body: "{}".to_string(),
-        };
+        let resp_json = HttpResponse::new(200, headers_json, "{}");
 
-        let resp_html = HttpResponse {
-            status: 200,
-            headers: headers_html,
-            body: "<html></html>".to_string(),
-        };
+        let resp_html = HttpResponse::new(200, headers_html, "<html></html>");
 
-        let resp_no_content_type = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: "data".to_string(),
-        };
+        let resp_no_content_type = HttpResponse::new(200, HeaderMap::new(), "data");
 
         assert_eq!(
             resp_json.content_type(),
@@ -215,31 +324,11 @@ mod tests {
             ::http::HeaderValue::from_static("text/html"),
         );
 
-        let resp_json = HttpResponse {
-            status: 200,
-            headers: headers_json,
-            body: "{}".to_string(),
-        };
-        let resp_json_charset = HttpResponse {
-            status: 200,
-            headers: headers_json_charset,
-            body: "{}".to_string(),
-        };
-        let resp_text_json = HttpResponse {
-            status: 200,
-            headers: headers_text_json,
-            body: "{}".to_string(),
-        };
-        let resp_html = HttpResponse {
-            status: 200,
-            headers: headers_html,
-            body: "<html></html>".to_string(),
-        };
-        let resp_no_headers = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: "{}".to_string(),
-        };
+        let resp_json = HttpResponse::new(200, headers_json, "{}");
+        let resp_json_charset = HttpResponse::new(200, headers_json_charset, "{}");
+        let resp_text_json = HttpResponse::new(200, headers_text_json, "{}");
+        let resp_html = HttpResponse::new(200, headers_html, "<html></html>");
+        let resp_no_headers = HttpResponse::new(200, HeaderMap::new(), "{}");
 
         assert!(resp_json.is_json());
         assert!(resp_json_charset.is_json());
@@ -258,17 +347,10 @@ mod tests {
             age: u32,
         }
 
-        let resp_valid_json = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: r#"{"name": "Alice", "age": 30}"#.to_string(),
-        };
+        let resp_valid_json =
+            HttpResponse::new(200, HeaderMap::new(), r#"{"name": "Alice", "age": 30}"#);
 
-        let resp_invalid_json = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: "invalid json".to_string(),
-        };
+        let resp_invalid_json = HttpResponse::new(200, HeaderMap::new(), "invalid json");
 
         let parsed: Result<TestData, _> = resp_valid_json.json();
         assert!(parsed.is_ok());
@@ -288,13 +370,17 @@ mod tests {
 
     #[test]
     fn test_response_text() {
-        let resp = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: "Hello, World!".to_string(),
-        };
+        let resp = HttpResponse::new(200, HeaderMap::new(), "Hello, World!");
 
         assert_eq!(resp.text(), "Hello, World!");
         assert_eq!(resp.text(), &resp.body); // Ensure it's the same reference
     }
+
+    #[test]
+    fn test_response_keeps_raw_bytes() {
+        let raw = vec![0x89, b'P', b'N', b'G', 0x00, 0xff];
+        let resp = HttpResponse::new(200, HeaderMap::new(), raw.clone());
+        assert_eq!(resp.bytes, raw);
+        assert!(resp.body.contains('\u{fffd}'));
+    }
 }
@@ -0,0 +1,158 @@
+//! TLS configuration for the reqwest backend
+
+use crate::http::error::HttpError;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// TLS settings: certificate verification, private CAs and client certificates
+///
+/// Can be given on the command line or in a collection's `tls:` block:
+///
+/// ```yaml
+/// tls:
+///   cacert: ./certs/ca.pem
+///   cert: ./certs/client.pem
+///   key: ./certs/client.key
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsOptions {
+    /// Skip server certificate and hostname verification
+    #[serde(default)]
+    pub insecure: bool,
+    /// PEM file with additional trusted root certificates
+    pub cacert: Option<PathBuf>,
+    /// PEM client certificate for mutual TLS
+    pub cert: Option<PathBuf>,
+    /// PEM private key for the client certificate (defaults to the `cert` file)
+    pub key: Option<PathBuf>,
+}
+
+impl TlsOptions {
+    /// Fills settings missing from `self` with those from `fallback`
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::http::TlsOptions;
+    ///
+    /// let cli = TlsOptions { cacert: Some("cli.pem".into()), ..Default::default() };
+    /// let collection = TlsOptions {
+    ///     cacert: Some("collection.pem".into()),
+    ///     insecure: true,
+    ///     ..Default::default()
+    /// };
+    /// let merged = cli.or(&collection);
+    /// assert_eq!(merged.cacert, Some("cli.pem".into()));
+    /// assert!(merged.insecure);
+    /// ```
+    pub fn or(self, fallback: &TlsOptions) -> TlsOptions {
+        TlsOptions {
+            insecure: self.insecure || fallback.insecure,
+            cacert: self.cacert.or_else(|| fallback.cacert.clone()),
+            cert: self.cert.or_else(|| fallback.cert.clone()),
+            key: self.key.or_else(|| fallback.key.clone()),
+        }
+    }
+
+    /// Applies the settings to a reqwest client builder
+    pub(crate) fn apply(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, HttpError> {
+        if self.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        if let Some(path) = &self.cacert {
+            let pem = read_pem(path, "CA certificate")?;
+            let cert = reqwest::Certificate::from_pem(&pem).map_err(|e| {
+                HttpError::Other(format!("Invalid CA certificate '{}': {e}", path.display()))
+            })?;
+            builder = builder.add_root_certificate(cert);
+        }
+        if let Some(cert_path) = &self.cert {
+            let key_path = self.key.as_deref().unwrap_or(cert_path);
+            let cert = read_pem(cert_path, "client certificate")?;
+            let key = read_pem(key_path, "client key")?;
+            let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key).map_err(|e| {
+                HttpError::Other(format!(
+                    "Invalid client certificate '{}': {e}",
+                    cert_path.display()
+                ))
+            })?;
+            builder = builder.identity(identity);
+        }
+        Ok(builder)
+    }
+
+    /// Builds a native-tls connector offering the `alpn` protocols, for
+    /// connections made without reqwest such as `wave grpc`
+    pub(crate) fn native_connector(
+        &self,
+        alpn: &[&str],
+    ) -> Result<native_tls::TlsConnector, HttpError> {
+        let mut builder = native_tls::TlsConnector::builder();
+        builder.request_alpns(alpn);
+        if self.insecure {
+            builder
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+        if let Some(path) = &self.cacert {
+            let pem = read_pem(path, "CA certificate")?;
+            let cert = native_tls::Certificate::from_pem(&pem).map_err(|e| {
+                HttpError::Other(format!("Invalid CA certificate '{}': {e}", path.display()))
+            })?;
+            builder.add_root_certificate(cert);
+        }
+        if let Some(cert_path) = &self.cert {
+            let key_path = self.key.as_deref().unwrap_or(cert_path);
+            let cert = read_pem(cert_path, "client certificate")?;
+            let key = read_pem(key_path, "client key")?;
+            let identity = native_tls::Identity::from_pkcs8(&cert, &key).map_err(|e| {
+                HttpError::Other(format!(
+                    "Invalid client certificate '{}': {e}",
+                    cert_path.display()
+                ))
+            })?;
+            builder.identity(identity);
+        }
+        builder
+            .build()
+            .map_err(|e| HttpError::Other(format!("Failed to set up TLS: {e}")))
+    }
+}
+
+fn read_pem(path: &Path, what: &str) -> Result<Vec<u8>, HttpError> {
+    std::fs::read(path)
+        .map_err(|e| HttpError::Other(format!("Failed to read {what} '{}': {e}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tls_options_from_yaml() {
+        let tls: TlsOptions =
+            serde_yaml::from_str("cacert: ./ca.pem\ncert: ./client.pem\n").unwrap();
+        assert!(!tls.insecure);
+        assert_eq!(tls.cacert, Some(PathBuf::from("./ca.pem")));
+        assert_eq!(tls.cert, Some(PathBuf::from("./client.pem")));
+        assert_eq!(tls.key, None);
+    }
+
+    #[test]
+    fn test_tls_options_rejects_unknown_keys() {
+        assert!(serde_yaml::from_str::<TlsOptions>("verify: false\n").is_err());
+    }
+
+    #[test]
+    fn test_missing_ca_file_is_reported() {
+        let tls = TlsOptions {
+            cacert: Some(PathBuf::from("/nonexistent/wave-ca.pem")),
+            ..Default::default()
+        };
+        let err = tls.apply(reqwest::Client::builder()).unwrap_err();
+        assert!(err.to_string().contains("Failed to read CA certificate"));
+    }
+}
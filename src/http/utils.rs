@@ -1,10 +1,13 @@
 use crate::http::error::HttpError;
 use ::http::Method;
+use url::{Host, Url};
 
 /// Parse a string into an HTTP method
 ///
-/// Supports the standard HTTP methods commonly used in REST APIs and web development.
-/// Each method has specific semantics according to HTTP specifications.
+/// The standard methods (GET, POST, PUT, DELETE, PATCH, HEAD, OPTIONS, TRACE
+/// and CONNECT) are matched case-insensitively. Any other valid RFC 9110
+/// token, such as `PURGE` or `PROPFIND`, is accepted as an extension method
+/// and kept exactly as written, since method names are case-sensitive.
 ///
 /// # Examples
 ///
@@ -14,6 +17,8 @@ use ::http::Method;
 ///
 /// assert_eq!(parse_method("GET").unwrap(), Method::GET);
 /// assert_eq!(parse_method("post").unwrap(), Method::POST);
+/// assert_eq!(parse_method("PROPFIND").unwrap().as_str(), "PROPFIND");
+/// assert!(parse_method("NOT A METHOD").is_err());
 /// ```
 pub fn parse_method(s: &str) -> Result<Method, HttpError> {
     match s.to_uppercase().as_str() {
@@ -24,7 +29,33 @@ pub fn parse_method(s: &str) -> Result<Method, HttpError> {
         "PATCH" => Ok(Method::PATCH),
         "HEAD" => Ok(Method::HEAD),
         "OPTIONS" => Ok(Method::OPTIONS),
-        _ => Err(HttpError::UnsupportedMethod(s.to_string())),
+        "TRACE" => Ok(Method::TRACE),
+        "CONNECT" => Ok(Method::CONNECT),
+        _ => Method::from_bytes(s.as_bytes())
+            .map_err(|_| HttpError::UnsupportedMethod(s.to_string())),
+    }
+}
+
+/// Whether `url` points at this machine: `localhost`, a `.localhost` name or
+/// a loopback address
+///
+/// # Examples
+///
+/// ```
+/// use url::Url;
+/// use wave::http::is_loopback;
+///
+/// assert!(is_loopback(&Url::parse("http://localhost:8080").unwrap()));
+/// assert!(is_loopback(&Url::parse("http://[::1]/").unwrap()));
+/// assert!(!is_loopback(&Url::parse("http://example.com").unwrap()));
+/// ```
+pub fn is_loopback(url: &Url) -> bool {
+    match url.host() {
+        // Parsed domains are already lowercase
+        Some(Host::Domain(host)) => host == "localhost" || host.ends_with(".localhost"),
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
     }
 }
 
@@ -59,9 +90,44 @@ mod tests {
             Method::OPTIONS
         );
 
-        assert!(matches!(
-            parse_method("INVALID"),
-            Err(HttpError::UnsupportedMethod(_))
-        ));
+        assert_eq!(
+            parse_method("PURGE")
+                .expect("Test: Extension PURGE")
+                .as_str(),
+            "PURGE"
+        );
+        assert_eq!(
+            parse_method("propfind")
+                .expect("Test: Extension propfind")
+                .as_str(),
+            "propfind"
+        );
+        for invalid in ["", "NOT VALID", "GET/", "R\u{e9}PORT"] {
+            assert!(matches!(
+                parse_method(invalid),
+                Err(HttpError::UnsupportedMethod(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_is_loopback() {
+        for url in [
+            "http://localhost",
+            "http://LOCALHOST:3000/x",
+            "http://api.localhost",
+            "http://127.0.0.1:8080",
+            "http://127.1.2.3",
+            "http://[::1]:8080",
+        ] {
+            assert!(is_loopback(&Url::parse(url).unwrap()), "{url}");
+        }
+        for url in [
+            "http://example.com",
+            "http://10.0.0.5",
+            "http://localhost.example.com",
+        ] {
+            assert!(!is_loopback(&Url::parse(url).unwrap()), "{url}");
+        }
     }
 }
@@ -1,13 +1,49 @@
+pub mod assertions;
+pub mod bench;
 pub mod collection;
+pub mod completions;
+pub mod condition;
+pub mod config;
+pub mod dataset;
+pub mod doctor;
+pub mod dotenv;
+pub mod dynamic;
 pub mod error;
+pub mod fields;
+pub mod grpc;
+pub mod hooks;
 pub mod http;
+pub mod logging;
+pub mod markup;
+pub mod mock;
+pub mod oauth2;
+pub mod paginate;
 pub mod printer;
+pub mod query;
+pub mod runner;
+pub mod session;
+pub mod snapshot;
+pub mod validate;
+pub mod watch;
 
-use crate::http::{Client, HttpRequest, RequestBody, ReqwestBackend};
-use ::http::{HeaderMap, Method};
-use clap::{Parser, Subcommand};
-use error::{CliError, CollectionError, WaveError};
-use std::collections::HashMap;
+use crate::http::{
+    to_curl, Auth, CassetteBackend, Charset, Client, HeaderLayer, HttpBackend, HttpError,
+    HttpRequest, HttpResponse, MultipartPart, OfflineBackend, RequestBody, RequestFailure,
+    RequestIdFormat, ReqwestBackend, Resolve, TlsOptions,
+};
+use ::http::{header, HeaderMap, HeaderName, HeaderValue, Method};
+use clap::{Args, Parser, Subcommand};
+use collection::{Collection, SaveTarget, Timeouts};
+use config::{ColorChoice, Config, DefaultScheme};
+use error::{CliError, CollectionError, ParseError, WaveError};
+use grpc::proto::{FieldType, Scalar};
+use paginate::Paginate;
+use query::Query;
+use runner::RunResult;
+use session::Session;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 // Type aliases for clarity and consistency
 pub type KeyValuePairs = Vec<(String, String)>;
@@ -20,17 +56,32 @@ pub mod http_client {
 }
 
 /// Convert Vec of header tuples to HeaderMap
-fn headers_to_map(headers: Headers) -> HeaderMap {
-    let mut header_map = HeaderMap::new();
-    for (key, value) in headers {
-        if let (Ok(header_name), Ok(header_value)) = (
-            key.parse::<::http::HeaderName>(),
-            value.parse::<::http::HeaderValue>(),
-        ) {
-            header_map.insert(header_name, header_value);
-        }
-    }
-    header_map
+///
+/// A key ending in `+` (from `key+:value`) adds another value to the header;
+/// other keys replace earlier values. Fails with `ParseError::Header` naming
+/// the first header that is not valid HTTP, e.g. a name with spaces or a value
+/// with a line break.
+fn headers_to_map(headers: Headers) -> Result<HeaderMap, WaveError> {
+    layer_headers(&headers, &[])
+}
+
+/// Layers command line headers over a collection request's headers
+///
+/// See [`http::headers`] for the precedence rules.
+fn layer_headers(
+    collection: &[(String, String)],
+    cli: &[(String, String)],
+) -> Result<HeaderMap, WaveError> {
+    let layer = |pairs: &[(String, String)]| {
+        HeaderLayer::parse(pairs.iter().map(|(k, v)| (k, v))).map_err(|e| {
+            let msg = match e {
+                HttpError::Parse(msg) => msg,
+                other => other.to_string(),
+            };
+            WaveError::Parse(ParseError::Header(msg))
+        })
+    };
+    Ok(HeaderLayer::merge([&layer(collection)?, &layer(cli)?]))
 }
 
 #[derive(Subcommand)]
@@ -42,77 +93,909 @@ pub enum Command {
         /// Headers and body data (key:value or key=value)
         #[arg(value_parser, trailing_var_arg = true)]
         params: Vec<String>,
-        /// Print the full response (status, headers, body)
+        /// A body is only sent when one is given, e.g. for Elasticsearch queries
+        #[command(flatten)]
+        body: BodyOptions,
+        /// Print the outgoing request and the full response (status, headers, body)
         #[arg(short, long)]
         verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
     },
     /// Send a POST request
     Post {
         url: String,
         #[arg(value_parser, trailing_var_arg = true)]
         params: Vec<String>,
-        #[arg(long)]
-        form: bool,
+        #[command(flatten)]
+        body: BodyOptions,
         #[arg(short, long)]
         verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
     },
     /// Send a PUT request
     Put {
         url: String,
         #[arg(value_parser, trailing_var_arg = true)]
         params: Vec<String>,
-        #[arg(long)]
-        form: bool,
+        #[command(flatten)]
+        body: BodyOptions,
         #[arg(short, long)]
         verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
     },
     /// Send a PATCH request
     Patch {
         url: String,
         #[arg(value_parser, trailing_var_arg = true)]
         params: Vec<String>,
-        #[arg(long)]
-        form: bool,
+        #[command(flatten)]
+        body: BodyOptions,
         #[arg(short, long)]
         verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
     },
     /// Send a DELETE request
     Delete {
         url: String,
         #[arg(value_parser, trailing_var_arg = true)]
         params: Vec<String>,
+        #[command(flatten)]
+        body: BodyOptions,
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
+    },
+    /// Send a HEAD request (response headers only)
+    Head {
+        url: String,
+        #[arg(value_parser, trailing_var_arg = true)]
+        params: Vec<String>,
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
+    },
+    /// Send an OPTIONS request
+    Options {
+        url: String,
+        #[arg(value_parser, trailing_var_arg = true)]
+        params: Vec<String>,
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
+    },
+    /// Send a request with any method, including extensions such as PURGE or PROPFIND
+    Request {
+        /// The method to send, e.g. PROPFIND
+        #[arg(short = 'X', long)]
+        method: String,
+        url: String,
+        #[arg(value_parser, trailing_var_arg = true)]
+        params: Vec<String>,
+        #[command(flatten)]
+        body: BodyOptions,
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
+    },
+    /// Send a GraphQL query (POSTed as JSON)
+    Graphql {
+        /// The GraphQL endpoint
+        url: String,
+        /// The query or mutation, or @path to read it from a file
+        query: String,
+        /// Headers and variables (key:value or key=value)
+        #[arg(value_parser, trailing_var_arg = true)]
+        params: Vec<String>,
+        #[command(flatten)]
+        graphql: GraphqlOptions,
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
+    },
+    /// Call a gRPC method with a JSON request message, or list a server's methods
+    Grpc {
+        /// Server address: host:port for plaintext HTTP/2, or https://host[:port] for TLS
+        address: String,
+        /// Method to call as package.Service/Method (lists every method when omitted)
+        method: Option<String>,
+        /// Metadata and request fields (key:value or key=value)
+        #[arg(value_parser, trailing_var_arg = true)]
+        params: Vec<String>,
+        #[command(flatten)]
+        grpc: GrpcOptions,
         #[arg(short, long)]
         verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
     },
-    /// Run a saved request from a collection
+    /// Run a saved request from a collection, or remove or rename one
     #[command(
         short_flag = 'c',
         visible_alias = "c",
         visible_short_flag_alias = 'c',
-        about = "Run a saved request from a collection"
+        about = "Run a saved request from a collection",
+        args_conflicts_with_subcommands = true,
+        subcommand_negates_reqs = true
     )]
     Collection {
+        /// Name of the collection
+        #[arg(required = true)]
+        collection: Option<String>,
+        /// Name of the request in the collection
+        #[arg(required = true)]
+        request: Option<String>,
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(flatten)]
+        options: RequestOptions,
+        #[command(flatten)]
+        vars: VariableOptions,
+        /// Headers and body data (key:value or key=value)
+        #[arg(value_parser, trailing_var_arg = true)]
+        params: Vec<String>,
+        #[command(subcommand)]
+        edit: Option<CollectionEdit>,
+    },
+    /// Run every request in a collection in order and report pass/fail
+    Run {
+        /// Name of the collection
+        collection: String,
+        /// Names of the requests to run (defaults to every request in the collection)
+        requests: Vec<String>,
+        /// Print the full response of every request
+        #[arg(short, long)]
+        verbose: bool,
+        #[command(flatten)]
+        run: RunOptions,
+        #[command(flatten)]
+        options: RequestOptions,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// Save a collection request's response body as a snapshot, or check it against one
+    Snapshot {
         /// Name of the collection
         collection: String,
         /// Name of the request in the collection
         request: String,
+        /// Compare the response with the saved snapshot instead of saving it
+        #[arg(long)]
+        check: bool,
+        /// Leave a JSON field out of --check, e.g. 'updated_at' or '..id' (repeatable)
+        #[arg(long, value_name = "PATH")]
+        ignore: Vec<Query>,
+        /// Print the outgoing request and the full response
         #[arg(short, long)]
         verbose: bool,
-        /// Variable overrides in KEY=VALUE format (overrides collection variables)
-        #[arg(long = "var", value_name = "KEY=VALUE")]
-        var: Vec<String>,
+        #[command(flatten)]
+        options: RequestOptions,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// Send a request many times and report latency percentiles, throughput and errors
+    Bench {
+        /// URL to send GET requests to, or the name of a collection
+        target: String,
+        /// Name of the request when TARGET is a collection
+        request: Option<String>,
+        #[command(flatten)]
+        bench: BenchOptions,
+        #[command(flatten)]
+        options: RequestOptions,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// Serve the `mock:` responses of a collection from a local HTTP server
+    Mock {
+        /// Name of the collection
+        collection: String,
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// List the requests in one collection, or in every collection
+    List {
+        /// Name of the collection (defaults to every collection)
+        collection: Option<String>,
+        /// Only list requests with this tag (repeatable; any tag matches)
+        #[arg(long, value_name = "TAG")]
+        tag: Vec<String>,
+    },
+    /// Check collection files for mistakes, e.g. in CI
+    Validate {
+        /// Name of the collection (defaults to every collection)
+        collection: Option<String>,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// List the variables a collection uses, where, and where their values come from
+    Vars {
+        /// Name of the collection
+        collection: String,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// Show the description, URL, variables and body of a collection request
+    Describe {
+        /// Name of the collection
+        collection: String,
+        /// Name of the request (defaults to listing the collection's requests)
+        request: Option<String>,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// Open a collection file in $VISUAL or $EDITOR at a request, then validate it
+    Edit {
+        /// Name of the collection
+        collection: String,
+        /// Name of the request to jump to
+        request: Option<String>,
+        #[command(flatten)]
+        vars: VariableOptions,
+    },
+    /// Print the JSON Schema of collection files, for editor completion and CI
+    Schema,
+    /// Export a collection request in another format
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+    /// Print a shell completion script, e.g. `wave completions bash > /etc/bash_completion.d/wave`
+    Completions {
+        /// Shell to generate the script for
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    /// Print collection names, or the request names of a collection, for completion scripts
+    #[command(name = "__names", hide = true)]
+    Names {
+        /// Collection whose request names to print
+        collection: Option<String>,
+    },
+    /// Check configuration, proxies and connectivity, for bug reports
+    Doctor {
+        /// URL or host to check DNS, IPv4, IPv6 and TLS against, e.g. api.example.com
+        target: Option<String>,
+    },
+    /// Create a .wave directory with a starter collection
+    Init {
+        /// Name of the collection to create (file name without extension)
+        #[arg(long, default_value = "api")]
+        name: String,
+        /// Overwrite the collection file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Formats supported by `wave export`
+/// Changes to the requests of a collection, made by `wave collection rm` and
+/// `wave collection mv`
+#[derive(Subcommand)]
+pub enum CollectionEdit {
+    /// Remove a request from a collection file
+    Rm {
+        /// Name of the collection
+        collection: String,
+        /// Name of the request to remove
+        request: String,
+    },
+    /// Rename a request of a collection, and the depends_on entries naming it
+    Mv {
+        /// Name of the collection
+        collection: String,
+        /// Current name of the request
+        old: String,
+        /// New name of the request
+        new: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExportFormat {
+    /// Print a collection request as an equivalent curl command
+    Curl {
+        /// Name of the collection
+        collection: String,
+        /// Name of the request in the collection
+        request: String,
+        #[command(flatten)]
+        vars: VariableOptions,
         /// Headers and body data (key:value or key=value)
         #[arg(value_parser, trailing_var_arg = true)]
         params: Vec<String>,
     },
 }
 
+/// Variable options shared by the subcommands that resolve collection requests
+#[derive(Args, Debug, Clone, Default)]
+pub struct VariableOptions {
+    /// Environment whose variables are layered over the collection variables
+    #[arg(long = "env", value_name = "NAME")]
+    pub env: Option<String>,
+    /// Variable overrides in KEY=VALUE format (overrides collection variables)
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    pub var: Vec<String>,
+    /// Fail on missing variables instead of prompting for them
+    #[arg(long)]
+    pub no_input: bool,
+}
+
+/// Options for `wave run`
+#[derive(Args, Debug, Clone)]
+pub struct RunOptions {
+    /// Send up to N requests at once; requests using a variable captured
+    /// by an earlier request still wait for it
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub parallel: u64,
+    /// Only run requests with this tag (repeatable; any tag matches)
+    #[arg(long, value_name = "TAG")]
+    pub tag: Vec<String>,
+    /// Start at most this many requests a second, e.g. 5, 5/s or 120/min
+    #[arg(long, value_name = "N/SEC", value_parser = runner::parse_rate)]
+    pub rate: Option<f64>,
+    /// Also write a report for CI: junit:PATH or tap:PATH, or junit or tap
+    /// alone for stdout, which moves the usual output to stderr (repeatable)
+    #[arg(long, value_name = "FORMAT[:PATH]", value_parser = runner::parse_report)]
+    pub report: Vec<runner::Report>,
+    /// Run the requests once per row of a .csv or .json file, with its
+    /// columns as variables
+    #[arg(long, value_name = "FILE")]
+    pub data: Option<PathBuf>,
+    /// Send requests even when a request they name in depends_on: did not
+    /// pass, instead of skipping them
+    #[arg(long)]
+    pub continue_on_error: bool,
+    /// Wait this long before each request after the first, e.g. 500ms or 2s;
+    /// a request's own delay: wins
+    #[arg(long, value_name = "DURATION", value_parser = runner::parse_delay)]
+    pub delay: Option<Duration>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            parallel: 1,
+            tag: Vec::new(),
+            rate: None,
+            report: Vec::new(),
+            data: None,
+            continue_on_error: false,
+            delay: None,
+        }
+    }
+}
+
+/// Options for `wave bench`
+#[derive(Args, Debug, Clone)]
+pub struct BenchOptions {
+    /// Total number of requests to send
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub requests: u64,
+    /// Number of requests in flight at once
+    #[arg(long, value_name = "N", default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub concurrency: u64,
+    /// Start at most this many requests a second, e.g. 50, 50/s or 600/min
+    #[arg(long, value_name = "N/SEC", value_parser = runner::parse_rate)]
+    pub rate: Option<f64>,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            requests: 100,
+            concurrency: 10,
+            rate: None,
+        }
+    }
+}
+
+/// Options for `wave graphql`
+#[derive(Args, Debug, Clone, Default)]
+pub struct GraphqlOptions {
+    /// Variables as a JSON object, inline or @path (key=value params override them)
+    #[arg(long, value_name = "JSON")]
+    pub variables: Option<String>,
+    /// Operation to run when the query document defines several
+    #[arg(long, value_name = "NAME")]
+    pub operation_name: Option<String>,
+}
+
+/// Options for `wave grpc`
+#[derive(Args, Debug, Clone, Default)]
+pub struct GrpcOptions {
+    /// .proto file defining the service (repeatable; server reflection is used without one)
+    #[arg(long, value_name = "FILE")]
+    pub proto: Vec<PathBuf>,
+    /// Directory to search for files imported by --proto files (repeatable)
+    #[arg(short = 'I', long, value_name = "DIR", requires = "proto")]
+    pub import_path: Vec<PathBuf>,
+    /// Request message as a JSON object, inline or @path (key=value params override its fields)
+    #[arg(long, value_name = "JSON")]
+    pub data: Option<String>,
+}
+
+/// Options shared by every subcommand that sends a request
+#[derive(Args, Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// Abort the request if no complete response arrives within this many seconds
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+    /// Give up if no connection to the server is established within this many seconds
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub connect_timeout: Option<u64>,
+    /// Give up if the server sends nothing for this many seconds while responding
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub read_timeout: Option<u64>,
+    /// Write the raw response body to a file instead of printing it
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// With --output, resume a partial download, asking the server only for the bytes the file lacks
+    #[arg(long = "continue", requires = "output")]
+    pub resume: bool,
+    /// Basic authentication credentials in user:password form
+    #[arg(long, value_name = "USER:PASS", conflicts_with = "bearer")]
+    pub auth: Option<String>,
+    /// Bearer token sent in the Authorization header
+    #[arg(long, value_name = "TOKEN")]
+    pub bearer: Option<String>,
+    /// Print the request that would be sent instead of sending it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// With --dry-run, print the request as an equivalent curl command
+    #[arg(long, requires = "dry_run")]
+    pub as_curl: bool,
+    /// Send requests through this proxy (overrides HTTP_PROXY/HTTPS_PROXY)
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+    /// Proxy credentials in user:password form
+    #[arg(long, value_name = "USER:PASS", requires = "proxy")]
+    pub proxy_user: Option<String>,
+    /// Ignore proxies configured in the environment
+    #[arg(long, conflicts_with = "proxy")]
+    pub no_proxy: bool,
+    /// Connect to ADDR for requests to HOST instead of looking it up, e.g. api.example.com:443:10.0.0.5 (repeatable)
+    #[arg(long, value_name = "HOST:PORT:ADDR")]
+    pub resolve: Vec<Resolve>,
+    /// Skip TLS certificate verification
+    #[arg(short = 'k', long)]
+    pub insecure: bool,
+    /// Trust the CA certificates in this PEM file
+    #[arg(long, value_name = "PEM")]
+    pub cacert: Option<PathBuf>,
+    /// Client certificate (PEM) for mutual TLS
+    #[arg(long, value_name = "PEM")]
+    pub cert: Option<PathBuf>,
+    /// Private key (PEM) for --cert, if not included in the certificate file
+    #[arg(long, value_name = "PEM", requires = "cert")]
+    pub key: Option<PathBuf>,
+    /// Print only the part of a JSON response selected by a JSONPath query, e.g. '$.data[*].email'
+    #[arg(long, value_name = "QUERY", conflicts_with = "output")]
+    pub filter: Option<Query>,
+    /// Output format: pretty for people, json for scripts (status, headers, body, timing)
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t)]
+    pub format: OutputFormat,
+    /// Print only the status line and response headers
+    #[arg(long, conflicts_with_all = ["body_only", "output"])]
+    pub headers_only: bool,
+    /// Print the status line, headers and body exactly as received, without color or formatting
+    #[arg(long, conflicts_with_all = ["headers_only", "body_only", "output", "format"])]
+    pub raw: bool,
+    /// Print only this response header, whatever the status (repeatable)
+    #[arg(long, value_name = "NAME", conflicts_with = "body_only")]
+    pub show_header: Vec<HeaderName>,
+    /// Print only the raw response body, e.g. for piping into jq
+    #[arg(long, conflicts_with = "output")]
+    pub body_only: bool,
+    /// When to color output (NO_COLOR is honored with auto)
+    #[arg(long, value_enum, value_name = "WHEN")]
+    pub color: Option<ColorChoice>,
+    /// Never color output, same as --color never
+    #[arg(long, conflicts_with = "color")]
+    pub no_color: bool,
+    /// Exit with an error when the response status is 4xx or 5xx
+    #[arg(short = 'f', long)]
+    pub fail: bool,
+    /// Exit with an error instead of a warning when params or body options would not be used
+    #[arg(long)]
+    pub strict: bool,
+    /// Persist cookies and the Authorization header in .wave/sessions/NAME.json
+    #[arg(long, value_name = "NAME")]
+    pub session: Option<String>,
+    /// Send requests and write their responses to a cassette file (.yaml or .json), replacing it
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Answer requests from a cassette written with --record instead of sending them
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+    /// Never use the network: only localhost is reached, and --replay answers everything else
+    #[arg(long, conflicts_with = "record")]
+    pub offline: bool,
+    /// Append the request to a collection, e.g. --save mycoll:create-user
+    #[arg(long, value_name = "COLLECTION:REQUEST")]
+    pub save: Option<SaveTarget>,
+    /// Send the request again INTERVAL after each response (e.g. 500ms, 5s, 1m) until Ctrl-C
+    #[arg(long, value_name = "INTERVAL", value_parser = watch::parse_interval, conflicts_with_all = ["output", "dry_run", "save"])]
+    pub watch: Option<Duration>,
+    /// Cut printed response bodies after SIZE bytes, e.g. 4096, 64k or 1m (default 64k on a terminal)
+    #[arg(long, value_name = "SIZE", value_parser = printer::parse_size)]
+    pub max_body: Option<usize>,
+    /// Print response bodies in full, however large
+    #[arg(long, conflicts_with = "max_body")]
+    pub full: bool,
+    /// Show the response through $PAGER (less by default) when printing to a terminal
+    #[arg(long)]
+    pub pager: bool,
+    /// Ask the server to compress the response (sends Accept-Encoding: gzip, deflate)
+    #[arg(long)]
+    pub compressed: bool,
+    /// Keep gzip and deflate response bodies compressed, as received, instead of decoding them
+    #[arg(long)]
+    pub no_decompress: bool,
+    /// Decode response text with this charset, e.g. iso-8859-1, whatever the Content-Type says
+    #[arg(long, value_name = "CHARSET")]
+    pub response_charset: Option<Charset>,
+    /// Follow paginated responses and combine them: link (default), next:PATH, page:PARAM or offset:PARAM
+    #[arg(long, value_name = "STRATEGY", num_args = 0..=1, require_equals = true, default_missing_value = "link", conflicts_with_all = ["output", "watch"])]
+    pub paginate: Option<Paginate>,
+    /// With --paginate, where the items of each page are, e.g. data or '$.results'
+    #[arg(long, value_name = "PATH", requires = "paginate")]
+    pub items: Option<String>,
+    /// With --paginate, stop after this many pages (default 100)
+    #[arg(long, value_name = "N", requires = "paginate", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_pages: Option<u64>,
+    /// Tag each request with a fresh correlation ID and print it: uuid (default) or traceparent
+    #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "uuid")]
+    pub request_id: Option<RequestIdFormat>,
+    /// Header the correlation ID is sent in (default X-Request-Id, or traceparent); implies --request-id
+    #[arg(long, value_name = "NAME")]
+    pub request_id_header: Option<HeaderName>,
+    /// Scheme for URLs given without one: https (default, but http for localhost) or http
+    #[arg(long, value_enum, value_name = "SCHEME")]
+    pub default_scheme: Option<DefaultScheme>,
+    /// Headers from the config file, added when a request does not set them
+    #[arg(skip)]
+    pub default_headers: HashMap<String, String>,
+    /// Collection directory from the config file, used by `--save`
+    #[arg(skip)]
+    pub collection_dir: PathBuf,
+    /// Responses are GraphQL results, set by `wave graphql`
+    #[arg(skip)]
+    pub graphql: bool,
+}
+
+impl RequestOptions {
+    /// Applies the options to a request built by one of the handlers
+    pub fn apply(&self, mut req: HttpRequest) -> HttpRequest {
+        http::headers::fill_defaults(
+            &mut req.headers,
+            &http::headers::lenient_header_map(&self.default_headers),
+        );
+        if let Some(secs) = self.timeout {
+            req.timeout = Some(Duration::from_secs(secs));
+        }
+        if let Some(auth) = self.credentials() {
+            tracing::debug!("Authorization header set from --auth/--bearer");
+            auth.apply(&mut req.headers);
+        }
+        if self.compressed {
+            req.headers
+                .entry(header::ACCEPT_ENCODING)
+                .or_insert(HeaderValue::from_static("gzip, deflate"));
+        }
+        if let Some((name, format)) = self.request_id() {
+            if let Ok(value) = HeaderValue::from_str(&format.generate()) {
+                tracing::debug!(header = %name, "correlation ID added");
+                req.headers.entry(name).or_insert(value);
+            }
+        }
+        req
+    }
+
+    /// The header and format of the correlation ID added to each request, if any
+    pub fn request_id(&self) -> Option<(HeaderName, RequestIdFormat)> {
+        if self.request_id.is_none() && self.request_id_header.is_none() {
+            return None;
+        }
+        let format = self.request_id.unwrap_or_default();
+        let name = self
+            .request_id_header
+            .clone()
+            .unwrap_or_else(|| format.default_header());
+        Some((name, format))
+    }
+
+    /// Builds the backend shared by every request the command sends: the
+    /// reqwest backend, wrapped in a cassette with `--record` or `--replay`
+    pub fn backend(&self) -> Result<Box<dyn HttpBackend + Send + Sync>, WaveError> {
+        if let Some(path) = &self.replay {
+            let cassette =
+                CassetteBackend::<ReqwestBackend>::replay(path).map_err(|e| match e {
+                    HttpError::Other(msg) => WaveError::Io(msg),
+                    e => e.into(),
+                })?;
+            return Ok(Box::new(cassette));
+        }
+        let backend = self.network_backend()?;
+        Ok(match &self.record {
+            Some(path) => Box::new(CassetteBackend::record(backend, path)),
+            None => backend,
+        })
+    }
+
+    /// Builds the reqwest backend, limited to localhost with `--offline`
+    pub fn network_backend(&self) -> Result<Box<dyn HttpBackend + Send + Sync>, WaveError> {
+        let backend = self.reqwest_backend()?;
+        Ok(if self.offline {
+            Box::new(OfflineBackend::new(backend))
+        } else {
+            Box::new(backend)
+        })
+    }
+
+    /// Builds the reqwest backend, which sends requests over the network
+    pub fn reqwest_backend(&self) -> Result<ReqwestBackend, WaveError> {
+        let mut builder = ReqwestBackend::builder();
+        if let Some(secs) = self.timeout {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = self.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = self.read_timeout {
+            builder = builder.read_timeout(Duration::from_secs(secs));
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(user) = &self.proxy_user {
+            let (username, password) = user.split_once(':').unwrap_or((user, ""));
+            builder = builder.proxy_auth(username, password);
+        }
+        if self.no_proxy {
+            builder = builder.no_proxy();
+        }
+        for resolve in &self.resolve {
+            builder = builder.resolve(resolve.clone());
+        }
+        if self.no_decompress {
+            builder = builder.no_decompress();
+        }
+        if let Some(charset) = self.response_charset {
+            builder = builder.response_charset(charset);
+        }
+        builder.tls(self.tls()).build().map_err(WaveError::from)
+    }
+
+    /// Fills options not given on the command line from the config file
+    pub fn with_config(&self, config: &Config) -> RequestOptions {
+        RequestOptions {
+            timeout: self.timeout.or(config.timeout),
+            proxy: if self.no_proxy {
+                None
+            } else {
+                self.proxy.clone().or_else(|| config.proxy.clone())
+            },
+            color: self.color.or(config.color),
+            fail: self.fail || config.fail.unwrap_or(false),
+            strict: self.strict || config.strict.unwrap_or(false),
+            max_body: self.max_body.or(config.max_body),
+            pager: self.pager || config.pager.unwrap_or(false),
+            request_id: self.request_id.or(config.request_id),
+            request_id_header: self.request_id_header.clone().or_else(|| {
+                config
+                    .request_id_header
+                    .as_deref()
+                    .and_then(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+            }),
+            default_scheme: self.default_scheme.or(config.default_scheme),
+            default_headers: config.headers.clone(),
+            collection_dir: config.collection_dir(),
+            ..self.clone()
+        }
+    }
+
+    /// Asks for the rest of a partial `--output` file with `--continue`
+    ///
+    /// Sets `Range: bytes=N-`, where N is the size of the file, and returns
+    /// N. Nothing is resumed if the file is missing or empty, or the request
+    /// sets its own `Range`.
+    fn resume_download(&self, req: &mut HttpRequest) -> Option<u64> {
+        let path = self.output.as_ref().filter(|_| self.resume)?;
+        let offset = std::fs::metadata(path).ok()?.len();
+        if offset == 0 || req.headers.contains_key(header::RANGE) {
+            return None;
+        }
+        tracing::debug!(offset, path = %path.display(), "resuming download");
+        req.headers
+            .insert(header::RANGE, format!("bytes={offset}-").parse().ok()?);
+        Some(offset)
+    }
+
+    /// Which parts of the response to print
+    pub fn output_mode(&self) -> OutputMode {
+        if self.headers_only {
+            OutputMode::HeadersOnly
+        } else if self.body_only {
+            OutputMode::BodyOnly
+        } else if self.raw {
+            OutputMode::Raw
+        } else {
+            OutputMode::Full
+        }
+    }
+
+    /// Whether output should be colored
+    pub fn use_color(&self) -> bool {
+        !self.no_color && self.color.unwrap_or_default().enabled()
+    }
+
+    /// How responses are printed
+    ///
+    /// Bodies are cut at `--max-body`, or at [`printer::DEFAULT_MAX_BODY`]
+    /// on a terminal, unless `--full` is given or a pager shows them.
+    pub fn print_options(&self, verbose: bool) -> PrintOptions {
+        use std::io::IsTerminal;
+        let max_body = if self.full || self.use_pager() {
+            None
+        } else {
+            self.max_body.or_else(|| {
+                std::io::stdout()
+                    .is_terminal()
+                    .then_some(printer::DEFAULT_MAX_BODY)
+            })
+        };
+        PrintOptions {
+            verbose,
+            mode: self.output_mode(),
+            color: self.use_color(),
+            graphql: self.graphql,
+            max_body,
+            show_headers: self.show_header.clone(),
+        }
+    }
+
+    /// Whether responses go through the pager: `--pager` was given and
+    /// stdout is a terminal
+    pub fn use_pager(&self) -> bool {
+        use std::io::IsTerminal;
+        self.pager && self.output_mode() != OutputMode::BodyOnly && std::io::stdout().is_terminal()
+    }
+
+    /// Turns an error status into `HttpError::Status` when `--fail` is set
+    ///
+    /// The response has already been printed, so scripts still see it.
+    pub fn check_status(&self, response: Option<&HttpResponse>) -> Result<(), WaveError> {
+        match response {
+            Some(resp) if self.fail && resp.status >= 400 => {
+                Err(WaveError::Http(HttpError::Status(resp.status)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the request to the collection named with `--save`, if any
+    ///
+    /// Called before the request is sent, so `--dry-run --save` stores a
+    /// request without sending it. Credentials from `--auth`, `--bearer` and
+    /// the config file are not saved.
+    pub fn save_request(&self, req: &HttpRequest) -> Result<(), WaveError> {
+        let Some(target) = &self.save else {
+            return Ok(());
+        };
+        let path = collection::save_request(&self.collection_dir, target, req)?;
+        eprintln!("Saved request '{}' to {}", target.request, path.display());
+        Ok(())
+    }
+
+    /// Loads the session named with `--session`, with the path to save it back to
+    pub fn open_session(&self) -> Result<Option<(Session, PathBuf)>, WaveError> {
+        let Some(name) = &self.session else {
+            return Ok(None);
+        };
+        let path = Session::path_for(&self.collection_dir, name)?;
+        Ok(Some((Session::load(&path)?, path)))
+    }
+
+    /// Returns the TLS settings given on the command line
+    pub fn tls(&self) -> TlsOptions {
+        TlsOptions {
+            insecure: self.insecure,
+            cacert: self.cacert.clone(),
+            cert: self.cert.clone(),
+            key: self.key.clone(),
+        }
+    }
+
+    /// Fills TLS settings not given on the command line from a collection
+    pub fn with_collection_tls(&self, tls: Option<&TlsOptions>) -> RequestOptions {
+        let Some(tls) = tls else {
+            return self.clone();
+        };
+        let merged = self.tls().or(tls);
+        RequestOptions {
+            insecure: merged.insecure,
+            cacert: merged.cacert,
+            cert: merged.cert,
+            key: merged.key,
+            ..self.clone()
+        }
+    }
+
+    /// Fills timeouts not given on the command line or in the config file
+    /// from a collection
+    pub fn with_collection_timeouts(&self, timeouts: Option<&Timeouts>) -> RequestOptions {
+        let Some(timeouts) = timeouts else {
+            return self.clone();
+        };
+        RequestOptions {
+            timeout: self.timeout.or(timeouts.total),
+            connect_timeout: self.connect_timeout.or(timeouts.connect),
+            read_timeout: self.read_timeout.or(timeouts.read),
+            ..self.clone()
+        }
+    }
+
+    /// Fills settings not given on the command line from a collection's
+    /// `tls:` and `timeouts:` blocks
+    pub fn with_collection(&self, coll: &Collection) -> RequestOptions {
+        self.with_collection_tls(coll.tls.as_ref())
+            .with_collection_timeouts(coll.timeouts.as_ref())
+    }
+
+    /// Returns the credentials given with `--auth` or `--bearer`, if any
+    pub fn credentials(&self) -> Option<Auth> {
+        match (&self.auth, &self.bearer) {
+            (Some(basic), _) => Some(Auth::parse_basic(basic)),
+            (None, Some(token)) => Some(Auth::Bearer(token.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Body options shared by the subcommands that send a request body
+#[derive(Args, Debug, Clone, Default)]
+pub struct BodyOptions {
+    /// Send key=value body data as application/x-www-form-urlencoded
+    #[arg(long)]
+    pub form: bool,
+    /// Send a raw JSON document as the body, inline or from a file with @path
+    #[arg(long, value_name = "JSON|@FILE", conflicts_with = "form")]
+    pub json: Option<String>,
+    /// Send this string as the body verbatim (use - as a param to read stdin)
+    #[arg(long, value_name = "DATA", conflicts_with_all = ["form", "json"])]
+    pub data_raw: Option<String>,
+    /// Content-Type of the body (inferred for raw bodies when omitted)
+    #[arg(long, value_name = "TYPE")]
+    pub content_type: Option<String>,
+}
+
 #[derive(Parser)]
 #[command(name = "wave")]
 #[command(author, version, about, long_about)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+    /// Log each step (config, variables, headers, backend calls) to stderr
+    #[arg(short = 'd', long, global = true)]
+    pub debug: bool,
+    /// Write the --debug log to this file instead of stderr
+    #[arg(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
 }
 
 pub type HeaderDataTuple = (Headers, FormData);
@@ -163,6 +1046,52 @@ pub fn extract_var_overrides(
     Ok((remaining, overrides))
 }
 
+/// Splits `name@=path` file fields out of a list of trailing params.
+///
+/// File fields turn the request body into multipart/form-data. Returns the
+/// remaining params (headers and body data) along with the file parts.
+pub fn extract_file_params(params: &[String]) -> (Vec<String>, Vec<MultipartPart>) {
+    let mut remaining = Vec::with_capacity(params.len());
+    let mut files = Vec::new();
+    for param in params {
+        match param.split_once("@=") {
+            Some((name, path))
+                if !name.trim().is_empty() && !name.contains([':', '=']) && !path.is_empty() =>
+            {
+                files.push(MultipartPart::File {
+                    name: name.trim().to_string(),
+                    path: PathBuf::from(path),
+                });
+            }
+            _ => remaining.push(param.clone()),
+        }
+    }
+    (remaining, files)
+}
+
+/// A trailing param, split at its first `:` or `=`
+enum Param<'a> {
+    Header(&'a str, &'a str),
+    Data(&'a str, &'a str),
+}
+
+/// Splits a `key:value` header or `key=value` body param
+///
+/// Whichever of `:` and `=` comes first separates the key, so values may
+/// contain either. `key:=json` is body data whose value keeps the `:=`
+/// marker; see [`fields`] for the value markers.
+fn split_param(param: &str) -> Option<Param<'_>> {
+    let pos = param.find([':', '='])?;
+    let (key, rest) = (param[..pos].trim(), &param[pos..]);
+    Some(if let Some(value) = rest.strip_prefix('=') {
+        Param::Data(key, value.trim())
+    } else if rest.starts_with(":=") {
+        Param::Data(key, rest.trim_end())
+    } else {
+        Param::Header(key, rest[1..].trim())
+    })
+}
+
 pub fn parse_params(params: &[String]) -> HeaderDataTuple {
     let mut headers = Vec::new();
     let mut data = Vec::new();
@@ -171,10 +1100,10 @@ pub fn parse_params(params: &[String]) -> HeaderDataTuple {
         if param == "--form" {
             continue;
         }
-        if let Some((k, v)) = param.split_once(':') {
-            headers.push((k.trim().to_string(), v.trim().to_string()));
-        } else if let Some((k, v)) = param.split_once('=') {
-            data.push((k.trim().to_string(), v.trim().to_string()));
+        match split_param(param) {
+            Some(Param::Header(k, v)) => headers.push((k.to_string(), v.to_string())),
+            Some(Param::Data(k, v)) => data.push((k.to_string(), v.to_string())),
+            None => {}
         }
     }
     (headers, data)
@@ -197,85 +1126,127 @@ pub fn validate_params(params: &[String]) -> Result<HeaderDataTuple, WaveError>
             )));
         }
 
-        if let Some((k, v)) = param.split_once(':') {
-            let key = k.trim();
-            let value = v.trim();
+        match split_param(param) {
+            Some(Param::Header(key, value)) => {
+                // Validate header format; `key+:value` adds a value instead of replacing
+                let name = key.strip_suffix('+').unwrap_or(key);
+                if name.is_empty() {
+                    return Err(WaveError::Cli(CliError::InvalidHeaderFormat(param.clone())));
+                }
+                if name.contains(' ') {
+                    return Err(WaveError::Cli(CliError::InvalidHeaderFormat(param.clone())));
+                }
 
-            // Validate header format
-            if key.is_empty() {
-                return Err(WaveError::Cli(CliError::InvalidHeaderFormat(param.clone())));
+                headers.push((key.to_string(), value.to_string()));
             }
-            if key.contains(' ') {
-                return Err(WaveError::Cli(CliError::InvalidHeaderFormat(param.clone())));
-            }
-
-            headers.push((key.to_string(), value.to_string()));
-        } else if let Some((k, v)) = param.split_once('=') {
-            let key = k.trim();
-            let value = v.trim();
+            Some(Param::Data(key, value)) => {
+                // Validate body data format
+                if key.is_empty() {
+                    return Err(WaveError::Cli(CliError::InvalidBodyFormat(param.clone())));
+                }
 
-            // Validate body data format
-            if key.is_empty() {
-                return Err(WaveError::Cli(CliError::InvalidBodyFormat(param.clone())));
+                data.push((key.to_string(), value.to_string()));
+            }
+            None => {
+                // Parameter doesn't match either format
+                return Err(WaveError::Cli(CliError::InvalidHeaderFormat(format!(
+                    "Parameter '{param}' must be in 'key:value' (header) or 'key=value' (body) format"
+                ))));
             }
-
-            data.push((key.to_string(), value.to_string()));
-        } else {
-            // Parameter doesn't match either format
-            return Err(WaveError::Cli(CliError::InvalidHeaderFormat(format!(
-                "Parameter '{param}' must be in 'key:value' (header) or 'key=value' (body) format"
-            ))));
         }
     }
 
     Ok((headers, data))
 }
 
-/// Validates URL format
-pub fn validate_url(url: &str) -> Result<String, WaveError> {
-    if url.trim().is_empty() {
-        return Err(WaveError::Cli(CliError::InvalidUrl(
-            "URL cannot be empty".to_string(),
-        )));
+/// Validates a URL from the command line, adding a scheme if it has none
+///
+/// The URL is checked with a real URL parser. Without a scheme, its host must
+/// be `localhost`, an IP address (IPv6 in brackets, e.g. `[::1]:8080`) or a
+/// name with a dot, which catches a path or word given where the URL belongs.
+/// With an explicit scheme any host is accepted, so `http://api:8080` reaches
+/// a Docker Compose or Kubernetes service. The URL is returned as given, apart
+/// from the scheme; see [`ensure_url_scheme`].
+///
+/// # Errors
+/// Returns `ParseError::Url` saying what is wrong with the URL
+pub fn validate_url(url: &str, default_scheme: DefaultScheme) -> Result<String, WaveError> {
+    let invalid = |msg: String| WaveError::Parse(ParseError::Url(msg));
+    let url = url.trim();
+    if url.is_empty() {
+        return Err(invalid("URL cannot be empty".to_string()));
     }
 
-    // Add scheme if missing
-    let url_with_scheme = if url.starts_with("http://") || url.starts_with("https://") {
-        url.to_string()
-    } else {
-        format!("http://{url}")
-    };
-
-    // Basic URL validation - allow localhost, IP addresses, and domains with dots
-    let url_without_scheme = url_with_scheme
-        .strip_prefix("http://")
-        .or_else(|| url_with_scheme.strip_prefix("https://"))
-        .unwrap_or(&url_with_scheme);
-
-    let host_part = url_without_scheme
-        .split('/')
-        .next()
-        .unwrap_or(url_without_scheme);
-    let host_part = host_part.split(':').next().unwrap_or(host_part);
-
-    if !url_with_scheme.contains('.') && host_part != "localhost" {
-        return Err(WaveError::Cli(CliError::InvalidUrl(url.to_string())));
+    let url_with_scheme = ensure_url_scheme(url, default_scheme);
+    let parsed = url::Url::parse(&url_with_scheme).map_err(|e| invalid(format!("'{url}': {e}")))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid(format!(
+            "'{url}': unsupported scheme '{}', use http:// or https://",
+            parsed.scheme()
+        )));
+    }
+    match parsed.host() {
+        Some(url::Host::Domain(host))
+            if !url.contains("://") && host != "localhost" && !host.contains('.') =>
+        {
+            Err(invalid(format!(
+                "'{url}': host '{host}' is not localhost, an IP address or a domain name"
+            )))
+        }
+        Some(_) => Ok(url_with_scheme),
+        None => Err(invalid(format!("'{url}': missing host"))),
     }
+}
 
-    Ok(url_with_scheme)
+/// Adds a scheme to a URL without one
+///
+/// With [`DefaultScheme::Https`], localhost and loopback addresses still get
+/// `http://`, since local development servers rarely have certificates.
+///
+/// # Examples
+///
+/// ```
+/// use wave::config::DefaultScheme;
+/// use wave::ensure_url_scheme;
+///
+/// let https = DefaultScheme::Https;
+/// assert_eq!(ensure_url_scheme("api.example.com", https), "https://api.example.com");
+/// assert_eq!(ensure_url_scheme("localhost:8080", https), "http://localhost:8080");
+/// assert_eq!(ensure_url_scheme("api.example.com", DefaultScheme::Http), "http://api.example.com");
+/// assert_eq!(ensure_url_scheme("http://api.example.com", https), "http://api.example.com");
+/// ```
+pub fn ensure_url_scheme(url: &str, default_scheme: DefaultScheme) -> String {
+    if url.contains("://") {
+        return url.to_string();
+    }
+    let http = format!("http://{url}");
+    let local = url::Url::parse(&http).is_ok_and(|parsed| http::is_loopback(&parsed));
+    match default_scheme {
+        DefaultScheme::Https if !local => format!("https://{url}"),
+        _ => http,
+    }
 }
 
-pub fn ensure_url_scheme(url: &str) -> String {
-    if url.starts_with("http://") || url.starts_with("https://") {
-        url.to_string()
-    } else {
-        format!("http://{url}")
+/// Warns on stderr when a request sends Basic or Bearer credentials over
+/// plain http to another machine
+fn warn_cleartext_credentials(req: &HttpRequest) {
+    if let Some(scheme) = http::cleartext_credentials(&req.url, &req.headers) {
+        eprintln!(
+            "Warning: sending {scheme} credentials unencrypted to {}; use https:// to protect them",
+            req.url
+        );
     }
 }
 
+use futures_util::stream::{FuturesUnordered, StreamExt};
 use indicatif::{ProgressBar, ProgressStyle};
-use printer::print_response;
-use std::time::Duration;
+use printer::{
+    apply_color, format_grpc_reply, format_grpc_services, format_request, page_response,
+    print_download_summary, print_request, print_response, OutputFormat, OutputMode, PrintOptions,
+    ResponseReport,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 pub async fn run_with_spinner<F, Fut, T>(message: &str, f: F) -> T
 where
@@ -308,473 +1279,4832 @@ pub async fn execute_request_with_spinner(
     req: &HttpRequest,
     spinner_msg: &str,
     verbose: bool,
+    options: &RequestOptions,
 ) -> Result<(), WaveError> {
-    let client = Client::new(ReqwestBackend);
-    let result = run_with_spinner(spinner_msg, || client.send(req)).await;
-    print_response(result, verbose);
-    Ok(())
+    if let Some(interval) = options.watch {
+        return watch_request(req, interval, verbose, options).await;
+    }
+    let response = send_request_with_spinner(req, spinner_msg, verbose, options).await?;
+    options.check_status(response.as_ref())
 }
 
-pub async fn handle_get(
-    url: &str,
-    params: &[String],
+/// Sends a request every `interval` until interrupted
+///
+/// In a terminal the screen is cleared before each response, which is shown
+/// under a header saying whether it changed since the previous one. With
+/// `--format json` one report is printed per line instead.
+async fn watch_request(
+    req: &HttpRequest,
+    interval: Duration,
     verbose: bool,
-    spinner_msg: &str,
+    options: &RequestOptions,
 ) -> Result<(), WaveError> {
-    let url = validate_url(url)?;
-    let (headers, _) = validate_params(params)?;
-    let req = HttpRequest::new(&url, Method::GET, None, headers_to_map(headers));
-    execute_request_with_spinner(&req, spinner_msg, verbose).await
-}
+    let mut req = options.apply(req.clone());
+    let mut session = options.open_session()?;
+    let client = Client::new(options.backend()?);
+    let json = options.format == OutputFormat::Json;
+    let term = console::Term::stdout();
+    let clear = !json && term.is_term();
+    let request_line = format!("{} {}", req.method, req.url);
+    let mut previous = None;
 
-pub async fn handle_method_with_body(
-    method: Method,
-    url: &str,
-    params: &[String],
-    form: bool,
-    verbose: bool,
-    spinner_msg: &str,
-) -> Result<(), WaveError> {
-    let url = validate_url(url)?;
-    let (headers, data) = validate_params(params)?;
+    for count in 1u64.. {
+        if let Some((session, _)) = &session {
+            session.apply(&mut req);
+        }
+        if count == 1 {
+            warn_cleartext_credentials(&req);
+        }
+        let started = Instant::now();
+        let result = client.send(&req).await;
+        let elapsed = started.elapsed();
+        if let (Some((session, path)), Ok(resp)) = (&mut session, &result) {
+            session.update(&req, resp);
+            session.save(path)?;
+        }
+        let observation = watch::Observation::new(&result);
+        let change = watch::Change::detect(previous.as_ref(), &observation);
+        previous = Some(observation);
+
+        let shown = match (&result, &options.filter) {
+            (Ok(resp), Some(query)) => Ok(filter_response(resp, query)?),
+            (result, _) => result.clone(),
+        };
+        if json {
+            println!("{}", ResponseReport::new(&req, &shown, elapsed).to_json());
+        } else {
+            if clear {
+                let _ = term.clear_screen();
+            } else if count > 1 {
+                println!();
+            }
+            let header = watch::format_header(&request_line, interval, count, &change);
+            println!("{}", apply_color(header, options.use_color()));
+            println!();
+            if verbose {
+                print_request(&req, options.use_color());
+            }
+            print_response(shown, options.print_options(verbose));
+        }
+        tokio::time::sleep(interval).await;
+    }
+    Ok(())
+}
 
-    let req = if form {
-        HttpRequest::builder(&url, method)
-            .headers(headers_to_map(headers))
-            .body(RequestBody::form(data))
-            .build()
+/// Sends (or previews) a request and prints the outcome
+///
+/// Returns the response when one was received, so callers can inspect it
+/// further, and `None` for dry runs. A request that gets no response fails
+/// with its `HttpError`, which `main` reports with its exit code; with
+/// `--format json` the report is printed first.
+pub async fn send_request_with_spinner(
+    req: &HttpRequest,
+    spinner_msg: &str,
+    verbose: bool,
+    options: &RequestOptions,
+) -> Result<Option<HttpResponse>, WaveError> {
+    let mut req = options.apply(req.clone());
+    let mut session = options.open_session()?;
+    if let Some((session, _)) = &session {
+        session.apply(&mut req);
+    }
+    let resumed_at = options.resume_download(&mut req);
+    if options.dry_run {
+        if options.as_curl {
+            println!("{}", to_curl(&req));
+        } else {
+            print!("{}", apply_color(format_request(&req), options.use_color()));
+        }
+        return Ok(None);
+    }
+    warn_cleartext_credentials(&req);
+    let json = options.format == OutputFormat::Json;
+    if verbose && !json {
+        print_request(&req, options.use_color());
+    } else if let Some((name, _)) = options.request_id() {
+        if let Some(id) = req.headers.get(&name).and_then(|v| v.to_str().ok()) {
+            eprintln!("{name}: {id}");
+        }
+    }
+    let client = Client::new(options.backend()?);
+    let response = if let Some(path) = &options.output {
+        let (resp, written) = download_with_progress(&client, &req, path, spinner_msg).await?;
+        let color = options.use_color();
+        match resumed_at {
+            Some(offset) if resp.status == 206 => {
+                print_download_summary(resp.status, written, path, Some(offset), color);
+            }
+            Some(offset) if download_complete(&resp, offset) => {
+                eprintln!("{} is already complete ({offset} bytes)", path.display());
+                return Ok(None);
+            }
+            Some(_) if resp.is_error() => {
+                print_response(Ok(resp.clone()), options.print_options(verbose));
+                eprintln!("{} was left as it was", path.display());
+            }
+            Some(_) => {
+                eprintln!(
+                    "Warning: the server cannot resume downloads, so {} was downloaded again from the start",
+                    path.display()
+                );
+                print_download_summary(resp.status, written, path, None, color);
+            }
+            None => print_download_summary(resp.status, written, path, None, color),
+        }
+        resp
     } else {
-        match RequestBody::json(&data.into_iter().collect::<HashMap<String, String>>()) {
-            Ok(body) => HttpRequest::builder(&url, method)
-                .headers(headers_to_map(headers))
-                .body(body)
-                .build(),
-            Err(_) => HttpRequest::new(
-                &url,
-                method,
-                Some("{}".to_string()),
-                headers_to_map(headers),
-            ),
+        let started = Instant::now();
+        let result = match &options.paginate {
+            Some(strategy) => fetch_pages(&client, &req, strategy, spinner_msg, options).await,
+            None => run_with_spinner(spinner_msg, || client.send(&req)).await,
+        };
+        let elapsed = started.elapsed();
+        let resp = match result {
+            Ok(resp) => resp,
+            Err(e) => {
+                if json {
+                    let report = ResponseReport::new(&req, &Err(e.clone()), elapsed);
+                    println!("{}", report.to_json());
+                }
+                return Err(WaveError::Http(e));
+            }
+        };
+        let shown = match &options.filter {
+            Some(query) => filter_response(&resp, query)?,
+            None => resp.clone(),
+        };
+        if json {
+            println!(
+                "{}",
+                ResponseReport::new(&req, &Ok(shown), elapsed).to_json()
+            );
+        } else if options.use_pager() {
+            page_response(Ok(shown), options.print_options(verbose));
+        } else {
+            print_response(Ok(shown), options.print_options(verbose));
+        }
+        resp
+    };
+    if let Some((session, path)) = &mut session {
+        session.update(&req, &response);
+        session.save(path)?;
+    }
+    Ok(Some(response))
+}
+
+/// Replaces a JSON response body with the part selected by `--filter`
+fn filter_response(resp: &HttpResponse, query: &Query) -> Result<HttpResponse, WaveError> {
+    let body: serde_json::Value = serde_json::from_str(&resp.body).map_err(|e| {
+        WaveError::Parse(ParseError::Json(format!(
+            "--filter needs a JSON response body: {e}"
+        )))
+    })?;
+    let filtered = serde_json::to_string(&query.apply(&body))?;
+    let mut shown = HttpResponse::new(resp.status, resp.headers.clone(), filtered);
+    shown.timing = resp.timing;
+    Ok(shown)
+}
+
+/// Fetches every page of a paginated response and combines them into one
+///
+/// JSON pages become a single array of their items; other pages are joined.
+/// The status and headers are those of the last page. A page with an error
+/// status ends pagination and is returned as it is.
+async fn fetch_pages<B: HttpBackend + Send + Sync>(
+    client: &Client<B>,
+    req: &HttpRequest,
+    strategy: &Paginate,
+    spinner_msg: &str,
+    options: &RequestOptions,
+) -> Result<HttpResponse, HttpError> {
+    let max_pages = options.max_pages.unwrap_or(paginate::DEFAULT_MAX_PAGES);
+    let mut page_req = req.clone();
+    let mut items: Vec<serde_json::Value> = Vec::new();
+    let mut texts: Vec<String> = Vec::new();
+    let mut page = 1;
+    loop {
+        let message = if page == 1 {
+            spinner_msg.to_string()
+        } else {
+            format!("{spinner_msg} (page {page})")
+        };
+        let resp = run_with_spinner(&message, || client.send(&page_req)).await?;
+        if resp.status >= 400 {
+            return Ok(resp);
+        }
+        let page_error = |e: String| HttpError::Parse(format!("page {page}: {e}"));
+        let count = match serde_json::from_str::<serde_json::Value>(&resp.body) {
+            Ok(body) if texts.is_empty() => {
+                let found =
+                    paginate::page_items(&body, options.items.as_deref()).map_err(page_error)?;
+                let count = found.len();
+                items.extend(found);
+                count
+            }
+            _ if items.is_empty() || page == 1 => {
+                if options.items.is_some() || !matches!(strategy, Paginate::Link) {
+                    return Err(page_error(format!(
+                        "{} did not return JSON, which --paginate={strategy} needs",
+                        page_req.url
+                    )));
+                }
+                texts.push(resp.body.clone());
+                1
+            }
+            _ => return Err(page_error("mixed JSON and non-JSON pages".to_string())),
+        };
+        let next = strategy
+            .next_url(&page_req.url, &resp, count)
+            .map_err(page_error)?;
+        match next {
+            Some(url) if page < max_pages && url != page_req.url => {
+                page_req.url = url;
+                page += 1;
+            }
+            next => {
+                if next.is_some() && page >= max_pages {
+                    eprintln!("Stopped after {max_pages} pages; raise the limit with --max-pages");
+                }
+                let mut headers = resp.headers.clone();
+                headers.remove(header::CONTENT_LENGTH);
+                headers.remove(header::CONTENT_ENCODING);
+                let body = if texts.is_empty() {
+                    headers.insert(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/json"),
+                    );
+                    serde_json::to_string(&items).map_err(|e| HttpError::Other(e.to_string()))?
+                } else {
+                    texts.concat()
+                };
+                return Ok(HttpResponse::new(resp.status, headers, body));
+            }
+        }
+    }
+}
+
+/// Returns true if a 416 answer to resuming at `offset` says the file is
+/// already whole, with a `Content-Range` of `bytes */<offset>`
+fn download_complete(resp: &HttpResponse, offset: u64) -> bool {
+    resp.status == 416
+        && resp
+            .headers
+            .get(header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|range| range.strip_prefix("bytes */"))
+            .and_then(|total| total.trim().parse::<u64>().ok())
+            == Some(offset)
+}
+
+/// Downloads at or above this size switch the spinner to a progress bar
+const PROGRESS_BAR_THRESHOLD: u64 = 1024 * 1024;
+
+/// Streams the response body to `path`, showing a spinner that turns into a
+/// byte progress bar once the download is known to be large
+async fn download_with_progress<B: HttpBackend + Send + Sync>(
+    client: &Client<B>,
+    req: &HttpRequest,
+    path: &Path,
+    spinner_msg: &str,
+) -> Result<(HttpResponse, u64), HttpError> {
+    let pb = ProgressBar::new_spinner();
+    pb.set_message(spinner_msg.to_string());
+    pb.enable_steady_tick(Duration::from_millis(100));
+    if let Ok(style) = ProgressStyle::default_spinner()
+        .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+        .template("{spinner} {msg}")
+    {
+        pb.set_style(style);
+    }
+
+    let showing_bar = AtomicBool::new(false);
+    let progress = |written: u64, total: Option<u64>| {
+        if total.unwrap_or(written) >= PROGRESS_BAR_THRESHOLD
+            && !showing_bar.swap(true, Ordering::Relaxed)
+        {
+            let template = match total {
+                Some(_) => "{msg} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+                None => "{spinner} {msg} {bytes} ({bytes_per_sec})",
+            };
+            if let Some(total) = total {
+                pb.set_length(total);
+            }
+            if let Ok(style) = ProgressStyle::with_template(template) {
+                pb.set_style(style.progress_chars("=> "));
+            }
+        }
+        pb.set_position(written);
+    };
+
+    let result = client.download(req, path, &progress).await;
+    pb.finish_and_clear();
+    result
+}
+
+pub async fn handle_method_without_body(
+    method: Method,
+    url: &str,
+    params: &[String],
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    let url = validate_url(url, options.default_scheme.unwrap_or_default())?;
+    let (headers, _) = validate_params(params)?;
+    let req = HttpRequest::new(&url, method, None, headers_to_map(headers)?);
+    options.save_request(&req)?;
+    execute_request_with_spinner(&req, spinner_msg, verbose, options).await
+}
+
+/// Sends a GET request, with a body only if one is given (see
+/// [`handle_request`])
+pub async fn handle_get(
+    url: &str,
+    params: &[String],
+    body: &BodyOptions,
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    handle_method_with_optional_body(
+        Method::GET,
+        url,
+        params,
+        body,
+        verbose,
+        options,
+        spinner_msg,
+    )
+    .await
+}
+
+pub async fn handle_head(
+    url: &str,
+    params: &[String],
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    let ignored = ignored_params(Some(&Method::HEAD), params, &BodyOptions::default());
+    report_ignored(ignored, options.strict)?;
+    handle_method_without_body(Method::HEAD, url, params, verbose, options, spinner_msg).await
+}
+
+pub async fn handle_options(
+    url: &str,
+    params: &[String],
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    let ignored = ignored_params(Some(&Method::OPTIONS), params, &BodyOptions::default());
+    report_ignored(ignored, options.strict)?;
+    handle_method_without_body(Method::OPTIONS, url, params, verbose, options, spinner_msg).await
+}
+
+/// Reads a raw JSON document given inline or as `@path` and validates it
+///
+/// Used by `--json` so malformed documents are reported before anything is
+/// sent over the network.
+pub fn load_json_document(raw: &str) -> Result<serde_json::Value, WaveError> {
+    let text = match raw.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| WaveError::Io(format!("Failed to read JSON body from '{path}': {e}")))?,
+        None => raw.to_string(),
+    };
+    serde_json::from_str(&text)
+        .map_err(|e| WaveError::Cli(CliError::InvalidJsonArgument(format!("--json body: {e}"))))
+}
+
+/// Builds the request body from the body options, `key=value` params and file fields
+fn build_request_body(
+    body: &BodyOptions,
+    data: FormData,
+    files: Vec<MultipartPart>,
+) -> Result<RequestBody, WaveError> {
+    if let Some(raw) = &body.data_raw {
+        if body.json.is_some() || body.form || !data.is_empty() || !files.is_empty() {
+            return Err(WaveError::Cli(CliError::ConflictingArguments(
+                "a raw body (--data-raw or -) cannot be combined with --json, --form, key=value body data or file fields".to_string(),
+            )));
+        }
+        return Ok(RequestBody::text(raw.clone()));
+    }
+    if let Some(raw) = &body.json {
+        if !data.is_empty() || !files.is_empty() {
+            return Err(WaveError::Cli(CliError::ConflictingArguments(
+                "--json cannot be combined with key=value body data or file fields".to_string(),
+            )));
+        }
+        return Ok(RequestBody::Json(load_json_document(raw)?));
+    }
+    if !files.is_empty() {
+        return Ok(RequestBody::multipart(merge_multipart_parts(
+            Vec::new(),
+            &resolve_text_values(&data)?,
+            files,
+        )));
+    }
+    if body.form {
+        return Ok(RequestBody::form(resolve_text_values(&data)?));
+    }
+    let mut json = serde_json::json!({});
+    for (key, value) in data {
+        let value = fields::marked_value(&value)?.unwrap_or(serde_json::Value::String(value));
+        insert_body_field(&mut json, &key, value)?;
+    }
+    Ok(RequestBody::Json(json))
+}
+
+/// Resolves the `==`, `=@` and `:=` markers of values sent as text
+fn resolve_text_values(data: &[(String, String)]) -> Result<FormData, WaveError> {
+    data.iter()
+        .map(|(key, value)| {
+            let text = fields::marked_text(value)?.unwrap_or_else(|| value.clone());
+            Ok((key.clone(), text))
+        })
+        .collect()
+}
+
+/// Sets a (possibly nested) field of a JSON body from a `key=value` param
+fn insert_body_field(
+    body: &mut serde_json::Value,
+    key: &str,
+    value: serde_json::Value,
+) -> Result<(), WaveError> {
+    fields::insert(body, key, value).map_err(|msg| WaveError::Cli(CliError::InvalidBodyField(msg)))
+}
+
+/// Splits the `-` stdin marker out of the params
+///
+/// Returns the remaining params and whether the body should be read from stdin.
+fn split_stdin_marker(params: &[String]) -> (Vec<String>, bool) {
+    let remaining: Vec<String> = params.iter().filter(|p| *p != "-").cloned().collect();
+    let read_stdin = remaining.len() != params.len();
+    (remaining, read_stdin)
+}
+
+/// Reads the whole request body from stdin
+fn read_stdin_body() -> Result<String, WaveError> {
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .map_err(|e| WaveError::Io(format!("Failed to read the request body from stdin: {e}")))?;
+    Ok(text)
+}
+
+/// Sets the Content-Type from `--content-type`, or infers it for raw bodies
+///
+/// Raw bodies that parse as JSON are sent as `application/json`; any other
+/// raw body falls back to `text/plain` when the body is serialized. A
+/// Content-Type given as a header param is kept unless `--content-type` is set.
+fn apply_content_type(headers: &mut HeaderMap, body: &BodyOptions) -> Result<(), WaveError> {
+    if let Some(content_type) = &body.content_type {
+        let value = ::http::HeaderValue::from_str(content_type).map_err(|_| {
+            WaveError::Cli(CliError::InvalidHeaderFormat(format!(
+                "Content-Type:{content_type}"
+            )))
+        })?;
+        headers.insert(::http::header::CONTENT_TYPE, value);
+    } else if let Some(raw) = &body.data_raw {
+        if serde_json::from_str::<serde_json::Value>(raw).is_ok() {
+            RequestBody::ensure_content_type(headers, "application/json");
+        }
+    }
+    Ok(())
+}
+
+/// Ensures every file part points at a readable file before sending
+fn validate_file_parts(parts: &[MultipartPart]) -> Result<(), WaveError> {
+    for part in parts {
+        if let MultipartPart::File { path, .. } = part {
+            if !path.is_file() {
+                return Err(WaveError::Io(format!(
+                    "File not found for upload: '{}'",
+                    path.display()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Merge multipart parts, with CLI text fields and files replacing parts of the same name
+fn merge_multipart_parts(
+    collection_parts: Vec<MultipartPart>,
+    cli_body: &[(String, String)],
+    cli_files: Vec<MultipartPart>,
+) -> Vec<MultipartPart> {
+    let part_name = |part: &MultipartPart| match part {
+        MultipartPart::Text { name, .. } | MultipartPart::File { name, .. } => name.clone(),
+    };
+    let cli_parts: Vec<MultipartPart> = cli_body
+        .iter()
+        .map(|(name, value)| MultipartPart::Text {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .chain(cli_files)
+        .collect();
+    let mut parts: Vec<MultipartPart> = collection_parts
+        .into_iter()
+        .filter(|part| {
+            !cli_parts
+                .iter()
+                .any(|cli| part_name(cli) == part_name(part))
+        })
+        .collect();
+    parts.extend(cli_parts);
+    parts
+}
+
+pub async fn handle_method_with_body(
+    method: Method,
+    url: &str,
+    params: &[String],
+    body: &BodyOptions,
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    let url = validate_url(url, options.default_scheme.unwrap_or_default())?;
+    report_ignored(ignored_params(None, params, body), options.strict)?;
+    let (params, read_stdin) = split_stdin_marker(params);
+    let (params, files) = extract_file_params(&params);
+    let (headers, data) = validate_params(&params)?;
+    validate_file_parts(&files)?;
+
+    let stdin_body;
+    let body = if read_stdin {
+        if body.data_raw.is_some() {
+            return Err(WaveError::Cli(CliError::ConflictingArguments(
+                "--data-raw cannot be combined with a body read from stdin (-)".to_string(),
+            )));
         }
+        stdin_body = BodyOptions {
+            data_raw: Some(read_stdin_body()?),
+            ..body.clone()
+        };
+        &stdin_body
+    } else {
+        body
     };
+    let mut headers = headers_to_map(headers)?;
+    apply_content_type(&mut headers, body)?;
+
+    let req = HttpRequest::builder(&url, method)
+        .headers(headers)
+        .body(build_request_body(body, data, files)?)
+        .build();
 
-    execute_request_with_spinner(&req, spinner_msg, verbose).await
+    options.save_request(&req)?;
+    execute_request_with_spinner(&req, spinner_msg, verbose, options).await
 }
 
 pub async fn handle_post(
     url: &str,
     params: &[String],
-    form: bool,
+    body: &BodyOptions,
     verbose: bool,
+    options: &RequestOptions,
     spinner_msg: &str,
 ) -> Result<(), WaveError> {
-    handle_method_with_body(Method::POST, url, params, form, verbose, spinner_msg).await
+    handle_method_with_body(
+        Method::POST,
+        url,
+        params,
+        body,
+        verbose,
+        options,
+        spinner_msg,
+    )
+    .await
 }
 
 pub async fn handle_put(
     url: &str,
     params: &[String],
-    form: bool,
+    body: &BodyOptions,
     verbose: bool,
+    options: &RequestOptions,
     spinner_msg: &str,
 ) -> Result<(), WaveError> {
-    handle_method_with_body(Method::PUT, url, params, form, verbose, spinner_msg).await
+    handle_method_with_body(
+        Method::PUT,
+        url,
+        params,
+        body,
+        verbose,
+        options,
+        spinner_msg,
+    )
+    .await
 }
 
 pub async fn handle_patch(
     url: &str,
     params: &[String],
-    form: bool,
+    body: &BodyOptions,
     verbose: bool,
+    options: &RequestOptions,
     spinner_msg: &str,
 ) -> Result<(), WaveError> {
-    handle_method_with_body(Method::PATCH, url, params, form, verbose, spinner_msg).await
+    handle_method_with_body(
+        Method::PATCH,
+        url,
+        params,
+        body,
+        verbose,
+        options,
+        spinner_msg,
+    )
+    .await
 }
 
+/// Sends a DELETE request, with a body only if one is given (see
+/// [`handle_request`])
 pub async fn handle_delete(
     url: &str,
     params: &[String],
+    body: &BodyOptions,
     verbose: bool,
+    options: &RequestOptions,
     spinner_msg: &str,
 ) -> Result<(), WaveError> {
-    let url = validate_url(url)?;
-    let (headers, _) = validate_params(params)?;
-    let req = HttpRequest::new(&url, Method::DELETE, None, headers_to_map(headers));
-    execute_request_with_spinner(&req, spinner_msg, verbose).await
+    handle_method_with_optional_body(
+        Method::DELETE,
+        url,
+        params,
+        body,
+        verbose,
+        options,
+        spinner_msg,
+    )
+    .await
 }
 
-/// Parse a CLI parameter value to appropriate JSON type
-fn parse_cli_value_to_json(value: &str) -> serde_json::Value {
-    // Try parsing as integer first
-    if let Ok(int_val) = value.parse::<i64>() {
-        return serde_json::Value::Number(int_val.into());
+/// Sends a request with any method given by `wave request -X`
+///
+/// A body is only sent when one is given (`key=value` data, file fields,
+/// `--json`, `--data-raw` or `-`), so `wave request -X PURGE <url>` sends a
+/// bare request while `-X PROPFIND <url> --data-raw '<propfind .../>'` sends a body.
+/// A body on a GET, HEAD or DELETE request is sent with a warning on stderr,
+/// as some servers and proxies ignore or reject it.
+pub async fn handle_request(
+    method: &str,
+    url: &str,
+    params: &[String],
+    body: &BodyOptions,
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    let method = http::parse_method(method)
+        .map_err(|_| WaveError::Cli(CliError::UnsupportedMethod(method.to_string())))?;
+    handle_method_with_optional_body(method, url, params, body, verbose, options, spinner_msg).await
+}
+
+/// Returns true if the params or body options give a request body:
+/// `key=value` data, file fields, `--json`, `--data-raw` or `-`
+fn gives_body(params: &[String], body: &BodyOptions) -> Result<bool, WaveError> {
+    let (rest, read_stdin) = split_stdin_marker(params);
+    let (rest, files) = extract_file_params(&rest);
+    let (_, data) = validate_params(&rest)?;
+    Ok(read_stdin
+        || !files.is_empty()
+        || !data.is_empty()
+        || body.json.is_some()
+        || body.data_raw.is_some())
+}
+
+/// Lists the arguments a request would leave out without saying so
+///
+/// With `method`, the request is sent without a body, so its `key=value`
+/// data, file fields and `-`, and `--form` and `--content-type`, are unused.
+/// A `--form` among the params, after the URL, is never read as the flag.
+fn ignored_params(method: Option<&Method>, params: &[String], body: &BodyOptions) -> Vec<String> {
+    let mut ignored = Vec::new();
+    if params.iter().any(|p| p == "--form") {
+        ignored.push("'--form' after the URL is ignored; give it before the URL".to_string());
     }
-    // Try parsing as float
-    if let Ok(float_val) = value.parse::<f64>() {
-        if let Some(num) = serde_json::Number::from_f64(float_val) {
-            return serde_json::Value::Number(num);
+    if let Some(method) = method {
+        let data: Vec<&str> = params
+            .iter()
+            .filter(|p| *p == "-" || matches!(split_param(p), Some(Param::Data(..))))
+            .map(String::as_str)
+            .collect();
+        if !data.is_empty() {
+            ignored.push(format!(
+                "{method} requests are sent without a body, so {} is ignored",
+                data.join(" ")
+            ));
+        }
+        if body.form {
+            ignored.push("--form is ignored without key=value body data".to_string());
+        }
+        if body.content_type.is_some() {
+            ignored.push("--content-type is ignored without a body".to_string());
         }
     }
-    // Try parsing as boolean
-    if let Ok(bool_val) = value.parse::<bool>() {
-        return serde_json::Value::Bool(bool_val);
+    ignored
+}
+
+/// Warns on stderr about ignored arguments, or fails on them with `--strict`
+fn report_ignored(ignored: Vec<String>, strict: bool) -> Result<(), WaveError> {
+    if strict && !ignored.is_empty() {
+        return Err(WaveError::Cli(CliError::IgnoredArguments(
+            ignored.join("; "),
+        )));
     }
-    // Default to string
-    serde_json::Value::String(value.to_string())
+    for msg in ignored {
+        eprintln!("Warning: {msg}");
+    }
+    Ok(())
+}
+
+/// Warns on stderr when a body is sent with a method that usually has none
+fn warn_body_method(method: &Method) {
+    if matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::DELETE | Method::OPTIONS
+    ) {
+        eprintln!(
+            "Warning: sending a body with {method}; some servers and proxies ignore or reject it"
+        );
+    }
+}
+
+/// Sends a request with a body if one is given, or a bare request otherwise
+async fn handle_method_with_optional_body(
+    method: Method,
+    url: &str,
+    params: &[String],
+    body: &BodyOptions,
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    if !gives_body(params, body)? {
+        report_ignored(ignored_params(Some(&method), params, body), options.strict)?;
+        return handle_method_without_body(method, url, params, verbose, options, spinner_msg)
+            .await;
+    }
+    warn_body_method(&method);
+    handle_method_with_body(method, url, params, body, verbose, options, spinner_msg).await
+}
+
+/// Builds the standard GraphQL request envelope
+///
+/// `query` is the query text, or `@path` to read it from a file. Variables
+/// come from `--variables` (a JSON object, inline or `@path`) with `key=value`
+/// params layered on top; their values get the same type inference as
+/// collection overrides, so `id=42` is sent as a number.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use wave::graphql_body;
+///
+/// let body = graphql_body("{ user(id: $id) { name } }", None, None, &[("id".into(), "42".into())]).unwrap();
+/// assert_eq!(body, json!({"query": "{ user(id: $id) { name } }", "variables": {"id": 42}}));
+/// ```
+pub fn graphql_body(
+    query: &str,
+    operation_name: Option<&str>,
+    variables: Option<&str>,
+    data: &[(String, String)],
+) -> Result<serde_json::Value, WaveError> {
+    let query = match query.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| {
+            WaveError::Io(format!("Failed to read GraphQL query from '{path}': {e}"))
+        })?,
+        None => query.to_string(),
+    };
+    if query.trim().is_empty() {
+        return Err(WaveError::Cli(CliError::MissingArguments(
+            "the GraphQL query is empty".to_string(),
+        )));
+    }
+
+    let base = match variables {
+        Some(raw) => match load_json_document(raw) {
+            Ok(serde_json::Value::Object(map)) => Some(serde_json::Value::Object(map)),
+            Ok(_) => {
+                return Err(WaveError::Cli(CliError::InvalidJsonArgument(
+                    "--variables: expected a JSON object".to_string(),
+                )))
+            }
+            Err(WaveError::Cli(CliError::InvalidJsonArgument(msg))) => {
+                return Err(WaveError::Cli(CliError::InvalidJsonArgument(
+                    msg.replace("--json body", "--variables"),
+                )))
+            }
+            Err(e) => return Err(e),
+        },
+        None => None,
+    };
+
+    let mut envelope = serde_json::Map::new();
+    envelope.insert("query".to_string(), serde_json::Value::String(query));
+    if let Some(name) = operation_name {
+        envelope.insert(
+            "operationName".to_string(),
+            serde_json::Value::String(name.to_string()),
+        );
+    }
+    if base.is_some() || !data.is_empty() {
+        envelope.insert(
+            "variables".to_string(),
+            merge_json_with_cli_params(base, data)?,
+        );
+    }
+    Ok(serde_json::Value::Object(envelope))
+}
+
+/// Sends a GraphQL query as a JSON POST and prints `data` and `errors` separately
+pub async fn handle_graphql(
+    url: &str,
+    query: &str,
+    params: &[String],
+    graphql: &GraphqlOptions,
+    verbose: bool,
+    options: &RequestOptions,
+    spinner_msg: &str,
+) -> Result<(), WaveError> {
+    let url = validate_url(url, options.default_scheme.unwrap_or_default())?;
+    let (headers, data) = validate_params(params)?;
+    let body = graphql_body(
+        query,
+        graphql.operation_name.as_deref(),
+        graphql.variables.as_deref(),
+        &data,
+    )?;
+    let req = HttpRequest::builder(&url, Method::POST)
+        .headers(headers_to_map(headers)?)
+        .body(RequestBody::Json(body))
+        .build();
+
+    let options = RequestOptions {
+        graphql: true,
+        ..options.clone()
+    };
+    options.save_request(&req)?;
+    execute_request_with_spinner(&req, spinner_msg, verbose, &options).await
+}
+
+/// Builds a gRPC request message from `--data` and `key=value` params
+///
+/// Values of string and bytes fields are kept as text, so `id=42` can set a
+/// string field; other values get the same type inference as GraphQL
+/// variables, and the codec checks them against the field types.
+fn grpc_request_message(
+    descriptors: &grpc::Descriptors,
+    input: &str,
+    data: Option<&str>,
+    fields: &[(String, String)],
+) -> Result<serde_json::Value, WaveError> {
+    let mut message = match data.map(load_json_document).transpose() {
+        Ok(None) => serde_json::Map::new(),
+        Ok(Some(serde_json::Value::Object(map))) => map,
+        Ok(Some(_)) => {
+            return Err(WaveError::Cli(CliError::InvalidJsonArgument(
+                "--data: expected a JSON object".to_string(),
+            )))
+        }
+        Err(WaveError::Cli(CliError::InvalidJsonArgument(msg))) => {
+            return Err(WaveError::Cli(CliError::InvalidJsonArgument(
+                msg.replace("--json body", "--data"),
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+    let input = descriptors.message(input)?;
+    for (key, value) in fields {
+        let text = input
+            .fields
+            .iter()
+            .find(|f| f.name == *key || f.json_name == *key)
+            .is_some_and(|f| matches!(f.ty, FieldType::Scalar(Scalar::String | Scalar::Bytes)));
+        let value = match fields::marked_value(value)? {
+            Some(value) => value,
+            None if text => serde_json::Value::String(value.clone()),
+            None => parse_cli_value_to_json(value)?,
+        };
+        message.insert(key.clone(), value);
+    }
+    Ok(serde_json::Value::Object(message))
 }
 
-/// Merge collection JSON with CLI parameters, preserving types from collection
-fn merge_json_with_cli_params(
-    collection_json: Option<serde_json::Value>,
-    cli_body: &[(String, String)],
-) -> serde_json::Value {
-    let mut result = collection_json.unwrap_or(serde_json::json!({}));
+/// Awaits `fut`, failing with a timeout error for `address` once `deadline` has passed
+async fn within_deadline<T, E: Into<WaveError>>(
+    address: &str,
+    deadline: Option<tokio::time::Instant>,
+    fut: impl std::future::Future<Output = Result<T, E>>,
+) -> Result<T, WaveError> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut)
+            .await
+            .map_err(|_| {
+                WaveError::Http(HttpError::Timeout(RequestFailure::new(
+                    Method::POST,
+                    address,
+                    "deadline exceeded",
+                )))
+            })?
+            .map_err(Into::into),
+        None => fut.await.map_err(Into::into),
+    }
+}
+
+/// Calls a gRPC method and prints the decoded response messages
+///
+/// Service definitions come from `--proto` files or, without them, from the
+/// server's reflection service. `key:value` params are sent as metadata and
+/// `key=value` params set fields of the request message on top of `--data`.
+/// Without a method, the services and methods are listed instead. Unary and
+/// server-streaming methods are supported; client-streaming methods are sent
+/// the single request message.
+pub async fn handle_grpc(
+    address: &str,
+    method: Option<&str>,
+    params: &[String],
+    grpc: &GrpcOptions,
+    verbose: bool,
+    options: &RequestOptions,
+) -> Result<(), WaveError> {
+    let unsupported = [
+        (options.output.is_some(), "--output"),
+        (options.save.is_some(), "--save"),
+        (options.watch.is_some(), "--watch"),
+        (options.session.is_some(), "--session"),
+        (!options.resolve.is_empty(), "--resolve"),
+        (options.filter.is_some(), "--filter"),
+        (options.as_curl, "--as-curl"),
+        (options.format == OutputFormat::Json, "--format json"),
+        (options.record.is_some(), "--record"),
+        (options.replay.is_some(), "--replay"),
+    ];
+    if let Some((_, flag)) = unsupported.iter().find(|(set, _)| *set) {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(format!(
+            "{flag} cannot be used with 'wave grpc'"
+        ))));
+    }
+
+    let target = grpc::Target::parse(address)?;
+    let local = match target.host.parse::<std::net::IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => url::Url::parse(&format!("http://{}", target.host))
+            .is_ok_and(|parsed| http::is_loopback(&parsed)),
+    };
+    if options.offline && !local {
+        return Err(WaveError::Http(HttpError::Offline(RequestFailure::new(
+            Method::POST,
+            address,
+            "--offline only allows requests to localhost",
+        ))));
+    }
+    let (headers, data) = validate_params(params)?;
+    let mut metadata = headers_to_map(headers)?;
+    if let Some(auth) = options.credentials() {
+        auth.apply(&mut metadata);
+    }
+    if let Some(secs) = options.timeout {
+        if let Ok(value) = format!("{}S", secs.min(99_999_999)).parse() {
+            metadata.insert("grpc-timeout", value);
+        }
+    }
+    let deadline = options
+        .timeout
+        .map(|secs| tokio::time::Instant::now() + Duration::from_secs(secs));
+    let color = options.use_color();
+
+    let mut channel = None;
+    let descriptors = if grpc.proto.is_empty() {
+        let service = method
+            .map(grpc::split_method)
+            .transpose()?
+            .map(|(service, _)| service);
+        let message = format!("Reading service definitions from {address}");
+        let (connected, descriptors) = within_deadline(
+            address,
+            deadline,
+            run_with_spinner(&message, || async {
+                let mut channel = grpc::Channel::connect(&target, &options.tls()).await?;
+                let descriptors = grpc::fetch_descriptors(&mut channel, &metadata, service).await?;
+                Ok::<_, grpc::GrpcError>((channel, descriptors))
+            }),
+        )
+        .await?;
+        channel = Some(connected);
+        descriptors
+    } else {
+        grpc::Descriptors::from_proto_files(&grpc.proto, &grpc.import_path)?
+    };
+    let Some(method) = method else {
+        print!("{}", format_grpc_services(&descriptors.services(), color));
+        return Ok(());
+    };
+
+    let (service, rpc) = descriptors.method(method)?;
+    let path = format!("/{}/{}", service.name, rpc.name);
+    let message = grpc_request_message(&descriptors, &rpc.input, grpc.data.as_deref(), &data)?;
+    let encoded = grpc::encode(&descriptors, &rpc.input, &message)?;
+    if options.dry_run || verbose {
+        let req = HttpRequest::builder(target.url(&path), Method::POST)
+            .headers(metadata.clone())
+            .header("content-type", "application/grpc")
+            .body(RequestBody::Json(message))
+            .build();
+        if options.dry_run {
+            print!("{}", apply_color(format_request(&req), color));
+            return Ok(());
+        }
+        print_request(&req, color);
+    }
+
+    let reply = within_deadline(
+        address,
+        deadline,
+        run_with_spinner(&format!("gRPC {method}"), || async {
+            let mut channel = match channel {
+                Some(channel) => channel,
+                None => grpc::Channel::connect(&target, &options.tls()).await?,
+            };
+            channel.call(&path, &metadata, &encoded).await
+        }),
+    )
+    .await?;
+    let messages = reply
+        .messages
+        .iter()
+        .map(|bytes| grpc::decode(&descriptors, &rpc.output, bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+    print!(
+        "{}",
+        format_grpc_reply(
+            &reply.headers,
+            &messages,
+            &reply.trailers,
+            options.print_options(verbose)
+        )
+    );
+    Ok(())
+}
+
+/// Parse a CLI parameter value to appropriate JSON type
+///
+/// A value with a type marker (`==text`, `:=json`, `=@path`) is taken as it
+/// says; other values are inferred as a number, boolean or string.
+fn parse_cli_value_to_json(value: &str) -> Result<serde_json::Value, WaveError> {
+    if let Some(value) = fields::marked_value(value)? {
+        return Ok(value);
+    }
+    // Try parsing as integer first
+    if let Ok(int_val) = value.parse::<i64>() {
+        return Ok(serde_json::Value::Number(int_val.into()));
+    }
+    // Try parsing as float
+    if let Ok(float_val) = value.parse::<f64>() {
+        if let Some(num) = serde_json::Number::from_f64(float_val) {
+            return Ok(serde_json::Value::Number(num));
+        }
+    }
+    // Try parsing as boolean
+    if let Ok(bool_val) = value.parse::<bool>() {
+        return Ok(serde_json::Value::Bool(bool_val));
+    }
+    // Default to string
+    Ok(serde_json::Value::String(value.to_string()))
+}
+
+/// Merge collection JSON with CLI parameters, preserving types from collection
+///
+/// Keys may be nested field paths (`user.name`, `tags[]`, `[0].id`), so a
+/// param can override a single field deep inside the collection's body while
+/// the rest of it is kept.
+fn merge_json_with_cli_params(
+    collection_json: Option<serde_json::Value>,
+    cli_body: &[(String, String)],
+) -> Result<serde_json::Value, WaveError> {
+    let mut result = match collection_json {
+        Some(json) if json.is_object() || json.is_array() || cli_body.is_empty() => json,
+        // If collection doesn't have a JSON object or array body but CLI has params, create new object
+        _ => serde_json::json!({}),
+    };
+    for (key, value) in cli_body {
+        // CLI parameters override collection values, with type inference
+        insert_body_field(&mut result, key, parse_cli_value_to_json(value)?)?;
+    }
+    Ok(result)
+}
+
+/// Merge form body data, with CLI params overriding collection params
+fn merge_body_data(
+    collection_body: &[(String, String)],
+    cli_body: &[(String, String)],
+) -> FormData {
+    let mut body = collection_body.to_vec();
+
+    // Override body with CLI values
+    for (cli_key, cli_value) in cli_body {
+        if let Some(pos) = body.iter().position(|(k, _)| k == cli_key) {
+            body[pos].1 = cli_value.clone();
+        } else {
+            body.push((cli_key.clone(), cli_value.clone()));
+        }
+    }
+
+    body
+}
+
+/// Parse form data string to key-value pairs
+fn parse_form_to_key_value_pairs(form_str: &str) -> KeyValuePairs {
+    form_str
+        .split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let k = parts.next()?;
+            let v = parts.next().unwrap_or("");
+            Some((k.to_string(), v.to_string()))
+        })
+        .collect()
+}
+
+// Collection request handling
+fn prepare_collection_headers_and_body(
+    resolved: &collection::Request,
+) -> (Headers, Option<serde_json::Value>, bool) {
+    let mut headers: Headers = resolved
+        .headers
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    match &resolved.body {
+        Some(collection::Body::Json(value)) => {
+            let json = collection::yaml_to_json(value);
+            if !headers
+                .iter()
+                .any(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+            {
+                headers.push(("Content-Type".to_string(), "application/json".to_string()));
+            }
+            (headers, Some(json), false)
+        }
+        Some(collection::Body::Form(map)) => {
+            let form_data: FormData = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            let mut header_map = ::http::HeaderMap::new();
+            let body = http::RequestBody::form(form_data);
+            let form_str = body.serialize(&mut header_map);
+
+            // Convert HeaderMap back to Vec for compatibility
+            let form_headers: Headers = header_map
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                .collect();
+            headers.extend(form_headers);
+            // For form data, we return the serialized string as a JSON string value
+            (headers, Some(serde_json::Value::String(form_str)), true)
+        }
+        // Multipart, text, raw and file bodies are built separately, and
+        // templates are raw bodies once resolved
+        Some(
+            collection::Body::Multipart(_)
+            | collection::Body::Text(_)
+            | collection::Body::Raw { .. }
+            | collection::Body::File { .. }
+            | collection::Body::Template { .. },
+        )
+        | None => (headers, None, false),
+    }
+}
+
+/// Loads `<dir>/<name>.yaml`, falling back to `<dir>/<name>.yml`, then to
+/// the files of the `<dir>/<name>/` directory
+///
+/// Variables from `./.env` and `<dir>/.env` are attached to the collection.
+pub fn load_named_collection(dir: &Path, collection_name: &str) -> Result<Collection, WaveError> {
+    let yaml_path = dir.join(format!("{collection_name}.yaml"));
+    let yml_path = dir.join(format!("{collection_name}.yml"));
+    let coll_dir = dir.join(collection_name);
+    let loaded = match [yaml_path, yml_path].into_iter().find(|p| p.is_file()) {
+        Some(path) => collection::load_collection(&path.to_string_lossy()),
+        None if coll_dir.is_dir() => collection::load_collection_dir(&coll_dir),
+        None => {
+            return Err(WaveError::Collection(CollectionError::FileNotFound(
+                format!("{collection_name}.yaml or {collection_name}.yml"),
+            )))
+        }
+    };
+    let mut coll = loaded.map_err(|e| match e.downcast::<CollectionError>() {
+        Ok(e) => WaveError::Collection(*e),
+        Err(e) => WaveError::Collection(CollectionError::InvalidYaml(e.to_string())),
+    })?;
+    coll.dotenv = dotenv::load_project(dir)?;
+    Ok(coll)
+}
+
+/// Turns a collection request into the request `wave -c` would send
+///
+/// Runs the request's `pre:` hooks, resolves variables (collection, selected
+/// environment, then `--var` overrides) and merges CLI headers and body data
+/// over the collection values.
+pub fn build_collection_request(
+    coll: &Collection,
+    collection_name: &str,
+    request_name: &str,
+    vars: &VariableOptions,
+    params: &[String],
+) -> Result<HttpRequest, WaveError> {
+    let mut run_vars = HashMap::new();
+    run_pre_hooks(
+        coll,
+        collection_name,
+        request_name,
+        vars,
+        params,
+        &mut run_vars,
+    )?;
+    let resolved =
+        resolve_collection_request(coll, collection_name, request_name, vars, &mut run_vars)?;
+    collection_http_request(&resolved, params, false)
+}
+
+/// Runs the `pre:` hooks of a collection request, adding the variables they
+/// set to `run_vars`
+///
+/// The hooks see the request as it would be sent, except that variables not
+/// defined yet (usually the ones the hooks set) are empty and OAuth2 tokens
+/// have not been fetched. Callers resolve the request afterwards, so a header
+/// such as `X-Signature: ${signature}` gets the value a hook computed from
+/// the body.
+pub fn run_pre_hooks(
+    coll: &Collection,
+    collection_name: &str,
+    request_name: &str,
+    vars: &VariableOptions,
+    params: &[String],
+    run_vars: &mut HashMap<String, String>,
+) -> Result<(), WaveError> {
+    let req = coll.request(request_name).ok_or_else(|| {
+        WaveError::Collection(CollectionError::RequestNotFound {
+            collection: collection_name.to_string(),
+            request: request_name.to_string(),
+        })
+    })?;
+    if req.pre.is_empty() {
+        return Ok(());
+    }
+    let mut draft_vars = collection_variables(coll, vars, run_vars)?;
+    for name in req.missing_variables(&draft_vars) {
+        draft_vars.insert(name, String::new());
+    }
+    let draft = collection::resolve_request_vars(&req, &draft_vars)
+        .map_err(|e| WaveError::Collection(CollectionError::VariableResolution(e)))?;
+    let built = merge_collection_request(&draft, params)?;
+    let context = hooks::HookContext {
+        name: request_name,
+        request: &built,
+        response: None,
+        vars: &draft_vars,
+    };
+    run_vars.extend(hooks::run_hooks(&req.pre, hooks::Stage::Pre, &context)?);
+    Ok(())
+}
+
+/// Runs the `post:` hooks of a resolved collection request once its response
+/// has arrived, adding the variables they set to `run_vars`
+pub fn run_post_hooks(
+    coll: &Collection,
+    resolved: &collection::Request,
+    req: &HttpRequest,
+    response: &HttpResponse,
+    vars: &VariableOptions,
+    run_vars: &mut HashMap<String, String>,
+) -> Result<(), WaveError> {
+    if resolved.post.is_empty() {
+        return Ok(());
+    }
+    let context = hooks::HookContext {
+        name: &resolved.name,
+        request: req,
+        response: Some(response),
+        vars: &collection_variables(coll, vars, run_vars)?,
+    };
+    run_vars.extend(hooks::run_hooks(
+        &resolved.post,
+        hooks::Stage::Post,
+        &context,
+    )?);
+    Ok(())
+}
+
+/// Finds a request in a collection and resolves all of its variables
+///
+/// Variables come from the collection, then the selected environment, then
+/// `run_vars` (values captured or entered earlier in a `wave run`), then
+/// `--var` overrides, each layer winning over the previous one.
+///
+/// Variables still missing are prompted for when stdin is a terminal and
+/// `--no-input` is not set; the answers are added to `run_vars` so later
+/// requests in the run reuse them.
+pub fn resolve_collection_request(
+    coll: &Collection,
+    collection_name: &str,
+    request_name: &str,
+    vars: &VariableOptions,
+    run_vars: &mut HashMap<String, String>,
+) -> Result<collection::Request, WaveError> {
+    let (templates, mut literals) = collection_variable_layers(coll, vars, run_vars)?;
+    let req = coll.request(request_name).ok_or_else(|| {
+        WaveError::Collection(CollectionError::RequestNotFound {
+            collection: collection_name.to_string(),
+            request: request_name.to_string(),
+        })
+    })?;
+    if !vars.no_input && is_interactive() {
+        for name in req.undefined_variables(&templates, &literals) {
+            let value = prompt_variable(&name)?;
+            literals.insert(name.clone(), value.clone());
+            run_vars.insert(name, value);
+        }
+    }
+    let (file_vars, problems) = collection::expand_variables(&templates, &literals);
+    let mut used: Vec<String> = req.variable_names().into_iter().collect();
+    used.sort();
+    if let Some(problem) = used.iter().find_map(|name| problems.get(name)) {
+        return Err(WaveError::Collection(CollectionError::VariableResolution(
+            problem.clone(),
+        )));
+    }
+    collection::resolve_request_vars(&req, &file_vars)
+        .map_err(|e| WaveError::Collection(CollectionError::VariableResolution(e)))
+}
+
+/// Swaps an `auth: oauth2:` block for a bearer token from its token endpoint
+///
+/// Tokens are cached per `--env` environment (see [`oauth2`]). Dry runs,
+/// `--replay` and requests given `--auth` or `--bearer` do not fetch a token,
+/// so neither a dry run nor a replay touches the network. Token requests are
+/// never written to a `--record` cassette.
+pub async fn authorize(
+    resolved: &mut collection::Request,
+    collection_dir: &Path,
+    vars: &VariableOptions,
+    options: &RequestOptions,
+) -> Result<(), WaveError> {
+    let Some(Auth::OAuth2(settings)) = &resolved.auth else {
+        return Ok(());
+    };
+    if options.dry_run || options.replay.is_some() || options.credentials().is_some() {
+        return Ok(());
+    }
+    let cache = oauth2::cache_path(collection_dir, vars.env.as_deref());
+    let token = oauth2::access_token(&options.network_backend()?, settings, &cache).await?;
+    resolved.auth = Some(Auth::Bearer(token));
+    Ok(())
+}
+
+/// Layers the variables of a collection: collection variables, then the
+/// selected environment, then `run_vars`, then `--var` overrides
+///
+/// Only collection and environment values are expanded when they refer to
+/// other variables; `run_vars` and `--var` values are used as they are.
+pub fn collection_variables(
+    coll: &Collection,
+    vars: &VariableOptions,
+    run_vars: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, WaveError> {
+    let (templates, literals) = collection_variable_layers(coll, vars, run_vars)?;
+    Ok(collection::expand_variables(&templates, &literals).0)
+}
+
+/// Returns the layered variables of [`collection_variables`] before
+/// expansion, as in [`Collection::variable_layers`]
+fn collection_variable_layers(
+    coll: &Collection,
+    vars: &VariableOptions,
+    run_vars: &HashMap<String, String>,
+) -> Result<collection::VariableLayers, WaveError> {
+    let (mut templates, mut literals) = coll.variable_layers(vars.env.as_deref())?;
+    let overrides = var_overrides(vars)?;
+    tracing::debug!(
+        env = vars.env.as_deref().unwrap_or("none"),
+        collection = templates.len() + literals.len(),
+        run = run_vars.len(),
+        overrides = overrides.len(),
+        "collection variables loaded"
+    );
+    for (name, value) in run_vars.iter().chain(&overrides) {
+        templates.remove(name);
+        literals.insert(name.clone(), value.clone());
+    }
+    Ok((templates, literals))
+}
+
+/// Parses the `--var KEY=VALUE` overrides
+fn var_overrides(vars: &VariableOptions) -> Result<HashMap<String, String>, WaveError> {
+    let mut overrides = HashMap::new();
+    for kv in &vars.var {
+        let (k, v) = kv.split_once('=').ok_or_else(|| {
+            WaveError::Cli(CliError::InvalidVarOverride(format!(
+                "'{kv}' must be in KEY=VALUE format"
+            )))
+        })?;
+        let key = k.trim();
+        if key.is_empty() {
+            return Err(WaveError::Cli(CliError::InvalidVarOverride(format!(
+                "'{kv}' has an empty key"
+            ))));
+        }
+        overrides.insert(key.to_string(), v.to_string());
+    }
+    Ok(overrides)
+}
+
+/// Returns true when a person can answer prompts on this terminal
+fn is_interactive() -> bool {
+    use std::io::IsTerminal;
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Returns true for variable names whose values should not be echoed
+fn is_secret_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    ["token", "password", "secret"]
+        .iter()
+        .any(|word| lower.contains(word))
+}
+
+/// Asks for the value of a missing variable on the terminal
+///
+/// Input is hidden for names that look like secrets (see [`is_secret_name`]).
+fn prompt_variable(name: &str) -> Result<String, WaveError> {
+    let term = console::Term::stderr();
+    term.write_str(&format!("Value for ${{{name}}}: "))?;
+    let value = if is_secret_name(name) {
+        term.read_secure_line()
+    } else {
+        term.read_line()
+    };
+    value.map_err(|e| WaveError::Io(format!("Failed to read a value for '{name}': {e}")))
+}
+
+/// Builds the HTTP request for a resolved collection request
+///
+/// CLI headers and body data in `params` are merged over the collection values.
+/// A body sent with GET, DELETE or OPTIONS is warned about. Body data a HEAD
+/// request leaves out is warned about too, or fails with `strict`.
+pub fn collection_http_request(
+    resolved: &collection::Request,
+    params: &[String],
+    strict: bool,
+) -> Result<HttpRequest, WaveError> {
+    let none = BodyOptions::default();
+    let mut ignored = ignored_params(
+        (resolved.method == Method::HEAD).then_some(&resolved.method),
+        params,
+        &none,
+    );
+    if resolved.method == Method::HEAD && resolved.body.is_some() {
+        ignored.push(format!(
+            "HEAD requests are sent without a body, so the body of '{}' is ignored",
+            resolved.name
+        ));
+    }
+    report_ignored(ignored, strict)?;
+    if resolved.method != Method::HEAD && (resolved.body.is_some() || gives_body(params, &none)?) {
+        warn_body_method(&resolved.method);
+    }
+    merge_collection_request(resolved, params)
+}
+
+/// Builds the HTTP request for a resolved collection request, without
+/// warnings; see [`collection_http_request`]
+fn merge_collection_request(
+    resolved: &collection::Request,
+    params: &[String],
+) -> Result<HttpRequest, WaveError> {
+    // Parse CLI params for potential override
+    let (params, cli_files) = extract_file_params(params);
+    let (cli_headers, cli_body) = parse_params(&params);
+    validate_file_parts(&cli_files)?;
+    let is_multipart =
+        matches!(resolved.body, Some(collection::Body::Multipart(_))) || !cli_files.is_empty();
+    // Methods other than POST, PUT and PATCH only send a body when one is given
+    let has_body = match resolved.method {
+        Method::HEAD => false,
+        Method::POST | Method::PUT | Method::PATCH => true,
+        _ => resolved.body.is_some() || !cli_body.is_empty() || is_multipart,
+    };
+    let mut http_req = match resolved.method {
+        _ if !has_body => {
+            let collection_headers: Headers = resolved
+                .headers
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+            HttpRequest::new(
+                &resolved.url,
+                resolved.method.clone(),
+                None,
+                layer_headers(&collection_headers, &cli_headers)?,
+            )
+        }
+        _ if is_multipart => {
+            let collection_parts = match &resolved.body {
+                Some(collection::Body::Multipart(fields)) => fields
+                    .iter()
+                    .map(|(name, field)| field.to_part(name))
+                    .collect(),
+                None => Vec::new(),
+                Some(_) => return Err(WaveError::Cli(CliError::ConflictingArguments(
+                    "file fields (name@=path) need a multipart body or no body in the collection"
+                        .to_string(),
+                ))),
+            };
+            let collection_headers: Headers = resolved
+                .headers
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+            HttpRequest::builder(&resolved.url, resolved.method.clone())
+                .headers(layer_headers(&collection_headers, &cli_headers)?)
+                .body(RequestBody::multipart(merge_multipart_parts(
+                    collection_parts,
+                    &resolve_text_values(&cli_body)?,
+                    cli_files,
+                )))
+                .build()
+        }
+        _ if matches!(
+            resolved.body,
+            Some(
+                collection::Body::Text(_)
+                    | collection::Body::Raw { .. }
+                    | collection::Body::File { .. }
+            )
+        ) =>
+        {
+            if !cli_body.is_empty() {
+                return Err(WaveError::Cli(CliError::ConflictingArguments(format!(
+                    "key=value body data can only be added to JSON, form or multipart bodies, and '{}' has a text, raw or file body",
+                    resolved.name
+                ))));
+            }
+            let mut collection_headers: Headers = resolved
+                .headers
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+            let (body, content_type) = match &resolved.body {
+                Some(collection::Body::Text(data)) => (RequestBody::text(data.clone()), None),
+                Some(collection::Body::Raw { data, content_type }) => {
+                    (RequestBody::text(data.clone()), content_type.as_ref())
+                }
+                Some(collection::Body::File { path, content_type }) => {
+                    (RequestBody::file(path), content_type.as_ref())
+                }
+                _ => unreachable!("guarded by the match arm"),
+            };
+            if let Some(content_type) = content_type {
+                collection_headers.retain(|(k, _)| !k.eq_ignore_ascii_case("content-type"));
+                collection_headers.push(("Content-Type".to_string(), content_type.clone()));
+            }
+            HttpRequest::builder(&resolved.url, resolved.method.clone())
+                .headers(layer_headers(&collection_headers, &cli_headers)?)
+                .body(body)
+                .build()
+        }
+        _ => {
+            let (collection_headers, collection_json, is_form) =
+                prepare_collection_headers_and_body(resolved);
+
+            // Merge headers (CLI overrides collection)
+            let merged_headers = layer_headers(&collection_headers, &cli_headers)?;
+
+            // Handle body based on type
+            let final_body = if is_form {
+                // For form data, extract the string from JSON and merge with CLI params
+                let form_str = collection_json
+                    .as_ref()
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                let collection_body_data = if form_str.is_empty() {
+                    vec![]
+                } else {
+                    parse_form_to_key_value_pairs(&form_str)
+                };
+                let merged_body_data =
+                    merge_body_data(&collection_body_data, &resolve_text_values(&cli_body)?);
+                merged_body_data
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect::<Vec<_>>()
+                    .join("&")
+            } else {
+                // Array bodies are only addressed by element, e.g. [0].name=x
+                let addressable = match &collection_json {
+                    Some(json) if json.is_array() => {
+                        cli_body.iter().all(|(key, _)| key.starts_with('['))
+                    }
+                    Some(json) => json.is_object(),
+                    None => true,
+                };
+                if !addressable && !cli_body.is_empty() {
+                    return Err(WaveError::Cli(CliError::ConflictingArguments(format!(
+                        "key=value body data can only be added to a JSON object body, or to a JSON array body with [N] or [] keys, and the body of '{}' is neither",
+                        resolved.name
+                    ))));
+                }
+                // JSON encoding - use new merge function that preserves types
+                let merged_json = merge_json_with_cli_params(collection_json, &cli_body)?;
+                serde_json::to_string(&merged_json).unwrap_or_else(|_| "{}".to_string())
+            };
+
+            HttpRequest::new(
+                &resolved.url,
+                resolved.method.clone(),
+                Some(final_body),
+                merged_headers,
+            )
+        }
+    };
+    if let Some(auth) = &resolved.auth {
+        auth.apply(&mut http_req.headers);
+    }
+    Ok(http_req)
+}
+
+pub async fn handle_collection(
+    collection_dir: &Path,
+    collection_name: &str,
+    request_name: &str,
+    verbose: bool,
+    options: &RequestOptions,
+    vars: &VariableOptions,
+    params: &[String],
+) -> Result<(), WaveError> {
+    if options.save.is_some() {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--save only applies to ad-hoc requests, not collection requests".to_string(),
+        )));
+    }
+    let coll = load_named_collection(collection_dir, collection_name)?;
+    let options = &options.with_collection(&coll);
+    let mut run_vars = HashMap::new();
+    run_pre_hooks(
+        &coll,
+        collection_name,
+        request_name,
+        vars,
+        params,
+        &mut run_vars,
+    )?;
+    let mut resolved =
+        resolve_collection_request(&coll, collection_name, request_name, vars, &mut run_vars)?;
+    authorize(&mut resolved, collection_dir, vars, options).await?;
+    let req = collection_http_request(&resolved, params, options.strict)?;
+    if let Some(interval) = options.watch {
+        return watch_request(&req, interval, verbose, options).await;
+    }
+    let spinner_msg = format!("{} {}", req.method, req.url);
+    let response = send_request_with_spinner(&req, &spinner_msg, verbose, options).await?;
+    if let Some(resp) = &response {
+        if let Some(path) = &resolved.save_response {
+            runner::save_response(Path::new(path), resp)
+                .map_err(|e| WaveError::Io(format!("failed to save response to '{path}': {e}")))?;
+        }
+        run_post_hooks(&coll, &resolved, &req, resp, vars, &mut run_vars)?;
+    }
+
+    // An expect: block states which statuses are acceptable, so it takes over from --fail
+    let (Some(expect), false) = (&resolved.expect, options.dry_run) else {
+        return options.check_status(response.as_ref());
+    };
+    let checks = match &response {
+        Some(resp) => expect.evaluate(resp),
+        None => Vec::new(),
+    };
+    print!(
+        "{}",
+        apply_color(assertions::format_checks(&checks), options.use_color())
+    );
+    let failed = checks.iter().filter(|c| !c.passed()).count();
+    if response.is_none() || failed > 0 {
+        return Err(WaveError::Collection(CollectionError::ExpectationFailed {
+            request: request_name.to_string(),
+            failed,
+            total: checks.len(),
+        }));
+    }
+    Ok(())
+}
+
+/// Runs the requests of a collection, up to `parallel` at a time
+///
+/// Every request (or only those named in `request_names`, in the given order)
+/// is sent through a single shared client. With `parallel` above one,
+/// requests start in order as slots free up, except that a request using a
+/// variable captured by an earlier one waits for it. A request passes when its
+/// `expect:` block holds, or, without one, when it gets a 2xx response. A
+/// summary line is printed as each request completes, followed by the
+/// pass/fail count.
+/// Requests that fail to resolve or send are reported and the run continues.
+///
+/// Each request waits for its `delay:` before it is sent; `--delay` sets one
+/// for the requests without their own, except the first.
+///
+/// The response of a request with `save_response:` is written to that file,
+/// and the request fails if it cannot be.
+///
+/// Requests named in a selected request's `depends_on:` are run too, and
+/// before it. If one of them does not pass, the requests depending on it
+/// are skipped, unless `--continue-on-error` is given.
+///
+/// A request whose `only_if:` condition does not hold, or whose `skip_if:`
+/// condition does, is skipped along with the requests depending on it.
+///
+/// With `--data`, the requests run once per row of the dataset, with the
+/// row's columns as variables (`--var` still wins), and a table of the
+/// outcome of each request in each row precedes the count.
+///
+/// # Errors
+/// Returns `CollectionError::RunFailed` if any request did not pass, so the
+/// process exits non-zero in CI.
+pub async fn handle_run(
+    collection_dir: &Path,
+    collection_name: &str,
+    request_names: &[String],
+    verbose: bool,
+    run: &RunOptions,
+    options: &RequestOptions,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    if options.output.is_some() {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--output cannot be used with 'wave run'".to_string(),
+        )));
+    }
+    if options.format == OutputFormat::Json {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--format json cannot be used with 'wave run'".to_string(),
+        )));
+    }
+    if options.save.is_some() {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--save cannot be used with 'wave run'".to_string(),
+        )));
+    }
+    if options.watch.is_some() {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--watch cannot be used with 'wave run'".to_string(),
+        )));
+    }
+    if !run.report.is_empty() && options.dry_run {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--report cannot be used with --dry-run".to_string(),
+        )));
+    }
+    // A report on stdout keeps it clean for the tool reading it
+    let report_on_stdout = run.report.iter().any(|r| r.path.is_none());
+    if report_on_stdout && verbose {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--verbose cannot be used with a --report written to stdout; give the report a file name"
+                .to_string(),
+        )));
+    }
+    let say = |line: String| {
+        if report_on_stdout {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    };
+    let coll = load_named_collection(collection_dir, collection_name)?;
+    let options = &options.with_collection(&coll);
+    let names = select_run_requests(&coll, collection_name, request_names, &run.tag)?;
+    let parallel = run.parallel as usize;
+    // With collection defaults merged in, so headers like `Authorization:
+    // Bearer ${token}` wait for the request that captures `token`
+    let selected: Vec<collection::Request> =
+        names.iter().filter_map(|name| coll.request(name)).collect();
+    let selected_refs: Vec<&collection::Request> = selected.iter().collect();
+    let dependencies = runner::dependencies(&selected_refs);
+    let prerequisites = runner::prerequisites(&selected_refs);
+    let client = Client::new(options.backend()?);
+    let mut session = options.open_session()?;
+    // Without --data, a single pass with no extra variables
+    let rows = match &run.data {
+        Some(path) => dataset::load(path)?,
+        None => vec![HashMap::new()],
+    };
+    // Outcome of each request in each pass, for the table printed after a --data run
+    let mut table: Vec<Vec<Option<String>>> = vec![vec![None; selected.len()]; rows.len()];
+    let mut results = Vec::with_capacity(names.len() * rows.len());
+    let mut run_vars = HashMap::new();
+    let color = options.use_color();
+    let mut limiter = run
+        .rate
+        .map(|rate| runner::RateLimiter::new(rate, Instant::now()));
+    let mut first = true;
+
+    for (pass, row) in rows.into_iter().enumerate() {
+        // Captures and prompted values carry over; the row's values replace
+        // those of the previous row
+        run_vars.extend(row);
+        let label = |index: usize| match run.data {
+            Some(_) => format!("{} (row {})", selected[index].name, pass + 1),
+            None => selected[index].name.clone(),
+        };
+        let mut scheduler = runner::Scheduler::new(dependencies.clone(), parallel);
+        let mut in_flight = FuturesUnordered::new();
+        // For each request, the prerequisite it is skipped for
+        let mut skipped: Vec<Option<usize>> = vec![None; selected.len()];
+        loop {
+            while let Some(index) = scheduler.start() {
+                let name = &selected[index].name;
+                // Conditions are checked before any pre: hook runs
+                let unmet = match skipped[index] {
+                    Some(prerequisite) => Ok(Some(format!(
+                        "'{}' did not pass",
+                        selected[prerequisite].name
+                    ))),
+                    None => collection_variables(&coll, vars, &run_vars).and_then(|file_vars| {
+                        runner::unmet_condition(&selected[index], &file_vars).map_err(|e| {
+                            WaveError::Collection(CollectionError::VariableResolution(e))
+                        })
+                    }),
+                };
+                if let Ok(Some(reason)) = &unmet {
+                    let result = RunResult {
+                        name: label(index),
+                        method: selected[index].method.to_string(),
+                        url: String::new(),
+                        status: None,
+                        elapsed: Duration::ZERO,
+                        error: None,
+                        checks: Vec::new(),
+                        skipped: Some(reason.clone()),
+                    };
+                    say(apply_color(runner::format_result_line(&result), color));
+                    table[pass][index] = Some(runner::format_outcome_cell(&result));
+                    results.push(result);
+                    runner::skip_dependents(&prerequisites, index, &mut skipped);
+                    scheduler.finish(index);
+                    continue;
+                }
+                let built = match unmet
+                    .and_then(|_| {
+                        run_pre_hooks(&coll, collection_name, name, vars, &[], &mut run_vars)
+                    })
+                    .and_then(|()| {
+                        resolve_collection_request(
+                            &coll,
+                            collection_name,
+                            name,
+                            vars,
+                            &mut run_vars,
+                        )
+                    }) {
+                    Ok(mut resolved) => authorize(&mut resolved, collection_dir, vars, options)
+                        .await
+                        .and_then(|()| {
+                            let req = collection_http_request(&resolved, &[], options.strict)?;
+                            Ok((options.apply(req), resolved))
+                        }),
+                    Err(e) => Err(e),
+                };
+                let (mut req, resolved) = match built {
+                    Ok(built) => built,
+                    Err(e) => {
+                        let result = RunResult {
+                            name: label(index),
+                            method: selected[index].method.to_string(),
+                            url: String::new(),
+                            status: None,
+                            elapsed: Duration::ZERO,
+                            error: Some(e.to_string()),
+                            checks: Vec::new(),
+                            skipped: None,
+                        };
+                        say(apply_color(runner::format_result_line(&result), color));
+                        table[pass][index] = Some(runner::format_outcome_cell(&result));
+                        results.push(result);
+                        if !run.continue_on_error {
+                            runner::skip_dependents(&prerequisites, index, &mut skipped);
+                        }
+                        scheduler.finish(index);
+                        continue;
+                    }
+                };
+                if let Some((session, _)) = &session {
+                    session.apply(&mut req);
+                }
+                if options.dry_run {
+                    if options.as_curl {
+                        println!("{}", to_curl(&req));
+                    } else {
+                        print!("{}", apply_color(format_request(&req), color));
+                    }
+                    println!();
+                    scheduler.finish(index);
+                    continue;
+                }
+                warn_cleartext_credentials(&req);
+
+                let client = &client;
+                let wait = limiter
+                    .as_mut()
+                    .map_or(Duration::ZERO, |l| l.reserve(Instant::now()))
+                    + runner::delay_before(&selected[index], run.delay, first);
+                first = false;
+                in_flight.push(async move {
+                    tokio::time::sleep(wait).await;
+                    let started = Instant::now();
+                    // A spinner per request only makes sense when they run one at a time
+                    let response = if parallel > 1 {
+                        client.send(&req).await
+                    } else {
+                        let spinner_msg = format!("{} {}", req.method, req.url);
+                        run_with_spinner(&spinner_msg, || client.send(&req)).await
+                    };
+                    (index, req, resolved, response, started.elapsed())
+                });
+            }
+
+            // Results are printed as requests complete, one whole block at a time
+            let Some((index, req, resolved, response, elapsed)) = in_flight.next().await else {
+                break;
+            };
+            let (status, error, checks) = match &response {
+                Ok(resp) => {
+                    if let Some((session, path)) = &mut session {
+                        session.update(&req, resp);
+                        session.save(path)?;
+                    }
+                    let mut checks = runner::check_response(resolved.expect.as_ref(), resp);
+                    if let Some(capture) = &resolved.capture {
+                        checks.extend(runner::capture_values(capture, resp, &mut run_vars));
+                    }
+                    if let Some(path) = &resolved.save_response {
+                        checks.push(assertions::Check {
+                            description: format!("response saved to {path}"),
+                            failure: runner::save_response(Path::new(path), resp)
+                                .err()
+                                .map(|e| e.to_string()),
+                        });
+                    }
+                    if !resolved.post.is_empty() {
+                        let hooks =
+                            run_post_hooks(&coll, &resolved, &req, resp, vars, &mut run_vars);
+                        checks.push(assertions::Check {
+                            description: "post: hooks succeed".to_string(),
+                            failure: hooks.err().map(|e| match e {
+                                WaveError::Collection(CollectionError::HookFailed {
+                                    command,
+                                    message,
+                                    ..
+                                }) => format!("'{command}' {message}"),
+                                e => e.to_string(),
+                            }),
+                        });
+                    }
+                    (Some(resp.status), None, checks)
+                }
+                Err(e) => (None, Some(e.to_string()), Vec::new()),
+            };
+            let result = RunResult {
+                name: label(index),
+                method: req.method.to_string(),
+                url: req.url.clone(),
+                status,
+                elapsed,
+                error,
+                checks,
+                skipped: None,
+            };
+            say(apply_color(runner::format_result_line(&result), color));
+            table[pass][index] = Some(runner::format_outcome_cell(&result));
+            if verbose {
+                print_request(&req, color);
+                if let Ok(resp) = response {
+                    print_response(Ok(resp), options.print_options(true));
+                }
+            }
+            if !result.passed() && !run.continue_on_error {
+                runner::skip_dependents(&prerequisites, index, &mut skipped);
+            }
+            results.push(result);
+            scheduler.finish(index);
+        }
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+    if run.data.is_some() {
+        let names: Vec<&str> = selected.iter().map(|r| r.name.as_str()).collect();
+        say(String::new());
+        say(runner::format_data_table(&names, &table));
+    }
+    say(String::new());
+    say(apply_color(runner::format_summary(&results), color));
+    for report in &run.report {
+        let text = report.render(collection_name, &results);
+        match &report.path {
+            Some(path) => write_report(path, &text)?,
+            None => print!("{text}"),
+        }
+    }
+    let failed = results.iter().filter(|r| r.failed()).count();
+    if failed > 0 {
+        return Err(WaveError::Collection(CollectionError::RunFailed {
+            collection: collection_name.to_string(),
+            failed,
+            total: results.len(),
+        }));
+    }
+    Ok(())
+}
+
+/// Writes a `--report` file, creating its directory if needed
+fn write_report(path: &Path, text: &str) -> Result<(), WaveError> {
+    let failed = |e: std::io::Error| {
+        WaveError::Io(format!("failed to write report '{}': {e}", path.display()))
+    };
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(failed)?;
+    }
+    std::fs::write(path, text).map_err(failed)
+}
+
+/// Picks the requests to run: all of them in file order, or the named ones,
+/// keeping only those with one of `tags` when any are given, along with the
+/// requests they name in `depends_on:`
+///
+/// # Errors
+/// Returns `CollectionError::RequestNotFound` for the first unknown name, and
+/// `CollectionError::NoTaggedRequests` if the tags leave nothing to run
+fn select_run_requests(
+    coll: &Collection,
+    collection_name: &str,
+    request_names: &[String],
+    tags: &[String],
+) -> Result<Vec<String>, WaveError> {
+    for name in request_names {
+        if !coll.requests.iter().any(|r| &r.name == name) {
+            return Err(WaveError::Collection(CollectionError::RequestNotFound {
+                collection: collection_name.to_string(),
+                request: name.clone(),
+            }));
+        }
+    }
+    let selected: Vec<String> = if request_names.is_empty() {
+        coll.requests
+            .iter()
+            .filter(|r| r.has_any_tag(tags))
+            .map(|r| r.name.clone())
+            .collect()
+    } else {
+        request_names
+            .iter()
+            .filter(|name| {
+                coll.requests
+                    .iter()
+                    .any(|r| &r.name == *name && r.has_any_tag(tags))
+            })
+            .cloned()
+            .collect()
+    };
+    if selected.is_empty() && !tags.is_empty() {
+        return Err(WaveError::Collection(CollectionError::NoTaggedRequests {
+            collection: collection_name.to_string(),
+            tags: tags.to_vec(),
+        }));
+    }
+    // Prerequisites run too, whatever their tags, ahead of the requests
+    // needing them; loading the collection ruled out depends_on cycles
+    fn add(coll: &Collection, name: &str, ordered: &mut Vec<String>) {
+        if ordered.iter().any(|n| n == name) {
+            return;
+        }
+        if let Some(req) = coll.requests.iter().find(|r| r.name == name) {
+            for dep in &req.depends_on {
+                add(coll, dep, ordered);
+            }
+        }
+        ordered.push(name.to_string());
+    }
+    let mut ordered = Vec::with_capacity(selected.len());
+    for name in &selected {
+        add(coll, name, &mut ordered);
+    }
+    Ok(ordered)
+}
+
+/// Formats the requests of a collection for `wave list`, one per line
+///
+/// Lines show the name, method and unresolved URL, aligned in columns, with
+/// any tags in brackets. Requests without one of `tags` are left out when
+/// tags are given.
+pub fn format_request_list(name: &str, coll: &Collection, tags: &[String]) -> Option<String> {
+    let requests: Vec<&collection::Request> = coll
+        .requests
+        .iter()
+        .filter(|r| r.has_any_tag(tags))
+        .collect();
+    if requests.is_empty() {
+        return None;
+    }
+    let name_width = requests.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    let method_width = requests
+        .iter()
+        .map(|r| r.method.as_str().len())
+        .max()
+        .unwrap_or(0);
+    let mut output = name.to_string();
+    for req in requests {
+        let mut line = format!(
+            "\n  {:name_width$}  {:method_width$}  {}",
+            req.name,
+            req.method.as_str(),
+            req.url
+        );
+        if !req.tags.is_empty() {
+            line.push_str(&format!("  [{}]", req.tags.join(", ")));
+        }
+        output.push_str(&line);
+    }
+    Some(output)
+}
+
+/// Lists the requests of one collection, or of every collection in the directory
+///
+/// # Errors
+/// Returns `CollectionError::NoTaggedRequests` if tags are given and no
+/// request has any of them.
+pub fn handle_list(
+    collection_dir: &Path,
+    collection_name: Option<&str>,
+    tags: &[String],
+) -> Result<(), WaveError> {
+    let names = match collection_name {
+        Some(name) => vec![name.to_string()],
+        None => collection::collection_names(collection_dir)?,
+    };
+    let mut listed = Vec::new();
+    for name in &names {
+        let coll = match collection_name {
+            Some(_) => load_named_collection(collection_dir, name)?,
+            None => match load_named_collection(collection_dir, name) {
+                Ok(coll) => coll,
+                Err(e) => {
+                    eprintln!("Skipping collection '{name}': {e}");
+                    continue;
+                }
+            },
+        };
+        listed.extend(format_request_list(name, &coll, tags));
+    }
+    if listed.is_empty() && !tags.is_empty() {
+        return Err(WaveError::Collection(CollectionError::NoTaggedRequests {
+            collection: collection_name
+                .map_or_else(|| collection_dir.display().to_string(), str::to_string),
+            tags: tags.to_vec(),
+        }));
+    }
+    println!("{}", listed.join("\n\n"));
+    Ok(())
+}
+
+/// Checks one collection, or every collection in the directory, and prints
+/// each problem as `file:line: message`
+///
+/// # Errors
+/// Returns `CollectionError::ValidationFailed` if any problem is found.
+pub fn handle_validate(
+    collection_dir: &Path,
+    collection_name: Option<&str>,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    let names = match collection_name {
+        Some(name) => vec![name.to_string()],
+        None => collection::collection_names(collection_dir)?,
+    };
+    let overrides = var_overrides(vars)?;
+    let mut found = 0;
+    for name in &names {
+        let problems =
+            validate::validate_collection(collection_dir, name, vars.env.as_deref(), &overrides)?;
+        for problem in &problems {
+            println!("{problem}");
+        }
+        found += problems.len();
+    }
+    if found > 0 {
+        return Err(WaveError::Collection(CollectionError::ValidationFailed {
+            problems: found,
+        }));
+    }
+    let noun = if names.len() == 1 {
+        "collection"
+    } else {
+        "collections"
+    };
+    println!("{} {noun} checked, no problems found", names.len());
+    Ok(())
+}
+
+/// Where the variables of a collection get their values, for `wave vars`
+/// and `wave describe`
+struct VariableSources<'a> {
+    coll: &'a Collection,
+    overrides: HashMap<String, String>,
+    environment: Option<(&'a str, &'a HashMap<String, String>)>,
+    /// Variable name -> first request whose `capture:` sets it
+    captured: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> VariableSources<'a> {
+    /// # Errors
+    /// Returns an error if `--env` names an unknown environment or a `--var`
+    /// is malformed
+    fn new(coll: &'a Collection, vars: &'a VariableOptions) -> Result<Self, WaveError> {
+        // Fails early on an unknown environment
+        coll.variables_for_env(vars.env.as_deref())?;
+        let environment = vars.env.as_deref().and_then(|env| {
+            coll.environments
+                .as_ref()
+                .and_then(|envs| envs.get(env))
+                .map(|values| (env, values))
+        });
+        let mut captured = HashMap::new();
+        for req in &coll.requests {
+            for var in req.capture.iter().flat_map(|c| c.keys()) {
+                captured.entry(var.as_str()).or_insert(req.name.as_str());
+            }
+        }
+        Ok(VariableSources {
+            coll,
+            overrides: var_overrides(vars)?,
+            environment,
+            captured,
+        })
+    }
+
+    /// Returns where `var` gets its value, and whether it gets one at all
+    fn source(&self, var: &str, has_default: bool) -> (String, bool) {
+        let found = |source: String| (source, true);
+        if self.overrides.contains_key(var) {
+            return found("--var".to_string());
+        }
+        if let Some(env_name) = var.strip_prefix("env:") {
+            if std::env::var_os(env_name).is_some() {
+                return found("environment variable".to_string());
+            }
+            if self.coll.dotenv.contains_key(env_name) {
+                return found(".env".to_string());
+            }
+        } else if let Some(request) = self.captured.get(var) {
+            return found(format!("captured by '{request}'"));
+        } else if let Some((env, _)) = self
+            .environment
+            .filter(|(_, values)| values.contains_key(var))
+        {
+            return found(format!("environment '{env}'"));
+        } else if self.coll.dotenv.contains_key(var) {
+            return found(".env".to_string());
+        } else if self
+            .coll
+            .variables
+            .as_ref()
+            .is_some_and(|v| v.contains_key(var))
+        {
+            return found("variables".to_string());
+        } else if dynamic::is_dynamic(var) || var == collection::REQUEST_NAME_VAR {
+            return found("built-in".to_string());
+        }
+        if has_default {
+            found("default".to_string())
+        } else {
+            ("missing".to_string(), false)
+        }
+    }
+
+    /// Returns the value of `var` when it comes from the `--env` environment
+    /// or `variables:`, the only values expanded when they refer to other
+    /// variables
+    fn template(&self, var: &str) -> Option<&'a str> {
+        if self.overrides.contains_key(var) || self.captured.contains_key(var) {
+            return None;
+        }
+        if let Some(value) = self.environment.and_then(|(_, values)| values.get(var)) {
+            return Some(value);
+        }
+        if self.coll.dotenv.contains_key(var) {
+            return None;
+        }
+        self.coll.variables.as_ref()?.get(var).map(String::as_str)
+    }
+}
+
+/// Groups the variable uses of a request by variable, in order of first use,
+/// with whether every use has a default and the fields using it
+fn grouped_variable_uses(req: &collection::Request) -> Vec<(String, bool, Vec<&'static str>)> {
+    let mut grouped: Vec<(String, bool, Vec<&'static str>)> = Vec::new();
+    for (field, var, has_default) in req.variable_uses() {
+        match grouped.iter_mut().find(|(name, _, _)| *name == var) {
+            Some((_, default, fields)) => {
+                *default &= has_default;
+                if !fields.contains(&field) {
+                    fields.push(field);
+                }
+            }
+            None => grouped.push((var, has_default, vec![field])),
+        }
+    }
+    grouped
+}
+
+/// Formats the `wave vars` report of a collection
+///
+/// Lists every `${...}` variable its requests refer to, and those the values
+/// of `variables:` and the environment refer to in turn, sorted by name, with
+/// where the value would come from and the requests, fields and variables
+/// using it.
+/// Sources follow the order in which [`collection_variables`] layers them:
+/// `--var`, a `capture:` of an earlier request in a run, the `--env`
+/// environment, `.env`, `variables:`, then built-in dynamic variables and
+/// `:-` defaults. `${env:NAME}` references come from the process environment
+/// or `.env`. Values are never shown, as they are often secrets.
+///
+/// # Errors
+/// Returns an error if `--env` names an unknown environment or a `--var` is
+/// malformed
+pub fn format_variable_report(
+    name: &str,
+    coll: &Collection,
+    vars: &VariableOptions,
+) -> Result<String, WaveError> {
+    let sources = VariableSources::new(coll, vars)?;
+
+    // Variable name -> (every use has a default, "request (fields)" entries)
+    let mut used: std::collections::BTreeMap<String, (bool, Vec<String>)> = Default::default();
+    for entry in &coll.requests {
+        let Some(req) = coll.request(&entry.name) else {
+            continue;
+        };
+        for (var, has_default, fields) in grouped_variable_uses(&req) {
+            let (all_default, uses) = used.entry(var).or_insert((true, Vec::new()));
+            *all_default &= has_default;
+            uses.push(format!("{} ({})", entry.name, fields.join(", ")));
+        }
+    }
+    // Variables the values of other variables refer to are used too
+    let mut pending: Vec<String> = used.keys().cloned().collect();
+    let mut followed = HashSet::new();
+    while let Some(var) = pending.pop() {
+        let Some(template) = sources
+            .template(&var)
+            .filter(|_| followed.insert(var.clone()))
+        else {
+            continue;
+        };
+        for (inner, has_default) in collection::variable_references(template) {
+            let (all_default, uses) = used.entry(inner.clone()).or_insert((true, Vec::new()));
+            *all_default &= has_default;
+            let user = format!("variable '{var}'");
+            if !uses.contains(&user) {
+                uses.push(user);
+            }
+            pending.push(inner);
+        }
+    }
+
+    let rows: Vec<(String, String, bool, String)> = used
+        .into_iter()
+        .map(|(var, (has_default, uses))| {
+            let (from, resolves) = sources.source(&var, has_default);
+            (var, from, resolves, uses.join(", "))
+        })
+        .collect();
+    let mut output = match vars.env.as_deref() {
+        Some(env) => format!("{name} (environment '{env}')"),
+        None => name.to_string(),
+    };
+    if rows.is_empty() {
+        output.push_str("\n  no variables used");
+        return Ok(output);
+    }
+    let name_width = rows.iter().map(|(var, ..)| var.len()).max().unwrap_or(0);
+    let source_width = rows
+        .iter()
+        .map(|(_, from, ..)| from.len())
+        .max()
+        .unwrap_or(0);
+    for (var, from, _, uses) in &rows {
+        output.push_str(&format!(
+            "\n  {var:name_width$}  {from:source_width$}  {uses}"
+        ));
+    }
+    let missing = rows.iter().filter(|(_, _, resolves, _)| !resolves).count();
+    let noun = if rows.len() == 1 {
+        "variable"
+    } else {
+        "variables"
+    };
+    output.push_str(&format!("\n\n{} {noun}, {missing} missing", rows.len()));
+    Ok(output)
+}
+
+/// Prints which variables a collection uses and where their values come from
+pub fn handle_vars(
+    collection_dir: &Path,
+    collection_name: &str,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    let coll = load_named_collection(collection_dir, collection_name)?;
+    println!("{}", format_variable_report(collection_name, &coll, vars)?);
+    Ok(())
+}
+
+/// Formats the `wave describe` documentation of a collection or one of its
+/// requests
+///
+/// Without a request, shows the collection's `description:` and its requests
+/// with the first line of theirs. With one, shows the request's description,
+/// its URL and headers as templates, the variables it uses with where their
+/// values would come from (as in [`format_variable_report`]), and its body
+/// as written. Nothing is resolved, so no secrets are shown.
+///
+/// # Errors
+/// Returns `CollectionError::RequestNotFound` for an unknown request, or an
+/// error if `--env` names an unknown environment or a `--var` is malformed
+pub fn format_description(
+    name: &str,
+    coll: &Collection,
+    request_name: Option<&str>,
+    vars: &VariableOptions,
+) -> Result<String, WaveError> {
+    let Some(request_name) = request_name else {
+        let mut output = name.to_string();
+        if let Some(description) = &coll.description {
+            output.push_str(&indent(description.trim_end()));
+        }
+        if coll.requests.is_empty() {
+            output.push_str(
+                "
+
+  no requests",
+            );
+            return Ok(output);
+        }
+        let name_width = coll
+            .requests
+            .iter()
+            .map(|r| r.name.len())
+            .max()
+            .unwrap_or(0);
+        let method_width = coll
+            .requests
+            .iter()
+            .map(|r| r.method.as_str().len())
+            .max()
+            .unwrap_or(0);
+        output.push('\n');
+        for req in &coll.requests {
+            let summary = req
+                .description
+                .as_deref()
+                .and_then(|d| d.lines().next())
+                .unwrap_or("");
+            let line = format!(
+                "\n  {:name_width$}  {:method_width$}  {summary}",
+                req.name,
+                req.method.as_str()
+            );
+            output.push_str(line.trim_end());
+        }
+        return Ok(output);
+    };
+
+    let sources = VariableSources::new(coll, vars)?;
+    let req = coll.request(request_name).ok_or_else(|| {
+        WaveError::Collection(CollectionError::RequestNotFound {
+            collection: name.to_string(),
+            request: request_name.to_string(),
+        })
+    })?;
+    let mut url = req.url.clone();
+    for (i, (key, value)) in req.query.iter().enumerate() {
+        let separator = if i == 0 && !url.contains('?') {
+            '?'
+        } else {
+            '&'
+        };
+        url.push_str(&format!("{separator}{key}={value}"));
+    }
+    let mut output = format!("{}  {} {}", req.name, req.method.as_str(), url);
+    if let Some(description) = &req.description {
+        output.push_str(&indent(description.trim_end()));
+    }
+
+    if let Some(headers) = req.headers.as_ref().filter(|h| !h.is_empty()) {
+        let mut headers: Vec<_> = headers.iter().collect();
+        headers.sort();
+        output.push_str("\n\nheaders");
+        for (key, value) in headers {
+            output.push_str(&format!("\n  {key}: {value}"));
+        }
+    }
+
+    let uses = grouped_variable_uses(&req);
+    if !uses.is_empty() {
+        let rows: Vec<(String, String, String)> = uses
+            .into_iter()
+            .map(|(var, has_default, fields)| {
+                let (from, _) = sources.source(&var, has_default);
+                (var, from, fields.join(", "))
+            })
+            .collect();
+        let name_width = rows.iter().map(|(var, ..)| var.len()).max().unwrap_or(0);
+        let source_width = rows
+            .iter()
+            .map(|(_, from, _)| from.len())
+            .max()
+            .unwrap_or(0);
+        output.push_str("\n\nvariables");
+        for (var, from, fields) in &rows {
+            output.push_str(&format!(
+                "\n  {var:name_width$}  {from:source_width$}  {fields}"
+            ));
+        }
+    }
+
+    if let Some(body) = &req.body {
+        let (kind, shape) = match body {
+            collection::Body::Json(value) => (
+                "json",
+                serde_json::to_string_pretty(&collection::yaml_to_json(value))?,
+            ),
+            collection::Body::Form(fields) => {
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort();
+                let lines: Vec<String> = fields.iter().map(|(k, v)| format!("{k}={v}")).collect();
+                ("form", lines.join("\n"))
+            }
+            collection::Body::Multipart(fields) => {
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort_by_key(|(k, _)| *k);
+                let lines: Vec<String> = fields
+                    .iter()
+                    .map(|(k, field)| match field {
+                        collection::MultipartField::Text(value) => format!("{k}={value}"),
+                        collection::MultipartField::File { file } => format!("{k}=@{file}"),
+                    })
+                    .collect();
+                ("multipart", lines.join("\n"))
+            }
+            collection::Body::Text(text) => ("text", text.trim_end().to_string()),
+            collection::Body::Raw { data, content_type } => (
+                content_type.as_deref().unwrap_or("raw"),
+                data.trim_end().to_string(),
+            ),
+            collection::Body::File { path, .. } => ("file", path.display().to_string()),
+            collection::Body::Template { path, .. } => ("template", path.display().to_string()),
+        };
+        output.push_str(&format!("\n\nbody ({kind})"));
+        output.push_str(&indent(&shape));
+    }
+
+    if !req.tags.is_empty() {
+        output.push_str(&format!("\n\ntags: {}", req.tags.join(", ")));
+    }
+    Ok(output)
+}
+
+/// Indents every line of `text` by two spaces, each on a new line
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                "\n".to_string()
+            } else {
+                format!("\n  {line}")
+            }
+        })
+        .collect()
+}
+
+/// Prints the documentation of a collection, or of one of its requests
+pub fn handle_describe(
+    collection_dir: &Path,
+    collection_name: &str,
+    request_name: Option<&str>,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    let coll = load_named_collection(collection_dir, collection_name)?;
+    println!(
+        "{}",
+        format_description(collection_name, &coll, request_name, vars)?
+    );
+    Ok(())
+}
+
+/// Removes or renames a request in its collection file and reports the file
+/// that changed
+///
+/// # Errors
+/// Returns `CollectionError::RequestNotFound` for an unknown request,
+/// `CollectionError::RequestExists` when renaming onto an existing name and
+/// `CollectionError::RequestInUse` when removing a request others depend on.
+pub fn handle_collection_edit(
+    collection_dir: &Path,
+    edit: &CollectionEdit,
+) -> Result<(), WaveError> {
+    match edit {
+        CollectionEdit::Rm {
+            collection,
+            request,
+        } => {
+            let path = collection::remove_request(collection_dir, collection, request)?;
+            println!("Removed '{request}' from {}", path.display());
+        }
+        CollectionEdit::Mv {
+            collection,
+            old,
+            new,
+        } => {
+            let path = collection::rename_request(collection_dir, collection, old, new)?;
+            println!("Renamed '{old}' to '{new}' in {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Builds the command that opens `path` in `editor` at `line`
+///
+/// `editor` may carry arguments, as in `code --wait`. Most terminal editors
+/// (vi, vim, nano, emacs, micro, helix via `hx`) take `+LINE` before the
+/// file; VS Code and its forks take `--goto FILE:LINE` and Sublime Text and
+/// Zed take `FILE:LINE`.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use wave::editor_command;
+///
+/// let (program, args) = editor_command("vim", Path::new("api.yaml"), Some(12));
+/// assert_eq!((program.as_str(), args), ("vim", vec!["+12".to_string(), "api.yaml".to_string()]));
+///
+/// let (program, args) = editor_command("code --wait", Path::new("api.yaml"), Some(12));
+/// assert_eq!(program, "code");
+/// assert_eq!(args, ["--wait", "--goto", "api.yaml:12"]);
+/// ```
+pub fn editor_command(editor: &str, path: &Path, line: Option<usize>) -> (String, Vec<String>) {
+    let mut words = editor.split_whitespace().map(str::to_string);
+    let program = words.next().unwrap_or_else(|| "vi".to_string());
+    let mut args: Vec<String> = words.collect();
+    let file = path.display().to_string();
+    let name = Path::new(&program)
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match line {
+        Some(line)
+            if matches!(
+                name.as_str(),
+                "code" | "code-insiders" | "codium" | "cursor"
+            ) =>
+        {
+            args.extend(["--goto".to_string(), format!("{file}:{line}")]);
+        }
+        Some(line) if matches!(name.as_str(), "subl" | "zed") => {
+            args.push(format!("{file}:{line}"))
+        }
+        Some(line) => args.extend([format!("+{line}"), file]),
+        None => args.push(file),
+    }
+    (program, args)
+}
+
+/// Opens a collection file in the user's editor, at the line of a request
+/// when one is named, and validates the collection once the editor exits
+///
+/// The editor is `$VISUAL`, then `$EDITOR`, then `vi`. For a collection
+/// split over several files, the file defining the request is opened.
+///
+/// # Errors
+/// Returns `CollectionError::RequestNotFound` for an unknown request,
+/// `CollectionError::EditorFailed` if the editor cannot start or exits with
+/// an error, and `CollectionError::ValidationFailed` if the edited
+/// collection has problems.
+pub fn handle_edit(
+    collection_dir: &Path,
+    collection_name: &str,
+    request_name: Option<&str>,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    let (path, line) = validate::request_location(collection_dir, collection_name, request_name)?;
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let (program, args) = editor_command(&editor, &path, line);
+    tracing::debug!(%program, ?args, "starting editor");
+    let failed = |message: String| {
+        WaveError::Collection(CollectionError::EditorFailed {
+            editor: editor.clone(),
+            message,
+        })
+    };
+    let status = std::process::Command::new(&program)
+        .args(&args)
+        .status()
+        .map_err(|e| failed(format!("could not start: {e}")))?;
+    if !status.success() {
+        return Err(failed(format!("exited with {status}")));
+    }
+    handle_validate(collection_dir, Some(collection_name), vars)
+}
+
+/// Sends a URL or collection request `bench.requests` times, up to
+/// `bench.concurrency` at once, and prints a latency and error summary
+///
+/// A plain URL is sent as a GET; use a collection request for other methods,
+/// headers or bodies. Every request goes through one shared client, so
+/// connections are reused as they would be by a real client.
+///
+/// # Errors
+/// Returns an error if the request cannot be built or the options conflict;
+/// failed requests are reported in the summary, not as an error.
+pub async fn handle_bench(
+    collection_dir: &Path,
+    target: &str,
+    request_name: Option<&str>,
+    bench: &BenchOptions,
+    options: &RequestOptions,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    if options.output.is_some() || options.save.is_some() || options.watch.is_some() {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--output, --save and --watch cannot be used with 'wave bench'".to_string(),
+        )));
+    }
+    let (req, options) = match request_name {
+        Some(name) => {
+            let coll = load_named_collection(collection_dir, target)?;
+            let options = options.with_collection(&coll);
+            let mut run_vars = HashMap::new();
+            run_pre_hooks(&coll, target, name, vars, &[], &mut run_vars)?;
+            let mut resolved =
+                resolve_collection_request(&coll, target, name, vars, &mut run_vars)?;
+            authorize(&mut resolved, collection_dir, vars, &options).await?;
+            (
+                collection_http_request(&resolved, &[], options.strict)?,
+                options,
+            )
+        }
+        None => {
+            let url = validate_url(target, options.default_scheme.unwrap_or_default())?;
+            let req = HttpRequest::new(&url, Method::GET, None, HeaderMap::new());
+            (req, options.clone())
+        }
+    };
+    let mut req = options.apply(req);
+    if let Some((session, _)) = options.open_session()? {
+        session.apply(&mut req);
+    }
+    let color = options.use_color();
+    if options.dry_run {
+        if options.as_curl {
+            println!("{}", to_curl(&req));
+        } else {
+            print!("{}", apply_color(format_request(&req), color));
+        }
+        return Ok(());
+    }
+    warn_cleartext_credentials(&req);
+
+    let total = bench.requests as usize;
+    let concurrency = (bench.concurrency as usize).min(total);
+    let client = Client::new(options.backend()?);
+    let pb = ProgressBar::new(bench.requests);
+    if let Ok(style) = ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} ({per_sec})") {
+        pb.set_style(style.progress_chars("=> "));
+    }
+    let request_line = format!("{} {}", req.method, req.url);
+    pb.set_message(request_line.clone());
+
+    let started = Instant::now();
+    let mut samples = Vec::with_capacity(total);
+    let mut in_flight = FuturesUnordered::new();
+    let mut limiter = bench
+        .rate
+        .map(|rate| runner::RateLimiter::new(rate, started));
+    let mut sent = 0;
+    loop {
+        while sent < total && in_flight.len() < concurrency {
+            let (client, req) = (&client, &req);
+            let wait = limiter
+                .as_mut()
+                .map_or(Duration::ZERO, |l| l.reserve(Instant::now()));
+            in_flight.push(async move {
+                tokio::time::sleep(wait).await;
+                let started = Instant::now();
+                let result = client.send(req).await;
+                bench::Sample {
+                    elapsed: started.elapsed(),
+                    outcome: result.map(|resp| resp.status).map_err(|e| e.to_string()),
+                }
+            });
+            sent += 1;
+        }
+        let Some(sample) = in_flight.next().await else {
+            break;
+        };
+        samples.push(sample);
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+
+    let report = bench::BenchReport::new(&request_line, concurrency, &samples, started.elapsed());
+    if options.format == OutputFormat::Json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", bench::format_report(&report));
+    }
+    Ok(())
+}
+
+/// Prints a collection request as an equivalent `curl` command
+///
+/// Variables are resolved and CLI overrides merged first, so the command
+/// sends exactly what `wave -c` would.
+pub fn handle_export_curl(
+    collection_dir: &Path,
+    collection_name: &str,
+    request_name: &str,
+    vars: &VariableOptions,
+    params: &[String],
+) -> Result<(), WaveError> {
+    let coll = load_named_collection(collection_dir, collection_name)?;
+    let req = build_collection_request(&coll, collection_name, request_name, vars, params)?;
+    println!("{}", to_curl(&req));
+    Ok(())
+}
+
+/// Saves the response of a collection request as its snapshot, or with
+/// `check` compares the response with the saved snapshot
+///
+/// Fields in `ignore` and in the request's `snapshot.ignore` list are left
+/// out of the comparison. Differences are printed one per line.
+///
+/// # Errors
+/// Returns `CollectionError::SnapshotNotFound` when checking a request that
+/// has no snapshot, and `CollectionError::SnapshotMismatch` when the
+/// response differs from it.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_snapshot(
+    collection_dir: &Path,
+    collection_name: &str,
+    request_name: &str,
+    check: bool,
+    ignore: &[Query],
+    verbose: bool,
+    options: &RequestOptions,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    if options.output.is_some() || options.save.is_some() || options.watch.is_some() {
+        return Err(WaveError::Cli(CliError::ConflictingArguments(
+            "--output, --save and --watch cannot be used with 'wave snapshot'".to_string(),
+        )));
+    }
+    let coll = load_named_collection(collection_dir, collection_name)?;
+    let options = &options.with_collection(&coll);
+    let mut run_vars = HashMap::new();
+    run_pre_hooks(
+        &coll,
+        collection_name,
+        request_name,
+        vars,
+        &[],
+        &mut run_vars,
+    )?;
+    let mut resolved =
+        resolve_collection_request(&coll, collection_name, request_name, vars, &mut run_vars)?;
+    let mut ignore = ignore.to_vec();
+    for path in resolved.snapshot.iter().flat_map(|s| &s.ignore) {
+        ignore.push(Query::parse(path).map_err(|e| {
+            WaveError::Collection(CollectionError::InvalidYaml(format!(
+                "request '{request_name}' snapshot.ignore: {e}"
+            )))
+        })?);
+    }
+    let saved = snapshot::find(collection_dir, collection_name, request_name);
+    if check && saved.is_none() {
+        return Err(WaveError::Collection(CollectionError::SnapshotNotFound {
+            collection: collection_name.to_string(),
+            request: request_name.to_string(),
+        }));
+    }
+
+    authorize(&mut resolved, collection_dir, vars, options).await?;
+    let mut req = options.apply(collection_http_request(&resolved, &[], options.strict)?);
+    let mut session = options.open_session()?;
+    if let Some((session, _)) = &session {
+        session.apply(&mut req);
+    }
+    let color = options.use_color();
+    if options.dry_run {
+        if options.as_curl {
+            println!("{}", to_curl(&req));
+        } else {
+            print!("{}", apply_color(format_request(&req), color));
+        }
+        return Ok(());
+    }
+    warn_cleartext_credentials(&req);
+    if verbose {
+        print_request(&req, color);
+    }
+    let client = Client::new(options.backend()?);
+    let spinner_msg = format!("{} {}", req.method, req.url);
+    let resp = run_with_spinner(&spinner_msg, || client.send(&req)).await?;
+    if let Some((session, path)) = &mut session {
+        session.update(&req, &resp);
+        session.save(path)?;
+    }
+    if verbose {
+        print_response(Ok(resp.clone()), options.print_options(true));
+    }
+    options.check_status(Some(&resp))?;
+
+    let Some(path) = saved.filter(|_| check) else {
+        let path = snapshot::save(collection_dir, collection_name, request_name, &resp.body)?;
+        println!("Saved snapshot '{}' ({})", path.display(), resp.status);
+        return Ok(());
+    };
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| WaveError::Io(format!("Failed to read '{}': {e}", path.display())))?;
+    let differences = snapshot::compare(&content, &resp.body, &ignore);
+    if differences.is_empty() {
+        println!("Response matches snapshot '{}'", path.display());
+        return Ok(());
+    }
+    println!("Response differs from snapshot '{}':", path.display());
+    print!(
+        "{}",
+        apply_color(snapshot::format_differences(&differences), color)
+    );
+    Err(WaveError::Collection(CollectionError::SnapshotMismatch {
+        request: request_name.to_string(),
+        differences: differences.len(),
+    }))
+}
+
+/// Serves the mock responses of a collection until the process is stopped
+///
+/// Collection variables, the environment and `--var` values are resolved in
+/// the mock headers and bodies along with the path parameters of each request.
+///
+/// # Errors
+/// Returns `CollectionError::NoMocks` if no request has a `mock:` block, and
+/// `WaveError::Io` if the address cannot be bound.
+pub async fn handle_mock(
+    collection_dir: &Path,
+    collection_name: &str,
+    host: &str,
+    port: u16,
+    vars: &VariableOptions,
+) -> Result<(), WaveError> {
+    let coll = load_named_collection(collection_dir, collection_name)?;
+    let routes = mock::routes(&coll);
+    if routes.is_empty() {
+        return Err(WaveError::Collection(CollectionError::NoMocks {
+            collection: collection_name.to_string(),
+        }));
+    }
+    let file_vars = collection_variables(&coll, vars, &HashMap::new())?;
+    let listener = tokio::net::TcpListener::bind((host, port))
+        .await
+        .map_err(|e| WaveError::Io(format!("Failed to listen on {host}:{port}: {e}")))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| WaveError::Io(e.to_string()))?;
+    println!(
+        "Serving {} mock(s) from '{collection_name}' on http://{addr}",
+        routes.len()
+    );
+    for route in &routes {
+        println!(
+            "  {:<7} {}  ({})",
+            route.method.as_str(),
+            route.path,
+            route.name
+        );
+    }
+    println!("Press Ctrl-C to stop");
+    mock::serve(listener, routes, file_vars).await
+}
+
+/// Prints the completion script for `shell`
+pub fn handle_completions(shell: completions::Shell) {
+    print!(
+        "{}",
+        completions::generate(shell, &mut <Cli as clap::CommandFactory>::command())
+    );
+}
+
+/// Prints collection names, or the request names of one collection, one per line
+///
+/// Used by the completion scripts while typing, so problems such as a missing
+/// collection directory print nothing instead of an error.
+pub fn handle_names(collection_dir: &Path, collection_name: Option<&str>) {
+    let names = match collection_name {
+        None => collection::collection_names(collection_dir).unwrap_or_default(),
+        Some(name) => load_named_collection(collection_dir, name)
+            .map(|coll| coll.requests.into_iter().map(|r| r.name).collect())
+            .unwrap_or_default(),
+    };
+    for name in names {
+        println!("{name}");
+    }
+}
+
+/// Prints a diagnostics report of the environment, and of the connection
+/// to `target` when given
+///
+/// Configuration is loaded here rather than by the caller so that invalid
+/// files show up in the report. Every check runs even after one fails; the
+/// command then returns an error, so `wave doctor` exits non-zero, after
+/// printing the full report.
+pub async fn handle_doctor(target: Option<&str>) -> Result<(), WaveError> {
+    let config = Config::load().unwrap_or_default();
+    let env = |name: &str| std::env::var(name).ok();
+    let url = target
+        .map(|target| {
+            let scheme = config.default_scheme.unwrap_or_default();
+            url::Url::parse(&validate_url(target, scheme)?)
+                .map_err(|e| WaveError::Parse(ParseError::Url(format!("'{target}': {e}"))))
+        })
+        .transpose()?;
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let mut sections = vec![
+        doctor::environment(&cwd),
+        doctor::configuration(&cwd),
+        doctor::proxies(env, config.proxy.as_deref()),
+    ];
+    match &url {
+        Some(url) => {
+            let proxy = doctor::proxy_for(url, env, config.proxy.as_deref());
+            sections.push(doctor::connectivity(url, proxy.as_deref()).await);
+        }
+        None => sections.push(doctor::Section {
+            title: "Connectivity".to_string(),
+            findings: vec![doctor::Finding::new(
+                doctor::Status::Info,
+                "target",
+                "none given, try `wave doctor api.example.com`",
+            )],
+        }),
+    }
+    let color = config.color.unwrap_or_default().enabled();
+    print!("{}", apply_color(doctor::format_report(&sections), color));
+    let failed = sections
+        .iter()
+        .flat_map(|s| &s.findings)
+        .filter(|f| f.status == doctor::Status::Failed)
+        .count();
+    match failed {
+        0 => Ok(()),
+        1 => Err(error::runtime_error("wave doctor found 1 failed check")),
+        n => Err(error::runtime_error(&format!(
+            "wave doctor found {n} failed checks"
+        ))),
+    }
+}
+
+/// Scaffolds a starter collection in the collection directory
+///
+/// Refuses to replace an existing collection unless `force` is set.
+pub fn handle_init(collection_dir: &Path, name: &str, force: bool) -> Result<(), WaveError> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(WaveError::Cli(CliError::InvalidCollectionName(
+            name.to_string(),
+        )));
+    }
+    if collection::RESERVED_COLLECTION_NAMES.contains(&name) {
+        return Err(WaveError::Cli(CliError::ReservedCollectionName(
+            name.to_string(),
+        )));
+    }
+    let path = collection::init_collection(collection_dir, name, force)?;
+    println!("Created collection '{}'", path.display());
+    println!("Try it with: wave -c {name} get-example");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::f64;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_params_json_body() {
+        let params = vec![
+            "name=joe".to_string(),
+            "age=42".to_string(),
+            "Authorization:Bearer123".to_string(),
+        ];
+        let (headers, data) = parse_params(&params);
+        assert_eq!(
+            headers,
+            vec![("Authorization".to_string(), "Bearer123".to_string())]
+        );
+        assert_eq!(
+            data,
+            vec![
+                ("name".to_string(), "joe".to_string()),
+                ("age".to_string(), "42".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_params_form_flag_ignored() {
+        let params = vec![
+            "--form".to_string(),
+            "foo=bar".to_string(),
+            "baz=qux".to_string(),
+            "X-Test:1".to_string(),
+        ];
+        let (headers, data) = parse_params(&params);
+        assert_eq!(headers, vec![("X-Test".to_string(), "1".to_string())]);
+        assert_eq!(
+            data,
+            vec![
+                ("foo".to_string(), "bar".to_string()),
+                ("baz".to_string(), "qux".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_options_timeout_flag() {
+        let cli = Cli::try_parse_from(["wave", "get", "--timeout", "5", "example.com"])
+            .expect("Test: parse --timeout");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let req = options.apply(HttpRequest::new(
+            "http://example.com",
+            Method::GET,
+            None,
+            HeaderMap::new(),
+        ));
+        assert_eq!(req.timeout, Some(Duration::from_secs(5)));
+
+        assert!(Cli::try_parse_from(["wave", "get", "--timeout", "0", "example.com"]).is_err());
+    }
+
+    #[test]
+    fn test_offline_flag_keeps_requests_local() {
+        let cli = Cli::try_parse_from(["wave", "get", "--offline", "example.com"])
+            .expect("Test: parse --offline");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let client = Client::new(options.backend().expect("Test: backend"));
+        let remote = tokio_test::block_on(client.get("https://example.com/").send());
+        assert!(matches!(
+            remote.map_err(WaveError::from),
+            Err(WaveError::Http(HttpError::Offline(_)))
+        ));
+
+        assert!(Cli::try_parse_from([
+            "wave",
+            "get",
+            "--offline",
+            "--record",
+            "c.yaml",
+            "example.com"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_load_json_document_inline_and_file() {
+        let inline = load_json_document(r#"{"a":{"b":1},"tags":["x","y"]}"#).unwrap();
+        assert_eq!(
+            inline,
+            serde_json::json!({"a": {"b": 1}, "tags": ["x", "y"]})
+        );
+
+        let path = std::env::temp_dir().join(format!("wave_json_body_{}.json", std::process::id()));
+        std::fs::write(&path, "[1, 2, 3]").expect("Test: write payload");
+        let from_file = load_json_document(&format!("@{}", path.display())).unwrap();
+        assert_eq!(from_file, serde_json::json!([1, 2, 3]));
+        let _ = std::fs::remove_file(&path);
+
+        assert!(matches!(
+            load_json_document("{not json"),
+            Err(WaveError::Cli(CliError::InvalidJsonArgument(_)))
+        ));
+        assert!(matches!(
+            load_json_document("@/definitely/not/here.json"),
+            Err(WaveError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_request_body_json_flag() {
+        let body = BodyOptions {
+            json: Some(r#"{"nested":{"ok":true}}"#.to_string()),
+            ..Default::default()
+        };
+        let req = HttpRequest::builder("http://example.com", Method::POST)
+            .body(build_request_body(&body, vec![], vec![]).unwrap())
+            .build();
+        assert_eq!(req.body.as_deref(), Some(r#"{"nested":{"ok":true}}"#));
+        assert_eq!(req.headers.get("content-type").unwrap(), "application/json");
+
+        let conflict =
+            build_request_body(&body, vec![("name".to_string(), "joe".to_string())], vec![]);
+        assert!(matches!(
+            conflict,
+            Err(WaveError::Cli(CliError::ConflictingArguments(_)))
+        ));
+
+        assert!(
+            Cli::try_parse_from(["wave", "post", "--form", "--json", "{}", "example.com"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_build_request_body_data_raw() {
+        let body = BodyOptions {
+            data_raw: Some(r#"{"raw": [1, 2]}"#.to_string()),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        apply_content_type(&mut headers, &body).unwrap();
+        let req = HttpRequest::builder("http://example.com", Method::POST)
+            .headers(headers)
+            .body(build_request_body(&body, vec![], vec![]).unwrap())
+            .build();
+        assert_eq!(req.body.as_deref(), Some(r#"{"raw": [1, 2]}"#));
+        assert_eq!(req.headers.get("content-type").unwrap(), "application/json");
+
+        let text = BodyOptions {
+            data_raw: Some("hello".to_string()),
+            ..Default::default()
+        };
+        let req = HttpRequest::builder("http://example.com", Method::POST)
+            .body(build_request_body(&text, vec![], vec![]).unwrap())
+            .build();
+        assert_eq!(req.headers.get("content-type").unwrap(), "text/plain");
+
+        let conflict =
+            build_request_body(&text, vec![("name".to_string(), "joe".to_string())], vec![]);
+        assert!(matches!(
+            conflict,
+            Err(WaveError::Cli(CliError::ConflictingArguments(_)))
+        ));
+    }
+
+    #[test]
+    fn test_build_request_body_nested_fields() {
+        let data = [
+            ("user[name]", "joe"),
+            ("user.age", "30"),
+            ("tags[]", "a"),
+            ("tags[]", "b"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .to_vec();
+        let req = HttpRequest::builder("http://example.com", Method::POST)
+            .body(build_request_body(&BodyOptions::default(), data, vec![]).unwrap())
+            .build();
+        let body: serde_json::Value = serde_json::from_str(req.body.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"user": {"name": "joe", "age": "30"}, "tags": ["a", "b"]})
+        );
+
+        let data = vec![("user.age".to_string(), ":=30".to_string())];
+        let req = HttpRequest::builder("http://example.com", Method::POST)
+            .body(build_request_body(&BodyOptions::default(), data.clone(), vec![]).unwrap())
+            .build();
+        assert_eq!(req.body.as_deref(), Some(r#"{"user":{"age":30}}"#));
+        let form = BodyOptions {
+            form: true,
+            ..Default::default()
+        };
+        let req = HttpRequest::builder("http://example.com", Method::POST)
+            .body(build_request_body(&form, data, vec![]).unwrap())
+            .build();
+        assert_eq!(req.body.as_deref(), Some("user.age=30"));
+
+        let clash = vec![
+            ("user".to_string(), "joe".to_string()),
+            ("user.name".to_string(), "joe".to_string()),
+        ];
+        assert!(matches!(
+            build_request_body(&BodyOptions::default(), clash, vec![]),
+            Err(WaveError::Cli(CliError::InvalidBodyField(_)))
+        ));
+    }
+
+    #[test]
+    fn test_content_type_flag_overrides_header_param() {
+        let body = BodyOptions {
+            data_raw: Some("<a/>".to_string()),
+            content_type: Some("application/xml".to_string()),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "text/plain".parse().unwrap());
+        apply_content_type(&mut headers, &body).unwrap();
+        assert_eq!(headers.get("content-type").unwrap(), "application/xml");
+    }
+
+    #[test]
+    fn test_split_stdin_marker() {
+        let (params, read_stdin) = split_stdin_marker(&["X-Test:1".to_string(), "-".to_string()]);
+        assert_eq!(params, vec!["X-Test:1"]);
+        assert!(read_stdin);
+        assert!(!split_stdin_marker(&["a=b".to_string()]).1);
+    }
+
+    #[test]
+    fn test_gives_body() {
+        let params = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let none = BodyOptions::default();
+        assert!(!gives_body(&params(&["Accept:application/json"]), &none).unwrap());
+        assert!(gives_body(&params(&["query:={}"]), &none).unwrap());
+        assert!(gives_body(&params(&["-"]), &none).unwrap());
+        let raw = BodyOptions {
+            data_raw: Some("ids=1,2".to_string()),
+            ..BodyOptions::default()
+        };
+        assert!(gives_body(&[], &raw).unwrap());
+        let cli = Cli::try_parse_from(["wave", "delete", "example.com/items", "--json", "[1,2]"])
+            .expect("Test: parse delete --json");
+        let Command::Delete { body, .. } = cli.command else {
+            panic!("Expected delete command");
+        };
+        assert!(gives_body(&[], &body).unwrap());
+    }
+
+    #[test]
+    fn test_ignored_params() {
+        let params = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let none = BodyOptions::default();
+        let head = ignored_params(
+            Some(&Method::HEAD),
+            &params(&["X-A:1", "a=b", "f@=x"]),
+            &none,
+        );
+        assert_eq!(
+            head,
+            ["HEAD requests are sent without a body, so a=b f@=x is ignored"]
+        );
+        assert!(ignored_params(Some(&Method::GET), &params(&["X-A:1"]), &none).is_empty());
+        let form = BodyOptions {
+            form: true,
+            content_type: Some("text/plain".to_string()),
+            ..BodyOptions::default()
+        };
+        assert_eq!(ignored_params(Some(&Method::GET), &[], &form).len(), 2);
+        assert!(ignored_params(None, &params(&["a=b"]), &form).is_empty());
+        let misplaced = ignored_params(None, &params(&["a=b", "--form"]), &none);
+        assert_eq!(misplaced.len(), 1);
+        assert!(misplaced[0].contains("after the URL"));
+
+        assert!(report_ignored(misplaced.clone(), false).is_ok());
+        let err = report_ignored(misplaced, true).unwrap_err();
+        assert!(matches!(err, WaveError::Cli(CliError::IgnoredArguments(_))));
+        assert!(report_ignored(Vec::new(), true).is_ok());
+    }
+
+    #[test]
+    fn test_init_rejects_reserved_collection_names() {
+        let dir = std::env::temp_dir().join(format!("wave_init_reserved_{}", std::process::id()));
+        for name in collection::RESERVED_COLLECTION_NAMES {
+            let err = handle_init(&dir, name, false).unwrap_err();
+            assert!(matches!(
+                err,
+                WaveError::Cli(CliError::ReservedCollectionName(_))
+            ));
+        }
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_collection_requests_send_bodies_with_any_method() {
+        let yaml = r#"
+requests:
+  - name: search
+    method: GET
+    url: https://api.example.com/search
+  - name: purge
+    method: DELETE
+    url: https://api.example.com/cache
+    body:
+      json:
+        all: true
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let resolve = |name| {
+            resolve_collection_request(&coll, "api", name, &vars, &mut HashMap::new())
+                .expect("Test: resolve request")
+        };
+        let extra = ["extra=1".to_string()];
+
+        let search = collection_http_request(&resolve("search"), &extra, true).unwrap();
+        assert_eq!(search.body.as_deref(), Some(r#"{"extra":1}"#));
+        let purge = collection_http_request(&resolve("purge"), &[], true).unwrap();
+        assert_eq!(purge.body.as_deref(), Some(r#"{"all":true}"#));
+        assert!(collection_http_request(&resolve("search"), &[], true)
+            .unwrap()
+            .body
+            .is_none());
+    }
+
+    #[test]
+    fn test_collection_requests_report_ignored_bodies() {
+        let yaml = r#"
+requests:
+  - name: ping
+    method: HEAD
+    url: https://api.example.com/
+    body:
+      json:
+        all: true
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let ping = resolve_collection_request(&coll, "api", "ping", &vars, &mut HashMap::new())
+            .expect("Test: resolve request");
+
+        assert!(collection_http_request(&ping, &[], false)
+            .unwrap()
+            .body
+            .is_none());
+        let err = collection_http_request(&ping, &["extra=1".to_string()], true).unwrap_err();
+        let WaveError::Cli(CliError::IgnoredArguments(msg)) = err else {
+            panic!("Expected IgnoredArguments, got {err:?}");
+        };
+        assert!(msg.contains("so extra=1 is ignored"), "{msg}");
+        assert!(msg.contains("the body of 'ping' is ignored"), "{msg}");
+    }
+
+    #[test]
+    fn test_request_options_auth_flags() {
+        let cli = Cli::try_parse_from(["wave", "get", "--auth", "alice:secret", "example.com"])
+            .expect("Test: parse --auth");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let req = options.apply(HttpRequest::new(
+            "http://example.com",
+            Method::GET,
+            None,
+            headers_to_map(vec![(
+                "Authorization".to_string(),
+                "Bearer old".to_string(),
+            )])
+            .unwrap(),
+        ));
+        assert_eq!(
+            req.headers.get("authorization").unwrap(),
+            "Basic YWxpY2U6c2VjcmV0"
+        );
+
+        let options = RequestOptions {
+            bearer: Some("token123".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.credentials(),
+            Some(Auth::Bearer("token123".to_string()))
+        );
+
+        assert!(Cli::try_parse_from([
+            "wave",
+            "get",
+            "--auth",
+            "a:b",
+            "--bearer",
+            "t",
+            "example.com"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_as_curl_requires_dry_run() {
+        assert!(Cli::try_parse_from(["wave", "get", "--as-curl", "example.com"]).is_err());
+        let cli = Cli::try_parse_from(["wave", "get", "--dry-run", "--as-curl", "example.com"])
+            .expect("Test: parse --dry-run --as-curl");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        assert!(options.dry_run && options.as_curl);
+    }
+
+    #[test]
+    fn test_compressed_asks_for_gzip_and_deflate() {
+        let req = HttpRequest::builder("https://example.com", Method::GET).build();
+        let options = RequestOptions {
+            compressed: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.apply(req.clone()).headers["accept-encoding"],
+            "gzip, deflate"
+        );
+
+        let req = HttpRequest::builder("https://example.com", Method::GET)
+            .header("Accept-Encoding", "gzip")
+            .build();
+        assert_eq!(options.apply(req).headers["accept-encoding"], "gzip");
+    }
+
+    #[test]
+    fn test_request_id_adds_a_fresh_correlation_header() {
+        let cli = Cli::try_parse_from(["wave", "get", "--request-id", "example.com"])
+            .expect("Test: parse bare --request-id");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let req = HttpRequest::builder("https://example.com", Method::GET).build();
+        let first = options.apply(req.clone()).headers["x-request-id"].clone();
+        let second = options.apply(req).headers["x-request-id"].clone();
+        assert_eq!(first.len(), 36);
+        assert_ne!(first, second);
+
+        let options = RequestOptions {
+            request_id: Some(RequestIdFormat::Traceparent),
+            request_id_header: Some(HeaderName::from_static("x-trace")),
+            ..Default::default()
+        };
+        let req = HttpRequest::builder("https://example.com", Method::GET).build();
+        let value = options.apply(req).headers["x-trace"].clone();
+        assert!(value.to_str().unwrap().starts_with("00-"));
+
+        let req = HttpRequest::builder("https://example.com", Method::GET)
+            .header("X-Trace", "mine")
+            .build();
+        assert_eq!(options.apply(req).headers["x-trace"], "mine");
+        assert!(RequestOptions::default().request_id().is_none());
+    }
+
+    #[test]
+    fn test_parse_paginate_flags() {
+        let cli = Cli::try_parse_from(["wave", "get", "--paginate", "example.com"])
+            .expect("Test: parse bare --paginate");
+        let Command::Get { url, options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        assert_eq!(url, "example.com");
+        assert_eq!(options.paginate, Some(Paginate::Link));
+
+        let cli = Cli::try_parse_from([
+            "wave",
+            "get",
+            "--paginate=offset:skip",
+            "--items",
+            "$.results",
+            "--max-pages",
+            "5",
+            "example.com",
+        ])
+        .expect("Test: parse --paginate=offset:skip");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        assert_eq!(options.paginate, Some(Paginate::Offset("skip".to_string())));
+        assert_eq!(options.items.as_deref(), Some("$.results"));
+        assert_eq!(options.max_pages, Some(5));
+
+        assert!(Cli::try_parse_from(["wave", "get", "--items", "data", "example.com"]).is_err());
+        assert!(
+            Cli::try_parse_from(["wave", "get", "--paginate", "-o", "x", "example.com"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_build_collection_request_export_curl() {
+        let yaml = r#"
+variables:
+  host: api.example.com
+requests:
+  - name: create
+    method: POST
+    url: https://${host}/users
+    auth:
+      bearer: secret
+    body:
+      json:
+        name: alice
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            var: vec!["host=staging.example.com".to_string()],
+            no_input: true,
+            ..Default::default()
+        };
+        let result =
+            build_collection_request(&coll, "export", "create", &vars, &["age=30".to_string()]);
+
+        let req = result.expect("Test: build request");
+        assert_eq!(req.url, "https://staging.example.com/users");
+        let curl = to_curl(&req);
+        assert!(curl.starts_with("curl -X POST 'https://staging.example.com/users'"));
+        assert!(curl.contains("-H 'authorization: Bearer secret'"));
+        assert!(curl.contains(r#""name":"alice""#));
+        assert!(curl.contains(r#""age":30"#));
+    }
+
+    #[test]
+    fn test_collection_json_bodies_need_not_be_objects() {
+        let yaml = r#"
+variables:
+  id: "7"
+requests:
+  - name: batch
+    method: POST
+    url: https://api.example.com/batch
+    body:
+      json:
+        - ${id}
+        - 8
+        - name: nested
+  - name: count
+    method: PUT
+    url: https://api.example.com/count
+    body:
+      json: 42
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let batch =
+            build_collection_request(&coll, "api", "batch", &vars, &[]).expect("Test: build batch");
+        assert_eq!(batch.body.as_deref(), Some(r#"["7",8,{"name":"nested"}]"#));
+        assert_eq!(
+            batch.headers.get("content-type").unwrap(),
+            "application/json"
+        );
+        let count =
+            build_collection_request(&coll, "api", "count", &vars, &[]).expect("Test: build count");
+        assert_eq!(count.body.as_deref(), Some("42"));
+        assert!(matches!(
+            build_collection_request(&coll, "api", "batch", &vars, &["x=1".to_string()]),
+            Err(WaveError::Cli(CliError::ConflictingArguments(_)))
+        ));
+        assert!(matches!(
+            build_collection_request(&coll, "api", "count", &vars, &["[0]=1".to_string()]),
+            Err(WaveError::Cli(CliError::ConflictingArguments(_)))
+        ));
+
+        let params = ["[2].name=changed".to_string(), "[]=9".to_string()];
+        let batch = build_collection_request(&coll, "api", "batch", &vars, &params)
+            .expect("Test: build batch with overrides");
+        assert_eq!(
+            batch.body.as_deref(),
+            Some(r#"["7",8,{"name":"changed"},9]"#)
+        );
+    }
+
+    #[test]
+    fn test_collection_overrides_merge_into_nested_json() {
+        let yaml = r#"
+requests:
+  - name: update-settings
+    method: PATCH
+    url: https://api.example.com/settings
+    body:
+      json:
+        settings:
+          theme: light
+          lang: en
+        owners:
+          - id: 1
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let params = [
+            "settings.theme=dark",
+            "settings[notify][email]=false",
+            "owners[0].role=admin",
+        ]
+        .map(String::from);
+        let req = build_collection_request(&coll, "api", "update-settings", &vars, &params)
+            .expect("Test: build request");
+        let body: serde_json::Value =
+            serde_json::from_str(req.body.as_deref().unwrap()).expect("Test: JSON body");
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "settings": {"theme": "dark", "lang": "en", "notify": {"email": false}},
+                "owners": [{"id": 1, "role": "admin"}]
+            })
+        );
+    }
+
+    #[test]
+    fn test_collection_extension_methods() {
+        let yaml = r#"
+requests:
+  - name: purge
+    method: PURGE
+    url: https://cdn.example.com/app.js
+  - name: props
+    method: PROPFIND
+    url: https://dav.example.com/files/
+    headers:
+      Depth: "1"
+    body:
+      raw: <propfind xmlns="DAV:"><allprop/></propfind>
+      content_type: application/xml
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let purge =
+            build_collection_request(&coll, "cdn", "purge", &vars, &[]).expect("Test: build purge");
+        assert_eq!(purge.method.as_str(), "PURGE");
+        assert_eq!(purge.body, None);
+        let purge = build_collection_request(&coll, "cdn", "purge", &vars, &["x=1".to_string()])
+            .expect("Test: build purge with data");
+        assert_eq!(purge.body.as_deref(), Some(r#"{"x":1}"#));
+        let props =
+            build_collection_request(&coll, "dav", "props", &vars, &[]).expect("Test: build props");
+        assert_eq!(props.method.as_str(), "PROPFIND");
+        assert_eq!(
+            props.body.as_deref(),
+            Some(r#"<propfind xmlns="DAV:"><allprop/></propfind>"#)
+        );
+        assert_eq!(props.headers.get("depth").unwrap(), "1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pre_hooks_set_variables_from_the_built_request() {
+        let yaml = r#"
+variables:
+  key: k1
+requests:
+  - name: signed
+    method: POST
+    url: https://api.example.com/orders
+    pre: 'echo "signature=$WAVE_VAR_key-$(wc -c | tr -d " ")"'
+    headers:
+      X-Signature: ${signature}
+    body:
+      json:
+        item: book
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let req = build_collection_request(&coll, "api", "signed", &vars, &[])
+            .expect("Test: build signed");
+        assert_eq!(req.body.as_deref(), Some(r#"{"item":"book"}"#));
+        assert_eq!(req.headers.get("x-signature").unwrap(), "k1-15");
+        // The hook signs the body with CLI data merged in
+        let req = build_collection_request(&coll, "api", "signed", &vars, &["qty=2".to_string()])
+            .expect("Test: build signed with data");
+        assert_eq!(req.headers.get("x-signature").unwrap(), "k1-23");
+
+        let failing = yaml.replace("pre: 'echo", "pre: 'exit 1; echo");
+        let coll: Collection = serde_yaml::from_str(&failing).expect("Test: parse collection");
+        let err = build_collection_request(&coll, "api", "signed", &vars, &[]).unwrap_err();
+        assert!(matches!(
+            err,
+            WaveError::Collection(CollectionError::HookFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_collection_text_raw_and_file_bodies() {
+        let yaml = r#"
+requests:
+  - name: note
+    method: POST
+    url: https://api.example.com/notes
+    body:
+      text: hello
+  - name: xml
+    method: POST
+    url: https://api.example.com/users
+    headers:
+      content-type: text/plain
+    body:
+      raw: <user/>
+      content_type: application/xml
+  - name: avatar
+    method: PUT
+    url: https://api.example.com/avatar
+    body:
+      file: ./avatar.png
+      content_type: image/png
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let build = |name: &str, params: &[String]| {
+            build_collection_request(&coll, "api", name, &vars, params)
+        };
+        let note = build("note", &[]).expect("Test: build note");
+        assert_eq!(note.body.as_deref(), Some("hello"));
+        assert_eq!(note.headers.get("content-type").unwrap(), "text/plain");
+        let xml = build("xml", &[]).expect("Test: build xml");
+        assert_eq!(xml.body.as_deref(), Some("<user/>"));
+        assert_eq!(xml.headers.get("content-type").unwrap(), "application/xml");
+        let xml = build("xml", &["content-type:text/xml".to_string()]).expect("Test: build xml");
+        assert_eq!(xml.headers.get("content-type").unwrap(), "text/xml");
+        let avatar = build("avatar", &[]).expect("Test: build avatar");
+        assert_eq!(avatar.body_file, Some(PathBuf::from("./avatar.png")));
+        assert_eq!(avatar.headers.get("content-type").unwrap(), "image/png");
+        assert!(matches!(
+            build("note", &["x=1".to_string()]),
+            Err(WaveError::Cli(CliError::ConflictingArguments(_)))
+        ));
+    }
+
+    #[test]
+    fn test_collection_headers_layer_under_request_and_cli_headers() {
+        let yaml = r#"
+variables:
+  team: payments
+headers:
+  Accept: application/json
+  X-Team: ${team}
+  X-Trace: collection
+requests:
+  - name: list
+    method: GET
+    url: https://api.example.com/items
+    headers:
+      x-trace: request
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let req = build_collection_request(&coll, "api", "list", &vars, &[])
+            .expect("Test: build request");
+        assert_eq!(req.headers["x-team"], "payments");
+        assert_eq!(req.headers["x-trace"], "request");
+        assert_eq!(req.headers.get_all("x-trace").iter().count(), 1);
+
+        let req =
+            build_collection_request(&coll, "api", "list", &vars, &["Accept:text/csv".into()])
+                .expect("Test: build request");
+        assert_eq!(req.headers["accept"], "text/csv");
+
+        // Config headers only fill in what the collection leaves unset
+        let options = RequestOptions {
+            default_headers: HashMap::from([
+                ("accept".to_string(), "*/*".to_string()),
+                ("User-Agent".to_string(), "wave-test".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let req = options.apply(
+            build_collection_request(&coll, "api", "list", &vars, &[]).expect("Test: build"),
+        );
+        assert_eq!(req.headers["accept"], "application/json");
+        assert_eq!(req.headers["user-agent"], "wave-test");
+    }
+
+    #[test]
+    fn test_resolve_collection_request_uses_captured_vars() {
+        let yaml = r#"
+variables:
+  token: placeholder
+  user: alice
+requests:
+  - name: me
+    method: GET
+    url: https://example.com/${user}
+    headers:
+      Authorization: Bearer ${token}
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let mut captured = HashMap::from([
+            ("token".to_string(), "captured".to_string()),
+            ("user".to_string(), "bob".to_string()),
+        ]);
+        let vars = VariableOptions {
+            var: vec!["user=carol".to_string()],
+            no_input: true,
+            ..Default::default()
+        };
+
+        let resolved = resolve_collection_request(&coll, "api", "me", &vars, &mut captured)
+            .expect("Test: resolve request");
+        assert_eq!(resolved.url, "https://example.com/carol");
+        assert_eq!(
+            resolved.headers.unwrap()["Authorization"],
+            "Bearer captured"
+        );
+
+        // A captured value is sent as it is: a response cannot read the
+        // environment or other variables through `${...}`
+        std::env::set_var("WAVE_CAPTURED_SECRET_TEST", "hunter2");
+        captured.insert(
+            "token".to_string(),
+            "${env:WAVE_CAPTURED_SECRET_TEST}".to_string(),
+        );
+        let resolved = resolve_collection_request(&coll, "api", "me", &vars, &mut captured)
+            .expect("Test: resolve request");
+        std::env::remove_var("WAVE_CAPTURED_SECRET_TEST");
+        assert_eq!(
+            resolved.headers.unwrap()["Authorization"],
+            "Bearer ${env:WAVE_CAPTURED_SECRET_TEST}"
+        );
+    }
+
+    #[test]
+    fn test_secret_names_are_masked() {
+        for name in ["api_token", "PASSWORD", "clientSecret"] {
+            assert!(is_secret_name(name), "{name}");
+        }
+        for name in ["user_id", "base_url"] {
+            assert!(!is_secret_name(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_parse_no_input_flag() {
+        let cli = Cli::try_parse_from(["wave", "run", "api", "--no-input"]).expect("Test: parse");
+        let Command::Run { vars, .. } = cli.command else {
+            panic!("Expected run command");
+        };
+        assert!(vars.no_input);
+    }
+
+    #[test]
+    fn test_parse_proxy_flags() {
+        let cli = Cli::try_parse_from([
+            "wave",
+            "get",
+            "--proxy",
+            "http://proxy.local:3128",
+            "--proxy-user",
+            "alice:secret",
+            "example.com",
+        ])
+        .expect("Test: parse proxy flags");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        assert_eq!(options.proxy.as_deref(), Some("http://proxy.local:3128"));
+        assert_eq!(options.proxy_user.as_deref(), Some("alice:secret"));
+
+        assert!(
+            Cli::try_parse_from(["wave", "get", "--proxy-user", "a:b", "example.com"]).is_err()
+        );
+        assert!(Cli::try_parse_from([
+            "wave",
+            "get",
+            "--proxy",
+            "http://proxy.local",
+            "--no-proxy",
+            "example.com"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_collection_timeouts_fill_unset_cli_options() {
+        let cli = Cli::try_parse_from(["wave", "get", "--connect-timeout", "2", "example.com"])
+            .expect("Test: parse --connect-timeout");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let timeouts = Timeouts {
+            total: Some(60),
+            connect: Some(10),
+            read: Some(20),
+        };
+
+        let merged = options.with_collection_timeouts(Some(&timeouts));
+        assert_eq!(merged.connect_timeout, Some(2));
+        assert_eq!(merged.read_timeout, Some(20));
+        assert_eq!(merged.timeout, Some(60));
+        assert!(merged.backend().is_ok());
+
+        assert!(
+            Cli::try_parse_from(["wave", "get", "--read-timeout", "0", "example.com"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_collection_tls_fills_unset_cli_options() {
+        let cli = Cli::try_parse_from([
+            "wave",
+            "get",
+            "--cacert",
+            "cli-ca.pem",
+            "--cert",
+            "client.pem",
+            "--key",
+            "client.key",
+            "example.com",
+        ])
+        .expect("Test: parse TLS flags");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let collection_tls = TlsOptions {
+            insecure: true,
+            cacert: Some(PathBuf::from("collection-ca.pem")),
+            ..Default::default()
+        };
+
+        let merged = options.with_collection_tls(Some(&collection_tls));
+        assert!(merged.insecure);
+        assert_eq!(merged.cacert, Some(PathBuf::from("cli-ca.pem")));
+        assert_eq!(merged.cert, Some(PathBuf::from("client.pem")));
+        assert_eq!(merged.key, Some(PathBuf::from("client.key")));
+
+        assert!(
+            Cli::try_parse_from(["wave", "get", "--key", "client.key", "example.com"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_config_fills_unset_options() {
+        let config = Config::parse(
+            "timeout = 30\nproxy = \"http://proxy.local\"\n[headers]\nUser-Agent = \"wave-test\"\nAccept = \"text/plain\"\n",
+        )
+        .expect("Test: parse config");
+        let cli = Cli::try_parse_from(["wave", "get", "--timeout", "5", "example.com"])
+            .expect("Test: parse get");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+
+        let options = options.with_config(&config);
+        assert_eq!(options.timeout, Some(5));
+        assert_eq!(options.proxy.as_deref(), Some("http://proxy.local"));
+
+        let req = HttpRequest::builder("https://example.com", Method::GET)
+            .header("Accept", "application/json")
+            .build();
+        let req = options.apply(req);
+        assert_eq!(req.headers["user-agent"], "wave-test");
+        assert_eq!(req.headers["accept"], "application/json");
+    }
+
+    #[test]
+    fn test_fail_flag_rejects_error_status() {
+        let cli =
+            Cli::try_parse_from(["wave", "get", "--fail", "example.com"]).expect("Test: parse get");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let not_found = HttpResponse::new(404, HeaderMap::new(), "");
+        let ok = HttpResponse::new(200, HeaderMap::new(), "");
+
+        assert!(matches!(
+            options.check_status(Some(&not_found)),
+            Err(WaveError::Http(HttpError::Status(404)))
+        ));
+        assert!(options.check_status(Some(&ok)).is_ok());
+        assert!(options.check_status(None).is_ok());
+        assert!(RequestOptions::default()
+            .check_status(Some(&not_found))
+            .is_ok());
+
+        let config = Config::parse("fail = true\n").expect("Test: parse config");
+        assert!(RequestOptions::default().with_config(&config).fail);
+    }
+
+    #[test]
+    fn test_filter_response() {
+        let cli = Cli::try_parse_from(["wave", "get", "--filter", "$.data[*].id", "example.com"])
+            .expect("Test: parse get");
+        let Command::Get { options, .. } = cli.command else {
+            panic!("Expected get command");
+        };
+        let query = options.filter.expect("Test: filter parsed");
+        let resp = HttpResponse::new(200, HeaderMap::new(), r#"{"data":[{"id":1},{"id":2}]}"#);
+        assert_eq!(filter_response(&resp, &query).unwrap().body, "[1,2]");
+
+        let text = HttpResponse::new(200, HeaderMap::new(), "plain");
+        assert!(filter_response(&text, &query).is_err());
+        assert!(Cli::try_parse_from(["wave", "get", "--filter", "$.[", "example.com"]).is_err());
+    }
+
+    #[test]
+    fn test_output_mode_flags() {
+        let mode = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).expect("Test: parse get");
+            let Command::Get { options, .. } = cli.command else {
+                panic!("Expected get command");
+            };
+            options.output_mode()
+        };
+        assert_eq!(mode(&["wave", "get", "example.com"]), OutputMode::Full);
+        assert_eq!(
+            mode(&["wave", "get", "--headers-only", "example.com"]),
+            OutputMode::HeadersOnly
+        );
+        assert_eq!(
+            mode(&["wave", "get", "--body-only", "example.com"]),
+            OutputMode::BodyOnly
+        );
+        assert_eq!(
+            mode(&["wave", "get", "--raw", "example.com"]),
+            OutputMode::Raw
+        );
+        assert!(
+            Cli::try_parse_from(["wave", "get", "--raw", "--body-only", "example.com"]).is_err()
+        );
+        assert!(Cli::try_parse_from([
+            "wave",
+            "get",
+            "--headers-only",
+            "--body-only",
+            "example.com"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_color_flags() {
+        let options = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).expect("Test: parse get");
+            let Command::Get { options, .. } = cli.command else {
+                panic!("Expected get command");
+            };
+            options
+        };
+        assert!(options(&["wave", "get", "--color", "always", "example.com"]).use_color());
+        assert!(!options(&["wave", "get", "--color", "never", "example.com"]).use_color());
+        assert!(!options(&["wave", "get", "--no-color", "example.com"]).use_color());
+        assert!(
+            !options(&["wave", "get", "--no-color", "example.com"])
+                .print_options(false)
+                .color
+        );
+
+        let config = Config::parse("color = \"always\"\n").expect("Test: parse config");
+        let configured = options(&["wave", "get", "example.com"]).with_config(&config);
+        assert!(configured.use_color());
+        let overridden =
+            options(&["wave", "get", "--no-color", "example.com"]).with_config(&config);
+        assert!(!overridden.use_color());
+    }
+
+    #[test]
+    fn test_format_flag() {
+        let format = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).expect("Test: parse get");
+            let Command::Get { options, .. } = cli.command else {
+                panic!("Expected get command");
+            };
+            options.format
+        };
+        assert_eq!(
+            format(&["wave", "get", "example.com"]),
+            OutputFormat::Pretty
+        );
+        assert_eq!(
+            format(&["wave", "get", "--format", "json", "example.com"]),
+            OutputFormat::Json
+        );
+        assert!(Cli::try_parse_from(["wave", "get", "--format", "xml", "example.com"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_run_command() {
+        let cli = Cli::try_parse_from(["wave", "run", "api", "one", "two", "--env", "staging"])
+            .expect("Test: parse run");
+        let Command::Run {
+            collection,
+            requests,
+            vars,
+            ..
+        } = cli.command
+        else {
+            panic!("Expected run command");
+        };
+        assert_eq!(collection, "api");
+        assert_eq!(requests, vec!["one", "two"]);
+        assert_eq!(vars.env.as_deref(), Some("staging"));
+    }
+
+    #[test]
+    fn test_graphql_body_envelope() {
+        let body = graphql_body(
+            "query U($id: ID!) { user(id: $id) { name } }",
+            Some("U"),
+            Some(r#"{"id": "7", "first": 3}"#),
+            &[("id".to_string(), "42".to_string())],
+        )
+        .unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "query": "query U($id: ID!) { user(id: $id) { name } }",
+                "operationName": "U",
+                "variables": {"id": 42, "first": 3}
+            })
+        );
+
+        let bare = graphql_body("{ users { id } }", None, None, &[]).unwrap();
+        assert_eq!(bare, serde_json::json!({"query": "{ users { id } }"}));
+    }
+
+    #[test]
+    fn test_graphql_body_reads_files_and_rejects_bad_input() {
+        let path =
+            std::env::temp_dir().join(format!("wave_graphql_{}.graphql", std::process::id()));
+        std::fs::write(&path, "{ me { id } }").unwrap();
+        let body = graphql_body(&format!("@{}", path.display()), None, None, &[]).unwrap();
+        assert_eq!(body["query"], "{ me { id } }");
+        let _ = std::fs::remove_file(&path);
+
+        assert!(matches!(
+            graphql_body("  ", None, None, &[]),
+            Err(WaveError::Cli(CliError::MissingArguments(_)))
+        ));
+        for variables in ["[1, 2]", "{not json"] {
+            let err = graphql_body("{ a }", None, Some(variables), &[]).unwrap_err();
+            assert!(err.to_string().contains("--variables"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_parse_graphql_command() {
+        let cli = Cli::try_parse_from([
+            "wave",
+            "graphql",
+            "--variables",
+            "{}",
+            "https://api.example.com/graphql",
+            "{ users { id } }",
+            "limit=5",
+        ])
+        .expect("Test: parse graphql");
+        let Command::Graphql {
+            query,
+            params,
+            graphql,
+            ..
+        } = cli.command
+        else {
+            panic!("Expected graphql command");
+        };
+        assert_eq!(query, "{ users { id } }");
+        assert_eq!(params, vec!["limit=5"]);
+        assert_eq!(graphql.variables.as_deref(), Some("{}"));
+    }
+
+    #[test]
+    fn test_grpc_request_message_uses_field_types() {
+        let path = std::env::temp_dir().join(format!("wave_grpc_{}.proto", std::process::id()));
+        std::fs::write(
+            &path,
+            "syntax = \"proto3\";\nmessage User { string id = 1; int32 age = 2; bool admin = 3; }",
+        )
+        .unwrap();
+        let descriptors =
+            grpc::Descriptors::from_proto_files(std::slice::from_ref(&path), &[]).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let fields = [
+            ("id".to_string(), "42".to_string()),
+            ("admin".to_string(), "true".to_string()),
+        ];
+        let message = grpc_request_message(
+            &descriptors,
+            "User",
+            Some(r#"{"id": "1", "age": 30}"#),
+            &fields,
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            serde_json::json!({"id": "42", "age": 30, "admin": true})
+        );
+        let err = grpc_request_message(&descriptors, "User", Some("[1]"), &[]).unwrap_err();
+        assert!(err.to_string().contains("--data"), "{err}");
+    }
+
+    #[test]
+    fn test_parse_grpc_command() {
+        let cli = Cli::try_parse_from([
+            "wave",
+            "grpc",
+            "--proto",
+            "api.proto",
+            "localhost:50051",
+            "helloworld.Greeter/SayHello",
+            "name=alice",
+            "x-request-id:7",
+        ])
+        .expect("Test: parse grpc");
+        let Command::Grpc {
+            address,
+            method,
+            params,
+            grpc,
+            ..
+        } = cli.command
+        else {
+            panic!("Expected grpc command");
+        };
+        assert_eq!(address, "localhost:50051");
+        assert_eq!(method.as_deref(), Some("helloworld.Greeter/SayHello"));
+        assert_eq!(params, vec!["name=alice", "x-request-id:7"]);
+        assert_eq!(grpc.proto, vec![PathBuf::from("api.proto")]);
+
+        let cli = Cli::try_parse_from(["wave", "grpc", "localhost:50051"]).unwrap();
+        assert!(matches!(cli.command, Command::Grpc { method: None, .. }));
+    }
+
+    #[test]
+    fn test_select_run_requests() {
+        let yaml = r#"
+requests:
+  - name: first
+    method: GET
+    url: https://example.com/1
+  - name: second
+    method: GET
+    url: https://example.com/2
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+
+        let all = select_run_requests(&coll, "api", &[], &[]).expect("Test: select all");
+        assert_eq!(all, vec!["first", "second"]);
 
-    if let Some(obj) = result.as_object_mut() {
-        for (key, value) in cli_body {
-            // CLI parameters override collection values, with type inference
-            let json_value = parse_cli_value_to_json(value);
-            obj.insert(key.clone(), json_value);
-        }
-    } else if !cli_body.is_empty() {
-        // If collection doesn't have JSON body but CLI has params, create new object
-        let mut obj = serde_json::Map::new();
-        for (key, value) in cli_body {
-            let json_value = parse_cli_value_to_json(value);
-            obj.insert(key.clone(), json_value);
-        }
-        result = serde_json::Value::Object(obj);
+        let subset = select_run_requests(&coll, "api", &["second".to_string()], &[])
+            .expect("Test: select subset");
+        assert_eq!(subset, vec!["second"]);
+
+        let missing = select_run_requests(&coll, "api", &["third".to_string()], &[]);
+        assert!(matches!(
+            missing,
+            Err(WaveError::Collection(
+                CollectionError::RequestNotFound { .. }
+            ))
+        ));
     }
 
-    result
-}
+    #[test]
+    fn test_select_run_requests_adds_prerequisites() {
+        let yaml = r#"
+requests:
+  - name: me
+    method: GET
+    url: https://example.com/me
+    depends_on: [login]
+    tags: [smoke]
+  - name: seed
+    method: POST
+    url: https://example.com/seed
+  - name: login
+    method: POST
+    url: https://example.com/login
+    depends_on: [seed]
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let smoke = select_run_requests(&coll, "api", &[], &["smoke".to_string()])
+            .expect("Test: select smoke");
+        assert_eq!(smoke, vec!["seed", "login", "me"]);
+        let all = select_run_requests(&coll, "api", &[], &[]).expect("Test: select all");
+        assert_eq!(all, vec!["seed", "login", "me"]);
+    }
 
-/// Merge headers and body data, with CLI params overriding collection params
-fn merge_headers_and_body(
-    collection_headers: &[(String, String)],
-    collection_body: &[(String, String)],
-    cli_headers: &[(String, String)],
-    cli_body: &[(String, String)],
-) -> (Headers, FormData) {
-    let mut headers = collection_headers.to_vec();
-    let mut body = collection_body.to_vec();
+    #[test]
+    fn test_select_run_requests_by_tag() {
+        let yaml = r#"
+requests:
+  - name: login
+    method: POST
+    url: https://example.com/login
+    tags: [smoke, auth]
+  - name: report
+    method: GET
+    url: https://example.com/report
+  - name: health
+    method: GET
+    url: https://example.com/health
+    tags: [smoke]
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
 
-    // Override headers with CLI values
-    for (cli_key, cli_value) in cli_headers {
-        if let Some(pos) = headers.iter().position(|(k, _)| k == cli_key) {
-            headers[pos].1 = cli_value.clone();
-        } else {
-            headers.push((cli_key.clone(), cli_value.clone()));
-        }
-    }
+        let smoke = select_run_requests(&coll, "api", &[], &tags(&["smoke"])).expect("Test: smoke");
+        assert_eq!(smoke, vec!["login", "health"]);
+        let named = select_run_requests(
+            &coll,
+            "api",
+            &["health".to_string(), "report".to_string()],
+            &tags(&["smoke"]),
+        )
+        .expect("Test: named and tagged");
+        assert_eq!(named, vec!["health"]);
+        assert!(matches!(
+            select_run_requests(&coll, "api", &[], &tags(&["slow"])),
+            Err(WaveError::Collection(
+                CollectionError::NoTaggedRequests { .. }
+            ))
+        ));
 
-    // Override body with CLI values
-    for (cli_key, cli_value) in cli_body {
-        if let Some(pos) = body.iter().position(|(k, _)| k == cli_key) {
-            body[pos].1 = cli_value.clone();
-        } else {
-            body.push((cli_key.clone(), cli_value.clone()));
-        }
+        let listed = format_request_list("api", &coll, &tags(&["auth"])).expect("Test: list");
+        assert_eq!(
+            listed,
+            "api\n  login  POST  https://example.com/login  [smoke, auth]"
+        );
+        let all = format_request_list("api", &coll, &[]).expect("Test: list all");
+        assert!(all.contains("\n  report  GET   https://example.com/report\n"));
+        assert_eq!(format_request_list("api", &coll, &tags(&["slow"])), None);
     }
 
-    (headers, body)
-}
+    #[test]
+    fn test_format_variable_report() {
+        let yaml = r#"
+variables:
+  base_url: https://api.example.com
+environments:
+  staging:
+    region: eu
+headers:
+  X-Client: ${client}
+requests:
+  - name: login
+    method: POST
+    url: ${base_url}/login
+    body:
+      json:
+        user: ${user}
+        password: ${env:WAVE_TEST_NEVER_SET:-dev}
+    capture:
+      token: $.token
+  - name: me
+    method: GET
+    url: ${base_url}/me/${uuid}
+    headers:
+      Authorization: Bearer ${token}
+      X-Region: ${region:-us}
+"#;
+        let mut coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        coll.dotenv.insert("client".to_string(), "cli".to_string());
+        let report = format_variable_report("api", &coll, &VariableOptions::default())
+            .expect("Test: report");
+        assert_eq!(
+            report,
+            "api
+  base_url                 variables            login (url), me (url)
+  client                   .env                 login (headers), me (headers)
+  env:WAVE_TEST_NEVER_SET  default              login (body)
+  region                   default              me (headers)
+  token                    captured by 'login'  me (headers)
+  user                     missing              login (body)
+  uuid                     built-in             me (url)
 
-/// Parse form data string to key-value pairs
-fn parse_form_to_key_value_pairs(form_str: &str) -> KeyValuePairs {
-    form_str
-        .split('&')
-        .filter_map(|pair| {
-            let mut parts = pair.splitn(2, '=');
-            let k = parts.next()?;
-            let v = parts.next().unwrap_or("");
-            Some((k.to_string(), v.to_string()))
-        })
-        .collect()
-}
+7 variables, 1 missing"
+        );
 
-// Collection request handling
-fn prepare_collection_headers_and_body(
-    resolved: &collection::Request,
-) -> (Headers, Option<serde_json::Value>, bool) {
-    let mut headers: Headers = resolved
-        .headers
-        .clone()
-        .unwrap_or_default()
-        .into_iter()
-        .collect();
-    match &resolved.body {
-        Some(collection::Body::Json(map)) => {
-            let json_obj = serde_json::Value::Object(
-                map.iter()
-                    .map(|(k, v)| (k.clone(), collection::yaml_to_json(v)))
-                    .collect(),
-            );
-            if !headers
-                .iter()
-                .any(|(k, _)| k.eq_ignore_ascii_case("content-type"))
-            {
-                headers.push(("Content-Type".to_string(), "application/json".to_string()));
-            }
-            (headers, Some(json_obj), false)
-        }
-        Some(collection::Body::Form(map)) => {
-            let form_data: FormData = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
-            let mut header_map = ::http::HeaderMap::new();
-            let body = http::RequestBody::form(form_data);
-            let form_str = body.serialize(&mut header_map);
+        let vars = VariableOptions {
+            env: Some("staging".to_string()),
+            var: vec!["user=ann".to_string()],
+            ..Default::default()
+        };
+        let report = format_variable_report("api", &coll, &vars).expect("Test: staging report");
+        assert!(report.starts_with("api (environment 'staging')\n"));
+        assert!(report.contains("\n  region                   environment 'staging'  me (headers)"));
+        assert!(report.contains("\n  user                     --var"));
+        assert!(report.ends_with("7 variables, 0 missing"));
 
-            // Convert HeaderMap back to Vec for compatibility
-            let form_headers: Headers = header_map
-                .iter()
-                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-                .collect();
-            headers.extend(form_headers);
-            // For form data, we return the serialized string as a JSON string value
-            (headers, Some(serde_json::Value::String(form_str)), true)
-        }
-        None => (headers, None, false),
-    }
-}
+        // Variables referred to by the values of other variables count too
+        let nested: Collection = serde_yaml::from_str(
+            "variables:\n  api: http://${host}/v1\n  users: ${api}/users\nrequests:\n  - name: list\n    method: GET\n    url: ${users}\n",
+        )
+        .expect("Test: parse collection");
+        let report = format_variable_report("api", &nested, &VariableOptions::default())
+            .expect("Test: nested report");
+        assert_eq!(
+            report,
+            "api
+  api    variables  variable 'users'
+  host   missing    variable 'api'
+  users  variables  list (url)
 
-pub async fn handle_collection(
-    collection_name: &str,
-    request_name: &str,
-    verbose: bool,
-    var_overrides: &[String],
-    params: &[String],
-) -> Result<(), WaveError> {
-    let yaml_path = format!(".wave/{collection_name}.yaml");
-    let yml_path = format!(".wave/{collection_name}.yml");
-    let coll_result =
-        collection::load_collection(&yaml_path).or_else(|_| collection::load_collection(&yml_path));
-
-    match coll_result {
-        Ok(coll) => {
-            let mut file_vars = coll.variables.unwrap_or_default();
-            for kv in var_overrides {
-                let (k, v) = kv.split_once('=').ok_or_else(|| {
-                    WaveError::Cli(CliError::InvalidVarOverride(format!(
-                        "'{kv}' must be in KEY=VALUE format"
-                    )))
-                })?;
-                let key = k.trim();
-                if key.is_empty() {
-                    return Err(WaveError::Cli(CliError::InvalidVarOverride(format!(
-                        "'{kv}' has an empty key"
-                    ))));
-                }
-                file_vars.insert(key.to_string(), v.to_string());
-            }
-            match coll.requests.iter().find(|r| r.name == request_name) {
-                Some(req) => match collection::resolve_request_vars(req, &file_vars) {
-                    Ok(resolved) => {
-                        let spinner_msg = format!("{} {}", resolved.method, resolved.url);
-                        // Parse CLI params for potential override
-                        let (cli_headers, cli_body) = parse_params(params);
-                        match resolved.method {
-                            Method::GET => {
-                                let collection_headers: Headers =
-                                    resolved.headers.unwrap_or_default().into_iter().collect();
-                                let (headers, _) = merge_headers_and_body(
-                                    &collection_headers,
-                                    &[],
-                                    &cli_headers,
-                                    &[],
-                                );
-                                let req = HttpRequest::new(
-                                    &resolved.url,
-                                    Method::GET,
-                                    None,
-                                    headers_to_map(headers),
-                                );
-                                execute_request_with_spinner(&req, &spinner_msg, verbose).await?;
-                            }
-                            Method::DELETE => {
-                                let collection_headers: Headers =
-                                    resolved.headers.unwrap_or_default().into_iter().collect();
-                                let (headers, _) = merge_headers_and_body(
-                                    &collection_headers,
-                                    &[],
-                                    &cli_headers,
-                                    &[],
-                                );
-                                let req = HttpRequest::new(
-                                    &resolved.url,
-                                    Method::DELETE,
-                                    None,
-                                    headers_to_map(headers),
-                                );
-                                execute_request_with_spinner(&req, &spinner_msg, verbose).await?;
-                            }
-                            Method::POST | Method::PUT | Method::PATCH => {
-                                let (collection_headers, collection_json, is_form) =
-                                    prepare_collection_headers_and_body(&resolved);
-
-                                // Merge headers (CLI overrides collection)
-                                let (merged_headers, _) = merge_headers_and_body(
-                                    &collection_headers,
-                                    &[],
-                                    &cli_headers,
-                                    &[],
-                                );
-
-                                // Handle body based on type
-                                let final_body = if is_form {
-                                    // For form data, extract the string from JSON and merge with CLI params
-                                    let form_str = collection_json
-                                        .as_ref()
-                                        .and_then(|v| v.as_str())
-                                        .unwrap_or("")
-                                        .to_string();
-                                    let collection_body_data = if form_str.is_empty() {
-                                        vec![]
-                                    } else {
-                                        parse_form_to_key_value_pairs(&form_str)
-                                    };
-                                    let (_, merged_body_data) = merge_headers_and_body(
-                                        &[],
-                                        &collection_body_data,
-                                        &[],
-                                        &cli_body,
-                                    );
-                                    merged_body_data
-                                        .iter()
-                                        .map(|(k, v)| format!("{k}={v}"))
-                                        .collect::<Vec<_>>()
-                                        .join("&")
-                                } else {
-                                    // JSON encoding - use new merge function that preserves types
-                                    let merged_json =
-                                        merge_json_with_cli_params(collection_json, &cli_body);
-                                    serde_json::to_string(&merged_json)
-                                        .unwrap_or_else(|_| "{}".to_string())
-                                };
-
-                                let req = HttpRequest::new(
-                                    &resolved.url,
-                                    resolved.method.clone(),
-                                    Some(final_body),
-                                    headers_to_map(merged_headers),
-                                );
-                                execute_request_with_spinner(&req, &spinner_msg, verbose).await?;
-                            }
-                            _ => {
-                                return Err(WaveError::Cli(CliError::UnsupportedMethod(
-                                    resolved.method.to_string(),
-                                )))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        return Err(WaveError::Collection(CollectionError::VariableResolution(
-                            e.to_string(),
-                        )))
-                    }
-                },
-                None => {
-                    return Err(WaveError::Collection(CollectionError::RequestNotFound {
-                        collection: collection_name.to_string(),
-                        request: request_name.to_string(),
-                    }));
-                }
-            }
-        }
-        Err(_e) => {
-            println!("{_e}");
-            return Err(WaveError::Collection(CollectionError::FileNotFound(
-                format!("{collection_name}.yaml or {collection_name}.yml"),
-            )));
-        }
+3 variables, 1 missing"
+        );
+
+        let unknown = VariableOptions {
+            env: Some("prod".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            format_variable_report("api", &coll, &unknown),
+            Err(WaveError::Collection(
+                CollectionError::EnvironmentNotFound { .. }
+            ))
+        ));
     }
-    Ok(())
-}
 
-#[cfg(test)]
-mod tests {
-    use core::f64;
+    #[test]
+    fn test_format_description() {
+        let yaml = r#"
+description: |
+  Accounts API.
+base_url: https://api.example.com
+requests:
+  - name: create-user
+    description: Creates a user and returns its id
+    method: POST
+    url: /users
+    query:
+      notify: ${notify:-false}
+    headers:
+      Authorization: Bearer ${token}
+    body:
+      json:
+        name: ${name}
+        roles: [admin]
+    tags: [users]
+  - name: upload
+    method: POST
+    url: /files
+    body:
+      multipart:
+        title: ${title}
+        file:
+          file: ./a.png
+"#;
+        let coll: Collection = serde_yaml::from_str(yaml).expect("Test: parse collection");
+        let vars = VariableOptions {
+            var: vec!["token=secret".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            format_description("api", &coll, None, &vars).expect("Test: collection"),
+            "api
+  Accounts API.
 
-    use super::*;
+  create-user  POST  Creates a user and returns its id
+  upload       POST"
+        );
+        assert_eq!(
+            format_description("api", &coll, Some("create-user"), &vars).expect("Test: request"),
+            r#"create-user  POST https://api.example.com/users?notify=${notify:-false}
+  Creates a user and returns its id
+
+headers
+  Authorization: Bearer ${token}
+
+variables
+  notify  default  query
+  token   --var    headers
+  name    missing  body
+
+body (json)
+  {
+    "name": "${name}",
+    "roles": [
+      "admin"
+    ]
+  }
+
+tags: users"#
+        );
+        let upload = format_description("api", &coll, Some("upload"), &vars).expect("Test: upload");
+        assert!(upload.ends_with("body (multipart)\n  file=@./a.png\n  title=${title}"));
+        assert!(matches!(
+            format_description("api", &coll, Some("nope"), &vars),
+            Err(WaveError::Collection(
+                CollectionError::RequestNotFound { .. }
+            ))
+        ));
+    }
 
     #[test]
-    fn test_parse_params_json_body() {
+    fn test_extract_file_params() {
         let params = vec![
-            "name=joe".to_string(),
-            "age=42".to_string(),
-            "Authorization:Bearer123".to_string(),
+            "avatar@=./photo.png".to_string(),
+            "name=alice".to_string(),
+            "X-Test:1".to_string(),
+            "email=a@=b".to_string(),
         ];
-        let (headers, data) = parse_params(&params);
-        assert_eq!(
-            headers,
-            vec![("Authorization".to_string(), "Bearer123".to_string())]
-        );
+        let (remaining, files) = extract_file_params(&params);
         assert_eq!(
-            data,
+            remaining,
             vec![
-                ("name".to_string(), "joe".to_string()),
-                ("age".to_string(), "42".to_string())
+                "name=alice".to_string(),
+                "X-Test:1".to_string(),
+                "email=a@=b".to_string()
             ]
         );
+        assert_eq!(
+            files,
+            vec![MultipartPart::File {
+                name: "avatar".to_string(),
+                path: PathBuf::from("./photo.png"),
+            }]
+        );
     }
 
     #[test]
-    fn test_parse_params_form_flag_ignored() {
-        let params = vec![
-            "--form".to_string(),
-            "foo=bar".to_string(),
-            "baz=qux".to_string(),
-            "X-Test:1".to_string(),
-        ];
-        let (headers, data) = parse_params(&params);
-        assert_eq!(headers, vec![("X-Test".to_string(), "1".to_string())]);
+    fn test_build_request_body_multipart() {
+        let files = vec![MultipartPart::File {
+            name: "avatar".to_string(),
+            path: PathBuf::from("photo.png"),
+        }];
+        let body = build_request_body(
+            &BodyOptions::default(),
+            vec![("name".to_string(), "alice".to_string())],
+            files,
+        )
+        .unwrap();
+        let RequestBody::Multipart(parts) = body else {
+            panic!("Expected multipart body");
+        };
         assert_eq!(
-            data,
+            parts,
             vec![
-                ("foo".to_string(), "bar".to_string()),
-                ("baz".to_string(), "qux".to_string())
+                MultipartPart::Text {
+                    name: "name".to_string(),
+                    value: "alice".to_string(),
+                },
+                MultipartPart::File {
+                    name: "avatar".to_string(),
+                    path: PathBuf::from("photo.png"),
+                },
             ]
         );
+
+        assert!(validate_file_parts(&[MultipartPart::File {
+            name: "missing".to_string(),
+            path: PathBuf::from("/definitely/not/here.png"),
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn test_build_request_body_form_file_fields() {
+        let path = std::env::temp_dir().join(format!("wave_form_notes_{}.txt", std::process::id()));
+        std::fs::write(&path, "hi there").unwrap();
+        let form = BodyOptions {
+            form: true,
+            ..BodyOptions::default()
+        };
+        let data = || vec![("notes".to_string(), format!("@{}", path.display()))];
+        // field=@path sends the file's contents as the value
+        let RequestBody::Form(fields) = build_request_body(&form, data(), Vec::new()).unwrap()
+        else {
+            panic!("Expected form body");
+        };
+        assert_eq!(fields, vec![("notes".to_string(), "hi there".to_string())]);
+        // field@=path attaches the file, turning the --form body into multipart
+        let avatar = MultipartPart::File {
+            name: "avatar".to_string(),
+            path: PathBuf::from("photo.png"),
+        };
+        let body = build_request_body(&form, data(), vec![avatar.clone()]).unwrap();
+        let RequestBody::Multipart(parts) = body else {
+            panic!("Expected multipart body");
+        };
+        let notes = MultipartPart::Text {
+            name: "notes".to_string(),
+            value: "hi there".to_string(),
+        };
+        assert_eq!(parts, vec![notes, avatar]);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
     fn test_validate_url_with_scheme() {
         assert_eq!(
-            validate_url("https://example.com").unwrap(),
+            validate_url("https://example.com", DefaultScheme::Http).unwrap(),
             "https://example.com"
         );
         assert_eq!(
-            validate_url("http://example.com").unwrap(),
+            validate_url("http://example.com", DefaultScheme::Http).unwrap(),
             "http://example.com"
         );
     }
 
     #[test]
     fn test_validate_url_adds_scheme() {
-        assert_eq!(validate_url("example.com").unwrap(), "http://example.com");
         assert_eq!(
-            validate_url("api.example.com").unwrap(),
-            "http://api.example.com"
+            validate_url("example.com", DefaultScheme::Http).unwrap(),
+            "http://example.com"
+        );
+        assert_eq!(
+            validate_url("api.example.com", DefaultScheme::Https).unwrap(),
+            "https://api.example.com"
+        );
+        assert_eq!(
+            validate_url("localhost:8080/users", DefaultScheme::Https).unwrap(),
+            "http://localhost:8080/users"
+        );
+        assert_eq!(
+            validate_url("[::1]:8080", DefaultScheme::Https).unwrap(),
+            "http://[::1]:8080"
         );
     }
 
     #[test]
     fn test_validate_url_rejects_empty() {
-        assert!(validate_url("").is_err());
-        assert!(validate_url("   ").is_err());
+        assert!(validate_url("", DefaultScheme::Http).is_err());
+        assert!(validate_url("   ", DefaultScheme::Http).is_err());
     }
 
     #[test]
     fn test_validate_url_rejects_invalid() {
-        assert!(validate_url("not-a-url").is_err()); // No dot and not localhost
+        assert!(validate_url("not-a-url", DefaultScheme::Http).is_err()); // No dot and not localhost
+    }
+
+    #[test]
+    fn test_validate_url_accepts_single_label_hosts_with_a_scheme() {
+        assert_eq!(
+            validate_url("http://api:8080", DefaultScheme::Https).unwrap(),
+            "http://api:8080"
+        );
+        assert_eq!(
+            validate_url("http://db/health", DefaultScheme::Http).unwrap(),
+            "http://db/health"
+        );
+        assert!(validate_url("api:8080", DefaultScheme::Http).is_err());
+    }
+
+    #[test]
+    fn test_validate_url_accepts_ipv6_and_ports() {
+        assert_eq!(
+            validate_url("[::1]:8080", DefaultScheme::Http).unwrap(),
+            "http://[::1]:8080"
+        );
+        assert_eq!(
+            validate_url("https://[2001:db8::1]/users", DefaultScheme::Http).unwrap(),
+            "https://[2001:db8::1]/users"
+        );
+        assert_eq!(
+            validate_url("127.0.0.1:3000/health?full=1", DefaultScheme::Http).unwrap(),
+            "http://127.0.0.1:3000/health?full=1"
+        );
+    }
+
+    #[test]
+    fn test_validate_url_explains_errors() {
+        let err = validate_url("not-a-url", DefaultScheme::Http).unwrap_err();
+        assert!(matches!(err, WaveError::Parse(ParseError::Url(_))));
+        assert!(err.to_string().contains("host 'not-a-url'"));
+        let err = validate_url("ftp://example.com", DefaultScheme::Http).unwrap_err();
+        assert!(err.to_string().contains("unsupported scheme 'ftp'"));
+        let err = validate_url("localhost:port", DefaultScheme::Http).unwrap_err();
+        assert!(err.to_string().contains("invalid port number"));
     }
 
     #[test]
     fn test_validate_url_accepts_localhost() {
-        assert!(validate_url("localhost").is_ok());
-        assert!(validate_url("localhost:8080").is_ok());
-        assert_eq!(validate_url("localhost").unwrap(), "http://localhost");
+        assert!(validate_url("localhost", DefaultScheme::Http).is_ok());
+        assert!(validate_url("localhost:8080", DefaultScheme::Http).is_ok());
+        assert_eq!(
+            validate_url("localhost", DefaultScheme::Http).unwrap(),
+            "http://localhost"
+        );
         assert_eq!(
-            validate_url("localhost:8080").unwrap(),
+            validate_url("localhost:8080", DefaultScheme::Http).unwrap(),
             "http://localhost:8080"
         );
     }
@@ -806,6 +6136,53 @@ mod tests {
         assert!(validate_params(&params).is_err());
     }
 
+    #[test]
+    fn test_invalid_headers_are_reported_not_dropped() {
+        let (headers, _) = validate_params(&["X(Trace):1".to_string()]).unwrap();
+        let err = headers_to_map(headers).unwrap_err();
+        assert!(matches!(
+            &err,
+            WaveError::Parse(ParseError::Header(msg)) if msg == "invalid header name 'X(Trace)'"
+        ));
+        assert!(err.suggestion().unwrap().contains("X-Api-Key:abc123"));
+
+        let err = headers_to_map(vec![("X-Note".to_string(), "a\nb".to_string())]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid value for header 'X-Note'"));
+        assert_eq!(
+            headers_to_map(vec![("X-Ok".to_string(), "1".to_string())]).unwrap()["x-ok"],
+            "1"
+        );
+    }
+
+    #[test]
+    fn test_plus_colon_params_add_header_values() {
+        let params = ["Accept:a".to_string(), "Accept+:b".to_string()];
+        let (headers, _) = validate_params(&params).unwrap();
+        let map = headers_to_map(headers).unwrap();
+        let accept: Vec<_> = map.get_all("accept").iter().collect();
+        assert_eq!(accept, ["a", "b"]);
+
+        let (headers, _) =
+            validate_params(&["Accept:a".to_string(), "Accept:b".to_string()]).unwrap();
+        assert_eq!(
+            headers_to_map(headers)
+                .unwrap()
+                .get_all("accept")
+                .iter()
+                .count(),
+            1
+        );
+        assert!(validate_params(&["+:a".to_string()]).is_err());
+
+        let collection = vec![("Accept".to_string(), "a".to_string())];
+        let cli = vec![("Accept+".to_string(), "b".to_string())];
+        let map = layer_headers(&collection, &cli).unwrap();
+        let accept: Vec<_> = map.get_all("accept").iter().collect();
+        assert_eq!(accept, ["a", "b"]);
+    }
+
     #[test]
     fn test_validate_params_header_with_space() {
         let params = vec!["Auth orization:Bearer123".to_string()];
@@ -827,24 +6204,63 @@ mod tests {
     #[tokio::test]
     async fn test_error_propagation_integration() {
         // Test that validation errors propagate through the handle functions
-        let result = handle_get("", &[], false, "test").await;
+        let result = handle_get(
+            "",
+            &[],
+            &BodyOptions::default(),
+            false,
+            &RequestOptions::default(),
+            "test",
+        )
+        .await;
+        assert!(result.is_err());
+
+        let result = handle_get(
+            "localhost",
+            &["invalid-param".to_string()],
+            &BodyOptions::default(),
+            false,
+            &RequestOptions::default(),
+            "test",
+        )
+        .await;
+        assert!(result.is_err());
+
+        let result = handle_get(
+            "example.com",
+            &[":empty-key".to_string()],
+            &BodyOptions::default(),
+            false,
+            &RequestOptions::default(),
+            "test",
+        )
+        .await;
         assert!(result.is_err());
 
-        let result = handle_get("localhost", &["invalid-param".to_string()], false, "test").await;
+        let result = handle_head("", &[], false, &RequestOptions::default(), "test").await;
         assert!(result.is_err());
 
-        let result = handle_get("example.com", &[":empty-key".to_string()], false, "test").await;
+        let result = handle_options(
+            "localhost",
+            &["invalid-param".to_string()],
+            false,
+            &RequestOptions::default(),
+            "test",
+        )
+        .await;
         assert!(result.is_err());
     }
 
     #[test]
     fn test_validate_url_edge_cases() {
         // Test various URL edge cases
-        assert!(validate_url("https://").is_err());
-        assert!(validate_url("http://").is_err());
-        assert!(validate_url("ftp://example.com").is_ok()); // We allow any protocol and add http if missing
-        assert!(validate_url("localhost:8080").is_ok()); // localhost is valid
-        assert!(validate_url("192.168.1.1").is_ok()); // IP addresses have dots
+        assert!(validate_url("https://", DefaultScheme::Http).is_err());
+        assert!(validate_url("http://", DefaultScheme::Http).is_err());
+        assert!(validate_url("ftp://example.com", DefaultScheme::Http).is_err()); // Only http and https are sent
+        assert!(validate_url("localhost:8080", DefaultScheme::Http).is_ok()); // localhost is valid
+        assert!(validate_url("192.168.1.1", DefaultScheme::Http).is_ok()); // IP addresses are valid
+        assert!(validate_url("http://exa mple.com", DefaultScheme::Http).is_err());
+        assert!(validate_url("example.com:99999", DefaultScheme::Http).is_err());
     }
 
     #[test]
@@ -863,10 +6279,30 @@ mod tests {
 
         let result = validate_params(&["key:value:more".to_string()]).unwrap();
         assert_eq!(result.0[0].1, "value:more");
+
+        // The first ':' or '=' decides, and ':=' marks raw JSON body data
+        let (headers, data) = validate_params(&[
+            "data=value:with:colons".to_string(),
+            "callback=http://example.com".to_string(),
+            "ids:=[1, 2]".to_string(),
+            "zip==01234".to_string(),
+        ])
+        .unwrap();
+        assert!(headers.is_empty());
+        assert_eq!(
+            data,
+            [
+                ("data", "value:with:colons"),
+                ("callback", "http://example.com"),
+                ("ids", ":=[1, 2]"),
+                ("zip", "=01234"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
     }
 
     #[test]
-    fn test_merge_headers_and_body() {
+    fn test_layer_headers_and_merge_body_data() {
         let collection_headers = vec![
             ("Authorization".to_string(), "Bearer123".to_string()),
             ("Content-Type".to_string(), "application/json".to_string()),
@@ -884,21 +6320,20 @@ mod tests {
             ("new_field".to_string(), "value".to_string()),
         ];
 
-        let (merged_headers, merged_body) = merge_headers_and_body(
-            &collection_headers,
-            &collection_body,
-            &cli_headers,
-            &cli_body,
-        );
+        let merged_headers = layer_headers(&collection_headers, &cli_headers).unwrap();
+        let merged_body = merge_body_data(&collection_body, &cli_body);
 
         // Check that CLI overrides collection headers
-        assert!(merged_headers.contains(&("Authorization".to_string(), "BearerCLI".to_string())));
+        assert_eq!(merged_headers["authorization"], "BearerCLI");
         // Check that collection headers are preserved when not overridden
-        assert!(
-            merged_headers.contains(&("Content-Type".to_string(), "application/json".to_string()))
-        );
+        assert_eq!(merged_headers["content-type"], "application/json");
         // Check that new CLI headers are added
-        assert!(merged_headers.contains(&("X-Custom".to_string(), "header".to_string())));
+        assert_eq!(merged_headers["x-custom"], "header");
+        // Names are matched case-insensitively
+        let cli_headers = vec![("content-type".to_string(), "text/csv".to_string())];
+        let merged_headers = layer_headers(&collection_headers, &cli_headers).unwrap();
+        assert_eq!(merged_headers.get_all("content-type").iter().count(), 1);
+        assert_eq!(merged_headers["content-type"], "text/csv");
 
         // Check that CLI overrides collection body
         assert!(merged_body.contains(&("name".to_string(), "override".to_string())));
@@ -912,16 +6347,16 @@ mod tests {
     fn test_parse_cli_value_to_json() {
         // Test integer parsing
         assert_eq!(
-            parse_cli_value_to_json("42"),
+            parse_cli_value_to_json("42").unwrap(),
             serde_json::Value::Number(42.into())
         );
         assert_eq!(
-            parse_cli_value_to_json("-123"),
+            parse_cli_value_to_json("-123").unwrap(),
             serde_json::Value::Number((-123).into())
         );
 
         // Test float parsing
-        if let serde_json::Value::Number(n) = parse_cli_value_to_json("2.5") {
+        if let serde_json::Value::Number(n) = parse_cli_value_to_json("2.5").unwrap() {
             assert_eq!(n.as_f64(), Some(2.5));
         } else {
             panic!("Expected number value for float");
@@ -929,25 +6364,25 @@ mod tests {
 
         // Test boolean parsing
         assert_eq!(
-            parse_cli_value_to_json("true"),
+            parse_cli_value_to_json("true").unwrap(),
             serde_json::Value::Bool(true)
         );
         assert_eq!(
-            parse_cli_value_to_json("false"),
+            parse_cli_value_to_json("false").unwrap(),
             serde_json::Value::Bool(false)
         );
 
         // Test string fallback
         assert_eq!(
-            parse_cli_value_to_json("hello"),
+            parse_cli_value_to_json("hello").unwrap(),
             serde_json::Value::String("hello".to_string())
         );
         assert_eq!(
-            parse_cli_value_to_json("123abc"),
+            parse_cli_value_to_json("123abc").unwrap(),
             serde_json::Value::String("123abc".to_string())
         );
         assert_eq!(
-            parse_cli_value_to_json(""),
+            parse_cli_value_to_json("").unwrap(),
             serde_json::Value::String("".to_string())
         );
     }
@@ -961,7 +6396,7 @@ mod tests {
             ("active".to_string(), "true".to_string()),
         ];
 
-        let result = merge_json_with_cli_params(None, &cli_params);
+        let result = merge_json_with_cli_params(None, &cli_params).unwrap();
 
         let expected = serde_json::json!({
             "name": "alice",
@@ -991,7 +6426,7 @@ mod tests {
             ("new_field".to_string(), "123".to_string()),
         ];
 
-        let result = merge_json_with_cli_params(Some(collection_json), &cli_params);
+        let result = merge_json_with_cli_params(Some(collection_json), &cli_params).unwrap();
 
         let expected = serde_json::json!({
             "user_id": 42,           // Preserved from collection
@@ -1016,11 +6451,61 @@ mod tests {
             "enabled": false
         });
 
-        let result = merge_json_with_cli_params(Some(collection_json.clone()), &[]);
+        let result = merge_json_with_cli_params(Some(collection_json.clone()), &[]).unwrap();
 
         assert_eq!(result, collection_json);
     }
 
+    #[test]
+    fn test_merge_json_with_cli_params_nested_fields() {
+        let collection_json = serde_json::json!({
+            "user": {"name": "ann", "id": 7},
+            "tags": ["a"]
+        });
+        let cli_params = vec![
+            ("user.name".to_string(), "joe".to_string()),
+            ("user[address][city]".to_string(), "Oslo".to_string()),
+            ("tags[]".to_string(), "b".to_string()),
+        ];
+
+        let result = merge_json_with_cli_params(Some(collection_json), &cli_params).unwrap();
+
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "user": {"name": "joe", "id": 7, "address": {"city": "Oslo"}},
+                "tags": ["a", "b"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge_json_with_cli_params_type_markers() {
+        let cli_params = [
+            ("zip", "=01234"),
+            ("ids", ":=[1, 2]"),
+            ("meta", r#":={"draft": true}"#),
+            ("count", ":=3"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        let result = merge_json_with_cli_params(None, &cli_params).unwrap();
+
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "zip": "01234",
+                "ids": [1, 2],
+                "meta": {"draft": true},
+                "count": 3
+            })
+        );
+        assert!(matches!(
+            merge_json_with_cli_params(None, &[("ids".to_string(), ":=[1,".to_string())]),
+            Err(WaveError::Parse(ParseError::Json(_)))
+        ));
+    }
+
     #[test]
     fn test_merge_json_with_cli_params_type_inference() {
         // Test that CLI parameters are properly typed
@@ -1036,7 +6521,7 @@ mod tests {
             ("number_like_string".to_string(), "123abc".to_string()),
         ];
 
-        let result = merge_json_with_cli_params(Some(collection_json), &cli_params);
+        let result = merge_json_with_cli_params(Some(collection_json), &cli_params).unwrap();
 
         assert_eq!(result["integer"], serde_json::Value::Number(42.into()));
         assert_eq!(result["negative"], serde_json::Value::Number((-10).into()));
@@ -0,0 +1,164 @@
+//! Debug logging for `-d/--debug` and `--log-file`
+//!
+//! wave emits [`tracing`] events as it loads configuration, resolves
+//! variables, merges headers and calls the HTTP backend. [`init`] installs a
+//! small subscriber that writes wave's own events, one per line, to stderr or
+//! a file:
+//!
+//! ```text
+//!   0.004s DEBUG wave::http::backend: sending request method=GET url=https://example.com/
+//! ```
+//!
+//! Header and variable values are left out of the logs so they can be
+//! attached to bug reports.
+
+use crate::error::WaveError;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// Starts logging when `--debug` or `--log-file` is given
+///
+/// Logs go to `log_file` when set (implying `--debug`), otherwise to stderr.
+///
+/// # Errors
+/// Returns `WaveError::Io` if the log file cannot be created
+pub fn init(debug: bool, log_file: Option<&Path>) -> Result<(), WaveError> {
+    let out: Box<dyn Write + Send> = match log_file {
+        Some(path) => Box::new(File::create(path).map_err(|e| {
+            WaveError::Io(format!(
+                "Failed to create log file '{}': {e}",
+                path.display()
+            ))
+        })?),
+        None if debug => Box::new(std::io::stderr()),
+        None => return Ok(()),
+    };
+    // Only fails if a subscriber is already installed, which then keeps logging
+    let _ = tracing::subscriber::set_global_default(Logger::new(out));
+    Ok(())
+}
+
+/// Writes wave's debug events as plain text lines
+pub struct Logger {
+    started: Instant,
+    out: Mutex<Box<dyn Write + Send>>,
+    next_span: AtomicU64,
+}
+
+impl Logger {
+    /// Creates a logger writing to `out`
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        Logger {
+            started: Instant::now(),
+            out: Mutex::new(out),
+            next_span: AtomicU64::new(1),
+        }
+    }
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // Events from hyper, h2 and other dependencies are left out
+        *metadata.level() <= Level::DEBUG
+            && (metadata.target() == "wave" || metadata.target().starts_with("wave::"))
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(LevelFilter::DEBUG)
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut line = format!(
+            "{:>8.3}s {:<5} {}:",
+            self.started.elapsed().as_secs_f64(),
+            metadata.level(),
+            metadata.target()
+        );
+        event.record(&mut LineVisitor(&mut line));
+        line.push('\n');
+        if let Ok(mut out) = self.out.lock() {
+            let _ = out.write_all(line.as_bytes());
+            let _ = out.flush();
+        }
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+/// Appends the message and `key=value` fields of an event to a line
+struct LineVisitor<'a>(&'a mut String);
+
+impl Visit for LineVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {value}");
+        } else {
+            let _ = write!(self.0, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A writer tests can read back after logging
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_logger_writes_wave_events_as_lines() {
+        let buffer = Buffer::default();
+        let logger = Logger::new(Box::new(buffer.clone()));
+        tracing::subscriber::with_default(logger, || {
+            tracing::debug!(method = "GET", status = 200, "sent request");
+            tracing::trace!("too detailed");
+            tracing::debug!(target: "hyper::proto", "not wave's");
+        });
+        let logged = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = logged.lines().collect();
+        assert_eq!(lines.len(), 1, "{logged}");
+        assert!(
+            lines[0].ends_with("DEBUG wave::logging::tests: sent request method=GET status=200")
+        );
+    }
+}
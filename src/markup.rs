@@ -0,0 +1,420 @@
+//! Pretty printing of XML, HTML and YAML bodies
+//!
+//! The printer pretty prints JSON itself; this module covers the other
+//! structured formats it recognizes from the `Content-Type` header:
+//!
+//! - XML is re-indented, two spaces per level, with elements that hold only
+//!   text kept on one line
+//! - HTML is only highlighted, since whitespace in it can be significant
+//! - YAML is re-indented by parsing and serializing it again, which drops
+//!   comments
+//!
+//! Tag names are blue, attribute names and YAML keys yellow, attribute values
+//! green and comments dimmed. Like the rest of the printer, output is always
+//! styled and the escapes are stripped when color is off. A body that does
+//! not parse as its content type claims is left to be printed as plain text.
+
+use anstyle::{AnsiColor, Style};
+use serde::Deserialize;
+
+/// A structured body format recognized from the `Content-Type` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Xml,
+    Html,
+    Yaml,
+}
+
+impl Markup {
+    /// Recognizes the format of a `Content-Type` value, ignoring parameters
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::markup::Markup;
+    ///
+    /// assert_eq!(Markup::from_content_type("text/xml; charset=utf-8"), Some(Markup::Xml));
+    /// assert_eq!(Markup::from_content_type("application/atom+xml"), Some(Markup::Xml));
+    /// assert_eq!(Markup::from_content_type("text/html"), Some(Markup::Html));
+    /// assert_eq!(Markup::from_content_type("application/x-yaml"), Some(Markup::Yaml));
+    /// assert_eq!(Markup::from_content_type("text/plain"), None);
+    /// ```
+    pub fn from_content_type(content_type: &str) -> Option<Markup> {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let (_, subtype) = essence.split_once('/')?;
+        match subtype {
+            "html" => Some(Markup::Html),
+            "xml" => Some(Markup::Xml),
+            "yaml" | "x-yaml" => Some(Markup::Yaml),
+            _ if subtype.ends_with("+xml") => Some(Markup::Xml),
+            _ if subtype.ends_with("+yaml") => Some(Markup::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Formats `body`, or returns `None` if it is not valid in this format
+    pub fn format(self, body: &str) -> Option<String> {
+        match self {
+            Markup::Xml => pretty_xml(body),
+            Markup::Html => Some(highlight_html(body)),
+            Markup::Yaml => pretty_yaml(body),
+        }
+    }
+}
+
+/// A piece of an XML or HTML document
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    /// `<name ...>`, with its name
+    Open(&'a str, &'a str),
+    /// `</name>`, with its name
+    Close(&'a str, &'a str),
+    /// `<name .../>`
+    Empty(&'a str),
+    /// Comments, CDATA sections, declarations and processing instructions
+    Other(&'a str),
+}
+
+/// Splits a document into tokens, or returns `None` if a markup construct is
+/// not terminated
+///
+/// With `html` set, an unterminated construct is kept as text instead, as are
+/// the contents of `<script>` and `<style>`, since they may contain `<`.
+fn tokenize(doc: &str, html: bool) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = doc;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+            rest = &rest[start..];
+        }
+        let terminator = if rest.starts_with("<!--") {
+            "-->"
+        } else if rest.starts_with("<![CDATA[") {
+            "]]>"
+        } else if rest.starts_with("<?") {
+            "?>"
+        } else {
+            ">"
+        };
+        let end = if terminator == ">" {
+            tag_end(rest)
+        } else {
+            rest[2..].find(terminator).map(|i| i + 2 + terminator.len())
+        };
+        let Some(end) = end else {
+            // Browsers show an unterminated tag as text
+            if html {
+                tokens.push(Token::Text(rest));
+                break;
+            }
+            return None;
+        };
+        let markup = &rest[..end];
+        rest = &rest[end..];
+        if terminator != ">" || markup.starts_with("<!") {
+            tokens.push(Token::Other(markup));
+        } else if let Some(name) = markup.strip_prefix("</") {
+            tokens.push(Token::Close(markup, tag_name(name)));
+        } else if markup.ends_with("/>") {
+            tokens.push(Token::Empty(markup));
+        } else {
+            let name = tag_name(&markup[1..]);
+            tokens.push(Token::Open(markup, name));
+            if html && (name.eq_ignore_ascii_case("script") || name.eq_ignore_ascii_case("style")) {
+                let close = format!("</{}", name.to_ascii_lowercase());
+                let len = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+                if len > 0 {
+                    tokens.push(Token::Text(&rest[..len]));
+                }
+                rest = &rest[len..];
+            }
+        }
+    }
+    Some(tokens)
+}
+
+/// Returns the length of the tag at the start of `s`, skipping `>` in quoted
+/// attribute values
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i + 1),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+fn tag_name(s: &str) -> &str {
+    let end = s
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+fn styled(text: &str, style: Style) -> String {
+    format!("{}{text}{}", style.render(), anstyle::Reset.render())
+}
+
+fn color(color: AnsiColor) -> Style {
+    Style::new().fg_color(Some(anstyle::Color::Ansi(color)))
+}
+
+/// Highlights a tag: its name in blue, attribute names in yellow and
+/// attribute values in green
+fn highlight_tag(tag: &str) -> String {
+    let blue = color(AnsiColor::Blue);
+    let prefix = if tag.starts_with("</") { 2 } else { 1 };
+    let suffix = if tag.ends_with("/>") { 2 } else { 1 };
+    if tag.len() < prefix + suffix {
+        return tag.to_string();
+    }
+    let name = tag_name(&tag[prefix..]);
+    let mut output = styled(&tag[..prefix + name.len()], blue);
+    let mut attributes = &tag[prefix + name.len()..tag.len() - suffix];
+    while !attributes.is_empty() {
+        let (piece, len, style) = match attributes.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let len = attributes[1..].find(q).map_or(attributes.len(), |i| i + 2);
+                (&attributes[..len], len, Some(color(AnsiColor::Green)))
+            }
+            Some(c) if c.is_whitespace() || c == '=' => {
+                let len = attributes
+                    .find(|c: char| !c.is_whitespace() && c != '=')
+                    .unwrap_or(attributes.len());
+                (&attributes[..len], len, None)
+            }
+            _ => {
+                let len = attributes
+                    .find(|c: char| c.is_whitespace() || c == '=')
+                    .unwrap_or(attributes.len());
+                (&attributes[..len], len, Some(color(AnsiColor::Yellow)))
+            }
+        };
+        match style {
+            Some(style) => output.push_str(&styled(piece, style)),
+            None => output.push_str(piece),
+        }
+        attributes = &attributes[len..];
+    }
+    output.push_str(&styled(&tag[tag.len() - suffix..], blue));
+    output
+}
+
+fn highlight_token(token: &Token<'_>) -> String {
+    match token {
+        Token::Text(text) => text.to_string(),
+        Token::Open(tag, _) | Token::Close(tag, _) | Token::Empty(tag) => highlight_tag(tag),
+        Token::Other(markup) => styled(markup, Style::new().dimmed()),
+    }
+}
+
+/// Re-indents an XML document, or returns `None` if it is not well nested
+///
+/// # Examples
+/// ```
+/// use wave::markup::pretty_xml;
+/// use wave::printer::strip_ansi;
+///
+/// let pretty = pretty_xml(r#"<user id="7"><name>Ann</name><tags/></user>"#).unwrap();
+/// assert_eq!(strip_ansi(&pretty), "<user id=\"7\">\n  <name>Ann</name>\n  <tags/>\n</user>\n");
+/// assert_eq!(pretty_xml("<a><b></a>"), None);
+/// ```
+pub fn pretty_xml(doc: &str) -> Option<String> {
+    let tokens: Vec<Token<'_>> = tokenize(doc, false)?
+        .into_iter()
+        .filter(|token| !matches!(token, Token::Text(text) if text.trim().is_empty()))
+        .collect();
+    let mut output = String::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let indent = "  ".repeat(open.len());
+        let line = match (&tokens[i], tokens.get(i + 1), tokens.get(i + 2)) {
+            // Elements holding only text, or nothing, stay on one line
+            (Token::Open(_, name), Some(Token::Text(text)), Some(Token::Close(_, end)))
+                if name == end =>
+            {
+                i += 2;
+                format!(
+                    "{}{}{}",
+                    highlight_token(&tokens[i - 2]),
+                    text.trim(),
+                    highlight_token(&tokens[i])
+                )
+            }
+            (Token::Open(_, name), Some(Token::Close(_, end)), _) if name == end => {
+                i += 1;
+                format!(
+                    "{}{}",
+                    highlight_token(&tokens[i - 1]),
+                    highlight_token(&tokens[i])
+                )
+            }
+            (Token::Open(_, name), _, _) => {
+                open.push(name);
+                highlight_token(&tokens[i])
+            }
+            (Token::Close(_, name), _, _) => {
+                if open.pop() != Some(name) {
+                    return None;
+                }
+                output.push_str(&"  ".repeat(open.len()));
+                output.push_str(&highlight_token(&tokens[i]));
+                output.push('\n');
+                i += 1;
+                continue;
+            }
+            (Token::Text(text), _, _) => text.trim().to_string(),
+            (token, _, _) => highlight_token(token),
+        };
+        output.push_str(&indent);
+        output.push_str(&line);
+        output.push('\n');
+        i += 1;
+    }
+    open.is_empty().then_some(output)
+}
+
+/// Highlights tags and comments of an HTML document, leaving its layout as
+/// it is
+///
+/// # Examples
+/// ```
+/// use wave::markup::highlight_html;
+/// use wave::printer::strip_ansi;
+///
+/// let page = "<p class=\"note\">1 < 2</p>\n<script>if (a<b) go()</script>";
+/// assert_eq!(strip_ansi(&highlight_html(page)), format!("{page}\n"));
+/// ```
+pub fn highlight_html(doc: &str) -> String {
+    let mut output: String = tokenize(doc, true)
+        .unwrap_or_default()
+        .iter()
+        .map(highlight_token)
+        .collect();
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
+
+/// Re-indents a YAML document (or stream of documents) and highlights its
+/// keys, or returns `None` if it does not parse
+///
+/// # Examples
+/// ```
+/// use wave::markup::pretty_yaml;
+/// use wave::printer::strip_ansi;
+///
+/// let pretty = pretty_yaml("user:   {name: Ann,  roles: [admin]}").unwrap();
+/// assert_eq!(strip_ansi(&pretty), "user:\n  name: Ann\n  roles:\n  - admin\n");
+/// assert_eq!(pretty_yaml("a: [1"), None);
+/// ```
+pub fn pretty_yaml(doc: &str) -> Option<String> {
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(doc) {
+        let value = serde_yaml::Value::deserialize(document).ok()?;
+        documents.push(serde_yaml::to_string(&value).ok()?);
+    }
+    let key_style = color(AnsiColor::Yellow).bold();
+    let mut output = String::new();
+    for (n, document) in documents.iter().enumerate() {
+        if n > 0 {
+            output.push_str("---\n");
+        }
+        for line in document.lines() {
+            match yaml_key_len(line) {
+                Some((start, len)) => {
+                    output.push_str(&line[..start]);
+                    output.push_str(&styled(&line[start..start + len], key_style));
+                    output.push_str(&line[start + len..]);
+                }
+                None => output.push_str(line),
+            }
+            output.push('\n');
+        }
+    }
+    Some(output)
+}
+
+/// Finds the key of a `key: value` line as serialized by `serde_yaml`,
+/// returning where it starts and how long it is
+fn yaml_key_len(line: &str) -> Option<(usize, usize)> {
+    let mut start = line.len() - line.trim_start().len();
+    while line[start..].starts_with("- ") {
+        start += 2;
+    }
+    let rest = &line[start..];
+    let len = match rest.chars().next()? {
+        q @ ('"' | '\'') => rest[1..].find(q)? + 2,
+        _ => match rest.find(": ") {
+            Some(len) => len,
+            None => rest.strip_suffix(':')?.len(),
+        },
+    };
+    let after = &rest[len..];
+    (after == ":" || after.starts_with(": ")).then_some((start, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::strip_ansi;
+
+    #[test]
+    fn test_pretty_xml_indents_nested_elements() {
+        let doc = r#"<?xml version="1.0"?><!-- users --><users><user id="1" role='a>b'><name> Ann </name><![CDATA[<raw>]]></user><empty></empty></users>"#;
+        let pretty = strip_ansi(&pretty_xml(doc).unwrap());
+        assert_eq!(
+            pretty,
+            "<?xml version=\"1.0\"?>\n<!-- users -->\n<users>\n  <user id=\"1\" role='a>b'>\n    <name>Ann</name>\n    <![CDATA[<raw>]]>\n  </user>\n  <empty></empty>\n</users>\n"
+        );
+        assert_eq!(pretty_xml("<a>"), None);
+        assert_eq!(pretty_xml("<a><!-- open"), None);
+    }
+
+    #[test]
+    fn test_highlight_tag_colors_names_and_attributes() {
+        let tag = highlight_tag(r#"<a href="/x" hidden>"#);
+        assert_eq!(strip_ansi(&tag), r#"<a href="/x" hidden>"#);
+        let blue = color(AnsiColor::Blue).render().to_string();
+        let yellow = color(AnsiColor::Yellow).render().to_string();
+        let green = color(AnsiColor::Green).render().to_string();
+        assert!(tag.starts_with(&format!("{blue}<a")));
+        assert!(tag.contains(&format!("{yellow}href")));
+        assert!(tag.contains(&format!("{green}\"/x\"")));
+        assert!(tag.contains(&format!("{yellow}hidden")));
+    }
+
+    #[test]
+    fn test_highlight_html_keeps_unterminated_markup() {
+        let page = "<b>bold</b> and <i oops <!-- x";
+        assert_eq!(strip_ansi(&highlight_html(page)), format!("{page}\n"));
+    }
+
+    #[test]
+    fn test_pretty_yaml_documents_and_keys() {
+        let pretty = pretty_yaml("a: 1\n---\n- name: x\n  'odd key': [2]\n").unwrap();
+        assert_eq!(
+            strip_ansi(&pretty),
+            "a: 1\n---\n- name: x\n  odd key:\n  - 2\n"
+        );
+        let yellow = color(AnsiColor::Yellow).bold().render().to_string();
+        assert!(pretty.contains(&format!("- {yellow}name")));
+    }
+}
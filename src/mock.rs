@@ -0,0 +1,540 @@
+//! Mock server for `wave mock`
+//!
+//! Requests in a collection can describe a canned response in a `mock:`
+//! block. `wave mock <collection>` serves those responses from a local HTTP
+//! server, so a frontend can be built against an API that does not exist yet:
+//!
+//! ```yaml
+//! - name: get-user
+//!   method: GET
+//!   url: ${base_url}/users/${id}
+//!   mock:
+//!     status: 200
+//!     headers:
+//!       x-mocked: "true"
+//!     body:
+//!       id: ${id}
+//!       name: Alice
+//! ```
+//!
+//! Requests are matched on method and path. The path is the request URL
+//! without its scheme and host, or without a leading `${base_url}`-style
+//! variable. Path segments containing a variable match any value, and a
+//! segment that is exactly `${name}` makes the value available as `${name}`
+//! in the mock headers and body, along with the collection variables.
+//! When several requests match, the one with the most literal segments wins.
+//!
+//! Bodies given as YAML mappings or lists are sent as JSON; strings are sent
+//! as they are. Every response allows cross-origin requests, and `OPTIONS`
+//! preflight requests are answered automatically.
+
+use crate::collection::{resolve_vars, yaml_to_json, Collection};
+use crate::error::WaveError;
+use http::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head (request line and headers) the server accepts
+const MAX_HEAD: usize = 64 * 1024;
+
+/// Canned response for a collection request
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mock {
+    /// Status code, 200 when omitted
+    #[serde(default = "default_status")]
+    pub status: u16,
+    /// Response headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Response body: a string, or a mapping or list sent as JSON
+    pub body: Option<serde_yaml::Value>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// One segment of a route path
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    /// Must match exactly
+    Literal(String),
+    /// Matches any value, captured under the name if there is one
+    Param(Option<String>),
+}
+
+/// A mocked request the server can answer
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// Name of the collection request
+    pub name: String,
+    /// Method the route answers
+    pub method: Method,
+    /// Path template, e.g. `/users/${id}`
+    pub path: String,
+    segments: Vec<Segment>,
+    mock: Mock,
+}
+
+/// A response ready to be written to the client
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    fn json(status: u16, value: &Value) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        MockResponse::json(status, &serde_json::json!({ "error": message }))
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Returns the path part of a collection URL template
+///
+/// # Examples
+/// ```
+/// use wave::mock::url_path;
+///
+/// assert_eq!(url_path("${base_url}/users/${id}?expand=1"), "/users/${id}");
+/// assert_eq!(url_path("https://api.example.com/v1/users"), "/v1/users");
+/// assert_eq!(url_path("${base_url}"), "/");
+/// ```
+pub fn url_path(url: &str) -> &str {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    let rest = if url.starts_with("${") {
+        url.find('}').map_or("", |end| &url[end + 1..])
+    } else if let Some((_, after)) = url.split_once("://") {
+        after.find('/').map_or("", |start| &after[start..])
+    } else {
+        url
+    };
+    if rest.is_empty() {
+        "/"
+    } else {
+        rest
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+impl Route {
+    /// Builds the route of a collection request with a `mock:` block
+    pub fn new(name: &str, method: Method, url: &str, mock: Mock) -> Self {
+        let path = url_path(url);
+        let segments = split_path(path)
+            .map(|segment| {
+                if !segment.contains("${") {
+                    return Segment::Literal(segment.to_string());
+                }
+                let name = segment
+                    .strip_prefix("${")
+                    .and_then(|s| s.strip_suffix('}'))
+                    .filter(|name| !name.contains([':', '$', '{', '}']));
+                Segment::Param(name.map(str::to_string))
+            })
+            .collect();
+        Route {
+            name: name.to_string(),
+            method,
+            path: path.to_string(),
+            segments,
+            mock,
+        }
+    }
+
+    /// Returns the captured path parameters if `path` matches the route
+    fn match_path(&self, path: &str) -> Option<HashMap<String, String>> {
+        let parts: Vec<&str> = split_path(path).collect();
+        if parts.len() != self.segments.len() {
+            return None;
+        }
+        let mut params = HashMap::new();
+        for (segment, part) in self.segments.iter().zip(parts) {
+            match segment {
+                Segment::Literal(literal) if literal != part => return None,
+                Segment::Literal(_) | Segment::Param(None) => {}
+                Segment::Param(Some(name)) => {
+                    let value = urlencoding::decode(part).unwrap_or_else(|_| part.into());
+                    params.insert(name.clone(), value.into_owned());
+                }
+            }
+        }
+        Some(params)
+    }
+
+    fn literal_count(&self) -> usize {
+        self.segments
+            .iter()
+            .filter(|s| matches!(s, Segment::Literal(_)))
+            .count()
+    }
+
+    /// Renders the mock response, resolving variables in headers and body
+    fn respond(&self, vars: &HashMap<String, String>) -> Result<MockResponse, String> {
+        let mut headers = Vec::new();
+        for (name, value) in &self.mock.headers {
+            headers.push((name.clone(), resolve_vars(value, vars)?));
+        }
+        let (body, content_type) = match &self.mock.body {
+            None => (Vec::new(), None),
+            Some(serde_yaml::Value::String(text)) => {
+                (resolve_vars(text, vars)?.into_bytes(), Some("text/plain"))
+            }
+            Some(value) => {
+                let json = resolve_json(yaml_to_json(value), vars)?;
+                (json.to_string().into_bytes(), Some("application/json"))
+            }
+        };
+        let mut response = MockResponse {
+            status: self.mock.status,
+            headers,
+            body,
+        };
+        if let (Some(content_type), None) = (content_type, response.header("content-type")) {
+            response
+                .headers
+                .push(("content-type".to_string(), content_type.to_string()));
+        }
+        Ok(response)
+    }
+}
+
+/// Resolves variables in every string of a JSON value
+fn resolve_json(value: Value, vars: &HashMap<String, String>) -> Result<Value, String> {
+    Ok(match value {
+        Value::String(text) => Value::String(resolve_vars(&text, vars)?),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| resolve_json(item, vars))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| Ok((k, resolve_json(v, vars)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        other => other,
+    })
+}
+
+/// Returns the routes of every request with a `mock:` block, most specific first
+pub fn routes(coll: &Collection) -> Vec<Route> {
+    let mut routes: Vec<Route> = coll
+        .requests
+        .iter()
+        .filter_map(|req| {
+            let mock = req.mock.clone()?;
+            Some(Route::new(&req.name, req.method.clone(), &req.url, mock))
+        })
+        .collect();
+    routes.sort_by_key(|route| std::cmp::Reverse(route.literal_count()));
+    routes
+}
+
+/// Answers one request, returning the response and the name of the route used
+///
+/// `HEAD` falls back to a `GET` route without the body, and `OPTIONS` to a
+/// CORS preflight response, when the collection does not mock them.
+pub fn dispatch<'a>(
+    routes: &'a [Route],
+    method: &Method,
+    target: &str,
+    vars: &HashMap<String, String>,
+) -> (MockResponse, Option<&'a str>) {
+    let path = target.split(['?', '#']).next().unwrap_or(target);
+    let matching: Vec<(&Route, HashMap<String, String>)> = routes
+        .iter()
+        .filter_map(|route| route.match_path(path).map(|params| (route, params)))
+        .collect();
+    let allowed = || {
+        let mut methods: Vec<&str> = matching.iter().map(|(r, _)| r.method.as_str()).collect();
+        methods.dedup();
+        methods.join(", ")
+    };
+    let found = matching
+        .iter()
+        .find(|(route, _)| route.method == *method)
+        .or_else(|| {
+            (*method == Method::HEAD)
+                .then(|| {
+                    matching
+                        .iter()
+                        .find(|(route, _)| route.method == Method::GET)
+                })
+                .flatten()
+        });
+
+    let mut response = match found {
+        Some((route, params)) => {
+            let mut vars = vars.clone();
+            vars.extend(params.iter().map(|(k, v)| (k.clone(), v.clone())));
+            let mut response = route
+                .respond(&vars)
+                .unwrap_or_else(|e| MockResponse::error(500, &e));
+            if *method == Method::HEAD {
+                response.body.clear();
+            }
+            return (with_cors(response), Some(&route.name));
+        }
+        None if matching.is_empty() => {
+            MockResponse::error(404, &format!("No mock for {method} {path}"))
+        }
+        None if *method == Method::OPTIONS => MockResponse {
+            status: 204,
+            headers: vec![
+                ("access-control-allow-methods".to_string(), allowed()),
+                ("access-control-allow-headers".to_string(), "*".to_string()),
+            ],
+            body: Vec::new(),
+        },
+        None => {
+            let mut response = MockResponse::error(405, &format!("No mock for {method} {path}"));
+            response.headers.push(("allow".to_string(), allowed()));
+            response
+        }
+    };
+    response = with_cors(response);
+    (response, None)
+}
+
+fn with_cors(mut response: MockResponse) -> MockResponse {
+    if response.header("access-control-allow-origin").is_none() {
+        response
+            .headers
+            .push(("access-control-allow-origin".to_string(), "*".to_string()));
+    }
+    response
+}
+
+/// Serializes a response as HTTP/1.1, closing the connection afterwards
+fn encode(response: &MockResponse) -> Vec<u8> {
+    let reason = StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("");
+    let mut head = format!("HTTP/1.1 {} {reason}\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!(
+        "content-length: {}\r\nconnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    let mut bytes = head.into_bytes();
+    bytes.extend_from_slice(&response.body);
+    bytes
+}
+
+/// Accepts connections until the process is stopped, answering each from `routes`
+///
+/// Every request is logged as `METHOD /path -> STATUS (request name)`.
+///
+/// # Errors
+/// Returns `WaveError::Io` if accepting a connection fails.
+pub async fn serve(
+    listener: TcpListener,
+    routes: Vec<Route>,
+    vars: HashMap<String, String>,
+) -> Result<(), WaveError> {
+    let shared = Arc::new((routes, vars));
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| WaveError::Io(format!("Failed to accept a connection: {e}")))?;
+        let shared = Arc::clone(&shared);
+        tokio::spawn(async move {
+            let (routes, vars) = &*shared;
+            if let Err(e) = handle_connection(stream, routes, vars).await {
+                eprintln!("Connection error: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    routes: &[Route],
+    vars: &HashMap<String, String>,
+) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let (method, target, head_len, content_length) = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        match req.parse(&buf) {
+            Ok(httparse::Status::Complete(head_len)) => {
+                let content_length = req
+                    .headers
+                    .iter()
+                    .find(|h| h.name.eq_ignore_ascii_case("content-length"))
+                    .and_then(|h| std::str::from_utf8(h.value).ok())
+                    .and_then(|v| v.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                break (
+                    req.method.unwrap_or("GET").to_string(),
+                    req.path.unwrap_or("/").to_string(),
+                    head_len,
+                    content_length,
+                );
+            }
+            Ok(httparse::Status::Partial) if buf.len() < MAX_HEAD => continue,
+            _ => {
+                let response = MockResponse::error(400, "Malformed request");
+                return stream.write_all(&encode(&response)).await;
+            }
+        }
+    };
+    // Read (and discard) the request body so the client sees a clean close
+    let mut remaining = (head_len + content_length).saturating_sub(buf.len());
+    while remaining > 0 {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        remaining = remaining.saturating_sub(n);
+    }
+
+    let method = Method::from_bytes(method.as_bytes()).unwrap_or(Method::GET);
+    let (response, name) = dispatch(routes, &method, &target, vars);
+    match name {
+        Some(name) => println!("{method} {target} -> {} ({name})", response.status),
+        None => println!("{method} {target} -> {}", response.status),
+    }
+    stream.write_all(&encode(&response)).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(name: &str, method: Method, url: &str, yaml: &str) -> Route {
+        Route::new(name, method, url, serde_yaml::from_str(yaml).unwrap())
+    }
+
+    fn routes() -> Vec<Route> {
+        let mut routes = vec![
+            route(
+                "get-user",
+                Method::GET,
+                "${base_url}/users/${id}",
+                "body:\n  id: ${id}\n  name: ${name}\n  tags: [\"${id}\"]\n",
+            ),
+            route(
+                "me",
+                Method::GET,
+                "https://api.example.com/users/me",
+                "status: 200\nbody: it's me\n",
+            ),
+            route(
+                "delete-user",
+                Method::DELETE,
+                "${base_url}/users/${id}",
+                "status: 204\n",
+            ),
+        ];
+        routes.sort_by_key(|r| std::cmp::Reverse(r.literal_count()));
+        routes
+    }
+
+    fn vars() -> HashMap<String, String> {
+        HashMap::from([("name".to_string(), "Alice".to_string())])
+    }
+
+    #[test]
+    fn test_dispatch_resolves_params_and_variables() {
+        let routes = routes();
+        let (response, name) = dispatch(&routes, &Method::GET, "/users/42?x=1", &vars());
+        assert_eq!(name, Some("get-user"));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+        let body: Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"id": "42", "name": "Alice", "tags": ["42"]})
+        );
+    }
+
+    #[test]
+    fn test_dispatch_prefers_literal_segments() {
+        let routes = routes();
+        let (response, name) = dispatch(&routes, &Method::GET, "/users/me", &vars());
+        assert_eq!(name, Some("me"));
+        assert_eq!(response.body, b"it's me");
+        assert_eq!(response.header("content-type"), Some("text/plain"));
+    }
+
+    #[test]
+    fn test_dispatch_fallbacks() {
+        let routes = routes();
+        let (response, _) = dispatch(&routes, &Method::GET, "/orders", &vars());
+        assert_eq!(response.status, 404);
+
+        let (response, name) = dispatch(&routes, &Method::PUT, "/users/1", &vars());
+        assert_eq!((response.status, name), (405, None));
+        assert_eq!(response.header("allow"), Some("GET, DELETE"));
+
+        let (response, _) = dispatch(&routes, &Method::OPTIONS, "/users/1", &vars());
+        assert_eq!(response.status, 204);
+        assert_eq!(
+            response.header("access-control-allow-methods"),
+            Some("GET, DELETE")
+        );
+
+        let (response, name) = dispatch(&routes, &Method::HEAD, "/users/1", &vars());
+        assert_eq!((response.status, name), (200, Some("get-user")));
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn test_missing_variable_is_a_server_error() {
+        let routes = routes();
+        let (response, _) = dispatch(&routes, &Method::GET, "/users/1", &HashMap::new());
+        assert_eq!(response.status, 500);
+        assert!(String::from_utf8_lossy(&response.body).contains("name"));
+    }
+
+    #[test]
+    fn test_encode_response() {
+        let response = MockResponse {
+            status: 201,
+            headers: vec![("x-a".to_string(), "1".to_string())],
+            body: b"ok".to_vec(),
+        };
+        assert_eq!(
+            String::from_utf8(encode(&response)).unwrap(),
+            "HTTP/1.1 201 Created\r\nx-a: 1\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! OAuth2 client-credentials tokens for `auth: oauth2:` blocks
+//!
+//! Before sending a request with an `oauth2:` auth block, wave asks the token
+//! endpoint for an access token (authenticating with HTTP Basic client
+//! credentials, as RFC 6749 recommends) and sends it as a bearer token.
+//!
+//! Tokens are cached in `.wave/tokens/<environment>.json` (`default.json`
+//! without `--env`) and reused until shortly before they expire. Tokens whose
+//! response has no `expires_in` are not cached. Cache files are only
+//! readable by their owner (mode 0600 on unix).
+
+use crate::error::{CollectionError, WaveError};
+use crate::http::{Auth, HttpBackend, HttpRequest, HttpResponse, OAuth2, RequestBody};
+use ::http::Method;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory inside the collection directory that holds cached tokens
+pub const TOKEN_DIR: &str = "tokens";
+
+/// Seconds before expiry at which a cached token is fetched again, so it does
+/// not expire while a request is in flight
+const EXPIRY_MARGIN: u64 = 30;
+
+/// An access token and when it expires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Token {
+    pub access_token: String,
+    /// Expiry as seconds since the Unix epoch
+    pub expires_at: Option<u64>,
+}
+
+impl Token {
+    /// Returns true if the token can still be used at `now`
+    pub fn is_fresh(&self, now: u64) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| now.saturating_add(EXPIRY_MARGIN) < expires_at)
+    }
+}
+
+/// Cached tokens of one environment, keyed by [`cache_key`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenCache {
+    #[serde(default)]
+    pub tokens: BTreeMap<String, Token>,
+}
+
+impl TokenCache {
+    /// Loads a cache file, starting an empty cache if it is missing or unreadable
+    ///
+    /// A broken cache only costs a token request, so it is not an error.
+    pub fn load(path: &Path) -> TokenCache {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache file, creating the tokens directory if needed
+    ///
+    /// Like session files, it is only readable by its owner.
+    pub fn save(&self, path: &Path) -> Result<(), WaveError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        crate::session::write_private(path, json.as_bytes()).map_err(|e| {
+            WaveError::Io(format!(
+                "Failed to save token cache '{}': {e}",
+                path.display()
+            ))
+        })
+    }
+}
+
+/// Returns the cache file for an environment
+pub fn cache_path(collection_dir: &Path, env: Option<&str>) -> PathBuf {
+    let name = env.unwrap_or("default").replace(['/', '\\'], "_");
+    collection_dir.join(TOKEN_DIR).join(format!("{name}.json"))
+}
+
+/// Identifies the tokens of one client, endpoint and set of scopes
+pub fn cache_key(oauth2: &OAuth2) -> String {
+    format!(
+        "{} {} {}",
+        oauth2.token_url,
+        oauth2.client_id,
+        oauth2.scopes.join(" ")
+    )
+    .trim_end()
+    .to_string()
+}
+
+/// Builds the client-credentials token request
+pub fn token_request(oauth2: &OAuth2) -> HttpRequest {
+    let mut form = vec![("grant_type".to_string(), "client_credentials".to_string())];
+    if !oauth2.scopes.is_empty() {
+        form.push(("scope".to_string(), oauth2.scopes.join(" ")));
+    }
+    HttpRequest::builder(oauth2.token_url.clone(), Method::POST)
+        .header("Accept", "application/json")
+        .auth(Auth::Basic {
+            username: oauth2.client_id.clone(),
+            password: Some(oauth2.client_secret.clone()),
+        })
+        .body(RequestBody::form(form))
+        .build()
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Reads the token from a token endpoint response received at `now`
+///
+/// # Errors
+/// Returns a message for error statuses, OAuth2 error responses and responses
+/// without an `access_token`.
+pub fn parse_token_response(resp: &HttpResponse, now: u64) -> Result<Token, String> {
+    let parsed: Option<TokenResponse> = serde_json::from_str(&resp.body).ok();
+    if let Some(TokenResponse {
+        error: Some(error),
+        error_description,
+        ..
+    }) = &parsed
+    {
+        return Err(match error_description {
+            Some(description) => format!("{error} ({description})"),
+            None => error.clone(),
+        });
+    }
+    if !(200..300).contains(&resp.status) {
+        return Err(format!("status {}", resp.status));
+    }
+    match parsed {
+        Some(TokenResponse {
+            access_token: Some(access_token),
+            expires_in,
+            ..
+        }) => Ok(Token {
+            access_token,
+            expires_at: expires_in.map(|secs| now.saturating_add(secs)),
+        }),
+        _ => Err("the response has no access_token".to_string()),
+    }
+}
+
+/// Returns an access token for `oauth2`, from the cache at `cache` if it
+/// is still fresh, otherwise from the token endpoint
+///
+/// # Errors
+/// Returns `CollectionError::TokenRequest` if the token endpoint cannot be
+/// reached or does not return a token, and `WaveError::Io` if the cache
+/// cannot be written.
+pub async fn access_token<B: HttpBackend>(
+    backend: &B,
+    oauth2: &OAuth2,
+    cache: &Path,
+) -> Result<String, WaveError> {
+    let key = cache_key(oauth2);
+    let mut tokens = TokenCache::load(cache);
+    if let Some(token) = tokens.tokens.get(&key).filter(|t| t.is_fresh(unix_now())) {
+        return Ok(token.access_token.clone());
+    }
+    let token_error = |message: String| {
+        WaveError::Collection(CollectionError::TokenRequest {
+            token_url: oauth2.token_url.clone(),
+            message,
+        })
+    };
+    let resp = backend
+        .send(&token_request(oauth2))
+        .await
+        .map_err(|e| token_error(e.to_string()))?;
+    let token = parse_token_response(&resp, unix_now()).map_err(token_error)?;
+    let access_token = token.access_token.clone();
+    if token.expires_at.is_some() {
+        tokens.tokens.insert(key, token);
+        tokens.save(cache)?;
+    }
+    Ok(access_token)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::http::header::{AUTHORIZATION, CONTENT_TYPE};
+    use ::http::HeaderMap;
+
+    fn oauth2() -> OAuth2 {
+        OAuth2 {
+            token_url: "https://auth.io/token".to_string(),
+            client_id: "app".to_string(),
+            client_secret: "s3cret".to_string(),
+            scopes: vec!["read".to_string(), "write".to_string()],
+        }
+    }
+
+    fn response(status: u16, body: &str) -> HttpResponse {
+        HttpResponse::new(status, HeaderMap::new(), body.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_token_request() {
+        let req = token_request(&oauth2());
+        assert_eq!(req.method, Method::POST);
+        assert_eq!(req.url, "https://auth.io/token");
+        assert_eq!(
+            req.headers.get(AUTHORIZATION).unwrap(),
+            "Basic YXBwOnMzY3JldA=="
+        );
+        assert_eq!(
+            req.headers.get(CONTENT_TYPE).unwrap(),
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(
+            req.body.as_deref(),
+            Some("grant_type=client_credentials&scope=read%20write")
+        );
+    }
+
+    #[test]
+    fn test_parse_token_response() {
+        let token = parse_token_response(
+            &response(200, r#"{"access_token": "abc", "expires_in": 3600}"#),
+            1000,
+        );
+        assert_eq!(
+            token,
+            Ok(Token {
+                access_token: "abc".to_string(),
+                expires_at: Some(4600),
+            })
+        );
+        let error = parse_token_response(
+            &response(
+                400,
+                r#"{"error": "invalid_client", "error_description": "bad secret"}"#,
+            ),
+            0,
+        );
+        assert_eq!(error, Err("invalid_client (bad secret)".to_string()));
+        assert_eq!(
+            parse_token_response(&response(500, "oops"), 0),
+            Err("status 500".to_string())
+        );
+        assert!(parse_token_response(&response(200, "{}"), 0).is_err());
+    }
+
+    #[test]
+    fn test_token_freshness() {
+        let token = |expires_at| Token {
+            access_token: "abc".to_string(),
+            expires_at,
+        };
+        assert!(token(Some(1000)).is_fresh(900));
+        assert!(!token(Some(1000)).is_fresh(980));
+        assert!(!token(None).is_fresh(0));
+    }
+
+    #[test]
+    fn test_cache_path_and_key() {
+        let dir = Path::new(".wave");
+        assert_eq!(
+            cache_path(dir, None),
+            Path::new(".wave/tokens/default.json")
+        );
+        assert_eq!(
+            cache_path(dir, Some("staging")),
+            Path::new(".wave/tokens/staging.json")
+        );
+        assert_eq!(cache_key(&oauth2()), "https://auth.io/token app read write");
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("wave-tokens-{}", std::process::id()))
+            .join("default.json");
+        assert_eq!(TokenCache::load(&path), TokenCache::default());
+        let mut cache = TokenCache::default();
+        cache.tokens.insert(
+            cache_key(&oauth2()),
+            Token {
+                access_token: "abc".to_string(),
+                expires_at: Some(10),
+            },
+        );
+        cache.save(&path).unwrap();
+        assert_eq!(TokenCache::load(&path), cache);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,325 @@
+//! Following paginated responses for `--paginate`
+//!
+//! Pages are requested one after another until the API says there are no
+//! more, and their results are combined into a single response. Four ways of
+//! finding the next page are supported:
+//!
+//! - `link`: the `Link` header's `rel="next"` URL (RFC 8288, formerly 5988)
+//! - `next:PATH`: a URL in the JSON body, e.g. `next:next_url` or `next:$.links.next`
+//! - `page:PARAM`: a page number query parameter, increased by one per page
+//! - `offset:PARAM`: an offset query parameter, increased by the items on each page
+//!
+//! The items of each JSON page are the body itself when it is an array,
+//! otherwise the array at `--items PATH` or the first array field of the
+//! body. They are concatenated into one JSON array. Pages that are not JSON
+//! are joined as text instead, which only makes sense with `link`. The
+//! `page` and `offset` strategies stop at the first page without items.
+
+use crate::http::HttpResponse;
+use crate::query;
+use ::http::HeaderMap;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+use url::Url;
+
+/// Pages fetched when `--max-pages` is not given
+pub const DEFAULT_MAX_PAGES: u64 = 100;
+
+/// How to find the next page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Paginate {
+    /// The `rel="next"` URL of the `Link` header
+    Link,
+    /// A URL at a dotted path or JSONPath query in the JSON body
+    Next(String),
+    /// A page number query parameter
+    Page(String),
+    /// An offset query parameter
+    Offset(String),
+}
+
+impl FromStr for Paginate {
+    type Err = String;
+
+    /// Parses `link`, `next:PATH`, `page:PARAM` or `offset:PARAM`
+    ///
+    /// # Examples
+    /// ```
+    /// use wave::paginate::Paginate;
+    ///
+    /// assert_eq!("link".parse(), Ok(Paginate::Link));
+    /// assert_eq!("next:$.links.next".parse(), Ok(Paginate::Next("$.links.next".to_string())));
+    /// assert_eq!("page:p".parse(), Ok(Paginate::Page("p".to_string())));
+    /// assert!("page:".parse::<Paginate>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (s, None),
+        };
+        let invalid = || {
+            format!(
+                "invalid pagination '{s}', expected link, next:PATH, page:PARAM or offset:PARAM"
+            )
+        };
+        match (kind, arg) {
+            ("link", None) => Ok(Paginate::Link),
+            (_, Some("")) => Err(invalid()),
+            ("next", Some(path)) => Ok(Paginate::Next(path.to_string())),
+            ("page", Some(param)) => Ok(Paginate::Page(param.to_string())),
+            ("offset", Some(param)) => Ok(Paginate::Offset(param.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Paginate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Paginate::Link => write!(f, "link"),
+            Paginate::Next(path) => write!(f, "next:{path}"),
+            Paginate::Page(param) => write!(f, "page:{param}"),
+            Paginate::Offset(param) => write!(f, "offset:{param}"),
+        }
+    }
+}
+
+impl Paginate {
+    /// Returns the URL of the page after `resp`, fetched from `url`, or
+    /// `None` when it was the last one
+    ///
+    /// `items` is the number of items `resp` held, which the `page` and
+    /// `offset` strategies use to detect the end.
+    pub fn next_url(
+        &self,
+        url: &str,
+        resp: &HttpResponse,
+        items: usize,
+    ) -> Result<Option<String>, String> {
+        let current = Url::parse(url).map_err(|e| format!("invalid URL '{url}': {e}"))?;
+        match self {
+            Paginate::Link => Ok(next_link(&resp.headers)
+                .map(|link| resolve(&current, &link))
+                .transpose()?),
+            Paginate::Next(path) => {
+                let body: Value = serde_json::from_str(&resp.body)
+                    .map_err(|_| format!("{url} did not return JSON to read '{path}' from"))?;
+                match query::lookup(&body, path)? {
+                    None | Some(Value::Null) => Ok(None),
+                    Some(Value::String(s)) if s.is_empty() => Ok(None),
+                    Some(Value::String(next)) => resolve(&current, &next).map(Some),
+                    Some(other) => Err(format!("'{path}' is {other}, not a URL")),
+                }
+            }
+            Paginate::Page(_) | Paginate::Offset(_) if items == 0 => Ok(None),
+            Paginate::Page(param) => {
+                let page = query_number(&current, param)?.unwrap_or(1);
+                Ok(Some(with_param(&current, param, page + 1)))
+            }
+            Paginate::Offset(param) => {
+                let offset = query_number(&current, param)?.unwrap_or(0);
+                Ok(Some(with_param(&current, param, offset + items as u64)))
+            }
+        }
+    }
+}
+
+/// Returns the `rel="next"` target of the `Link` headers, if any
+///
+/// # Examples
+/// ```
+/// use http::{HeaderMap, HeaderValue};
+/// use wave::paginate::next_link;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert("link", HeaderValue::from_static(
+///     r#"<https://api.example.com/items?page=3>; rel="next", <https://api.example.com/items?page=9>; rel="last""#,
+/// ));
+/// assert_eq!(next_link(&headers).as_deref(), Some("https://api.example.com/items?page=3"));
+/// ```
+pub fn next_link(headers: &HeaderMap) -> Option<String> {
+    for value in headers.get_all("link") {
+        let mut rest = value.to_str().ok()?;
+        while let Some(start) = rest.find('<') {
+            let end = start + rest[start..].find('>')?;
+            let target = &rest[start + 1..end];
+            let params_end = rest[end..].find('<').map_or(rest.len(), |i| end + i);
+            let is_next = rest[end + 1..params_end].split(';').any(|param| {
+                param
+                    .trim()
+                    .trim_end_matches(',')
+                    .trim()
+                    .strip_prefix("rel=")
+                    .is_some_and(|rel| {
+                        rel.trim_matches('"')
+                            .split_whitespace()
+                            .any(|r| r.eq_ignore_ascii_case("next"))
+                    })
+            });
+            if is_next {
+                return Some(target.to_string());
+            }
+            rest = &rest[params_end..];
+        }
+    }
+    None
+}
+
+/// Returns the items of a JSON page
+///
+/// The page itself when it is an array, otherwise the array at `path`, or
+/// the first array field of an object. Anything else is a single item.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use wave::paginate::page_items;
+///
+/// assert_eq!(page_items(&json!([1, 2]), None), Ok(vec![json!(1), json!(2)]));
+/// assert_eq!(page_items(&json!({"total": 9, "data": [3]}), None), Ok(vec![json!(3)]));
+/// assert_eq!(page_items(&json!({"a": {"b": [4]}}), Some("a.b")), Ok(vec![json!(4)]));
+/// ```
+pub fn page_items(body: &Value, path: Option<&str>) -> Result<Vec<Value>, String> {
+    let found = match path {
+        Some(path) => {
+            query::lookup(body, path)?.ok_or_else(|| format!("no items found at '{path}'"))?
+        }
+        None => match body {
+            Value::Object(fields) => fields
+                .values()
+                .find(|v| v.is_array())
+                .cloned()
+                .unwrap_or_else(|| body.clone()),
+            _ => body.clone(),
+        },
+    };
+    Ok(match found {
+        Value::Array(items) => items,
+        Value::Null if path.is_some() => Vec::new(),
+        other => vec![other],
+    })
+}
+
+fn resolve(base: &Url, link: &str) -> Result<String, String> {
+    base.join(link)
+        .map(String::from)
+        .map_err(|e| format!("invalid next page URL '{link}': {e}"))
+}
+
+fn query_number(url: &Url, param: &str) -> Result<Option<u64>, String> {
+    url.query_pairs()
+        .find(|(name, _)| name == param)
+        .map(|(_, value)| {
+            value
+                .parse()
+                .map_err(|_| format!("query parameter '{param}={value}' is not a number"))
+        })
+        .transpose()
+}
+
+/// Returns `url` with the query parameter `param` set to `value`, keeping the
+/// other parameters in order
+fn with_param(url: &Url, param: &str, value: u64) -> String {
+    let mut next = url.clone();
+    let mut found = false;
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, v)| {
+            if name == param {
+                found = true;
+                (name.into_owned(), value.to_string())
+            } else {
+                (name.into_owned(), v.into_owned())
+            }
+        })
+        .collect();
+    {
+        let mut query = next.query_pairs_mut();
+        query.clear();
+        query.extend_pairs(&pairs);
+        if !found {
+            query.append_pair(param, &value.to_string());
+        }
+    }
+    next.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::http::HeaderValue;
+
+    fn page(body: &str) -> HttpResponse {
+        HttpResponse::new(200, HeaderMap::new(), body)
+    }
+
+    #[test]
+    fn test_link_strategy() {
+        let mut resp = page("[]");
+        assert_eq!(
+            Paginate::Link.next_url("https://api.example.com/items", &resp, 0),
+            Ok(None)
+        );
+        resp.headers.append(
+            "link",
+            HeaderValue::from_static(r#"</items?page=1>; rel="prev first""#),
+        );
+        resp.headers.append(
+            "link",
+            HeaderValue::from_static(r#"</items?cursor=a,b>; title="x"; rel="next""#),
+        );
+        assert_eq!(
+            Paginate::Link.next_url("https://api.example.com/items?page=2", &resp, 0),
+            Ok(Some("https://api.example.com/items?cursor=a,b".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_next_field_strategy() {
+        let next = Paginate::Next("links.next".to_string());
+        let url = "https://api.example.com/v1/items";
+        let resp = page(r#"{"items": [1], "links": {"next": "items?after=7"}}"#);
+        assert_eq!(
+            next.next_url(url, &resp, 1),
+            Ok(Some("https://api.example.com/v1/items?after=7".to_string()))
+        );
+        let last = page(r#"{"items": [1], "links": {"next": null}}"#);
+        assert_eq!(next.next_url(url, &last, 1), Ok(None));
+        assert!(next.next_url(url, &page("<html>"), 1).is_err());
+        assert!(next
+            .next_url(url, &page(r#"{"links": {"next": 3}}"#), 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_page_and_offset_strategies() {
+        let url = "https://api.example.com/items?q=a%20b&limit=2";
+        let resp = page("[1, 2]");
+        assert_eq!(
+            Paginate::Page("page".to_string()).next_url(url, &resp, 2),
+            Ok(Some(
+                "https://api.example.com/items?q=a+b&limit=2&page=2".to_string()
+            ))
+        );
+        assert_eq!(
+            Paginate::Page("page".to_string()).next_url(
+                "https://api.example.com/items?page=4&q=x",
+                &resp,
+                2
+            ),
+            Ok(Some("https://api.example.com/items?page=5&q=x".to_string()))
+        );
+        let offset = Paginate::Offset("offset".to_string());
+        assert_eq!(
+            offset.next_url(url, &resp, 2),
+            Ok(Some(
+                "https://api.example.com/items?q=a+b&limit=2&offset=2".to_string()
+            ))
+        );
+        assert_eq!(offset.next_url(url, &page("[]"), 0), Ok(None));
+        assert!(offset
+            .next_url("https://api.example.com/items?offset=x", &resp, 2)
+            .is_err());
+    }
+}
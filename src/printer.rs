@@ -2,33 +2,374 @@
 //!
 //! This module handles the visual presentation of HTTP responses, including:
 //! - Colored status codes and headers
-//! - Pretty-printed JSON responses
+//! - Pretty-printed JSON responses, and XML, HTML and YAML ones (see [`crate::markup`])
 //! - Conditional header display (verbose mode or error status)
+//! - Headers-only, body-only and unstyled `--raw` output modes
 //! - Error message formatting
+//! - A machine-readable JSON report of the whole exchange (`--format json`)
 //!
 //! The output is optimized for terminal viewing with appropriate color coding
-//! to help users quickly understand response status and content.
+//! to help users quickly understand response status and content. Output is
+//! always built with ANSI styles; when color is turned off (`--color never`,
+//! `NO_COLOR`, or output that is not a terminal) the escapes are stripped.
 
-use crate::http::{HttpError, HttpResponse};
+use crate::http::{HttpError, HttpRequest, HttpResponse, MultipartPart};
+use crate::markup::Markup;
 use anstyle::{AnsiColor, Style};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Which parts of a response to print
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Status line, headers when relevant, and the formatted body
+    #[default]
+    Full,
+    /// Status line and every header, without the body
+    HeadersOnly,
+    /// The raw body exactly as received, for piping into other tools
+    BodyOnly,
+    /// The status line, headers sorted by name, a blank line and the body
+    /// exactly as received, without styling, for golden files and diffs
+    Raw,
+}
+
+/// Overall output format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-friendly output
+    #[default]
+    Pretty,
+    /// A single JSON document with the request, status, headers, body and timing
+    Json,
+}
+
+/// The full exchange as printed by `--format json`
+///
+/// JSON response bodies are embedded as JSON; other bodies are included as a
+/// string. Header names are lowercase, and repeated headers are joined with
+/// `, `. When no response was received, `status` is omitted and `error`
+/// explains why.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use http::{HeaderMap, Method};
+/// use wave::http::{HttpRequest, HttpResponse};
+/// use wave::printer::ResponseReport;
+///
+/// let req = HttpRequest::builder("https://api.example.com/users/1", Method::GET).build();
+/// let resp = HttpResponse::new(200, HeaderMap::new(), r#"{"id":1}"#);
+/// let report = ResponseReport::new(&req, &Ok(resp), Duration::from_millis(12));
+///
+/// let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+/// assert_eq!(json["status"], 200);
+/// assert_eq!(json["body"]["id"], 1);
+/// assert_eq!(json["time_ms"], 12);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResponseReport {
+    /// HTTP method that was sent
+    pub method: String,
+    /// URL the request was sent to
+    pub url: String,
+    /// Response status, if a response was received
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Response headers
+    pub headers: BTreeMap<String, String>,
+    /// Response body, parsed when it is JSON
+    pub body: serde_json::Value,
+    /// Wall-clock time from sending the request to receiving the whole body
+    pub time_ms: u64,
+    /// Time until the status line and headers arrived, when measured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttfb_ms: Option<u64>,
+    /// Size of the response body in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    /// Size of the body as received, when it arrived compressed and was decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoded_size: Option<usize>,
+    /// Why no response was received
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ResponseReport {
+    /// Describes a request and its outcome
+    pub fn new(
+        req: &HttpRequest,
+        result: &Result<HttpResponse, HttpError>,
+        elapsed: Duration,
+    ) -> Self {
+        let mut report = ResponseReport {
+            method: req.method.to_string(),
+            url: req.url.clone(),
+            status: None,
+            headers: BTreeMap::new(),
+            body: serde_json::Value::Null,
+            time_ms: millis(elapsed),
+            ttfb_ms: None,
+            size: None,
+            encoded_size: None,
+            error: None,
+        };
+        match result {
+            Ok(resp) => {
+                report.status = Some(resp.status);
+                report.ttfb_ms = resp.timing.map(|t| millis(t.ttfb));
+                report.size = Some(resp.bytes.len());
+                report.encoded_size = resp.content_encoding.as_ref().map(|e| e.encoded_size);
+                for (name, value) in &resp.headers {
+                    let value = String::from_utf8_lossy(value.as_bytes());
+                    report
+                        .headers
+                        .entry(name.as_str().to_string())
+                        .and_modify(|existing| {
+                            existing.push_str(", ");
+                            existing.push_str(&value);
+                        })
+                        .or_insert_with(|| value.into_owned());
+                }
+                report.body = serde_json::from_str(&resp.body)
+                    .unwrap_or_else(|_| serde_json::Value::String(resp.body.clone()));
+            }
+            Err(e) => report.error = Some(e.to_string()),
+        }
+        report
+    }
+
+    /// Serializes the report as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Formats the dimmed timing and size line shown in verbose output
+///
+/// Empty when the backend did not measure the response.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use http::HeaderMap;
+/// use wave::http::{HttpResponse, Timing};
+/// use wave::printer::{format_timing, strip_ansi};
+///
+/// let resp = HttpResponse::new(200, HeaderMap::new(), "hello").with_timing(Timing {
+///     ttfb: Duration::from_millis(80),
+///     total: Duration::from_millis(120),
+/// });
+/// assert_eq!(
+///     strip_ansi(&format_timing(&resp)),
+///     "Time: 120 ms (first byte 80 ms), size: 5 bytes\n"
+/// );
+/// ```
+pub fn format_timing(resp: &HttpResponse) -> String {
+    let Some(timing) = resp.timing else {
+        return String::new();
+    };
+    let encoded = match &resp.content_encoding {
+        Some(encoding) => format!(
+            " ({} bytes {} encoded)",
+            encoding.encoded_size, encoding.coding
+        ),
+        None => String::new(),
+    };
+    format!(
+        "{}Time: {} ms (first byte {} ms), size: {} bytes{encoded}{}\n",
+        Style::new().dimmed().render(),
+        millis(timing.total),
+        millis(timing.ttfb),
+        resp.bytes.len(),
+        anstyle::Reset.render()
+    )
+}
+
+/// Body bytes shown on a terminal when no `--max-body` is given
+pub const DEFAULT_MAX_BODY: usize = 64 * 1024;
+
+/// How a response is printed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// Show all headers regardless of status
+    pub verbose: bool,
+    /// Which parts of the response to include
+    pub mode: OutputMode,
+    /// Emit ANSI colors and styles
+    pub color: bool,
+    /// Show GraphQL `errors` and `data` as separate sections
+    pub graphql: bool,
+    /// Cut the formatted body after this many bytes, see [`truncate_body`]
+    pub max_body: Option<usize>,
+    /// Show only these response headers, whenever the response has them
+    pub show_headers: Vec<http::HeaderName>,
+}
+
+impl PrintOptions {
+    /// Colored output of the full response with all headers
+    pub fn verbose() -> Self {
+        Self {
+            verbose: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            verbose: false,
+            mode: OutputMode::Full,
+            color: true,
+            graphql: false,
+            max_body: None,
+            show_headers: Vec::new(),
+        }
+    }
+}
+
+/// Removes ANSI escape sequences from styled text
+///
+/// # Examples
+/// ```
+/// use wave::printer::strip_ansi;
+///
+/// assert_eq!(strip_ansi("\x1b[1m\x1b[32mStatus: 200\x1b[0m"), "Status: 200");
+/// ```
+pub fn strip_ansi(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            output.push(c);
+            continue;
+        }
+        // CSI sequences end with a byte in the range '@'..='~'
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Returns `text` unchanged when `color` is set, or without its ANSI styles
+pub fn apply_color(text: String, color: bool) -> String {
+    if color {
+        text
+    } else {
+        strip_ansi(&text)
+    }
+}
 
 /// Pretty-prints JSON with colored syntax highlighting
 ///
 /// Uses the colored_json crate to format JSON with colored keys and values.
+/// Colors are always emitted here and stripped later if color is off.
 /// Falls back to standard pretty-printing if coloring fails.
 fn pretty_print_json_colored(value: &serde_json::Value) -> String {
-    use colored_json::{Color, ColoredFormatter, PrettyFormatter, Styler};
+    use colored_json::{Color, ColorMode, ColoredFormatter, PrettyFormatter, Styler};
     let styler = Styler {
         key: Color::Yellow.bold(),
         ..Default::default()
     };
     let formatter = ColoredFormatter::with_styler(PrettyFormatter::new(), styler);
     formatter
-        .to_colored_json_auto(value)
+        .to_colored_json(value, ColorMode::On)
         .unwrap_or_else(|_| serde_json::to_string_pretty(value).unwrap_or_default())
 }
 
+/// Returns true for an object with the `data` or `errors` keys of a GraphQL result
+fn is_graphql_result(value: &serde_json::Value) -> bool {
+    value
+        .as_object()
+        .is_some_and(|obj| obj.contains_key("data") || obj.contains_key("errors"))
+}
+
+/// Formats a GraphQL result with its errors listed before the data
+///
+/// Each error is shown as its message followed by the `path` and the first
+/// location in the query, when the server reports them. `extensions` and any
+/// other top-level keys are printed last as JSON.
+fn format_graphql_body(value: &serde_json::Value) -> String {
+    let heading =
+        |text: &str, style: Style| format!("{}{text}{}", style.render(), anstyle::Reset.render());
+    let bold = Style::new().bold();
+    let red = Style::new()
+        .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Red)))
+        .bold();
+    let mut sections = Vec::new();
+
+    let errors = value["errors"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+    if !errors.is_empty() {
+        let mut lines = vec![heading(&format!("Errors ({}):", errors.len()), red)];
+        for error in errors {
+            let message = error["message"]
+                .as_str()
+                .map_or_else(|| error.to_string(), str::to_string);
+            let mut details = Vec::new();
+            if let Some(path) = error["path"].as_array() {
+                let path: Vec<String> = path
+                    .iter()
+                    .map(|p| p.as_str().map_or_else(|| p.to_string(), str::to_string))
+                    .collect();
+                details.push(format!("at {}", path.join(".")));
+            }
+            if let Some(location) = error["locations"].get(0) {
+                details.push(format!(
+                    "line {}, column {}",
+                    location["line"], location["column"]
+                ));
+            }
+            let details = if details.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", details.join(", "))
+            };
+            lines.push(format!(
+                "  {}{details}",
+                heading(&format!("- {message}"), red)
+            ));
+        }
+        sections.push(lines.join("\n"));
+    }
+
+    let data = value.get("data").filter(|data| !data.is_null());
+    let extra = value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| *key != "data" && *key != "errors");
+    for (key, section) in data
+        .map(|data| ("data", data))
+        .into_iter()
+        .chain(extra.map(|(key, section)| (key.as_str(), section)))
+    {
+        let title = match key {
+            "data" => "Data:".to_string(),
+            other => format!("{other}:"),
+        };
+        sections.push(format!(
+            "{}\n{}",
+            heading(&title, bold),
+            pretty_print_json_colored(section)
+        ));
+    }
+    sections.join("\n")
+}
+
 /// Returns the appropriate color style for HTTP status codes
 ///
 /// Colors follow standard HTTP conventions:
@@ -82,19 +423,27 @@ fn format_header(name: &str, value: &str) -> String {
     )
 }
 
-/// Determines whether to show all headers based on verbosity and status
+/// Determines whether to show all headers based on verbosity, status and body
 ///
 /// Headers are shown when:
 /// - Verbose mode is enabled, OR
-/// - Response status indicates an error (4xx/5xx)
-fn should_show_all_headers(verbose: bool, status: u16) -> bool {
-    verbose || (400..=599).contains(&status)
+/// - Response status indicates an error (4xx/5xx), OR
+/// - The response has no body (e.g. HEAD), so headers are all there is to show
+fn should_show_all_headers(verbose: bool, status: u16, body: &str) -> bool {
+    verbose || (400..=599).contains(&status) || body.is_empty()
 }
 
-/// Formats all headers in the response
-fn format_all_headers(headers: &http::HeaderMap) -> String {
+/// Formats headers sorted by name, or only those in `only` when it is not empty
+///
+/// The sort is stable, so the values of a repeated header keep their order.
+fn format_all_headers(headers: &http::HeaderMap, only: &[http::HeaderName]) -> String {
+    let mut sorted: Vec<_> = headers
+        .iter()
+        .filter(|(name, _)| only.is_empty() || only.contains(name))
+        .collect();
+    sorted.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
     let mut output = String::new();
-    for (name, value) in headers {
+    for (name, value) in sorted {
         output.push_str(&format_header(
             name.as_str(),
             value.to_str().unwrap_or("<invalid header value>"),
@@ -105,14 +454,21 @@ fn format_all_headers(headers: &http::HeaderMap) -> String {
 
 /// Formats the headers section and returns whether headers were shown
 ///
+/// Headers picked with `--show-header` are always shown, and no others.
+///
 /// # Returns
 /// A tuple of (formatted_headers_string, headers_were_displayed)
-fn format_headers_section(resp: &HttpResponse, verbose: bool) -> (String, bool) {
+fn format_headers_section(
+    resp: &HttpResponse,
+    verbose: bool,
+    show_headers: &[http::HeaderName],
+) -> (String, bool) {
     let mut output = String::new();
-    let showed_headers = should_show_all_headers(verbose, resp.status);
+    let showed_headers =
+        !show_headers.is_empty() || should_show_all_headers(verbose, resp.status, &resp.body);
 
     if showed_headers {
-        output.push_str(&format_all_headers(&resp.headers));
+        output.push_str(&format_all_headers(&resp.headers, show_headers));
     }
 
     (output, showed_headers)
@@ -140,21 +496,41 @@ fn format_content_type_if_needed(
 
 /// Formats the response body with appropriate styling
 ///
-/// JSON content is pretty-printed with syntax highlighting.
-/// Non-JSON content is displayed with basic white coloring.
-fn format_body(body: &str, parsed_json: Option<&serde_json::Value>) -> String {
-    match parsed_json {
-        Some(json) => pretty_print_json_colored(json),
-        None => {
-            let value_style = Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::White)));
-            format!(
-                "{}{}{}\n",
-                value_style.render(),
-                body,
-                anstyle::Reset.render()
-            )
-        }
+/// JSON content is pretty-printed with syntax highlighting. XML, HTML and
+/// YAML, recognized from `content_type`, are formatted by [`Markup`]; other
+/// content, or content that does not parse as its type, is displayed with
+/// basic white coloring. Empty bodies (e.g. HEAD responses) produce no output.
+fn format_body(
+    body: &str,
+    parsed_json: Option<&serde_json::Value>,
+    content_type: Option<&str>,
+) -> String {
+    if body.is_empty() {
+        return String::new();
+    }
+    if let Some(json) = parsed_json {
+        return pretty_print_json_colored(json);
     }
+    if let Some(formatted) = content_type
+        .and_then(Markup::from_content_type)
+        .and_then(|markup| markup.format(body))
+    {
+        return formatted;
+    }
+    let value_style = Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::White)));
+    format!(
+        "{}{}{}\n",
+        value_style.render(),
+        body,
+        anstyle::Reset.render()
+    )
+}
+
+/// Returns the `Content-Type` of a message, if it is set and readable
+fn content_type(headers: &http::HeaderMap) -> Option<&str> {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
 }
 
 /// Formats an HTTP response for terminal display
@@ -164,37 +540,70 @@ fn format_body(body: &str, parsed_json: Option<&serde_json::Value>) -> String {
 /// - Headers (when appropriate)
 /// - Pretty-printed JSON or plain text body
 ///
+/// The output mode narrows this down to the status line and headers, or to
+/// the raw body with no styling at all.
+///
 /// # Arguments
 /// * `resp` - The HTTP response to format
-/// * `verbose` - Whether to show all headers regardless of status
+/// * `options` - Verbosity, output mode and whether to use color
 ///
 /// # Examples
 /// ```
 /// use wave::http::HttpResponse;
-/// use wave::printer::format_response;
+/// use wave::printer::{format_response, OutputMode, PrintOptions};
 /// use http::HeaderMap;
 ///
-/// let response = HttpResponse {
-///     status: 200,
-///     headers: HeaderMap::new(),
-///     body: r#"{"message": "success"}"#.to_string(),
-/// };
+/// let response = HttpResponse::new(200, HeaderMap::new(), r#"{"message": "success"}"#);
 ///
-/// let formatted = format_response(&response, false);
+/// let formatted = format_response(&response, PrintOptions::default());
 /// // Output includes colored status and pretty-printed JSON
+///
+/// let plain = format_response(&response, PrintOptions { color: false, ..Default::default() });
+/// assert!(plain.starts_with("Status: 200\n"));
+///
+/// let body_only = PrintOptions { mode: OutputMode::BodyOnly, ..Default::default() };
+/// let raw = format_response(&response, body_only);
+/// assert_eq!(raw, r#"{"message": "success"}"#);
 /// ```
-pub fn format_response(resp: &HttpResponse, verbose: bool) -> String {
+pub fn format_response(resp: &HttpResponse, options: PrintOptions) -> String {
+    match options.mode {
+        OutputMode::BodyOnly => return resp.body.clone(),
+        OutputMode::Raw => return format_raw_response(resp, &options.show_headers),
+        _ => {}
+    }
+    apply_color(format_styled_response(resp, &options), options.color)
+}
+
+fn format_styled_response(resp: &HttpResponse, options: &PrintOptions) -> String {
+    let PrintOptions {
+        verbose,
+        mode,
+        graphql,
+        max_body,
+        ref show_headers,
+        ..
+    } = *options;
+
     let mut output = String::new();
 
     // Format status line
     output.push_str(&format_status_line(resp.status));
 
+    if verbose {
+        output.push_str(&format_timing(resp));
+    }
+
+    if mode == OutputMode::HeadersOnly {
+        output.push_str(&format_all_headers(&resp.headers, show_headers));
+        return output;
+    }
+
     // Parse JSON once and reuse the result
     let parsed_json = serde_json::from_str::<serde_json::Value>(&resp.body).ok();
     let is_json = parsed_json.is_some();
 
     // Format headers section
-    let (headers_output, showed_headers) = format_headers_section(resp, verbose);
+    let (headers_output, showed_headers) = format_headers_section(resp, verbose, show_headers);
     output.push_str(&headers_output);
 
     // Show Content-Type if needed
@@ -205,11 +614,145 @@ pub fn format_response(resp: &HttpResponse, verbose: bool) -> String {
     ));
 
     // Format body using pre-parsed JSON
-    output.push_str(&format_body(&resp.body, parsed_json.as_ref()));
+    let body = match parsed_json.as_ref().filter(|_| graphql) {
+        Some(json) if is_graphql_result(json) => format_graphql_body(json),
+        _ => format_body(
+            &resp.body,
+            parsed_json.as_ref(),
+            content_type(&resp.headers),
+        ),
+    };
+    match max_body {
+        Some(limit) => output.push_str(&truncate_body(body, limit)),
+        None => output.push_str(&body),
+    }
 
     output
 }
 
+/// Formats a response for `--raw`: the status line, headers, a blank line
+/// and the body, with no styling, timing or pretty-printing
+///
+/// Headers are sorted by name so runs against the same server can be diffed.
+///
+/// # Examples
+/// ```
+/// use http::{HeaderMap, HeaderValue};
+/// use wave::http::HttpResponse;
+/// use wave::printer::format_raw_response;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert("x-b", HeaderValue::from_static("2"));
+/// headers.insert("content-type", HeaderValue::from_static("application/json"));
+/// let resp = HttpResponse::new(404, headers, r#"{"error":"missing"}"#);
+///
+/// assert_eq!(
+///     format_raw_response(&resp, &[]),
+///     "HTTP 404 Not Found\ncontent-type: application/json\nx-b: 2\n\n{\"error\":\"missing\"}"
+/// );
+/// ```
+pub fn format_raw_response(resp: &HttpResponse, show_headers: &[http::HeaderName]) -> String {
+    let reason = http::StatusCode::from_u16(resp.status)
+        .ok()
+        .and_then(|status| status.canonical_reason());
+    let mut output = match reason {
+        Some(reason) => format!("HTTP {} {reason}\n", resp.status),
+        None => format!("HTTP {}\n", resp.status),
+    };
+    output.push_str(&strip_ansi(&format_all_headers(
+        &resp.headers,
+        show_headers,
+    )));
+    output.push('\n');
+    output.push_str(&resp.body);
+    output
+}
+
+/// Cuts a formatted body after `limit` bytes of text and says how much was left out
+///
+/// The cut is made at the end of the last line that fits, so styles stay
+/// intact. A first line longer than the limit, such as minified JSON, is cut
+/// in the middle and loses its styles.
+///
+/// # Examples
+/// ```
+/// use wave::printer::{strip_ansi, truncate_body};
+///
+/// let body = "line 1\nline 2\nline 3\n".to_string();
+/// assert_eq!(truncate_body(body.clone(), 100), body);
+/// assert_eq!(
+///     strip_ansi(&truncate_body(body, 15)),
+///     "line 1\nline 2\n… truncated, 6 more bytes (use --full to show everything, or -o FILE to save it)"
+/// );
+/// ```
+pub fn truncate_body(body: String, limit: usize) -> String {
+    // The newline that ends a printed body is not part of it
+    let total = strip_ansi(&body).trim_end_matches('\n').len();
+    if total <= limit {
+        return body;
+    }
+    let mut shown = String::new();
+    let mut visible = 0;
+    for line in body.split_inclusive('\n') {
+        let plain = strip_ansi(line);
+        if visible + plain.len() > limit {
+            if visible == 0 {
+                let mut end = limit;
+                while !plain.is_char_boundary(end) {
+                    end -= 1;
+                }
+                shown.push_str(&plain[..end]);
+                visible = end;
+            }
+            break;
+        }
+        shown.push_str(line);
+        visible += plain.len();
+    }
+    if !shown.ends_with('\n') {
+        shown.push('\n');
+    }
+    let style = Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::Yellow)));
+    format!(
+        "{shown}{}{}… truncated, {} more bytes (use --full to show everything, or -o FILE to save it){}",
+        anstyle::Reset.render(),
+        style.render(),
+        total - visible,
+        anstyle::Reset.render()
+    )
+}
+
+/// Parses a size in bytes, with an optional `k`, `m` or `g` suffix (powers of 1024)
+///
+/// # Examples
+/// ```
+/// use wave::printer::parse_size;
+///
+/// assert_eq!(parse_size("512"), Ok(512));
+/// assert_eq!(parse_size("64k"), Ok(64 * 1024));
+/// assert_eq!(parse_size("2MB"), Ok(2 * 1024 * 1024));
+/// assert!(parse_size("0").is_err());
+/// assert!(parse_size("lots").is_err());
+/// ```
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let lower = s.trim().to_ascii_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier = match lower[digits.len()..].trim_end_matches('b') {
+        "" => 1,
+        "k" | "ki" => 1024,
+        "m" | "mi" => 1024 * 1024,
+        "g" | "gi" => 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid size '{s}', expected e.g. 4096, 64k or 1m")),
+    };
+    match digits.trim().parse::<usize>() {
+        Ok(0) => Err("size must be greater than 0".to_string()),
+        Ok(n) => n
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("size '{s}' is too large")),
+        Err(_) => Err(format!("invalid size '{s}', expected e.g. 4096, 64k or 1m")),
+    }
+}
+
 /// Prints an HTTP response result to stdout
 ///
 /// Handles both successful responses and errors, formatting them appropriately
@@ -217,25 +760,233 @@ pub fn format_response(resp: &HttpResponse, verbose: bool) -> String {
 ///
 /// # Arguments
 /// * `result` - The HTTP response result (success or error)
-/// * `verbose` - Whether to show all headers in successful responses
+/// * `options` - Verbosity, output mode and whether to use color
 ///
 /// # Examples
 /// ```
 /// use wave::http::{HttpResponse, HttpError};
-/// use wave::printer::print_response;
+/// use wave::printer::{print_response, PrintOptions};
 /// use http::HeaderMap;
 ///
-/// let response = Ok(HttpResponse {
-///     status: 200,
-///     headers: HeaderMap::new(),
-///     body: "Hello, World!".to_string(),
-/// });
+/// let response = Ok(HttpResponse::new(200, HeaderMap::new(), "Hello, World!"));
 ///
-/// print_response(response, false);
+/// print_response(response, PrintOptions::default());
 /// // Prints formatted response to stdout
 /// ```
-pub fn print_response(result: Result<HttpResponse, HttpError>, verbose: bool) {
-    let _ = print_response_to(&mut io::stdout(), result, verbose);
+pub fn print_response(result: Result<HttpResponse, HttpError>, options: PrintOptions) {
+    let _ = print_response_to(&mut io::stdout(), result, options);
+}
+
+/// Prints an HTTP response result through the user's pager
+///
+/// The pager is `$PAGER`, or `less` when it is not set. Like git, `LESS` is
+/// set to `FRX` unless already set, so colors are shown and output that fits
+/// on one screen is printed as usual. Falls back to [`print_response`] if
+/// the pager cannot be started.
+pub fn page_response(result: Result<HttpResponse, HttpError>, options: PrintOptions) {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or("less");
+    let mut command = std::process::Command::new(program);
+    command.args(words).stdin(std::process::Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    match command.spawn() {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // A pager closed early makes the write fail, which is fine
+                let _ = print_response_to(&mut stdin, result, options);
+            }
+            let _ = child.wait();
+        }
+        Err(_) => print_response(result, options),
+    }
+}
+
+/// Formats an HTTP request for terminal display
+///
+/// Used by `--dry-run` to preview exactly what would be sent: a bold
+/// request line, the headers, and the body (pretty-printed when it is JSON).
+///
+/// # Examples
+/// ```
+/// use wave::http::HttpRequest;
+/// use wave::printer::format_request;
+/// use http::Method;
+///
+/// let req = HttpRequest::builder("https://api.example.com/users", Method::GET)
+///     .header("Accept", "application/json")
+///     .build();
+///
+/// let formatted = format_request(&req);
+/// assert!(formatted.contains("GET https://api.example.com/users"));
+/// assert!(formatted.contains("accept: "));
+/// ```
+pub fn format_request(req: &HttpRequest) -> String {
+    let mut output = format!(
+        "{}{} {}{}\n",
+        Style::new().bold().render(),
+        req.method,
+        req.url,
+        anstyle::Reset.render()
+    );
+    output.push_str(&format_all_headers(&req.headers, &[]));
+    if let Some(body) = &req.body {
+        let parsed_json = serde_json::from_str::<serde_json::Value>(body).ok();
+        output.push('\n');
+        output.push_str(&format_body(
+            body,
+            parsed_json.as_ref(),
+            content_type(&req.headers),
+        ));
+        // Pretty-printed JSON has no final newline
+        if !output.ends_with('\n') {
+            output.push('\n');
+        }
+    }
+    if let Some(path) = &req.body_file {
+        output.push('\n');
+        output.push_str(&format!("@{}\n", path.display()));
+    }
+    if let Some(parts) = &req.multipart {
+        output.push('\n');
+        for part in parts {
+            match part {
+                MultipartPart::Text { name, value } => {
+                    output.push_str(&format_header(name, value));
+                }
+                MultipartPart::File { name, path } => {
+                    output.push_str(&format_header(name, &format!("@{}", path.display())));
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Prints the outgoing request, followed by a blank line separating it from the response
+///
+/// Used by `--verbose` so the resolved URL, headers (including automatically
+/// added ones like `Content-Type`) and body can be checked against what the
+/// server returned.
+pub fn print_request(req: &HttpRequest, color: bool) {
+    println!("{}", apply_color(format_request(req), color));
+}
+
+/// Formats the summary shown after a response body is saved to a file
+///
+/// Used by `--output`: the body is streamed to disk untouched, bypassing the
+/// colored printer, so only the status line and a byte count are shown. A
+/// download resumed with `--continue` says where it picked up.
+pub fn format_download_summary(
+    status: u16,
+    written: u64,
+    path: &Path,
+    resumed_at: Option<u64>,
+) -> String {
+    let status_line = format_status_line(status);
+    match resumed_at {
+        Some(offset) => format!(
+            "{status_line}Saved {written} more bytes to {} (resumed at byte {offset})",
+            path.display()
+        ),
+        None => format!("{status_line}Saved {written} bytes to {}", path.display()),
+    }
+}
+
+/// Prints the download summary to stderr to keep stdout clean
+pub fn print_download_summary(
+    status: u16,
+    written: u64,
+    path: &Path,
+    resumed_at: Option<u64>,
+    color: bool,
+) {
+    let summary = format_download_summary(status, written, path, resumed_at);
+    eprintln!("{}", apply_color(summary, color));
+}
+
+/// Formats the messages of a gRPC call, decoded to JSON
+///
+/// Messages are pretty-printed one after another, in the order a server
+/// stream delivered them. Verbose output adds the response headers before
+/// them and the trailers after; `--headers-only` shows just those, and
+/// `--body-only` prints each message as compact JSON on its own line.
+///
+/// # Examples
+/// ```
+/// use http::HeaderMap;
+/// use serde_json::json;
+/// use wave::printer::{format_grpc_reply, OutputMode, PrintOptions};
+///
+/// let messages = [json!({"message": "Hello alice"}), json!({"message": "Hello bob"})];
+/// let options = PrintOptions { mode: OutputMode::BodyOnly, ..Default::default() };
+/// let output = format_grpc_reply(&HeaderMap::new(), &messages, &HeaderMap::new(), options);
+/// assert_eq!(output, "{\"message\":\"Hello alice\"}\n{\"message\":\"Hello bob\"}\n");
+/// ```
+pub fn format_grpc_reply(
+    headers: &http::HeaderMap,
+    messages: &[serde_json::Value],
+    trailers: &http::HeaderMap,
+    options: PrintOptions,
+) -> String {
+    if options.mode == OutputMode::BodyOnly {
+        return messages.iter().map(|m| format!("{m}\n")).collect();
+    }
+    let show_metadata = options.verbose || options.mode != OutputMode::Full;
+    let mut sections = Vec::new();
+    if show_metadata && !headers.is_empty() {
+        sections.push(format_all_headers(headers, &[]));
+    }
+    match options.mode {
+        OutputMode::Full => sections.extend(
+            messages
+                .iter()
+                .map(|m| format!("{}\n", pretty_print_json_colored(m))),
+        ),
+        OutputMode::Raw => sections.push(messages.iter().map(|m| format!("{m}\n")).collect()),
+        _ => {}
+    }
+    if show_metadata && !trailers.is_empty() {
+        sections.push(format_all_headers(trailers, &[]));
+    }
+    apply_color(
+        sections.join("\n"),
+        options.color && options.mode != OutputMode::Raw,
+    )
+}
+
+/// Formats the services and methods of a gRPC server, one method per line
+///
+/// Streaming requests and responses are marked with `stream`, as in the
+/// `.proto` syntax.
+pub fn format_grpc_services(services: &[&crate::grpc::Service], color: bool) -> String {
+    let bold = Style::new().bold();
+    let mut output = String::new();
+    for service in services {
+        output.push_str(&format!(
+            "{}{}{}\n",
+            bold.render(),
+            service.name,
+            anstyle::Reset.render()
+        ));
+        for method in &service.methods {
+            let stream = |streaming: bool| if streaming { "stream " } else { "" };
+            output.push_str(&format!(
+                "  {}({}{}) returns ({}{})\n",
+                method.name,
+                stream(method.client_streaming),
+                method.input,
+                stream(method.server_streaming),
+                method.output
+            ));
+        }
+    }
+    apply_color(output, color)
 }
 
 /// Prints an HTTP response result to any writer
@@ -246,28 +997,33 @@ pub fn print_response(result: Result<HttpResponse, HttpError>, verbose: bool) {
 /// # Arguments
 /// * `writer` - The output destination
 /// * `result` - The HTTP response result
-/// * `verbose` - Whether to show all headers
+/// * `options` - Verbosity, output mode and whether to use color
 ///
 /// # Errors
 /// Returns IO errors from the underlying writer
 fn print_response_to<W: Write>(
     writer: &mut W,
     result: Result<HttpResponse, HttpError>,
-    verbose: bool,
+    options: PrintOptions,
 ) -> io::Result<()> {
     match result {
         Ok(resp) => {
-            writeln!(writer, "{}", format_response(&resp, verbose))
+            let raw = matches!(options.mode, OutputMode::BodyOnly | OutputMode::Raw);
+            let output = format_response(&resp, options);
+            // Raw bodies that already end in a newline are written untouched
+            if raw && (output.is_empty() || output.ends_with('\n')) {
+                write!(writer, "{output}")
+            } else {
+                writeln!(writer, "{output}")
+            }
         }
         Err(e) => {
             let style = Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::Red)));
-            writeln!(
-                writer,
-                "{}Error: {}{}",
-                style.render(),
-                e,
-                anstyle::Reset.render()
-            )
+            let mut message = format!("{}Error: {}{}", style.render(), e, anstyle::Reset.render());
+            if let Some(suggestion) = e.suggestion() {
+                message.push_str(&format!("\nSuggestion: {suggestion}"));
+            }
+            writeln!(writer, "{}", apply_color(message, options.color))
         }
     }
 }
@@ -275,16 +1031,13 @@ fn print_response_to<W: Write>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::RequestFailure;
     use http::HeaderMap;
 
     #[test]
     fn test_format_status_color_2xx() {
-        let resp = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: "{}".to_string(),
-        };
-        let output = format_response(&resp, false);
+        let resp = HttpResponse::new(200, HeaderMap::new(), "{}");
+        let output = format_response(&resp, PrintOptions::default());
         assert!(output.contains("Status: 200"));
         assert!(output.contains(
             &anstyle::Style::new()
@@ -296,12 +1049,8 @@ mod tests {
 
     #[test]
     fn test_format_status_color_4xx() {
-        let resp = HttpResponse {
-            status: 404,
-            headers: HeaderMap::new(),
-            body: "{}".to_string(),
-        };
-        let output = format_response(&resp, false);
+        let resp = HttpResponse::new(404, HeaderMap::new(), "{}");
+        let output = format_response(&resp, PrintOptions::default());
         assert!(output.contains("Status: 404"));
         assert!(output.contains(
             &anstyle::Style::new()
@@ -314,12 +1063,8 @@ mod tests {
     #[test]
     fn test_format_pretty_print_json() {
         let body = r#"{\"foo\":1,\"bar\":{\"baz\":2}}"#;
-        let resp = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: body.to_string(),
-        };
-        let output = format_response(&resp, false);
+        let resp = HttpResponse::new(200, HeaderMap::new(), body);
+        let output = format_response(&resp, PrintOptions::default());
         assert!(output.contains("foo"));
         assert!(output.contains("bar"));
         assert!(output.contains("baz"));
@@ -336,30 +1081,76 @@ mod tests {
             http::HeaderValue::from_static("application/json"),
         );
 
-        let resp = HttpResponse {
-            status: 200,
-            headers,
-            body: "{}".to_string(),
-        };
-        let output = format_response(&resp, true);
+        let resp = HttpResponse::new(200, headers, "{}");
+        let output = format_response(&resp, PrintOptions::verbose());
         assert!(output.contains("content-type: "));
         assert!(output.contains("application/json"));
     }
 
+    #[test]
+    fn test_format_headers_sorted_by_name() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", http::HeaderValue::from_static("9"));
+        headers.insert("content-type", http::HeaderValue::from_static("text/plain"));
+        headers.append("set-cookie", http::HeaderValue::from_static("b=2"));
+        headers.append("set-cookie", http::HeaderValue::from_static("a=1"));
+
+        let resp = HttpResponse::new(200, headers, "ok");
+        let plain = PrintOptions {
+            color: false,
+            ..PrintOptions::verbose()
+        };
+        assert_eq!(
+            format_response(&resp, plain.clone()),
+            "Status: 200\ncontent-type: text/plain\nset-cookie: b=2\nset-cookie: a=1\nx-ratelimit-remaining: 9\nok\n"
+        );
+
+        // Picked headers are shown without --verbose, and no others
+        let picked = PrintOptions {
+            verbose: false,
+            show_headers: vec![http::HeaderName::from_static("x-ratelimit-remaining")],
+            ..plain
+        };
+        assert_eq!(
+            format_response(&resp, picked.clone()),
+            "Status: 200\nx-ratelimit-remaining: 9\nok\n"
+        );
+        let headers_only = PrintOptions {
+            mode: OutputMode::HeadersOnly,
+            ..picked
+        };
+        assert_eq!(
+            format_response(&resp, headers_only),
+            "Status: 200\nx-ratelimit-remaining: 9\n"
+        );
+    }
+
     #[test]
     fn test_format_content_type_if_not_json() {
         let mut headers = HeaderMap::new();
         headers.insert("content-type", "text/html".parse().unwrap());
 
-        let resp = HttpResponse {
-            status: 200,
-            headers,
-            body: "<html></html>".to_string(),
-        };
-        let output = format_response(&resp, false);
+        let resp = HttpResponse::new(200, headers, "<html></html>");
+        let output = format_response(&resp, PrintOptions::default());
         assert!(output.contains("Content-Type: "));
         assert!(output.contains("text/html"));
-        assert!(output.contains("<html></html>"));
+        assert!(strip_ansi(&output).contains("<html></html>"));
+    }
+
+    #[test]
+    fn test_format_markup_bodies_by_content_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/xml".parse().unwrap());
+        let resp = HttpResponse::new(200, headers.clone(), "<a><b>1</b></a>");
+        let plain = PrintOptions {
+            color: false,
+            ..Default::default()
+        };
+        assert!(format_response(&resp, plain.clone()).ends_with("<a>\n  <b>1</b>\n</a>\n"));
+
+        // Bodies that do not parse as their content type are printed as they are
+        let resp = HttpResponse::new(200, headers, "<a>");
+        assert!(format_response(&resp, plain).ends_with("\n<a>\n"));
     }
 
     #[test]
@@ -371,12 +1162,8 @@ mod tests {
         );
         headers.insert("x-error", http::HeaderValue::from_static("Not Found"));
 
-        let resp = HttpResponse {
-            status: 404,
-            headers,
-            body: "{}".to_string(),
-        };
-        let output = format_response(&resp, false);
+        let resp = HttpResponse::new(404, headers, "{}");
+        let output = format_response(&resp, PrintOptions::default());
         assert!(output.contains("content-type: "));
         assert!(output.contains("application/json"));
         assert!(output.contains("x-error: "));
@@ -384,15 +1171,303 @@ mod tests {
     }
 
     #[test]
-    fn test_print_response_to_writer_trailing_newline() {
-        let resp = HttpResponse {
-            status: 200,
-            headers: HeaderMap::new(),
-            body: "hello".to_string(),
+    fn test_format_headers_only_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-length", http::HeaderValue::from_static("512"));
+        headers.insert(
+            "allow",
+            http::HeaderValue::from_static("GET, HEAD, OPTIONS"),
+        );
+
+        let resp = HttpResponse::new(200, headers, "");
+        let output = format_response(&resp, PrintOptions::default());
+        assert!(output.contains("content-length: "));
+        assert!(output.contains("allow: "));
+        assert!(output.contains("GET, HEAD, OPTIONS"));
+        assert!(output.ends_with(&format!("{}\n", anstyle::Reset.render())));
+    }
+
+    #[test]
+    fn test_format_response_headers_only() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-trace", http::HeaderValue::from_static("abc"));
+
+        let resp = HttpResponse::new(200, headers, r#"{"secret":1}"#);
+        let output = format_response(
+            &resp,
+            PrintOptions {
+                mode: OutputMode::HeadersOnly,
+                ..Default::default()
+            },
+        );
+        assert!(output.contains("Status: 200"));
+        assert!(output.contains("x-trace: "));
+        assert!(!output.contains("secret"));
+    }
+
+    #[test]
+    fn test_format_response_body_only_is_raw() {
+        let resp = HttpResponse::new(404, HeaderMap::new(), r#"{"a":1}"#);
+        let body_only = PrintOptions {
+            mode: OutputMode::BodyOnly,
+            ..PrintOptions::verbose()
+        };
+        assert_eq!(format_response(&resp, body_only.clone()), r#"{"a":1}"#);
+
+        let mut buf = Vec::new();
+        let resp = HttpResponse::new(200, HeaderMap::new(), "line\n");
+        print_response_to(&mut buf, Ok(resp), body_only).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "line\n");
+    }
+
+    #[test]
+    fn test_format_response_without_color() {
+        let resp = HttpResponse::new(404, HeaderMap::new(), r#"{"error":"missing"}"#);
+        let plain = PrintOptions {
+            color: false,
+            ..Default::default()
         };
+        let output = format_response(&resp, plain.clone());
+        assert!(!output.contains('\x1b'));
+        assert!(output.starts_with("Status: 404\n"));
+        assert!(output.contains(r#""error": "missing""#));
+
+        let mut buf = Vec::new();
+        let down = HttpError::Network(RequestFailure::new(
+            http::Method::GET,
+            "https://example.com",
+            "down",
+        ));
+        print_response_to(&mut buf, Err(down), plain.clone()).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "Error: Network error: GET https://example.com: down\n"
+        );
+
         let mut buf = Vec::new();
-        print_response_to(&mut buf, Ok(resp), false).unwrap();
+        let refused = HttpError::Connect(RequestFailure::new(
+            http::Method::GET,
+            "http://localhost:9",
+            "refused",
+        ));
+        print_response_to(&mut buf, Err(refused), plain).unwrap();
+        let printed = String::from_utf8(buf).unwrap();
+        assert!(printed.starts_with("Error: Connection failed: GET http://localhost:9: refused\n"));
+        assert!(printed.contains("\nSuggestion: Is the host reachable"));
+    }
+
+    #[test]
+    fn test_response_report_non_json_and_errors() {
+        let req = HttpRequest::builder("https://example.com", http::Method::POST).build();
+        let mut headers = HeaderMap::new();
+        headers.append("set-cookie", http::HeaderValue::from_static("a=1"));
+        headers.append("set-cookie", http::HeaderValue::from_static("b=2"));
+        let resp = HttpResponse::new(201, headers, "created");
+
+        let report = ResponseReport::new(&req, &Ok(resp), Duration::from_millis(3));
+        assert_eq!(report.status, Some(201));
+        assert_eq!(report.headers["set-cookie"], "a=1, b=2");
+        assert_eq!(report.body, serde_json::json!("created"));
+        assert!(!report.to_json().contains("error"));
+
+        let failed = ResponseReport::new(
+            &req,
+            &Err(HttpError::Connect(RequestFailure::new(
+                http::Method::POST,
+                "https://example.com",
+                "refused",
+            ))),
+            Duration::ZERO,
+        );
+        let json: serde_json::Value = serde_json::from_str(&failed.to_json()).unwrap();
+        assert_eq!(
+            json["error"],
+            "Connection failed: POST https://example.com: refused"
+        );
+        assert!(json.get("status").is_none());
+        assert_eq!(json["method"], "POST");
+    }
+
+    #[test]
+    fn test_graphql_output_separates_errors_and_data() {
+        let body = r#"{"errors":[{"message":"Not allowed","path":["user","email"],"locations":[{"line":1,"column":9}]}],"data":{"user":{"name":"Ann","email":null}}}"#;
+        let resp = HttpResponse::new(200, HeaderMap::new(), body);
+        let graphql = PrintOptions {
+            color: false,
+            graphql: true,
+            ..Default::default()
+        };
+        let output = format_response(&resp, graphql.clone());
+        let errors = output.find("Errors (1):").expect("Test: errors section");
+        let data = output.find("Data:").expect("Test: data section");
+        assert!(errors < data);
+        assert!(output.contains("  - Not allowed (at user.email, line 1, column 9)"));
+        assert!(output.contains("\"name\": \"Ann\""));
+
+        let plain = format_response(
+            &resp,
+            PrintOptions {
+                graphql: false,
+                ..graphql
+            },
+        );
+        assert!(!plain.contains("Errors (1):"));
+    }
+
+    #[test]
+    fn test_format_response_truncates_long_bodies() {
+        let resp = HttpResponse::new(200, HeaderMap::new(), r#"{"a": 1, "b": 2}"#);
+        let limited = PrintOptions {
+            color: false,
+            max_body: Some(12),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_response(&resp, limited.clone()),
+            "Status: 200\n{\n  \"a\": 1,\n… truncated, 10 more bytes (use --full to show everything, or -o FILE to save it)"
+        );
+
+        // One long line is cut in the middle
+        let resp = HttpResponse::new(200, HeaderMap::new(), "é".repeat(10));
+        let output = format_response(
+            &resp,
+            PrintOptions {
+                max_body: Some(5),
+                ..limited.clone()
+            },
+        );
+        assert!(output.starts_with("Status: 200\néé\n… truncated, 16 more bytes"));
+
+        // Raw bodies are never cut
+        let body_only = PrintOptions {
+            mode: OutputMode::BodyOnly,
+            ..limited
+        };
+        assert_eq!(format_response(&resp, body_only), "é".repeat(10));
+    }
+
+    #[test]
+    fn test_verbose_output_shows_timing() {
+        let resp =
+            HttpResponse::new(200, HeaderMap::new(), "{}").with_timing(crate::http::Timing {
+                ttfb: Duration::from_millis(5),
+                total: Duration::from_millis(9),
+            });
+        let plain = PrintOptions {
+            color: false,
+            ..Default::default()
+        };
+        assert!(!format_response(&resp, plain.clone()).contains("Time:"));
+        let verbose = format_response(
+            &resp,
+            PrintOptions {
+                verbose: true,
+                ..plain
+            },
+        );
+        assert!(verbose.starts_with("Status: 200\nTime: 9 ms (first byte 5 ms), size: 2 bytes\n"));
+
+        let req = HttpRequest::builder("https://example.com", http::Method::GET).build();
+        let report = ResponseReport::new(&req, &Ok(resp), Duration::from_millis(11));
+        assert_eq!(report.time_ms, 11);
+        assert_eq!(report.ttfb_ms, Some(5));
+        assert_eq!(report.size, Some(2));
+        assert_eq!(report.encoded_size, None);
+    }
+
+    #[test]
+    fn test_verbose_output_shows_encoded_size() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", "deflate".parse().unwrap());
+        let raw = vec![0x78, 0x9c, 0xcb, 0xc8, 4, 0, 1, 0x3b, 0, 0xd2];
+        let resp = HttpResponse::new(200, headers, raw)
+            .with_timing(crate::http::Timing::default())
+            .decompress()
+            .unwrap();
+        let verbose = PrintOptions {
+            verbose: true,
+            color: false,
+            ..Default::default()
+        };
+        let output = format_response(&resp, verbose);
+        assert!(output.contains("size: 2 bytes (10 bytes deflate encoded)\n"));
+        assert!(output.contains("content-encoding: deflate\n"));
+        assert!(output.ends_with("hi\n"));
+
+        let req = HttpRequest::builder("https://example.com", http::Method::GET).build();
+        let report = ResponseReport::new(&req, &Ok(resp), Duration::from_millis(1));
+        assert_eq!((report.size, report.encoded_size), (Some(2), Some(10)));
+    }
+
+    #[test]
+    fn test_format_request_preview() {
+        let req = HttpRequest::builder("https://example.com/users", http::Method::POST)
+            .header("X-Trace", "abc")
+            .body(crate::http::RequestBody::text("hello".to_string()))
+            .build();
+        let output = format_request(&req);
+        assert!(output.contains("POST https://example.com/users"));
+        assert!(output.contains("x-trace: "));
+        assert!(output.contains("content-type: "));
+        assert!(output.contains("hello"));
+
+        let req = HttpRequest::builder("https://example.com/users", http::Method::POST)
+            .header("Authorization", "Bearer abc")
+            .body(crate::http::RequestBody::Json(
+                serde_json::json!({"name": "ann"}),
+            ))
+            .build();
+        assert_eq!(
+            strip_ansi(&format_request(&req)),
+            "POST https://example.com/users\nauthorization: Bearer abc\ncontent-type: application/json\n\n{\n  \"name\": \"ann\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_download_summary() {
+        let summary = format_download_summary(200, 1234, Path::new("image.png"), None);
+        assert!(summary.contains("200"));
+        assert!(summary.ends_with("Saved 1234 bytes to image.png"));
+        let resumed = format_download_summary(206, 10, Path::new("image.png"), Some(1234));
+        assert!(resumed.ends_with("Saved 10 more bytes to image.png (resumed at byte 1234)"));
+    }
+
+    #[test]
+    fn test_print_response_to_writer_trailing_newline() {
+        let resp = HttpResponse::new(200, HeaderMap::new(), "hello");
+        let mut buf = Vec::new();
+        print_response_to(&mut buf, Ok(resp), PrintOptions::default()).unwrap();
         let output = String::from_utf8(buf).unwrap();
         assert!(output.ends_with('\n'));
     }
+
+    #[test]
+    fn test_raw_output_is_unstyled_and_exact() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-trace", http::HeaderValue::from_static("abc"));
+        headers.insert(
+            "content-type",
+            http::HeaderValue::from_static("application/json"),
+        );
+        let raw = PrintOptions {
+            mode: OutputMode::Raw,
+            max_body: Some(2),
+            ..PrintOptions::verbose()
+        };
+
+        let resp = HttpResponse::new(200, headers.clone(), "{\"a\": 1}\n");
+        let mut buf = Vec::new();
+        print_response_to(&mut buf, Ok(resp), raw.clone()).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "HTTP 200 OK\ncontent-type: application/json\nx-trace: abc\n\n{\"a\": 1}\n"
+        );
+
+        let resp = HttpResponse::new(599, headers, "");
+        let picked = PrintOptions {
+            show_headers: vec![http::HeaderName::from_static("x-trace")],
+            ..raw
+        };
+        assert_eq!(format_response(&resp, picked), "HTTP 599\nx-trace: abc\n\n");
+    }
 }
@@ -0,0 +1,370 @@
+//! JSONPath-style queries over JSON response bodies
+//!
+//! Used by `--filter` to print part of a response, and by collection
+//! `expect:` and `capture:` blocks to pick values out of a body. The supported
+//! syntax is a practical subset of JSONPath:
+//!
+//! | Expression        | Selects                                     |
+//! |-------------------|---------------------------------------------|
+//! | `$`               | the whole document                          |
+//! | `.name`, `name`   | an object member                            |
+//! | `['some key']`    | an object member with any characters        |
+//! | `[0]`, `.0`       | an array element (`[-1]` is the last one)   |
+//! | `[*]`, `.*`       | every member or element                     |
+//! | `..name`          | `name` at any depth                         |
+//!
+//! The leading `$` is optional, so the dotted paths used in collections
+//! (`user.name`, `items.0.id`) are valid queries.
+
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// How a step picks children of the current value
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    /// Object member by name, or array element when the name is an index
+    Key(String),
+    /// Array element, counting from the end when negative
+    Index(i64),
+    /// Every member or element
+    Wildcard,
+}
+
+/// One step of a query
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// Applies to the direct children of the current values
+    Child(Selector),
+    /// Applies to the current values and all of their descendants
+    Descendant(Selector),
+}
+
+/// A parsed query
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use wave::query::Query;
+///
+/// let body = json!({"data": [{"email": "a@x.io"}, {"email": "b@x.io"}]});
+/// let query = Query::parse("$.data[*].email").unwrap();
+/// assert_eq!(query.apply(&body), json!(["a@x.io", "b@x.io"]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    source: String,
+    steps: Vec<Step>,
+}
+
+impl Query {
+    /// Parses a query expression
+    ///
+    /// # Errors
+    /// Returns a description of the problem for malformed expressions
+    pub fn parse(expr: &str) -> Result<Query, String> {
+        let source = expr.trim();
+        let invalid = |reason: &str| format!("Invalid query '{source}': {reason}");
+        let mut rest = source.strip_prefix('$').unwrap_or(source);
+        let mut steps = Vec::new();
+        let mut first = rest.len() == source.len();
+
+        while !rest.is_empty() {
+            let descendant = rest.starts_with("..");
+            if descendant {
+                rest = &rest[2..];
+            } else if let Some(after) = rest.strip_prefix('.') {
+                rest = after;
+            } else if !first && !rest.starts_with('[') {
+                return Err(invalid("expected '.' or '['"));
+            }
+            first = false;
+
+            let selector = if let Some(after) = rest.strip_prefix('[') {
+                let end = bracket_end(after).ok_or_else(|| invalid("unclosed '['"))?;
+                rest = &after[end + 1..];
+                parse_bracket(after[..end].trim()).ok_or_else(|| invalid("bad selector in []"))?
+            } else {
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                let name = &rest[..end];
+                rest = &rest[end..];
+                match name {
+                    "" => return Err(invalid("empty name")),
+                    "*" => Selector::Wildcard,
+                    name => Selector::Key(name.to_string()),
+                }
+            };
+            steps.push(if descendant {
+                Step::Descendant(selector)
+            } else {
+                Step::Child(selector)
+            });
+        }
+
+        Ok(Query {
+            source: source.to_string(),
+            steps,
+        })
+    }
+
+    /// Returns true if the query selects at most one value
+    pub fn is_singular(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| matches!(step, Step::Child(Selector::Key(_) | Selector::Index(_))))
+    }
+
+    /// Returns every value the query selects, in document order
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![value];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for value in current {
+                match step {
+                    Step::Child(selector) => select_children(value, selector, &mut next),
+                    Step::Descendant(selector) => select_descendants(value, selector, &mut next),
+                }
+            }
+            current = next;
+        }
+        current
+    }
+
+    /// Calls `f` on every value the query selects, so it can be changed in place
+    pub fn for_each_mut(&self, value: &mut Value, f: &mut dyn FnMut(&mut Value)) {
+        visit_mut(value, &self.steps, f);
+    }
+
+    /// Applies the query the way `--filter` prints it
+    ///
+    /// Singular queries yield the selected value (`null` if there is none);
+    /// queries with wildcards or `..` yield an array of every match.
+    pub fn apply(&self, value: &Value) -> Value {
+        let selected = self.select(value);
+        if self.is_singular() {
+            selected.first().map_or(Value::Null, |v| (*v).clone())
+        } else {
+            Value::Array(selected.into_iter().cloned().collect())
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Looks up a path in a JSON value for assertions and captures
+///
+/// Returns `Ok(None)` when a singular path matches nothing; paths with
+/// wildcards always match, yielding an array.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use wave::query::lookup;
+///
+/// let body = json!({"items": [{"id": 7}]});
+/// assert_eq!(lookup(&body, "items.0.id"), Ok(Some(json!(7))));
+/// assert_eq!(lookup(&body, "$.items[0].id"), Ok(Some(json!(7))));
+/// assert_eq!(lookup(&body, "$.items[*].id"), Ok(Some(json!([7]))));
+/// assert_eq!(lookup(&body, "items.1.id"), Ok(None));
+/// ```
+pub fn lookup(value: &Value, path: &str) -> Result<Option<Value>, String> {
+    let query = Query::parse(path)?;
+    if query.is_singular() {
+        Ok(query.select(value).first().map(|v| (*v).clone()))
+    } else {
+        Ok(Some(query.apply(value)))
+    }
+}
+
+fn select_children<'a>(value: &'a Value, selector: &Selector, out: &mut Vec<&'a Value>) {
+    match (selector, value) {
+        (Selector::Key(key), Value::Object(map)) => out.extend(map.get(key)),
+        (Selector::Key(key), Value::Array(items)) => {
+            out.extend(key.parse::<usize>().ok().and_then(|i| items.get(i)));
+        }
+        (Selector::Index(index), Value::Array(items)) => {
+            out.extend(array_index(*index, items.len()).and_then(|i| items.get(i)));
+        }
+        (Selector::Wildcard, Value::Object(map)) => out.extend(map.values()),
+        (Selector::Wildcard, Value::Array(items)) => out.extend(items),
+        _ => {}
+    }
+}
+
+fn children_mut<'a>(value: &'a mut Value, selector: &Selector) -> Vec<&'a mut Value> {
+    match (selector, value) {
+        (Selector::Key(key), Value::Object(map)) => map.get_mut(key).into_iter().collect(),
+        (Selector::Key(key), Value::Array(items)) => key
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| items.get_mut(i))
+            .into_iter()
+            .collect(),
+        (Selector::Index(index), Value::Array(items)) => array_index(*index, items.len())
+            .and_then(|i| items.get_mut(i))
+            .into_iter()
+            .collect(),
+        (Selector::Wildcard, Value::Object(map)) => map.values_mut().collect(),
+        (Selector::Wildcard, Value::Array(items)) => items.iter_mut().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn visit_mut(value: &mut Value, steps: &[Step], f: &mut dyn FnMut(&mut Value)) {
+    let Some((step, rest)) = steps.split_first() else {
+        return f(value);
+    };
+    match step {
+        Step::Child(selector) => {
+            for child in children_mut(value, selector) {
+                visit_mut(child, rest, f);
+            }
+        }
+        Step::Descendant(selector) => {
+            for child in children_mut(value, selector) {
+                visit_mut(child, rest, f);
+            }
+            for child in children_mut(value, &Selector::Wildcard) {
+                visit_mut(child, steps, f);
+            }
+        }
+    }
+}
+
+/// Resolves an index that counts from the end when negative
+fn array_index(index: i64, len: usize) -> Option<usize> {
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize)
+    }
+}
+
+fn select_descendants<'a>(value: &'a Value, selector: &Selector, out: &mut Vec<&'a Value>) {
+    select_children(value, selector, out);
+    let children: Box<dyn Iterator<Item = &'a Value>> = match value {
+        Value::Object(map) => Box::new(map.values()),
+        Value::Array(items) => Box::new(items.iter()),
+        _ => return,
+    };
+    for child in children {
+        select_descendants(child, selector, out);
+    }
+}
+
+/// Finds the `]` closing a bracket, skipping over quoted names
+fn bracket_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ']') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_bracket(inner: &str) -> Option<Selector> {
+    if inner == "*" {
+        return Some(Selector::Wildcard);
+    }
+    for quote in ['\'', '"'] {
+        if let Some(name) = inner
+            .strip_prefix(quote)
+            .and_then(|s| s.strip_suffix(quote))
+        {
+            return Some(Selector::Key(name.to_string()));
+        }
+    }
+    inner.parse().ok().map(Selector::Index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn body() -> Value {
+        json!({
+            "data": [
+                {"email": "a@x.io", "tags": ["admin"]},
+                {"email": "b@x.io", "tags": []}
+            ],
+            "meta": {"total": 2, "odd key": true}
+        })
+    }
+
+    fn apply(expr: &str) -> Value {
+        Query::parse(expr)
+            .expect("Test: parse query")
+            .apply(&body())
+    }
+
+    #[test]
+    fn test_root_and_members() {
+        assert_eq!(apply("$"), body());
+        assert_eq!(apply("$.meta.total"), json!(2));
+        assert_eq!(apply("meta.total"), json!(2));
+        assert_eq!(apply("$['meta']['odd key']"), json!(true));
+        assert_eq!(apply("$.missing"), Value::Null);
+    }
+
+    #[test]
+    fn test_indices() {
+        assert_eq!(apply("$.data[1].email"), json!("b@x.io"));
+        assert_eq!(apply("data.0.email"), json!("a@x.io"));
+        assert_eq!(apply("$.data[-1].email"), json!("b@x.io"));
+        assert_eq!(apply("$.data[5]"), Value::Null);
+    }
+
+    #[test]
+    fn test_wildcards_and_descendants() {
+        assert_eq!(apply("$.data[*].email"), json!(["a@x.io", "b@x.io"]));
+        assert_eq!(apply("$.data.*.tags[0]"), json!(["admin"]));
+        assert_eq!(apply("$..email"), json!(["a@x.io", "b@x.io"]));
+        assert_eq!(apply("$..nothing"), json!([]));
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        for expr in ["$.", "$.data[", "$.data[x]", "$foo", "a..", "a[0]b"] {
+            assert!(Query::parse(expr).is_err(), "{expr} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_for_each_mut_changes_every_match() {
+        let mut value = json!({"id": 1, "items": [{"id": 2, "at": "x"}, {"id": 3, "at": "y"}]});
+        Query::parse("..id")
+            .unwrap()
+            .for_each_mut(&mut value, &mut |v| *v = json!(0));
+        Query::parse("items[-1].at")
+            .unwrap()
+            .for_each_mut(&mut value, &mut |v| *v = json!("z"));
+        assert_eq!(
+            value,
+            json!({"id": 0, "items": [{"id": 0, "at": "x"}, {"id": 0, "at": "z"}]})
+        );
+    }
+
+    #[test]
+    fn test_lookup_distinguishes_missing() {
+        assert_eq!(lookup(&body(), "$.meta.total"), Ok(Some(json!(2))));
+        assert_eq!(lookup(&body(), "$.meta.nope"), Ok(None));
+        assert!(lookup(&body(), "$.[").is_err());
+    }
+}
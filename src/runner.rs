@@ -0,0 +1,1171 @@
+//! Execution of a whole collection for `wave run`
+//!
+//! This module decides which requests may run concurrently and how fast they
+//! may start (`--rate`, shared with `wave bench`), records the outcome of each
+//! request in a run and formats the compact per-request summary lines, the
+//! final pass/fail count and the JUnit XML and TAP reports of `--report`. The
+//! requests themselves are built and sent by the handlers in the crate root.
+
+use crate::assertions::{Check, Expect};
+use crate::collection::Request;
+use crate::http::HttpResponse;
+use crate::query;
+use anstyle::{AnsiColor, Style};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Outcome of a single request executed as part of a collection run
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunResult {
+    /// Name of the request in the collection
+    pub name: String,
+    /// HTTP method that was sent
+    pub method: String,
+    /// Resolved URL the request was sent to
+    pub url: String,
+    /// Response status, if a response was received
+    pub status: Option<u16>,
+    /// Time from sending the request to receiving the full response
+    pub elapsed: Duration,
+    /// Why the request could not be built or sent, if it failed before a response
+    pub error: Option<String>,
+    /// Assertions evaluated against the response
+    pub checks: Vec<Check>,
+    /// Why the request was skipped, e.g. `'login' did not pass` for a
+    /// `depends_on:` request, or its `only_if:` condition
+    pub skipped: Option<String>,
+}
+
+impl RunResult {
+    /// A request passes when it got a response and every check held
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.status.is_some() && self.checks.iter().all(Check::passed)
+    }
+
+    /// A request fails when it did not pass; skipped requests neither pass
+    /// nor fail
+    pub fn failed(&self) -> bool {
+        !self.passed() && self.skipped.is_none()
+    }
+}
+
+/// Time to wait before sending a request of a run: its own `delay:`, or
+/// `default` (from `--delay`) for every request but the first
+pub fn delay_before(request: &Request, default: Option<Duration>, first: bool) -> Duration {
+    match (request.delay, default) {
+        (Some(delay), _) => delay,
+        (None, Some(delay)) if !first => delay,
+        _ => Duration::ZERO,
+    }
+}
+
+/// Finds, for each request, the requests it names in `depends_on:`
+///
+/// Names that are not among `requests` are left out.
+pub fn prerequisites(requests: &[&Request]) -> Vec<Vec<usize>> {
+    requests
+        .iter()
+        .map(|req| {
+            req.depends_on
+                .iter()
+                .filter_map(|name| requests.iter().position(|r| &r.name == name))
+                .collect()
+        })
+        .collect()
+}
+
+/// Finds, for each request, the requests it has to wait for
+///
+/// A request waits for its [`prerequisites`] and for every earlier request
+/// that captures a variable it refers to, unless that request itself waits
+/// for it through `depends_on:`. Requests without such dependencies may run
+/// concurrently.
+pub fn dependencies(requests: &[&Request]) -> Vec<Vec<usize>> {
+    let mut dependencies = prerequisites(requests);
+    for (i, req) in requests.iter().enumerate() {
+        let uses = req.variable_names();
+        for (j, earlier) in requests[..i].iter().enumerate() {
+            let captures = earlier
+                .capture
+                .as_ref()
+                .is_some_and(|capture| capture.keys().any(|name| uses.contains(name)));
+            if captures && !dependencies[i].contains(&j) && !waits_for(&dependencies, j, i) {
+                dependencies[i].push(j);
+            }
+        }
+    }
+    dependencies
+}
+
+/// Returns true if request `from` waits for `target`, directly or not
+fn waits_for(dependencies: &[Vec<usize>], from: usize, target: usize) -> bool {
+    let mut seen = vec![false; dependencies.len()];
+    let mut pending = vec![from];
+    while let Some(i) = pending.pop() {
+        if i == target {
+            return true;
+        }
+        if !std::mem::replace(&mut seen[i], true) {
+            pending.extend(&dependencies[i]);
+        }
+    }
+    false
+}
+
+/// Marks the requests that have `index` among their [`prerequisites`] as
+/// skipped because of it, unless they already are
+pub fn skip_dependents(prerequisites: &[Vec<usize>], index: usize, skipped: &mut [Option<usize>]) {
+    for (i, deps) in prerequisites.iter().enumerate() {
+        if deps.contains(&index) {
+            skipped[i].get_or_insert(index);
+        }
+    }
+}
+
+/// Hands out requests in file order once their dependencies have finished,
+/// keeping at most `limit` of them running at a time
+///
+/// # Examples
+/// ```
+/// use wave::runner::Scheduler;
+///
+/// // The third request waits for the first; at most two run at once
+/// let mut scheduler = Scheduler::new(vec![vec![], vec![], vec![0]], 2);
+/// assert_eq!(scheduler.start(), Some(0));
+/// assert_eq!(scheduler.start(), Some(1));
+/// assert_eq!(scheduler.start(), None);
+/// scheduler.finish(0);
+/// assert_eq!(scheduler.start(), Some(2));
+/// ```
+#[derive(Debug)]
+pub struct Scheduler {
+    /// Number of unfinished dependencies of each request
+    waiting: Vec<usize>,
+    /// Requests to notify when each request finishes
+    dependents: Vec<Vec<usize>>,
+    ready: BTreeSet<usize>,
+    running: usize,
+    limit: usize,
+}
+
+impl Scheduler {
+    /// Creates a scheduler for requests with the given dependencies
+    pub fn new(dependencies: Vec<Vec<usize>>, limit: usize) -> Self {
+        let mut dependents = vec![Vec::new(); dependencies.len()];
+        for (i, deps) in dependencies.iter().enumerate() {
+            for &dep in deps {
+                dependents[dep].push(i);
+            }
+        }
+        let waiting: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+        let ready = (0..waiting.len()).filter(|&i| waiting[i] == 0).collect();
+        Scheduler {
+            waiting,
+            dependents,
+            ready,
+            running: 0,
+            limit: limit.max(1),
+        }
+    }
+
+    /// Marks the next ready request as running, if there is room for it
+    pub fn start(&mut self) -> Option<usize> {
+        if self.running >= self.limit {
+            return None;
+        }
+        let index = self.ready.pop_first()?;
+        self.running += 1;
+        Some(index)
+    }
+
+    /// Marks a started request as finished, releasing its dependents
+    pub fn finish(&mut self, index: usize) {
+        self.running -= 1;
+        for &dependent in &self.dependents[index] {
+            self.waiting[dependent] -= 1;
+            if self.waiting[dependent] == 0 {
+                self.ready.insert(dependent);
+            }
+        }
+    }
+}
+
+/// Parses a request rate such as `5`, `5/s`, `0.5/sec` or `120/min`
+///
+/// A bare number is requests per second. Returns the rate per second.
+///
+/// # Examples
+/// ```
+/// use wave::runner::parse_rate;
+///
+/// assert_eq!(parse_rate("5"), Ok(5.0));
+/// assert_eq!(parse_rate("120/min"), Ok(2.0));
+/// assert_eq!(parse_rate("0.5/s"), Ok(0.5));
+/// assert!(parse_rate("0").is_err());
+/// assert!(parse_rate("5/day").is_err());
+/// ```
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let invalid = || format!("invalid rate '{s}', expected e.g. 5, 5/s or 120/min");
+    let (number, unit) = s.split_once('/').unwrap_or((s, "s"));
+    let n: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let per_second = match unit.trim() {
+        "s" | "sec" | "second" => n,
+        "m" | "min" | "minute" => n / 60.0,
+        "h" | "hour" => n / 3600.0,
+        _ => return Err(invalid()),
+    };
+    if !per_second.is_finite() || per_second <= 0.0 {
+        return Err(format!("rate '{s}' must be greater than zero"));
+    }
+    Ok(per_second)
+}
+
+/// Parses a delay such as `500ms`, `2s`, `1.5s` or `1m`
+///
+/// The unit is required, so `500` is not mistaken for seconds or
+/// milliseconds; only `0` may go without one.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wave::runner::parse_delay;
+///
+/// assert_eq!(parse_delay("500ms"), Ok(Duration::from_millis(500)));
+/// assert_eq!(parse_delay("1.5s"), Ok(Duration::from_millis(1500)));
+/// assert_eq!(parse_delay("1m"), Ok(Duration::from_secs(60)));
+/// assert_eq!(parse_delay("0"), Ok(Duration::ZERO));
+/// assert!(parse_delay("500").is_err());
+/// assert!(parse_delay("-1s").is_err());
+/// ```
+pub fn parse_delay(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let invalid = || format!("invalid delay '{s}', expected e.g. 500ms, 2s or 1m");
+    if s == "0" {
+        return Ok(Duration::ZERO);
+    }
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(invalid)?;
+    let (number, unit) = s.split_at(split);
+    let n: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit.trim() {
+        "ms" => n / 1000.0,
+        "s" | "sec" => n,
+        "m" | "min" => n * 60.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Token bucket that spaces out request dispatch for `--rate`
+///
+/// The bucket holds one token and refills at the given rate, so requests
+/// start evenly spaced rather than in bursts. A request that finds the
+/// bucket empty takes the next token in advance and waits for it, which
+/// keeps the spacing when many requests are dispatched at once.
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, Instant};
+/// use wave::runner::RateLimiter;
+///
+/// let start = Instant::now();
+/// let mut limiter = RateLimiter::new(4.0, start);
+/// assert_eq!(limiter.reserve(start), Duration::ZERO);
+/// assert_eq!(limiter.reserve(start), Duration::from_millis(250));
+/// assert_eq!(limiter.reserve(start), Duration::from_millis(500));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    per_second: f64,
+    /// Tokens available at `updated`; negative when requests are waiting
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a full bucket for `per_second` requests a second
+    pub fn new(per_second: f64, now: Instant) -> Self {
+        RateLimiter {
+            per_second,
+            tokens: 1.0,
+            updated: now,
+        }
+    }
+
+    /// Takes a token, returning how long to wait before sending the request
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(1.0);
+        self.updated = now.max(self.updated);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+/// Checks a response against the request's `expect:` block
+///
+/// Requests without one are expected to return a 2xx status.
+pub fn check_response(expect: Option<&Expect>, resp: &HttpResponse) -> Vec<Check> {
+    match expect {
+        Some(expect) => expect.evaluate(resp),
+        None => vec![Check {
+            description: "status is 2xx".to_string(),
+            failure: (!resp.is_success()).then(|| format!("got {}", resp.status)),
+        }],
+    }
+}
+
+/// Extracts `capture:` values from a JSON response into run variables
+///
+/// Each entry maps a variable name to a dotted path or JSONPath query (see
+/// [`crate::query`]) in the response body. String values are stored as-is; other values are stored as
+/// their JSON text. Returns one check per capture so a missing value fails
+/// the request instead of silently breaking later ones.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use http::HeaderMap;
+/// use wave::http::HttpResponse;
+/// use wave::runner::capture_values;
+///
+/// let resp = HttpResponse::new(200, HeaderMap::new(), r#"{"auth":{"token":"abc"}}"#);
+/// let capture = HashMap::from([("token".to_string(), "$.auth.token".to_string())]);
+/// let mut vars = HashMap::new();
+///
+/// let checks = capture_values(&capture, &resp, &mut vars);
+/// assert!(checks[0].passed());
+/// assert_eq!(vars["token"], "abc");
+/// ```
+pub fn capture_values(
+    capture: &HashMap<String, String>,
+    resp: &HttpResponse,
+    vars: &mut HashMap<String, String>,
+) -> Vec<Check> {
+    let body = serde_json::from_str::<Value>(&resp.body).ok();
+    let mut names: Vec<_> = capture.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let path = &capture[name];
+            let failure = match body.as_ref().map(|b| query::lookup(b, path)) {
+                None => Some("body is not JSON".to_string()),
+                Some(Err(e)) => Some(e),
+                Some(Ok(None)) => Some("path not found".to_string()),
+                Some(Ok(Some(value))) => {
+                    let text = match value {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    vars.insert(name.clone(), text);
+                    None
+                }
+            };
+            Check {
+                description: format!("capture {name} from {path}"),
+                failure,
+            }
+        })
+        .collect()
+}
+
+/// Writes the response body to a request's `save_response:` file, as
+/// received, creating its directory if needed
+///
+/// # Errors
+/// Returns the I/O error if the directory or file cannot be written
+///
+/// # Examples
+/// ```
+/// use http::HeaderMap;
+/// use wave::http::HttpResponse;
+/// use wave::runner::save_response;
+///
+/// let dir = std::env::temp_dir().join(format!("wave_doc_save_{}", std::process::id()));
+/// let path = dir.join("out").join("get-user.json");
+/// let resp = HttpResponse::new(200, HeaderMap::new(), r#"{"id":1}"#);
+/// save_response(&path, &resp).unwrap();
+/// assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"id":1}"#);
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+pub fn save_response(path: &Path, resp: &HttpResponse) -> std::io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, &resp.bytes)
+}
+
+/// Formats the one-line summary printed after each request in a run
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wave::runner::{format_result_line, RunResult};
+///
+/// let result = RunResult {
+///     name: "get-user".to_string(),
+///     method: "GET".to_string(),
+///     url: "https://api.example.com/users/1".to_string(),
+///     status: Some(200),
+///     elapsed: Duration::from_millis(42),
+///     error: None,
+///     checks: Vec::new(),
+///     skipped: None,
+/// };
+/// let line = format_result_line(&result);
+/// assert!(line.contains("get-user"));
+/// assert!(line.contains("200"));
+/// assert!(line.contains("42 ms"));
+/// ```
+pub fn format_result_line(result: &RunResult) -> String {
+    let (mark, color) = if result.passed() {
+        ("✓", AnsiColor::Green)
+    } else if result.skipped.is_some() {
+        ("-", AnsiColor::Yellow)
+    } else {
+        ("✗", AnsiColor::Red)
+    };
+    let style = Style::new().fg_color(Some(anstyle::Color::Ansi(color)));
+    let outcome = match (&result.error, result.status) {
+        _ if result.skipped.is_some() => skip_reason(result),
+        (Some(error), _) => error.clone(),
+        (None, Some(status)) => format!("{status} ({} ms)", result.elapsed.as_millis()),
+        (None, None) => "no response".to_string(),
+    };
+    // Requests that were never built have no URL
+    let request = match result.url.as_str() {
+        "" => result.method.clone(),
+        url => format!("{} {url}", result.method),
+    };
+    let mut line = format!(
+        "{}{mark}{} {} {request} {}{outcome}{}",
+        style.render(),
+        anstyle::Reset.render(),
+        result.name,
+        style.render(),
+        anstyle::Reset.render()
+    );
+    for check in result.checks.iter().filter(|c| !c.passed()) {
+        line.push_str(&format!(
+            "\n    {}: {}",
+            check.description,
+            check.failure.as_deref().unwrap_or_default()
+        ));
+    }
+    line
+}
+
+/// Why a skipped request was not sent
+fn skip_reason(result: &RunResult) -> String {
+    format!("skipped, {}", result.skipped.as_deref().unwrap_or_default())
+}
+
+/// Returns why a request's `only_if:` or `skip_if:` condition keeps it from
+/// being sent, or `None` if it should be sent
+///
+/// # Errors
+/// Returns a message naming the condition if one of its variables cannot
+/// be resolved
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use wave::collection::Request;
+/// use wave::runner::unmet_condition;
+///
+/// let yaml = "name: seed\nmethod: POST\nurl: /seed\nonly_if: ${env} == staging\n";
+/// let req: Request = serde_yaml::from_str(yaml).unwrap();
+/// let vars = |env: &str| HashMap::from([("env".to_string(), env.to_string())]);
+/// assert_eq!(unmet_condition(&req, &vars("staging")), Ok(None));
+/// assert_eq!(
+///     unmet_condition(&req, &vars("prod")),
+///     Ok(Some("only_if ${env} == staging does not hold".to_string()))
+/// );
+/// ```
+pub fn unmet_condition(
+    request: &Request,
+    vars: &HashMap<String, String>,
+) -> Result<Option<String>, String> {
+    for (field, condition, skip_when) in [
+        ("only_if", &request.only_if, false),
+        ("skip_if", &request.skip_if, true),
+    ] {
+        let Some(condition) = condition else {
+            continue;
+        };
+        let holds = condition
+            .resolve_vars(vars)
+            .map_err(|e| format!("{field}: {e}"))?
+            .holds();
+        if holds == skip_when {
+            let outcome = if holds { "holds" } else { "does not hold" };
+            return Ok(Some(format!("{field} {condition} {outcome}")));
+        }
+    }
+    Ok(None)
+}
+
+/// Formats the final pass/fail count for a run, with the number of skipped
+/// requests if there are any
+pub fn format_summary(results: &[RunResult]) -> String {
+    let passed = results.iter().filter(|r| r.passed()).count();
+    let failed = results.iter().filter(|r| r.failed()).count();
+    let skipped = results.len() - passed - failed;
+    let style = if failed == 0 {
+        Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::Green)))
+    } else {
+        Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::Red)))
+    };
+    let skipped = match skipped {
+        0 => String::new(),
+        n => format!(", {n} skipped"),
+    };
+    format!(
+        "{}{passed} passed, {failed} failed{skipped}{}",
+        style.bold().render(),
+        anstyle::Reset.render()
+    )
+}
+
+/// Formats the outcome of a request for the table of a `--data` run: the
+/// status, or `error`, marked with `✗` when the request did not pass, or
+/// `skipped`
+pub fn format_outcome_cell(result: &RunResult) -> String {
+    if result.skipped.is_some() {
+        return "skipped".to_string();
+    }
+    let outcome = match result.status {
+        Some(status) if result.error.is_none() => status.to_string(),
+        _ => "error".to_string(),
+    };
+    if result.passed() {
+        outcome
+    } else {
+        format!("{outcome} ✗")
+    }
+}
+
+/// Formats the table printed after a `--data` run: one line per row of the
+/// dataset, one column per request, `-` for requests that did not run
+///
+/// # Examples
+/// ```
+/// use wave::runner::format_data_table;
+///
+/// let table = vec![
+///     vec![Some("201".to_string())],
+///     vec![Some("409 ✗".to_string())],
+/// ];
+/// assert_eq!(
+///     format_data_table(&["create-user"], &table),
+///     "row  create-user\n1    201\n2    409 ✗"
+/// );
+/// ```
+pub fn format_data_table(requests: &[&str], cells: &[Vec<Option<String>>]) -> String {
+    let width = |text: &str| text.chars().count();
+    let row_width = width("row").max(cells.len().to_string().len());
+    let widths: Vec<usize> = requests
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            cells
+                .iter()
+                .filter_map(|row| row.get(i)?.as_deref())
+                .map(width)
+                .max()
+                .unwrap_or(1)
+                .max(width(name))
+        })
+        .collect();
+    let line = |first: String, texts: Vec<&str>| {
+        let mut line = format!("{first:row_width$}");
+        for (text, width) in texts.iter().zip(&widths) {
+            line.push_str(&format!("  {text:width$}"));
+        }
+        line.trim_end().to_string()
+    };
+    let mut lines = vec![line("row".to_string(), requests.to_vec())];
+    for (i, row) in cells.iter().enumerate() {
+        let texts = row
+            .iter()
+            .map(|cell| cell.as_deref().unwrap_or("-"))
+            .collect();
+        lines.push(line((i + 1).to_string(), texts));
+    }
+    lines.join("\n")
+}
+
+/// Format of a `wave run --report`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// JUnit XML, as read by most CI systems
+    Junit,
+    /// Test Anything Protocol, version 13
+    Tap,
+}
+
+/// A report to write at the end of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub format: ReportFormat,
+    /// File to write the report to; `None` writes it to stdout
+    pub path: Option<PathBuf>,
+}
+
+impl Report {
+    /// Formats the report of a run of the collection `suite`
+    pub fn render(&self, suite: &str, results: &[RunResult]) -> String {
+        match self.format {
+            ReportFormat::Junit => format_junit(suite, results),
+            ReportFormat::Tap => format_tap(results),
+        }
+    }
+}
+
+/// Parses a `--report` value: `junit` or `tap`, optionally followed by
+/// `:PATH`
+///
+/// # Examples
+/// ```
+/// use std::path::PathBuf;
+/// use wave::runner::{parse_report, ReportFormat};
+///
+/// let report = parse_report("junit:reports/api.xml").unwrap();
+/// assert_eq!(report.format, ReportFormat::Junit);
+/// assert_eq!(report.path, Some(PathBuf::from("reports/api.xml")));
+/// assert_eq!(parse_report("tap").unwrap().path, None);
+/// assert!(parse_report("html:out.html").is_err());
+/// ```
+pub fn parse_report(s: &str) -> Result<Report, String> {
+    let (format, path) = match s.split_once(':') {
+        Some((format, path)) => (format, Some(path)),
+        None => (s, None),
+    };
+    let format = match format.to_ascii_lowercase().as_str() {
+        "junit" => ReportFormat::Junit,
+        "tap" => ReportFormat::Tap,
+        _ => {
+            return Err(format!(
+                "unknown report format '{format}', expected junit or tap, e.g. junit:report.xml"
+            ))
+        }
+    };
+    if path.is_some_and(str::is_empty) {
+        return Err(format!("missing file name after '{s}'"));
+    }
+    Ok(Report {
+        format,
+        path: path.map(PathBuf::from),
+    })
+}
+
+/// Escapes text for XML attribute values and element content
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newlines are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Failed checks of a result, one `description: failure` per entry
+fn failures(result: &RunResult) -> Vec<String> {
+    result
+        .checks
+        .iter()
+        .filter(|c| !c.passed())
+        .map(|c| {
+            format!(
+                "{}: {}",
+                c.description,
+                c.failure.as_deref().unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
+/// Formats a run as a JUnit XML report with one test case per request
+///
+/// A request that got no response is an `<error>`, one that failed a check
+/// a `<failure>` and one skipped for a failed `depends_on:` `<skipped>`.
+/// The collection name is the suite and class name.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wave::runner::{format_junit, RunResult};
+///
+/// let result = RunResult {
+///     name: "get-user".to_string(),
+///     method: "GET".to_string(),
+///     url: "https://api.example.com/users/1".to_string(),
+///     status: None,
+///     elapsed: Duration::ZERO,
+///     error: Some("connection refused".to_string()),
+///     checks: Vec::new(),
+///     skipped: None,
+/// };
+/// let xml = format_junit("api", &[result]);
+/// assert!(xml.contains(r#"<testsuite name="api" tests="1" failures="0" errors="1""#));
+/// assert!(xml.contains(r#"<error message="connection refused""#));
+/// ```
+pub fn format_junit(suite: &str, results: &[RunResult]) -> String {
+    let errors = results
+        .iter()
+        .filter(|r| r.failed() && (r.error.is_some() || r.status.is_none()))
+        .count();
+    let failures_count = results.iter().filter(|r| r.failed()).count() - errors;
+    let skipped = results.iter().filter(|r| r.skipped.is_some()).count();
+    let time: f64 = results.iter().map(|r| r.elapsed.as_secs_f64()).sum();
+    let suite = xml_escape(suite);
+    let skipped = match skipped {
+        0 => String::new(),
+        n => format!(r#" skipped="{n}""#),
+    };
+    let totals = format!(
+        r#"tests="{}" failures="{failures_count}" errors="{errors}"{skipped} time="{time:.3}""#,
+        results.len()
+    );
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"wave\" {totals}>\n  <testsuite name=\"{suite}\" {totals}>\n"
+    );
+    for result in results {
+        xml.push_str(&format!(
+            r#"    <testcase name="{}" classname="{suite}" time="{:.3}""#,
+            xml_escape(&result.name),
+            result.elapsed.as_secs_f64()
+        ));
+        if result.skipped.is_some() {
+            xml.push_str(&format!(
+                ">\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                xml_escape(&skip_reason(result))
+            ));
+            continue;
+        }
+        let request = format!("{} {}", result.method, result.url);
+        let (element, message, details) = match (&result.error, result.status) {
+            (Some(error), _) => ("error", error.clone(), request),
+            (None, None) => ("error", "no response".to_string(), request),
+            (None, Some(status)) => {
+                let failed = failures(result);
+                if failed.is_empty() {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                let details = format!("{request}\nstatus {status}\n{}", failed.join("\n"));
+                ("failure", failed.join("; "), details)
+            }
+        };
+        xml.push_str(&format!(
+            ">\n      <{element} message=\"{}\">{}</{element}>\n    </testcase>\n",
+            xml_escape(&message),
+            xml_escape(&details)
+        ));
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// Formats a run as a TAP version 13 report with one test point per request
+///
+/// Failed points carry a YAML block with the request, status and the failed
+/// checks or error; skipped requests are points with a `SKIP` directive.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wave::runner::{format_tap, RunResult};
+///
+/// let result = RunResult {
+///     name: "health".to_string(),
+///     method: "GET".to_string(),
+///     url: "https://api.example.com/health".to_string(),
+///     status: Some(200),
+///     elapsed: Duration::from_millis(12),
+///     error: None,
+///     checks: Vec::new(),
+///     skipped: None,
+/// };
+/// assert_eq!(format_tap(&[result]), "TAP version 13\n1..1\nok 1 - health\n");
+/// ```
+pub fn format_tap(results: &[RunResult]) -> String {
+    let mut tap = format!("TAP version 13\n1..{}\n", results.len());
+    for (i, result) in results.iter().enumerate() {
+        // `#` would start a directive such as SKIP
+        let name = result.name.replace('\\', "\\\\").replace('#', "\\#");
+        if result.passed() {
+            tap.push_str(&format!("ok {} - {name}\n", i + 1));
+            continue;
+        }
+        if result.skipped.is_some() {
+            tap.push_str(&format!(
+                "ok {} - {name} # SKIP {}\n",
+                i + 1,
+                skip_reason(result)
+            ));
+            continue;
+        }
+        tap.push_str(&format!("not ok {} - {name}\n  ---\n", i + 1));
+        // JSON strings are valid YAML scalars
+        let quote = |text: &str| Value::String(text.to_string()).to_string();
+        tap.push_str(&format!("  method: {}\n", quote(&result.method)));
+        tap.push_str(&format!("  url: {}\n", quote(&result.url)));
+        if let Some(status) = result.status {
+            tap.push_str(&format!("  status: {status}\n"));
+        }
+        tap.push_str(&format!("  duration_ms: {}\n", result.elapsed.as_millis()));
+        match &result.error {
+            Some(error) => tap.push_str(&format!("  error: {}\n", quote(error))),
+            None if result.status.is_none() => tap.push_str("  error: \"no response\"\n"),
+            None => {
+                tap.push_str("  failures:\n");
+                for failure in failures(result) {
+                    tap.push_str(&format!("    - {}\n", quote(&failure)));
+                }
+            }
+        }
+        tap.push_str("  ...\n");
+    }
+    tap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::http::HeaderMap;
+
+    fn result(status: Option<u16>, error: Option<&str>) -> RunResult {
+        let checks = match status {
+            Some(status) => check_response(None, &HttpResponse::new(status, HeaderMap::new(), "")),
+            None => Vec::new(),
+        };
+        RunResult {
+            name: "req".to_string(),
+            method: "GET".to_string(),
+            url: "https://example.com".to_string(),
+            status,
+            elapsed: Duration::from_millis(5),
+            error: error.map(str::to_string),
+            checks,
+            skipped: None,
+        }
+    }
+
+    #[test]
+    fn test_passed_requires_success_status() {
+        assert!(result(Some(204), None).passed());
+        assert!(!result(Some(404), None).passed());
+        assert!(!result(None, Some("connection refused")).passed());
+    }
+
+    #[test]
+    fn test_check_response_uses_expect_status() {
+        let expect = Expect {
+            status: Some(404),
+            ..Default::default()
+        };
+        let resp = HttpResponse::new(404, HeaderMap::new(), "");
+        assert!(check_response(Some(&expect), &resp)
+            .iter()
+            .all(Check::passed));
+        assert!(!check_response(None, &resp)[0].passed());
+    }
+
+    #[test]
+    fn test_rate_limiter_refills_over_time() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(2.0, start);
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+        // Half a second later the token is back, but not two of them
+        let later = start + Duration::from_secs(5);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+        assert_eq!(limiter.reserve(later), Duration::from_millis(500));
+        // A request arriving while another waits queues behind it
+        let soon = later + Duration::from_millis(250);
+        assert_eq!(limiter.reserve(soon), Duration::from_millis(750));
+    }
+
+    #[test]
+    fn test_capture_values_stores_strings_and_json() {
+        let resp = HttpResponse::new(
+            200,
+            HeaderMap::new(),
+            r#"{"id":7,"items":[{"name":"a"}],"meta":{"ok":true}}"#,
+        );
+        let capture = HashMap::from([
+            ("id".to_string(), "id".to_string()),
+            ("first".to_string(), "items[0].name".to_string()),
+            ("meta".to_string(), "meta".to_string()),
+            ("missing".to_string(), "nope".to_string()),
+        ]);
+        let mut vars = HashMap::new();
+        let checks = capture_values(&capture, &resp, &mut vars);
+
+        assert_eq!(vars["id"], "7");
+        assert_eq!(vars["first"], "a");
+        assert_eq!(vars["meta"], r#"{"ok":true}"#);
+        assert!(!vars.contains_key("missing"));
+        let failed: Vec<_> = checks.iter().filter(|c| !c.passed()).collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].description, "capture missing from nope");
+    }
+
+    #[test]
+    fn test_dependencies_follow_captures() {
+        let coll: crate::collection::Collection = serde_yaml::from_str(
+            r#"
+requests:
+  - name: login
+    method: POST
+    url: https://example.com/login
+    capture:
+      token: $.token
+  - name: health
+    method: GET
+    url: https://example.com/health
+  - name: me
+    method: GET
+    url: https://example.com/me
+    headers:
+      Authorization: Bearer ${token}
+"#,
+        )
+        .expect("Test: parse collection");
+        let requests: Vec<&Request> = coll.requests.iter().collect();
+        assert_eq!(dependencies(&requests), vec![vec![], vec![], vec![0]]);
+    }
+
+    #[test]
+    fn test_dependencies_follow_depends_on() {
+        let coll: crate::collection::Collection = serde_yaml::from_str(
+            r#"
+requests:
+  - name: me
+    method: GET
+    url: https://example.com/me
+    headers:
+      Authorization: Bearer ${token}
+  - name: login
+    method: POST
+    url: https://example.com/login
+    capture:
+      token: $.token
+    depends_on: [seed]
+  - name: seed
+    method: POST
+    url: https://example.com/seed
+    depends_on: [me]
+"#,
+        )
+        .expect("Test: parse collection");
+        let requests: Vec<&Request> = coll.requests.iter().collect();
+        assert_eq!(prerequisites(&requests), vec![vec![], vec![2], vec![0]]);
+        // `me` uses the token of a later request, which waits for it anyway
+        assert_eq!(dependencies(&requests), vec![vec![], vec![2], vec![0]]);
+
+        let mut skipped = vec![None; 3];
+        skip_dependents(&prerequisites(&requests), 0, &mut skipped);
+        assert_eq!(skipped, vec![None, None, Some(0)]);
+    }
+
+    #[test]
+    fn test_delay_before_prefers_request_delay() {
+        let coll: crate::collection::Collection = serde_yaml::from_str(
+            "requests:\n  - name: a\n    method: GET\n    url: /\n    delay: 2s\n  - name: b\n    method: GET\n    url: /\n",
+        )
+        .expect("Test: parse collection");
+        let (own, plain) = (&coll.requests[0], &coll.requests[1]);
+        let default = Some(Duration::from_millis(300));
+        assert_eq!(delay_before(own, default, true), Duration::from_secs(2));
+        assert_eq!(
+            delay_before(plain, default, false),
+            Duration::from_millis(300)
+        );
+        assert_eq!(delay_before(plain, default, true), Duration::ZERO);
+        assert_eq!(delay_before(plain, None, false), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_skipped_results_in_summary_and_reports() {
+        let mut skipped = result(None, None);
+        skipped.name = "me".to_string();
+        skipped.skipped = Some("'login' did not pass".to_string());
+        let results = vec![result(Some(500), None), skipped];
+        assert!(!results[1].passed() && !results[1].failed());
+        assert!(format_summary(&results).contains("0 passed, 1 failed, 1 skipped"));
+        assert!(format_result_line(&results[1]).contains("skipped, 'login' did not pass"));
+        assert_eq!(format_outcome_cell(&results[1]), "skipped");
+        let xml = format_junit("api", &results);
+        assert!(xml.contains(r#"tests="2" failures="1" errors="0" skipped="1""#));
+        assert!(xml.contains(
+            r#"<testcase name="me" classname="api" time="0.005">
+      <skipped message="skipped, &apos;login&apos; did not pass"/>"#
+        ));
+        assert!(format_tap(&results).ends_with("ok 2 - me # SKIP skipped, 'login' did not pass\n"));
+    }
+
+    #[test]
+    fn test_scheduler_respects_limit_and_dependencies() {
+        let mut scheduler = Scheduler::new(vec![vec![], vec![0], vec![], vec![1]], 3);
+        assert_eq!(scheduler.start(), Some(0));
+        assert_eq!(scheduler.start(), Some(2));
+        assert_eq!(scheduler.start(), None);
+        scheduler.finish(2);
+        assert_eq!(scheduler.start(), None);
+        scheduler.finish(0);
+        assert_eq!(scheduler.start(), Some(1));
+        scheduler.finish(1);
+        assert_eq!(scheduler.start(), Some(3));
+        scheduler.finish(3);
+        assert_eq!(scheduler.start(), None);
+
+        let mut sequential = Scheduler::new(vec![vec![], vec![]], 1);
+        assert_eq!(sequential.start(), Some(0));
+        assert_eq!(sequential.start(), None);
+        sequential.finish(0);
+        assert_eq!(sequential.start(), Some(1));
+    }
+
+    #[test]
+    fn test_format_result_line_lists_failed_checks() {
+        let line = format_result_line(&result(Some(500), None));
+        assert!(line.contains("status is 2xx: got 500"));
+    }
+
+    #[test]
+    fn test_format_result_line_shows_error() {
+        let line = format_result_line(&result(None, Some("connection refused")));
+        assert!(line.contains("✗"));
+        assert!(line.contains("connection refused"));
+    }
+
+    #[test]
+    fn test_parse_report() {
+        assert_eq!(
+            parse_report("TAP:out/run.tap"),
+            Ok(Report {
+                format: ReportFormat::Tap,
+                path: Some(PathBuf::from("out/run.tap")),
+            })
+        );
+        assert_eq!(
+            parse_report("junit"),
+            Ok(Report {
+                format: ReportFormat::Junit,
+                path: None,
+            })
+        );
+        assert!(parse_report("junit:").is_err());
+        assert!(parse_report("xml:report.xml").is_err());
+    }
+
+    #[test]
+    fn test_format_junit_report() {
+        let mut failing = result(Some(500), None);
+        failing.name = "create <user>".to_string();
+        let results = vec![
+            result(Some(200), None),
+            failing,
+            result(None, Some("connection refused")),
+        ];
+        assert_eq!(
+            format_junit("api & co", &results),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="wave" tests="3" failures="1" errors="1" time="0.015">
+  <testsuite name="api &amp; co" tests="3" failures="1" errors="1" time="0.015">
+    <testcase name="req" classname="api &amp; co" time="0.005"/>
+    <testcase name="create &lt;user&gt;" classname="api &amp; co" time="0.005">
+      <failure message="status is 2xx: got 500">GET https://example.com
+status 500
+status is 2xx: got 500</failure>
+    </testcase>
+    <testcase name="req" classname="api &amp; co" time="0.005">
+      <error message="connection refused">GET https://example.com</error>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn test_format_tap_report() {
+        let mut failing = result(Some(404), None);
+        failing.name = "get #1".to_string();
+        let results = vec![
+            result(Some(200), None),
+            failing,
+            result(None, Some("timed out")),
+        ];
+        assert_eq!(
+            format_tap(&results),
+            r#"TAP version 13
+1..3
+ok 1 - req
+not ok 2 - get \#1
+  ---
+  method: "GET"
+  url: "https://example.com"
+  status: 404
+  duration_ms: 5
+  failures:
+    - "status is 2xx: got 404"
+  ...
+not ok 3 - req
+  ---
+  method: "GET"
+  url: "https://example.com"
+  duration_ms: 5
+  error: "timed out"
+  ...
+"#
+        );
+    }
+
+    #[test]
+    fn test_format_data_table_cells() {
+        let cells = vec![
+            vec![
+                Some(format_outcome_cell(&result(Some(201), None))),
+                Some(format_outcome_cell(&result(Some(500), None))),
+            ],
+            vec![
+                Some(format_outcome_cell(&result(None, Some("refused")))),
+                None,
+            ],
+        ];
+        assert_eq!(
+            format_data_table(&["create", "get"], &cells),
+            "row  create   get\n1    201      500 ✗\n2    error ✗  -"
+        );
+    }
+
+    #[test]
+    fn test_format_summary_counts() {
+        let results = vec![
+            result(Some(200), None),
+            result(Some(500), None),
+            result(Some(201), None),
+        ];
+        assert!(format_summary(&results).contains("2 passed, 1 failed"));
+    }
+}
@@ -0,0 +1,482 @@
+//! Persistent sessions: cookies and auth headers kept between invocations
+//!
+//! A session named `NAME` is stored in `sessions/NAME.json` of the collection
+//! directory, usually the project's `.wave`, so the same file is used from any
+//! subdirectory. Cookies set by responses are saved there and sent
+//! automatically with later requests to matching domains and paths. An `Authorization` header sent with a request
+//! is remembered too, so logging in once is enough. Session files are only
+//! readable by their owner (mode 0600 on unix), as they hold credentials.
+
+use crate::error::{CliError, WaveError};
+use crate::http::{HttpRequest, HttpResponse};
+use ::http::header::{HeaderValue, AUTHORIZATION, COOKIE, SET_COOKIE};
+use reqwest::Url;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory holding session files, relative to the collection directory
+pub const SESSION_DIR: &str = "sessions";
+
+/// A cookie stored in a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Domain the cookie belongs to, without a leading dot
+    pub domain: String,
+    /// Only send to exactly `domain`, not its subdomains (no Domain attribute was given)
+    #[serde(default)]
+    pub host_only: bool,
+    pub path: String,
+    /// Expiry as seconds since the Unix epoch; `None` keeps the cookie until replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+    /// Only send over HTTPS
+    #[serde(default)]
+    pub secure: bool,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value received from `url`
+    ///
+    /// Returns `None` for malformed cookies and cookies whose `Domain`
+    /// attribute does not cover the host that set them.
+    pub fn parse(header: &str, url: &Url, now: u64) -> Option<Cookie> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            expires: None,
+            secure: false,
+        };
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, val) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let val = val.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "domain" if !val.is_empty() => {
+                    let domain = val.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if val.starts_with('/') => cookie.path = val.to_string(),
+                "max-age" => max_age = val.parse::<i64>().ok(),
+                "expires" if cookie.expires.is_none() => cookie.expires = parse_http_date(val),
+                "secure" => cookie.secure = true,
+                _ => {}
+            }
+        }
+        // Max-Age takes precedence over Expires
+        if let Some(seconds) = max_age {
+            cookie.expires = Some(match u64::try_from(seconds) {
+                Ok(seconds) if seconds > 0 => now.saturating_add(seconds),
+                _ => 0,
+            });
+        }
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let host_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+        host_ok && path_matches(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// Cookies and headers persisted between invocations
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    /// Headers re-sent with requests in the session (e.g. Authorization),
+    /// keyed by the origin they were sent to, such as `https://example.com:8443`
+    #[serde(default, deserialize_with = "origin_headers")]
+    pub headers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Reads the saved headers by origin
+///
+/// Session files written before headers were tied to an origin hold the
+/// headers themselves; those are dropped, as nothing says where to send them.
+fn origin_headers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, BTreeMap<String, String>>, D::Error> {
+    let saved = BTreeMap::<String, serde_json::Value>::deserialize(deserializer)?;
+    Ok(saved
+        .into_iter()
+        .filter_map(|(origin, headers)| Some((origin, serde_json::from_value(headers).ok()?)))
+        .collect())
+}
+
+/// The origin (scheme, host and port) of a URL, or `None` for URLs without one
+fn origin(url: &Url) -> Option<String> {
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+impl Session {
+    /// Path of the file backing the session `name` in a collection directory
+    ///
+    /// # Errors
+    /// Returns `CliError::InvalidSessionName` for empty names or names with path separators
+    pub fn path_for(collection_dir: &Path, name: &str) -> Result<PathBuf, WaveError> {
+        let name = name.trim();
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(WaveError::Cli(CliError::InvalidSessionName(
+                name.to_string(),
+            )));
+        }
+        Ok(collection_dir
+            .join(SESSION_DIR)
+            .join(format!("{name}.json")))
+    }
+
+    /// Loads a session file, starting an empty session if it does not exist yet
+    pub fn load(path: &Path) -> Result<Session, WaveError> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| {
+                WaveError::Io(format!("Invalid session file '{}': {e}", path.display()))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Session::default()),
+            Err(e) => Err(WaveError::Io(format!(
+                "Failed to read session '{}': {e}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Writes the session file, creating the sessions directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), WaveError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        write_private(path, json.as_bytes())
+            .map_err(|e| WaveError::Io(format!("Failed to save session '{}': {e}", path.display())))
+    }
+
+    /// Adds the session's headers for the request's origin and matching
+    /// cookies to a request
+    ///
+    /// Headers already set on the request win over saved ones; saved cookies
+    /// are appended to any `Cookie` header given explicitly.
+    pub fn apply(&self, req: &mut HttpRequest) {
+        let Ok(url) = Url::parse(&req.url) else {
+            return;
+        };
+        if let Some(headers) = origin(&url).and_then(|origin| self.headers.get(&origin)) {
+            crate::http::headers::fill_defaults(
+                &mut req.headers,
+                &crate::http::headers::lenient_header_map(headers),
+            );
+        }
+        let now = unix_now();
+        let cookies: Vec<String> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired(now) && c.matches(&url))
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        if cookies.is_empty() {
+            return;
+        }
+        let mut header = cookies.join("; ");
+        if let Some(existing) = req.headers.get(COOKIE).and_then(|v| v.to_str().ok()) {
+            header = format!("{existing}; {header}");
+        }
+        if let Ok(value) = HeaderValue::from_str(&header) {
+            req.headers.insert(COOKIE, value);
+        }
+    }
+
+    /// Records the request's `Authorization` header for its origin, and the
+    /// response's cookies
+    pub fn update(&mut self, req: &HttpRequest, resp: &HttpResponse) {
+        let Ok(url) = Url::parse(&req.url) else {
+            return;
+        };
+        let auth = req.headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+        if let (Some(auth), Some(origin)) = (auth, origin(&url)) {
+            self.headers
+                .entry(origin)
+                .or_default()
+                .insert(AUTHORIZATION.as_str().to_string(), auth.to_string());
+        }
+        let now = unix_now();
+        for header in resp.headers.get_all(SET_COOKIE) {
+            let Some(cookie) = header
+                .to_str()
+                .ok()
+                .and_then(|h| Cookie::parse(h, &url, now))
+            else {
+                continue;
+            };
+            self.cookies.retain(|c| {
+                !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path)
+            });
+            if !cookie.is_expired(now) {
+                self.cookies.push(cookie);
+            }
+        }
+        self.cookies.retain(|c| !c.is_expired(now));
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Default cookie path: the request path up to, but not including, its last `/`
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => url.path()[..i].to_string(),
+    }
+}
+
+/// Parses an HTTP date such as `Wed, 21 Oct 2015 07:28:00 GMT` into Unix seconds
+fn parse_http_date(value: &str) -> Option<u64> {
+    let rest = value.split_once(',').map_or(value, |(_, rest)| rest);
+    let normalized = rest.replace('-', " ");
+    let fields: Vec<&str> = normalized.split_whitespace().collect();
+    let [day, month, year, time, ..] = fields.as_slice() else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ]
+    .iter()
+    .position(|m| month.to_ascii_lowercase().starts_with(m))? as u64
+        + 1;
+    let mut year: i64 = year.parse().ok()?;
+    if year < 100 {
+        year += if year < 70 { 2000 } else { 1900 };
+    }
+    let mut clock = time.split(':').map(|n| n.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (clock.next()??, clock.next()??, clock.next()??);
+
+    // Days since the epoch for a proleptic Gregorian date
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = days * 86_400 + (hours * 3600 + minutes * 60 + seconds) as i64;
+    u64::try_from(secs).ok()
+}
+
+/// Writes a file only its owner can read, for files holding credentials
+///
+/// On unix the file is created with mode 0600, and an existing file that
+/// others could read is restricted before it is written.
+pub(crate) fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o077 != 0) {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::http::{HeaderMap, Method};
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(
+            parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(1_445_412_480)
+        );
+        assert_eq!(
+            parse_http_date("Wed, 21-Oct-2015 07:28:00 GMT"),
+            Some(1_445_412_480)
+        );
+        assert_eq!(parse_http_date("not a date"), None);
+    }
+
+    #[test]
+    fn test_cookie_parse_attributes() {
+        let cookie = Cookie::parse(
+            "sid=abc; Domain=.example.com; Path=/api; Max-Age=60; Secure; HttpOnly",
+            &url("https://www.example.com/login"),
+            1000,
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "sid");
+        assert_eq!(cookie.value, "abc");
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.path, "/api");
+        assert_eq!(cookie.expires, Some(1060));
+        assert!(cookie.secure);
+    }
+
+    #[test]
+    fn test_cookie_rejects_foreign_domain() {
+        assert!(
+            Cookie::parse("sid=abc; Domain=evil.com", &url("https://example.com/"), 0).is_none()
+        );
+    }
+
+    #[test]
+    fn test_cookie_matching() {
+        let cookie =
+            Cookie::parse("sid=abc; Path=/api", &url("https://example.com/login"), 0).unwrap();
+        assert!(cookie.matches(&url("https://example.com/api/users")));
+        assert!(!cookie.matches(&url("https://example.com/apix")));
+        assert!(!cookie.matches(&url("https://sub.example.com/api")));
+
+        let secure = Cookie::parse("t=1; Secure", &url("https://example.com/"), 0).unwrap();
+        assert!(!secure.matches(&url("http://example.com/")));
+    }
+
+    #[test]
+    fn test_session_update_and_apply() {
+        let login = HttpRequest::builder("https://example.com/login", Method::POST)
+            .header("Authorization", "Bearer token")
+            .build();
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, "sid=abc; Path=/".parse().unwrap());
+        headers.append(SET_COOKIE, "theme=dark; Path=/".parse().unwrap());
+        let resp = HttpResponse::new(200, headers, "");
+
+        let mut session = Session::default();
+        session.update(&login, &resp);
+        assert_eq!(session.cookies.len(), 2);
+
+        let mut next = HttpRequest::builder("https://example.com/me", Method::GET).build();
+        session.apply(&mut next);
+        assert_eq!(next.headers[COOKIE], "sid=abc; theme=dark");
+        assert_eq!(next.headers[AUTHORIZATION], "Bearer token");
+
+        let mut other = HttpRequest::builder("https://other.com/", Method::GET).build();
+        session.apply(&mut other);
+        assert!(other.headers.get(COOKIE).is_none());
+    }
+
+    #[test]
+    fn test_session_headers_stay_with_their_origin() {
+        let login = HttpRequest::builder("http://localhost:8080/login", Method::POST)
+            .header("Authorization", "Bearer token")
+            .build();
+        let mut session = Session::default();
+        session.update(&login, &HttpResponse::new(200, HeaderMap::new(), ""));
+
+        let mut same = HttpRequest::builder("http://localhost:8080/me", Method::GET).build();
+        session.apply(&mut same);
+        assert_eq!(same.headers[AUTHORIZATION], "Bearer token");
+        for url in [
+            "http://127.0.0.1:8080/me",
+            "http://localhost:9090/me",
+            "https://localhost:8080/me",
+        ] {
+            let mut other = HttpRequest::builder(url, Method::GET).build();
+            session.apply(&mut other);
+            assert!(other.headers.get(AUTHORIZATION).is_none(), "{url}");
+        }
+
+        // Headers saved before they were tied to an origin are dropped
+        let old: Session =
+            serde_json::from_str(r#"{"cookies":[],"headers":{"authorization":"Bearer old"}}"#)
+                .unwrap();
+        assert!(old.headers.is_empty());
+    }
+
+    #[test]
+    fn test_session_update_removes_expired_cookie() {
+        let req = HttpRequest::builder("https://example.com/", Method::GET).build();
+        let mut session = Session::default();
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, "sid=abc".parse().unwrap());
+        session.update(&req, &HttpResponse::new(200, headers, ""));
+        assert_eq!(session.cookies.len(), 1);
+
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, "sid=; Max-Age=0".parse().unwrap());
+        session.update(&req, &HttpResponse::new(200, headers, ""));
+        assert!(session.cookies.is_empty());
+    }
+
+    #[test]
+    fn test_session_path_rejects_separators() {
+        let dir = Path::new("/project/.wave");
+        assert!(Session::path_for(dir, "../secrets").is_err());
+        assert_eq!(
+            Session::path_for(dir, "dev").unwrap(),
+            Path::new("/project/.wave/sessions/dev.json")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_session_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("wave_session_mode_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("dev.json");
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        Session::default().save(&path).expect("Test: save");
+        assert_eq!(mode(&path), 0o600);
+        // Files written before sessions were private are restricted too
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        Session::default().save(&path).expect("Test: save again");
+        assert_eq!(mode(&path), 0o600);
+        assert!(Session::load(&path).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,333 @@
+//! Response snapshots for `wave snapshot`
+//!
+//! `wave snapshot <collection> <request>` saves the response body under
+//! `.wave/snapshots/<collection>/<request>.json` (or `.txt` for bodies that
+//! are not JSON). `--check` sends the request again and compares the new body
+//! with the saved one, failing on any difference.
+//!
+//! JSON bodies are compared structurally, so formatting and key order do not
+//! matter. Fields that change on every call, such as timestamps or ids, can be
+//! left out of the comparison with `--ignore` or in the collection:
+//!
+//! ```yaml
+//! - name: get-user
+//!   method: GET
+//!   url: ${base_url}/users/1
+//!   snapshot:
+//!     ignore: [updated_at, $.meta.request_id, ..etag]
+//! ```
+//!
+//! Ignored fields are dotted paths or JSONPath queries (see [`crate::query`]);
+//! their values are not compared, but they must still be present.
+
+use crate::error::WaveError;
+use crate::query::Query;
+use anstyle::{AnsiColor, Style};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory inside the collection directory that holds snapshots
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Differences listed before the rest are summarized
+const MAX_SHOWN: usize = 20;
+
+/// Snapshot settings for a collection request
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnapshotConfig {
+    /// Dotted paths or JSONPath queries whose values are not compared
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+/// One way the new response differs from the snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// JSONPath of the value, or `line N` for text bodies
+    pub path: String,
+    /// Value in the snapshot, `None` if the response added it
+    pub expected: Option<String>,
+    /// Value in the response, `None` if the response dropped it
+    pub actual: Option<String>,
+}
+
+/// Returns the directory holding the snapshots of a collection
+fn collection_dir(dir: &Path, collection: &str) -> PathBuf {
+    dir.join(SNAPSHOT_DIR).join(file_name(collection))
+}
+
+/// Makes a collection or request name safe to use as a file name
+fn file_name(name: &str) -> String {
+    name.replace(['/', '\\'], "_")
+}
+
+/// Returns the path of an existing snapshot, if the request has one
+pub fn find(dir: &Path, collection: &str, request: &str) -> Option<PathBuf> {
+    let base = collection_dir(dir, collection);
+    ["json", "txt"]
+        .iter()
+        .map(|ext| base.join(format!("{}.{ext}", file_name(request))))
+        .find(|path| path.is_file())
+}
+
+/// Saves a response body as the snapshot of a request
+///
+/// JSON bodies are pretty-printed to `<request>.json` so snapshots review
+/// well in diffs; other bodies are written as-is to `<request>.txt`. A
+/// snapshot of the other kind is removed.
+///
+/// # Errors
+/// Returns `WaveError::Io` if the snapshot cannot be written.
+pub fn save(dir: &Path, collection: &str, request: &str, body: &str) -> Result<PathBuf, WaveError> {
+    let base = collection_dir(dir, collection);
+    let (ext, stale, content) = match serde_json::from_str::<Value>(body) {
+        Ok(json) => ("json", "txt", serde_json::to_string_pretty(&json)? + "\n"),
+        Err(_) => ("txt", "json", body.to_string()),
+    };
+    let io_error = |path: &Path, e: std::io::Error| {
+        WaveError::Io(format!("Failed to write '{}': {e}", path.display()))
+    };
+    fs::create_dir_all(&base).map_err(|e| io_error(&base, e))?;
+    let path = base.join(format!("{}.{ext}", file_name(request)));
+    fs::write(&path, content).map_err(|e| io_error(&path, e))?;
+    let stale = base.join(format!("{}.{stale}", file_name(request)));
+    if stale.is_file() {
+        fs::remove_file(&stale).map_err(|e| io_error(&stale, e))?;
+    }
+    Ok(path)
+}
+
+/// Compares a response body with a snapshot
+///
+/// When both are JSON, values are compared structurally after masking the
+/// `ignore` paths in both; otherwise the bodies are compared line by line.
+///
+/// # Examples
+/// ```
+/// use wave::query::Query;
+/// use wave::snapshot::compare;
+///
+/// let ignore = [Query::parse("updated_at").unwrap()];
+/// let saved = r#"{"name": "Ann", "updated_at": 1}"#;
+/// assert!(compare(saved, r#"{"updated_at": 2, "name": "Ann"}"#, &ignore).is_empty());
+///
+/// let diffs = compare(saved, r#"{"name": "Bob", "updated_at": 2}"#, &ignore);
+/// assert_eq!(diffs[0].path, "$.name");
+/// ```
+pub fn compare(snapshot: &str, body: &str, ignore: &[Query]) -> Vec<Difference> {
+    let (Ok(mut expected), Ok(mut actual)) = (
+        serde_json::from_str::<Value>(snapshot),
+        serde_json::from_str::<Value>(body),
+    ) else {
+        return compare_text(snapshot, body);
+    };
+    for query in ignore {
+        for value in [&mut expected, &mut actual] {
+            query.for_each_mut(value, &mut |v| *v = Value::String("<ignored>".to_string()));
+        }
+    }
+    let mut differences = Vec::new();
+    compare_json("$", &expected, &actual, &mut differences);
+    differences
+}
+
+fn compare_json(path: &str, expected: &Value, actual: &Value, out: &mut Vec<Difference>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                let path = member_path(path, key);
+                match actual.get(key) {
+                    Some(other) => compare_json(&path, value, other, out),
+                    None => out.push(Difference {
+                        path,
+                        expected: Some(value.to_string()),
+                        actual: None,
+                    }),
+                }
+            }
+            for (key, value) in actual {
+                if !expected.contains_key(key) {
+                    out.push(Difference {
+                        path: member_path(path, key),
+                        expected: None,
+                        actual: Some(value.to_string()),
+                    });
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            for i in 0..expected.len().max(actual.len()) {
+                let path = format!("{path}[{i}]");
+                match (expected.get(i), actual.get(i)) {
+                    (Some(value), Some(other)) => compare_json(&path, value, other, out),
+                    (value, other) => out.push(Difference {
+                        path,
+                        expected: value.map(Value::to_string),
+                        actual: other.map(Value::to_string),
+                    }),
+                }
+            }
+        }
+        _ if expected != actual => out.push(Difference {
+            path: path.to_string(),
+            expected: Some(expected.to_string()),
+            actual: Some(actual.to_string()),
+        }),
+        _ => {}
+    }
+}
+
+/// Appends an object member to a JSONPath, quoting names that need it
+fn member_path(path: &str, key: &str) -> String {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if plain {
+        format!("{path}.{key}")
+    } else {
+        format!("{path}['{key}']")
+    }
+}
+
+fn compare_text(snapshot: &str, body: &str) -> Vec<Difference> {
+    let expected: Vec<&str> = snapshot.lines().collect();
+    let actual: Vec<&str> = body.lines().collect();
+    let quote = |line: &&str| Value::String(line.to_string()).to_string();
+    (0..expected.len().max(actual.len()))
+        .filter(|&i| expected.get(i) != actual.get(i))
+        .map(|i| Difference {
+            path: format!("line {}", i + 1),
+            expected: expected.get(i).map(quote),
+            actual: actual.get(i).map(quote),
+        })
+        .collect()
+}
+
+/// Formats differences as one line each: `~` changed, `-` removed, `+` added
+///
+/// Only the first few are listed, followed by a count of the rest.
+pub fn format_differences(differences: &[Difference]) -> String {
+    let red = Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::Red)));
+    let green = Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::Green)));
+    let reset = anstyle::Reset.render();
+    let mut output = String::new();
+    for diff in differences.iter().take(MAX_SHOWN) {
+        let line = match (&diff.expected, &diff.actual) {
+            (Some(expected), Some(actual)) => format!(
+                "  ~ {}: {}{expected}{reset} -> {}{actual}{reset}",
+                diff.path,
+                red.render(),
+                green.render()
+            ),
+            (Some(expected), None) => {
+                format!("  {}- {}: {expected}{reset}", red.render(), diff.path)
+            }
+            (None, Some(actual)) => {
+                format!("  {}+ {}: {actual}{reset}", green.render(), diff.path)
+            }
+            (None, None) => continue,
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+    if differences.len() > MAX_SHOWN {
+        output.push_str(&format!(
+            "  ... and {} more\n",
+            differences.len() - MAX_SHOWN
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::strip_ansi;
+
+    fn queries(paths: &[&str]) -> Vec<Query> {
+        paths.iter().map(|p| Query::parse(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_compare_json_lists_changes() {
+        let diffs = compare(
+            r#"{"user": {"name": "Ann", "old": true}, "x y": [1, 2]}"#,
+            r#"{"user": {"name": "Bob", "new": 1}, "x y": [1]}"#,
+            &[],
+        );
+        let rendered = strip_ansi(&format_differences(&diffs));
+        assert_eq!(
+            rendered,
+            "  ~ $.user.name: \"Ann\" -> \"Bob\"\n  - $.user.old: true\n  + $.user.new: 1\n  - $['x y'][1]: 2\n"
+        );
+    }
+
+    #[test]
+    fn test_compare_ignores_fields() {
+        let saved = r#"{"id": 1, "meta": {"at": "t1"}, "rows": [{"etag": "a", "v": 1}]}"#;
+        let body = r#"{"id": 2, "meta": {"at": "t2"}, "rows": [{"etag": "b", "v": 1}]}"#;
+        assert!(compare(saved, body, &queries(&["id", "$.meta.at", "..etag"])).is_empty());
+        let diffs = compare(saved, body, &queries(&["id"]));
+        assert_eq!(
+            diffs.iter().map(|d| d.path.as_str()).collect::<Vec<_>>(),
+            vec!["$.meta.at", "$.rows[0].etag"]
+        );
+        // Ignored fields must still be present
+        let missing = compare(
+            saved,
+            r#"{"meta": {}, "rows": []}"#,
+            &queries(&["id", "..at", "rows"]),
+        );
+        assert_eq!(missing.len(), 2);
+    }
+
+    #[test]
+    fn test_compare_text_by_line() {
+        let diffs = compare("a\nb\nc", "a\nB", &[]);
+        assert_eq!(
+            diffs,
+            vec![
+                Difference {
+                    path: "line 2".to_string(),
+                    expected: Some("\"b\"".to_string()),
+                    actual: Some("\"B\"".to_string()),
+                },
+                Difference {
+                    path: "line 3".to_string(),
+                    expected: Some("\"c\"".to_string()),
+                    actual: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_differences_truncates() {
+        let diffs: Vec<Difference> = (0..25)
+            .map(|i| Difference {
+                path: format!("line {i}"),
+                expected: None,
+                actual: Some("x".to_string()),
+            })
+            .collect();
+        let rendered = strip_ansi(&format_differences(&diffs));
+        assert_eq!(rendered.lines().count(), MAX_SHOWN + 1);
+        assert!(rendered.ends_with("  ... and 5 more\n"));
+    }
+
+    #[test]
+    fn test_save_and_find() {
+        let dir = std::env::temp_dir().join(format!("wave-snapshot-{}", std::process::id()));
+        let path = save(&dir, "api", "get/user", "{\"a\":1}").unwrap();
+        assert!(path.ends_with("snapshots/api/get_user.json"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\n  \"a\": 1\n}\n");
+        assert_eq!(find(&dir, "api", "get/user"), Some(path.clone()));
+
+        let text = save(&dir, "api", "get/user", "plain").unwrap();
+        assert!(!path.exists());
+        assert_eq!(find(&dir, "api", "get/user"), Some(text));
+        assert_eq!(find(&dir, "api", "other"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,696 @@
+//! Collection linting for `wave validate`
+//!
+//! Every file of a collection, including the files it pulls in with
+//! `includes:`, is checked for YAML and schema errors, keys wave does not
+//! know, invalid methods, duplicate request names and unknown templates.
+//! When the files are sound, the collection is loaded and every request is
+//! checked for variables that neither the collection, the selected
+//! environment, `--var` nor a `capture:` defines, for body templates that
+//! cannot be read, and the variables for values that refer to each other in
+//! a cycle.
+//!
+//! Problems name the file and, when it can be found, the line.
+
+use crate::collection::{self, Collection, KeyLines};
+use crate::error::CollectionError;
+use crate::http::parse_method;
+use serde_yaml::Value;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Something wrong with a collection file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub file: PathBuf,
+    /// 1-based line number, when the problem can be traced to one
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.file.display(), self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+/// A collection file read for checking
+struct File {
+    path: PathBuf,
+    lines: KeyLines,
+    value: Value,
+}
+
+/// Checks the collection `name` in `dir` and returns its problems
+///
+/// `vars` holds the values given with `--var`; with `env`, the variables of
+/// that environment are available too and the environment must exist.
+///
+/// # Errors
+///
+/// Returns `CollectionError::FileNotFound` if there is no collection named
+/// `name`.
+pub fn validate_collection(
+    dir: &Path,
+    name: &str,
+    env: Option<&str>,
+    vars: &HashMap<String, String>,
+) -> Result<Vec<Problem>, CollectionError> {
+    let mut problems = Vec::new();
+    let files = read_files(root_files(dir, name)?, &mut problems);
+
+    let templates: HashSet<&str> = files
+        .iter()
+        .filter_map(|file| file.value.get("templates")?.as_mapping())
+        .flat_map(|templates| templates.keys().filter_map(Value::as_str))
+        .collect();
+    let mut defined: HashMap<String, (PathBuf, Option<usize>)> = HashMap::new();
+    for file in &files {
+        check_file(file, &templates, &mut defined, &mut problems);
+    }
+    if !problems.is_empty() {
+        return Ok(problems);
+    }
+
+    let root = files.first().map(|f| f.path.clone()).unwrap_or_default();
+    match crate::load_named_collection(dir, name) {
+        Ok(coll) => {
+            check_body_templates(&coll, &defined, &root, &mut problems);
+            check_variables(&coll, env, vars, &defined, &root, &mut problems);
+        }
+        Err(e) => problems.push(Problem {
+            file: root,
+            line: None,
+            message: e.to_string(),
+        }),
+    }
+    Ok(problems)
+}
+
+/// Finds the file of the collection `name` in `dir` that defines `request`,
+/// and the line of its `name:` key, for `wave edit`
+///
+/// Without a request, returns the collection's first file. Files pulled in
+/// with `includes:` are searched too.
+///
+/// # Errors
+///
+/// Returns `CollectionError::FileNotFound` if there is no collection named
+/// `name`, and `CollectionError::RequestNotFound` if no file defines
+/// `request`.
+pub fn request_location(
+    dir: &Path,
+    name: &str,
+    request: Option<&str>,
+) -> Result<(PathBuf, Option<usize>), CollectionError> {
+    let Some(request) = request else {
+        return Ok((root_files(dir, name)?.swap_remove(0), None));
+    };
+    let (file, index) = find_request(dir, name, request)?;
+    Ok((file.path, file.lines.request_key(index, "name")))
+}
+
+/// Finds the file of the collection `name` in `dir` that defines `request`,
+/// and the index of the request in its `requests:` list
+pub(crate) fn request_index(
+    dir: &Path,
+    name: &str,
+    request: &str,
+) -> Result<(PathBuf, usize), CollectionError> {
+    find_request(dir, name, request).map(|(file, index)| (file.path, index))
+}
+
+fn find_request(dir: &Path, name: &str, request: &str) -> Result<(File, usize), CollectionError> {
+    // Files that cannot be read or parsed are reported by validation instead
+    for file in read_files(root_files(dir, name)?, &mut Vec::new()) {
+        let requests = file.value.get("requests").and_then(Value::as_sequence);
+        let index = requests
+            .into_iter()
+            .flatten()
+            .position(|r| r.get("name").and_then(Value::as_str) == Some(request));
+        if let Some(index) = index {
+            return Ok((file, index));
+        }
+    }
+    Err(CollectionError::RequestNotFound {
+        collection: name.to_string(),
+        request: request.to_string(),
+    })
+}
+
+/// Returns the requests of the collection `name` in `dir` whose
+/// `depends_on:` names `request`: the file of each, its index in the file's
+/// `requests:` list and its name
+pub(crate) fn dependents(
+    dir: &Path,
+    name: &str,
+    request: &str,
+) -> Result<Vec<(PathBuf, usize, String)>, CollectionError> {
+    let mut found = Vec::new();
+    for file in read_files(root_files(dir, name)?, &mut Vec::new()) {
+        let requests = file.value.get("requests").and_then(Value::as_sequence);
+        for (index, entry) in requests.into_iter().flatten().enumerate() {
+            let depends_on = entry.get("depends_on").and_then(Value::as_sequence);
+            if depends_on
+                .into_iter()
+                .flatten()
+                .any(|dep| dep.as_str() == Some(request))
+            {
+                let entry_name = entry
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                found.push((file.path.clone(), index, entry_name.to_string()));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Returns the files `load_named_collection` starts from
+pub(crate) fn root_files(dir: &Path, name: &str) -> Result<Vec<PathBuf>, CollectionError> {
+    let file = [
+        dir.join(format!("{name}.yaml")),
+        dir.join(format!("{name}.yml")),
+    ]
+    .into_iter()
+    .find(|p| p.is_file());
+    if let Some(file) = file {
+        return Ok(vec![file]);
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(dir.join(name))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect();
+    if files.is_empty() {
+        return Err(CollectionError::FileNotFound(format!(
+            "{name}.yaml or {name}.yml"
+        )));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads `roots` and every file they include, each once, reporting files
+/// that are missing or are not valid collection files
+fn read_files(roots: Vec<PathBuf>, problems: &mut Vec<Problem>) -> Vec<File> {
+    let mut queue = VecDeque::from(roots);
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    while let Some(path) = queue.pop_front() {
+        if !seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone())) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&path) else {
+            problems.push(Problem {
+                file: path,
+                line: None,
+                message: "file not found".to_string(),
+            });
+            continue;
+        };
+        if let Err(e) = collection::check_syntax(&content) {
+            let message = e.to_string();
+            problems.push(Problem {
+                file: path.clone(),
+                line: e.location().map(|l| l.line()),
+                message: message
+                    .split_once(" at line ")
+                    .map_or(message.as_str(), |(m, _)| m)
+                    .to_string(),
+            });
+        }
+        let value: Value = serde_yaml::from_str(&content).unwrap_or(Value::Null);
+        let base = path.parent().unwrap_or(Path::new(""));
+        let includes = value.get("includes").and_then(Value::as_sequence);
+        for include in includes.into_iter().flatten().filter_map(Value::as_str) {
+            queue.push_back(base.join(include));
+        }
+        files.push(File {
+            lines: KeyLines::new(&content),
+            path,
+            value,
+        });
+    }
+    files
+}
+
+/// Checks the keys, methods, names and templates of one file's requests
+///
+/// `defined` maps request names to where they were first seen, across files.
+fn check_file(
+    file: &File,
+    templates: &HashSet<&str>,
+    defined: &mut HashMap<String, (PathBuf, Option<usize>)>,
+    problems: &mut Vec<Problem>,
+) {
+    let mut found = Vec::new();
+    let mut problem = |line: Option<usize>, message: String| {
+        found.push(Problem {
+            file: file.path.clone(),
+            line,
+            message,
+        })
+    };
+    for (line, message) in collection::unknown_keys(&file.value, &file.lines) {
+        problem(line, message);
+    }
+    let requests = file.value.get("requests").and_then(Value::as_sequence);
+    for (i, request) in requests.into_iter().flatten().enumerate() {
+        if !request.is_mapping() {
+            continue;
+        }
+        let key_line = |key: &str| file.lines.request_key(i, key);
+        let name = request.get("name").and_then(Value::as_str).unwrap_or("");
+        if let Some(method) = request.get("method").and_then(Value::as_str) {
+            if parse_method(method).is_err() {
+                problem(
+                    key_line("method"),
+                    format!("invalid method '{method}' in request '{name}'"),
+                );
+            }
+        }
+        if let Some(template) = request.get("extends").and_then(Value::as_str) {
+            if !templates.contains(template) {
+                problem(
+                    key_line("extends"),
+                    format!("request '{name}' extends unknown template '{template}'"),
+                );
+            }
+        }
+        let line = key_line("name");
+        match defined.get(name) {
+            Some((first, first_line)) => {
+                let at = match first_line {
+                    Some(l) => format!("{}:{l}", first.display()),
+                    None => first.display().to_string(),
+                };
+                problem(
+                    line,
+                    format!("duplicate request name '{name}' (first defined at {at})"),
+                );
+            }
+            None => {
+                defined.insert(name.to_string(), (file.path.clone(), line));
+            }
+        }
+    }
+    found.sort_by_key(|p| p.line);
+    problems.extend(found);
+}
+
+/// Reports `template:` bodies whose file cannot be read, unless the path
+/// depends on variables
+fn check_body_templates(
+    coll: &Collection,
+    defined: &HashMap<String, (PathBuf, Option<usize>)>,
+    root: &Path,
+    problems: &mut Vec<Problem>,
+) {
+    for req in &coll.requests {
+        let Some(collection::Body::Template { path, .. }) = &req.body else {
+            continue;
+        };
+        if path.to_string_lossy().contains("${") {
+            continue;
+        }
+        if let Err(e) = fs::read_to_string(path) {
+            let (file, line) = defined
+                .get(&req.name)
+                .cloned()
+                .unwrap_or_else(|| (root.to_path_buf(), None));
+            problems.push(Problem {
+                file,
+                line,
+                message: format!(
+                    "request '{}' cannot read body template '{}': {e}",
+                    req.name,
+                    path.display()
+                ),
+            });
+        }
+    }
+}
+
+/// Reports variables that a request uses but nothing defines
+fn check_variables(
+    coll: &Collection,
+    env: Option<&str>,
+    vars: &HashMap<String, String>,
+    defined: &HashMap<String, (PathBuf, Option<usize>)>,
+    root: &Path,
+    problems: &mut Vec<Problem>,
+) {
+    let (mut templates, mut literals) = match coll.variable_layers(env) {
+        Ok(layers) => layers,
+        Err(e) => {
+            problems.push(Problem {
+                file: root.to_path_buf(),
+                line: None,
+                message: e.to_string(),
+            });
+            return;
+        }
+    };
+    for (name, value) in vars {
+        templates.remove(name);
+        literals.insert(name.clone(), value.clone());
+    }
+    let cycles: BTreeSet<String> = collection::expand_variables(&templates, &literals)
+        .1
+        .into_values()
+        .filter_map(|problem| problem.strip_prefix("Variable cycle: ").map(str::to_string))
+        .collect();
+    problems.extend(cycles.into_iter().map(|cycle| Problem {
+        file: root.to_path_buf(),
+        line: None,
+        message: format!("variables refer to each other in a cycle: {cycle}"),
+    }));
+    let captured: HashSet<&String> = coll
+        .requests
+        .iter()
+        .flat_map(|r| r.capture.iter().flat_map(|c| c.keys()))
+        .collect();
+    // Hooks can set any variable, so requests whose own `pre:` hooks or an
+    // earlier request's `post:` hooks may define it are not checked
+    let mut after_post_hooks = false;
+    for req in &coll.requests {
+        let set_by_hooks = after_post_hooks || !req.pre.is_empty();
+        after_post_hooks |= !req.post.is_empty();
+        if set_by_hooks {
+            continue;
+        }
+        let Some(resolved) = coll.request(&req.name) else {
+            continue;
+        };
+        let (file, line) = defined
+            .get(&req.name)
+            .cloned()
+            .unwrap_or_else(|| (root.to_path_buf(), None));
+        for name in resolved.undefined_variables(&templates, &literals) {
+            if captured.contains(&name) {
+                continue;
+            }
+            let message = match env {
+                Some(env) => format!(
+                    "request '{}' uses undefined variable '{name}' (environment '{env}')",
+                    req.name
+                ),
+                None => format!("request '{}' uses undefined variable '{name}'", req.name),
+            };
+            problems.push(Problem {
+                file: file.clone(),
+                line,
+                message,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wave_validate_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, content) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Test: create dir");
+            fs::write(path, content).expect("Test: write");
+        }
+        dir
+    }
+
+    fn messages(problems: &[Problem]) -> Vec<String> {
+        problems
+            .iter()
+            .map(|p| {
+                let file = p.file.file_name().unwrap().to_string_lossy();
+                match p.line {
+                    Some(line) => format!("{file}:{line}: {}", p.message),
+                    None => format!("{file}: {}", p.message),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_keys_methods_names_and_templates() {
+        let dir = collection_dir(
+            "lint",
+            &[
+                (
+                    "api.yaml",
+                    "includes: [more.yaml]
+varibles:
+  x: \"1\"
+requests:
+  - name: a
+    method: GET
+    url: /a
+    header:
+      Accept: text/plain
+  - name: b
+    method: GET ALL
+    url: /b
+",
+                ),
+                (
+                    "more.yaml",
+                    "requests:
+  - method: GET
+    name: a
+    url: /a2
+  - name: c
+    extends: missing
+    url: /c
+",
+                ),
+            ],
+        );
+        let problems = validate_collection(&dir, "api", None, &HashMap::new()).unwrap();
+        let api = dir.join("api.yaml").display().to_string();
+        assert_eq!(
+            messages(&problems),
+            [
+                "api.yaml:2: unknown key 'varibles'",
+                "api.yaml:8: unknown key 'header' in request 'a'",
+                "api.yaml:11: invalid method 'GET ALL' in request 'b'",
+                &format!("more.yaml:3: duplicate request name 'a' (first defined at {api}:5)"),
+                "more.yaml:6: request 'c' extends unknown template 'missing'",
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_reports_schema_errors_with_lines() {
+        let dir = collection_dir(
+            "schema",
+            &[(
+                "api.yaml",
+                "requests:
+  - name: a
+    method: GET
+    url: /a
+    expect:
+      stauts: 200
+",
+            )],
+        );
+        let problems = validate_collection(&dir, "api", None, &HashMap::new()).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, Some(6));
+        assert!(problems[0].message.contains("unknown field `stauts`"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_reports_undefined_variables() {
+        let dir = collection_dir(
+            "vars",
+            &[(
+                "api.yaml",
+                "variables:
+  base_url: https://api.io
+environments:
+  prod:
+    user_id: \"7\"
+    trace: ${span}
+    span: ${trace}-${depth}
+requests:
+  - name: login
+    method: POST
+    url: ${base_url}/login
+    capture:
+      token: $.token
+  - name: get-user
+    method: GET
+    url: ${base_url}/users/${user_id}?page=${page:-1}&id=${uuid}
+    headers:
+      Authorization: Bearer ${token}
+      X-Trace: ${trace}
+",
+            )],
+        );
+        let check = |env, vars: &[(&str, &str)]| {
+            let vars = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            messages(&validate_collection(&dir, "api", env, &vars).unwrap())
+        };
+        assert_eq!(
+            check(None, &[]),
+            [
+                "api.yaml:14: request 'get-user' uses undefined variable 'trace'",
+                "api.yaml:14: request 'get-user' uses undefined variable 'user_id'",
+            ]
+        );
+        assert!(check(Some("prod"), &[("trace", "1")]).is_empty());
+        // `--var` values are taken literally, so they cannot form a cycle
+        assert!(check(Some("prod"), &[("trace", "${trace}"), ("depth", "${x}")]).is_empty());
+        assert_eq!(
+            check(Some("prod"), &[]),
+            [
+                "api.yaml: variables refer to each other in a cycle: span -> trace -> span",
+                "api.yaml:14: request 'get-user' uses undefined variable 'depth' (environment 'prod')",
+            ]
+        );
+        assert_eq!(
+            check(Some("dev"), &[]),
+            ["api.yaml: Environment 'dev' not found. Available environments: prod"]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_reports_unreadable_body_templates() {
+        let dir = collection_dir(
+            "body_templates",
+            &[
+                (
+                    "api.yaml",
+                    "requests:
+  - name: order
+    method: POST
+    url: https://api.io/orders
+    body:
+      template: order.json.tmpl
+  - name: refund
+    method: POST
+    url: https://api.io/refunds
+    body:
+      template: refund.json.tmpl
+",
+                ),
+                ("order.json.tmpl", "{\"sku\": \"${sku}\"}"),
+            ],
+        );
+        let problems = messages(&validate_collection(&dir, "api", None, &HashMap::new()).unwrap());
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert!(problems[0].starts_with("api.yaml:7: request 'refund' cannot read body template"));
+        assert_eq!(
+            problems[1],
+            "api.yaml:2: request 'order' uses undefined variable 'sku'"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_variables_hooks_may_set_are_not_reported() {
+        let dir = collection_dir(
+            "hooks",
+            &[(
+                "api.yaml",
+                "requests:
+  - name: signed
+    method: POST
+    url: https://api.io/orders
+    pre: ./sign.sh
+    headers:
+      X-Signature: ${signature}
+  - name: before-login
+    method: GET
+    url: https://api.io/${missing}
+  - name: login
+    method: POST
+    url: https://api.io/login
+    post: ./extract-token.sh
+  - name: me
+    method: GET
+    url: https://api.io/me?token=${token}
+",
+            )],
+        );
+        assert_eq!(
+            messages(&validate_collection(&dir, "api", None, &HashMap::new()).unwrap()),
+            ["api.yaml:8: request 'before-login' uses undefined variable 'missing'"]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_request_location_follows_includes() {
+        let dir = collection_dir(
+            "location",
+            &[
+                (
+                    "api.yaml",
+                    "includes: [more.yaml]
+requests:
+  - name: a
+    url: /a
+",
+                ),
+                (
+                    "more.yaml",
+                    "requests:
+  - url: /b
+    method: POST
+    name: b
+",
+                ),
+            ],
+        );
+        let api = dir.join("api.yaml");
+        assert_eq!(
+            request_location(&dir, "api", None).unwrap(),
+            (api.clone(), None)
+        );
+        assert_eq!(
+            request_location(&dir, "api", Some("a")).unwrap(),
+            (api, Some(3))
+        );
+        assert_eq!(
+            request_location(&dir, "api", Some("b")).unwrap(),
+            (dir.join("more.yaml"), Some(4))
+        );
+        assert!(matches!(
+            request_location(&dir, "api", Some("c")),
+            Err(CollectionError::RequestNotFound { .. })
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unknown_collection() {
+        let dir = collection_dir("missing", &[]);
+        assert!(matches!(
+            validate_collection(&dir, "api", None, &HashMap::new()),
+            Err(CollectionError::FileNotFound(_))
+        ));
+    }
+}
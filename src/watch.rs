@@ -0,0 +1,199 @@
+//! Repeating a request with `--watch`
+//!
+//! `--watch 5s` sends the request again five seconds after each response,
+//! redrawing the screen with the latest response and a header saying whether
+//! it changed since the previous one. Intervals take an `ms`, `s`, `m` or `h`
+//! suffix; a bare number is seconds.
+
+use crate::http::{HttpError, HttpResponse};
+use anstyle::{AnsiColor, Style};
+use std::time::Duration;
+
+/// Parses a watch interval such as `500ms`, `5s`, `2m` or `1h`
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use wave::watch::parse_interval;
+///
+/// assert_eq!(parse_interval("5s"), Ok(Duration::from_secs(5)));
+/// assert_eq!(parse_interval("250ms"), Ok(Duration::from_millis(250)));
+/// assert_eq!(parse_interval("10"), Ok(Duration::from_secs(10)));
+/// assert!(parse_interval("0s").is_err());
+/// ```
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let invalid = || format!("invalid interval '{s}', expected e.g. 500ms, 5s, 2m or 1h");
+    let n: u64 = number.parse().map_err(|_| invalid())?;
+    let interval = match unit {
+        "ms" => Duration::from_millis(n),
+        "" | "s" => Duration::from_secs(n),
+        "m" => Duration::from_secs(n.saturating_mul(60)),
+        "h" => Duration::from_secs(n.saturating_mul(3600)),
+        _ => return Err(invalid()),
+    };
+    if interval.is_zero() {
+        return Err(format!("interval '{s}' must be greater than zero"));
+    }
+    Ok(interval)
+}
+
+/// Formats an interval the way it is usually written, e.g. `5s` or `1500ms`
+pub fn format_interval(interval: Duration) -> String {
+    if interval.subsec_millis() != 0 {
+        format!("{}ms", interval.as_millis())
+    } else {
+        format!("{}s", interval.as_secs())
+    }
+}
+
+/// The part of an outcome that decides whether it changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// Response status, `None` if the request failed
+    pub status: Option<u16>,
+    /// Response body, or the error message
+    pub body: String,
+}
+
+impl Observation {
+    /// Records the outcome of one request
+    pub fn new(result: &Result<HttpResponse, HttpError>) -> Self {
+        match result {
+            Ok(resp) => Observation {
+                status: Some(resp.status),
+                body: resp.body.clone(),
+            },
+            Err(e) => Observation {
+                status: None,
+                body: e.to_string(),
+            },
+        }
+    }
+}
+
+/// How a response compares with the previous one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Nothing to compare with yet
+    First,
+    /// Same status and body
+    Unchanged,
+    /// The status code changed (or the request started or stopped failing)
+    Status { from: Option<u16>, to: Option<u16> },
+    /// Same status, different body
+    Body,
+}
+
+impl Change {
+    /// Compares an outcome with the previous one
+    pub fn detect(previous: Option<&Observation>, current: &Observation) -> Change {
+        match previous {
+            None => Change::First,
+            Some(previous) if previous.status != current.status => Change::Status {
+                from: previous.status,
+                to: current.status,
+            },
+            Some(previous) if previous.body != current.body => Change::Body,
+            Some(_) => Change::Unchanged,
+        }
+    }
+}
+
+/// Formats the line shown above each response
+///
+/// Changes are highlighted so they stand out when glancing at the terminal.
+pub fn format_header(request: &str, interval: Duration, count: u64, change: &Change) -> String {
+    let status = |s: &Option<u16>| s.map_or_else(|| "error".to_string(), |s| s.to_string());
+    let (text, style) = match change {
+        Change::First => (String::new(), Style::new()),
+        Change::Unchanged => ("unchanged".to_string(), Style::new().dimmed()),
+        Change::Status { from, to } => (
+            format!("status changed: {} -> {}", status(from), status(to)),
+            Style::new()
+                .bold()
+                .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Yellow))),
+        ),
+        Change::Body => (
+            "body changed".to_string(),
+            Style::new()
+                .bold()
+                .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Yellow))),
+        ),
+    };
+    let mut header = format!("Every {}: {request}  #{count}", format_interval(interval));
+    if !text.is_empty() {
+        header.push_str(&format!(
+            "  {}{text}{}",
+            style.render(),
+            anstyle::Reset.render()
+        ));
+    }
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::strip_ansi;
+
+    fn observed(status: Option<u16>, body: &str) -> Observation {
+        Observation {
+            status,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_interval_units_and_errors() {
+        assert_eq!(parse_interval("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_interval("5x").is_err());
+        assert!(parse_interval("s").is_err());
+        assert!(parse_interval("-1s").is_err());
+        assert!(parse_interval("0ms").is_err());
+    }
+
+    #[test]
+    fn test_format_interval() {
+        assert_eq!(format_interval(Duration::from_secs(5)), "5s");
+        assert_eq!(format_interval(Duration::from_millis(1500)), "1500ms");
+    }
+
+    #[test]
+    fn test_detect_change() {
+        let ok = observed(Some(200), "a");
+        assert_eq!(Change::detect(None, &ok), Change::First);
+        assert_eq!(Change::detect(Some(&ok), &ok), Change::Unchanged);
+        assert_eq!(
+            Change::detect(Some(&ok), &observed(Some(200), "b")),
+            Change::Body
+        );
+        assert_eq!(
+            Change::detect(Some(&ok), &observed(None, "timeout")),
+            Change::Status {
+                from: Some(200),
+                to: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_format_header() {
+        let interval = Duration::from_secs(5);
+        assert_eq!(
+            format_header("GET https://x.io", interval, 1, &Change::First),
+            "Every 5s: GET https://x.io  #1"
+        );
+        let change = Change::Status {
+            from: Some(200),
+            to: Some(503),
+        };
+        assert_eq!(
+            strip_ansi(&format_header("GET https://x.io", interval, 4, &change)),
+            "Every 5s: GET https://x.io  #4  status changed: 200 -> 503"
+        );
+    }
+}